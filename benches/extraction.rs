@@ -0,0 +1,37 @@
+//! Converting the same video with its frames extracted to PNG files, and piped as raw RGB, as PNGs
+//! and as YUV4MPEG2.
+//!
+//! `CASCII_BENCH_VIDEO=clip.mp4 cargo bench --bench extraction` prints the time of each mode and
+//! the frames it converted per second. `CASCII_BENCH_COLUMNS` (default 200) and `CASCII_BENCH_FPS`
+//! (default 30) set the conversion; without a video there is nothing to measure.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use cascii::{AsciiConverter, ConversionOptions, PipeFormat, VideoOptions};
+
+/// Time of converting `input` into a fresh directory, and the frames converted
+fn time(converter: &AsciiConverter, input: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions) -> (Duration, usize) {
+    let out = tempfile::tempdir().expect("creating an output directory");
+    let start = Instant::now();
+    let result = converter.convert_video(input, out.path(), video_opts, conv_opts, false).expect("converting the video");
+    (start.elapsed(), result.frame_count)
+}
+
+fn main() {
+    let Some(input) = std::env::var_os("CASCII_BENCH_VIDEO") else {
+        println!("set CASCII_BENCH_VIDEO to a video file to compare frame extraction modes");
+        return;
+    };
+    let setting = |name: &str, default: u32| std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default);
+    let (columns, fps) = (setting("CASCII_BENCH_COLUMNS", 200), setting("CASCII_BENCH_FPS", 30));
+    let converter = AsciiConverter::new();
+    let conv_opts = ConversionOptions::default().with_columns(columns);
+    let files = VideoOptions {fps, columns, ..VideoOptions::default()};
+    println!("{} at {} columns and {} fps", Path::new(&input).display(), columns, fps);
+    println!("{:<12} {:>12} {:>8} {:>10}", "extraction", "time", "frames", "frames/s");
+    for (name, video_opts) in [("png files", files.clone()), ("raw pipe", VideoOptions {pipe_frames: true, pipe_format: PipeFormat::Raw, ..files.clone()}), ("png pipe", VideoOptions {pipe_frames: true, pipe_format: PipeFormat::Png, ..files.clone()}), ("y4m pipe", VideoOptions {pipe_frames: true, pipe_format: PipeFormat::Y4m, ..files.clone()})] {
+        let (elapsed, frames) = time(&converter, Path::new(&input), &video_opts, &conv_opts);
+        println!("{:<12} {:>12?} {:>8} {:>10.1}", name, elapsed, frames, frames as f64 / elapsed.as_secs_f64());
+    }
+}
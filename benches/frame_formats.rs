@@ -0,0 +1,64 @@
+//! Encoding and reading the same frame as `.cframe` and as a `.cfb` FlatBuffer.
+//!
+//! `cargo bench --features flatbuffers --bench frame_formats` prints the size of each format and the
+//! mean time to encode a frame, and to read every cell's character and color back from the bytes
+//! the way a player does: the `.cframe` body in place, and the FlatBuffer through `FrameView`,
+//! including its verification.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use cascii::fbs::FrameView;
+use cascii::AsciiFrame;
+
+const ROUNDS: u32 = 2000;
+
+/// A 200x60 frame with colors and a background layer
+fn sample_frame() -> AsciiFrame {
+    let (width, height) = (200u32, 60u32);
+    let ramp: Vec<char> = " .:-=+*#%@".chars().collect();
+    let mut text = String::new();
+    for row in 0..height {
+        text.extend((0..width).map(|column| ramp[((column * 7 + row * 3) % 10) as usize]));
+        text.push('\n');
+    }
+    let rgb = (0..width * height * 3).map(|index| (index * 31 % 256) as u8).collect();
+    let bg_rgb = (0..width * height * 3).map(|index| (index * 17 % 256) as u8).collect();
+    AsciiFrame {text, width, height, rgb, bg_rgb}
+}
+
+/// Mean time of `run` over [`ROUNDS`] calls
+fn time(run: impl Fn() -> u64) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        black_box(run());
+    }
+    start.elapsed() / ROUNDS
+}
+
+/// Sum every cell's character and color from a `.cframe` body
+fn read_cframe(bytes: &[u8]) -> u64 {
+    let width = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    bytes[8..8 + width * height * 4].chunks_exact(4).map(|cell| cell[0] as u64 + cell[1] as u64 + cell[2] as u64 + cell[3] as u64).sum()
+}
+
+/// Sum every cell's character and color through a verified [`FrameView`]
+fn read_flatbuffer(bytes: &[u8]) -> u64 {
+    let view = FrameView::from_bytes(bytes).unwrap();
+    let chars: u64 = view.chars().iter().map(u64::from).sum();
+    chars + view.fg().iter().map(|&byte| byte as u64).sum::<u64>()
+}
+
+fn main() {
+    let frame = sample_frame();
+    let (cframe, flatbuffer) = (frame.cframe_bytes(), frame.to_flatbuffer());
+    println!("{}x{} frame with background, {} rounds", frame.width, frame.height, ROUNDS);
+    println!("{:<12} {:>10} {:>12} {:>12}", "format", "bytes", "encode", "read cells");
+    let cframe_encode = time(|| frame.cframe_bytes().len() as u64);
+    let cframe_read = time(|| read_cframe(black_box(&cframe)));
+    println!("{:<12} {:>10} {:>12?} {:>12?}", ".cframe", cframe.len(), cframe_encode, cframe_read);
+    let flatbuffer_encode = time(|| frame.to_flatbuffer().len() as u64);
+    let flatbuffer_read = time(|| read_flatbuffer(black_box(&flatbuffer)));
+    println!("{:<12} {:>10} {:>12?} {:>12?}", ".cfb", flatbuffer.len(), flatbuffer_encode, flatbuffer_read);
+}
@@ -16,6 +16,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         columns: 200,
         extract_audio: false,
         preprocess_filter: None,
+        ..VideoOptions::default()
     };
 
     // Configure conversion options
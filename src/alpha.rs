@@ -0,0 +1,128 @@
+//! Transparent sources: what the see-through parts of a PNG, GIF or WebP turn into.
+//!
+//! Without an [`AlphaMode`] the alpha channel is dropped and the color stored under it converted
+//! as if it were opaque, so the transparent background of most logos and sprites, stored as
+//! transparent black, comes out as a black box of the darkest characters.
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, Rgb, RgbImage};
+
+/// How transparent pixels are converted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Drop the alpha channel and convert the stored colors as they are
+    #[default]
+    Ignore,
+    /// Cells that are mostly transparent become uncolored spaces, whatever the ramp
+    Space,
+    /// Blend over a solid background color, as a viewer showing the image on that color would
+    Composite([u8; 3]),
+    /// Pick characters by opacity instead of brightness, for silhouettes; the cells come out gray
+    Luminance,
+}
+
+impl AlphaMode {
+    /// Cells with less average opacity than this are transparent in [`AlphaMode::Space`]
+    const OPAQUE: u8 = 128;
+
+    /// `img` with its alpha resolved, or `None` when there is nothing to resolve (an opaque image, or
+    /// [`AlphaMode::Ignore`]). [`AlphaMode::Space`] flattens over black, so colors sampled around
+    /// the blanked cells don't pick up hidden ones.
+    pub(crate) fn flatten(self, img: &DynamicImage) -> Option<DynamicImage> {
+        if self == Self::Ignore || !img.color().has_alpha() {
+            return None;
+        }
+        let rgba = img.to_rgba8();
+        let flat = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let [r, g, b, a] = rgba.get_pixel(x, y).0;
+            Rgb(match self {
+                Self::Luminance => [a; 3],
+                Self::Composite(background) => {
+                    let blend = |fg: u8, bg: u8| ((fg as u32 * a as u32 + bg as u32 * (255 - a as u32) + 127) / 255) as u8;
+                    [blend(r, background[0]), blend(g, background[1]), blend(b, background[2])]
+                }
+                _ => [r, g, b].map(|channel| ((channel as u32 * a as u32 + 127) / 255) as u8),
+            })
+        });
+        Some(DynamicImage::ImageRgb8(flat))
+    }
+
+    /// In [`AlphaMode::Space`], which cells of a `width`×`height` grid sampled from `img` are
+    /// transparent, row-major; `None` in the other modes or when `img` is opaque.
+    pub(crate) fn transparent_cells(self, img: &DynamicImage, width: u32, height: u32) -> Option<Vec<bool>> {
+        if self != Self::Space || !img.color().has_alpha() || width == 0 || height == 0 {
+            return None;
+        }
+        let alpha = GrayImage::from_fn(img.width(), img.height(), |x, y| image::Luma([image::GenericImageView::get_pixel(img, x, y).0[3]]));
+        let alpha = if alpha.dimensions() == (width, height) {alpha} else {image::imageops::resize(&alpha, width, height, FilterType::Triangle)};
+        Some(alpha.pixels().map(|px| px[0] < Self::OPAQUE).collect())
+    }
+}
+
+/// Parses `ignore`, `space`, `luminance`, or a background color to composite over as `#rrggbb`.
+impl std::str::FromStr for AlphaMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "space" => Ok(Self::Space),
+            "luminance" => Ok(Self::Luminance),
+            _ => {
+                let hex = s.strip_prefix('#').unwrap_or(s);
+                let channel = |index: usize| hex.get(index..index + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok());
+                match (hex.len(), channel(0), channel(2), channel(4)) {
+                    (6, Some(r), Some(g), Some(b)) => Ok(Self::Composite([r, g, b])),
+                    _ => Err("expected ignore, space, luminance or a #rrggbb background color".to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Blank the `transparent` cells of a converted grid: spaces with black colors. `transparent`
+/// has one entry per sample, and each sample spans `repeat` cells of the `text` rows.
+pub(crate) fn blank_cells(text: &mut String, rgb: &mut [u8], bg_rgb: &mut [u8], transparent: &[bool], repeat: usize) {
+    let mut blanked = String::with_capacity(text.len());
+    let mut cell = 0;
+    for line in text.lines() {
+        for ch in line.chars() {
+            if transparent.get(cell / repeat).copied().unwrap_or(false) {
+                blanked.push(' ');
+                for colors in [&mut *rgb, &mut *bg_rgb] {
+                    if let Some(color) = colors.get_mut(cell * 3..cell * 3 + 3) {
+                        color.fill(0);
+                    }
+                }
+            } else {
+                blanked.push(ch);
+            }
+            cell += 1;
+        }
+        blanked.push('\n');
+    }
+    *text = blanked;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image_to_frame, ConversionOptions};
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn test_alpha_modes() {
+        // An opaque dark red square in the middle of a transparent (black) 8×4 sprite
+        let sprite = DynamicImage::ImageRgba8(RgbaImage::from_fn(8, 4, |x, _| if (2..6).contains(&x) {Rgba([255, 0, 0, 255])} else {Rgba([0, 0, 0, 0])}));
+        let convert = |alpha_mode: AlphaMode| image_to_frame(&sprite, &ConversionOptions::default().with_columns(8).with_font_ratio(0.5).with_ascii_chars("#.:@".to_string()).with_luminance(0).with_alpha_mode(alpha_mode)).unwrap();
+
+        assert_eq!(convert(AlphaMode::Ignore).text, "########\n########\n");
+        let space = convert(AlphaMode::Space);
+        assert_eq!(space.text, "  ####  \n  ####  \n");
+        assert_eq!((&space.rgb[..3], &space.rgb[6..9]), (&[0, 0, 0][..], &[255, 0, 0][..]));
+        assert_eq!(convert(AlphaMode::Composite([255, 255, 255])).text, "@@####@@\n@@####@@\n");
+        assert_eq!(convert(AlphaMode::Luminance).text, "##@@@@##\n##@@@@##\n");
+        assert_eq!("#ff8000".parse(), Ok(AlphaMode::Composite([255, 128, 0])));
+        assert!("#ff80".parse::<AlphaMode>().is_err());
+    }
+}
@@ -0,0 +1,290 @@
+//! Classic ANSI-art scene formats: `.ans` with a SAUCE record, and XBin (`.xb`).
+//!
+//! Art boards and viewers such as PabloDraw, Moebius or 16colo.rs expect files made for a DOS text
+//! screen: characters from code page 437 and the 16 colors of the VGA palette. [`encode_ansi_art`]
+//! turns an [`AsciiFrame`] into one. Every color is quantized to the nearest VGA color; backgrounds
+//! get all 16 with iCE colors, and the 8 dark ones otherwise, as the bright half blinks on real
+//! hardware. Characters are mapped to CP437 bytes ([`crate::codepage`]), with [`Cp437Mapping`]
+//! deciding what happens to the ones it lacks.
+//!
+//! Both formats end with a SAUCE record (title, author, group, date and the canvas size) that
+//! viewers and boards read, so the size and the iCE flag come through.
+//!
+//! ```
+//! use cascii::ansi_art::{encode_ansi_art, AnsiArtOptions, LegacyFormat};
+//! use cascii::AsciiFrame;
+//!
+//! let frame = AsciiFrame {text: "░█\n".into(), width: 2, height: 1, rgb: vec![255, 0, 0, 250, 250, 250], bg_rgb: Vec::new()};
+//! let ans = encode_ansi_art(&frame, &AnsiArtOptions::default().with_title("Tiny")).unwrap();
+//! assert!(ans.starts_with(b"\x1b[0m\x1b[0;31;40m\xb0\x1b[0;1;37;40m\xdb"));
+//! let xbin = encode_ansi_art(&frame, &AnsiArtOptions::default().with_format(LegacyFormat::XBin)).unwrap();
+//! assert_eq!(&xbin[..5], b"XBIN\x1a");
+//! ```
+
+use crate::codepage::Codepage;
+use crate::error::{Error, Result};
+use crate::frame::AsciiFrame;
+
+/// The 16 colors of the VGA text mode palette, in attribute order
+pub const VGA_PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0], [0, 0, 170], [0, 170, 0], [0, 170, 170], [170, 0, 0], [170, 0, 170], [170, 85, 0], [170, 170, 170],
+    [85, 85, 85], [85, 85, 255], [85, 255, 85], [85, 255, 255], [255, 85, 85], [255, 85, 255], [255, 255, 85], [255, 255, 255],
+];
+
+/// The ANSI color number (30 + n, 40 + n) of each of the 8 dark VGA colors
+const ANSI_ORDER: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+/// Which classic format to write.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LegacyFormat {
+    /// ANSI escapes around CP437 text, the format of most art packs
+    #[default]
+    Ans,
+    /// XBin: a binary grid of character and attribute bytes, which keeps widths past 80 columns exact
+    XBin,
+}
+
+impl LegacyFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ans => "ans",
+            Self::XBin => "xb",
+        }
+    }
+}
+
+/// What becomes of characters code page 437 has no glyph for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cp437Mapping {
+    /// Substitute a lookalike ([`Codepage::restrict`]): braille cells become shades by how many dots
+    /// they raise, quadrant blocks become half blocks, and anything else becomes `?`
+    #[default]
+    Nearest,
+    /// Fail on the first character without a CP437 glyph
+    Strict,
+}
+
+/// How [`encode_ansi_art`] writes a frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnsiArtOptions {
+    pub format: LegacyFormat,
+    pub mapping: Cp437Mapping,
+    /// Allow all 16 background colors (iCE colors) instead of the 8 dark ones
+    pub ice_colors: bool,
+    /// SAUCE title, at most 35 characters
+    pub title: String,
+    /// SAUCE author, at most 20 characters
+    pub author: String,
+    /// SAUCE group, at most 20 characters
+    pub group: String,
+    /// SAUCE date as `CCYYMMDD`; today when `None`
+    pub date: Option<String>,
+}
+
+impl AnsiArtOptions {
+    pub fn with_format(mut self, format: LegacyFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_mapping(mut self, mapping: Cp437Mapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    pub fn with_ice_colors(mut self, ice_colors: bool) -> Self {
+        self.ice_colors = ice_colors;
+        self
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = author.into();
+        self
+    }
+
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = group.into();
+        self
+    }
+
+    pub fn with_date(mut self, date: impl Into<String>) -> Self {
+        self.date = Some(date.into());
+        self
+    }
+}
+
+/// The index in [`VGA_PALETTE`] of the color nearest to `rgb`, among the first `colors` entries
+pub fn nearest_vga_color(rgb: [u8; 3], colors: usize) -> u8 {
+    let distance = |candidate: &[u8; 3]| candidate.iter().zip(rgb).map(|(&a, b)| (a as i32 - b as i32).pow(2)).sum::<i32>();
+    (0..colors.min(16)).min_by_key(|&index| distance(&VGA_PALETTE[index])).unwrap_or(0) as u8
+}
+
+/// The CP437 byte of `ch` under `mapping`
+fn map_char(ch: char, mapping: Cp437Mapping) -> Result<u8> {
+    match mapping {
+        Cp437Mapping::Nearest => Ok(Codepage::Cp437.encode_lossy(ch.encode_utf8(&mut [0; 4]))[0]),
+        Cp437Mapping::Strict => Ok(Codepage::Cp437.encode(ch.encode_utf8(&mut [0; 4]))?[0]),
+    }
+}
+
+/// The CP437 bytes and VGA attributes (background in the high nibble) of every cell of `frame`
+fn cells(frame: &AsciiFrame, options: &AnsiArtOptions) -> Result<Vec<(u8, u8)>> {
+    let background_colors = if options.ice_colors {16} else {8};
+    frame.cells().map(|cell| {
+        let fg = cell.fg.map_or(7, |rgb| nearest_vga_color(rgb, 16));
+        let bg = cell.bg.map_or(0, |rgb| nearest_vga_color(rgb, background_colors));
+        Ok((map_char(cell.ch, options.mapping)?, bg << 4 | fg))
+    }).collect()
+}
+
+/// Encode `frame` as a `.ans` or `.xb` file, SAUCE record included.
+pub fn encode_ansi_art(frame: &AsciiFrame, options: &AnsiArtOptions) -> Result<Vec<u8>> {
+    if frame.width == 0 || frame.height == 0 || frame.width > u16::MAX as u32 || frame.height > u16::MAX as u32 {
+        return Err(Error::InvalidOptions(format!("a {}x{} frame doesn't fit an ANSI-art canvas", frame.width, frame.height)));
+    }
+    let cells = cells(frame, options)?;
+    let mut out = match options.format {
+        LegacyFormat::Ans => encode_ans(&cells, frame.width as usize),
+        LegacyFormat::XBin => encode_xbin(&cells, frame.width as u16, frame.height as u16, options.ice_colors),
+    };
+    let sauce = sauce_record(options, out.len() as u32, frame.width as u16, frame.height as u16);
+    out.push(0x1A);
+    out.extend_from_slice(&sauce);
+    Ok(out)
+}
+
+/// ANSI escapes around the cell bytes, changing colors only between cells that differ
+fn encode_ans(cells: &[(u8, u8)], width: usize) -> Vec<u8> {
+    let mut out = b"\x1b[0m".to_vec();
+    for row in cells.chunks(width) {
+        let mut current = None;
+        for &(byte, attribute) in row {
+            if current != Some(attribute) {
+                let (fg, bg) = (attribute & 0x0F, attribute >> 4);
+                let bright = if fg >= 8 {"1;"} else {""};
+                // Blink stands for a bright background, which viewers show as such in iCE mode
+                let blink = if bg >= 8 {"5;"} else {""};
+                out.extend(format!("\x1b[0;{}{}{};{}m", bright, blink, 30 + ANSI_ORDER[fg as usize & 7], 40 + ANSI_ORDER[bg as usize & 7]).bytes());
+                current = Some(attribute);
+            }
+            out.push(byte);
+        }
+        out.extend_from_slice(b"\x1b[0m");
+        // An 80 column row already wraps the cursor on a DOS screen
+        if width != 80 {
+            out.extend_from_slice(b"\r\n");
+        }
+    }
+    out
+}
+
+/// An XBin header without palette or font, then the character and attribute of every cell
+fn encode_xbin(cells: &[(u8, u8)], width: u16, height: u16, ice_colors: bool) -> Vec<u8> {
+    let mut out = b"XBIN\x1a".to_vec();
+    out.extend_from_slice(&width.to_le_bytes());
+    out.extend_from_slice(&height.to_le_bytes());
+    // 16 pixel high characters; flag bit 3 turns blinking into bright backgrounds
+    out.extend_from_slice(&[16, if ice_colors {0x08} else {0}]);
+    for &(byte, attribute) in cells {
+        out.extend_from_slice(&[byte, attribute]);
+    }
+    out
+}
+
+/// The 128 byte SAUCE 00 record describing a `file_size` byte file of `width`×`height` cells
+fn sauce_record(options: &AnsiArtOptions, file_size: u32, width: u16, height: u16) -> [u8; 128] {
+    let mut record = [0u8; 128];
+    record[..7].copy_from_slice(b"SAUCE00");
+    let mut text = |offset: usize, length: usize, value: &str| {
+        let bytes = value.chars().map(|ch| map_char(ch, Cp437Mapping::Nearest).unwrap_or(b'?')).chain(std::iter::repeat(b' ')).take(length);
+        for (slot, byte) in record[offset..offset + length].iter_mut().zip(bytes) {
+            *slot = byte;
+        }
+    };
+    text(7, 35, &options.title);
+    text(42, 20, &options.author);
+    text(62, 20, &options.group);
+    text(82, 8, options.date.as_deref().unwrap_or(&today()));
+    record[90..94].copy_from_slice(&file_size.to_le_bytes());
+    match options.format {
+        LegacyFormat::Ans => {
+            // Character data of file type ANSi, its size in columns and lines
+            record[94..96].copy_from_slice(&[1, 1]);
+            record[96..98].copy_from_slice(&width.to_le_bytes());
+            record[98..100].copy_from_slice(&height.to_le_bytes());
+            // Non-blink mode with iCE colors, and the 8 pixel wide VGA font
+            record[105] = if options.ice_colors {0x01} else {0} | 0x02;
+            record[106..113].copy_from_slice(b"IBM VGA");
+        }
+        // The XBin header holds the size and flags itself
+        LegacyFormat::XBin => record[94] = 6,
+    }
+    record
+}
+
+/// Today's date as `CCYYMMDD`
+fn today() -> String {
+    let days = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() / 86_400) as i64;
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {month_index + 3} else {month_index - 9};
+    let year = year_of_era + era * 400 + if month <= 2 {1} else {0};
+    format!("{:04}{:02}{:02}", year, month, day)
+}
+
+/// Write every frame of a converted directory or `.casc` archive to `output_dir` as
+/// `frame_0001.ans` (or `.xb`) and on, in playback order. Returns the number of files written.
+#[cfg(feature = "cli")]
+pub fn export_frame_dir(input: impl AsRef<std::path::Path>, output_dir: impl AsRef<std::path::Path>, options: &AnsiArtOptions) -> Result<usize> {
+    use crate::error::Context;
+
+    let output_dir = output_dir.as_ref();
+    std::fs::create_dir_all(output_dir).with_context(|| format!("creating {}", output_dir.display()))?;
+    let mut count = 0;
+    for frame in crate::reader::FrameSequence::open(input)?.reader() {
+        count += 1;
+        let path = output_dir.join(format!("frame_{:04}.{}", count, options.format.extension()));
+        std::fs::write(&path, encode_ansi_art(&frame?, options)?).with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ansi_art_quantizes_maps_cp437_and_appends_sauce() {
+        // Red on bright blue, then a braille cell with 4 dots and a quadrant block, in white
+        let frame = AsciiFrame {text: "#⠛▘\n".into(), width: 3, height: 1, rgb: vec![200, 10, 10, 255, 255, 255, 255, 255, 255], bg_rgb: vec![80, 80, 250, 0, 0, 0, 0, 0, 0]};
+        let options = AnsiArtOptions::default().with_ice_colors(true).with_title("Test").with_author("Ada").with_date("20240102");
+        let ans = encode_ansi_art(&frame, &options).unwrap();
+        let (body, sauce) = ans.split_at(ans.len() - 129);
+        assert_eq!(body, b"\x1b[0m\x1b[0;5;31;44m#\x1b[0;1;37;40m\xb1\xdf\x1b[0m\r\n");
+        assert_eq!(&sauce[..8], b"\x1aSAUCE00");
+        let sauce = &sauce[1..];
+        assert_eq!((&sauce[7..11], &sauce[42..46], &sauce[82..90]), (&b"Test"[..], &b"Ada "[..], &b"20240102"[..]));
+        assert_eq!(u32::from_le_bytes(sauce[90..94].try_into().unwrap()) as usize, body.len());
+        assert_eq!((&sauce[94..100], sauce[105]), (&[1, 1, 3, 0, 1, 0][..], 0x03));
+
+        // Without iCE colors the bright background falls back to a dark one
+        let xbin = encode_ansi_art(&frame, &options.clone().with_ice_colors(false).with_format(LegacyFormat::XBin)).unwrap();
+        assert_eq!(&xbin[..11], b"XBIN\x1a\x03\x00\x01\x00\x10\x00");
+        assert_eq!(&xbin[11..17], &[b'#', 0x14, 0xB1, 0x0F, 0xDF, 0x0F]);
+        assert_eq!(xbin[xbin.len() - 128 + 94], 6);
+
+        assert!(encode_ansi_art(&frame, &options.with_mapping(Cp437Mapping::Strict)).is_err());
+        assert_eq!(today().len(), 8);
+    }
+}
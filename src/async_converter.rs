@@ -0,0 +1,153 @@
+//! Conversions as futures, for async applications (the `async` feature).
+//!
+//! [`AsciiConverterAsync`] wraps an [`AsciiConverter`] for use on a tokio runtime. ffprobe runs as a
+//! `tokio::process` child, and the video methods run the blocking pipeline (ffmpeg, the rayon frame
+//! workers) on tokio's blocking threads while the returned future waits for it, so a web handler can
+//! simply `.await` them. Progress goes to an unbounded `mpsc` sender instead of a callback: every
+//! event is delivered, and the conversion never waits on a slow reader. Dropping the future stops
+//! the conversion, unless the wrapped converter has a [`CancelToken`] of its own, which then stays
+//! in charge.
+//!
+//! ```no_run
+//! use cascii::{AsciiConverter, AsciiConverterAsync, ConversionOptions, Progress, VideoOptions};
+//!
+//! # async fn run() -> cascii::Result<()> {
+//! let converter = AsciiConverterAsync::new(AsciiConverter::new());
+//! let (progress, mut events) = tokio::sync::mpsc::unbounded_channel::<Progress>();
+//! tokio::spawn(async move {
+//!     while let Some(event) = events.recv().await {
+//!         println!("{:?} {:.0}%", event.phase, event.percentage);
+//!     }
+//! });
+//! let result = converter.convert_video("input.mp4", "out", VideoOptions::default(), ConversionOptions::default(), false, progress).await?;
+//! println!("{} frames", result.frame_count);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{AsciiConverter, CancelToken, ConversionOptions, ConversionResult, MediaInfo, Progress, ToVideoOptions, VideoOptions};
+
+/// An [`AsciiConverter`] whose video methods return futures.
+#[derive(Clone)]
+pub struct AsciiConverterAsync {
+    converter: AsciiConverter,
+}
+
+/// Cancels a token it created when the conversion's future is dropped before it finishes
+struct CancelOnDrop(Option<CancelToken>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(token) = self.0.take() {
+            token.cancel();
+        }
+    }
+}
+
+impl AsciiConverterAsync {
+    pub fn new(converter: AsciiConverter) -> Self {
+        Self {converter}
+    }
+
+    /// The wrapped blocking converter
+    pub fn converter(&self) -> &AsciiConverter {
+        &self.converter
+    }
+
+    /// [`AsciiConverter::probe_media`], with ffprobe run as a `tokio::process` child
+    pub async fn probe_media(&self, input: impl AsRef<Path>) -> Result<MediaInfo> {
+        let input = input.as_ref();
+        if let Ok((width, height)) = image::image_dimensions(input) {
+            return Ok(MediaInfo::image(width, height));
+        }
+        let output = tokio::process::Command::new(self.converter.ffmpeg_config().ffprobe_cmd()).args(crate::video::PROBE_MEDIA_ARGS).arg(input).kill_on_drop(true).output().await.map_err(|err| Error::spawn(self.converter.ffmpeg_config().ffprobe_cmd(), err))?;
+        if !output.status.success() {
+            return Err(Error::ffmpeg("ffprobe failed to read the video stream", &output.stderr));
+        }
+        crate::video::parse_media_info(input, &String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// [`AsciiConverter::convert_video_with_detailed_progress`], sending its progress to `progress`
+    pub async fn convert_video(&self, input: impl Into<PathBuf>, output_dir: impl Into<PathBuf>, video_opts: VideoOptions, conv_opts: ConversionOptions, keep_images: bool, progress: UnboundedSender<Progress>) -> Result<ConversionResult> {
+        let (input, output_dir) = (input.into(), output_dir.into());
+        self.run_blocking(move |converter| converter.convert_video_with_detailed_progress(&input, &output_dir, &video_opts, &conv_opts, keep_images, forward(progress))).await
+    }
+
+    /// [`AsciiConverter::convert_video_to_video`], sending its progress to `progress`
+    pub async fn convert_video_to_video(&self, input: impl Into<PathBuf>, video_opts: VideoOptions, conv_opts: ConversionOptions, to_video_opts: ToVideoOptions, progress: UnboundedSender<Progress>) -> Result<ConversionResult> {
+        let input = input.into();
+        self.run_blocking(move |converter| converter.convert_video_to_video(&input, &video_opts, &conv_opts, &to_video_opts, forward(progress))).await
+    }
+
+    /// [`AsciiConverter::render_frames_to_video`], sending its progress to `progress`
+    pub async fn render_frames_to_video(&self, input_dir: impl Into<PathBuf>, fps: u32, to_video_opts: ToVideoOptions, progress: UnboundedSender<Progress>) -> Result<ConversionResult> {
+        let input_dir = input_dir.into();
+        self.run_blocking(move |converter| converter.render_frames_to_video(&input_dir, fps, &to_video_opts, forward(progress))).await
+    }
+
+    /// Run `run` on a blocking thread with a converter the future's drop cancels
+    async fn run_blocking<T: Send + 'static>(&self, run: impl FnOnce(&AsciiConverter) -> Result<T> + Send + 'static) -> Result<T> {
+        let mut converter = self.converter.clone();
+        let mut guard = CancelOnDrop(None);
+        if converter.cancel_token.is_none() {
+            let token = CancelToken::new();
+            converter.cancel_token = Some(token.clone());
+            guard.0 = Some(token);
+        }
+        let result = tokio::task::spawn_blocking(move || run(&converter)).await.map_err(|err| Error::Other(format!("the conversion task failed: {}", err)))?;
+        guard.0 = None;
+        result
+    }
+}
+
+/// A progress callback sending to `sender`; a closed receiver only means nobody is watching any more
+fn forward(sender: UnboundedSender<Progress>) -> impl Fn(Progress) + Send + Sync {
+    move |progress| {
+        let _ = sender.send(progress);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::converter::{fake_ffmpeg, write_fake_program};
+    use crate::ProgressPhase;
+    use std::fs;
+
+    #[test]
+    fn async_converter_probes_and_converts_with_progress_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.png");
+        image::RgbImage::from_pixel(8, 4, image::Rgb([255, 255, 255])).save(&first).unwrap();
+        // Stand in for ffprobe and ffmpeg: the first frame as a PNG for the size probe, otherwise
+        // three white 8x4 raw frames
+        let converter = fake_ffmpeg(dir.path(), &format!("#!/bin/sh\ncase \"$*\" in *image2pipe*) cat '{}' ;; *) head -c 288 /dev/zero | tr '\\000' '\\377' ;; esac\n", first.display()));
+        let ffprobe = dir.path().join("ffprobe");
+        write_fake_program(&ffprobe, "#!/bin/sh\nprintf 'width=8\\nheight=4\\navg_frame_rate=3/1\\nduration=1.0\\n'\n");
+        let ffmpeg_config = converter.ffmpeg_config().clone().with_ffprobe(&ffprobe);
+        let converter = AsciiConverterAsync::new(converter.with_ffmpeg_config(ffmpeg_config));
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let info = converter.probe_media("in.mp4").await.unwrap();
+            assert_eq!(info, MediaInfo {width: 8, height: 4, fps: Some(3.0), duration: Some(1.0)});
+            assert_eq!(converter.probe_media(&first).await.unwrap(), MediaInfo::image(8, 4));
+
+            let (progress, mut events) = tokio::sync::mpsc::unbounded_channel();
+            let video_opts = VideoOptions {fps: 3, end: Some("1".into()), columns: 8, pipe_frames: true, ..VideoOptions::default()};
+            let result = converter.convert_video("in.mp4", dir.path().join("out"), video_opts, ConversionOptions::default().with_columns(8), false, progress).await.unwrap();
+            assert_eq!(result.frame_count, 3);
+            let mut phases = Vec::new();
+            while let Some(event) = events.recv().await {
+                phases.push(event.phase);
+            }
+            assert_eq!(phases.first(), Some(&ProgressPhase::ExtractingFrames));
+            assert_eq!(phases.last(), Some(&ProgressPhase::Complete));
+        });
+        assert!(fs::read_to_string(dir.path().join("out/frame_0003.txt")).unwrap().starts_with("$$$$$$$$"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Creator and license information stamped on everything a conversion writes.
+//!
+//! An `attribution` block in the config file ([`AppConfig::attribution`](crate::AppConfig::attribution))
+//! is set once and applied by the converter to every output of a run: the details report of each
+//! frame directory, the `artist`, `copyright` and `comment` metadata of rendered videos, and, unless
+//! `overlay` is turned off, a faint line of text in the bottom-right corner of rendered videos and GIFs.
+//!
+//! ```
+//! let config: cascii::AppConfig = serde_json::from_str(r#"{
+//!     "presets": {"default": {"columns": 120, "fps": 24, "font_ratio": 0.5, "luminance": 20}},
+//!     "default_preset": "default",
+//!     "attribution": {"creator": "Ada", "license": "CC BY 4.0", "url": "https://example.com/ada"}
+//! }"#).unwrap();
+//! assert_eq!(config.attribution.unwrap().overlay_text().as_deref(), Some("© Ada · CC BY 4.0"));
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// Who made a piece and under which license it is released.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attribution {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator: Option<String>,
+    /// License name, e.g. `CC BY-NC 4.0` or `All rights reserved`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// Where to find the creator or the license terms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Draw the creator and license in the corner of rendered videos and GIFs (on by default)
+    #[serde(default = "default_overlay", skip_serializing)]
+    pub overlay: bool,
+}
+
+fn default_overlay() -> bool {
+    true
+}
+
+impl Default for Attribution {
+    fn default() -> Self {
+        Self {creator: None, license: None, url: None, overlay: true}
+    }
+}
+
+impl Attribution {
+    pub fn with_creator(mut self, creator: impl Into<String>) -> Self {
+        self.creator = Some(creator.into());
+        self
+    }
+
+    pub fn with_license(mut self, license: impl Into<String>) -> Self {
+        self.license = Some(license.into());
+        self
+    }
+
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    pub fn with_overlay(mut self, overlay: bool) -> Self {
+        self.overlay = overlay;
+        self
+    }
+
+    fn creator(&self) -> Option<&str> {
+        self.creator.as_deref().map(str::trim).filter(|creator| !creator.is_empty())
+    }
+
+    fn license(&self) -> Option<&str> {
+        self.license.as_deref().map(str::trim).filter(|license| !license.is_empty())
+    }
+
+    /// The corner line, `© creator · license`, or `None` when neither is set
+    pub fn overlay_text(&self) -> Option<String> {
+        match (self.creator(), self.license()) {
+            (Some(creator), Some(license)) => Some(format!("© {} · {}", creator, license)),
+            (Some(creator), None) => Some(format!("© {}", creator)),
+            (None, Some(license)) => Some(license.to_string()),
+            (None, None) => None,
+        }
+    }
+
+    /// Container metadata for rendered videos: `artist`, `copyright` and `comment` (the url)
+    pub fn video_metadata(&self) -> Vec<(&'static str, &str)> {
+        let url = self.url.as_deref().map(str::trim).filter(|url| !url.is_empty());
+        [("artist", self.creator()), ("copyright", self.license()), ("comment", url)].into_iter().filter_map(|(key, value)| value.map(|value| (key, value))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attribution_builds_overlay_and_metadata() {
+        let attribution: Attribution = serde_json::from_str(r#"{"creator": " Ada ", "url": "https://example.com"}"#).unwrap();
+        assert!(attribution.overlay);
+        assert_eq!(attribution.overlay_text().as_deref(), Some("© Ada"));
+        assert_eq!(attribution.video_metadata(), [("artist", "Ada"), ("comment", "https://example.com")]);
+
+        let licensed = Attribution::default().with_license("CC0").with_overlay(false);
+        assert_eq!((licensed.overlay_text().as_deref(), licensed.video_metadata()), (Some("CC0"), vec![("copyright", "CC0")]));
+        assert_eq!(Attribution::default().with_creator("").overlay_text(), None);
+        assert_eq!(serde_json::to_string(&licensed).unwrap(), r#"{"license":"CC0"}"#);
+    }
+}
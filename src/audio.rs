@@ -0,0 +1,144 @@
+//! Keeping muxed audio in step with rendered frames.
+//!
+//! Before a rendered video is encoded with audio, its length (`frames / fps`) is compared with the
+//! audio's (after any [`ToVideoOptions::audio_offset`](crate::ToVideoOptions::audio_offset)).
+//! Longer audio is always cut at the end of the video; what happens when the audio is shorter is
+//! chosen with [`AudioPadding`]. With the default, [`AudioPadding::Auto`], small differences
+//! (typically frame rounding at the cut points) are absorbed by retiming the video so every frame
+//! is kept and both tracks end together, and larger ones are padded with silence.
+
+use serde::{Deserialize, Serialize};
+
+/// Largest relative length difference that is corrected by retiming the video instead of padding
+/// or trimming the audio (2%, e.g. 24 fps played at up to 24.48 fps).
+pub const RETIME_TOLERANCE: f64 = 0.02;
+
+/// What to do when the audio is shorter than the video.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AudioPadding {
+    /// Retime the video for differences up to [`RETIME_TOLERANCE`], pad with silence beyond that (the default)
+    #[default]
+    Auto,
+    /// Pad with silence; never change the frame rate
+    Silence,
+    /// Repeat the audio from the start until the video ends
+    Loop,
+    /// Cut the video where the audio ends
+    TrimVideo,
+}
+
+impl AudioPadding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Silence => "silence",
+            Self::Loop => "loop",
+            Self::TrimVideo => "trim-video",
+        }
+    }
+}
+
+/// What was done to line up audio and video.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncCorrection {
+    /// The lengths already matched to within one frame
+    None,
+    /// The video frame rate was adjusted so the frames span the audio exactly
+    AdjustedFps,
+    /// The audio was padded with silence to the video length
+    PaddedAudio,
+    /// The audio was cut at the end of the video
+    TrimmedAudio,
+    /// The audio was repeated until the end of the video
+    LoopedAudio,
+    /// The video was cut at the end of the audio
+    TrimmedVideo,
+}
+
+/// Audio/video length comparison for one encode.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AudioSync {
+    /// Length of the rendered frames at the requested fps, in seconds
+    pub video_secs: f64,
+    /// Length of the audio track after the offset (a delay adds to it, an advance cuts from it), in seconds
+    pub audio_secs: f64,
+    /// Frame rate the video is encoded at; differs from the requested one after [`SyncCorrection::AdjustedFps`]
+    pub encoded_fps: f64,
+    /// What was done about the difference
+    pub correction: SyncCorrection,
+}
+
+impl AudioSync {
+    /// Decide how to line up `frames` rendered at `fps` with `audio_secs` of audio shifted by
+    /// `offset_secs`, using `padding` when the audio comes up short.
+    pub fn plan(frames: usize, fps: u32, audio_secs: f64, offset_secs: f64, padding: AudioPadding) -> Self {
+        let fps = fps.max(1) as f64;
+        let video_secs = frames as f64 / fps;
+        let audio_secs = (audio_secs + offset_secs).max(0.0);
+        let drift = audio_secs - video_secs;
+        let (encoded_fps, correction) = if drift.abs() <= 1.0 / fps || frames == 0 || audio_secs <= 0.0 {
+            (fps, SyncCorrection::None)
+        } else if padding == AudioPadding::Auto && drift.abs() <= video_secs * RETIME_TOLERANCE {
+            (frames as f64 / audio_secs, SyncCorrection::AdjustedFps)
+        } else if drift > 0.0 {
+            (fps, SyncCorrection::TrimmedAudio)
+        } else {
+            let correction = match padding {
+                AudioPadding::Auto | AudioPadding::Silence => SyncCorrection::PaddedAudio,
+                AudioPadding::Loop => SyncCorrection::LoopedAudio,
+                AudioPadding::TrimVideo => SyncCorrection::TrimmedVideo,
+            };
+            (fps, correction)
+        };
+        Self {video_secs, audio_secs, encoded_fps, correction}
+    }
+
+    /// Audio length minus video length, in seconds.
+    pub fn drift_secs(&self) -> f64 {
+        self.audio_secs - self.video_secs
+    }
+
+    /// Human-readable summary of the mismatch and its correction, or `None` when nothing was changed.
+    pub fn describe(&self) -> Option<String> {
+        let drift = format!("audio ({:.3}s) is {:.3}s {} than the video ({:.3}s)", self.audio_secs, self.drift_secs().abs(), if self.drift_secs() < 0.0 {"shorter"} else {"longer"}, self.video_secs);
+        match self.correction {
+            SyncCorrection::None => None,
+            SyncCorrection::AdjustedFps => Some(format!("{}; encoded at {:.3} fps so both end together", drift, self.encoded_fps)),
+            SyncCorrection::PaddedAudio => Some(format!("{}; padded the audio with silence", drift)),
+            SyncCorrection::TrimmedAudio => Some(format!("{}; cut the audio at the end of the video", drift)),
+            SyncCorrection::LoopedAudio => Some(format!("{}; looped the audio until the end of the video", drift)),
+            SyncCorrection::TrimmedVideo => Some(format!("{}; cut the video at the end of the audio", drift)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plans_by_drift_size() {
+        // 240 frames at 24 fps = 10s
+        assert_eq!(AudioSync::plan(240, 24, 10.03, 0.0, AudioPadding::Auto).correction, SyncCorrection::None);
+        let retimed = AudioSync::plan(240, 24, 10.1, 0.0, AudioPadding::Auto);
+        assert_eq!(retimed.correction, SyncCorrection::AdjustedFps);
+        assert!((240.0 / retimed.encoded_fps - 10.1).abs() < 1e-9);
+        let padded = AudioSync::plan(240, 24, 6.0, 0.0, AudioPadding::Auto);
+        assert_eq!((padded.correction, padded.encoded_fps), (SyncCorrection::PaddedAudio, 24.0));
+        assert!(padded.describe().unwrap().contains("4.000s shorter"));
+        assert_eq!(AudioSync::plan(240, 24, 14.0, 0.0, AudioPadding::Auto).correction, SyncCorrection::TrimmedAudio);
+    }
+
+    #[test]
+    fn padding_policy_and_offset() {
+        assert_eq!(AudioSync::plan(240, 24, 9.9, 0.0, AudioPadding::Silence).correction, SyncCorrection::PaddedAudio);
+        assert_eq!(AudioSync::plan(240, 24, 6.0, 0.0, AudioPadding::Loop).correction, SyncCorrection::LoopedAudio);
+        assert_eq!(AudioSync::plan(240, 24, 6.0, 0.0, AudioPadding::TrimVideo).correction, SyncCorrection::TrimmedVideo);
+        assert_eq!(AudioSync::plan(240, 24, 14.0, 0.0, AudioPadding::TrimVideo).correction, SyncCorrection::TrimmedAudio);
+        // A 2s delay makes 8s of audio line up with 10s of video; a 2s advance leaves 8s of 10s.
+        assert_eq!(AudioSync::plan(240, 24, 8.0, 2.0, AudioPadding::Auto).correction, SyncCorrection::None);
+        assert_eq!(AudioSync::plan(240, 24, 10.0, -2.0, AudioPadding::Silence).audio_secs, 8.0);
+    }
+}
@@ -0,0 +1,123 @@
+//! Playing a clip's `audio.mp3` alongside its frames (feature `audio`).
+//!
+//! An [`AudioPlayer`] follows the [`AudioCue`]s of a [`PlaybackClock`]: it seeks whenever the
+//! playhead jumps, resamples to the playback speed, and pauses while playback is paused or muted.
+//! In return its [`position`](AudioPlayer::position) is the master clock the video locks to with
+//! [`PlaybackClock::lock_to_audio`], which drops frames when drawing falls behind and repeats them
+//! when it runs ahead, so a slow terminal never lets the picture drift from the sound.
+//!
+//! ```no_run
+//! use cascii::audio_playback::{self, AudioPlayer};
+//! use cascii::playback::PlaybackClock;
+//! use cascii::FrameSequence;
+//! use std::time::{Duration, Instant};
+//!
+//! # fn main() -> cascii::Result<()> {
+//! let frames: Vec<_> = FrameSequence::open("out/clip")?.reader().collect::<cascii::Result<_>>()?;
+//! let mut clock = PlaybackClock::new(frames.len(), 24.0);
+//! let mut audio = AudioPlayer::open("out/clip/audio.mp3")?;
+//! let mut last = Instant::now();
+//! while !clock.is_finished() {
+//!     clock.advance(last.elapsed());
+//!     last = Instant::now();
+//!     audio_playback::sync(&mut clock, &mut audio);
+//!     print!("\x1b[H{}", frames[clock.frame()].text);
+//!     std::thread::sleep(Duration::from_millis(10));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Context, Error, Result};
+use rodio::{Decoder, OutputStream, Sink};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::playback::{AudioCue, AudioOutput, PlaybackClock};
+
+/// An audio track playing on the default output device.
+pub struct AudioPlayer {
+    // Dropping the stream closes the output device
+    _stream: OutputStream,
+    sink: Sink,
+    /// [`AudioCue::jumps`] of the last cue followed
+    jumps: Option<u64>,
+    rate: Option<f64>,
+    /// Clip time and sink position when the position or rate last changed. The sink reports the
+    /// wall time played since then, which is clip time divided by the rate.
+    anchor: (Duration, Duration),
+}
+
+impl AudioPlayer {
+    /// Decode `path` (typically the `audio.mp3` a conversion extracted) and open the default output
+    /// device, paused at the start until the first [`follow`](Self::follow).
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        let source = Decoder::new(BufReader::new(file)).map_err(|err| Error::Other(format!("decoding {}: {}", path.display(), err)))?;
+        let (stream, handle) = OutputStream::try_default().map_err(|err| Error::Other(format!("opening the default audio output: {}", err)))?;
+        let sink = Sink::try_new(&handle).map_err(|err| Error::Other(format!("starting audio playback: {}", err)))?;
+        sink.pause();
+        sink.append(source);
+        Ok(Self {_stream: stream, sink, jumps: None, rate: None, anchor: (Duration::ZERO, Duration::ZERO)})
+    }
+
+    /// Seek to the cue's position when the playhead jumped, play at its rate, or pause without one.
+    pub fn follow(&mut self, cue: AudioCue) {
+        if self.jumps != Some(cue.jumps) {
+            self.jumps = Some(cue.jumps);
+            // Seeking past the end of the track empties the sink, which plays silence
+            let _ = self.sink.try_seek(cue.position);
+            self.anchor = (cue.position, self.sink.get_pos());
+        } else if cue.rate != self.rate {
+            self.anchor = (self.clip_time(), self.sink.get_pos());
+        }
+        match cue.rate {
+            Some(rate) => {
+                self.sink.set_speed(rate as f32);
+                self.sink.play();
+            }
+            None => self.sink.pause(),
+        }
+        self.rate = cue.rate;
+    }
+
+    /// Clip time the audio has reached, or `None` while it is paused or muted, or after the track ended.
+    pub fn position(&self) -> Option<Duration> {
+        (self.rate.is_some() && !self.sink.empty()).then(|| self.clip_time())
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.sink.set_volume(volume);
+    }
+
+    fn clip_time(&self) -> Duration {
+        let (clip, sink) = self.anchor;
+        clip + self.sink.get_pos().saturating_sub(sink).mul_f64(self.rate.unwrap_or(0.0))
+    }
+}
+
+impl AudioOutput for AudioPlayer {
+    fn follow(&mut self, cue: AudioCue) {
+        AudioPlayer::follow(self, cue);
+    }
+
+    fn position(&mut self) -> Option<Duration> {
+        AudioPlayer::position(self)
+    }
+}
+
+/// [`AudioPlayer::open`] on the `audio.mp3` of a converted frame directory, or `None` when the
+/// conversion extracted no audio.
+pub fn open_clip_audio(dir: impl AsRef<Path>) -> Result<Option<AudioPlayer>> {
+    let path = dir.as_ref().join("audio.mp3");
+    if path.is_file() {AudioPlayer::open(path).map(Some)} else {Ok(None)}
+}
+
+/// Keep `clock` locked to `player` for one drawn frame: follow the clock's cue, then correct the
+/// clock by the audio position. Returns the frames dropped (positive) or repeated (negative).
+pub fn sync(clock: &mut PlaybackClock, player: &mut AudioPlayer) -> isize {
+    clock.follow_audio(player)
+}
@@ -1,10 +1,12 @@
 use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
-use anyhow::{anyhow, Context, Result};
+use crate::error::{Error, Result};
 use image::{DynamicImage, Rgb};
 use rayon::prelude::*;
-use std::path::Path;
 
+use crate::cell_filter::Luminance;
+use crate::codepage::ramp_char;
 use crate::convert::AsciiFrameData;
+use crate::frame::Sampling;
 use crate::BgFitQuality;
 
 const FONT_DATA: &[u8] = include_bytes!("../resources/DejaVuSansMono.ttf");
@@ -34,7 +36,7 @@ struct ConvertedRow {
 }
 
 pub(crate) fn background_analysis_context(ascii_chars: &[u8], quality: BgFitQuality) -> Result<OptimizedBackgroundAnalysisContext> {
-    let font = FontRef::try_from_slice(FONT_DATA).map_err(|error| anyhow!("failed to load embedded font: {error}"))?;
+    let font = FontRef::try_from_slice(FONT_DATA).map_err(|error| Error::Other(format!("failed to load embedded font: {error}")))?;
     let scale = PxScale::from(quality.analysis_font_size());
     let scaled_font = font.as_scaled(scale);
     let cell_width = scaled_font.h_advance(font.glyph_id('M')).ceil() as u32;
@@ -43,7 +45,7 @@ pub(crate) fn background_analysis_context(ascii_chars: &[u8], quality: BgFitQual
 
     let mut glyphs = Vec::with_capacity(ascii_chars.len());
     for &byte in ascii_chars.iter().filter(|byte| **byte != b' ') {
-        let glyph = font.glyph_id(byte as char).with_scale_and_position(scale, ab_glyph::point(0.0, ascent));
+        let glyph = font.glyph_id(ramp_char(byte)).with_scale_and_position(scale, ab_glyph::point(0.0, ascent));
         let mut alpha = vec![0.0f32; (cell_width * cell_height) as usize];
         if let Some(outlined) = font.outline_glyph(glyph) {
             outlined.draw(|x, y, coverage| {
@@ -77,29 +79,25 @@ pub(crate) fn background_analysis_context(ascii_chars: &[u8], quality: BgFitQual
     Ok(OptimizedBackgroundAnalysisContext {glyphs, cell_width, cell_height})
 }
 
-pub(crate) fn fit_image_to_ascii_with_cell_backgrounds(image_path: &Path, font_ratio: f32, threshold: u8, background_threshold: u8, columns: Option<u32>, ascii_chars: &[u8], quality: BgFitQuality) -> Result<AsciiFrameData> {
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn fit_image_to_ascii_with_cell_backgrounds(image: &DynamicImage, font_ratio: f32, threshold: u8, background_threshold: u8, columns: Option<u32>, sampling: Sampling, ascii_chars: &[u8], quality: BgFitQuality) -> Result<AsciiFrameData> {
     let context = background_analysis_context(ascii_chars, quality)?;
-    fit_image_to_ascii_with_cell_backgrounds_with_context(image_path, font_ratio, threshold, background_threshold, columns, &context)
+    fit_image_to_ascii_with_cell_backgrounds_with_context(image, font_ratio, threshold, background_threshold, columns, sampling, &context)
 }
 
-pub(crate) fn fit_image_to_ascii_with_cell_backgrounds_with_context(image_path: &Path, font_ratio: f32, threshold: u8, background_threshold: u8, columns: Option<u32>, context: &OptimizedBackgroundAnalysisContext) -> Result<AsciiFrameData> {
-    let mut image = image::open(image_path).with_context(|| format!("opening {}", image_path.display()))?.to_rgb8();
+pub(crate) fn fit_image_to_ascii_with_cell_backgrounds_with_context(image: &DynamicImage, font_ratio: f32, threshold: u8, background_threshold: u8, columns: Option<u32>, sampling: Sampling, context: &OptimizedBackgroundAnalysisContext) -> Result<AsciiFrameData> {
+    let mut image = sampling.source(image).to_rgb8();
     let (original_width, original_height) = image.dimensions();
-    let (width_chars, height_chars) = if let Some(columns) = columns {
-        let rows = (original_height as f32 / original_width as f32 * columns as f32 * font_ratio).round() as u32;
-        (columns, rows.max(1))
-    } else {
-        let rows = (original_height as f32 * font_ratio).round() as u32;
-        (original_width, rows.max(1))
-    };
+    let (width_chars, height_chars) = sampling.grid_size(original_width, original_height, font_ratio, columns);
 
     let target_width = width_chars * context.cell_width;
     let target_height = height_chars * context.cell_height;
     if image.dimensions() != (target_width, target_height) {
-        image = DynamicImage::ImageRgb8(image).resize_exact(target_width, target_height, image::imageops::FilterType::Triangle).to_rgb8();
+        image = DynamicImage::ImageRgb8(image).resize_exact(target_width, target_height, sampling.resize_filter(width_chars, original_width)).to_rgb8();
     }
+    sampling.tone.apply_rgb8(&mut image);
 
-    let rows: Vec<ConvertedRow> = (0..height_chars).into_par_iter().map(|row| convert_row(&image, row, width_chars, threshold, background_threshold, context)).collect();
+    let rows: Vec<ConvertedRow> = (0..height_chars).into_par_iter().map(|row| convert_row(&image, row, width_chars, threshold, background_threshold, sampling.luminance, context)).collect();
 
     let cell_count = (width_chars * height_chars) as usize;
     let mut ascii_text = String::with_capacity(cell_count + height_chars as usize);
@@ -107,7 +105,7 @@ pub(crate) fn fit_image_to_ascii_with_cell_backgrounds_with_context(image_path:
     let mut bg_rgb_colors = Vec::with_capacity(cell_count * 3);
     for row in rows {
         for byte in row.ascii {
-            ascii_text.push(byte as char);
+            ascii_text.push(ramp_char(byte));
         }
         ascii_text.push('\n');
         rgb_colors.extend_from_slice(&row.foreground);
@@ -117,7 +115,7 @@ pub(crate) fn fit_image_to_ascii_with_cell_backgrounds_with_context(image_path:
     Ok(AsciiFrameData {ascii_text, width_chars, height_chars, rgb_colors, bg_rgb_colors})
 }
 
-fn convert_row(image: &image::RgbImage, row: u32, width_chars: u32, threshold: u8, background_threshold: u8, context: &OptimizedBackgroundAnalysisContext) -> ConvertedRow {
+fn convert_row(image: &image::RgbImage, row: u32, width_chars: u32, threshold: u8, background_threshold: u8, luma: Luminance, context: &OptimizedBackgroundAnalysisContext) -> ConvertedRow {
     let cell_pixels = (context.cell_width * context.cell_height) as usize;
     let mut ascii = Vec::with_capacity(width_chars as usize);
     let mut foreground = Vec::with_capacity(width_chars as usize * 3);
@@ -135,7 +133,7 @@ fn convert_row(image: &image::RgbImage, row: u32, width_chars: u32, threshold: u
         for y in 0..context.cell_height {
             for x in 0..context.cell_width {
                 let pixel = *image.get_pixel(base_x + x, base_y + y);
-                total_luminance += luminance(pixel) as u64;
+                total_luminance += luma.luminance(pixel[0], pixel[1], pixel[2]) as u64;
                 sum_rgb[0] += pixel[0] as u64;
                 sum_rgb[1] += pixel[1] as u64;
                 sum_rgb[2] += pixel[2] as u64;
@@ -234,10 +232,6 @@ fn constant_patch_error(cell_pixels: usize, color: [u8; 3], sum_pixel: [f64; 3],
     error
 }
 
-fn luminance(pixel: Rgb<u8>) -> u8 {
-    ((2126 * pixel[0] as u32 + 7152 * pixel[1] as u32 + 722 * pixel[2] as u32) / 10000) as u8
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -252,13 +246,11 @@ mod tests {
     fn assert_optimized_matches_legacy(quality: BgFitQuality) {
         let width = 37;
         let height = 29;
-        let image = image::RgbImage::from_fn(width, height, |x, y| Rgb([((x * 17 + y * 3) % 256) as u8, ((x * 5 + y * 23) % 256) as u8, ((x * 11 + y * 7) % 256) as u8]));
-        let input = tempfile::Builder::new().suffix(".png").tempfile().unwrap();
-        image.save_with_format(input.path(), image::ImageFormat::Png).unwrap();
+        let image = DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |x, y| Rgb([((x * 17 + y * 3) % 256) as u8, ((x * 5 + y * 23) % 256) as u8, ((x * 11 + y * 7) % 256) as u8])));
         let ascii_chars = b" .:-=+*#%@";
 
-        let legacy = crate::render::fit_image_to_ascii_with_cell_backgrounds(input.path(), 0.7, 20, 20, Some(24), ascii_chars, quality).unwrap();
-        let optimized = fit_image_to_ascii_with_cell_backgrounds(input.path(), 0.7, 20, 20, Some(24), ascii_chars, quality).unwrap();
+        let legacy = crate::render::fit_image_to_ascii_with_cell_backgrounds(&image, 0.7, 20, 20, Some(24), Sampling::default(), ascii_chars, quality).unwrap();
+        let optimized = fit_image_to_ascii_with_cell_backgrounds(&image, 0.7, 20, 20, Some(24), Sampling::default(), ascii_chars, quality).unwrap();
 
         assert_eq!(optimized.ascii_text, legacy.ascii_text);
         assert_eq!(optimized.rgb_colors, legacy.rgb_colors);
@@ -0,0 +1,140 @@
+//! Undo for the sequence edits that rewrite frames in place.
+//!
+//! Before trimming frames or repeating a loop, cascii copies every file the edit is about to
+//! change into a `.cascii-backup` directory next to them, with a manifest naming those files and
+//! the ones the edit adds. [`undo`] puts the copies back, removes the added files and deletes the
+//! backup. Only the last edit of a directory can be undone: a new backup replaces the previous one.
+
+use crate::error::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the backup directory inside an edited directory.
+pub const BACKUP_DIR_NAME: &str = ".cascii-backup";
+
+const MANIFEST_NAME: &str = "manifest.json";
+const FILES_DIR_NAME: &str = "files";
+
+/// What a backup holds, as written to its `manifest.json`. Paths are relative to the edited directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    /// The edit the backup was taken for, e.g. `trim`
+    pub operation: String,
+    /// When the backup was taken, in seconds since the Unix epoch
+    pub created_at: u64,
+    /// Files as they were before the edit, copied into the backup
+    pub files: Vec<PathBuf>,
+    /// Files the edit added, which undoing it removes
+    pub added: Vec<PathBuf>,
+}
+
+/// A backup in progress, taken with [`Backup::create`].
+#[derive(Debug)]
+pub struct Backup {
+    dir: PathBuf,
+    manifest: BackupManifest,
+}
+
+impl Backup {
+    /// Copy `files` (inside `dir`) into a new backup of `dir` for `operation`, replacing any
+    /// earlier backup. The manifest is written last, so an interrupted backup is never restored.
+    pub fn create(dir: &Path, operation: &str, files: &[PathBuf]) -> Result<Self> {
+        let backup_dir = dir.join(BACKUP_DIR_NAME);
+        if backup_dir.exists() {
+            fs::remove_dir_all(&backup_dir).with_context(|| format!("removing previous backup {}", backup_dir.display()))?;
+        }
+        let mut relative = Vec::with_capacity(files.len());
+        for file in files {
+            let name = file.strip_prefix(dir).unwrap_or(file).to_path_buf();
+            if name.is_absolute() {
+                return Err(Error::Other(format!("{} is outside {}", file.display(), dir.display())));
+            }
+            let copy = backup_dir.join(FILES_DIR_NAME).join(&name);
+            if let Some(parent) = copy.parent() {
+                fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+            }
+            fs::copy(dir.join(&name), &copy).with_context(|| format!("backing up {}", file.display()))?;
+            relative.push(name);
+        }
+        let created_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let backup = Self {dir: dir.to_path_buf(), manifest: BackupManifest {operation: operation.to_string(), created_at, files: relative, added: Vec::new()}};
+        backup.write_manifest()?;
+        Ok(backup)
+    }
+
+    /// Record the files the edit wrote; those that weren't backed up are removed on undo.
+    pub fn record_written(&mut self, written: &[PathBuf]) -> Result<()> {
+        for file in written {
+            let name = file.strip_prefix(&self.dir).unwrap_or(file).to_path_buf();
+            if !self.manifest.files.contains(&name) && !self.manifest.added.contains(&name) {
+                self.manifest.added.push(name);
+            }
+        }
+        self.write_manifest()
+    }
+
+    /// The manifest as written so far
+    pub fn manifest(&self) -> &BackupManifest {
+        &self.manifest
+    }
+
+    fn write_manifest(&self) -> Result<()> {
+        let path = self.dir.join(BACKUP_DIR_NAME).join(MANIFEST_NAME);
+        fs::create_dir_all(self.dir.join(BACKUP_DIR_NAME)).with_context(|| format!("creating backup directory in {}", self.dir.display()))?;
+        fs::write(&path, serde_json::to_string_pretty(&self.manifest)?).with_context(|| format!("writing {}", path.display()))
+    }
+}
+
+/// The manifest of the backup in `dir`, if it has one.
+pub fn read_manifest(dir: &Path) -> Result<Option<BackupManifest>> {
+    let path = dir.join(BACKUP_DIR_NAME).join(MANIFEST_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    Ok(Some(serde_json::from_str(&text).with_context(|| format!("parsing {}", path.display()))?))
+}
+
+/// Undo the last backed-up edit of `dir`: restore the backed-up files, remove the ones the edit
+/// added and delete the backup. Returns the manifest of the undone edit.
+pub fn undo(dir: &Path) -> Result<BackupManifest> {
+    let manifest = read_manifest(dir)?.ok_or_else(|| Error::NotFound(format!("{} has no backup to undo", dir.display())))?;
+    let files_dir = dir.join(BACKUP_DIR_NAME).join(FILES_DIR_NAME);
+    for name in &manifest.added {
+        match fs::remove_file(dir.join(name)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err).with_context(|| format!("removing {}", dir.join(name).display())),
+            _ => {}
+        }
+    }
+    for name in &manifest.files {
+        fs::copy(files_dir.join(name), dir.join(name)).with_context(|| format!("restoring {}", dir.join(name).display()))?;
+    }
+    fs::remove_dir_all(dir.join(BACKUP_DIR_NAME)).with_context(|| format!("removing backup in {}", dir.display()))?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_restores_changed_files_and_removes_added_ones() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (first, second) = (dir.path().join("frame_0001.txt"), dir.path().join("frame_0002.txt"));
+        fs::write(&first, "ab\n")?;
+
+        let mut backup = Backup::create(dir.path(), "repeat loop", std::slice::from_ref(&first))?;
+        fs::write(&first, "xx\n")?;
+        fs::write(&second, "ab\n")?;
+        backup.record_written(&[first.clone(), second.clone()])?;
+        assert_eq!(read_manifest(dir.path())?.as_ref(), Some(backup.manifest()));
+
+        let manifest = undo(dir.path())?;
+        assert_eq!((manifest.operation.as_str(), &manifest.added[..]), ("repeat loop", &[PathBuf::from("frame_0002.txt")][..]));
+        assert_eq!(fs::read_to_string(&first)?, "ab\n");
+        assert!(!second.exists() && !dir.path().join(BACKUP_DIR_NAME).exists());
+        assert!(undo(dir.path()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+//! Cooperative cancellation of running conversions.
+
+use crate::error::Error;
+
+/// A cheap, clonable cancellation flag shared between a running conversion and
+/// the code that wants to stop it.
+///
+/// Cloning shares the same underlying flag, so a clone handed to another thread
+/// (or stored in a registry) can cancel work running elsewhere. Conversions
+/// check the token cooperatively at frame boundaries and while waiting on
+/// `ffmpeg`, so cancellation is "stop soon" rather than instantaneous.
+#[derive(Clone, Default)]
+pub struct CancelToken(std::sync::Arc<std::sync::atomic::AtomicBool>);
+
+impl CancelToken {
+    /// Create a fresh, not-yet-cancelled token.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation. Idempotent and cheap to call from any thread.
+    pub fn cancel(&self) {
+        self.0.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Returns `true` once [`cancel`](Self::cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+/// Marker for a [`CancelToken`] triggered mid-flight; it converts into the
+/// [`Error::Cancelled`] that conversion functions return.
+///
+/// Callers match on that variant to tell a user-requested cancellation apart
+/// from a genuine failure:
+///
+/// ```no_run
+/// # let err = cascii::Error::Cancelled;
+/// if matches!(err, cascii::Error::Cancelled) {
+///     // cancelled by the user — not a real error
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "operation cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Returns `true` if `err` is [`Error::Cancelled`].
+pub fn is_cancelled_error(err: &Error) -> bool {
+    matches!(err, Error::Cancelled)
+}
@@ -0,0 +1,183 @@
+//! Timed captions drawn into converted frames.
+//!
+//! Unlike subtitles burned into the source video, captions are written in character space: every
+//! caption visible at a frame's time replaces the cells it covers with its own text, drawn white
+//! (on black, when the frame has a background layer). Tracks are built in code or loaded from JSON
+//! or a small SRT dialect (see [`CaptionTrack`]).
+
+use crate::error::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// Where a caption is drawn. Lines are centered horizontally except at an explicit cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptionPosition {
+    /// The first rows
+    Top,
+    /// The middle rows
+    Center,
+    /// The last rows
+    #[default]
+    Bottom,
+    /// Starting at a cell, counted from the top-left corner
+    At { column: u32, row: u32 },
+}
+
+/// Text shown from `start` until `end` seconds into the converted clip.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Caption {
+    /// The caption; `\n` starts another line
+    pub text: String,
+    /// First second the caption is visible
+    pub start: f64,
+    /// Second the caption disappears
+    pub end: f64,
+    /// Where the caption is drawn
+    #[serde(default)]
+    pub position: CaptionPosition,
+}
+
+/// A set of timed captions.
+///
+/// JSON is an array of captions:
+///
+/// ```json
+/// [
+///     {"text": "Hello", "start": 0.5, "end": 2.0},
+///     {"text": "Top left", "start": 1.0, "end": 3.0, "position": {"at": {"column": 2, "row": 1}}}
+/// ]
+/// ```
+///
+/// The SRT dialect accepts `HH:MM:SS,mmm` (or `.mmm`, or plain seconds) times, an optional cue
+/// number and an optional `top`, `center` or `bottom` after the times:
+///
+/// ```text
+/// 1
+/// 00:00:00,500 --> 00:00:02,000 top
+/// Hello
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CaptionTrack {
+    pub captions: Vec<Caption>,
+}
+
+impl CaptionTrack {
+    pub fn new(captions: Vec<Caption>) -> Self {
+        Self {captions}
+    }
+
+    /// Parse a JSON array of captions.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let captions: Vec<Caption> = serde_json::from_str(json).context("parsing captions json")?;
+        Ok(Self {captions})
+    }
+
+    /// Parse the SRT dialect described [above](CaptionTrack).
+    pub fn from_srt(srt: &str) -> Result<Self> {
+        let mut captions = Vec::new();
+        let normalized = srt.replace("\r\n", "\n");
+        for cue in normalized.split("\n\n").map(str::trim).filter(|cue| !cue.is_empty()) {
+            let mut lines = cue.lines().skip_while(|line| !line.contains("-->"));
+            let timing = lines.next().ok_or_else(|| Error::Parse(format!("caption cue without a `start --> end` line: {:?}", cue)))?;
+            let (start, rest) = timing.split_once("-->").expect("timing line contains -->");
+            let mut rest = rest.split_whitespace();
+            let end = rest.next().ok_or_else(|| Error::Parse(format!("caption cue without an end time: {:?}", timing)))?;
+            let position = match rest.next() {
+                None | Some("bottom") => CaptionPosition::Bottom,
+                Some("top") => CaptionPosition::Top,
+                Some("center") => CaptionPosition::Center,
+                Some(other) => return Err(Error::Parse(format!("unknown caption position {:?} (expected top, center or bottom)", other))),
+            };
+            captions.push(Caption {text: lines.collect::<Vec<_>>().join("\n"), start: parse_time(start)?, end: parse_time(end)?, position});
+        }
+        Ok(Self {captions})
+    }
+
+    /// Load a track from a `.json` file, or from the SRT dialect for any other extension.
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading captions {}", path.display()))?;
+        let track = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {Self::from_json(&text)} else {Self::from_srt(&text)};
+        track.with_context(|| format!("loading captions {}", path.display()))
+    }
+
+    /// Captions visible `seconds` into the clip.
+    pub fn at(&self, seconds: f64) -> impl Iterator<Item = &Caption> {
+        self.captions.iter().filter(move |caption| caption.start <= seconds && seconds < caption.end)
+    }
+
+    /// Draw the captions visible at `seconds` over a `width` × `height` grid. Color buffers that
+    /// don't hold 3 bytes per cell (an absent background layer) are skipped.
+    pub(crate) fn apply(&self, seconds: f64, text: &mut String, width: u32, height: u32, rgb: &mut [u8], bg_rgb: &mut [u8]) {
+        let (width, height) = (width as usize, height as usize);
+        let mut overlay: Vec<Option<u8>> = vec![None; width * height];
+        let mut drawn = false;
+        for caption in self.at(seconds) {
+            let lines: Vec<&str> = caption.text.lines().collect();
+            let first_row = match caption.position {
+                CaptionPosition::Top => 0,
+                CaptionPosition::Center => height.saturating_sub(lines.len()) / 2,
+                CaptionPosition::Bottom => height.saturating_sub(lines.len()),
+                CaptionPosition::At {row, ..} => row as usize,
+            };
+            for (row, line) in (first_row..height).zip(&lines) {
+                let line: Vec<u8> = line.chars().map(|ch| if ch.is_ascii() && !ch.is_ascii_control() {ch as u8} else {b'?'}).take(width).collect();
+                let first_column = match caption.position {
+                    CaptionPosition::At {column, ..} => column as usize,
+                    _ => (width - line.len()) / 2,
+                };
+                for (column, &byte) in (first_column..width).zip(&line) {
+                    overlay[row * width + column] = Some(byte);
+                    drawn = true;
+                }
+            }
+        }
+        if !drawn {
+            return;
+        }
+
+        let mut cells = overlay.iter();
+        *text = text.chars().map(|ch| if ch == '\n' {ch} else {cells.next().copied().flatten().map_or(ch, char::from)}).collect();
+        for (colors, fill) in [(rgb, 255), (bg_rgb, 0)] {
+            if colors.len() != overlay.len() * 3 {
+                continue;
+            }
+            for (cell, _) in colors.chunks_exact_mut(3).zip(&overlay).filter(|(_, byte)| byte.is_some()) {
+                cell.fill(fill);
+            }
+        }
+    }
+}
+
+/// Seconds from `HH:MM:SS,mmm`, `MM:SS.mmm` or plain seconds.
+fn parse_time(time: &str) -> Result<f64> {
+    let time = time.trim().replace(',', ".");
+    time.split(':').try_fold(0.0, |total, part| part.parse::<f64>().map(|value| total * 60.0 + value)).map_err(|_| Error::Parse(format!("invalid caption time {:?}", time)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captions_parse_and_burn_into_cells() {
+        let track = CaptionTrack::from_srt("1\n00:00:01,000 --> 00:00:02.000\nHi\n\n00:00:02 --> 3 top\nUP\n").unwrap();
+        assert_eq!(track.captions.len(), 2);
+        assert_eq!((track.captions[0].start, track.captions[0].end, track.captions[1].position), (1.0, 2.0, CaptionPosition::Top));
+        assert!(CaptionTrack::from_srt("00:00:01 --> 2 left\nx").is_err());
+
+        let json = CaptionTrack::from_json(r#"[{"text": "é", "start": 0, "end": 1, "position": {"at": {"column": 5, "row": 0}}}]"#).unwrap();
+        assert_eq!(json.captions[0].position, CaptionPosition::At {column: 5, row: 0});
+
+        let burn = |track: &CaptionTrack, seconds: f64| {
+            let (mut text, mut rgb, mut bg) = ("......\n......\n......\n".to_string(), vec![9; 54], vec![9; 54]);
+            track.apply(seconds, &mut text, 6, 3, &mut rgb, &mut bg);
+            (text, rgb, bg)
+        };
+        let (text, rgb, bg) = burn(&track, 1.5);
+        assert_eq!(text, "......\n......\n..Hi..\n");
+        assert_eq!((&rgb[42..45], &bg[42..45], &rgb[39..42]), (&[255; 3][..], &[0; 3][..], &[9; 3][..]));
+        assert_eq!(burn(&track, 2.0).0, "..UP..\n......\n......\n");
+        assert_eq!(burn(&json, 0.0).0, ".....?\n......\n......\n");
+        assert_eq!(burn(&track, 5.0).1, vec![9; 54]);
+    }
+}
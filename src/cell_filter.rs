@@ -3,7 +3,7 @@
 //! Used to gate cell selections (e.g. a lasso capture) by brightness or by local color coherence: failing cells are reported so callers can blank them per frame. 
 //! Filters only ever fail cells they can positively judge out-of-bounds cells and characters absent from the ascii ramp pass.
 
-use anyhow::{anyhow, Result};
+use crate::error::{Error, Result};
 
 const HEADER_SIZE: usize = 8;
 const CELL_SIZE: usize = 4;
@@ -14,6 +14,174 @@ pub fn luminance_rgb(r: u8, g: u8, b: u8) -> u8 {
     ((2126 * r as u32 + 7152 * g as u32 + 722 * b as u32) / 10000) as u8
 }
 
+/// Per-channel weights of the luminance that picks each cell's character.
+///
+/// Weights are relative; the luminance is their weighted average, so any non-zero set maps white to
+/// 255. The default is Rec.709, matching [`luminance_rgb`]. Thermal or scientific imagery that encodes
+/// data in one channel can weight only that channel instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LuminanceWeights {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+}
+
+impl LuminanceWeights {
+    /// Rec.709 relative luminance
+    pub const REC709: Self = Self {red: 2126, green: 7152, blue: 722};
+    /// Rec.601 (standard definition video) luma
+    pub const REC601: Self = Self {red: 299, green: 587, blue: 114};
+    /// The plain mean of the three channels
+    pub const AVERAGE: Self = Self {red: 1, green: 1, blue: 1};
+    /// Red channel only
+    pub const RED: Self = Self {red: 1, green: 0, blue: 0};
+    /// Green channel only
+    pub const GREEN: Self = Self {red: 0, green: 1, blue: 0};
+    /// Blue channel only
+    pub const BLUE: Self = Self {red: 0, green: 0, blue: 1};
+    /// Ignore green, so a green screen reads as dark and the subject stands out
+    pub const GREEN_SCREEN: Self = Self {red: 1, green: 0, blue: 1};
+
+    /// Named presets accepted by [`FromStr`](std::str::FromStr), in the order they are listed by the CLI.
+    pub const PRESETS: [(&'static str, Self); 5] = [("rec709", Self::REC709), ("red", Self::RED), ("green", Self::GREEN), ("blue", Self::BLUE), ("green-screen", Self::GREEN_SCREEN)];
+
+    /// Weights for the three channels; at least one must be non-zero.
+    pub fn new(red: u16, green: u16, blue: u16) -> Result<Self> {
+        if red == 0 && green == 0 && blue == 0 {
+            return Err(Error::InvalidOptions("luminance weights must not all be zero".into()));
+        }
+        Ok(Self {red, green, blue})
+    }
+
+    #[inline]
+    pub fn luminance(self, r: u8, g: u8, b: u8) -> u8 {
+        let total = self.red as u32 + self.green as u32 + self.blue as u32;
+        ((self.red as u32 * r as u32 + self.green as u32 * g as u32 + self.blue as u32 * b as u32) / total.max(1)) as u8
+    }
+
+    /// [`luminance`](Self::luminance) of a 16-bit pixel.
+    #[inline]
+    pub fn luminance16(self, r: u16, g: u16, b: u16) -> u16 {
+        let total = self.red as u64 + self.green as u64 + self.blue as u64;
+        ((self.red as u64 * r as u64 + self.green as u64 * g as u64 + self.blue as u64 * b as u64) / total.max(1)) as u16
+    }
+}
+
+impl Default for LuminanceWeights {
+    fn default() -> Self {
+        Self::REC709
+    }
+}
+
+/// Parses a preset name (`rec709`, `red`, `green`, `blue`, `green-screen`) or three comma-separated weights (`3,1,0`).
+impl std::str::FromStr for LuminanceWeights {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if let Some((_, weights)) = Self::PRESETS.iter().find(|(name, _)| *name == s) {
+            return Ok(*weights);
+        }
+        let presets = Self::PRESETS.map(|(name, _)| name).join(", ");
+        let parts: Vec<u16> = s.split(',').map(|part| part.trim().parse::<u16>()).collect::<std::result::Result<_, _>>().map_err(|_| format!("expected R,G,B weights or one of {}", presets))?;
+        match parts[..] {
+            [red, green, blue] => Self::new(red, green, blue).map_err(|err| err.to_string()),
+            _ => Err(format!("expected R,G,B weights or one of {}", presets)),
+        }
+    }
+}
+
+/// Formula measuring how bright a pixel is, and so which character of the ramp it gets.
+///
+/// Content responds differently to each: Rec.709 suits modern video, Rec.601 older standard
+/// definition footage, the plain average keeps saturated blues and reds from reading as near black,
+/// and CIE lightness spreads shadows and highlights the way the eye perceives them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LuminanceModel {
+    /// Weighted average of the channels by `ConversionOptions::luminance_weights` (Rec.709 unless changed)
+    #[default]
+    Luma,
+    /// Rec.601 luma, whatever the luminance weights
+    Rec601,
+    /// Rec.709 luma, whatever the luminance weights
+    Rec709,
+    /// Mean of the three channels
+    Average,
+    /// CIE L* (the lightness of Lab) of the sRGB color
+    Lightness,
+}
+
+impl LuminanceModel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Luma => "luma",
+            Self::Rec601 => "rec601",
+            Self::Rec709 => "rec709",
+            Self::Average => "average",
+            Self::Lightness => "lightness",
+        }
+    }
+}
+
+/// A [`LuminanceModel`] resolved against the luminance weights it may use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Luminance {
+    weights: LuminanceWeights,
+    lightness: bool,
+}
+
+impl Luminance {
+    pub(crate) fn new(model: LuminanceModel, weights: LuminanceWeights) -> Self {
+        let weights = match model {
+            LuminanceModel::Luma => weights,
+            LuminanceModel::Rec601 => LuminanceWeights::REC601,
+            LuminanceModel::Rec709 | LuminanceModel::Lightness => LuminanceWeights::REC709,
+            LuminanceModel::Average => LuminanceWeights::AVERAGE,
+        };
+        Self {weights, lightness: model == LuminanceModel::Lightness}
+    }
+
+    /// Whether this is the luma of `weights`, so a Y plane encoded with them can stand in for it
+    pub(crate) fn is_luma_of(self, weights: LuminanceWeights) -> bool {
+        !self.lightness && self.weights == weights
+    }
+
+    #[inline]
+    pub(crate) fn luminance(self, r: u8, g: u8, b: u8) -> u8 {
+        if !self.lightness {
+            return self.weights.luminance(r, g, b);
+        }
+        let linear = srgb_to_linear_table();
+        (lightness(linear[r as usize], linear[g as usize], linear[b as usize]) * 255.0).round() as u8
+    }
+
+    /// [`luminance`](Self::luminance) of a 16-bit pixel.
+    #[inline]
+    pub(crate) fn luminance16(self, r: u16, g: u16, b: u16) -> u16 {
+        if !self.lightness {
+            return self.weights.luminance16(r, g, b);
+        }
+        let linear = |channel: u16| srgb_to_linear(channel as f32 / 65535.0);
+        (lightness(linear(r), linear(g), linear(b)) * 65535.0).round() as u16
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {value / 12.92} else {((value + 0.055) / 1.055).powf(2.4)}
+}
+
+fn srgb_to_linear_table() -> &'static [f32; 256] {
+    static TABLE: std::sync::OnceLock<[f32; 256]> = std::sync::OnceLock::new();
+    TABLE.get_or_init(|| std::array::from_fn(|value| srgb_to_linear(value as f32 / 255.0)))
+}
+
+/// CIE L* of linear sRGB channels, scaled from `0..=100` to `0.0..=1.0`
+fn lightness(r: f32, g: f32, b: f32) -> f32 {
+    const EPSILON: f32 = 216.0 / 24389.0;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let f = if y > EPSILON {y.cbrt()} else {y * 24389.0 / 27.0 / 116.0 + 16.0 / 116.0};
+    (1.16 * f - 0.16).clamp(0.0, 1.0)
+}
+
 /// One-sided luminance bound. Cells strictly past `threshold` are dropped; `inclusive` also drops cells sitting exactly on the threshold.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct LuminanceBound {
@@ -56,6 +224,7 @@ impl LuminanceFilter {
 /// Returns one bool per entry in `cells` (parallel array): `true` = keep, `false` = the cell's luminance is dropped by the filter.
 /// Cells outside the frame grid return `true` — the mask is purely a "would luminance blank this cell" predicate, and consumers already skip out-of-bounds cells themselves.
 pub fn cframe_cells_luminance_mask(data: &[u8], cells: &[(usize, usize)], filter: LuminanceFilter) -> Result<Vec<bool>> {
+    let data = &crate::cframe::frame_bytes(data)?[..];
     let (width, height) = validated_cframe_dimensions(data)?;
 
     Ok(cells.iter().map(|&(row, col)| {
@@ -86,6 +255,7 @@ pub struct ProximityFilter {
 /// Evaluate the local color dominance of each `(row, col)` cell in a raw `.cframe` payload. Returns one bool per entry in `cells` (parallel array): `true` = keep.
 /// Cells outside the frame grid pass. Blank glyphs are invisible: they always pass, and they are excluded from the neighborhood mean so they cannot drag a bright region's dominant color toward black. Cells with no visible neighbor in the window pass (nothing to judge against).
 pub fn cframe_cells_proximity_mask(data: &[u8], cells: &[(usize, usize)], filter: ProximityFilter) -> Result<Vec<bool>> {
+    let data = &crate::cframe::frame_bytes(data)?[..];
     let (width, height) = validated_cframe_dimensions(data)?;
     let radius = filter.radius.clamp(1, 4) as isize;
 
@@ -121,20 +291,20 @@ pub fn cframe_cells_proximity_mask(data: &[u8], cells: &[(usize, usize)], filter
 
 fn validated_cframe_dimensions(data: &[u8]) -> Result<(usize, usize)> {
     if data.len() < HEADER_SIZE {
-        return Err(anyhow!("cframe file too small"));
+        return Err(Error::InvalidCframe("cframe file too small".into()));
     }
 
     let width = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
     let height = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
     if width == 0 || height == 0 {
-        return Err(anyhow!("cframe dimensions must be non-zero"));
+        return Err(Error::InvalidCframe("cframe dimensions must be non-zero".into()));
     }
 
-    let cell_count = width.checked_mul(height).ok_or_else(|| anyhow!("cframe dimensions overflow"))?;
-    let body_len = cell_count.checked_mul(CELL_SIZE).ok_or_else(|| anyhow!("cframe body size overflow"))?;
-    let body_end = HEADER_SIZE.checked_add(body_len).ok_or_else(|| anyhow!("cframe body offset overflow"))?;
+    let cell_count = width.checked_mul(height).ok_or_else(|| Error::InvalidCframe("cframe dimensions overflow".into()))?;
+    let body_len = cell_count.checked_mul(CELL_SIZE).ok_or_else(|| Error::InvalidCframe("cframe body size overflow".into()))?;
+    let body_end = HEADER_SIZE.checked_add(body_len).ok_or_else(|| Error::InvalidCframe("cframe body offset overflow".into()))?;
     if data.len() < body_end {
-        return Err(anyhow!("cframe file truncated: expected at least {} bytes, got {}", body_end, data.len()));
+        return Err(Error::InvalidCframe(format!("cframe file truncated: expected at least {} bytes, got {}", body_end, data.len())));
     }
 
     Ok((width, height))
@@ -168,10 +338,10 @@ pub struct RampLuminance {
 impl RampLuminance {
     pub fn new(ramp: &str) -> Result<Self> {
         if ramp.is_empty() {
-            return Err(anyhow!("ascii ramp must not be empty"));
+            return Err(Error::InvalidOptions("ascii ramp must not be empty".into()));
         }
         if !ramp.is_ascii() {
-            return Err(anyhow!("ascii ramp must contain only ASCII characters"));
+            return Err(Error::InvalidOptions("ascii ramp must contain only ASCII characters".into()));
         }
 
         let mut positions = [-1i16; 256];
@@ -227,6 +397,28 @@ mod tests {
         assert_eq!(luminance_rgb(0, 0, 255), 18);
     }
 
+    #[test]
+    fn test_luminance_weights() {
+        let weights = LuminanceWeights::default();
+        assert!((0..=255).step_by(15).all(|v| weights.luminance(v, 255 - v, v / 2) == luminance_rgb(v, 255 - v, v / 2)));
+        assert_eq!(LuminanceWeights::RED.luminance(200, 10, 90), 200);
+        assert_eq!(LuminanceWeights::GREEN_SCREEN.luminance(0, 255, 0), 0);
+        assert_eq!("green-screen".parse::<LuminanceWeights>(), Ok(LuminanceWeights::GREEN_SCREEN));
+        assert_eq!("3, 1,0".parse::<LuminanceWeights>(), Ok(LuminanceWeights {red: 3, green: 1, blue: 0}));
+        assert!("0,0,0".parse::<LuminanceWeights>().unwrap_err().contains("zero"));
+        assert!("1,2".parse::<LuminanceWeights>().unwrap_err().contains("green-screen"));
+    }
+
+    #[test]
+    fn test_luminance_models() {
+        let blue = |model: LuminanceModel| Luminance::new(model, LuminanceWeights::RED).luminance(0, 0, 255);
+        assert_eq!([LuminanceModel::Luma, LuminanceModel::Rec601, LuminanceModel::Rec709, LuminanceModel::Average, LuminanceModel::Lightness].map(blue), [0, 29, 18, 85, 82]);
+        // Lightness is perceptually even: sRGB mid gray is a little over half as light as white
+        let lightness = Luminance::new(LuminanceModel::Lightness, LuminanceWeights::default());
+        assert_eq!((lightness.luminance(0, 0, 0), lightness.luminance(128, 128, 128), lightness.luminance(255, 255, 255)), (0, 137, 255));
+        assert_eq!((lightness.luminance16(128 * 257, 128 * 257, 128 * 257) as u32 + 128) / 257, 137);
+    }
+
     fn drop_below(threshold: u8, inclusive: bool) -> LuminanceFilter {
         LuminanceFilter {drop_below: Some(LuminanceBound {threshold, inclusive}), drop_above: None}
     }
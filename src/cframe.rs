@@ -0,0 +1,733 @@
+//! The `.cframe` v2 container: a magic number, a version, metadata and an optionally compressed body.
+//!
+//! A v1 `.cframe` (the layout [`AsciiFrame::cframe_bytes`](crate::AsciiFrame::cframe_bytes) writes)
+//! is a bare size header and four bytes per cell, so at 400 columns a long clip takes gigabytes. A v2
+//! file wraps those same bytes:
+//!
+//! 1. `magic: [u8; 4]` = `CFRM`, then `version: u8` = 2 and `compression: u8` (0 none, 1 zlib)
+//! 2. `metadata_len: u32 LE` and `frame_len: u32 LE`, the length of the v1 frame once decompressed
+//! 3. `metadata_len` bytes of [`CframeMetadata`] as JSON
+//! 4. the v1 frame, compressed as `compression` says, to the end of the file
+//!
+//! The magic read as a v1 width is over a billion columns, which no v1 file has the bytes for, so
+//! the two can't be confused. Every reader of the crate takes either version; [`frame_bytes`] gives
+//! the v1 layout of any `.cframe` for code of its own.
+//!
+//! zlib compression needs the `zlib` feature (on by default); without it, encoding or reading a
+//! zlib-compressed frame fails, and only [`CframeCompression::None`] is in [`CframeCompression::ALL`].
+//! zlib rather than zstd because `png` already builds `flate2` with its pure-Rust backend, so the
+//! feature adds no C library and still compiles for `wasm32-unknown-unknown`, where the zstd crate
+//! needs libzstd built by a C toolchain.
+//!
+#![cfg_attr(feature = "zlib", doc = "```")]
+#![cfg_attr(not(feature = "zlib"), doc = "```ignore")]
+//! use cascii::cframe::{self, CframeCompression, CframeMetadata};
+//! use cascii::AsciiFrame;
+//!
+//! let frame = AsciiFrame {text: "@@@@\n".repeat(50), width: 4, height: 50, rgb: vec![255; 600], bg_rgb: Vec::new()};
+//! let v1 = frame.cframe_bytes();
+//! let v2 = cframe::encode_v2(&v1, CframeCompression::Zlib, &CframeMetadata::default().with_fps(24))?;
+//! assert!(v2.len() < v1.len() / 10);
+//! assert_eq!(cframe::frame_bytes(&v2)?, v1);
+//! assert_eq!(cframe::metadata(&v2)?.and_then(|metadata| metadata.fps), Some(24));
+//! # Ok::<(), cascii::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::fs::File;
+#[cfg(feature = "zlib")]
+use std::io::Cursor;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+#[cfg(feature = "zlib")]
+use flate2::read::ZlibDecoder;
+#[cfg(feature = "zlib")]
+use flate2::write::ZlibEncoder;
+use serde::{Deserialize, Serialize};
+
+use crate::codepage::Codepage;
+use crate::error::{Context, Error, Result};
+use crate::frame::{AsciiCell, CellBytes, CharMode, CFRAME_EXT_FLAG_HAS_BG};
+
+/// The first bytes of a v2 `.cframe`
+pub const CFRAME_V2_MAGIC: &[u8; 4] = b"CFRM";
+const CFRAME_V2_VERSION: u8 = 2;
+const CFRAME_V2_HEADER_SIZE: usize = 14;
+
+/// How the frame in a v2 `.cframe` is compressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CframeCompression {
+    /// Stored as is
+    #[default]
+    None,
+    /// zlib (DEFLATE); runs of equal cells, such as flat backgrounds, shrink the most. Needs the
+    /// `zlib` feature
+    Zlib,
+}
+
+impl CframeCompression {
+    /// The compressions this build writes and reads
+    pub const ALL: &'static [CframeCompression] = &[Self::None, #[cfg(feature = "zlib")] Self::Zlib];
+
+    /// The compression's name, as serialized
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Zlib => "zlib",
+        }
+    }
+
+    fn id(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zlib => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self> {
+        match id {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Zlib),
+            _ => Err(Error::InvalidCframe(format!("unknown cframe compression {}", id))),
+        }
+    }
+}
+
+/// Which `.cframe` layout conversions write.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CframeFormat {
+    /// The bare layout every cascii version and third-party reader understands
+    #[default]
+    V1,
+    /// The v2 container, with the conversion's [`CframeMetadata`]
+    V2 {compression: CframeCompression},
+}
+
+/// What a v2 `.cframe` records about the conversion that wrote it; unknown fields are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CframeMetadata {
+    /// Frame rate of the clip the frame belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<u32>,
+    /// The character ramp the cells were picked from, darkest first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charset: Option<String>,
+    /// Font aspect ratio the frame was converted for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_ratio: Option<f32>,
+}
+
+impl CframeMetadata {
+    /// Record the clip's frame rate
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+
+    /// Record the character ramp, darkest first
+    pub fn with_charset(mut self, charset: impl Into<String>) -> Self {
+        self.charset = Some(charset.into());
+        self
+    }
+
+    /// Record the font aspect ratio
+    pub fn with_font_ratio(mut self, font_ratio: f32) -> Self {
+        self.font_ratio = Some(font_ratio);
+        self
+    }
+}
+
+/// A `.cframe` of either version, taken apart.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cframe<'a> {
+    /// [`CframeFormat::V1`] for bare frames
+    pub format: CframeFormat,
+    /// Empty for v1 frames
+    pub metadata: CframeMetadata,
+    /// The frame in the v1 layout, borrowed unless it was compressed
+    pub frame: Cow<'a, [u8]>,
+}
+
+/// Whether `data` starts like a v2 `.cframe`
+pub fn is_v2(data: &[u8]) -> bool {
+    data.starts_with(CFRAME_V2_MAGIC)
+}
+
+/// Wrap the v1 `frame` in a v2 container; [`CframeCompression::Zlib`] fails without the `zlib` feature
+pub fn encode_v2(frame: &[u8], compression: CframeCompression, metadata: &CframeMetadata) -> Result<Vec<u8>> {
+    assemble(frame, compression, &serde_json::to_vec(metadata).expect("cframe metadata serializes to JSON"))
+}
+
+/// A v2 container of `frame` with the JSON `metadata`
+fn assemble(frame: &[u8], compression: CframeCompression, metadata: &[u8]) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(CFRAME_V2_HEADER_SIZE + metadata.len() + frame.len());
+    output.extend_from_slice(CFRAME_V2_MAGIC);
+    output.extend_from_slice(&[CFRAME_V2_VERSION, compression.id()]);
+    output.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+    output.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+    output.extend_from_slice(metadata);
+    match compression {
+        CframeCompression::None => output.extend_from_slice(frame),
+        #[cfg(feature = "zlib")]
+        CframeCompression::Zlib => {
+            let mut encoder = ZlibEncoder::new(output, flate2::Compression::default());
+            encoder.write_all(frame).expect("writing to a Vec doesn't fail");
+            output = encoder.finish().expect("writing to a Vec doesn't fail");
+        }
+        #[cfg(not(feature = "zlib"))]
+        CframeCompression::Zlib => return Err(zlib_unsupported()),
+    }
+    Ok(output)
+}
+
+/// The error for zlib-compressed frames in a build without the `zlib` feature
+#[cfg(not(feature = "zlib"))]
+fn zlib_unsupported() -> Error {
+    Error::InvalidCframe("zlib-compressed cframes need the `zlib` feature".into())
+}
+
+/// The parts of a v2 header: compression, metadata bytes, body and decompressed frame length
+struct Header<'a> {
+    compression: CframeCompression,
+    metadata: &'a [u8],
+    body: &'a [u8],
+    frame_len: usize,
+}
+
+/// The header of a v2 `.cframe`; `None` for v1
+fn header(data: &[u8]) -> Result<Option<Header<'_>>> {
+    if !is_v2(data) {
+        return Ok(None);
+    }
+    if data.len() < CFRAME_V2_HEADER_SIZE {
+        return Err(Error::InvalidCframe("cframe v2 header truncated".into()));
+    }
+    if data[4] != CFRAME_V2_VERSION {
+        return Err(Error::InvalidCframe(format!("unsupported cframe version {}", data[4])));
+    }
+    let compression = CframeCompression::from_id(data[5])?;
+    let metadata_len = u32::from_le_bytes(data[6..10].try_into().unwrap()) as usize;
+    let frame_len = u32::from_le_bytes(data[10..14].try_into().unwrap()) as usize;
+    let metadata_end = CFRAME_V2_HEADER_SIZE.checked_add(metadata_len).filter(|&end| end <= data.len()).ok_or_else(|| Error::InvalidCframe("cframe metadata truncated".into()))?;
+    Ok(Some(Header {compression, metadata: &data[CFRAME_V2_HEADER_SIZE..metadata_end], body: &data[metadata_end..], frame_len}))
+}
+
+/// Take a `.cframe` of either version apart, decompressing its frame
+pub fn decode(data: &[u8]) -> Result<Cframe<'_>> {
+    let Some(header) = header(data)? else {
+        return Ok(Cframe {format: CframeFormat::V1, metadata: CframeMetadata::default(), frame: Cow::Borrowed(data)});
+    };
+    let metadata = serde_json::from_slice(header.metadata).map_err(|err| Error::InvalidCframe(format!("cframe metadata: {}", err)))?;
+    let frame = match header.compression {
+        CframeCompression::None => Cow::Borrowed(header.body),
+        #[cfg(feature = "zlib")]
+        CframeCompression::Zlib => {
+            // Never more than the header promises, so a damaged length can't inflate without bound
+            let mut frame = Vec::new();
+            ZlibDecoder::new(header.body).take(header.frame_len as u64 + 1).read_to_end(&mut frame).map_err(|err| Error::InvalidCframe(format!("decompressing cframe: {}", err)))?;
+            Cow::Owned(frame)
+        }
+        #[cfg(not(feature = "zlib"))]
+        CframeCompression::Zlib => return Err(zlib_unsupported()),
+    };
+    if frame.len() != header.frame_len {
+        return Err(Error::InvalidCframe(format!("cframe frame is {} bytes, its header says {}", frame.len(), header.frame_len)));
+    }
+    Ok(Cframe {format: CframeFormat::V2 {compression: header.compression}, metadata, frame})
+}
+
+/// The frame of a `.cframe` of either version in the v1 layout
+pub fn frame_bytes(data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    decode(data).map(|cframe| cframe.frame)
+}
+
+/// The metadata of a v2 `.cframe`, read without decompressing its frame; `None` for v1
+pub fn metadata(data: &[u8]) -> Result<Option<CframeMetadata>> {
+    header(data)?.map(|header| serde_json::from_slice(header.metadata).map_err(|err| Error::InvalidCframe(format!("cframe metadata: {}", err)))).transpose()
+}
+
+/// `frame`, an edited copy of the frame of `original`, in the container `original` came in, its
+/// metadata kept byte for byte
+pub(crate) fn rewrap(original: &[u8], frame: Vec<u8>) -> Result<Vec<u8>> {
+    Ok(match header(original)? {
+        None => frame,
+        Some(header) => assemble(&frame, header.compression, header.metadata)?,
+    })
+}
+
+/// Where the v1 frame a [`CframeReader`] reads lives
+enum Source<R> {
+    /// `base` bytes into the reader
+    Stream {reader: R, base: u64},
+    /// Inflated from a compressed v2 file, which can't be read from the middle
+    #[cfg(feature = "zlib")]
+    Inflated(Cursor<Vec<u8>>),
+}
+
+impl<R: Read + Seek> Source<R> {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8], position: &mut Option<u64>) -> std::io::Result<()> {
+        match self {
+            Self::Stream {reader, base} => {
+                // Rows without a background follow each other, so a buffered reader keeps its buffer
+                if *position != Some(offset) {
+                    reader.seek(SeekFrom::Start(*base + offset))?;
+                }
+                reader.read_exact(buf)?;
+            }
+            #[cfg(feature = "zlib")]
+            Self::Inflated(frame) => {
+                frame.set_position(offset);
+                frame.read_exact(buf)?;
+            }
+        }
+        *position = Some(offset + buf.len() as u64);
+        Ok(())
+    }
+}
+
+/// Reads a `.cframe` of either version a row at a time, holding one row of cells instead of the
+/// whole frame. Rows are read from the file as they are asked for; only a zlib-compressed v2 frame
+/// is inflated into memory first, since it can't be read from the middle.
+///
+/// ```no_run
+/// use cascii::cframe::CframeReader;
+///
+/// # fn main() -> cascii::Result<()> {
+/// let mut frame = CframeReader::open("frame_0001.cframe")?;
+/// let mut row = Vec::with_capacity(frame.width() as usize);
+/// while frame.read_row(&mut row)? {
+///     let line: String = row.iter().map(|cell| cell.ch).collect();
+///     println!("{}", line);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct CframeReader<R> {
+    source: Source<R>,
+    format: CframeFormat,
+    metadata: CframeMetadata,
+    width: u32,
+    height: u32,
+    cell_bytes: CellBytes,
+    /// Where the background layer starts in the v1 frame, when it has one
+    background: Option<u64>,
+    /// The next row to read
+    row: u32,
+    /// Where the reader stands in the v1 frame, when known
+    position: Option<u64>,
+    buffer: Vec<u8>,
+}
+
+impl CframeReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        Self::new(BufReader::new(file)).with_context(|| format!("reading {}", path.display()))
+    }
+}
+
+impl<R: Read + Seek> CframeReader<R> {
+    /// Read the header of the `.cframe` starting where `reader` stands, and the extension flags
+    /// past its cells
+    pub fn new(mut reader: R) -> Result<Self> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).map_err(|_| Error::InvalidCframe("cframe file too small".into()))?;
+        let (mut source, format, metadata, len) = if &magic == CFRAME_V2_MAGIC {
+            let mut data = magic.to_vec();
+            data.resize(CFRAME_V2_HEADER_SIZE, 0);
+            reader.read_exact(&mut data[4..]).map_err(|_| Error::InvalidCframe("cframe v2 header truncated".into()))?;
+            let metadata_len = u32::from_le_bytes(data[6..10].try_into().unwrap()) as u64;
+            if start + CFRAME_V2_HEADER_SIZE as u64 + metadata_len > end {
+                return Err(Error::InvalidCframe("cframe metadata truncated".into()));
+            }
+            data.resize(CFRAME_V2_HEADER_SIZE + metadata_len as usize, 0);
+            reader.read_exact(&mut data[CFRAME_V2_HEADER_SIZE..])?;
+            let header = header(&data)?.expect("the data starts with the v2 magic");
+            let metadata = serde_json::from_slice(header.metadata).map_err(|err| Error::InvalidCframe(format!("cframe metadata: {}", err)))?;
+            let source = match header.compression {
+                CframeCompression::None => {
+                    let base = start + data.len() as u64;
+                    if end - base < header.frame_len as u64 {
+                        return Err(Error::InvalidCframe(format!("cframe frame is {} bytes, its header says {}", end - base, header.frame_len)));
+                    }
+                    Source::Stream {reader, base}
+                }
+                #[cfg(feature = "zlib")]
+                CframeCompression::Zlib => {
+                    let mut frame = Vec::new();
+                    ZlibDecoder::new(reader).take(header.frame_len as u64 + 1).read_to_end(&mut frame).map_err(|err| Error::InvalidCframe(format!("decompressing cframe: {}", err)))?;
+                    if frame.len() != header.frame_len {
+                        return Err(Error::InvalidCframe(format!("cframe frame is {} bytes, its header says {}", frame.len(), header.frame_len)));
+                    }
+                    Source::Inflated(Cursor::new(frame))
+                }
+                #[cfg(not(feature = "zlib"))]
+                CframeCompression::Zlib => return Err(zlib_unsupported()),
+            };
+            (source, CframeFormat::V2 {compression: header.compression}, metadata, header.frame_len as u64)
+        } else {
+            (Source::Stream {reader, base: start}, CframeFormat::V1, CframeMetadata::default(), end - start)
+        };
+
+        let mut size = [0u8; 8];
+        let mut position = None;
+        source.read_at(0, &mut size, &mut position).map_err(|_| Error::InvalidCframe("cframe file too small".into()))?;
+        let width = u32::from_le_bytes(size[0..4].try_into().unwrap());
+        let height = u32::from_le_bytes(size[4..8].try_into().unwrap());
+        let cells = width as u64 * height as u64;
+        let body_end = 8 + cells * 4;
+        if len < body_end {
+            return Err(Error::InvalidCframe(format!("cframe file truncated: expected {} body bytes, got {}", cells * 4, len.saturating_sub(8))));
+        }
+        // Legacy files end in a bare background block instead of the flag byte
+        let (trailing, background_len) = (len - body_end, cells * 3);
+        let mut flags = [0u8];
+        if trailing > 0 && trailing != background_len {
+            source.read_at(body_end, &mut flags, &mut position)?;
+        }
+        let background = if trailing > background_len && flags[0] & CFRAME_EXT_FLAG_HAS_BG != 0 {
+            Some(body_end + 1)
+        } else {
+            (trailing == background_len && cells > 0).then_some(body_end)
+        };
+        Ok(Self {source, format, metadata, width, height, cell_bytes: CellBytes::from_flags(flags[0]), background, row: 0, position, buffer: Vec::new()})
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// [`CframeFormat::V1`] for bare frames
+    pub fn format(&self) -> CframeFormat {
+        self.format
+    }
+
+    /// Empty for v1 frames
+    pub fn metadata(&self) -> &CframeMetadata {
+        &self.metadata
+    }
+
+    /// Whether the cells carry background colors
+    pub fn has_background(&self) -> bool {
+        self.background.is_some()
+    }
+
+    /// Replace the cells of `row` with the next row of the frame, top first; `false`, leaving `row`
+    /// empty, once every row has been read
+    pub fn read_row(&mut self, row: &mut Vec<AsciiCell>) -> Result<bool> {
+        row.clear();
+        if self.row >= self.height {
+            return Ok(false);
+        }
+        let (width, index) = (self.width as u64, self.row as u64);
+        self.buffer.resize(self.width as usize * 4, 0);
+        self.source.read_at(8 + index * width * 4, &mut self.buffer, &mut self.position).with_context(|| format!("reading cframe row {}", index + 1))?;
+        row.extend(self.buffer.chunks_exact(4).map(|cell| AsciiCell {ch: self.cell_bytes.char(cell[0]), fg: Some([cell[1], cell[2], cell[3]]), bg: None}));
+        if let Some(background) = self.background {
+            self.buffer.resize(self.width as usize * 3, 0);
+            self.source.read_at(background + index * width * 3, &mut self.buffer, &mut self.position).with_context(|| format!("reading the background of cframe row {}", index + 1))?;
+            for (cell, bg) in row.iter_mut().zip(self.buffer.chunks_exact(3)) {
+                cell.bg = Some([bg[0], bg[1], bg[2]]);
+            }
+        }
+        self.row += 1;
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek> Iterator for CframeReader<R> {
+    type Item = Result<Vec<AsciiCell>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut row = Vec::with_capacity(self.width as usize);
+        self.read_row(&mut row).map(|more| more.then_some(row)).transpose()
+    }
+}
+
+/// Writes a `.cframe` a row at a time, holding one row of cells instead of the whole frame. The
+/// background layer follows every cell in the file, so with [`with_background`](Self::with_background)
+/// the writer seeks between the two for every row.
+///
+/// Cells are stored a byte each in the encoding of the char mode and code page the frame was
+/// converted with; characters the encoding has no byte for are stored as `?`. With
+/// [`with_metadata`](Self::with_metadata) the frame goes in an uncompressed v2 container.
+///
+/// ```
+/// use std::io::Cursor;
+/// use cascii::cframe::{CframeReader, CframeWriter};
+/// use cascii::AsciiCell;
+///
+/// let mut writer = CframeWriter::new(Cursor::new(Vec::new()), 3, 2).with_background();
+/// for row in ["abc", "def"] {
+///     let cells: Vec<AsciiCell> = row.chars().map(|ch| AsciiCell {ch, fg: Some([255, 0, 0]), bg: Some([0, 0, 64])}).collect();
+///     writer.write_row(&cells)?;
+/// }
+/// let mut output = writer.finish()?;
+/// output.set_position(0);
+/// let rows = CframeReader::new(output)?.collect::<cascii::Result<Vec<_>>>()?;
+/// assert_eq!(rows[1][2].ch, 'f');
+/// # Ok::<(), cascii::Error>(())
+/// ```
+pub struct CframeWriter<W> {
+    output: W,
+    width: u32,
+    height: u32,
+    char_mode: CharMode,
+    codepage: Codepage,
+    background: bool,
+    metadata: Option<CframeMetadata>,
+    /// Where the v1 frame starts in the output, once its header is written
+    base: Option<u64>,
+    /// Rows written so far
+    rows: u32,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write + Seek> CframeWriter<W> {
+    /// A writer of a `width` by `height` frame to `output`, starting where it stands; nothing is
+    /// written until the first row
+    pub fn new(output: W, width: u32, height: u32) -> Self {
+        Self {output, width, height, char_mode: CharMode::Ascii, codepage: Codepage::Unicode, background: false, metadata: None, base: None, rows: 0, buffer: Vec::new()}
+    }
+
+    pub fn with_char_mode(mut self, char_mode: CharMode) -> Self {
+        self.char_mode = char_mode;
+        self
+    }
+
+    /// Store [`CharMode::Ascii`] cells as CP437 bytes rather than Latin-1
+    pub fn with_codepage(mut self, codepage: Codepage) -> Self {
+        self.codepage = codepage;
+        self
+    }
+
+    /// Write the background color of every cell, black for cells without one
+    pub fn with_background(mut self) -> Self {
+        self.background = true;
+        self
+    }
+
+    /// Write a v2 `.cframe` with `metadata`
+    pub fn with_metadata(mut self, metadata: CframeMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    fn cell_bytes(&self) -> CellBytes {
+        match self.char_mode {
+            CharMode::Ascii if self.codepage == Codepage::Cp437 => CellBytes::Cp437,
+            CharMode::Ascii => CellBytes::Latin1,
+            CharMode::Braille => CellBytes::Braille,
+            CharMode::HalfBlock | CharMode::QuarterBlock => CellBytes::Blocks,
+            CharMode::Sextant => CellBytes::Sextants,
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        self.cell_bytes().flag() | if self.background {CFRAME_EXT_FLAG_HAS_BG} else {0}
+    }
+
+    /// Bytes of the v1 frame up to its background layer
+    fn body_end(&self) -> u64 {
+        8 + self.width as u64 * self.height as u64 * 4 + if self.flags() != 0 {1} else {0}
+    }
+
+    /// Write the headers on the first call; where the v1 frame starts
+    fn start(&mut self) -> Result<u64> {
+        if let Some(base) = self.base {
+            return Ok(base);
+        }
+        let mut base = self.output.stream_position()?;
+        if let Some(metadata) = &self.metadata {
+            let frame_len = self.body_end() + if self.background {self.width as u64 * self.height as u64 * 3} else {0};
+            let frame_len = u32::try_from(frame_len).map_err(|_| Error::InvalidOptions(format!("a {}x{} frame is too large for a v2 .cframe", self.width, self.height)))?;
+            let metadata = serde_json::to_vec(metadata).expect("cframe metadata serializes to JSON");
+            self.output.write_all(CFRAME_V2_MAGIC)?;
+            self.output.write_all(&[CFRAME_V2_VERSION, CframeCompression::None.id()])?;
+            self.output.write_all(&(metadata.len() as u32).to_le_bytes())?;
+            self.output.write_all(&frame_len.to_le_bytes())?;
+            self.output.write_all(&metadata)?;
+            base += (CFRAME_V2_HEADER_SIZE + metadata.len()) as u64;
+        }
+        self.output.write_all(&self.width.to_le_bytes())?;
+        self.output.write_all(&self.height.to_le_bytes())?;
+        self.base = Some(base);
+        Ok(base)
+    }
+
+    /// Write the next row of the frame, top first; it must be `width` cells long. Cells without a
+    /// foreground are white.
+    pub fn write_row(&mut self, cells: &[AsciiCell]) -> Result<()> {
+        if self.rows >= self.height {
+            return Err(Error::InvalidOptions(format!("the frame only has {} rows", self.height)));
+        }
+        if cells.len() != self.width as usize {
+            return Err(Error::InvalidOptions(format!("row {} has {} cells, the frame is {} wide", self.rows + 1, cells.len(), self.width)));
+        }
+        let base = self.start()?;
+        let (width, index, cell_bytes) = (self.width as u64, self.rows as u64, self.cell_bytes());
+        self.buffer.clear();
+        for cell in cells {
+            self.buffer.push(cell_bytes.byte(cell.ch));
+            self.buffer.extend(cell.fg.unwrap_or([255; 3]));
+        }
+        let at = base + 8 + index * width * 4;
+        if self.background {
+            self.output.seek(SeekFrom::Start(at))?;
+        }
+        self.output.write_all(&self.buffer)?;
+        if self.background {
+            self.buffer.clear();
+            self.buffer.extend(cells.iter().flat_map(|cell| cell.bg.unwrap_or([0; 3])));
+            self.output.seek(SeekFrom::Start(base + self.body_end() + index * width * 3))?;
+            self.output.write_all(&self.buffer)?;
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Write the extension flags once every row is written, leaving `output` past the frame
+    pub fn finish(mut self) -> Result<W> {
+        if self.rows != self.height {
+            return Err(Error::InvalidOptions(format!("wrote {} of the frame's {} rows", self.rows, self.height)));
+        }
+        let base = self.start()?;
+        let flags = self.flags();
+        if flags != 0 {
+            let cells = self.width as u64 * self.height as u64;
+            self.output.seek(SeekFrom::Start(base + 8 + cells * 4))?;
+            self.output.write_all(&[flags])?;
+            if self.background {
+                self.output.seek(SeekFrom::Start(base + self.body_end() + cells * 3))?;
+            }
+        }
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+/// Writes the `.cframe` files of a conversion in its [`CframeFormat`], v2 files with the metadata
+/// of the conversion
+#[cfg(feature = "cli")]
+pub(crate) struct CframeEncoder {
+    format: CframeFormat,
+    metadata: CframeMetadata,
+}
+
+#[cfg(feature = "cli")]
+impl CframeEncoder {
+    /// The writer for frames converted with `sampling` at `font_ratio` from the ramp `ascii_chars`,
+    /// which the metadata leaves out for braille and block cells
+    pub(crate) fn new(sampling: crate::frame::Sampling, font_ratio: f32, ascii_chars: &[u8]) -> Self {
+        let charset = (sampling.char_mode == crate::CharMode::Ascii).then(|| ascii_chars.iter().map(|&byte| crate::codepage::ramp_char(byte)).collect());
+        Self {format: sampling.cframe_format, metadata: CframeMetadata {fps: sampling.fps, charset, font_ratio: Some(font_ratio)}}
+    }
+
+    /// The v1 `frame` as the file to write
+    pub(crate) fn encode(&self, frame: Vec<u8>) -> Result<Vec<u8>> {
+        match self.format {
+            CframeFormat::V1 => Ok(frame),
+            CframeFormat::V2 {compression} => encode_v2(&frame, compression, &self.metadata),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::encode_cframe;
+    use std::io::Cursor;
+
+    #[test]
+    fn v2_cframes_round_trip_and_reject_damage() {
+        let v1 = encode_cframe(3, 2, "abc\ndef\n", &[9; 18], Some(&[7; 18]));
+        let written = CframeMetadata::default().with_fps(30).with_charset(" .#").with_font_ratio(0.5);
+        for &compression in CframeCompression::ALL {
+            let v2 = encode_v2(&v1, compression, &written).unwrap();
+            assert!(is_v2(&v2) && !is_v2(&v1));
+            assert_eq!(decode(&v2).unwrap(), Cframe {format: CframeFormat::V2 {compression}, metadata: written.clone(), frame: Cow::Borrowed(&v1[..])});
+            assert_eq!(metadata(&v2).unwrap().as_ref(), Some(&written));
+            // Edits come back in the same container
+            assert_eq!(rewrap(&v2, v1.clone()).unwrap(), v2);
+        }
+        assert_eq!(decode(&v1).unwrap().format, CframeFormat::V1);
+        assert_eq!((metadata(&v1).unwrap(), rewrap(&v1, vec![1]).unwrap()), (None, vec![1]));
+
+        let v2 = encode_v2(&v1, *CframeCompression::ALL.last().unwrap(), &written).unwrap();
+        assert!(matches!(decode(&v2[..v2.len() - 10]), Err(Error::InvalidCframe(_))));
+        let mut future = v2.clone();
+        future[4] = 3;
+        assert!(decode(&future).unwrap_err().to_string().contains("unsupported cframe version 3"));
+        let mut unknown = v2;
+        unknown[5] = 9;
+        assert!(decode(&unknown).is_err());
+    }
+
+    #[test]
+    fn streamed_cframes_match_whole_frames() -> Result<()> {
+        let rgb: Vec<u8> = (0..18).collect();
+        let rows = |text: &str, bg: Option<&[u8]>| -> Vec<Vec<AsciiCell>> {
+            text.lines().enumerate().map(|(y, line)| line.chars().enumerate().map(|(x, ch)| {
+                let color = |colors: &[u8]| [colors[(y * 3 + x) * 3], colors[(y * 3 + x) * 3 + 1], colors[(y * 3 + x) * 3 + 2]];
+                AsciiCell {ch, fg: Some(color(&rgb)), bg: bg.map(color)}
+            }).collect()).collect()
+        };
+        let write = |writer: CframeWriter<Cursor<Vec<u8>>>, rows: &[Vec<AsciiCell>]| -> Result<Vec<u8>> {
+            let mut writer = writer;
+            for row in rows {
+                writer.write_row(row)?;
+            }
+            Ok(writer.finish()?.into_inner())
+        };
+        let read = |data: Vec<u8>| -> Result<Vec<Vec<AsciiCell>>> {CframeReader::new(Cursor::new(data))?.collect()};
+
+        let bg = [7; 18];
+        let cells = rows("abc\ndef\n", Some(&bg));
+        let v1 = write(CframeWriter::new(Cursor::new(Vec::new()), 3, 2).with_background(), &cells)?;
+        assert_eq!(v1, encode_cframe(3, 2, "abc\ndef\n", &rgb, Some(&bg)));
+        assert_eq!(read(v1.clone())?, cells);
+        let braille = rows("⠁⠂⠃\n⠄⠅⠆\n", None);
+        let written = write(CframeWriter::new(Cursor::new(Vec::new()), 3, 2).with_char_mode(CharMode::Braille), &braille)?;
+        assert_eq!(written, encode_cframe(3, 2, "⠁⠂⠃\n⠄⠅⠆\n", &rgb, None));
+        assert_eq!(read(written)?, braille);
+
+        // v2 files of either compression, and a legacy file ending in a bare background
+        let metadata = CframeMetadata::default().with_fps(24);
+        let v2 = write(CframeWriter::new(Cursor::new(Vec::new()), 3, 2).with_background().with_metadata(metadata.clone()), &cells)?;
+        assert_eq!(v2, encode_v2(&v1, CframeCompression::None, &metadata)?);
+        #[cfg(feature = "zlib")]
+        {
+            let zlib = CframeReader::new(Cursor::new(encode_v2(&v1, CframeCompression::Zlib, &metadata)?))?;
+            assert_eq!((zlib.format(), zlib.metadata(), zlib.has_background()), (CframeFormat::V2 {compression: CframeCompression::Zlib}, &metadata, true));
+            assert_eq!(zlib.collect::<Result<Vec<_>>>()?, cells);
+        }
+        #[cfg(not(feature = "zlib"))]
+        {
+            assert!(encode_v2(&v1, CframeCompression::Zlib, &metadata).is_err_and(|err| err.to_string().contains("`zlib` feature")));
+            let mut zlib = v2.clone();
+            zlib[5] = 1;
+            assert!(CframeReader::new(Cursor::new(zlib.clone())).is_err_and(|err| err.to_string().contains("`zlib` feature")));
+            assert!(decode(&zlib).is_err_and(|err| err.to_string().contains("`zlib` feature")));
+        }
+        let mut legacy = encode_cframe(3, 2, "abc\ndef\n", &rgb, None);
+        legacy.extend(bg);
+        assert_eq!(read(legacy)?, cells);
+
+        let mut short = CframeWriter::new(Cursor::new(Vec::new()), 3, 2);
+        assert!(short.write_row(&cells[0][..2]).is_err());
+        short.write_row(&cells[0])?;
+        assert!(short.finish().is_err());
+        assert!(CframeReader::new(Cursor::new(v1[..20].to_vec())).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+//! Character ramps fitted to the font they are displayed in.
+//!
+//! A ramp lists characters from the least to the most ink, and the conversion picks among them by
+//! luminance, so a ramp ordered for one font bands or inverts in another: the default ramp was
+//! tuned by eye for DejaVu Sans Mono. [`calibrate_from_font`] measures how much of its cell each
+//! glyph of a font actually covers and orders the ramp by that. [`analyze`] reports what is wrong
+//! with a ramp in a font, and [`build_from_font`] picks a ramp of any length from scratch.
+//!
+//! ```no_run
+//! use cascii::charset::calibrate_from_font;
+//! use cascii::ConversionOptions;
+//!
+//! # fn main() -> cascii::Result<()> {
+//! let font = std::fs::read("/usr/share/fonts/truetype/ubuntu/UbuntuMono-R.ttf")?;
+//! let options = ConversionOptions::default();
+//! let options = ConversionOptions {ascii_chars: calibrate_from_font(&font, &options.ascii_chars)?, ..options};
+//! # Ok(())
+//! # }
+//! ```
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use crate::error::{Error, Result};
+
+/// Pixel size glyphs are rasterized at to measure them
+const MEASURE_SIZE: f32 = 64.0;
+
+/// Coverage difference below which two glyphs count as equally dense
+const SAME_DENSITY: f32 = 1e-3;
+
+/// Ink a character set measured in one font.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlyphDensity {
+    pub ch: char,
+    /// Share of the character cell the glyph covers, from 0.0 to 1.0, or `None` when the font has no glyph for it
+    pub density: Option<f32>,
+}
+
+/// What [`analyze`] found in a ramp.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RampAnalysis {
+    /// Every character of the ramp in order, with its measured ink
+    pub glyphs: Vec<GlyphDensity>,
+    /// Positions in `glyphs` of characters with less ink than one before them, where the ramp runs backwards
+    pub out_of_order: Vec<usize>,
+    /// Positions in `glyphs` of characters measuring the same as an earlier one; removing them loses no level
+    pub duplicates: Vec<usize>,
+    /// The ramp reordered by measured ink, without duplicates or missing glyphs (see [`calibrate_from_font`])
+    pub suggested: String,
+}
+
+impl RampAnalysis {
+    /// Whether the ramp already climbs strictly through the font's densities
+    pub fn is_monotonic(&self) -> bool {
+        self.out_of_order.is_empty() && self.duplicates.is_empty() && self.glyphs.iter().all(|glyph| glyph.density.is_some())
+    }
+}
+
+/// A font ready to measure glyphs in.
+struct InkMeter<'a> {
+    font: FontRef<'a>,
+    scale: PxScale,
+    cell_area: f32,
+}
+
+impl<'a> InkMeter<'a> {
+    fn new(font_bytes: &'a [u8]) -> Result<Self> {
+        let font = FontRef::try_from_slice(font_bytes).map_err(|error| Error::Other(format!("failed to load font: {error}")))?;
+        let scale = PxScale::from(MEASURE_SIZE);
+        let scaled_font = font.as_scaled(scale);
+        let cell_area = scaled_font.h_advance(font.glyph_id('M')) * (scaled_font.ascent() - scaled_font.descent());
+        if cell_area <= 0.0 {
+            return Err(Error::Other("the font has an empty character cell".into()));
+        }
+        Ok(Self {font, scale, cell_area})
+    }
+
+    /// Share of the cell `ch` covers, or `None` when the font lacks it (a space never is)
+    fn density(&self, ch: char) -> Option<f32> {
+        let glyph_id = self.font.glyph_id(ch);
+        if glyph_id.0 == 0 && ch != ' ' {
+            return None;
+        }
+        let mut ink = 0.0f32;
+        if let Some(outlined) = self.font.outline_glyph(glyph_id.with_scale(self.scale)) {
+            outlined.draw(|_, _, coverage| ink += coverage);
+        }
+        Some(ink / self.cell_area)
+    }
+
+    /// `chars` the font has, once each, ordered by ink, dropping every character measuring the same as one before it
+    fn levels(&self, chars: impl Iterator<Item = char>) -> Vec<(char, f32)> {
+        let mut measured: Vec<(char, f32)> = Vec::new();
+        for ch in chars {
+            if measured.iter().any(|(seen, _)| *seen == ch) {
+                continue;
+            }
+            if let Some(density) = self.density(ch) {
+                measured.push((ch, density));
+            }
+        }
+        // Stable, so equally dense characters keep the order they were given in
+        measured.sort_by(|left, right| left.1.total_cmp(&right.1));
+        let mut levels: Vec<(char, f32)> = Vec::with_capacity(measured.len());
+        for (ch, density) in measured {
+            if levels.last().is_none_or(|(_, last)| density - last >= SAME_DENSITY) {
+                levels.push((ch, density));
+            }
+        }
+        levels
+    }
+}
+
+fn check_ascii(chars: &str) -> Result<()> {
+    if chars.is_empty() || !chars.is_ascii() {
+        return Err(Error::InvalidOptions("the characters must be non-empty ASCII".into()));
+    }
+    Ok(())
+}
+
+/// The ASCII characters of `chars` ordered from the least to the most ink in the font `font_bytes`
+/// (TrueType or OpenType), measured as the share of a character cell each glyph covers.
+///
+/// Characters the font has no glyph for are dropped, as is every character measuring the same as
+/// one before it, since the conversion could never tell them apart. A space is always kept.
+pub fn calibrate_from_font(font_bytes: &[u8], chars: &str) -> Result<String> {
+    check_ascii(chars)?;
+    Ok(InkMeter::new(font_bytes)?.levels(chars.chars()).into_iter().map(|(ch, _)| ch).collect())
+}
+
+/// Measure every character of `ramp` in the font `font_bytes` and point out what keeps it from
+/// climbing evenly: characters out of order, characters adding no level, and glyphs the font lacks.
+pub fn analyze(ramp: &str, font_bytes: &[u8]) -> Result<RampAnalysis> {
+    check_ascii(ramp)?;
+    let meter = InkMeter::new(font_bytes)?;
+    let glyphs: Vec<GlyphDensity> = ramp.chars().map(|ch| GlyphDensity {ch, density: meter.density(ch)}).collect();
+    let (mut out_of_order, mut duplicates) = (Vec::new(), Vec::new());
+    let mut densest: Option<f32> = None;
+    for (index, glyph) in glyphs.iter().enumerate() {
+        let Some(density) = glyph.density else {continue};
+        if glyphs[..index].iter().any(|earlier| earlier.density.is_some_and(|earlier| (earlier - density).abs() < SAME_DENSITY)) {
+            duplicates.push(index);
+        } else if densest.is_some_and(|densest| density < densest) {
+            out_of_order.push(index);
+        }
+        densest = Some(densest.map_or(density, |densest| densest.max(density)));
+    }
+    let suggested = meter.levels(ramp.chars()).into_iter().map(|(ch, _)| ch).collect();
+    Ok(RampAnalysis {glyphs, out_of_order, duplicates, suggested})
+}
+
+/// The `n_levels` printable ASCII characters of the font `font_bytes` whose ink comes closest to
+/// `n_levels` evenly spaced steps from a space to the densest glyph, ordered as a ramp.
+pub fn build_from_font(font_bytes: &[u8], n_levels: usize) -> Result<String> {
+    let levels = InkMeter::new(font_bytes)?.levels((' '..='~').filter(char::is_ascii_graphic).chain([' ']));
+    if n_levels < 2 || n_levels > levels.len() {
+        return Err(Error::InvalidOptions(format!("the font has {} distinct ink levels; ask for 2 to {}", levels.len(), levels.len())));
+    }
+    // cost[k][j]: least squared distance from the targets for levels 0..=k with level k at
+    // candidate j; the ends are pinned to the lightest (a space) and the densest candidates
+    let densest = levels[levels.len() - 1].1;
+    let target = |k: usize| densest * k as f32 / (n_levels - 1) as f32;
+    let mut cost = vec![vec![f32::INFINITY; levels.len()]; n_levels];
+    let mut from = vec![vec![0usize; levels.len()]; n_levels];
+    cost[0][0] = (levels[0].1 - target(0)).powi(2);
+    for k in 1..n_levels {
+        let mut best = (f32::INFINITY, 0);
+        for j in k..levels.len() {
+            if cost[k - 1][j - 1] < best.0 {
+                best = (cost[k - 1][j - 1], j - 1);
+            }
+            cost[k][j] = best.0 + (levels[j].1 - target(k)).powi(2);
+            from[k][j] = best.1;
+        }
+    }
+    let mut picked = vec![levels.len() - 1];
+    for k in (1..n_levels).rev() {
+        picked.push(from[k][picked[picked.len() - 1]]);
+    }
+    Ok(picked.into_iter().rev().map(|index| levels[index].0).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT: &[u8] = include_bytes!("../resources/DejaVuSansMono.ttf");
+
+    #[test]
+    fn test_calibrate_from_font_orders_by_ink() {
+        assert_eq!(calibrate_from_font(FONT, "@#. -").unwrap(), " .-#@");
+        let ramp = calibrate_from_font(FONT, "ll|!").unwrap();
+        assert_eq!((ramp.matches('l').count(), ramp.len()), (1, 3));
+        assert!(calibrate_from_font(b"not a font", " .#").is_err());
+        assert!(calibrate_from_font(FONT, "").is_err());
+    }
+
+    #[test]
+    fn test_analyze_and_build_ramps() {
+        let analysis = analyze(" #.l@l", FONT).unwrap();
+        assert_eq!(analysis.glyphs.len(), 6);
+        assert_eq!((&analysis.out_of_order[..], &analysis.duplicates[..]), (&[2, 3][..], &[5][..]));
+        assert_eq!(analysis.suggested, " .l#@");
+        assert!(!analysis.is_monotonic() && analyze(&analysis.suggested, FONT).unwrap().is_monotonic());
+
+        let ramp = build_from_font(FONT, 8).unwrap();
+        assert_eq!(ramp.len(), 8);
+        assert!(ramp.starts_with(' ') && analyze(&ramp, FONT).unwrap().is_monotonic(), "{:?}", ramp);
+        assert!(build_from_font(FONT, 1).is_err() && build_from_font(FONT, 500).is_err());
+    }
+}
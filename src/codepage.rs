@@ -0,0 +1,167 @@
+//! Code pages for retro targets: CP437, the character set of the IBM PC text modes.
+//!
+//! DOS machines, BBS terminals and scene art viewers show one byte per cell from code page 437. It
+//! has the shades `░▒▓█`, half blocks and box drawing, but no braille or quadrant blocks.
+//! [`Codepage::Cp437`] (see [`ConversionOptions::codepage`](crate::ConversionOptions::codepage))
+//! keeps a conversion within it: braille cells become the shade of as many dots, quadrant blocks
+//! the nearest half block, and `.ans` output is written one CP437 byte per cell instead of UTF-8.
+//!
+//! Ramps may use any CP437 character whatever the code page, so `" .:-=░▒▓█"` ([`CP437_RAMP`])
+//! works as `ascii_chars` as well as plain ASCII does.
+//!
+//! ```
+//! use cascii::codepage::Codepage;
+//!
+//! assert_eq!(Codepage::Cp437.restrict('⣿'), '█');
+//! assert_eq!(Codepage::Cp437.encode("░▒▓█\n").unwrap(), b"\xb0\xb1\xb2\xdb\n");
+//! assert_eq!(Codepage::Cp437.decode(b"\xc9\xcd\xbb"), "╔═╗");
+//! assert!(Codepage::Cp437.encode("⠿").is_err());
+//! ```
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// A ramp running from blank through ASCII punctuation into the CP437 shades
+pub const CP437_RAMP: &str = " .:-=░▒▓█";
+
+/// The characters of CP437 bytes 0x80 to 0xFF
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// The characters a conversion may write, and how text files are encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codepage {
+    /// Any character; files are UTF-8
+    #[default]
+    Unicode,
+    /// The 256 characters of code page 437; `.ans` files hold one CP437 byte per cell
+    Cp437,
+}
+
+impl Codepage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Unicode => "unicode",
+            Self::Cp437 => "cp437",
+        }
+    }
+
+    /// Whether the code page has `ch`
+    pub fn contains(self, ch: char) -> bool {
+        match self {
+            Self::Unicode => true,
+            Self::Cp437 => cp437_byte(ch).is_some(),
+        }
+    }
+
+    /// `ch`, or the closest character the code page has: braille cells become the shade of their
+    /// share of raised dots, quadrant blocks and sextants the half block or shade they look most
+    /// like, anything else `?`
+    pub fn restrict(self, ch: char) -> char {
+        if self.contains(ch) {
+            return ch;
+        }
+        match ch as u32 {
+            0x2800..=0x28FF => [' ', '░', '░', '▒', '▒', '▒', '▓', '▓', '█'][(ch as u32 - 0x2800).count_ones() as usize],
+            0x1FB00..=0x1FB3B => match crate::frame::sextant_mask(ch).unwrap_or_default() {
+                // The top or bottom two rows
+                0b00_1111 => '▀',
+                0b11_1100 => '▄',
+                mask => [' ', '░', '░', '▒', '▓', '▓', '█'][mask.count_ones() as usize],
+            },
+            _ => match ch {
+                '▘' | '▝' => '▀',
+                '▖' | '▗' => '▄',
+                '▚' | '▞' => '▒',
+                '▛' | '▜' | '▙' | '▟' => '█',
+                _ => '?',
+            },
+        }
+    }
+
+    /// `text` in the code page's bytes; fails on the first character it lacks
+    pub fn encode(self, text: &str) -> Result<Vec<u8>> {
+        match self {
+            Self::Unicode => Ok(text.as_bytes().to_vec()),
+            Self::Cp437 => text.chars().map(|ch| cp437_byte(ch).ok_or_else(|| Error::InvalidOptions(format!("'{}' (U+{:04X}) has no glyph in code page 437", ch, ch as u32)))).collect(),
+        }
+    }
+
+    /// `text` in the code page's bytes, with [`restrict`](Self::restrict)'s stand-ins for the characters it lacks
+    pub fn encode_lossy(self, text: &str) -> Vec<u8> {
+        match self {
+            Self::Unicode => text.as_bytes().to_vec(),
+            Self::Cp437 => text.chars().map(|ch| cp437_byte(self.restrict(ch)).unwrap_or(b'?')).collect(),
+        }
+    }
+
+    /// The text `bytes` encode; invalid UTF-8 becomes U+FFFD
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Unicode => String::from_utf8_lossy(bytes).into_owned(),
+            Self::Cp437 => bytes.iter().map(|&byte| cp437_char(byte)).collect(),
+        }
+    }
+}
+
+/// The CP437 byte showing `ch`: ASCII as itself, the rest from the upper half of the code page
+pub fn cp437_byte(ch: char) -> Option<u8> {
+    if ch.is_ascii() {
+        return Some(ch as u8);
+    }
+    CP437_HIGH.iter().position(|&glyph| glyph == ch).map(|index| 0x80 + index as u8)
+}
+
+/// The character CP437 `byte` shows; bytes below 0x80 are ASCII
+pub fn cp437_char(byte: u8) -> char {
+    if byte < 0x80 {byte as char} else {CP437_HIGH[byte as usize - 0x80]}
+}
+
+/// A ramp as the one byte per character the converters index: ASCII as is, other characters as
+/// their CP437 bytes and `?` without one. [`ramp_char`] turns a byte back.
+pub(crate) fn ramp_bytes(ramp: &str) -> Cow<'_, [u8]> {
+    if ramp.is_ascii() {
+        Cow::Borrowed(ramp.as_bytes())
+    } else {
+        Cow::Owned(ramp.chars().map(|ch| cp437_byte(ch).unwrap_or(b'?')).collect())
+    }
+}
+
+/// The character of a [`ramp_bytes`] byte
+pub(crate) fn ramp_char(byte: u8) -> char {
+    cp437_char(byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cp437_round_trips_and_restricts_what_it_lacks() {
+        let all: Vec<u8> = (0x20..=0xFF).filter(|&byte| byte != 0x7F).collect();
+        let text = Codepage::Cp437.decode(&all);
+        assert_eq!(text.chars().count(), all.len());
+        assert_eq!(Codepage::Cp437.encode(&text).unwrap(), all);
+        assert_eq!((cp437_char(0xB0), cp437_byte('█'), cp437_byte('é'), cp437_byte('€')), ('░', Some(0xDB), Some(0x82), None));
+
+        assert_eq!("⠀⠁⠛⣿▘▗▚▛\u{1fb0e}\u{1fb39}\u{1fb0b}€#".chars().map(|ch| Codepage::Cp437.restrict(ch)).collect::<String>(), " ░▒█▀▄▒█▀▄░?#");
+        assert_eq!(Codepage::Unicode.restrict('⠛'), '⠛');
+        assert_eq!(Codepage::Cp437.encode_lossy("a⠿\n"), b"a\xb2\n");
+        assert!(Codepage::Cp437.encode("a€").is_err());
+
+        assert_eq!(ramp_bytes(" .#"), Cow::Borrowed(&b" .#"[..]));
+        assert_eq!(ramp_bytes(CP437_RAMP).iter().map(|&byte| ramp_char(byte)).collect::<String>(), CP437_RAMP);
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{anyhow, Result};
+use crate::error::{Error, Result};
 
 const HEADER_SIZE: usize = 8;
 const CELL_SIZE: usize = 4;
@@ -35,10 +35,10 @@ impl ColorShift {
 
 pub fn shift_rgb_triplets(rgb: &mut [u8], degrees: f32) -> Result<()> {
     if !rgb.len().is_multiple_of(RGB_SIZE) {
-        return Err(anyhow!("RGB payload length must be divisible by 3, got {}", rgb.len()));
+        return Err(Error::InvalidCframe(format!("RGB payload length must be divisible by 3, got {}", rgb.len())));
     }
     if !degrees.is_finite() {
-        return Err(anyhow!("hue shift must be finite"));
+        return Err(Error::InvalidOptions("hue shift must be finite".into()));
     }
 
     for color in rgb.chunks_exact_mut(RGB_SIZE) {
@@ -48,32 +48,34 @@ pub fn shift_rgb_triplets(rgb: &mut [u8], degrees: f32) -> Result<()> {
     Ok(())
 }
 
-pub fn shift_cframe_bytes(data: &[u8], shift: ColorShift) -> Result<Vec<u8>> {
+/// `data` with its colors hue-shifted; v2 files come back in their container
+pub fn shift_cframe_bytes(original: &[u8], shift: ColorShift) -> Result<Vec<u8>> {
+    let data = &crate::cframe::frame_bytes(original)?[..];
     if data.len() < HEADER_SIZE {
-        return Err(anyhow!("cframe file too small"));
+        return Err(Error::InvalidCframe("cframe file too small".into()));
     }
 
     let width = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
     let height = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
     if width == 0 || height == 0 {
-        return Err(anyhow!("cframe dimensions must be non-zero"));
+        return Err(Error::InvalidCframe("cframe dimensions must be non-zero".into()));
     }
     if width == 0 || height == 0 {
-        return Err(anyhow!("cframe dimensions must be non-zero"));
+        return Err(Error::InvalidCframe("cframe dimensions must be non-zero".into()));
     }
 
-    let cell_count = width.checked_mul(height).ok_or_else(|| anyhow!("cframe dimensions overflow"))?;
-    let body_len = cell_count.checked_mul(CELL_SIZE).ok_or_else(|| anyhow!("cframe body size overflow"))?;
-    let body_end = HEADER_SIZE.checked_add(body_len).ok_or_else(|| anyhow!("cframe body offset overflow"))?;
+    let cell_count = width.checked_mul(height).ok_or_else(|| Error::InvalidCframe("cframe dimensions overflow".into()))?;
+    let body_len = cell_count.checked_mul(CELL_SIZE).ok_or_else(|| Error::InvalidCframe("cframe body size overflow".into()))?;
+    let body_end = HEADER_SIZE.checked_add(body_len).ok_or_else(|| Error::InvalidCframe("cframe body offset overflow".into()))?;
     if data.len() < body_end {
-        return Err(anyhow!("cframe file truncated: expected at least {} bytes, got {}", body_end, data.len()));
+        return Err(Error::InvalidCframe(format!("cframe file truncated: expected at least {} bytes, got {}", body_end, data.len())));
     }
 
     let mut output = data.to_vec();
 
     if matches!(shift.target, ColorShiftTarget::Foreground | ColorShiftTarget::Both) {
         if !shift.foreground_degrees.is_finite() {
-            return Err(anyhow!("foreground hue shift must be finite"));
+            return Err(Error::InvalidOptions("foreground hue shift must be finite".into()));
         }
         for cell in 0..cell_count {
             let offset = HEADER_SIZE + cell * CELL_SIZE + 1;
@@ -84,29 +86,30 @@ pub fn shift_cframe_bytes(data: &[u8], shift: ColorShift) -> Result<Vec<u8>> {
 
     if matches!(shift.target, ColorShiftTarget::Background | ColorShiftTarget::Both) {
         if !shift.background_degrees.is_finite() {
-            return Err(anyhow!("background hue shift must be finite"));
+            return Err(Error::InvalidOptions("background hue shift must be finite".into()));
         }
-        let background_len = cell_count.checked_mul(RGB_SIZE).ok_or_else(|| anyhow!("cframe background size overflow"))?;
+        let background_len = cell_count.checked_mul(RGB_SIZE).ok_or_else(|| Error::InvalidCframe("cframe background size overflow".into()))?;
         if let Some(background_start) = background_payload_start(&output, body_end, background_len) {
             shift_rgb_triplets(&mut output[background_start..background_start + background_len], shift.background_degrees)?;
         }
     }
 
-    Ok(output)
+    crate::cframe::rewrap(original, output)
 }
 
 pub fn cframe_has_background(data: &[u8]) -> Result<bool> {
+    let data = &crate::cframe::frame_bytes(data)?[..];
     if data.len() < HEADER_SIZE {
-        return Err(anyhow!("cframe file too small"));
+        return Err(Error::InvalidCframe("cframe file too small".into()));
     }
     let width = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
     let height = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
-    let cell_count = width.checked_mul(height).ok_or_else(|| anyhow!("cframe dimensions overflow"))?;
-    let body_end = HEADER_SIZE.checked_add(cell_count.checked_mul(CELL_SIZE).ok_or_else(|| anyhow!("cframe body size overflow"))?).ok_or_else(|| anyhow!("cframe body offset overflow"))?;
+    let cell_count = width.checked_mul(height).ok_or_else(|| Error::InvalidCframe("cframe dimensions overflow".into()))?;
+    let body_end = HEADER_SIZE.checked_add(cell_count.checked_mul(CELL_SIZE).ok_or_else(|| Error::InvalidCframe("cframe body size overflow".into()))?).ok_or_else(|| Error::InvalidCframe("cframe body offset overflow".into()))?;
     if data.len() < body_end {
-        return Err(anyhow!("cframe file truncated"));
+        return Err(Error::InvalidCframe("cframe file truncated".into()));
     }
-    let background_len = cell_count.checked_mul(RGB_SIZE).ok_or_else(|| anyhow!("cframe background size overflow"))?;
+    let background_len = cell_count.checked_mul(RGB_SIZE).ok_or_else(|| Error::InvalidCframe("cframe background size overflow".into()))?;
     Ok(background_payload_start(data, body_end, background_len).is_some())
 }
 
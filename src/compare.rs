@@ -0,0 +1,176 @@
+//! A/B comparisons of two option sets on the same sample frames.
+//!
+//! [`AsciiConverter::compare_conversions`](crate::AsciiConverter::compare_conversions) converts the
+//! sample frames a dry run would measure with both option sets. Each converted frame is drawn with
+//! the embedded font, as `--to-video` draws it, and scored against its source frame, so the scores
+//! say how much of the picture each option set keeps rather than how busy its characters look:
+//!
+//! - PSNR of the luminance, in dB; higher is closer
+//! - SSIM of the luminance over 8×8 pixel windows, from 0.0 to 1.0; higher keeps more structure
+//! - How many distinct characters the frame uses, to spot ramps that collapse into a few glyphs
+
+use crate::error::{Error, Result};
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage, RgbImage};
+
+use crate::render::{build_glyph_atlas, render_frame_with_atlas};
+use crate::{frame_to_ansi_string, AnsiColor, AsciiFrame};
+
+/// Width the source and the drawn frames are scaled to before scoring, so frames of different
+/// column counts are scored at the same resolution
+pub const METRICS_WIDTH: u32 = 640;
+
+/// Font size frames are drawn at for scoring and side-by-side images
+pub const COMPARE_FONT_SIZE: f32 = 12.0;
+
+/// Side of the square windows SSIM is averaged over
+const SSIM_WINDOW: u32 = 8;
+
+/// How close one converted frame comes to its source.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityMetrics {
+    /// Peak signal-to-noise ratio of the luminance, in dB (infinite for an exact match)
+    pub psnr: f64,
+    /// Mean structural similarity of the luminance, from 0.0 to 1.0
+    pub ssim: f64,
+    /// Distinct characters in the frame, spaces included
+    pub distinct_chars: usize,
+}
+
+impl QualityMetrics {
+    /// Score `frame` against `source`, drawing it with colors when `colors` is set (white on black otherwise)
+    pub fn measure(source: &DynamicImage, frame: &AsciiFrame, colors: bool) -> Result<Self> {
+        let mut atlas = build_glyph_atlas(COMPARE_FONT_SIZE)?;
+        atlas.add_chars(&frame.text)?;
+        let drawn = render_frame_with_atlas(frame, &atlas, colors)?;
+        let height = ((source.height() as u64 * METRICS_WIDTH as u64) / source.width().max(1) as u64).max(1) as u32;
+        let reference = source.resize_exact(METRICS_WIDTH, height, FilterType::Triangle).to_luma8();
+        let drawn = DynamicImage::ImageRgb8(drawn).resize_exact(METRICS_WIDTH, height, FilterType::Triangle).to_luma8();
+        let mut chars: Vec<char> = frame.text.chars().filter(|&ch| ch != '\n').collect();
+        chars.sort_unstable();
+        chars.dedup();
+        Ok(Self {psnr: psnr(&reference, &drawn), ssim: ssim(&reference, &drawn), distinct_chars: chars.len()})
+    }
+
+    /// Mean of every field over `metrics`; `None` when empty
+    pub fn mean(metrics: &[Self]) -> Option<Self> {
+        let count = metrics.len();
+        (count > 0).then(|| Self {psnr: metrics.iter().map(|m| m.psnr).sum::<f64>() / count as f64, ssim: metrics.iter().map(|m| m.ssim).sum::<f64>() / count as f64, distinct_chars: metrics.iter().map(|m| m.distinct_chars).sum::<usize>() / count})
+    }
+}
+
+/// One sample frame converted with both option sets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameComparison {
+    pub left: AsciiFrame,
+    pub right: AsciiFrame,
+    pub left_metrics: QualityMetrics,
+    pub right_metrics: QualityMetrics,
+}
+
+impl FrameComparison {
+    /// Both frames as ANSI rows next to each other, the left one padded to its width
+    pub fn side_by_side_ansi(&self, colors: AnsiColor) -> String {
+        let (left, right) = (frame_to_ansi_string(&self.left, colors), frame_to_ansi_string(&self.right, colors));
+        let (mut left_rows, mut right_rows) = (left.lines(), right.lines());
+        let blank = " ".repeat(self.left.width as usize);
+        let mut out = String::with_capacity(left.len() + right.len());
+        for _ in 0..self.left.height.max(self.right.height) {
+            out.push_str(left_rows.next().unwrap_or(&blank));
+            out.push_str("\x1b[0m │ ");
+            out.push_str(right_rows.next().unwrap_or(""));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Both frames drawn with the embedded font, next to each other on black with a gray divider
+    pub fn side_by_side_image(&self, colors: bool) -> Result<RgbImage> {
+        let mut atlas = build_glyph_atlas(COMPARE_FONT_SIZE)?;
+        atlas.add_chars(&self.left.text)?;
+        atlas.add_chars(&self.right.text)?;
+        let (left, right) = (render_frame_with_atlas(&self.left, &atlas, colors)?, render_frame_with_atlas(&self.right, &atlas, colors)?);
+        let gap = atlas.cell_width;
+        let mut image = RgbImage::new(left.width() + gap + right.width(), left.height().max(right.height()));
+        for y in 0..image.height() {
+            image.put_pixel(left.width() + gap / 2, y, image::Rgb([128; 3]));
+        }
+        image::imageops::replace(&mut image, &left, 0, 0);
+        image::imageops::replace(&mut image, &right, (left.width() + gap) as i64, 0);
+        Ok(image)
+    }
+}
+
+/// Every sample frame converted with both option sets, from
+/// [`AsciiConverter::compare_conversions`](crate::AsciiConverter::compare_conversions).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionComparison {
+    pub frames: Vec<FrameComparison>,
+}
+
+impl ConversionComparison {
+    /// Mean metrics of the left and the right option set over the sample frames
+    pub fn mean_metrics(&self) -> Result<(QualityMetrics, QualityMetrics)> {
+        let left: Vec<QualityMetrics> = self.frames.iter().map(|frame| frame.left_metrics).collect();
+        let right: Vec<QualityMetrics> = self.frames.iter().map(|frame| frame.right_metrics).collect();
+        QualityMetrics::mean(&left).zip(QualityMetrics::mean(&right)).ok_or_else(|| Error::NotFound("no frames were compared".into()))
+    }
+}
+
+fn psnr(reference: &GrayImage, drawn: &GrayImage) -> f64 {
+    let squared: f64 = reference.as_raw().iter().zip(drawn.as_raw()).map(|(&a, &b)| (a as f64 - b as f64).powi(2)).sum();
+    let mse = squared / reference.as_raw().len().max(1) as f64;
+    if mse == 0.0 {f64::INFINITY} else {10.0 * (255.0 * 255.0 / mse).log10()}
+}
+
+fn ssim(reference: &GrayImage, drawn: &GrayImage) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (width, height) = reference.dimensions();
+    let (mut total, mut windows) = (0.0, 0usize);
+    for top in (0..height).step_by(SSIM_WINDOW as usize) {
+        for left in (0..width).step_by(SSIM_WINDOW as usize) {
+            let pixels: Vec<(f64, f64)> = (top..(top + SSIM_WINDOW).min(height)).flat_map(|y| (left..(left + SSIM_WINDOW).min(width)).map(move |x| (y, x))).map(|(y, x)| (reference.get_pixel(x, y)[0] as f64, drawn.get_pixel(x, y)[0] as f64)).collect();
+            let count = pixels.len() as f64;
+            let (mean_a, mean_b) = (pixels.iter().map(|p| p.0).sum::<f64>() / count, pixels.iter().map(|p| p.1).sum::<f64>() / count);
+            let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+            for (a, b) in &pixels {
+                var_a += (a - mean_a).powi(2);
+                var_b += (b - mean_b).powi(2);
+                covariance += (a - mean_a) * (b - mean_b);
+            }
+            let (var_a, var_b, covariance) = (var_a / count, var_b / count, covariance / count);
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2)) / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    if windows == 0 {1.0} else {total / windows as f64}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AsciiConverter, ConversionOptions, VideoOptions};
+
+    #[test]
+    fn test_compare_conversions_scores_both_option_sets() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = crate::test_pattern::generate_test_pattern(crate::test_pattern::TestPattern::Gradient, 160, 80);
+        image.save(dir.path().join("frame_0001.png")).unwrap();
+
+        let options = ConversionOptions::default().with_columns(40).with_luminance(0);
+        let blank = options.clone().with_ascii_chars(" ".to_string());
+        let comparison = AsciiConverter::new().compare_conversions(dir.path(), &VideoOptions::default(), &options, &blank).unwrap();
+        assert_eq!(comparison.frames.len(), 1);
+        let (ramp, spaces) = comparison.mean_metrics().unwrap();
+        assert!(ramp.ssim > spaces.ssim && ramp.psnr > spaces.psnr, "{:?} vs {:?}", ramp, spaces);
+        assert_eq!(spaces.distinct_chars, 1);
+
+        let frame = &comparison.frames[0];
+        let rows = frame.side_by_side_ansi(AnsiColor::TrueColor);
+        assert_eq!(rows.lines().count(), frame.left.height as usize);
+        assert!(rows.lines().all(|row| row.contains(" │ ")));
+        let image = frame.side_by_side_image(false).unwrap();
+        assert!(image.width() > 2 * render_frame_with_atlas(&frame.left, &build_glyph_atlas(COMPARE_FONT_SIZE).unwrap(), false).unwrap().width());
+    }
+}
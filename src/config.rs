@@ -0,0 +1,438 @@
+//! Presets, profiles and the JSON configuration file they are read from.
+
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use crate::error::{Error, Result};
+#[cfg(feature = "cli")]
+use crate::preprocessing;
+use crate::{Attribution, ConversionOptions, OutputMode, ToVideoOptions, VideoOptions};
+
+/// Configuration preset defining quality settings
+#[derive(Debug, Deserialize, Clone)]
+pub struct Preset {
+    pub columns: u32,
+    pub fps: u32,
+    pub font_ratio: f32,
+    pub luminance: u8,
+    /// Output files to generate; `None` leaves the choice to the caller (text-only by default).
+    #[serde(default)]
+    pub output_mode: Option<OutputMode>,
+    /// Character ramp overriding the config-wide `ascii_chars`.
+    #[serde(default)]
+    pub ascii_chars: Option<String>,
+    /// Raw ffmpeg filtergraph applied before conversion (like `--preprocess`).
+    #[serde(default)]
+    pub preprocess: Option<String>,
+    /// Built-in preprocessing preset name (like `--preprocess-preset`).
+    #[serde(default)]
+    pub preprocess_preset: Option<String>,
+}
+
+/// Size, frame rate and length of a source, used to [suggest a preset](AppConfig::suggest_preset).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MediaInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Frames per second; `None` for still images or when the container doesn't say
+    pub fps: Option<f64>,
+    /// Length in seconds; `None` for still images
+    pub duration: Option<f64>,
+}
+
+impl MediaInfo {
+    /// A still image of `width`×`height` pixels
+    pub fn image(width: u32, height: u32) -> Self {
+        Self {width, height, fps: None, duration: None}
+    }
+
+    /// Number of frames the source holds (1 for a still image)
+    pub fn frame_count(&self) -> f64 {
+        match self.duration {
+            Some(duration) => duration * self.fps.unwrap_or(30.0),
+            None => 1.0,
+        }
+    }
+}
+
+/// Sources whose shorter side is below this many pixels get the `small` preset
+const SMALL_SOURCE_MAX_SIDE: u32 = 480;
+/// Sources whose shorter side is at least this many pixels (4K) may get the `large` preset
+const LARGE_SOURCE_MIN_SIDE: u32 = 2160;
+/// ...as long as they hold at most this many frames (30 seconds at 60 fps)
+const LARGE_SOURCE_MAX_FRAMES: f64 = 1800.0;
+
+/// A preset as written in the config file: every field is optional and may be filled in from the
+/// preset named by `inherit`.
+#[derive(Debug, Deserialize, Clone, Default)]
+struct PresetSpec {
+    inherit: Option<String>,
+    columns: Option<u32>,
+    fps: Option<u32>,
+    font_ratio: Option<f32>,
+    luminance: Option<u8>,
+    output_mode: Option<OutputMode>,
+    ascii_chars: Option<String>,
+    preprocess: Option<String>,
+    preprocess_preset: Option<String>,
+}
+
+impl PresetSpec {
+    /// Fill every unset field from `parent`.
+    fn inherit_from(self, parent: &PresetSpec) -> PresetSpec {
+        PresetSpec {inherit: None, columns: self.columns.or(parent.columns), fps: self.fps.or(parent.fps), font_ratio: self.font_ratio.or(parent.font_ratio), luminance: self.luminance.or(parent.luminance), output_mode: self.output_mode.or_else(|| parent.output_mode.clone()), ascii_chars: self.ascii_chars.or_else(|| parent.ascii_chars.clone()), preprocess: self.preprocess.or_else(|| parent.preprocess.clone()), preprocess_preset: self.preprocess_preset.or_else(|| parent.preprocess_preset.clone())}
+    }
+
+    fn into_preset(self, name: &str) -> Result<Preset, String> {
+        let missing = |field: &str| format!("preset '{}' is missing '{}' (set it or inherit from a preset that does)", name, field);
+        if self.preprocess.is_some() && self.preprocess_preset.is_some() {
+            return Err(format!("preset '{}' sets both 'preprocess' and 'preprocess_preset'", name));
+        }
+        Ok(Preset {columns: self.columns.ok_or_else(|| missing("columns"))?, fps: self.fps.ok_or_else(|| missing("fps"))?, font_ratio: self.font_ratio.ok_or_else(|| missing("font_ratio"))?, luminance: self.luminance.ok_or_else(|| missing("luminance"))?, output_mode: self.output_mode, ascii_chars: self.ascii_chars, preprocess: self.preprocess, preprocess_preset: self.preprocess_preset})
+    }
+}
+
+/// Resolve `inherit` chains into fully specified presets, rejecting unknown parents and cycles.
+fn resolve_presets(specs: &std::collections::HashMap<String, PresetSpec>) -> Result<std::collections::HashMap<String, Preset>, String> {
+    fn resolve(name: &str, specs: &std::collections::HashMap<String, PresetSpec>, resolved: &mut std::collections::HashMap<String, PresetSpec>, chain: &mut Vec<String>) -> Result<PresetSpec, String> {
+        if let Some(done) = resolved.get(name) {
+            return Ok(done.clone());
+        }
+        if chain.iter().any(|n| n == name) {
+            chain.push(name.to_string());
+            return Err(format!("preset inheritance cycle: {}", chain.join(" -> ")));
+        }
+        let spec = specs.get(name).ok_or_else(|| format!("preset '{}' inherits from unknown preset '{}'", chain.last().map(String::as_str).unwrap_or(""), name))?;
+        let flattened = match spec.inherit.as_deref() {
+            Some(parent) => {
+                chain.push(name.to_string());
+                let parent = resolve(parent, specs, resolved, chain)?;
+                chain.pop();
+                spec.clone().inherit_from(&parent)
+            }
+            None => spec.clone(),
+        };
+        resolved.insert(name.to_string(), flattened.clone());
+        Ok(flattened)
+    }
+
+    let mut resolved = std::collections::HashMap::new();
+    let mut presets = std::collections::HashMap::new();
+    for name in specs.keys() {
+        let flattened = resolve(name, specs, &mut resolved, &mut Vec::new())?;
+        presets.insert(name.clone(), flattened.into_preset(name)?);
+    }
+    Ok(presets)
+}
+
+pub(crate) fn default_ascii_chars() -> String {
+    " .'`^,:;Il!i><~+_-?][}{1)(|/tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$".to_string()
+}
+
+fn default_start_str() -> String {
+    "0".to_string()
+}
+fn default_end_str() -> String {
+    String::new()
+}
+
+/// Application configuration with presets and ASCII character set
+///
+/// Presets may name a parent with `"inherit": "<preset>"` and only override the fields that differ;
+/// inheritance is resolved while parsing, so [`presets`](Self::presets) always holds complete presets.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "AppConfigSpec")]
+pub struct AppConfig {
+    pub presets: std::collections::HashMap<String, Preset>,
+    /// Destination profiles: the built-in ones plus any defined (or overridden) in the config file.
+    pub profiles: std::collections::HashMap<String, Profile>,
+    pub default_preset: String,
+    pub ascii_chars: String,
+    pub default_start: String,
+    pub default_end: String,
+    /// Creator and license stamped on every output: reports, video metadata and a corner overlay
+    pub attribution: Option<Attribution>,
+}
+
+impl AppConfig {
+    /// Name of the preset best suited to a source: `small` below 480p, `large` for 4K stills and clips
+    /// of at most 1800 frames, the default preset otherwise. Falls back to the default preset when
+    /// the config has no preset of the suggested name.
+    pub fn suggest_preset_name(&self, info: &MediaInfo) -> &str {
+        let side = info.width.min(info.height);
+        let name = if side < SMALL_SOURCE_MAX_SIDE {
+            "small"
+        } else if side >= LARGE_SOURCE_MIN_SIDE && info.frame_count() <= LARGE_SOURCE_MAX_FRAMES {
+            "large"
+        } else {
+            return &self.default_preset;
+        };
+        if self.presets.contains_key(name) {name} else {&self.default_preset}
+    }
+
+    /// The preset named by [`suggest_preset_name`](Self::suggest_preset_name), or `None` if the config
+    /// lacks its own default preset.
+    pub fn suggest_preset(&self, info: &MediaInfo) -> Option<&Preset> {
+        self.presets.get(self.suggest_preset_name(info))
+    }
+}
+
+/// On-disk shape of [`AppConfig`], before preset inheritance is resolved.
+#[derive(Debug, Deserialize)]
+struct AppConfigSpec {
+    presets: std::collections::HashMap<String, PresetSpec>,
+    #[serde(default)]
+    profiles: std::collections::HashMap<String, Profile>,
+    default_preset: String,
+    #[serde(default = "default_ascii_chars")]
+    ascii_chars: String,
+    #[serde(default = "default_start_str")]
+    default_start: String,
+    #[serde(default = "default_end_str")]
+    default_end: String,
+    #[serde(default)]
+    attribution: Option<Attribution>,
+}
+
+impl TryFrom<AppConfigSpec> for AppConfig {
+    type Error = String;
+
+    fn try_from(spec: AppConfigSpec) -> Result<Self, Self::Error> {
+        let presets = resolve_presets(&spec.presets)?;
+        let mut profiles = builtin_profiles();
+        profiles.extend(spec.profiles);
+        Ok(Self {presets, profiles, default_preset: spec.default_preset, ascii_chars: spec.ascii_chars, default_start: spec.default_start, default_end: spec.default_end, attribution: spec.attribution})
+    }
+}
+
+/// A named bundle of conversion, extraction and rendering settings tuned for one destination
+/// (e.g. `discord`, `tiktok`, `terminal-80col`).
+///
+/// A profile starts from a [`Preset`] (the config's default preset unless `preset` is set) and overrides
+/// any of its values; unset fields fall back to the preset or to the regular option defaults.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Profile {
+    /// Short human-readable description
+    #[serde(default)]
+    pub description: String,
+    /// Preset the profile builds on
+    #[serde(default)]
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub columns: Option<u32>,
+    #[serde(default)]
+    pub fps: Option<u32>,
+    #[serde(default)]
+    pub font_ratio: Option<f32>,
+    #[serde(default)]
+    pub luminance: Option<u8>,
+    #[serde(default)]
+    pub output_mode: Option<OutputMode>,
+    #[serde(default)]
+    pub ascii_chars: Option<String>,
+    /// Render an encoded video instead of frame files
+    #[serde(default)]
+    pub to_video: bool,
+    /// Extract (or mux, when rendering video) the source audio
+    #[serde(default)]
+    pub audio: bool,
+    /// Glyph size in pixels when rendering video
+    #[serde(default)]
+    pub font_size: Option<f32>,
+    /// H.264 CRF when rendering video
+    #[serde(default)]
+    pub crf: Option<u8>,
+}
+
+impl Profile {
+    /// The preset this profile builds on
+    pub fn base_preset<'a>(&self, config: &'a AppConfig) -> Result<&'a Preset> {
+        let name = self.preset.as_deref().unwrap_or(&config.default_preset);
+        config.presets.get(name).ok_or_else(|| Error::InvalidOptions(format!("profile references unknown preset '{}'", name)))
+    }
+
+    /// ASCII conversion options for this profile
+    pub fn conversion_options(&self, config: &AppConfig) -> Result<ConversionOptions> {
+        let mut options = ConversionOptions::from_preset(self.base_preset(config)?, config.ascii_chars.clone());
+        options.columns = self.columns.or(options.columns);
+        options.font_ratio = self.font_ratio.unwrap_or(options.font_ratio);
+        options.luminance = self.luminance.unwrap_or(options.luminance);
+        if let Some(mode) = &self.output_mode {
+            options.output_mode = mode.clone();
+        }
+        if let Some(chars) = &self.ascii_chars {
+            options.ascii_chars = chars.clone();
+        }
+        Ok(options)
+    }
+
+    /// Frame extraction options for this profile
+    pub fn video_options(&self, config: &AppConfig) -> Result<VideoOptions> {
+        let preset = self.base_preset(config)?;
+        let preprocess_filter = match (&preset.preprocess, &preset.preprocess_preset) {
+            (Some(filter), _) => Some(filter.clone()),
+            #[cfg(feature = "cli")]
+            (None, Some(name)) => preprocessing::find_preprocess_preset(name).map(|p| p.filter.to_string()),
+            _ => None,
+        };
+        Ok(VideoOptions {fps: self.fps.unwrap_or(preset.fps), columns: self.columns.unwrap_or(preset.columns), extract_audio: self.audio, preprocess_filter, ..VideoOptions::default()})
+    }
+
+    /// Video rendering options for this profile, writing to `output_path`
+    pub fn to_video_options<P: Into<PathBuf>>(&self, output_path: P) -> ToVideoOptions {
+        let defaults = ToVideoOptions::default();
+        let use_colors = self.output_mode.as_ref().map(|mode| *mode != OutputMode::TextOnly);
+        ToVideoOptions {output_path: output_path.into(), font_size: self.font_size.unwrap_or(defaults.font_size), crf: self.crf.unwrap_or(defaults.crf), mux_audio: self.audio, use_colors, ..defaults}
+    }
+}
+
+/// Profiles that are always available, even without a config file. They spell out every preset
+/// value instead of naming a preset, so they work with configs that define only their own presets.
+fn builtin_profiles() -> std::collections::HashMap<String, Profile> {
+    let json = r#"{
+        "discord":        {"description": "Small colored mp4 with audio that stays under Discord's upload limit", "columns": 160, "fps": 24, "font_ratio": 0.44, "luminance": 20, "output_mode": "text+color", "to_video": true, "audio": true, "font_size": 10.0, "crf": 28},
+        "tiktok":         {"description": "Colored mp4 with audio at a phone-friendly resolution", "columns": 120, "fps": 30, "font_ratio": 0.5, "output_mode": "text+color", "to_video": true, "audio": true, "font_size": 16.0, "crf": 20},
+        "terminal-80col": {"description": "Plain text frames that fit a classic 80-column terminal", "columns": 80, "fps": 24, "font_ratio": 0.5, "luminance": 20, "output_mode": "text-only"}
+    }"#;
+    serde_json::from_str(json).expect("built-in profiles are valid")
+}
+
+impl AppConfig {
+    /// Returns `true` when the global ramp and every preset ramp contain only ASCII characters.
+    pub fn uses_only_ascii_chars(&self) -> bool {
+        self.ascii_chars.is_ascii() && self.presets.values().all(|preset| preset.ascii_chars.as_deref().is_none_or(str::is_ascii)) && self.profiles.values().all(|profile| profile.ascii_chars.as_deref().is_none_or(str::is_ascii))
+    }
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        let default_json = r#"{
+            "presets": {
+                "default": {"columns": 400, "fps": 30, "font_ratio": 0.7, "luminance": 20},
+                "small":   {"columns": 80,  "fps": 24, "font_ratio": 0.44, "luminance": 20},
+                "large":   {"columns": 800, "fps": 60, "font_ratio": 0.7, "luminance": 20}
+            },
+            "default_preset": "default",
+            "ascii_chars": " .'`^,:;Il!i><~+_-?][}{1)(|/tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$",
+            "default_start": "0",
+            "default_end": ""
+        }"#;
+        serde_json::from_str(default_json).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_config(json: &str) -> Result<AppConfig, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn presets_inherit_unset_fields_from_parent() {
+        let cfg = parse_config(r#"{
+            "presets": {
+                "default": {"columns": 400, "fps": 30, "font_ratio": 0.7, "luminance": 20, "ascii_chars": " .:#"},
+                "color":   {"inherit": "default", "output_mode": "text+color"},
+                "narrow":  {"inherit": "color", "columns": 80, "preprocess_preset": "bg-white"}
+            },
+            "default_preset": "default"
+        }"#).unwrap();
+        let narrow = &cfg.presets["narrow"];
+        assert_eq!((narrow.columns, narrow.fps, narrow.luminance), (80, 30, 20));
+        assert_eq!(narrow.output_mode, Some(OutputMode::TextAndColor));
+        assert_eq!(narrow.ascii_chars.as_deref(), Some(" .:#"));
+        assert_eq!(narrow.preprocess_preset.as_deref(), Some("bg-white"));
+        assert_eq!(cfg.presets["default"].output_mode, None);
+
+        let options = ConversionOptions::from_preset(narrow, cfg.ascii_chars.clone());
+        assert_eq!(options.ascii_chars, " .:#");
+        assert_eq!(options.output_mode, OutputMode::TextAndColor);
+    }
+
+    #[test]
+    fn preset_inheritance_errors_are_reported() {
+        let cycle = parse_config(r#"{"presets": {"a": {"inherit": "b", "columns": 1}, "b": {"inherit": "a"}}, "default_preset": "a"}"#).unwrap_err();
+        assert!(cycle.to_string().contains("cycle"), "{}", cycle);
+        let unknown = parse_config(r#"{"presets": {"a": {"inherit": "missing"}}, "default_preset": "a"}"#).unwrap_err();
+        assert!(unknown.to_string().contains("unknown preset 'missing'"), "{}", unknown);
+        let incomplete = parse_config(r#"{"presets": {"a": {"columns": 80, "fps": 24, "font_ratio": 0.5}}, "default_preset": "a"}"#).unwrap_err();
+        assert!(incomplete.to_string().contains("missing 'luminance'"), "{}", incomplete);
+    }
+
+    #[test]
+    fn suggested_preset_follows_source_size_and_length() {
+        let cfg = parse_config(r#"{
+            "presets": {
+                "default": {"columns": 400, "fps": 30, "font_ratio": 0.7, "luminance": 20},
+                "small":   {"inherit": "default", "columns": 80},
+                "large":   {"inherit": "default", "columns": 800}
+            },
+            "default_preset": "default"
+        }"#).unwrap();
+        let clip = |width, height, fps, duration| MediaInfo {width, height, fps: Some(fps), duration: Some(duration)};
+        assert_eq!(cfg.suggest_preset_name(&clip(640, 360, 30.0, 600.0)), "small");
+        assert_eq!(cfg.suggest_preset_name(&MediaInfo::image(1080, 1920)), "default");
+        assert_eq!(cfg.suggest_preset_name(&MediaInfo::image(3840, 2160)), "large");
+        assert_eq!(cfg.suggest_preset_name(&clip(3840, 2160, 60.0, 20.0)), "large");
+        // A long 4K clip stays on the default preset, and so does a 4K clip at a frame rate that makes it long
+        assert_eq!(cfg.suggest_preset_name(&clip(3840, 2160, 24.0, 600.0)), "default");
+        assert_eq!(cfg.suggest_preset_name(&clip(3840, 2160, 120.0, 20.0)), "default");
+        assert_eq!(cfg.suggest_preset(&clip(640, 360, 30.0, 5.0)).unwrap().columns, 80);
+
+        let only_default = parse_config(r#"{"presets": {"main": {"columns": 120, "fps": 24, "font_ratio": 0.5, "luminance": 10}}, "default_preset": "main"}"#).unwrap();
+        assert_eq!(only_default.suggest_preset_name(&MediaInfo::image(320, 240)), "main");
+    }
+
+    #[test]
+    fn builtin_profiles_resolve_against_default_config() {
+        let cfg = AppConfig::default();
+        for name in ["discord", "tiktok", "terminal-80col"] {
+            let profile = &cfg.profiles[name];
+            profile.conversion_options(&cfg).unwrap();
+            profile.video_options(&cfg).unwrap();
+        }
+        let terminal = &cfg.profiles["terminal-80col"];
+        assert_eq!(terminal.conversion_options(&cfg).unwrap().columns, Some(80));
+        assert_eq!(terminal.video_options(&cfg).unwrap().fps, 24);
+        let discord = cfg.profiles["discord"].to_video_options("out.mp4");
+        assert_eq!((discord.crf, discord.mux_audio, discord.use_colors), (28, true, Some(true)));
+    }
+
+    #[test]
+    fn builtin_profiles_work_without_a_small_preset() {
+        let cfg = parse_config(r#"{"presets": {"main": {"columns": 400, "fps": 60, "font_ratio": 0.7, "luminance": 90}}, "default_preset": "main"}"#).unwrap();
+        for name in ["discord", "tiktok", "terminal-80col"] {
+            let profile = &cfg.profiles[name];
+            profile.conversion_options(&cfg).unwrap();
+            profile.video_options(&cfg).unwrap();
+        }
+        let discord = cfg.profiles["discord"].conversion_options(&cfg).unwrap();
+        assert_eq!((discord.columns, discord.font_ratio, discord.luminance), (Some(160), 0.44, 20));
+        assert_eq!(cfg.profiles["terminal-80col"].video_options(&cfg).unwrap().fps, 24);
+    }
+
+    #[test]
+    fn config_profiles_override_builtins_and_fall_back_to_preset() {
+        let cfg = parse_config(r#"{
+            "presets": {"default": {"columns": 400, "fps": 30, "font_ratio": 0.7, "luminance": 20, "preprocess": "hflip"}},
+            "profiles": {"discord": {"luminance": 5}},
+            "default_preset": "default"
+        }"#).unwrap();
+        let discord = &cfg.profiles["discord"];
+        let options = discord.conversion_options(&cfg).unwrap();
+        assert_eq!((options.columns, options.luminance), (Some(400), 5));
+        assert_eq!(discord.video_options(&cfg).unwrap().preprocess_filter.as_deref(), Some("hflip"));
+        assert!(cfg.profiles.contains_key("tiktok"));
+        let broken = Profile {preset: Some("missing".to_string()), ..Profile::default()};
+        assert!(broken.conversion_options(&cfg).is_err());
+    }
+
+    #[test]
+    fn default_config_still_parses() {
+        let cfg = AppConfig::default();
+        assert_eq!(cfg.presets.len(), 3);
+        assert!(cfg.uses_only_ascii_chars());
+    }
+}
@@ -1,13 +1,23 @@
-use anyhow::{anyhow, Context, Result};
+use crate::error::{Context, Error, Result};
+use image::{DynamicImage, GrayImage};
 use rayon::prelude::*;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::fs;
 use std::hash::{Hash, Hasher};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use walkdir::WalkDir;
 
-use crate::{background_fit_optimized, render, BgFitQuality, CancelToken, Cancelled, CellColorMode, OutputMode, Progress};
+use crate::captions::CaptionTrack;
+use crate::cframe::CframeEncoder;
+use crate::codepage::Codepage;
+use crate::depth::DepthOptions;
+use crate::frame::{encode_json, push_ansi_rows, AnsiColor, AsciiFrame, CellBytes, CharMode, Sampling};
+use crate::histogram::ColorHistogram;
+use crate::progress::ProgressCounter;
+use crate::{background_fit_optimized, render, BgFitQuality, CancelToken, Cancelled, CellColorMode, GlyphMatch, OutputMode, Palette, Progress};
 
 /// Intermediate representation of one converted ASCII frame
 pub(crate) struct AsciiFrameData {
@@ -23,6 +33,47 @@ pub(crate) struct AsciiFrameData {
     pub(crate) bg_rgb_colors: Vec<u8>,
 }
 
+impl AsciiFrameData {
+    /// Histogram of the frame's foreground and background colors
+    pub(crate) fn color_histogram(&self) -> ColorHistogram {
+        let mut histogram = ColorHistogram::new();
+        histogram.add_rgb(&self.rgb_colors);
+        histogram.add_rgb(&self.bg_rgb_colors);
+        histogram
+    }
+
+    /// Bytes the frame holds on the heap
+    pub(crate) fn heap_bytes(&self) -> u64 {
+        (self.ascii_text.capacity() + self.rgb_colors.capacity() + self.bg_rgb_colors.capacity()) as u64
+    }
+}
+
+impl From<AsciiFrameData> for AsciiFrame {
+    fn from(data: AsciiFrameData) -> Self {
+        Self {text: data.ascii_text, width: data.width_chars, height: data.height_chars, rgb: data.rgb_colors, bg_rgb: data.bg_rgb_colors}
+    }
+}
+
+impl AsciiFrameData {
+    /// Repeat every cell twice horizontally, for square-pixel output
+    pub(crate) fn double_width(&mut self) {
+        self.ascii_text = crate::frame::double_text_cells(&self.ascii_text);
+        self.rgb_colors = crate::frame::double_rgb_cells(&self.rgb_colors);
+        self.bg_rgb_colors = crate::frame::double_rgb_cells(&self.bg_rgb_colors);
+        self.width_chars *= 2;
+    }
+
+    /// Thin and/or darken the cells with an aligned depth map; `ascii_chars` is the ramp the frame was converted with
+    pub(crate) fn apply_depth(&mut self, depth: &image::DynamicImage, ascii_chars: &[u8], options: DepthOptions) {
+        options.apply(depth, &mut self.ascii_text, self.width_chars, self.height_chars, ascii_chars, &mut self.rgb_colors, &mut self.bg_rgb_colors);
+    }
+
+    /// Draw the captions visible `seconds` into the clip over the cells
+    pub(crate) fn apply_captions(&mut self, captions: &CaptionTrack, seconds: f64) {
+        captions.apply(seconds, &mut self.ascii_text, self.width_chars, self.height_chars, &mut self.rgb_colors, &mut self.bg_rgb_colors);
+    }
+}
+
 pub(crate) enum BackgroundAnalysisContext {
     Legacy(render::BackgroundAnalysisContext),
     Optimized(background_fit_optimized::OptimizedBackgroundAnalysisContext),
@@ -36,95 +87,178 @@ pub(crate) fn background_analysis_for_mode(ascii_chars: &[u8], cell_color_mode:
     }
 }
 
+/// Decode the image at `path`
+pub(crate) fn open_image(path: &Path) -> Result<DynamicImage> {
+    profile_span!("decode");
+    image::open(path).with_context(|| format!("opening {}", path.display()))
+}
+
 #[allow(clippy::too_many_arguments)]
-pub(crate) fn image_to_ascii_frame_data(img_path: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, ascii_chars: &[u8], cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality) -> Result<AsciiFrameData> {
+pub(crate) fn image_to_ascii_frame_data(img: &DynamicImage, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, ascii_chars: &[u8], cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, palette: Palette, sampling: Sampling) -> Result<AsciiFrameData> {
     let background_analysis = background_analysis_for_mode(ascii_chars, cell_color_mode, bg_fit_quality)?;
-    image_to_ascii_frame_data_with_analysis(img_path, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality, background_analysis.as_ref())
+    image_to_ascii_frame_data_with_analysis(img, None, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality, palette, sampling, background_analysis.as_ref())
 }
 
 #[allow(clippy::too_many_arguments)]
-pub(crate) fn image_to_ascii_frame_data_with_analysis(img_path: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, ascii_chars: &[u8], cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, background_analysis: Option<&BackgroundAnalysisContext>) -> Result<AsciiFrameData> {
-    match cell_color_mode {
+pub(crate) fn image_to_ascii_frame_data_with_analysis(img: &DynamicImage, luma: Option<&GrayImage>, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, ascii_chars: &[u8], cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, palette: Palette, sampling: Sampling, background_analysis: Option<&BackgroundAnalysisContext>) -> Result<AsciiFrameData> {
+    profile_span!("convert");
+    let (grid_font_ratio, grid_columns) = sampling.grid(font_ratio, columns);
+    if sampling.char_mode != CharMode::Ascii && cell_color_mode != CellColorMode::ForegroundOnly {
+        return Err(Error::InvalidOptions(format!("{} characters support only CellColorMode::ForegroundOnly", sampling.char_mode.as_str())));
+    }
+    if sampling.glyph_match != GlyphMatch::Luminance && (sampling.char_mode != CharMode::Ascii || cell_color_mode != CellColorMode::ForegroundOnly) {
+        return Err(Error::InvalidOptions("matching glyph shapes supports only ASCII characters with CellColorMode::ForegroundOnly".into()));
+    }
+    let mut frame = match cell_color_mode {
+        CellColorMode::ForegroundOnly if sampling.glyph_match != GlyphMatch::Luminance => render::shape_match_cells(img, grid_font_ratio, threshold, grid_columns, sampling, ascii_chars, bg_fit_quality),
         CellColorMode::ForegroundOnly => {
-            let (ascii_text, width_chars, height_chars, rgb_colors) = image_to_ascii_with_colors(img_path, font_ratio, threshold, columns, ascii_chars)?;
-            Ok(AsciiFrameData {ascii_text, width_chars, height_chars, rgb_colors, bg_rgb_colors: Vec::new()})
+            // Doubles square pixels, preserves markers and fits line limits itself
+            let cells = crate::frame::image_to_cells(img, luma, font_ratio, threshold, columns, ascii_chars, sampling);
+            let (mut rgb_colors, mut bg_rgb_colors) = (cells.rgb, cells.bg_rgb);
+            palette.apply_rgb_triplets(&mut rgb_colors);
+            palette.apply_background_triplets(&mut bg_rgb_colors);
+            return Ok(AsciiFrameData {ascii_text: cells.text, width_chars: cells.width, height_chars: cells.height, rgb_colors, bg_rgb_colors});
         }
         CellColorMode::FitForegroundBackground => match background_analysis {
-            Some(BackgroundAnalysisContext::Legacy(background_analysis)) => render::fit_image_to_ascii_with_cell_backgrounds_with_context(img_path, font_ratio, threshold, bg_threshold, columns, background_analysis),
-            None => render::fit_image_to_ascii_with_cell_backgrounds(img_path, font_ratio, threshold, bg_threshold, columns, ascii_chars, bg_fit_quality),
-            Some(BackgroundAnalysisContext::Optimized(_)) => Err(anyhow!("legacy background mode received an optimized analysis context")),
+            Some(BackgroundAnalysisContext::Legacy(background_analysis)) => render::fit_image_to_ascii_with_cell_backgrounds_with_context(img, grid_font_ratio, threshold, bg_threshold, grid_columns, sampling, background_analysis),
+            None => render::fit_image_to_ascii_with_cell_backgrounds(img, grid_font_ratio, threshold, bg_threshold, grid_columns, sampling, ascii_chars, bg_fit_quality),
+            Some(BackgroundAnalysisContext::Optimized(_)) => Err(Error::Other("legacy background mode received an optimized analysis context".into())),
         },
         CellColorMode::FitForegroundBackgroundOptimized => match background_analysis {
-            Some(BackgroundAnalysisContext::Optimized(background_analysis)) => background_fit_optimized::fit_image_to_ascii_with_cell_backgrounds_with_context(img_path, font_ratio, threshold, bg_threshold, columns, background_analysis),
-            None => background_fit_optimized::fit_image_to_ascii_with_cell_backgrounds(img_path, font_ratio, threshold, bg_threshold, columns, ascii_chars, bg_fit_quality),
-            Some(BackgroundAnalysisContext::Legacy(_)) => Err(anyhow!("optimized background mode received a legacy analysis context")),
+            Some(BackgroundAnalysisContext::Optimized(background_analysis)) => background_fit_optimized::fit_image_to_ascii_with_cell_backgrounds_with_context(img, grid_font_ratio, threshold, bg_threshold, grid_columns, sampling, background_analysis),
+            None => background_fit_optimized::fit_image_to_ascii_with_cell_backgrounds(img, grid_font_ratio, threshold, bg_threshold, grid_columns, sampling, ascii_chars, bg_fit_quality),
+            Some(BackgroundAnalysisContext::Legacy(_)) => Err(Error::Other("optimized background mode received a legacy analysis context".into())),
         },
+    }?;
+    if sampling.square_pixels {
+        frame.double_width();
+    }
+    if sampling.preserve_markers {
+        crate::markers::preserve_markers(&sampling.source(img).to_rgb8(), &mut frame.ascii_text, frame.width_chars, frame.height_chars, ascii_chars, &mut frame.rgb_colors, &mut frame.bg_rgb_colors);
     }
+    sampling.line_limits.apply(&mut frame.ascii_text, &mut frame.width_chars, &mut frame.height_chars, &mut frame.rgb_colors, &mut frame.bg_rgb_colors);
+    palette.apply_rgb_triplets(&mut frame.rgb_colors);
+    palette.apply_background_triplets(&mut frame.bg_rgb_colors);
+    Ok(frame)
 }
 
 #[allow(clippy::too_many_arguments)]
-pub(crate) fn convert_image_to_ascii(img_path: &Path, out_txt: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality) -> Result<()> {
+pub(crate) fn convert_image_to_ascii(img_path: &Path, out_txt: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, palette: Palette, sampling: Sampling) -> Result<()> {
+    let img = open_image(img_path)?;
+    let cframe = CframeEncoder::new(sampling, font_ratio, ascii_chars);
     match output_mode {
         OutputMode::TextOnly => {
-            let ascii_string = image_to_ascii_string(img_path, font_ratio, threshold, columns, ascii_chars)?;
-            fs::write(out_txt, ascii_string).with_context(|| format!("writing {}", out_txt.display()))?;
+            let ascii_string = image_to_ascii_string(&img, font_ratio, threshold, columns, ascii_chars, bg_fit_quality, sampling)?;
+            write_frame_file(out_txt, ascii_string).with_context(|| format!("writing {}", out_txt.display()))?;
         }
         OutputMode::ColorOnly => {
-            let frame = image_to_ascii_frame_data(img_path, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality)?;
+            let frame = image_to_ascii_frame_data(&img, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality, palette, sampling)?;
             let cframe_path = out_txt.with_extension("cframe");
-            write_frame_cframe(&frame, &cframe_path, cell_color_mode)?;
+            write_frame_cframe(&frame, &cframe_path, cell_color_mode, &cframe)?;
         }
         OutputMode::TextAndColor => {
-            let frame = image_to_ascii_frame_data(img_path, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality)?;
-            fs::write(out_txt, &frame.ascii_text).with_context(|| format!("writing {}", out_txt.display()))?;
+            let frame = image_to_ascii_frame_data(&img, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality, palette, sampling)?;
+            write_frame_file(out_txt, &frame.ascii_text).with_context(|| format!("writing {}", out_txt.display()))?;
             let cframe_path = out_txt.with_extension("cframe");
-            write_frame_cframe(&frame, &cframe_path, cell_color_mode)?;
+            write_frame_cframe(&frame, &cframe_path, cell_color_mode, &cframe)?;
+        }
+        OutputMode::Ansi | OutputMode::Ansi256 | OutputMode::Json => {
+            let frame = image_to_ascii_frame_data(&img, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality, palette, sampling)?;
+            write_frame_outputs(&frame, out_txt, output_mode, cell_color_mode, sampling.codepage, &cframe)?;
         }
     }
     Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
-fn convert_image_to_ascii_with_analysis(img_path: &Path, out_txt: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, background_analysis: Option<&BackgroundAnalysisContext>) -> Result<()> {
+pub(crate) fn convert_image_to_ascii_with_analysis(img_path: &Path, out_txt: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, palette: Palette, sampling: Sampling, background_analysis: Option<&BackgroundAnalysisContext>) -> Result<ColorHistogram> {
+    let img = open_image(img_path)?;
+    let cframe = CframeEncoder::new(sampling, font_ratio, ascii_chars);
     match output_mode {
         OutputMode::TextOnly => {
-            let ascii_string = image_to_ascii_string(img_path, font_ratio, threshold, columns, ascii_chars)?;
-            fs::write(out_txt, ascii_string).with_context(|| format!("writing {}", out_txt.display()))?;
+            let ascii_string = image_to_ascii_string(&img, font_ratio, threshold, columns, ascii_chars, bg_fit_quality, sampling)?;
+            write_frame_file(out_txt, ascii_string).with_context(|| format!("writing {}", out_txt.display()))?;
+            Ok(ColorHistogram::new())
         }
         OutputMode::ColorOnly => {
-            let frame = image_to_ascii_frame_data_with_analysis(img_path, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality, background_analysis)?;
+            let frame = image_to_ascii_frame_data_with_analysis(&img, None, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality, palette, sampling, background_analysis)?;
             let cframe_path = out_txt.with_extension("cframe");
-            write_frame_cframe(&frame, &cframe_path, cell_color_mode)?;
+            write_frame_cframe(&frame, &cframe_path, cell_color_mode, &cframe)?;
+            Ok(frame.color_histogram())
         }
         OutputMode::TextAndColor => {
-            let frame = image_to_ascii_frame_data_with_analysis(img_path, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality, background_analysis)?;
-            fs::write(out_txt, &frame.ascii_text).with_context(|| format!("writing {}", out_txt.display()))?;
+            let frame = image_to_ascii_frame_data_with_analysis(&img, None, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality, palette, sampling, background_analysis)?;
+            write_frame_file(out_txt, &frame.ascii_text).with_context(|| format!("writing {}", out_txt.display()))?;
             let cframe_path = out_txt.with_extension("cframe");
-            write_frame_cframe(&frame, &cframe_path, cell_color_mode)?;
+            write_frame_cframe(&frame, &cframe_path, cell_color_mode, &cframe)?;
+            Ok(frame.color_histogram())
+        }
+        OutputMode::Ansi | OutputMode::Ansi256 | OutputMode::Json => {
+            let frame = image_to_ascii_frame_data_with_analysis(&img, None, font_ratio, threshold, bg_threshold, columns, ascii_chars, cell_color_mode, bg_fit_quality, palette, sampling, background_analysis)?;
+            write_frame_outputs(&frame, out_txt, output_mode, cell_color_mode, sampling.codepage, &cframe)?;
+            Ok(frame.color_histogram())
         }
     }
-    Ok(())
 }
 
-fn write_frame_cframe(frame: &AsciiFrameData, path: &Path, cell_color_mode: CellColorMode) -> Result<()> {
-    let background = if frame.bg_rgb_colors.is_empty() {None} else {Some(frame.bg_rgb_colors.as_slice())};
-    if cell_color_mode == CellColorMode::FitForegroundBackgroundOptimized {
-        write_cframe_binary_buffered(frame.width_chars, frame.height_chars, &frame.ascii_text, &frame.rgb_colors, background, path)
-    } else {
-        write_cframe_binary(frame.width_chars, frame.height_chars, &frame.ascii_text, &frame.rgb_colors, background, path)
+/// Write an already converted frame as `out_txt` and/or its `.cframe` sibling, or as its `.ans`
+/// sibling in the ANSI modes and its `.json` sibling in the JSON mode, as `output_mode` asks.
+/// `.ans` files are written in `codepage`, the others always in UTF-8, and `.cframe` files by `cframe`.
+pub(crate) fn write_frame_outputs(frame: &AsciiFrameData, out_txt: &Path, output_mode: &OutputMode, cell_color_mode: CellColorMode, codepage: Codepage, cframe: &CframeEncoder) -> Result<()> {
+    if *output_mode == OutputMode::Json {
+        let out_json = out_txt.with_extension("json");
+        return write_frame_file(&out_json, encode_json(frame.width_chars, frame.height_chars, &frame.ascii_text, &frame.rgb_colors, &frame.bg_rgb_colors)).with_context(|| format!("writing {}", out_json.display()));
+    }
+    if let Some(colors) = output_mode.ansi_color() {
+        let mut ansi = String::new();
+        push_ansi_rows(&frame.ascii_text, frame.width_chars, &frame.rgb_colors, &frame.bg_rgb_colors, colors, &mut ansi);
+        let out_ans = out_txt.with_extension("ans");
+        return write_frame_file(&out_ans, codepage.encode_lossy(&ansi)).with_context(|| format!("writing {}", out_ans.display()));
+    }
+    if *output_mode != OutputMode::ColorOnly {
+        write_frame_file(out_txt, &frame.ascii_text).with_context(|| format!("writing {}", out_txt.display()))?;
     }
+    if *output_mode != OutputMode::TextOnly {
+        write_frame_cframe(frame, &out_txt.with_extension("cframe"), cell_color_mode, cframe)?;
+    }
+    Ok(())
+}
+
+/// Write a frame file whole or not at all: into a `.part` sibling first, renamed into place once
+/// written, so a frame file left by an interrupted run is complete
+fn write_frame_file(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    fs::write(&part, contents)?;
+    fs::rename(&part, path)
+}
+
+/// Append `frame` as ANSI text: cursor home, then each row with foreground (and cell background,
+/// if fitted) escapes, plain without `colors`, so frames written one after another play back when `cat`-ed.
+pub(crate) fn write_frame_ansi<W: Write>(frame: &AsciiFrameData, colors: Option<AnsiColor>, out: &mut W) -> std::io::Result<()> {
+    let mut ansi = String::from("\x1b[H");
+    let rgb: &[u8] = if colors.is_some() {&frame.rgb_colors} else {&[]};
+    push_ansi_rows(&frame.ascii_text, frame.width_chars, rgb, &frame.bg_rgb_colors, colors.unwrap_or_default(), &mut ansi);
+    out.write_all(ansi.as_bytes())
 }
 
-pub(crate) fn image_to_ascii_string(img_path: &Path, font_ratio: f32, threshold: u8, columns: Option<u32>, ascii_chars: &[u8]) -> Result<String> {
-    let img = image::open(img_path).with_context(|| format!("opening {}", img_path.display()))?.to_rgb8();
-    Ok(crate::frame::rgb_image_to_ascii_with_colors(img, font_ratio, threshold, columns, ascii_chars).0)
+fn write_frame_cframe(frame: &AsciiFrameData, path: &Path, cell_color_mode: CellColorMode, cframe: &CframeEncoder) -> Result<()> {
+    let background = if frame.bg_rgb_colors.is_empty() {None} else {Some(frame.bg_rgb_colors.as_slice())};
+    let bytes = if cell_color_mode == CellColorMode::FitForegroundBackgroundOptimized {
+        encode_cframe_checked(frame.width_chars, frame.height_chars, &frame.ascii_text, &frame.rgb_colors, background)?
+    } else {
+        crate::frame::encode_cframe(frame.width_chars, frame.height_chars, &frame.ascii_text, &frame.rgb_colors, background)
+    };
+    write_frame_file(path, cframe.encode(bytes)?).with_context(|| format!("writing cframe file {}", path.display()))
 }
 
-/// Returns (ascii_string, width, height, rgb_bytes)
-/// rgb_bytes is a flat Vec<u8> with 3 bytes (R, G, B) per character, row-major order
-pub(crate) fn image_to_ascii_with_colors(img_path: &Path, font_ratio: f32, threshold: u8, columns: Option<u32>, ascii_chars: &[u8]) -> Result<(String, u32, u32, Vec<u8>)> {
-    let img = image::open(img_path).with_context(|| format!("opening {}", img_path.display()))?.to_rgb8();
-    Ok(crate::frame::rgb_image_to_ascii_with_colors(img, font_ratio, threshold, columns, ascii_chars))
+/// The text of `img`; `bg_fit_quality` only sizes the glyph atlas when matching glyph shapes
+pub(crate) fn image_to_ascii_string(img: &DynamicImage, font_ratio: f32, threshold: u8, columns: Option<u32>, ascii_chars: &[u8], bg_fit_quality: BgFitQuality, sampling: Sampling) -> Result<String> {
+    profile_span!("convert");
+    if sampling.glyph_match != GlyphMatch::Luminance {
+        return image_to_ascii_frame_data(img, font_ratio, threshold, threshold, columns, ascii_chars, CellColorMode::ForegroundOnly, bg_fit_quality, Palette::Truecolor, sampling).map(|frame| frame.ascii_text);
+    }
+    Ok(crate::frame::image_to_cells(img, None, font_ratio, threshold, columns, ascii_chars, sampling).text)
 }
 
 pub(crate) use crate::frame::CFRAME_EXT_FLAG_HAS_BG;
@@ -155,49 +289,67 @@ pub(crate) fn write_cframe_binary(width: u32, height: u32, ascii_content: &str,
     fs::write(path, crate::frame::encode_cframe(width, height, ascii_content, rgb_data, bg_rgb_data)).with_context(|| format!("writing cframe file {}", path.display()))
 }
 
+#[cfg(test)]
 fn write_cframe_binary_buffered(width: u32, height: u32, ascii_content: &str, rgb_data: &[u8], bg_rgb_data: Option<&[u8]>, path: &Path) -> Result<()> {
+    fs::write(path, encode_cframe_checked(width, height, ascii_content, rgb_data, bg_rgb_data)?).with_context(|| format!("writing cframe file {}", path.display()))
+}
+
+/// [`encode_cframe`](crate::frame::encode_cframe), checking the payloads against the frame size first
+fn encode_cframe_checked(width: u32, height: u32, ascii_content: &str, rgb_data: &[u8], bg_rgb_data: Option<&[u8]>) -> Result<Vec<u8>> {
     let cell_count = (width * height) as usize;
     if rgb_data.len() != cell_count * 3 {
-        return Err(anyhow!("invalid foreground payload: expected {} bytes, got {}", cell_count * 3, rgb_data.len()));
+        return Err(Error::InvalidCframe(format!("invalid foreground payload: expected {} bytes, got {}", cell_count * 3, rgb_data.len())));
     }
     if let Some(background) = bg_rgb_data {
         if background.len() != cell_count * 3 {
-            return Err(anyhow!("invalid background payload: expected {} bytes, got {}", cell_count * 3, background.len()));
+            return Err(Error::InvalidCframe(format!("invalid background payload: expected {} bytes, got {}", cell_count * 3, background.len())));
         }
     }
 
-    let extension_size = bg_rgb_data.map_or(0, |background| 1 + background.len());
+    let extension_size = 1 + bg_rgb_data.map_or(0, <[u8]>::len);
     let mut output = Vec::with_capacity(8 + cell_count * 4 + extension_size);
     output.extend_from_slice(&width.to_le_bytes());
     output.extend_from_slice(&height.to_le_bytes());
 
+    let cell_bytes = CellBytes::of_text(ascii_content);
     let mut cell_index = 0usize;
-    for byte in ascii_content.bytes().filter(|byte| *byte != b'\n') {
+    for ch in ascii_content.chars().filter(|ch| *ch != '\n') {
         if cell_index >= cell_count {
-            return Err(anyhow!("ASCII payload contains more than {} cells", cell_count));
+            return Err(Error::InvalidCframe(format!("ASCII payload contains more than {} cells", cell_count)));
         }
         let color_offset = cell_index * 3;
-        output.extend_from_slice(&[byte, rgb_data[color_offset], rgb_data[color_offset + 1], rgb_data[color_offset + 2]]);
+        output.extend_from_slice(&[cell_bytes.byte(ch), rgb_data[color_offset], rgb_data[color_offset + 1], rgb_data[color_offset + 2]]);
         cell_index += 1;
     }
     if cell_index != cell_count {
-        return Err(anyhow!("ASCII payload contains {} cells, expected {}", cell_index, cell_count));
+        return Err(Error::InvalidCframe(format!("ASCII payload contains {} cells, expected {}", cell_index, cell_count)));
+    }
+    let flags = if bg_rgb_data.is_some() {CFRAME_EXT_FLAG_HAS_BG} else {0} | cell_bytes.flag();
+    if flags != 0 {
+        output.push(flags);
     }
     if let Some(background) = bg_rgb_data {
-        output.push(CFRAME_EXT_FLAG_HAS_BG);
         output.extend_from_slice(background);
     }
-    fs::write(path, output).with_context(|| format!("writing cframe file {}", path.display()))
+    Ok(output)
 }
 
 /// Read a .cframe binary file into AsciiFrameData.
 ///
-/// Recognises both the legacy fg-only layout and the new extension area. For backward compatibility with `.cframe` files written by older builds that
+/// Recognises v2 containers (see [`crate::cframe`]) as well as the v1 layout, both the legacy fg-only one and the new extension area. For backward compatibility with `.cframe` files written by older builds that
 /// appended the background payload **without** a leading flag byte, the reader also accepts an exact `width * height * 3` trailing block.
 pub(crate) fn read_cframe_to_frame_data(path: &Path) -> Result<AsciiFrameData> {
+    profile_span!("decode");
     let data = fs::read(path).with_context(|| format!("reading cframe {}", path.display()))?;
+    cframe_bytes_to_frame_data(&data, &path.display().to_string())
+}
+
+/// [`read_cframe_to_frame_data`] on the bytes of a `.cframe` already in memory, e.g. from a packed
+/// archive; `source` names them in errors.
+pub(crate) fn cframe_bytes_to_frame_data(data: &[u8], source: &str) -> Result<AsciiFrameData> {
+    let data = &crate::cframe::frame_bytes(data).with_context(|| format!("reading {}", source))?[..];
     if data.len() < 8 {
-        return Err(anyhow!("cframe file too small: {}", path.display()));
+        return Err(Error::InvalidCframe(format!("cframe file too small: {}", source)));
     }
 
     let width = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
@@ -206,17 +358,21 @@ pub(crate) fn read_cframe_to_frame_data(path: &Path) -> Result<AsciiFrameData> {
     let cell_count = (width * height) as usize;
 
     if data.len() < 8 + expected_body {
-        return Err(anyhow!("cframe file truncated: expected {} body bytes, got {} in {}", expected_body, data.len() - 8, path.display()));
+        return Err(Error::InvalidCframe(format!("cframe file truncated: expected {} body bytes, got {} in {}", expected_body, data.len() - 8, source)));
     }
 
-    let mut ascii_text = String::with_capacity((width as usize + 1) * height as usize);
+    let ext_offset = 8 + expected_body;
+    let expected_bg_len = cell_count * 3;
+    let cell_bytes = CellBytes::from_flags(cframe_flags(data, ext_offset, expected_bg_len));
+
+    let mut ascii_text = String::with_capacity((width as usize * if cell_bytes == CellBytes::Latin1 {1} else {3} + 1) * height as usize);
     let mut rgb_colors = Vec::with_capacity((width * height * 3) as usize);
     let mut bg_rgb_colors = Vec::new();
 
     if width > 0 {
         for row in data[8..8 + expected_body].chunks_exact(width as usize * 4) {
             for cell in row.chunks_exact(4) {
-                ascii_text.push(cell[0] as char);
+                ascii_text.push(cell_bytes.char(cell[0]));
                 rgb_colors.extend_from_slice(&cell[1..4]);
             }
             ascii_text.push('\n');
@@ -227,8 +383,6 @@ pub(crate) fn read_cframe_to_frame_data(path: &Path) -> Result<AsciiFrameData> {
         }
     }
 
-    let ext_offset = 8 + expected_body;
-    let expected_bg_len = cell_count * 3;
     if data.len() > ext_offset {
         let trailing = data.len() - ext_offset;
         if trailing > expected_bg_len && (data[ext_offset] & CFRAME_EXT_FLAG_HAS_BG) != 0 {
@@ -244,6 +398,12 @@ pub(crate) fn read_cframe_to_frame_data(path: &Path) -> Result<AsciiFrameData> {
     Ok(AsciiFrameData {ascii_text, width_chars: width, height_chars: height, rgb_colors, bg_rgb_colors})
 }
 
+/// The extension flags of a `.cframe` whose body ends at `body_end`; legacy files end in a bare
+/// background block instead of the flag byte.
+fn cframe_flags(data: &[u8], body_end: usize, background_len: usize) -> u8 {
+    if data.len() > body_end && data.len() - body_end != background_len {data[body_end]} else {0}
+}
+
 fn cframe_background_range(data: &[u8], body_end: usize, background_len: usize) -> Option<std::ops::Range<usize>> {
     let trailing = data.len().saturating_sub(body_end);
     if trailing > background_len && (data[body_end] & CFRAME_EXT_FLAG_HAS_BG) != 0 {
@@ -257,26 +417,30 @@ fn cframe_background_range(data: &[u8], body_end: usize, background_len: usize)
 }
 
 /// Erase selected cells in a raw `.cframe` payload while preserving unrelated channels. Returns `Ok(None)` when no selected cell changes the payload.
-pub fn erase_cframe_cells(data: &[u8], cells: &[(usize, usize)], layer: CframeEraseLayer) -> Result<Option<Vec<u8>>> {
+/// v2 files come back in their container, with the same compression and metadata.
+pub fn erase_cframe_cells(original: &[u8], cells: &[(usize, usize)], layer: CframeEraseLayer) -> Result<Option<Vec<u8>>> {
+    let data = &crate::cframe::frame_bytes(original)?[..];
     if data.len() < 8 {
-        return Err(anyhow!("cframe file too small"));
+        return Err(Error::InvalidCframe("cframe file too small".into()));
     }
 
     let width = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
     let height = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
-    let cell_count = width.checked_mul(height).ok_or_else(|| anyhow!("cframe dimensions overflow"))?;
-    let body_len = cell_count.checked_mul(4).ok_or_else(|| anyhow!("cframe body size overflow"))?;
-    let body_end = 8usize.checked_add(body_len).ok_or_else(|| anyhow!("cframe body offset overflow"))?;
+    let cell_count = width.checked_mul(height).ok_or_else(|| Error::InvalidCframe("cframe dimensions overflow".into()))?;
+    let body_len = cell_count.checked_mul(4).ok_or_else(|| Error::InvalidCframe("cframe body size overflow".into()))?;
+    let body_end = 8usize.checked_add(body_len).ok_or_else(|| Error::InvalidCframe("cframe body offset overflow".into()))?;
     if data.len() < body_end {
-        return Err(anyhow!("cframe file truncated"));
+        return Err(Error::InvalidCframe("cframe file truncated".into()));
     }
 
-    let background_len = cell_count.checked_mul(3).ok_or_else(|| anyhow!("cframe background size overflow"))?;
+    let background_len = cell_count.checked_mul(3).ok_or_else(|| Error::InvalidCframe("cframe background size overflow".into()))?;
     let background_range = cframe_background_range(data, body_end, background_len);
     if layer == CframeEraseLayer::Background && background_range.is_none() {
         return Ok(None);
     }
 
+    // A blank braille cell has no dots raised
+    let blank = CellBytes::from_flags(cframe_flags(data, body_end, background_len)).blank();
     let mut output = data.to_vec();
     let mut modified = false;
 
@@ -288,8 +452,8 @@ pub fn erase_cframe_cells(data: &[u8], cells: &[(usize, usize)], layer: CframeEr
         let cell_index = row * width + col;
         if matches!(layer, CframeEraseLayer::All | CframeEraseLayer::Text) {
             let offset = 8 + cell_index * 4;
-            if output[offset] != b' ' || output[offset + 1] != 0 || output[offset + 2] != 0 || output[offset + 3] != 0 {
-                output[offset] = b' ';
+            if output[offset] != blank || output[offset + 1] != 0 || output[offset + 2] != 0 || output[offset + 3] != 0 {
+                output[offset] = blank;
                 output[offset + 1] = 0;
                 output[offset + 2] = 0;
                 output[offset + 3] = 0;
@@ -310,19 +474,20 @@ pub fn erase_cframe_cells(data: &[u8], cells: &[(usize, usize)], layer: CframeEr
         }
     }
 
-    Ok(modified.then_some(output))
+    modified.then(|| crate::cframe::rewrap(original, output)).transpose()
 }
 
 /// Read a .txt ASCII frame file into AsciiFrameData (white-on-black, no color)
 pub(crate) fn read_txt_to_frame_data(path: &Path) -> Result<AsciiFrameData> {
+    profile_span!("decode");
     let content = fs::read_to_string(path).with_context(|| format!("reading txt frame {}", path.display()))?;
     let lines: Vec<&str> = content.lines().collect();
 
     if lines.is_empty() {
-        return Err(anyhow!("empty frame file: {}", path.display()));
+        return Err(Error::InvalidCframe(format!("empty frame file: {}", path.display())));
     }
 
-    let width = lines[0].len() as u32;
+    let width = lines[0].chars().count() as u32;
     let height = lines.len() as u32;
 
     // Rebuild with consistent newlines
@@ -384,11 +549,26 @@ fn outputs_for_stem(dst_dir: &Path, stem: &str, output_mode: &OutputMode) -> Vec
         OutputMode::TextOnly => vec![dst_dir.join(format!("{stem}.txt"))],
         OutputMode::ColorOnly => vec![dst_dir.join(format!("{stem}.cframe"))],
         OutputMode::TextAndColor => vec![dst_dir.join(format!("{stem}.txt")), dst_dir.join(format!("{stem}.cframe"))],
+        OutputMode::Ansi | OutputMode::Ansi256 => vec![dst_dir.join(format!("{stem}.ans"))],
+        OutputMode::Json => vec![dst_dir.join(format!("{stem}.json"))],
     }
 }
 
+/// Whether an earlier run wrote every output of the frame `stem`
+fn frame_converted(dst_dir: &Path, stem: &str, output_mode: &OutputMode) -> bool {
+    outputs_for_stem(dst_dir, stem, output_mode).iter().all(|path| path.is_file())
+}
+
+/// Colors of a frame an earlier run converted, read back from its `.cframe`; frames without one
+/// add none
+fn converted_histogram(dst_dir: &Path, stem: &str, output_mode: &OutputMode) -> ColorHistogram {
+    let cframe_path = dst_dir.join(format!("{stem}.cframe"));
+    let has_cframe = matches!(output_mode, OutputMode::ColorOnly | OutputMode::TextAndColor);
+    has_cframe.then(|| read_cframe_to_frame_data(&cframe_path).ok()).flatten().map_or_else(ColorHistogram::new, |frame| frame.color_histogram())
+}
+
 fn file_stem_str(path: &Path) -> Result<&str> {
-    path.file_stem().and_then(|s| s.to_str()).ok_or_else(|| anyhow!("bad file name"))
+    path.file_stem().and_then(|s| s.to_str()).ok_or_else(|| Error::Other("bad file name".into()))
 }
 
 fn copy_duplicate_outputs(dst_dir: &Path, pngs: &[PathBuf], duplicate_idx: usize, representative_idx: usize, output_mode: &OutputMode) -> Result<()> {
@@ -398,41 +578,39 @@ fn copy_duplicate_outputs(dst_dir: &Path, pngs: &[PathBuf], duplicate_idx: usize
     let duplicate_outputs = outputs_for_stem(dst_dir, duplicate_stem, output_mode);
 
     for (src, dst) in representative_outputs.iter().zip(duplicate_outputs.iter()) {
-        fs::copy(src, dst).with_context(|| format!("copying duplicate output {} -> {}", src.display(), dst.display()))?;
+        fs::read(src).and_then(|bytes| write_frame_file(dst, bytes)).with_context(|| format!("copying duplicate output {} -> {}", src.display(), dst.display()))?;
     }
 
     Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
-pub(crate) fn convert_directory_parallel(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, cancel: Option<&CancelToken>) -> Result<usize> {
-    convert_directory_parallel_with_progress(src_dir, dst_dir, font_ratio, threshold, bg_threshold, keep_images, ascii_chars, output_mode, cell_color_mode, bg_fit_quality, None::<fn(usize, usize)>, cancel)
+pub(crate) fn convert_directory_parallel_with_progress<F: Fn(usize, usize) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, palette: Palette, sampling: Sampling, progress_callback: Option<F>, cancel: Option<&CancelToken>) -> Result<(usize, ColorHistogram)> {
+    convert_directory_parallel_with_progress_at_columns(src_dir, dst_dir, font_ratio, threshold, bg_threshold, None, keep_images, ascii_chars, output_mode, cell_color_mode, bg_fit_quality, palette, sampling, progress_callback, cancel)
 }
 
 #[allow(clippy::too_many_arguments)]
-pub(crate) fn convert_directory_parallel_with_progress<F: Fn(usize, usize) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, progress_callback: Option<F>, cancel: Option<&CancelToken>) -> Result<usize> {
-    convert_directory_parallel_with_progress_at_columns(src_dir, dst_dir, font_ratio, threshold, bg_threshold, None, keep_images, ascii_chars, output_mode, cell_color_mode, bg_fit_quality, progress_callback, cancel)
-}
-
-#[allow(clippy::too_many_arguments)]
-pub(crate) fn convert_directory_parallel_optimized_with_progress<F: Fn(usize, usize) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: u32, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, bg_fit_quality: BgFitQuality, progress_callback: Option<F>, cancel: Option<&CancelToken>) -> Result<usize> {
+pub(crate) fn convert_directory_parallel_optimized_with_progress<F: Fn(usize, usize) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: u32, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, bg_fit_quality: BgFitQuality, palette: Palette, sampling: Sampling, progress_callback: Option<F>, cancel: Option<&CancelToken>) -> Result<(usize, ColorHistogram)> {
     let _ = columns;
-    convert_directory_parallel_with_progress_at_columns(src_dir, dst_dir, font_ratio, threshold, bg_threshold, None, keep_images, ascii_chars, output_mode, CellColorMode::FitForegroundBackgroundOptimized, bg_fit_quality, progress_callback, cancel)
+    convert_directory_parallel_with_progress_at_columns(src_dir, dst_dir, font_ratio, threshold, bg_threshold, None, keep_images, ascii_chars, output_mode, CellColorMode::FitForegroundBackgroundOptimized, bg_fit_quality, palette, sampling, progress_callback, cancel)
 }
 
 #[allow(clippy::too_many_arguments)]
-fn convert_directory_parallel_with_progress_at_columns<F: Fn(usize, usize) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, progress_callback: Option<F>, cancel: Option<&CancelToken>) -> Result<usize> {
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::sync::Arc;
-
+fn convert_directory_parallel_with_progress_at_columns<F: Fn(usize, usize) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, palette: Palette, sampling: Sampling, progress_callback: Option<F>, cancel: Option<&CancelToken>) -> Result<(usize, ColorHistogram)> {
     fs::create_dir_all(dst_dir)?;
     let mut pngs: Vec<PathBuf> = WalkDir::new(src_dir).min_depth(1).max_depth(1).into_iter().filter_map(|e| e.ok()).map(|e| e.into_path()).filter(|p| p.extension().map(|e| e == "png").unwrap_or(false)).collect();
     pngs.sort();
 
     let total = pngs.len();
-    let completed = Arc::new(AtomicUsize::new(0));
+    let counter = ProgressCounter::new(total);
+    let report = |current: usize| if let Some(ref callback) = progress_callback {callback(current, total)};
     let background_analysis = background_analysis_for_mode(ascii_chars, cell_color_mode, bg_fit_quality)?;
     let dedup_plan = dedup_buckets(&pngs);
+    let histogram = Mutex::new(ColorHistogram::new());
+    let mut repeats = vec![1u64; pngs.len()];
+    for &(_, representative_idx) in &dedup_plan.duplicates {
+        repeats[representative_idx] += 1;
+    }
 
     dedup_plan.representatives.par_iter().try_for_each(|&idx| -> Result<()> {
         if cancel.is_some_and(|c| c.is_cancelled()) {
@@ -440,14 +618,17 @@ fn convert_directory_parallel_with_progress_at_columns<F: Fn(usize, usize) + Sen
         }
         let img_path = &pngs[idx];
         let file_stem = file_stem_str(img_path)?;
+        if sampling.resume && frame_converted(dst_dir, file_stem, output_mode) {
+            histogram.lock().unwrap_or_else(|e| e.into_inner()).merge_weighted(&converted_histogram(dst_dir, file_stem, output_mode), repeats[idx]);
+            counter.complete_one(report);
+            return Ok(());
+        }
         let out_txt = dst_dir.join(format!("{}.txt", file_stem));
-        convert_image_to_ascii_with_analysis(img_path, &out_txt, font_ratio, threshold, bg_threshold, columns, ascii_chars, output_mode, cell_color_mode, bg_fit_quality, background_analysis.as_ref())?;
+        let frame_histogram = convert_image_to_ascii_with_analysis(img_path, &out_txt, font_ratio, threshold, bg_threshold, columns, ascii_chars, output_mode, cell_color_mode, bg_fit_quality, palette, sampling, background_analysis.as_ref())?;
+        // Duplicates are copies of their representative, so its colors count once per copy
+        histogram.lock().unwrap_or_else(|e| e.into_inner()).merge_weighted(&frame_histogram, repeats[idx]);
 
-        // Update progress
-        let current = completed.fetch_add(1, Ordering::Relaxed) + 1;
-        if let Some(ref callback) = progress_callback {
-            callback(current, total);
-        }
+        counter.complete_one(report);
 
         Ok(())
     })?;
@@ -457,14 +638,10 @@ fn convert_directory_parallel_with_progress_at_columns<F: Fn(usize, usize) + Sen
             return Err(Cancelled.into());
         }
         copy_duplicate_outputs(dst_dir, &pngs, duplicate_idx, representative_idx, output_mode)?;
-
-        let current = completed.fetch_add(1, Ordering::Relaxed) + 1;
-        if let Some(ref callback) = progress_callback {
-            callback(current, total);
-        }
-
+        counter.complete_one(report);
         Ok(())
     })?;
+    counter.finish(report);
 
     if !keep_images {
         for img_path in &pngs {
@@ -472,38 +649,39 @@ fn convert_directory_parallel_with_progress_at_columns<F: Fn(usize, usize) + Sen
         }
     }
 
-    Ok(total)
+    Ok((total, histogram.into_inner().unwrap_or_else(|e| e.into_inner())))
 }
 
 /// Internal function for directory conversion with detailed Progress reporting
 #[allow(clippy::too_many_arguments)]
-pub(crate) fn convert_directory_parallel_with_detailed_progress<F: Fn(Progress) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, progress_callback: &F, cancel: Option<&CancelToken>) -> Result<usize> {
-    convert_directory_parallel_with_detailed_progress_at_columns(src_dir, dst_dir, font_ratio, threshold, bg_threshold, None, keep_images, ascii_chars, output_mode, cell_color_mode, bg_fit_quality, progress_callback, cancel)
+pub(crate) fn convert_directory_parallel_with_detailed_progress<F: Fn(Progress) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, palette: Palette, sampling: Sampling, progress_callback: &F, cancel: Option<&CancelToken>) -> Result<(usize, ColorHistogram)> {
+    convert_directory_parallel_with_detailed_progress_at_columns(src_dir, dst_dir, font_ratio, threshold, bg_threshold, None, keep_images, ascii_chars, output_mode, cell_color_mode, bg_fit_quality, palette, sampling, progress_callback, cancel)
 }
 
 #[allow(clippy::too_many_arguments)]
-pub(crate) fn convert_directory_parallel_optimized_with_detailed_progress<F: Fn(Progress) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: u32, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, bg_fit_quality: BgFitQuality, progress_callback: &F, cancel: Option<&CancelToken>) -> Result<usize> {
+pub(crate) fn convert_directory_parallel_optimized_with_detailed_progress<F: Fn(Progress) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: u32, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, bg_fit_quality: BgFitQuality, palette: Palette, sampling: Sampling, progress_callback: &F, cancel: Option<&CancelToken>) -> Result<(usize, ColorHistogram)> {
     let _ = columns;
-    convert_directory_parallel_with_detailed_progress_at_columns(src_dir, dst_dir, font_ratio, threshold, bg_threshold, None, keep_images, ascii_chars, output_mode, CellColorMode::FitForegroundBackgroundOptimized, bg_fit_quality, progress_callback, cancel)
+    convert_directory_parallel_with_detailed_progress_at_columns(src_dir, dst_dir, font_ratio, threshold, bg_threshold, None, keep_images, ascii_chars, output_mode, CellColorMode::FitForegroundBackgroundOptimized, bg_fit_quality, palette, sampling, progress_callback, cancel)
 }
 
 #[allow(clippy::too_many_arguments)]
-fn convert_directory_parallel_with_detailed_progress_at_columns<F: Fn(Progress) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, progress_callback: &F, cancel: Option<&CancelToken>) -> Result<usize> {
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::sync::Arc;
-
+fn convert_directory_parallel_with_detailed_progress_at_columns<F: Fn(Progress) + Send + Sync>(src_dir: &Path, dst_dir: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, keep_images: bool, ascii_chars: &[u8], output_mode: &OutputMode, cell_color_mode: CellColorMode, bg_fit_quality: BgFitQuality, palette: Palette, sampling: Sampling, progress_callback: &F, cancel: Option<&CancelToken>) -> Result<(usize, ColorHistogram)> {
     fs::create_dir_all(dst_dir)?;
     let mut pngs: Vec<PathBuf> = WalkDir::new(src_dir).min_depth(1).max_depth(1).into_iter().filter_map(|e| e.ok()).map(|e| e.into_path()).filter(|p| p.extension().map(|e| e == "png").unwrap_or(false)).collect();
     pngs.sort();
 
     let total = pngs.len();
-    let completed = Arc::new(AtomicUsize::new(0));
-    let last_reported_percent = Arc::new(AtomicUsize::new(0));
+    let counter = ProgressCounter::throttled(total);
+    let report = |current: usize| progress_callback(Progress::converting_frames(current, total));
     let background_analysis = background_analysis_for_mode(ascii_chars, cell_color_mode, bg_fit_quality)?;
     let dedup_plan = dedup_buckets(&pngs);
+    let histogram = Mutex::new(ColorHistogram::new());
+    let mut repeats = vec![1u64; pngs.len()];
+    for &(_, representative_idx) in &dedup_plan.duplicates {
+        repeats[representative_idx] += 1;
+    }
 
-    // Report initial progress
-    progress_callback(Progress::converting_frames(0, total));
+    counter.start(report);
 
     dedup_plan.representatives.par_iter().try_for_each(|&idx| -> Result<()> {
         if cancel.is_some_and(|c| c.is_cancelled()) {
@@ -511,20 +689,18 @@ fn convert_directory_parallel_with_detailed_progress_at_columns<F: Fn(Progress)
         }
         let img_path = &pngs[idx];
         let file_stem = file_stem_str(img_path)?;
-        let out_txt = dst_dir.join(format!("{}.txt", file_stem));
-        convert_image_to_ascii_with_analysis(img_path, &out_txt, font_ratio, threshold, bg_threshold, columns, ascii_chars, output_mode, cell_color_mode, bg_fit_quality, background_analysis.as_ref())?;
-
-        // Update progress - throttle to only report every 1% change
-        let current = completed.fetch_add(1, Ordering::Relaxed) + 1;
-        let current_percent = current.checked_mul(100).and_then(|value| value.checked_div(total)).unwrap_or(0);
-        let last_percent = last_reported_percent.load(Ordering::Relaxed);
-
-        // Only report if percentage changed (throttle to ~100 updates max)
-        if current_percent > last_percent || current == total {
-            last_reported_percent.store(current_percent, Ordering::Relaxed);
-            progress_callback(Progress::converting_frames(current, total));
+        if sampling.resume && frame_converted(dst_dir, file_stem, output_mode) {
+            histogram.lock().unwrap_or_else(|e| e.into_inner()).merge_weighted(&converted_histogram(dst_dir, file_stem, output_mode), repeats[idx]);
+            counter.complete_one(report);
+            return Ok(());
         }
+        let out_txt = dst_dir.join(format!("{}.txt", file_stem));
+        let frame_histogram = convert_image_to_ascii_with_analysis(img_path, &out_txt, font_ratio, threshold, bg_threshold, columns, ascii_chars, output_mode, cell_color_mode, bg_fit_quality, palette, sampling, background_analysis.as_ref())?;
+        // Duplicates are copies of their representative, so its colors count once per copy
+        histogram.lock().unwrap_or_else(|e| e.into_inner()).merge_weighted(&frame_histogram, repeats[idx]);
 
+        // Reports only when the whole percentage moves (about 100 updates)
+        counter.complete_one(report);
         Ok(())
     })?;
 
@@ -533,18 +709,10 @@ fn convert_directory_parallel_with_detailed_progress_at_columns<F: Fn(Progress)
             return Err(Cancelled.into());
         }
         copy_duplicate_outputs(dst_dir, &pngs, duplicate_idx, representative_idx, output_mode)?;
-
-        let current = completed.fetch_add(1, Ordering::Relaxed) + 1;
-        let current_percent = current.checked_mul(100).and_then(|value| value.checked_div(total)).unwrap_or(0);
-        let last_percent = last_reported_percent.load(Ordering::Relaxed);
-
-        if current_percent > last_percent || current == total {
-            last_reported_percent.store(current_percent, Ordering::Relaxed);
-            progress_callback(Progress::converting_frames(current, total));
-        }
-
+        counter.complete_one(report);
         Ok(())
     })?;
+    counter.finish(report);
 
     if !keep_images {
         for img_path in &pngs {
@@ -552,12 +720,13 @@ fn convert_directory_parallel_with_detailed_progress_at_columns<F: Fn(Progress)
         }
     }
 
-    Ok(total)
+    Ok((total, histogram.into_inner().unwrap_or_else(|e| e.into_inner())))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cframe::{CframeCompression, CframeFormat, CframeMetadata};
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
     use tempfile::NamedTempFile;
@@ -575,7 +744,7 @@ mod tests {
         token.cancel(); // pre-cancel so the very first frame bails out
 
         // Keep images so cleanup does not affect the cancellation assertion.
-        let err = convert_directory_parallel(dir.path(), dir.path(), 0.5, 20, 20, true, b" .:-=+*#%@", &OutputMode::TextOnly, CellColorMode::ForegroundOnly, BgFitQuality::Fidelity, Some(&token)).expect_err("a pre-cancelled token should make conversion fail");
+        let err = convert_directory_parallel_with_progress(dir.path(), dir.path(), 0.5, 20, 20, true, b" .:-=+*#%@", &OutputMode::TextOnly, CellColorMode::ForegroundOnly, BgFitQuality::Fidelity, Palette::Truecolor, Sampling::default(), None::<fn(usize, usize)>, Some(&token)).expect_err("a pre-cancelled token should make conversion fail");
 
         assert!(crate::is_cancelled_error(&err), "expected Cancelled, got: {err}");
     }
@@ -588,9 +757,33 @@ mod tests {
             image::RgbImage::from_pixel(8, 8, image::Rgb([200, 200, 200])).save(&path).unwrap();
         }
 
-        let total = convert_directory_parallel(dir.path(), dir.path(), 0.5, 20, 20, true, b" .:-=+*#%@", &OutputMode::TextOnly, CellColorMode::ForegroundOnly, BgFitQuality::Fidelity, None).expect("conversion without a token should succeed");
+        let (total, histogram) = convert_directory_parallel_with_progress(dir.path(), dir.path(), 0.5, 20, 20, true, b" .:-=+*#%@", &OutputMode::TextOnly, CellColorMode::ForegroundOnly, BgFitQuality::Fidelity, Palette::Truecolor, Sampling::default(), None::<fn(usize, usize)>, None).expect("conversion without a token should succeed");
+
+        assert_eq!(total, 3);
+        assert!(histogram.is_empty());
+    }
 
+    #[test]
+    fn resumed_directory_conversion_keeps_complete_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        for (i, shade) in [60u8, 120, 180].into_iter().enumerate() {
+            image::RgbImage::from_pixel(8, 8, image::Rgb([shade, shade, shade])).save(dir.path().join(format!("frame_{:04}.png", i))).unwrap();
+        }
+        // Frame 0 was written whole, frame 1 lost its .cframe to the interruption and left a partial one behind
+        fs::write(dir.path().join("frame_0000.txt"), "kept\n").unwrap();
+        fs::write(dir.path().join("frame_0000.cframe"), crate::frame::encode_cframe(2, 1, "AB\n", &[1, 2, 3, 1, 2, 3], None)).unwrap();
+        fs::write(dir.path().join("frame_0001.txt"), "kept\n").unwrap();
+        fs::write(dir.path().join("frame_0001.cframe.part"), b"cut").unwrap();
+
+        let sampling = Sampling::default().with_resume(true);
+        let (total, histogram) = convert_directory_parallel_with_progress(dir.path(), dir.path(), 0.5, 20, 20, true, b" .:-=+*#%@", &OutputMode::TextAndColor, CellColorMode::ForegroundOnly, BgFitQuality::Fidelity, Palette::Truecolor, sampling, None::<fn(usize, usize)>, None).unwrap();
         assert_eq!(total, 3);
+        assert_eq!(fs::read_to_string(dir.path().join("frame_0000.txt")).unwrap(), "kept\n");
+        assert_ne!(fs::read_to_string(dir.path().join("frame_0001.txt")).unwrap(), "kept\n");
+        assert!(read_cframe_to_frame_data(&dir.path().join("frame_0001.cframe")).is_ok() && dir.path().join("frame_0002.cframe").exists());
+        assert!(!dir.path().join("frame_0001.cframe.part").exists());
+        // The kept frame's colors still count
+        assert!(histogram.dominant_colors(8).iter().any(|color| color.rgb == [1, 2, 3]));
     }
 
     #[test]
@@ -621,7 +814,7 @@ mod tests {
 
         let last_progress = Arc::new(AtomicUsize::new(0));
         let progress = Arc::clone(&last_progress);
-        let total = convert_directory_parallel_with_progress(
+        let (total, histogram) = convert_directory_parallel_with_progress(
             src.path(),
             dst.path(),
             0.5,
@@ -632,6 +825,8 @@ mod tests {
             &OutputMode::TextAndColor,
             CellColorMode::ForegroundOnly,
             BgFitQuality::Fidelity,
+            Palette::Truecolor,
+            Sampling::default(),
             Some(move |current, _total| {
                 progress.store(current, Ordering::SeqCst);
             }),
@@ -641,6 +836,9 @@ mod tests {
 
         assert_eq!(total, 4);
         assert_eq!(last_progress.load(Ordering::SeqCst), 4);
+        // Duplicated frames count once per copy
+        let dominant = histogram.dominant_colors(2);
+        assert_eq!((dominant[0].rgb, dominant[0].share, dominant[1].rgb), ([220, 0, 0], 0.75, [0, 220, 0]));
         for ext in ["txt", "cframe"] {
             let original = fs::read(dst.path().join(format!("frame_0000.{ext}"))).unwrap();
             let non_adjacent_duplicate = fs::read(dst.path().join(format!("frame_0002.{ext}"))).unwrap();
@@ -663,6 +861,42 @@ mod tests {
         out
     }
 
+    #[test]
+    fn converted_frame_writes_files_and_ansi() {
+        let frame = AsciiFrameData {ascii_text: "AB\nCD\n".to_string(), width_chars: 2, height_chars: 2, rgb_colors: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12], bg_rgb_colors: Vec::new()};
+        let dir = tempfile::tempdir().unwrap();
+        write_frame_outputs(&frame, &dir.path().join("frame_0001.txt"), &OutputMode::ColorOnly, CellColorMode::ForegroundOnly, Codepage::Unicode, &CframeEncoder::new(Sampling::default(), 0.5, b" .#")).unwrap();
+        assert!(!dir.path().join("frame_0001.txt").exists());
+        assert_eq!(read_cframe_to_frame_data(&dir.path().join("frame_0001.cframe")).unwrap().rgb_colors, frame.rgb_colors);
+
+        // Compressed v2 frames carry the conversion's metadata, read back the same and keep their container through edits
+        let compression = *CframeCompression::ALL.last().unwrap();
+        let sampling = Sampling {cframe_format: CframeFormat::V2 {compression}, ..Sampling::default()}.with_fps(24);
+        write_frame_outputs(&frame, &dir.path().join("frame_0002.txt"), &OutputMode::ColorOnly, CellColorMode::ForegroundOnly, Codepage::Unicode, &CframeEncoder::new(sampling, 0.5, b" .#")).unwrap();
+        let data = fs::read(dir.path().join("frame_0002.cframe")).unwrap();
+        assert_eq!(crate::cframe::metadata(&data).unwrap(), Some(CframeMetadata {fps: Some(24), charset: Some(" .#".into()), font_ratio: Some(0.5)}));
+        assert_eq!(read_cframe_to_frame_data(&dir.path().join("frame_0002.cframe")).unwrap().ascii_text, frame.ascii_text);
+        let erased = erase_cframe_cells(&data, &[(0, 0)], CframeEraseLayer::All).unwrap().unwrap();
+        assert!(crate::cframe::is_v2(&erased));
+        assert_eq!(cframe_bytes_to_frame_data(&erased, "erased").unwrap().ascii_text, " B\nCD\n");
+
+        let mut ansi = Vec::new();
+        write_frame_ansi(&frame, Some(AnsiColor::TrueColor), &mut ansi).unwrap();
+        assert_eq!(String::from_utf8(ansi).unwrap(), "\x1b[H\x1b[38;2;1;2;3mA\x1b[38;2;4;5;6mB\x1b[0m\n\x1b[38;2;7;8;9mC\x1b[38;2;10;11;12mD\x1b[0m\n");
+        let mut plain = Vec::new();
+        write_frame_ansi(&frame, None, &mut plain).unwrap();
+        assert_eq!(plain, b"\x1b[HAB\nCD\n");
+    }
+
+    #[test]
+    fn high_contrast_keeps_glyphs_apart_from_fitted_backgrounds() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(16, 8, |x, y| if (x / 2 + y / 2) % 2 == 0 {image::Rgb([10, 10, 10])} else {image::Rgb([230, 230, 230])}));
+        let frame = image_to_ascii_frame_data(&img, 0.5, 20, 20, Some(8), b" .:-=+*#%@", CellColorMode::FitForegroundBackground, BgFitQuality::Fast, Palette::HighContrast, Sampling::default()).unwrap();
+        assert_eq!(frame.bg_rgb_colors.len(), frame.rgb_colors.len());
+        assert!(frame.bg_rgb_colors.iter().all(|&channel| channel == 0));
+        assert!(frame.rgb_colors.chunks_exact(3).zip(frame.bg_rgb_colors.chunks_exact(3)).all(|(fg, bg)| fg != bg));
+    }
+
     #[test]
     fn cframe_round_trip_without_background() {
         let chars = [b'A', b'B', b'C', b'D'];
@@ -715,6 +949,20 @@ mod tests {
         assert_eq!(frame.bg_rgb_colors, bg);
     }
 
+    #[test]
+    fn braille_cframe_round_trips_its_dot_patterns() {
+        let text = "\u{2800}\u{28ff}\n";
+        let tmp = NamedTempFile::new().unwrap();
+        write_cframe_binary_buffered(2, 1, text, &[1, 2, 3, 4, 5, 6], Some(&[7, 8, 9, 10, 11, 12]), tmp.path()).unwrap();
+        let raw = fs::read(tmp.path()).unwrap();
+        assert_eq!(raw[16], CFRAME_EXT_FLAG_HAS_BG | crate::frame::CFRAME_EXT_FLAG_BRAILLE);
+        assert_eq!(read_cframe_to_frame_data(tmp.path()).unwrap().ascii_text, text);
+
+        // Erasing a braille cell lowers its dots instead of writing a space
+        let erased = erase_cframe_cells(&raw, &[(0, 1)], CframeEraseLayer::Text).unwrap().unwrap();
+        assert_eq!(read_cframe_to_frame_data_from_bytes_for_test(&erased).ascii_text, "\u{2800}\u{2800}\n");
+    }
+
     #[test]
     fn cframe_reads_legacy_bg_without_flag_byte() {
         // Simulate a file written by the pre-flag-byte build: no leading flag,
@@ -737,7 +985,7 @@ mod tests {
 
     #[test]
     fn erase_cframe_text_preserves_background() {
-        let text = ascii_content_for(2, 1, &[b'A', b'B']);
+        let text = ascii_content_for(2, 1, b"AB");
         let rgb = vec![10, 20, 30, 40, 50, 60];
         let bg = vec![100, 110, 120, 130, 140, 150];
         let tmp = NamedTempFile::new().unwrap();
@@ -753,7 +1001,7 @@ mod tests {
 
     #[test]
     fn erase_cframe_background_preserves_text_and_foreground() {
-        let text = ascii_content_for(2, 1, &[b'A', b'B']);
+        let text = ascii_content_for(2, 1, b"AB");
         let rgb = vec![10, 20, 30, 40, 50, 60];
         let bg = vec![100, 110, 120, 130, 140, 150];
         let tmp = NamedTempFile::new().unwrap();
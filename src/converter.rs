@@ -0,0 +1,2375 @@
+//! [`AsciiConverter`], which runs conversions of images, frame directories and videos, and its builder.
+
+#[cfg(feature = "cli")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+#[cfg(feature = "cli")]
+use std::io::Write;
+#[cfg(feature = "cli")]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "cli")]
+use walkdir::WalkDir;
+
+#[cfg(feature = "cli")]
+use crate::config::default_ascii_chars;
+#[cfg(feature = "cli")]
+use crate::error::Error;
+use crate::error::{Context, Result};
+#[cfg(feature = "cli")]
+use crate::progress::ProgressCounter;
+use crate::audio;
+#[cfg(feature = "cli")]
+use crate::{cframe, compare, convert, estimate, events, frame, refine, render, spritesheet, stream, video};
+use crate::report;
+#[cfg(any(feature = "osc", feature = "mqtt"))]
+use crate::show_control;
+use crate::{Attribution, ColorPalette, ReportFormat};
+#[cfg(feature = "cli")]
+use crate::{AppConfig, AsciiFrame, BudgetedConversion, CancelToken, Cancelled, CellColorMode, ColorHistogram, ConversionEstimate, ConversionPass, ConversionOptions, DepthOptions, FfmpegConfig, FrameReader, GifOptions, MediaInfo, OutputMode, OutputSink, Preset, PreviewOptions, Profile, Progress, ProgressPhase, TimeBudget, ToVideoOptions, TwoStageConversion, UpscalePolicy, VideoOptions};
+
+/// Result of a conversion operation, containing metadata about the conversion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionResult {
+    /// Number of frames generated
+    pub frame_count: usize,
+    /// Target columns (width) used
+    pub columns: u32,
+    /// Font ratio used
+    pub font_ratio: f32,
+    /// Luminance threshold used
+    pub luminance: u8,
+    /// FPS used (for video conversions)
+    pub fps: Option<u32>,
+    /// Output mode used
+    pub output_mode: String,
+    /// Whether audio was extracted
+    pub audio_extracted: bool,
+    /// Path to the output directory
+    pub output_dir: PathBuf,
+    /// Background color name
+    pub background_color: String,
+    /// Foreground color name
+    pub color: String,
+    /// Whether per-cell background fitting was enabled
+    pub fit_cell_backgrounds: bool,
+    /// Background fitting implementation: "off", "legacy", or "optimized"
+    #[serde(default = "default_cell_background_mode")]
+    pub cell_background_mode: String,
+    /// Background fitting analysis resolution: "fidelity" or "fast"
+    #[serde(default = "default_bg_fit_quality")]
+    pub bg_fit_quality: String,
+    /// Resolved background luminance threshold actually used by the bg-fit pass. Equal to `luminance` unless an explicit override was set via `ConversionOptions::with_bg_luminance`.
+    pub bg_luminance: u8,
+    /// Character ramp used for glyph selection, from darkest to lightest.
+    pub ascii_chars: String,
+    /// How the muxed audio was lined up with the frames (video output with audio only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_sync: Option<audio::AudioSync>,
+    /// Average and dominant cell colors, when the output had colors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colors: Option<ColorPalette>,
+    /// Seed of the conversion's random choices ([`ConversionOptions::seed`])
+    #[serde(default)]
+    pub seed: u64,
+    /// Creator and license from the config's [`AppConfig::attribution`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<Attribution>,
+}
+
+fn default_cell_background_mode() -> String {
+    "off".to_string()
+}
+
+fn default_bg_fit_quality() -> String {
+    "fidelity".to_string()
+}
+
+/// Serializable details written to `details.toml`
+#[derive(Debug, Serialize)]
+pub(crate) struct Details {
+    version: String,
+    frames: usize,
+    luminance: u8,
+    font_ratio: f32,
+    columns: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fps: Option<u32>,
+    output: String,
+    audio: bool,
+    background_color: String,
+    color: String,
+    fit_cell_backgrounds: bool,
+    cell_background_mode: String,
+    bg_fit_quality: String,
+    bg_luminance: u8,
+    ascii_chars: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    colors: Option<ColorPalette>,
+    seed: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attribution: Option<Attribution>,
+}
+
+impl ConversionResult {
+    pub(crate) fn to_details(&self) -> Details {
+        Details {version: env!("CARGO_PKG_VERSION").to_string(), frames: self.frame_count, luminance: self.luminance, font_ratio: self.font_ratio, columns: self.columns, fps: self.fps, output: self.output_mode.clone(), audio: self.audio_extracted, background_color: self.background_color.clone(), color: self.color.clone(), fit_cell_backgrounds: self.fit_cell_backgrounds, cell_background_mode: self.cell_background_mode.clone(), bg_fit_quality: self.bg_fit_quality.clone(), bg_luminance: self.bg_luminance, ascii_chars: self.ascii_chars.clone(), colors: self.colors.clone(), seed: self.seed, attribution: self.attribution.clone()}
+    }
+
+    /// Write the conversion details to a details.toml file in the output directory
+    pub fn write_details_file(&self) -> Result<PathBuf> {
+        let details_path = self.output_dir.join("details.toml");
+        let toml_string = toml::to_string_pretty(&self.to_details()).context("serializing details to TOML")?;
+        fs::write(&details_path, &toml_string).with_context(|| format!("writing details file to {}", details_path.display()))?;
+
+        Ok(details_path)
+    }
+
+    /// Get the details as a TOML string (without writing to file)
+    pub fn to_details_string(&self) -> String {
+        toml::to_string_pretty(&self.to_details()).expect("failed to serialize details to TOML")
+    }
+
+    /// Write the conversion report in `format` to `details.<ext>` in the output directory
+    pub fn write_report(&self, format: &ReportFormat) -> Result<PathBuf> {
+        report::write_report(self, format)
+    }
+
+    /// Get the conversion report in `format` as a string (without writing to file)
+    pub fn render_report(&self, format: &ReportFormat) -> Result<String> {
+        report::render_report(self, format)
+    }
+}
+
+/// Running ffmpeg encode of a video sink; killed on drop unless it was finished.
+#[cfg(feature = "cli")]
+struct SinkEncoder {
+    atlas: render::GlyphAtlas,
+    theme: render::Theme,
+    child: Option<std::process::Child>,
+    stdin: Option<std::process::ChildStdin>,
+}
+
+#[cfg(feature = "cli")]
+impl Drop for SinkEncoder {
+    fn drop(&mut self) {
+        drop(self.stdin.take());
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Memory video renders keep their frames within when [`ConverterLimits::memory_budget`] is not set
+pub const DEFAULT_MEMORY_BUDGET: u64 = 512 * 1024 * 1024;
+
+/// Most frames converted or read as one batch while rendering a video
+#[cfg(feature = "cli")]
+const MAX_BATCH_FRAMES: usize = 100;
+
+/// Batches alive at once while converting a video: one converting, two queued and one being drawn
+#[cfg(feature = "cli")]
+const PIPELINE_BATCHES: u64 = 4;
+
+/// Safety limits checked before a conversion does any work, e.g. for converters fed untrusted uploads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConverterLimits {
+    /// Largest input file, in bytes
+    pub max_input_bytes: Option<u64>,
+    /// Most frames a video or directory conversion may produce, checked once frames are extracted
+    pub max_frames: Option<usize>,
+    /// Bytes a video render may hold in frames at once: converted frames waiting to be drawn, decoded
+    /// source frames and the drawing buffer ([`DEFAULT_MEMORY_BUDGET`] when unset). Frames are
+    /// batched up to 100 at a time within it, and drawn one by one as they convert when even a few don't fit.
+    pub memory_budget: Option<u64>,
+}
+
+impl ConverterLimits {
+    /// Set the largest input file, in bytes
+    pub fn with_max_input_bytes(mut self, bytes: u64) -> Self {
+        self.max_input_bytes = Some(bytes);
+        self
+    }
+
+    /// Set the most frames a conversion may produce
+    pub fn with_max_frames(mut self, frames: usize) -> Self {
+        self.max_frames = Some(frames);
+        self
+    }
+
+    /// Set the bytes a video render may hold in frames at once
+    pub fn with_memory_budget(mut self, bytes: u64) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /// Frames per batch so that `batches` batches of frames of `frame_bytes` each, on top of
+    /// `fixed_bytes`, stay within the memory budget; 1 when not even that fits
+    #[cfg(feature = "cli")]
+    pub(crate) fn batch_size(&self, frame_bytes: u64, fixed_bytes: u64, batches: u64) -> usize {
+        let budget = self.memory_budget.unwrap_or(DEFAULT_MEMORY_BUDGET).saturating_sub(fixed_bytes);
+        (budget / (frame_bytes.max(1) * batches)).clamp(1, MAX_BATCH_FRAMES as u64) as usize
+    }
+}
+
+/// Fluent configuration of an [`AsciiConverter`], validated once by [`build`](Self::build).
+///
+/// ```no_run
+/// use cascii::{AsciiConverter, ConverterLimits, FfmpegConfig};
+///
+/// # fn main() -> cascii::Result<()> {
+/// let converter = AsciiConverter::builder()
+///     .with_config_file("cascii.json")
+///     .with_ffmpeg_config(FfmpegConfig::new().with_ffmpeg("/opt/ffmpeg/bin/ffmpeg"))
+///     .with_threads(4)
+///     .with_cache_dir("/var/cache/cascii")
+///     .with_limits(ConverterLimits::default().with_max_frames(10_000))
+///     .with_event_log_file("events.jsonl")
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "cli")]
+#[derive(Default)]
+pub struct AsciiConverterBuilder {
+    config: Option<AppConfig>,
+    config_file: Option<PathBuf>,
+    ffmpeg_config: FfmpegConfig,
+    threads: Option<usize>,
+    thread_pool: Option<std::sync::Arc<rayon::ThreadPool>>,
+    cache_dir: Option<PathBuf>,
+    limits: ConverterLimits,
+    cancel_token: Option<CancelToken>,
+    resume: bool,
+    report_format: ReportFormat,
+    event_log: Option<events::EventLog>,
+    event_log_file: Option<PathBuf>,
+}
+
+#[cfg(feature = "cli")]
+impl AsciiConverterBuilder {
+    /// Use this configuration instead of the built-in default
+    pub fn with_config(mut self, config: AppConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Load the configuration from a JSON file when building
+    pub fn with_config_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Set custom ffmpeg/ffprobe paths
+    pub fn with_ffmpeg_config(mut self, ffmpeg_config: FfmpegConfig) -> Self {
+        self.ffmpeg_config = ffmpeg_config;
+        self
+    }
+
+    /// Run conversions on a dedicated pool of `threads` worker threads instead of rayon's global pool
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Run conversions on an existing pool, shared with the rest of the application
+    pub fn with_thread_pool(mut self, pool: std::sync::Arc<rayon::ThreadPool>) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Keep intermediate frames under `dir` instead of the system temp directory; created when building
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Refuse inputs beyond these limits
+    pub fn with_limits(mut self, limits: ConverterLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Attach a [`CancelToken`] (see [`AsciiConverter::with_cancel_token`])
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel_token = Some(token);
+        self
+    }
+
+    /// Keep the frames an interrupted run already wrote (see [`AsciiConverter::with_resume`])
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Choose the report written next to converted frames (default: `details.toml`)
+    pub fn with_report_format(mut self, format: ReportFormat) -> Self {
+        self.report_format = format;
+        self
+    }
+
+    /// Append an `events.jsonl` record of every conversion (see [`events`])
+    pub fn with_event_log(mut self, event_log: events::EventLog) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    /// Open an event log at `path` when building
+    pub fn with_event_log_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.event_log_file = Some(path.into());
+        self
+    }
+
+    /// Validate the settings, load the config file, start the thread pool, create the cache
+    /// directory and open the event log.
+    pub fn build(self) -> Result<AsciiConverter> {
+        if self.threads.is_some() && self.thread_pool.is_some() {
+            return Err(Error::InvalidOptions("set either a thread count or a thread pool, not both".into()));
+        }
+        if self.event_log.is_some() && self.event_log_file.is_some() {
+            return Err(Error::InvalidOptions("set either an event log or an event log file, not both".into()));
+        }
+        if self.limits.max_input_bytes == Some(0) || self.limits.max_frames == Some(0) || self.limits.memory_budget == Some(0) {
+            return Err(Error::InvalidOptions("converter limits must be greater than zero".into()));
+        }
+
+        let mut converter = match (self.config, self.config_file) {
+            (Some(_), Some(_)) => return Err(Error::InvalidOptions("set either a config or a config file, not both".into())),
+            (Some(config), None) => AsciiConverter::with_config(config)?,
+            (None, Some(path)) => AsciiConverter::from_config_file(&path)?,
+            (None, None) => AsciiConverter::new(),
+        };
+        converter.thread_pool = match self.threads {
+            Some(0) => return Err(Error::InvalidOptions("thread count must be at least 1".into())),
+            Some(threads) => Some(std::sync::Arc::new(rayon::ThreadPoolBuilder::new().num_threads(threads).build().map_err(|err| Error::Other(format!("starting conversion thread pool: {}", err)))?)),
+            None => self.thread_pool,
+        };
+        if let Some(dir) = &self.cache_dir {
+            fs::create_dir_all(dir).with_context(|| format!("creating cache directory {}", dir.display()))?;
+        }
+        converter.event_log = match self.event_log_file {
+            Some(path) => Some(events::EventLog::open(&path)?),
+            None => self.event_log,
+        };
+        Ok(AsciiConverter {ffmpeg_config: self.ffmpeg_config, cancel_token: self.cancel_token, resume: self.resume, report_format: self.report_format, cache_dir: self.cache_dir, limits: self.limits, ..converter})
+    }
+}
+
+/// Main converter struct for ASCII art generation
+#[cfg(feature = "cli")]
+#[derive(Clone)]
+pub struct AsciiConverter {
+    config: AppConfig,
+    ffmpeg_config: FfmpegConfig,
+    pub(crate) cancel_token: Option<CancelToken>,
+    resume: bool,
+    report_format: ReportFormat,
+    event_log: Option<events::EventLog>,
+    thread_pool: Option<std::sync::Arc<rayon::ThreadPool>>,
+    cache_dir: Option<PathBuf>,
+    limits: ConverterLimits,
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    show_control: Option<std::sync::Arc<show_control::ShowControl>>,
+}
+
+#[cfg(feature = "cli")]
+impl AsciiConverter {
+    /// Create a new converter with default configuration
+    pub fn new() -> Self {
+        Self {config: AppConfig::default(), ffmpeg_config: FfmpegConfig::default(), cancel_token: None, resume: false, report_format: ReportFormat::Toml, event_log: None, thread_pool: None, cache_dir: None, limits: ConverterLimits::default(), #[cfg(any(feature = "osc", feature = "mqtt"))] show_control: None}
+    }
+
+    /// Start configuring a converter with [`AsciiConverterBuilder`]
+    pub fn builder() -> AsciiConverterBuilder {
+        AsciiConverterBuilder::default()
+    }
+
+    /// Create a converter with custom configuration
+    pub fn with_config(config: AppConfig) -> Result<Self> {
+        // Validate ASCII characters
+        if !config.uses_only_ascii_chars() {
+            return Err(Error::InvalidOptions("Config contains non-ASCII characters in ascii_chars field. This will cause corrupted output. Please use only ASCII characters.".into()));
+        }
+        Ok(Self {config, ..Self::new()})
+    }
+
+    /// Set custom ffmpeg/ffprobe paths for this converter
+    ///
+    /// Use this when bundling ffmpeg with your application:
+    /// ```no_run
+    /// use cascii::{AsciiConverter, FfmpegConfig};
+    ///
+    /// let converter = AsciiConverter::new()
+    ///     .with_ffmpeg_config(FfmpegConfig::new()
+    ///         .with_ffmpeg("/path/to/ffmpeg")
+    ///         .with_ffprobe("/path/to/ffprobe"));
+    /// ```
+    pub fn with_ffmpeg_config(mut self, ffmpeg_config: FfmpegConfig) -> Self {
+        self.ffmpeg_config = ffmpeg_config;
+        self
+    }
+
+    /// Attach a [`CancelToken`] so an in-flight conversion can be interrupted.
+    ///
+    /// Hold a clone of the token elsewhere (e.g. a job registry) and call
+    /// [`CancelToken::cancel`] to stop the work. Without a token, conversions
+    /// run to completion as before.
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, CancelToken};
+    ///
+    /// let token = CancelToken::new();
+    /// let converter = AsciiConverter::new().with_cancel_token(token.clone());
+    /// // hand `token` to another thread; calling `token.cancel()` stops the run.
+    /// ```
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel_token = Some(token);
+        self
+    }
+
+    /// Pick up a frame directory where an interrupted run left off: frames whose outputs are already
+    /// in the output directory are kept instead of converted again. Frame files are written under a
+    /// temporary name and renamed once complete, so every one that exists is whole. Applies to
+    /// conversions into a frame directory from extracted frames or an image directory; piped
+    /// (`pipe_frames`) and video conversions start over.
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions, VideoOptions};
+    /// use std::path::Path;
+    ///
+    /// let converter = AsciiConverter::new().with_resume(true);
+    /// converter.convert_video(Path::new("in.mp4"), Path::new("frames"), &VideoOptions::default(), &ConversionOptions::default(), false)?;
+    /// # Ok::<(), cascii::Error>(())
+    /// ```
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Choose the report written next to converted frames (default: `details.toml`).
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ReportFormat};
+    ///
+    /// let converter = AsciiConverter::new().with_report_format(ReportFormat::Html);
+    /// ```
+    pub fn with_report_format(mut self, format: ReportFormat) -> Self {
+        self.report_format = format;
+        self
+    }
+
+    /// Append an `events.jsonl` record of every conversion run by this converter.
+    ///
+    /// See [`events`] for what is recorded.
+    pub fn with_event_log(mut self, event_log: events::EventLog) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    /// Send a `progress` event to `show_control` with every progress update of conversions that
+    /// report progress (see [`show_control`]).
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    pub fn with_show_control(mut self, show_control: std::sync::Arc<show_control::ShowControl>) -> Self {
+        self.show_control = Some(show_control);
+        self
+    }
+
+    /// Wrap `progress_callback` so every update also goes to the event log and the show controller
+    fn observe<F: Fn(Progress) + Send + Sync>(&self, progress_callback: F) -> impl Fn(Progress) + Send + Sync {
+        #[cfg(any(feature = "osc", feature = "mqtt"))]
+        let progress_callback = {
+            let show_control = self.show_control.clone();
+            move |progress: Progress| {
+                if let Some(show_control) = show_control.as_ref() {
+                    show_control.observe(&progress);
+                }
+                progress_callback(progress)
+            }
+        };
+        events::observe(self.event_log.as_ref(), progress_callback)
+    }
+
+    /// Run `run` on the converter's thread pool after checking `input` against the limits, and record
+    /// start, completion, cancellation or failure in the event log, if one is attached.
+    fn logged<T: Send>(&self, operation: &str, input: &Path, output: &Path, frames: impl Fn(&T) -> usize, run: impl FnOnce() -> Result<T> + Send) -> Result<T> {
+        profile_span!("conversion", operation);
+        self.check_input(input)?;
+        let run = || match &self.thread_pool {
+            Some(pool) => pool.install(run),
+            None => run(),
+        };
+        let Some(events) = self.event_log.as_ref() else {return run()};
+        events.record(events::Event::Started {operation: operation.to_string(), input: input.to_path_buf(), output: output.to_path_buf()});
+        let result = run();
+        match &result {
+            Ok(value) => events.record(events::Event::Completed {frames: Some(frames(value))}),
+            Err(Error::Cancelled) => events.record(events::Event::Cancelled),
+            Err(err) => events.record(events::Event::Failed {error: format!("{:#}", err)}),
+        }
+        result
+    }
+
+    /// Refuse an input file larger than [`ConverterLimits::max_input_bytes`].
+    fn check_input(&self, input: &Path) -> Result<()> {
+        let Some(max) = self.limits.max_input_bytes else {return Ok(())};
+        match fs::metadata(input) {
+            Ok(metadata) if metadata.is_file() && metadata.len() > max => Err(Error::LimitExceeded(format!("{} is {} bytes, over the {} byte input limit", input.display(), metadata.len(), max))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Refuse a conversion producing more than [`ConverterLimits::max_frames`].
+    fn check_frame_count(&self, frames: usize) -> Result<()> {
+        match self.limits.max_frames {
+            Some(max) if frames > max => Err(Error::LimitExceeded(format!("{} frames exceed the limit of {}", frames, max))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Refuse converting a directory holding more than [`ConverterLimits::max_frames`] PNG frames.
+    fn check_frame_dir(&self, dir: &Path) -> Result<()> {
+        if self.limits.max_frames.is_none() {
+            return Ok(());
+        }
+        self.check_frame_count(WalkDir::new(dir).min_depth(1).max_depth(1).into_iter().filter_map(|e| e.ok()).filter(|e| e.path().extension().is_some_and(|ext| ext == "png")).count())
+    }
+
+    /// The attribution line drawn in the corner of rendered frames, when the config asks for one
+    fn stamp(&self) -> Option<String> {
+        self.config.attribution.as_ref().filter(|attribution| attribution.overlay).and_then(Attribution::overlay_text)
+    }
+
+    /// Error for a failed ffmpeg encode. A terminal Ctrl-C also kills the encoder, so once the
+    /// token is cancelled the failure is reported as [`Cancelled`].
+    fn encoder_error(&self, context: String, stderr: &[u8]) -> Error {
+        if self.cancel_token.as_ref().is_some_and(|token| token.is_cancelled()) {Error::Cancelled} else {Error::ffmpeg(context, stderr)}
+    }
+
+    /// The audio of `input` between the requested start and end, to be copied by the encoder.
+    fn source_audio(input: &Path, video_opts: &VideoOptions) -> render::AudioSource {
+        let start_secs = video_opts.start.as_deref().filter(|start| !start.is_empty()).map_or(0.0, video::parse_timestamp);
+        let duration_secs = video_opts.end.as_deref().filter(|end| !end.is_empty()).map(|end| video::parse_timestamp(end) - start_secs).filter(|duration| *duration > 0.0);
+        render::AudioSource {path: input.to_path_buf(), start_secs, duration_secs, copy: true}
+    }
+
+    /// Record a warning in the event log, if one is attached.
+    fn warn(&self, message: String) {
+        if let Some(events) = self.event_log.as_ref() {
+            events.warn(message);
+        }
+    }
+
+    /// Record the [`UpscalePolicy::Warn`] warning if `columns` would upscale `input`.
+    fn warn_on_upscale(&self, input: &Path, columns: Option<u32>, options: &ConversionOptions) {
+        if let Some(message) = columns.and_then(|columns| self.upscale_warning(input, columns, options)) {
+            self.warn(message);
+        }
+    }
+
+    /// Compare `audio` with `frames` rendered at `fps` and decide how to line them up, turning off
+    /// stream copy where it can't work. Corrections are recorded as warnings; if the audio can't be
+    /// measured it is muxed as is.
+    fn plan_audio_sync(&self, audio: Option<&mut render::AudioSource>, frames: usize, fps: u32, to_video_opts: &ToVideoOptions) -> Result<Option<audio::AudioSync>> {
+        let Some(audio) = audio else {return Ok(None)};
+        if audio.copy {
+            let codec = video::probe_audio_codec(&audio.path, &self.ffmpeg_config)?.ok_or_else(|| Error::NotFound(format!("{} has no audio stream", audio.path.display())))?;
+            if !render::container_accepts_audio(&to_video_opts.output_path, &codec) {
+                audio.copy = false;
+                self.warn(format!("{} audio can't be copied into {}; encoding it to AAC", codec, to_video_opts.output_path.display()));
+            }
+        }
+        let available = match video::get_media_duration_secs(&audio.path, &self.ffmpeg_config) {
+            Ok(total) => (total - audio.start_secs).max(0.0),
+            Err(err) => {
+                self.warn(format!("could not measure the audio length, skipping the sync check: {:#}", err));
+                return Ok(None);
+            }
+        };
+        let audio_secs = audio.duration_secs.map_or(available, |duration| duration.min(available));
+        let sync = audio::AudioSync::plan(frames, fps, audio_secs, to_video_opts.audio_offset.unwrap_or(0.0), to_video_opts.audio_padding);
+        if audio.copy && matches!(sync.correction, audio::SyncCorrection::PaddedAudio | audio::SyncCorrection::LoopedAudio) {
+            audio.copy = false;
+            self.warn("the audio is shorter than the video and copied audio can't be extended; encoding it to AAC".to_string());
+        }
+        if let Some(message) = sync.describe() {
+            self.warn(message);
+        }
+        Ok(Some(sync))
+    }
+
+    /// Frame-count callback that writes conversion checkpoints to the event log.
+    fn frame_checkpoints(&self) -> Option<impl Fn(usize, usize) + Send + Sync> {
+        self.event_log.clone().map(|events| move |completed, total| events.record_progress(&Progress::converting_frames(completed, total)))
+    }
+
+    /// Load configuration from a file
+    pub fn from_config_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("reading config {}", path.display()))?;
+        let config: AppConfig = serde_json::from_str(&text).context("parsing config json")?;
+
+        if !config.uses_only_ascii_chars() {
+            return Err(Error::InvalidOptions(format!("Config file {} contains non-ASCII characters in ascii_chars field. This will cause corrupted output. Please use only ASCII characters.", path.display())));
+        }
+
+        Ok(Self {config, ..Self::new()})
+    }
+
+    /// Get the current configuration
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// Get the current ffmpeg configuration
+    pub fn ffmpeg_config(&self) -> &FfmpegConfig {
+        &self.ffmpeg_config
+    }
+
+    /// Convert a single image to ASCII art
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Path to input image (PNG, JPG)
+    /// * `output` - Path to output text file
+    /// * `options` - Conversion options
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions};
+    /// use std::path::Path;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let converter = AsciiConverter::new();
+    /// let options = ConversionOptions::default().with_columns(200);
+    /// converter.convert_image(Path::new("image.png"), Path::new("output.txt"), &options)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn convert_image(&self, input: &Path, output: &Path, options: &ConversionOptions) -> Result<()> {
+        self.check_input(input)?;
+        self.warn_on_upscale(input, options.columns, options);
+        let ascii_chars = &options.ramp()[..];
+        convert::convert_image_to_ascii(input, output, options.font_ratio, options.luminance, options.resolve_bg_threshold(), options.columns, ascii_chars, &options.output_mode, options.cell_color_mode, options.bg_fit_quality, options.palette, frame::Sampling::from_options(options))
+    }
+
+    /// Convert an image to ASCII art modulated by an aligned depth map (see [`DepthMap`](crate::DepthMap)), writing the
+    /// same `.txt`/`.cframe` files as [`Self::convert_image`].
+    pub fn convert_image_with_depth(&self, input: &Path, depth: &Path, output: &Path, options: &ConversionOptions, depth_options: DepthOptions) -> Result<()> {
+        self.check_input(input)?;
+        self.warn_on_upscale(input, options.columns, options);
+        let ascii_chars = &options.ramp()[..];
+        let depth = image::open(depth).with_context(|| format!("opening depth map {}", depth.display()))?;
+        let mut frame = convert::image_to_ascii_frame_data(&convert::open_image(input)?, options.font_ratio, options.luminance, options.resolve_bg_threshold(), options.columns, ascii_chars, options.cell_color_mode, options.bg_fit_quality, options.palette, frame::Sampling::from_options(options))?;
+        frame.apply_depth(&depth, ascii_chars, depth_options);
+        convert::write_frame_outputs(&frame, output, &options.output_mode, options.cell_color_mode, options.codepage, &cframe::CframeEncoder::new(frame::Sampling::from_options(options), options.font_ratio, ascii_chars))
+    }
+
+    /// The warning [`UpscalePolicy::Warn`] asks for when converting `input` at `columns` would upscale
+    /// it, or `None` under any other policy, when the source is wide enough or when it can't be measured.
+    /// Image widths are read from the file header, video widths with ffprobe.
+    pub fn upscale_warning(&self, input: &Path, columns: u32, options: &ConversionOptions) -> Option<String> {
+        if options.upscale != UpscalePolicy::Warn {
+            return None;
+        }
+        let width = image::image_dimensions(input).map(|(width, _)| width).ok().or_else(|| video::probe_video_width(input, &self.ffmpeg_config).ok().flatten())?;
+        options.upscale.warning(columns, width / frame::Sampling::from_options(options).views())
+    }
+
+    /// Probe the size of an image, or the size, frame rate and duration of a video (with ffprobe)
+    pub fn probe_media(&self, input: &Path) -> Result<MediaInfo> {
+        match image::image_dimensions(input) {
+            Ok((width, height)) => Ok(MediaInfo::image(width, height)),
+            Err(_) => video::probe_media_info(input, &self.ffmpeg_config),
+        }
+    }
+
+    /// Convert image to ASCII string (without writing to file)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions};
+    /// use std::path::Path;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let converter = AsciiConverter::new();
+    /// let options = ConversionOptions::default();
+    /// let ascii_art = converter.image_to_string(Path::new("image.png"), &options)?;
+    /// println!("{}", ascii_art);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn image_to_string(&self, input: &Path, options: &ConversionOptions) -> Result<String> {
+        self.check_input(input)?;
+        self.convert_dynamic_image(&convert::open_image(input)?, options)
+    }
+
+    /// Convert an already decoded image to an ASCII string, for callers holding screenshots,
+    /// generated frames or camera buffers that never touch the disk.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cascii::{AsciiConverter, ConversionOptions};
+    /// use image::{DynamicImage, RgbImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let img = DynamicImage::ImageRgb8(RgbImage::from_fn(64, 32, |x, _| image::Rgb([(x * 4) as u8; 3])));
+    /// let ascii_art = AsciiConverter::new().convert_dynamic_image(&img, &ConversionOptions::default().with_columns(16))?;
+    /// assert_eq!(ascii_art.lines().next().unwrap().len(), 16);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn convert_dynamic_image(&self, img: &image::DynamicImage, options: &ConversionOptions) -> Result<String> {
+        let ascii_chars = &options.ramp()[..];
+        convert::image_to_ascii_string(img, options.font_ratio, options.luminance, options.columns, ascii_chars, options.bg_fit_quality, frame::Sampling::from_options(options))
+    }
+
+    /// Convert an image file to an in-memory [`AsciiFrame`], with its per-cell foreground colors and,
+    /// in the background-fitting [`CellColorMode`]s, its background colors. This is what
+    /// [`convert_image`](Self::convert_image) writes to `.txt`/`.cframe`, for GUIs that draw the cells
+    /// themselves.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, CellColorMode, ConversionOptions};
+    /// use std::path::Path;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let options = ConversionOptions::default().with_columns(80).with_cell_color_mode(CellColorMode::FitForegroundBackground);
+    /// let frame = AsciiConverter::new().image_to_frame(Path::new("input.png"), &options)?;
+    /// for cell in frame.cells() {
+    ///     let (_fg, _bg) = (cell.fg, cell.bg);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn image_to_frame(&self, input: &Path, options: &ConversionOptions) -> Result<AsciiFrame> {
+        self.check_input(input)?;
+        self.convert_dynamic_image_to_frame(&convert::open_image(input)?, options)
+    }
+
+    /// Convert an image to ASCII art and render that back to a PNG at `output`, with glyphs
+    /// `font_size` pixels tall, as [`render_frame_to_image`](crate::render_frame_to_image) draws them. Cells keep their colors
+    /// unless `options.output_mode` is [`OutputMode::TextOnly`], which renders white on black.
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions, OutputMode};
+    /// use std::path::Path;
+    ///
+    /// let options = ConversionOptions::default().with_columns(120).with_output_mode(OutputMode::TextAndColor);
+    /// AsciiConverter::new().convert_image_to_ascii_png(Path::new("input.png"), Path::new("ascii.png"), &options, 12.0).unwrap();
+    /// ```
+    pub fn convert_image_to_ascii_png(&self, input: &Path, output: &Path, options: &ConversionOptions, font_size: f32) -> Result<()> {
+        self.warn_on_upscale(input, options.columns, options);
+        let frame = self.image_to_frame(input, options)?;
+        let image = render::render_frame_to_image(&frame, font_size, options.output_mode != OutputMode::TextOnly)?;
+        image.save_with_format(output, image::ImageFormat::Png).with_context(|| format!("writing {}", output.display()))
+    }
+
+    /// Convert an already decoded image to an in-memory [`AsciiFrame`], like
+    /// [`image_to_frame`](Self::image_to_frame).
+    pub fn convert_dynamic_image_to_frame(&self, img: &image::DynamicImage, options: &ConversionOptions) -> Result<AsciiFrame> {
+        let ascii_chars = &options.ramp()[..];
+        convert::image_to_ascii_frame_data(img, options.font_ratio, options.luminance, options.resolve_bg_threshold(), options.columns, ascii_chars, options.cell_color_mode, options.bg_fit_quality, options.palette, frame::Sampling::from_options(options)).map(AsciiFrame::from)
+    }
+
+    /// Convert live frames from a webcam or capture device as they arrive and hand them to `sink`,
+    /// until the sink returns `false` (or its receiver is dropped), the cancel token fires or the
+    /// device stops. `device` is the ffmpeg input of the device: `/dev/video0` for `v4l2`, an
+    /// index such as `0` for `avfoundation`, `video=<name>` for `dshow`. See [`stream`].
+    pub fn stream_device(&self, device: &str, stream_opts: &stream::StreamOptions, conv_opts: &ConversionOptions, mut sink: impl stream::StreamSink) -> Result<stream::StreamStats> {
+        let mut command = std::process::Command::new(self.ffmpeg_config.ffmpeg_cmd());
+        command.args(stream::capture_args(device, stream_opts)?).stdin(std::process::Stdio::null()).stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::piped());
+        if let Some(events) = self.event_log.as_ref() {
+            events.record_command(&command);
+        }
+        let mut child = command.spawn().map_err(|err| Error::spawn(self.ffmpeg_config.ffmpeg_cmd(), err))?;
+        let stdout = child.stdout.take().ok_or_else(|| Error::Other("failed to open ffmpeg stdout pipe".into()))?;
+        let streamed = stream::stream_frames(stdout, stream_opts.width, stream_opts.height, |image| self.convert_dynamic_image_to_frame(image, conv_opts), &mut sink, self.cancel_token.as_ref());
+        // ffmpeg is still capturing when the stream was stopped here; when it ended on its own, say why
+        if child.try_wait()?.is_none() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return streamed;
+        }
+        let output = child.wait_with_output().context("waiting for ffmpeg")?;
+        if !output.status.success() {
+            return Err(Error::ffmpeg(format!("capturing from {} failed", device), &output.stderr));
+        }
+        streamed
+    }
+
+    /// Decode an encoded image (PNG or JPEG) from memory and convert it to an ASCII string, e.g. for
+    /// uploads received over HTTP. [`ConverterLimits::max_input_bytes`] applies to the buffer.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let upload: Vec<u8> = std::fs::read("upload.png")?;
+    /// let ascii_art = AsciiConverter::new().image_bytes_to_string(&upload, &ConversionOptions::default())?;
+    /// println!("{}", ascii_art);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn image_bytes_to_string(&self, bytes: &[u8], options: &ConversionOptions) -> Result<String> {
+        if let Some(max) = self.limits.max_input_bytes.filter(|&max| bytes.len() as u64 > max) {
+            return Err(Error::LimitExceeded(format!("image is {} bytes, over the {} byte input limit", bytes.len(), max)));
+        }
+        let img = image::load_from_memory(bytes).context("decoding image bytes")?;
+        self.convert_dynamic_image(&img, options)
+    }
+
+    /// Extract frames from video and convert to ASCII
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Path to input video file
+    /// * `output_dir` - Directory to write ASCII frames
+    /// * `video_opts` - Video extraction options
+    /// * `conv_opts` - ASCII conversion options
+    /// * `keep_images` - Whether to keep intermediate PNG frames
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, VideoOptions, ConversionOptions};
+    /// use std::path::Path;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let converter = AsciiConverter::new();
+    /// let video_opts = VideoOptions::default();
+    /// let conv_opts = ConversionOptions::default();
+    /// converter.convert_video(Path::new("video.mp4"), Path::new("output_dir"), &video_opts, &conv_opts, false)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn convert_video(&self, input: &Path, output_dir: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, keep_images: bool) -> Result<ConversionResult> {
+        self.convert_video_with_progress(input, output_dir, video_opts, conv_opts, keep_images, None::<fn(usize, usize)>)
+    }
+
+    /// Convert a video to ASCII animation frames with progress callback
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Input video file path
+    /// * `output_dir` - Directory to write ASCII frames
+    /// * `video_opts` - Video extraction options (fps, start, end, columns)
+    /// * `conv_opts` - ASCII conversion options
+    /// * `keep_images` - Whether to keep extracted PNG frames
+    /// * `progress_callback` - Optional callback called with (completed, total) for each frame
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions, VideoOptions};
+    /// use std::path::Path;
+    ///
+    /// let converter = AsciiConverter::new();
+    /// let video_opts = VideoOptions {fps: 24, columns: 120, ..VideoOptions::default()};
+    /// let conv_opts = ConversionOptions::default();
+    ///
+    /// converter.convert_video_with_progress(
+    ///     Path::new("video.mp4"),
+    ///     Path::new("output"),
+    ///     &video_opts,
+    ///     &conv_opts,
+    ///     false,
+    ///     Some(|completed, total| {
+    ///         println!("Progress: {}/{} ({:.1}%)", completed, total, (completed as f64 / total as f64) * 100.0);
+    ///     }),
+    /// ).unwrap();
+    /// ```
+    pub fn convert_video_with_progress<F: Fn(usize, usize) + Send + Sync>(&self, input: &Path, output_dir: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, keep_images: bool, progress_callback: Option<F>) -> Result<ConversionResult> {
+        if video_opts.needs_sink_pipeline() {
+            let sinks = [OutputSink::Frames {dir: output_dir.to_path_buf(), keep_images}];
+            return self.run_sinks("convert_video", input, video_opts, conv_opts, &sinks, |progress: Progress| {
+                if let (ProgressPhase::ConvertingFrames, Some(callback)) = (progress.phase, progress_callback.as_ref()) {
+                    callback(progress.completed, progress.total);
+                }
+            });
+        }
+        let checkpoints = self.frame_checkpoints();
+        let progress_callback = move |completed, total| {
+            if let Some(checkpoints) = checkpoints.as_ref() {
+                checkpoints(completed, total);
+            }
+            if let Some(callback) = progress_callback.as_ref() {
+                callback(completed, total);
+            }
+        };
+        self.logged("convert_video", input, output_dir, |result: &ConversionResult| result.frame_count, || self.convert_video_with_progress_inner(input, output_dir, video_opts, conv_opts, keep_images, Some(progress_callback)))
+    }
+
+    fn convert_video_with_progress_inner<F: Fn(usize, usize) + Send + Sync>(&self, input: &Path, output_dir: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, keep_images: bool, progress_callback: Option<F>) -> Result<ConversionResult> {
+        fs::create_dir_all(output_dir).context("creating output directory")?;
+
+        // Extract frames with ffmpeg
+        let ascii_chars = &conv_opts.ramp()[..];
+        self.warn_on_upscale(input, Some(video_opts.columns), conv_opts);
+        video::extract_video_frames(input, output_dir, video_opts, frame::Sampling::from_options(conv_opts), &self.ffmpeg_config, self.cancel_token.as_ref(), self.event_log.as_ref())?;
+        self.check_frame_dir(output_dir)?;
+
+        // Extract audio if requested
+        if video_opts.extract_audio {
+            video::extract_audio(input, output_dir, video_opts.start.as_deref(), video_opts.end.as_deref(), &self.ffmpeg_config, self.cancel_token.as_ref(), self.event_log.as_ref())?;
+        }
+
+        // Convert frames to ASCII with progress callback
+        let (total_frames, histogram) = if conv_opts.cell_color_mode == CellColorMode::FitForegroundBackgroundOptimized {convert::convert_directory_parallel_optimized_with_progress(output_dir, output_dir, conv_opts.font_ratio, conv_opts.luminance, conv_opts.resolve_bg_threshold(), conv_opts.columns.unwrap_or(video_opts.columns), keep_images, ascii_chars, &conv_opts.output_mode, conv_opts.bg_fit_quality, conv_opts.palette, frame::Sampling::from_options(conv_opts).with_fps(video_opts.fps).with_resume(self.resume), progress_callback, self.cancel_token.as_ref())?} else {convert::convert_directory_parallel_with_progress(output_dir, output_dir, conv_opts.font_ratio, conv_opts.luminance, conv_opts.resolve_bg_threshold(), keep_images, ascii_chars, &conv_opts.output_mode, conv_opts.cell_color_mode, conv_opts.bg_fit_quality, conv_opts.palette, frame::Sampling::from_options(conv_opts).with_fps(video_opts.fps).with_resume(self.resume), progress_callback, self.cancel_token.as_ref())?};
+
+        // Build result with conversion details
+        let output_mode_str = match conv_opts.output_mode {
+            OutputMode::TextOnly => "text-only",
+            OutputMode::ColorOnly => "color-only",
+            OutputMode::TextAndColor => "text+color",
+            OutputMode::Ansi => "ansi",
+            OutputMode::Ansi256 => "ansi-256",
+            OutputMode::Json => "json",
+        };
+
+        let result = ConversionResult {frame_count: total_frames, columns: conv_opts.columns.unwrap_or(video_opts.columns), font_ratio: conv_opts.font_ratio, luminance: conv_opts.luminance, fps: Some(video_opts.fps), output_mode: output_mode_str.to_string(), audio_extracted: video_opts.extract_audio, output_dir: output_dir.to_path_buf(), background_color: "black".to_string(), color: "white".to_string(), fit_cell_backgrounds: conv_opts.cell_color_mode.fits_cell_backgrounds(), cell_background_mode: conv_opts.cell_color_mode.as_str().to_string(), bg_fit_quality: conv_opts.bg_fit_quality.as_str().to_string(), bg_luminance: conv_opts.resolve_bg_threshold(), ascii_chars: conv_opts.ascii_chars.clone(), audio_sync: None, colors: histogram.palette(), seed: conv_opts.seed, attribution: self.config.attribution.clone()};
+
+        // Write the details report
+        result.write_report(&self.report_format)?;
+
+        Ok(result)
+    }
+
+    /// Convert a video to ASCII animation frames with detailed progress reporting
+    ///
+    /// This method provides comprehensive progress updates through different phases
+    /// of the conversion process, making it ideal for UI integration.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Input video file path
+    /// * `output_dir` - Directory to write ASCII frames
+    /// * `video_opts` - Video extraction options (fps, start, end, columns)
+    /// * `conv_opts` - ASCII conversion options
+    /// * `keep_images` - Whether to keep extracted PNG frames
+    /// * `progress_callback` - Callback called with detailed Progress information
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions, VideoOptions, Progress, ProgressPhase};
+    /// use std::path::Path;
+    ///
+    /// let converter = AsciiConverter::new();
+    /// let video_opts = VideoOptions::default();
+    /// let conv_opts = ConversionOptions::default();
+    ///
+    /// converter.convert_video_with_detailed_progress(
+    ///     Path::new("video.mp4"),
+    ///     Path::new("output"),
+    ///     &video_opts,
+    ///     &conv_opts,
+    ///     false,
+    ///     |progress| {
+    ///         match progress.phase {
+    ///             ProgressPhase::ExtractingFrames => {
+    ///                 println!("Extracting frames from video...");
+    ///             }
+    ///             ProgressPhase::ExtractingAudio => {
+    ///                 println!("Extracting audio...");
+    ///             }
+    ///             ProgressPhase::ConvertingFrames => {
+    ///                 println!("Converting: {}/{} ({:.1}%)",
+    ///                     progress.completed, progress.total, progress.percentage);
+    ///             }
+    ///             ProgressPhase::RenderingVideo => {
+    ///                 println!("Rendering video...");
+    ///             }
+    ///             ProgressPhase::PreviewReady | ProgressPhase::FullQualityReady => {
+    ///                 println!("{}", progress.message);
+    ///             }
+    ///             ProgressPhase::Complete => {
+    ///                 println!("Conversion complete!");
+    ///             }
+    ///             _ => {}
+    ///         }
+    ///     },
+    /// ).unwrap();
+    /// ```
+    pub fn convert_video_with_detailed_progress<F: Fn(Progress) + Send + Sync>(&self, input: &Path, output_dir: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, keep_images: bool, progress_callback: F) -> Result<ConversionResult> {
+        if video_opts.needs_sink_pipeline() {
+            return self.run_sinks("convert_video", input, video_opts, conv_opts, &[OutputSink::Frames {dir: output_dir.to_path_buf(), keep_images}], progress_callback);
+        }
+        let progress_callback = self.observe(progress_callback);
+        self.logged("convert_video", input, output_dir, |result: &ConversionResult| result.frame_count, || self.convert_video_with_detailed_progress_inner(input, output_dir, video_opts, conv_opts, keep_images, progress_callback))
+    }
+
+    /// Run [`convert_video_with_detailed_progress`](Self::convert_video_with_detailed_progress) on a
+    /// background thread, streaming its [`Progress`] events over a channel holding up to `capacity` events.
+    ///
+    /// Meant for GUI main loops: poll the receiver with `try_recv` each tick, and join the handle once
+    /// [`ProgressPhase::Complete`] arrives or the channel disconnects (the conversion failed). While the
+    /// channel is full, intermediate events are dropped instead of stalling the conversion; the 100%
+    /// event ending each phase waits for room, so it is never lost. Dropping the receiver doesn't stop
+    /// the conversion; attach a [`CancelToken`] for that.
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions, ProgressPhase, VideoOptions};
+    ///
+    /// let (handle, progress) = AsciiConverter::new().convert_video_channel("input.mp4", "frames", VideoOptions::default(), ConversionOptions::default(), false, 16);
+    /// while let Ok(progress) = progress.recv() {
+    ///     println!("{:?}: {:.1}%", progress.phase, progress.percentage);
+    ///     if progress.phase == ProgressPhase::Complete {
+    ///         break;
+    ///     }
+    /// }
+    /// let result = handle.join().expect("conversion thread panicked").unwrap();
+    /// println!("{} frames", result.frame_count);
+    /// ```
+    pub fn convert_video_channel(&self, input: impl Into<PathBuf>, output_dir: impl Into<PathBuf>, video_opts: VideoOptions, conv_opts: ConversionOptions, keep_images: bool, capacity: usize) -> (std::thread::JoinHandle<Result<ConversionResult>>, std::sync::mpsc::Receiver<Progress>) {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(capacity.max(1));
+        let converter = self.clone();
+        let (input, output_dir) = (input.into(), output_dir.into());
+        let handle = std::thread::spawn(move || {
+            converter.convert_video_with_detailed_progress(&input, &output_dir, &video_opts, &conv_opts, keep_images, |progress: Progress| {
+                // A closed receiver only means nobody is watching any more
+                if progress.percentage >= 100.0 || progress.phase == ProgressPhase::Complete {
+                    let _ = sender.send(progress);
+                } else {
+                    let _ = sender.try_send(progress);
+                }
+            })
+        });
+        (handle, receiver)
+    }
+
+    fn convert_video_with_detailed_progress_inner<F: Fn(Progress) + Send + Sync>(&self, input: &Path, output_dir: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, keep_images: bool, progress_callback: F) -> Result<ConversionResult> {
+        fs::create_dir_all(output_dir).context("creating output directory")?;
+
+        // Phase 1: Extract frames from video with progress reporting
+        let ascii_chars = &conv_opts.ramp()[..];
+        self.warn_on_upscale(input, Some(video_opts.columns), conv_opts);
+        video::extract_video_frames_with_progress(input, output_dir, video_opts, frame::Sampling::from_options(conv_opts), &self.ffmpeg_config, &progress_callback, self.cancel_token.as_ref(), self.event_log.as_ref())?;
+        self.check_frame_dir(output_dir)?;
+
+        // Phase 2: Extract audio if requested
+        if video_opts.extract_audio {
+            progress_callback(Progress::extracting_audio());
+            video::extract_audio(input, output_dir, video_opts.start.as_deref(), video_opts.end.as_deref(), &self.ffmpeg_config, self.cancel_token.as_ref(), self.event_log.as_ref())?;
+            progress_callback(Progress::extracting_audio_done());
+        }
+
+        // Phase 3: Convert frames to ASCII with progress
+        let (total_frames, histogram) = if conv_opts.cell_color_mode == CellColorMode::FitForegroundBackgroundOptimized {convert::convert_directory_parallel_optimized_with_detailed_progress(output_dir, output_dir, conv_opts.font_ratio, conv_opts.luminance, conv_opts.resolve_bg_threshold(), conv_opts.columns.unwrap_or(video_opts.columns), keep_images, ascii_chars, &conv_opts.output_mode, conv_opts.bg_fit_quality, conv_opts.palette, frame::Sampling::from_options(conv_opts).with_fps(video_opts.fps).with_resume(self.resume), &progress_callback, self.cancel_token.as_ref())?} else {convert::convert_directory_parallel_with_detailed_progress(output_dir, output_dir, conv_opts.font_ratio, conv_opts.luminance, conv_opts.resolve_bg_threshold(), keep_images, ascii_chars, &conv_opts.output_mode, conv_opts.cell_color_mode, conv_opts.bg_fit_quality, conv_opts.palette, frame::Sampling::from_options(conv_opts).with_fps(video_opts.fps).with_resume(self.resume), &progress_callback, self.cancel_token.as_ref())?};
+
+        // Phase 4: Complete
+        progress_callback(Progress::complete(total_frames));
+
+        // Build result with conversion details
+        let output_mode_str = match conv_opts.output_mode {
+            OutputMode::TextOnly => "text-only",
+            OutputMode::ColorOnly => "color-only",
+            OutputMode::TextAndColor => "text+color",
+            OutputMode::Ansi => "ansi",
+            OutputMode::Ansi256 => "ansi-256",
+            OutputMode::Json => "json",
+        };
+
+        let result = ConversionResult {frame_count: total_frames, columns: conv_opts.columns.unwrap_or(video_opts.columns), font_ratio: conv_opts.font_ratio, luminance: conv_opts.luminance, fps: Some(video_opts.fps), output_mode: output_mode_str.to_string(), audio_extracted: video_opts.extract_audio, output_dir: output_dir.to_path_buf(), background_color: "black".to_string(), color: "white".to_string(), fit_cell_backgrounds: conv_opts.cell_color_mode.fits_cell_backgrounds(), cell_background_mode: conv_opts.cell_color_mode.as_str().to_string(), bg_fit_quality: conv_opts.bg_fit_quality.as_str().to_string(), bg_luminance: conv_opts.resolve_bg_threshold(), ascii_chars: conv_opts.ascii_chars.clone(), audio_sync: None, colors: histogram.palette(), seed: conv_opts.seed, attribution: self.config.attribution.clone()};
+
+        // Write the details report
+        result.write_report(&self.report_format)?;
+
+        Ok(result)
+    }
+
+    /// Convert a directory of images to ASCII frames
+    ///
+    /// # Arguments
+    ///
+    /// * `input_dir` - Directory containing PNG images
+    /// * `output_dir` - Directory to write ASCII files
+    /// * `options` - Conversion options
+    /// * `keep_images` - Whether to keep original images
+    ///
+    /// Returns the number of frames converted.
+    pub fn convert_directory(&self, input_dir: &Path, output_dir: &Path, options: &ConversionOptions, keep_images: bool) -> Result<usize> {
+        self.logged("convert_directory", input_dir, output_dir, |frames: &usize| *frames, || {
+            self.check_frame_dir(input_dir)?;
+            fs::create_dir_all(output_dir)?;
+            let ascii_chars = &options.ramp()[..];
+            if options.cell_color_mode == CellColorMode::FitForegroundBackgroundOptimized {
+                convert::convert_directory_parallel_optimized_with_progress(input_dir, output_dir, options.font_ratio, options.luminance, options.resolve_bg_threshold(), options.columns.unwrap_or(400), keep_images, ascii_chars, &options.output_mode, options.bg_fit_quality, options.palette, frame::Sampling::from_options(options).with_resume(self.resume), self.frame_checkpoints(), self.cancel_token.as_ref())
+            } else {
+                convert::convert_directory_parallel_with_progress(input_dir, output_dir, options.font_ratio, options.luminance, options.resolve_bg_threshold(), keep_images, ascii_chars, &options.output_mode, options.cell_color_mode, options.bg_fit_quality, options.palette, frame::Sampling::from_options(options).with_resume(self.resume), self.frame_checkpoints(), self.cancel_token.as_ref())
+            }.map(|(frames, _)| frames)
+        })
+    }
+
+    /// Convert a directory of images to ASCII frames with detailed progress reporting
+    ///
+    /// # Arguments
+    ///
+    /// * `input_dir` - Directory containing PNG images
+    /// * `output_dir` - Directory to write ASCII files
+    /// * `options` - Conversion options
+    /// * `keep_images` - Whether to keep original images
+    /// * `progress_callback` - Callback called with detailed Progress information
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions, Progress};
+    /// use std::path::Path;
+    ///
+    /// let converter = AsciiConverter::new();
+    /// let options = ConversionOptions::default();
+    ///
+    /// converter.convert_directory_with_progress(
+    ///     Path::new("input_frames"),
+    ///     Path::new("output_ascii"),
+    ///     &options,
+    ///     false,
+    ///     |progress| {
+    ///         println!("Converting: {}/{} ({:.1}%)",
+    ///             progress.completed, progress.total, progress.percentage);
+    ///     },
+    /// ).unwrap();
+    /// ```
+    pub fn convert_directory_with_progress<F: Fn(Progress) + Send + Sync>(&self, input_dir: &Path, output_dir: &Path, options: &ConversionOptions, keep_images: bool, progress_callback: F) -> Result<usize> {
+        let progress_callback = self.observe(progress_callback);
+        self.logged("convert_directory", input_dir, output_dir, |frames: &usize| *frames, || {
+            self.check_frame_dir(input_dir)?;
+            fs::create_dir_all(output_dir)?;
+            let ascii_chars = &options.ramp()[..];
+            convert::convert_directory_parallel_with_detailed_progress(input_dir, output_dir, options.font_ratio, options.luminance, options.resolve_bg_threshold(), keep_images, ascii_chars, &options.output_mode, options.cell_color_mode, options.bg_fit_quality, options.palette, frame::Sampling::from_options(options).with_resume(self.resume), &progress_callback, self.cancel_token.as_ref()).map(|(frames, _)| frames)
+        })
+    }
+
+    /// Convert a sprite sheet of `tile_width`×`tile_height` tiles into an animation: each tile, in the
+    /// order of [`spritesheet::slice_tiles`], becomes a numbered frame in `output_dir`, and the details
+    /// report records `fps` so playback and renders of the directory run at the sheet's rate.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions};
+    /// use std::path::Path;
+    ///
+    /// let options = ConversionOptions::default().with_columns(32).with_pixel_art(true);
+    /// let result = AsciiConverter::new().convert_spritesheet(Path::new("hero_run.png"), 32, 32, Path::new("hero_run"), 12, &options).unwrap();
+    /// println!("{} frames", result.frame_count);
+    /// ```
+    pub fn convert_spritesheet(&self, input: &Path, tile_width: u32, tile_height: u32, output_dir: &Path, fps: u32, options: &ConversionOptions) -> Result<ConversionResult> {
+        self.logged("convert_spritesheet", input, output_dir, |result: &ConversionResult| result.frame_count, || {
+            if fps == 0 {
+                return Err(Error::InvalidOptions("sprite sheet fps must be positive".into()));
+            }
+            let tiles = spritesheet::slice_tiles(&convert::open_image(input)?, tile_width, tile_height)?;
+            self.check_frame_count(tiles.len())?;
+            if let Some(message) = options.columns.and_then(|columns| options.upscale.warning(columns, tile_width / frame::Sampling::from_options(options).views())) {
+                self.warn(message);
+            }
+            fs::create_dir_all(output_dir).context("creating output directory")?;
+
+            let ascii_chars = &options.ramp()[..];
+            let cframe = cframe::CframeEncoder::new(frame::Sampling::from_options(options), options.font_ratio, ascii_chars);
+            let frames = tiles.par_iter().enumerate().map(|(index, tile)| {
+                if self.cancel_token.as_ref().is_some_and(|token| token.is_cancelled()) {
+                    return Err(Cancelled.into());
+                }
+                let frame = convert::image_to_ascii_frame_data(tile, options.font_ratio, options.luminance, options.resolve_bg_threshold(), options.columns, ascii_chars, options.cell_color_mode, options.bg_fit_quality, options.palette, frame::Sampling::from_options(options))?;
+                convert::write_frame_outputs(&frame, &output_dir.join(format!("frame_{:04}.txt", index + 1)), &options.output_mode, options.cell_color_mode, options.codepage, &cframe)?;
+                Ok(frame)
+            }).collect::<Result<Vec<_>>>()?;
+            let mut histogram = ColorHistogram::new();
+            for frame in &frames {
+                histogram.add_rgb(&frame.rgb_colors);
+                histogram.add_rgb(&frame.bg_rgb_colors);
+            }
+
+            let output_mode_str = match options.output_mode {
+                OutputMode::TextOnly => "text-only",
+                OutputMode::ColorOnly => "color-only",
+                OutputMode::TextAndColor => "text+color",
+                OutputMode::Ansi => "ansi",
+                OutputMode::Ansi256 => "ansi-256",
+                OutputMode::Json => "json",
+            };
+            let result = ConversionResult {frame_count: frames.len(), columns: frames[0].width_chars, font_ratio: options.font_ratio, luminance: options.luminance, fps: Some(fps), output_mode: output_mode_str.to_string(), audio_extracted: false, output_dir: output_dir.to_path_buf(), background_color: "black".to_string(), color: "white".to_string(), fit_cell_backgrounds: options.cell_color_mode.fits_cell_backgrounds(), cell_background_mode: options.cell_color_mode.as_str().to_string(), bg_fit_quality: options.bg_fit_quality.as_str().to_string(), bg_luminance: options.resolve_bg_threshold(), ascii_chars: options.ascii_chars.clone(), audio_sync: None, colors: histogram.palette(), seed: options.seed, attribution: self.config.attribution.clone()};
+            result.write_report(&self.report_format)?;
+            Ok(result)
+        })
+    }
+
+    /// Estimate the output size and conversion time of converting `input` (an image, a directory of
+    /// images or a video) to frame files with `conv_opts`, without writing them: up to
+    /// [`SAMPLE_FRAMES`](crate::SAMPLE_FRAMES) frames spread across the source are converted in a temporary
+    /// directory and measured. Video frames are sampled with ffmpeg from the range, rate and filters
+    /// of `video_opts`, as a full conversion would extract them.
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions, OutputMode, VideoOptions};
+    /// use std::path::Path;
+    ///
+    /// let converter = AsciiConverter::new();
+    /// for mode in [OutputMode::TextOnly, OutputMode::TextAndColor] {
+    ///     let options = ConversionOptions::default().with_output_mode(mode.clone());
+    ///     let estimate = converter.estimate_conversion(Path::new("input.mp4"), &VideoOptions::default(), &options).unwrap();
+    ///     println!("{:?}: {} bytes in {:.0}s", mode, estimate.total_bytes(), estimate.total_seconds());
+    /// }
+    /// ```
+    pub fn estimate_conversion(&self, input: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions) -> Result<ConversionEstimate> {
+        self.check_input(input)?;
+        let work_dir = self.cache_dir.clone().unwrap_or_else(std::env::temp_dir).join(format!("cascii_estimate_{}", std::process::id()));
+        fs::create_dir_all(&work_dir).context("creating temp directory")?;
+        let result = self.estimate_conversion_inner(input, video_opts, conv_opts, &work_dir);
+        let _ = fs::remove_dir_all(&work_dir);
+        result
+    }
+
+    /// Convert `input`, a video, to frame files in `output_dir` within `budget`, for apps that need
+    /// something to show in a given time rather than the full quality. Sample frames are converted
+    /// and timed as [`estimate_conversion`](Self::estimate_conversion) does; when the full conversion
+    /// is predicted to take longer than the time left, the columns and the frame rate are lowered,
+    /// no further than the budget's floors, and the video is converted with those. The returned
+    /// [`BudgetedConversion`] reports the settings chosen.
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions, TimeBudget, VideoOptions};
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> cascii::Result<()> {
+    /// let budget = TimeBudget::new(Duration::from_secs(10)).with_min_columns(60);
+    /// let video_opts = VideoOptions {columns: 200, fps: 30, ..VideoOptions::default()};
+    /// let converted = AsciiConverter::new().convert_with_time_budget(Path::new("input.mp4"), Path::new("frames"), &budget, &video_opts, &ConversionOptions::default())?;
+    /// println!("{} columns at {} fps in {:.1?}", converted.columns, converted.fps, converted.elapsed);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn convert_with_time_budget(&self, input: &Path, output_dir: &Path, budget: &TimeBudget, video_opts: &VideoOptions, conv_opts: &ConversionOptions) -> Result<BudgetedConversion> {
+        if input.is_dir() || image::image_dimensions(input).is_ok() {
+            return Err(Error::InvalidOptions(format!("a time budget needs a video to lower the frame rate of, not {}", input.display())));
+        }
+        let started = std::time::Instant::now();
+        let estimate = self.estimate_conversion(input, video_opts, conv_opts)?;
+        let left = budget.duration.saturating_sub(started.elapsed()).as_secs_f64() * estimate::BUDGET_SHARE;
+        let (columns, fps, predicted_seconds) = estimate::plan_for_budget(&estimate, conv_opts.columns.unwrap_or(video_opts.columns), video_opts.fps, left, budget);
+        let video_opts = VideoOptions {columns, fps, ..video_opts.clone()};
+        let result = self.convert_video(input, output_dir, &video_opts, &conv_opts.clone().with_columns(columns), false)?;
+        Ok(BudgetedConversion {result, columns, fps, predicted_seconds, within_budget: predicted_seconds <= left, elapsed: started.elapsed()})
+    }
+
+    /// Convert `input`, a video, twice into versioned subdirectories of `output_dir`, for apps that
+    /// start playing before the conversion is done. The preview, capped to the columns and frame rate
+    /// of `preview`, is converted first into [`refine::PREVIEW_DIR`] and made current; this returns
+    /// once it is, leaving the full-quality pass into [`refine::FULL_DIR`] running on a background
+    /// thread. That pass is made current when complete, and the preview is left in place for players
+    /// still reading it; [`refine::current_version`] names the version to play.
+    ///
+    /// `progress_callback` receives the progress of both passes, [`ProgressPhase::PreviewReady`] when
+    /// the preview is current, then [`ProgressPhase::FullQualityReady`] and a single
+    /// [`ProgressPhase::Complete`] when the full quality replaced it.
+    pub fn convert_video_two_stage<F: Fn(Progress) + Send + Sync + 'static>(&self, input: &Path, output_dir: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, preview: PreviewOptions, progress_callback: F) -> Result<TwoStageConversion> {
+        if input.is_dir() || image::image_dimensions(input).is_ok() {
+            return Err(Error::InvalidOptions(format!("a two-stage conversion needs a video to preview, not {}", input.display())));
+        }
+        let progress_callback = std::sync::Arc::new(progress_callback);
+        // Each pass ends with its own Complete; only the full-quality pass finishing completes the conversion
+        let forward = |callback: std::sync::Arc<F>, pass| move |progress: Progress| if progress.phase != ProgressPhase::Complete {callback(progress.with_pass(pass))};
+        let (preview_dir, full_dir) = (output_dir.join(refine::PREVIEW_DIR), output_dir.join(refine::FULL_DIR));
+        let (preview_video, preview_conv) = preview.apply(video_opts, conv_opts);
+        let preview = self.convert_video_with_detailed_progress(input, &preview_dir, &preview_video, &preview_conv, false, forward(progress_callback.clone(), ConversionPass::Preview))?;
+        refine::set_current(output_dir, refine::PREVIEW_DIR)?;
+        progress_callback(Progress::preview_ready(preview.frame_count, &preview_dir));
+
+        let converter = self.clone();
+        let (input, output_dir, full_path) = (input.to_path_buf(), output_dir.to_path_buf(), full_dir.clone());
+        let (video_opts, conv_opts) = (video_opts.clone(), conv_opts.clone());
+        let full = std::thread::spawn(move || {
+            let result = converter.convert_video_with_detailed_progress(&input, &full_path, &video_opts, &conv_opts, false, forward(progress_callback.clone(), ConversionPass::FullQuality))?;
+            refine::set_current(&output_dir, refine::FULL_DIR)?;
+            progress_callback(Progress::full_quality_ready(result.frame_count, &full_path));
+            progress_callback(Progress::complete(result.frame_count));
+            Ok(result)
+        });
+        Ok(TwoStageConversion {preview, preview_dir, full_dir, full})
+    }
+
+    fn estimate_conversion_inner(&self, input: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, work_dir: &Path) -> Result<ConversionEstimate> {
+        let sampling = frame::Sampling::from_options(conv_opts);
+        let (samples, frames, columns) = self.sample_frames(input, video_opts, conv_opts, work_dir)?;
+        let ascii_chars = &conv_opts.ramp()[..];
+        let background_analysis = convert::background_analysis_for_mode(ascii_chars, conv_opts.cell_color_mode, conv_opts.bg_fit_quality)?;
+        let threads = self.thread_pool.as_ref().map_or_else(rayon::current_num_threads, |pool| pool.current_num_threads());
+        estimate::measure(&samples, frames, threads, &work_dir.join("converted"), |sample, out_txt| {
+            convert::convert_image_to_ascii_with_analysis(sample, out_txt, conv_opts.font_ratio, conv_opts.luminance, conv_opts.resolve_bg_threshold(), columns, ascii_chars, &conv_opts.output_mode, conv_opts.cell_color_mode, conv_opts.bg_fit_quality, conv_opts.palette, sampling, background_analysis.as_ref()).map(|_| ())
+        })
+    }
+
+    /// Up to [`SAMPLE_FRAMES`](crate::SAMPLE_FRAMES) frames spread across `input` (video frames extracted into
+    /// `work_dir`), with the number of frames a conversion would write and the columns to convert
+    /// the samples at
+    fn sample_frames(&self, input: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, work_dir: &Path) -> Result<(Vec<PathBuf>, usize, Option<u32>)> {
+        let sampling = frame::Sampling::from_options(conv_opts);
+        // Frame directories and extracted video frames convert one column per pixel, like a full conversion
+        let (samples, frames, columns) = if input.is_dir() {
+            let pngs = estimate::frame_images(input)?;
+            let samples = estimate::sample_indices(pngs.len(), estimate::SAMPLE_FRAMES).into_iter().map(|index| pngs[index].clone()).collect();
+            (samples, pngs.len(), None)
+        } else if image::image_dimensions(input).is_ok() {
+            (vec![input.to_path_buf()], 1, conv_opts.columns)
+        } else {
+            let (start, span) = video::extraction_span(input, video_opts, &self.ffmpeg_config)?;
+            if span <= 0.0 {
+                return Err(Error::NotFound(format!("{} has no frames between {}s and {}s", input.display(), start, start + span)));
+            }
+            let frames = (span * video_opts.fps as f64).ceil() as usize;
+            let times: Vec<f64> = estimate::sample_indices(frames, estimate::SAMPLE_FRAMES).into_iter().map(|index| start + index as f64 / video_opts.fps as f64).collect();
+            let video_opts = VideoOptions {columns: conv_opts.columns.unwrap_or(video_opts.columns), ..video_opts.clone()};
+            let samples = video::extract_sample_frames(input, work_dir, &video_opts, sampling, &times, &self.ffmpeg_config, self.cancel_token.as_ref())?;
+            (samples, frames, None)
+        };
+        if samples.is_empty() {
+            return Err(Error::NotFound(format!("No frames found in {} to sample", input.display())));
+        }
+        Ok((samples, frames, columns))
+    }
+
+    /// Convert the sample frames a dry run measures (see [`Self::estimate_conversion`]) with `left`
+    /// and with `right`, and score both against the source (see [`compare`]). Video frames are
+    /// extracted once, wide enough for the larger column count, with `video_opts`' preprocessing;
+    /// each side converts them at its own columns.
+    pub fn compare_conversions(&self, input: &Path, video_opts: &VideoOptions, left: &ConversionOptions, right: &ConversionOptions) -> Result<compare::ConversionComparison> {
+        self.check_input(input)?;
+        let work_dir = self.cache_dir.clone().unwrap_or_else(std::env::temp_dir).join(format!("cascii_compare_{}", std::process::id()));
+        fs::create_dir_all(&work_dir).context("creating temp directory")?;
+        let result = self.compare_conversions_inner(input, video_opts, left, right, &work_dir);
+        let _ = fs::remove_dir_all(&work_dir);
+        result
+    }
+
+    fn compare_conversions_inner(&self, input: &Path, video_opts: &VideoOptions, left: &ConversionOptions, right: &ConversionOptions, work_dir: &Path) -> Result<compare::ConversionComparison> {
+        let widest = left.columns.max(right.columns);
+        let (samples, _, _) = self.sample_frames(input, video_opts, &ConversionOptions {columns: widest, ..left.clone()}, work_dir)?;
+        let convert_with = |img: &image::DynamicImage, options: &ConversionOptions| -> Result<(AsciiFrame, compare::QualityMetrics)> {
+            let data = convert::image_to_ascii_frame_data(img, options.font_ratio, options.luminance, options.resolve_bg_threshold(), options.columns, &options.ramp(), options.cell_color_mode, options.bg_fit_quality, options.palette, frame::Sampling::from_options(options))?;
+            let frame = AsciiFrame::from(data);
+            let metrics = compare::QualityMetrics::measure(img, &frame, options.output_mode != OutputMode::TextOnly)?;
+            Ok((frame, metrics))
+        };
+        let mut frames = Vec::with_capacity(samples.len());
+        for sample in &samples {
+            let img = convert::open_image(sample)?;
+            let ((left, left_metrics), (right, right_metrics)) = (convert_with(&img, left)?, convert_with(&img, right)?);
+            frames.push(compare::FrameComparison {left, right, left_metrics, right_metrics});
+        }
+        Ok(compare::ConversionComparison {frames})
+    }
+
+    /// Get a preset by name
+    pub fn get_preset(&self, name: &str) -> Option<&Preset> {
+        self.config.presets.get(name)
+    }
+
+    /// The preset suited to a source, see [`AppConfig::suggest_preset`]
+    pub fn suggest_preset(&self, info: &MediaInfo) -> Option<&Preset> {
+        self.config.suggest_preset(info)
+    }
+
+    /// Get conversion options from a preset name
+    pub fn options_from_preset(&self, preset_name: &str) -> Result<ConversionOptions> {
+        let preset = self.get_preset(preset_name).ok_or_else(|| Error::NotFound(format!("Preset '{}' not found", preset_name)))?;
+        Ok(ConversionOptions::from_preset(preset, self.config.ascii_chars.clone()))
+    }
+
+    /// Get a profile by name
+    pub fn get_profile(&self, name: &str) -> Option<&Profile> {
+        self.config.profiles.get(name)
+    }
+
+    /// Convert a video to an ASCII-art video file
+    ///
+    /// Streams from one ffmpeg process into another: frames are decoded from the input's ffmpeg
+    /// on one thread, converted to ASCII art in parallel batches, rendered to pixel buffers and
+    /// piped to the encoding ffmpeg, all at once and without writing frames to disk
+    /// ([`VideoOptions::pipe_frames`] is always on here).
+    pub fn convert_video_to_video<F: Fn(Progress) + Send + Sync>(&self, input: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, to_video_opts: &ToVideoOptions, progress_callback: F) -> Result<ConversionResult> {
+        let video_opts = VideoOptions {pipe_frames: true, ..video_opts.clone()};
+        self.run_sinks("convert_video_to_video", input, &video_opts, conv_opts, &[OutputSink::Video(to_video_opts.clone())], progress_callback)
+    }
+
+    /// Convert a video once and send the frames to every sink
+    ///
+    /// Frames are extracted and converted a single time, then written to each frame directory,
+    /// piped to each video encoder and appended to each ANSI file as they come, instead of paying
+    /// for extraction and conversion again for every output with separate [`convert_video`] and
+    /// [`convert_video_to_video`] calls. Audio is extracted at most once and shared as well.
+    ///
+    /// The result describes the first sink; `audio_sync` comes from the first video sink with audio.
+    ///
+    /// [`convert_video`]: AsciiConverter::convert_video
+    /// [`convert_video_to_video`]: AsciiConverter::convert_video_to_video
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, ConversionOptions, OutputMode, OutputSink, ToVideoOptions, VideoOptions};
+    /// use std::path::{Path, PathBuf};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let converter = AsciiConverter::new();
+    /// let conv_opts = ConversionOptions::default().with_output_mode(OutputMode::TextAndColor);
+    /// let sinks = [
+    ///     OutputSink::Frames {dir: PathBuf::from("frames"), keep_images: false},
+    ///     OutputSink::Video(ToVideoOptions {output_path: PathBuf::from("ascii.mp4"), mux_audio: true, ..Default::default()}),
+    ///     OutputSink::Ansi(PathBuf::from("ascii.ans")),
+    /// ];
+    /// converter.convert_video_to_sinks(Path::new("video.mp4"), &VideoOptions::default(), &conv_opts, &sinks, |_| {})?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn convert_video_to_sinks<F: Fn(Progress) + Send + Sync>(&self, input: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, sinks: &[OutputSink], progress_callback: F) -> Result<ConversionResult> {
+        self.run_sinks("convert_video_to_sinks", input, video_opts, conv_opts, sinks, progress_callback)
+    }
+
+    fn run_sinks<F: Fn(Progress) + Send + Sync>(&self, operation: &str, input: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, sinks: &[OutputSink], progress_callback: F) -> Result<ConversionResult> {
+        let first_sink = sinks.first().ok_or_else(|| Error::InvalidOptions("no output sinks given".into()))?;
+
+        // Create temp directory for intermediate PNG frames
+        let temp_dir = self.cache_dir.clone().unwrap_or_else(std::env::temp_dir).join(format!("cascii_tovideo_{}", std::process::id()));
+        fs::create_dir_all(&temp_dir).context("creating temp directory")?;
+
+        // Ensure cleanup on exit (both success and error paths)
+        let progress_callback = self.observe(progress_callback);
+        let result = self.logged(operation, input, first_sink.path(), |result: &ConversionResult| result.frame_count, || self.convert_video_to_sinks_inner(input, video_opts, conv_opts, sinks, &temp_dir, &progress_callback));
+
+        // Clean up temp directory
+        let _ = fs::remove_dir_all(&temp_dir);
+
+        result
+    }
+
+    fn convert_video_to_sinks_inner<F: Fn(Progress) + Send + Sync>(&self, input: &Path, video_opts: &VideoOptions, conv_opts: &ConversionOptions, sinks: &[OutputSink], temp_dir: &Path, progress_callback: &F) -> Result<ConversionResult> {
+        use std::sync::mpsc::sync_channel;
+        use std::thread;
+
+        let frame_dirs: Vec<(&Path, bool)> = sinks.iter().filter_map(|sink| if let OutputSink::Frames {dir, keep_images} = sink {Some((dir.as_path(), *keep_images))} else {None}).collect();
+        let video_sinks: Vec<&ToVideoOptions> = sinks.iter().filter_map(|sink| if let OutputSink::Video(to_video_opts) = sink {Some(to_video_opts)} else {None}).collect();
+        for (dir, _) in &frame_dirs {
+            fs::create_dir_all(dir).context("creating output directory")?;
+        }
+
+        // Phase 1: Extract frames from video, or start decoding them into a pipe
+        let ascii_chars = &conv_opts.ramp()[..];
+        let cframe = cframe::CframeEncoder::new(frame::Sampling::from_options(conv_opts).with_fps(video_opts.fps), conv_opts.font_ratio, ascii_chars);
+        self.warn_on_upscale(input, Some(video_opts.columns), conv_opts);
+        let mut frame_pipe = if video_opts.pipe_frames {
+            progress_callback(Progress::extracting_frames());
+            Some(video::FramePipe::open(input, video_opts, frame::Sampling::from_options(conv_opts), &self.ffmpeg_config, self.event_log.as_ref())?)
+        } else {
+            video::extract_video_frames_with_progress(input, temp_dir, video_opts, frame::Sampling::from_options(conv_opts), &self.ffmpeg_config, progress_callback, self.cancel_token.as_ref(), self.event_log.as_ref())?;
+            None
+        };
+
+        // Phase 2: Extract audio once if any sink wants it; passthrough video sinks read it straight from the source while encoding instead
+        let frames_want_audio = video_opts.extract_audio && !frame_dirs.is_empty();
+        let audio_path = if frames_want_audio || video_sinks.iter().any(|to_video_opts| to_video_opts.mux_audio && !to_video_opts.audio_passthrough) {
+            progress_callback(Progress::extracting_audio());
+            video::extract_audio(input, temp_dir, video_opts.start.as_deref(), video_opts.end.as_deref(), &self.ffmpeg_config, self.cancel_token.as_ref(), self.event_log.as_ref())?;
+            progress_callback(Progress::extracting_audio_done());
+            Some(temp_dir.join("audio.mp3"))
+        } else {
+            None
+        };
+        if let Some(audio_path) = audio_path.as_deref().filter(|_| frames_want_audio) {
+            for (dir, _) in &frame_dirs {
+                fs::copy(audio_path, dir.join("audio.mp3")).with_context(|| format!("copying audio into {}", dir.display()))?;
+            }
+        }
+
+        // Collect and sort PNG frame paths; piped frames are counted as they arrive, against the count the duration implies
+        let mut png_paths: Vec<PathBuf> = WalkDir::new(temp_dir).min_depth(1).max_depth(1).into_iter().filter_map(|e| e.ok()).map(|e| e.into_path()).filter(|p| p.extension().map(|e| e == "png").unwrap_or(false)).collect();
+        png_paths.sort();
+
+        let total_frames = match &frame_pipe {
+            Some(pipe) if pipe.frame_count().is_some() => pipe.frame_count().unwrap_or_default(),
+            Some(_) => (video::extraction_span(input, video_opts, &self.ffmpeg_config)?.1 * video_opts.fps as f64).ceil().max(1.0) as usize,
+            None => png_paths.len(),
+        };
+        self.check_frame_count(total_frames)?;
+        if total_frames == 0 {
+            return Err(Error::NotFound("No frames extracted from video".into()));
+        }
+
+        // A depth image is shared by every frame; a depth video is extracted with the source's timing and matched frame by frame
+        let mut depth_frames = Vec::new();
+        let depth_image = match &video_opts.depth_map {
+            Some(depth_map) if image::image_dimensions(&depth_map.path).is_ok() => Some(image::open(&depth_map.path).with_context(|| format!("opening depth map {}", depth_map.path.display()))?),
+            Some(depth_map) => {
+                let depth_dir = temp_dir.join("depth");
+                fs::create_dir_all(&depth_dir).context("creating depth frame directory")?;
+                let depth_opts = VideoOptions {extract_audio: false, preprocess_filter: None, depth_map: None, ..video_opts.clone()};
+                video::extract_video_frames(&depth_map.path, &depth_dir, &depth_opts, frame::Sampling::from_options(conv_opts), &self.ffmpeg_config, self.cancel_token.as_ref(), self.event_log.as_ref())?;
+                depth_frames = WalkDir::new(&depth_dir).min_depth(1).max_depth(1).into_iter().filter_map(|e| e.ok()).map(|e| e.into_path()).filter(|p| p.extension().map(|e| e == "png").unwrap_or(false)).collect();
+                depth_frames.sort();
+                if depth_frames.is_empty() {
+                    return Err(Error::NotFound(format!("No frames extracted from depth map {}", depth_map.path.display())));
+                }
+                None
+            }
+            None => None,
+        };
+
+        // Phase 3: Convert first frame to determine output resolution
+        let background_analysis = convert::background_analysis_for_mode(ascii_chars, conv_opts.cell_color_mode, conv_opts.bg_fit_quality)?;
+        let bg_threshold = conv_opts.resolve_bg_threshold();
+        // `png` is the extracted frame file, which piped frames don't have
+        let luminance = frame::Sampling::from_options(conv_opts).luminance;
+        let convert_frame = |index: usize, image: &image::DynamicImage, luma: Option<&image::GrayImage>, png: Option<&Path>| -> Result<convert::AsciiFrameData> {
+            let mut frame = convert::image_to_ascii_frame_data_with_analysis(image, luma, conv_opts.font_ratio, conv_opts.luminance, bg_threshold, conv_opts.columns, ascii_chars, conv_opts.cell_color_mode, conv_opts.bg_fit_quality, conv_opts.palette, frame::Sampling::from_options(conv_opts), background_analysis.as_ref())?;
+            if let Some(depth_map) = &video_opts.depth_map {
+                // A depth video that ends a frame or two early keeps applying its last frame
+                let depth = match &depth_image {
+                    Some(image) => std::borrow::Cow::Borrowed(image),
+                    None => {
+                        let depth_path = &depth_frames[index.min(depth_frames.len() - 1)];
+                        std::borrow::Cow::Owned(image::open(depth_path).with_context(|| format!("opening depth frame {}", depth_path.display()))?)
+                    }
+                };
+                frame.apply_depth(&depth, ascii_chars, depth_map.options);
+            }
+            if let Some(captions) = &video_opts.captions {
+                frame.apply_captions(captions, index as f64 / video_opts.fps as f64);
+            }
+            // Frame files are written here, on the conversion workers, rather than in the ordered writer below
+            for (dir, keep_images) in &frame_dirs {
+                // ffmpeg numbers extracted frames from 1
+                let file_name = format!("frame_{:04}.png", index + 1);
+                convert::write_frame_outputs(&frame, &dir.join(&file_name).with_extension("txt"), &conv_opts.output_mode, conv_opts.cell_color_mode, conv_opts.codepage, &cframe)?;
+                match png.filter(|_| *keep_images) {
+                    Some(path) => fs::copy(path, dir.join(&file_name)).map(drop).with_context(|| format!("copying {} into {}", path.display(), dir.display()))?,
+                    None if *keep_images => image.save(dir.join(&file_name)).with_context(|| format!("writing {} into {}", file_name, dir.display()))?,
+                    None => {}
+                }
+            }
+            Ok(frame)
+        };
+        let first_frame = match frame_pipe.as_mut() {
+            Some(pipe) => {
+                let (image, luma) = pipe.next_frame()?.ok_or_else(|| Error::NotFound("No frames extracted from video".into()))?.decode(luminance)?;
+                progress_callback(Progress::extracting_frames_done());
+                convert_frame(0, &image::DynamicImage::ImageRgb8(image), luma.as_ref(), None)?
+            }
+            None => convert_frame(0, &convert::open_image(&png_paths[0])?, None, Some(&png_paths[0]))?,
+        };
+
+        // Phase 4: Spawn an ffmpeg encoder per video sink
+        let mut encoders = Vec::with_capacity(video_sinks.len());
+        let mut audio_sync = None;
+        let stamp = self.stamp();
+        for to_video_opts in &video_sinks {
+            let mut atlas = render::build_glyph_atlas_with_stroke(to_video_opts.font_size, to_video_opts.text_stroke_width)?;
+            atlas.add_chars(&conv_opts.ascii_chars)?;
+            atlas.add_chars(stamp.as_deref().unwrap_or_default())?;
+            let mut pixel_w = first_frame.width_chars * atlas.cell_width;
+            let mut pixel_h = first_frame.height_chars * atlas.cell_height;
+            // H.264 requires even dimensions
+            if pixel_w % 2 != 0 {
+                pixel_w += 1;
+            }
+            if pixel_h % 2 != 0 {
+                pixel_h += 1;
+            }
+
+            let mut audio = if !to_video_opts.mux_audio {None} else if to_video_opts.audio_passthrough {Some(Self::source_audio(input, video_opts))} else {audio_path.as_deref().map(|path| render::AudioSource::file(path, false))};
+            let sync = self.plan_audio_sync(audio.as_mut(), total_frames, video_opts.fps, to_video_opts)?;
+            let frame_rate = sync.map_or(video_opts.fps as f64, |sync| sync.encoded_fps);
+            audio_sync = audio_sync.or(sync);
+            let mut child = render::spawn_ffmpeg_encoder(pixel_w, pixel_h, frame_rate, audio.as_ref(), to_video_opts, self.config.attribution.as_ref(), &self.ffmpeg_config, self.event_log.as_ref())?;
+            let stdin = child.stdin.take();
+            let encoder = SinkEncoder {atlas, theme: render::Theme::of(to_video_opts), child: Some(child), stdin};
+            if encoder.stdin.is_none() {
+                return Err(Error::Other("failed to open ffmpeg stdin pipe".into()));
+            }
+            encoders.push(encoder);
+        }
+        let mut ansi_files = Vec::new();
+        for sink in sinks {
+            if let OutputSink::Ansi(path) = sink {
+                let mut file = std::io::BufWriter::new(fs::File::create(path).with_context(|| format!("creating {}", path.display()))?);
+                // Clear the screen once; every frame then starts with cursor-home
+                file.write_all(b"\x1b[2J").with_context(|| format!("writing {}", path.display()))?;
+                ansi_files.push((path, file));
+            }
+        }
+        let use_colors = conv_opts.output_mode != OutputMode::TextOnly;
+        let mut histogram = ColorHistogram::new();
+
+        // Phase 5: Process frames in batches sized to the memory budget: besides the batches in the
+        // pipeline, every worker holds a decoded source frame and the drawing buffer takes the largest video frame
+        let source_size = match &frame_pipe {
+            Some(pipe) => Ok((pipe.width, pipe.height)),
+            None => image::image_dimensions(&png_paths[0]),
+        };
+        let source_bytes = source_size.map_or(0, |(w, h)| w as u64 * h as u64 * 4);
+        let render_bytes = encoders.iter().map(|encoder| render::frame_buffer_len(&first_frame, &encoder.atlas) as u64).max().unwrap_or(0);
+        // Piped frames also wait decoded, up to a batch ahead of conversion, which is counted as if in every batch
+        let decoded_bytes = if frame_pipe.is_some() {source_bytes} else {0};
+        let batch_size = self.limits.batch_size(first_frame.heap_bytes() + decoded_bytes, source_bytes * rayon::current_num_threads() as u64 + render_bytes, PIPELINE_BATCHES);
+        // Frames too large to batch are handed over one at a time as they convert
+        let queued_batches = if batch_size == 1 {0} else {2};
+        let counter = ProgressCounter::throttled(total_frames);
+        let renders_video = !encoders.is_empty();
+        let report = |current: usize| progress_callback(if renders_video {Progress::rendering_video(current, total_frames)} else {Progress::converting_frames(current, total_frames)});
+
+        counter.start(report);
+
+        let frame_count = thread::scope(|scope| -> Result<usize> {
+            let (sender, receiver) = sync_channel::<Result<Vec<convert::AsciiFrameData>>>(queued_batches);
+            // The first frame was already converted for the resolution probe; feed it as the first batch instead of decoding it again.
+            let _ = sender.send(Ok(vec![first_frame]));
+            let png_paths = &png_paths;
+            let convert_frame = &convert_frame;
+            // Piped frames are read on a thread of their own, up to a batch ahead of conversion; PNGs are decoded by the workers
+            let decoded = frame_pipe.map(|mut pipe| {
+                let (decoded_sender, decoded) = sync_channel::<Result<video::PipedFrame>>(batch_size);
+                scope.spawn(move || {
+                    loop {
+                        match pipe.next_frame() {
+                            Ok(Some(image)) => {
+                                if decoded_sender.send(Ok(image)).is_err() {
+                                    return;
+                                }
+                            }
+                            Ok(None) => break,
+                            Err(err) => {
+                                let _ = decoded_sender.send(Err(err));
+                                return;
+                            }
+                        }
+                    }
+                    // The pipe is drained; a failed decode must not pass for the end of the video
+                    if let Err(err) = pipe.finish() {
+                        let _ = decoded_sender.send(Err(err));
+                    }
+                });
+                decoded
+            });
+            let worker = scope.spawn(move || -> usize {
+                let Some(decoded) = decoded else {
+                    for batch_start in (1..total_frames).step_by(batch_size) {
+                        let batch_end = (batch_start + batch_size).min(total_frames);
+                        let frame_data: Result<Vec<convert::AsciiFrameData>> = png_paths[batch_start..batch_end].par_iter().enumerate().map(|(offset, path)| convert_frame(batch_start + offset, &convert::open_image(path)?, None, Some(path))).collect();
+                        if sender.send(frame_data).is_err() {
+                            return batch_start;
+                        }
+                    }
+                    return total_frames;
+                };
+                let mut frames = 1;
+                loop {
+                    let frame_data = decoded.iter().take(batch_size).collect::<Result<Vec<_>>>().and_then(|images| {
+                        self.check_frame_count(frames + images.len())?;
+                        images.into_par_iter().enumerate().map(|(offset, image)| {
+                            let (image, luma) = image.decode(luminance)?;
+                            convert_frame(frames + offset, &image::DynamicImage::ImageRgb8(image), luma.as_ref(), None)
+                        }).collect::<Result<Vec<_>>>()
+                    });
+                    match frame_data {
+                        Ok(batch) if batch.is_empty() => return frames,
+                        Ok(batch) => {
+                            frames += batch.len();
+                            if sender.send(Ok(batch)).is_err() {
+                                return frames;
+                            }
+                        }
+                        Err(err) => {
+                            let _ = sender.send(Err(err));
+                            return frames;
+                        }
+                    }
+                }
+            });
+
+            let mut rgb_buf = Vec::new();
+            for frame_data in receiver {
+                let frame_data = frame_data?;
+
+                // Render, pipe and append sequentially (preserves frame order)
+                for frame in &frame_data {
+                    if self.cancel_token.as_ref().is_some_and(|c| c.is_cancelled()) {
+                        return Err(Cancelled.into());
+                    }
+                    for encoder in &mut encoders {
+                        encoder.atlas.add_chars(&frame.ascii_text)?;
+                        render::render_ascii_frame_into_rgb(frame, &encoder.atlas, use_colors, encoder.theme, stamp.as_deref(), &mut rgb_buf);
+                        if let Err(e) = render::encode_frame(encoder.stdin.as_mut().unwrap(), &rgb_buf) {
+                            drop(encoder.stdin.take());
+                            let output = encoder.child.take().unwrap().wait_with_output().context("waiting for ffmpeg")?;
+                            return Err(self.encoder_error(format!("ffmpeg encoding failed: {}", e), &output.stderr));
+                        }
+                    }
+                    if use_colors {
+                        histogram.add_rgb(&frame.rgb_colors);
+                        histogram.add_rgb(&frame.bg_rgb_colors);
+                    }
+                    for (path, file) in &mut ansi_files {
+                        convert::write_frame_ansi(frame, use_colors.then(|| conv_opts.output_mode.ansi_color().unwrap_or_default()), file).with_context(|| format!("writing {}", path.display()))?;
+                    }
+
+                    counter.complete_one(report);
+                }
+            }
+
+            worker.join().map_err(|_| Error::Other("frame conversion worker panicked".into()))
+        })?;
+        counter.finish(report);
+
+        for (path, mut file) in ansi_files {
+            file.flush().with_context(|| format!("writing {}", path.display()))?;
+        }
+
+        for mut encoder in encoders {
+            // Close stdin to signal end of input, then wait for ffmpeg to finish
+            drop(encoder.stdin.take());
+            let output = encoder.child.take().unwrap().wait_with_output().context("waiting for ffmpeg")?;
+            if !output.status.success() {
+                return Err(self.encoder_error("ffmpeg encoding failed".into(), &output.stderr));
+            }
+        }
+
+        // Phase 6: Complete
+        progress_callback(Progress::complete(frame_count));
+        let output_mode_str = match conv_opts.output_mode {
+            OutputMode::TextOnly => "text-only",
+            OutputMode::ColorOnly => "color-only",
+            OutputMode::TextAndColor => "text+color",
+            OutputMode::Ansi => "ansi",
+            OutputMode::Ansi256 => "ansi-256",
+            OutputMode::Json => "json",
+        };
+
+        let output_dir = match &sinks[0] {
+            OutputSink::Frames {dir, ..} => dir.clone(),
+            sink => sink.path().parent().unwrap_or(Path::new(".")).to_path_buf(),
+        };
+        let audio_extracted = frames_want_audio || video_sinks.iter().any(|to_video_opts| to_video_opts.mux_audio);
+        let (background_color, color) = match &sinks[0] {
+            OutputSink::Video(to_video_opts) => render::Theme::of(to_video_opts).names(),
+            _ => render::Theme::default().names(),
+        };
+        let result = ConversionResult {frame_count, columns: conv_opts.columns.unwrap_or(video_opts.columns), font_ratio: conv_opts.font_ratio, luminance: conv_opts.luminance, fps: Some(video_opts.fps), output_mode: output_mode_str.to_string(), audio_extracted, output_dir, background_color, color, fit_cell_backgrounds: conv_opts.cell_color_mode.fits_cell_backgrounds(), cell_background_mode: conv_opts.cell_color_mode.as_str().to_string(), bg_fit_quality: conv_opts.bg_fit_quality.as_str().to_string(), bg_luminance: conv_opts.resolve_bg_threshold(), ascii_chars: conv_opts.ascii_chars.clone(), audio_sync, colors: histogram.palette(), seed: conv_opts.seed, attribution: self.config.attribution.clone()};
+
+        // Write the details report into every frame directory
+        for (dir, _) in &frame_dirs {
+            ConversionResult {output_dir: dir.to_path_buf(), ..result.clone()}.write_report(&self.report_format)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Render existing ASCII frame files (.cframe or .txt) from a directory to a video file
+    ///
+    /// Scans the directory for .cframe files first; if none found, falls back to .txt files.
+    /// Renders each frame using the glyph atlas and pipes to ffmpeg.
+    pub fn render_frames_to_video<F: Fn(Progress) + Send + Sync>(&self, input_dir: &Path, fps: u32, to_video_opts: &ToVideoOptions, progress_callback: F) -> Result<ConversionResult> {
+        let progress_callback = self.observe(progress_callback);
+        self.logged("render_frames_to_video", input_dir, &to_video_opts.output_path, |result: &ConversionResult| result.frame_count, || self.render_frames_to_video_inner(input_dir, fps, to_video_opts, progress_callback))
+    }
+
+    fn render_frames_to_video_inner<F: Fn(Progress) + Send + Sync>(&self, input_dir: &Path, fps: u32, to_video_opts: &ToVideoOptions, progress_callback: F) -> Result<ConversionResult> {
+        // Scan for .cframe files first, then fall back to .txt
+        let mut frame_paths: Vec<PathBuf> = WalkDir::new(input_dir).min_depth(1).max_depth(1).into_iter().filter_map(|e| e.ok()).map(|e| e.into_path()).filter(|p| p.extension().map(|e| e == "cframe").unwrap_or(false)).collect();
+
+        let use_cframes = !frame_paths.is_empty();
+
+        if !use_cframes {
+            frame_paths = WalkDir::new(input_dir).min_depth(1).max_depth(1).into_iter().filter_map(|e| e.ok()).map(|e| e.into_path()).filter(|p| p.extension().map(|e| e == "txt").unwrap_or(false) && p.file_name().and_then(|n| n.to_str()).map(|n| n.starts_with("frame_")).unwrap_or(false)).collect();
+        }
+
+        frame_paths.sort();
+
+        let total_frames = frame_paths.len();
+        self.check_frame_count(total_frames)?;
+        if total_frames == 0 {
+            return Err(Error::NotFound(format!("No .cframe or .txt frame files found in {}", input_dir.display())));
+        }
+
+        // Build glyph atlas
+        let mut atlas = render::build_glyph_atlas_with_stroke(to_video_opts.font_size, to_video_opts.text_stroke_width)?;
+        let stamp = self.stamp();
+        atlas.add_chars(stamp.as_deref().unwrap_or_default())?;
+
+        // Read first frame to determine pixel dimensions
+        let first_frame = if use_cframes {
+            convert::read_cframe_to_frame_data(&frame_paths[0])?
+        } else {
+            convert::read_txt_to_frame_data(&frame_paths[0])?
+        };
+
+        let mut pixel_w = first_frame.width_chars * atlas.cell_width;
+        let mut pixel_h = first_frame.height_chars * atlas.cell_height;
+        if !pixel_w.is_multiple_of(2) {
+            pixel_w += 1;
+        }
+        if !pixel_h.is_multiple_of(2) {
+            pixel_h += 1;
+        }
+
+        // Check for audio.mp3 in the directory
+        let audio_path = if to_video_opts.mux_audio {
+            let ap = input_dir.join("audio.mp3");
+            if ap.exists() {
+                Some(ap)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        // Spawn ffmpeg encoder
+        let mut audio = audio_path.as_deref().map(|path| render::AudioSource::file(path, to_video_opts.audio_passthrough));
+        let audio_sync = self.plan_audio_sync(audio.as_mut(), total_frames, fps, to_video_opts)?;
+        let frame_rate = audio_sync.map_or(fps as f64, |sync| sync.encoded_fps);
+        let mut child = render::spawn_ffmpeg_encoder(pixel_w, pixel_h, frame_rate, audio.as_ref(), to_video_opts, self.config.attribution.as_ref(), &self.ffmpeg_config, self.event_log.as_ref())?;
+        let mut stdin = child.stdin.take().ok_or_else(|| Error::Other("failed to open ffmpeg stdin pipe".into()))?;
+
+        // Process frames in batches sized to the memory budget, reading frame by frame when they are huge
+        let batch_size = self.limits.batch_size(first_frame.heap_bytes(), render::frame_buffer_len(&first_frame, &atlas) as u64, 1);
+        let counter = ProgressCounter::throttled(total_frames);
+        let report = |current: usize| progress_callback(Progress::rendering_video(current, total_frames));
+        let render_with_colors = to_video_opts.use_colors.unwrap_or(use_cframes);
+        let theme = render::Theme::of(to_video_opts);
+        let mut histogram = ColorHistogram::new();
+        counter.start(report);
+
+        let mut rgb_buf = Vec::new();
+        for batch_start in (0..total_frames).step_by(batch_size) {
+            let batch_end = (batch_start + batch_size).min(total_frames);
+            let batch = &frame_paths[batch_start..batch_end];
+
+            // Read batch in parallel
+            let frame_data: Vec<convert::AsciiFrameData> = batch.par_iter().map(|path| if use_cframes {convert::read_cframe_to_frame_data(path)} else {convert::read_txt_to_frame_data(path)}).collect::<Result<Vec<_>>>()?;
+
+            // Render and pipe sequentially
+            for frame in &frame_data {
+                if self.cancel_token.as_ref().is_some_and(|token| token.is_cancelled()) {
+                    drop(stdin);
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(Cancelled.into());
+                }
+                atlas.add_chars(&frame.ascii_text)?;
+                render::render_ascii_frame_into_rgb(frame, &atlas, render_with_colors, theme, stamp.as_deref(), &mut rgb_buf);
+                histogram.add_rgb(&frame.rgb_colors);
+                histogram.add_rgb(&frame.bg_rgb_colors);
+                if let Err(e) = render::encode_frame(&mut stdin, &rgb_buf) {
+                    drop(stdin);
+                    let output = child.wait_with_output().context("waiting for ffmpeg")?;
+                    return Err(self.encoder_error(format!("ffmpeg encoding failed: {}", e), &output.stderr));
+                }
+
+                counter.complete_one(report);
+            }
+        }
+
+        drop(stdin);
+
+        let output = child.wait_with_output().context("waiting for ffmpeg")?;
+        if !output.status.success() {
+            return Err(self.encoder_error("ffmpeg encoding failed".into(), &output.stderr));
+        }
+
+        counter.finish(report);
+        progress_callback(Progress::complete(total_frames));
+
+        let mode_str = if use_cframes {"color"} else {"text-only"};
+
+        let fit_cell_backgrounds = first_frame.bg_rgb_colors.len() == (first_frame.width_chars * first_frame.height_chars * 3) as usize;
+        let (background_color, color) = theme.names();
+        Ok(ConversionResult {frame_count: total_frames, columns: first_frame.width_chars, font_ratio: 0.0, luminance: 0, fps: Some(fps), output_mode: mode_str.to_string(), audio_extracted: audio_path.is_some(), output_dir: to_video_opts.output_path.parent().unwrap_or(Path::new(".")).to_path_buf(), background_color, color, fit_cell_backgrounds, cell_background_mode: if fit_cell_backgrounds {"legacy"} else {"off"}.to_string(), bg_fit_quality: default_bg_fit_quality(), bg_luminance: 0, ascii_chars: default_ascii_chars(), audio_sync, colors: histogram.palette(), seed: 0, attribution: self.config.attribution.clone()})
+    }
+
+    /// Render existing .cframe/.txt frames to an animated GIF at `fps`, for places that take GIF but
+    /// not MP4 (READMEs, chat apps). Frames are read in frame-number order through [`FrameReader`]
+    /// and drawn with the same glyph atlas as [`render_frames_to_video`](Self::render_frames_to_video);
+    /// no ffmpeg is needed. GIF delays count in hundredths of a second, so rates that don't divide
+    /// 100 play slightly off.
+    ///
+    /// ```no_run
+    /// use cascii::{AsciiConverter, GifOptions};
+    /// use std::path::Path;
+    ///
+    /// let gif_opts = GifOptions {output_path: "clip.gif".into(), font_size: 10.0, ..GifOptions::default()};
+    /// AsciiConverter::new().render_frames_to_gif(Path::new("out/clip"), 12, &gif_opts).unwrap();
+    /// ```
+    pub fn render_frames_to_gif(&self, input_dir: &Path, fps: u32, gif_opts: &GifOptions) -> Result<ConversionResult> {
+        self.logged("render_frames_to_gif", input_dir, &gif_opts.output_path, |result: &ConversionResult| result.frame_count, || self.render_frames_to_gif_inner(input_dir, fps, gif_opts))
+    }
+
+    fn render_frames_to_gif_inner(&self, input_dir: &Path, fps: u32, gif_opts: &GifOptions) -> Result<ConversionResult> {
+        use image::codecs::gif::{GifEncoder, Repeat};
+
+        if fps == 0 {
+            return Err(Error::InvalidOptions("fps must be at least 1".into()));
+        }
+        if !(gif_opts.font_size.is_finite() && gif_opts.font_size > 0.0) {
+            return Err(Error::InvalidOptions(format!("font size must be a positive number of pixels, got {}", gif_opts.font_size)));
+        }
+        let mut frames = FrameReader::open(input_dir)?;
+        let total_frames = frames.len();
+        self.check_frame_count(total_frames)?;
+        let first = frames.next().ok_or_else(|| Error::NotFound(format!("No .cframe or .txt frame files found in {}", input_dir.display())))??;
+
+        let mut atlas = render::build_glyph_atlas(gif_opts.font_size)?;
+        let stamp = self.stamp();
+        atlas.add_chars(stamp.as_deref().unwrap_or_default())?;
+        let use_colors = gif_opts.use_colors.unwrap_or(!first.rgb.is_empty());
+        let file = fs::File::create(&gif_opts.output_path).with_context(|| format!("creating {}", gif_opts.output_path.display()))?;
+        let mut writer = std::io::BufWriter::new(file);
+        let mut encoder = GifEncoder::new_with_speed(&mut writer, gif_opts.quantize_speed.clamp(1, 30) as i32);
+        encoder.set_repeat(gif_opts.loop_count.map_or(Repeat::Infinite, Repeat::Finite)).context("writing the GIF header")?;
+        let delay = image::Delay::from_numer_denom_ms(1000, fps);
+
+        let mut histogram = ColorHistogram::new();
+        for (index, frame) in std::iter::once(Ok(first.clone())).chain(frames).enumerate() {
+            if self.cancel_token.as_ref().is_some_and(|token| token.is_cancelled()) {
+                return Err(Cancelled.into());
+            }
+            let frame = frame?;
+            if (frame.width, frame.height) != (first.width, first.height) {
+                return Err(Error::Other(format!("frame {} is {}x{} cells but the first frame is {}x{}; a GIF needs frames of one size", index + 1, frame.width, frame.height, first.width, first.height)));
+            }
+            atlas.add_chars(&frame.text)?;
+            let mut image = render::render_frame_with_atlas(&frame, &atlas, use_colors)?;
+            if let Some(text) = &stamp {
+                let (width, height) = image.dimensions();
+                render::draw_corner_text(&mut image, width, height, &atlas, text, render::Theme::default().foreground);
+            }
+            histogram.add_rgb(&frame.rgb);
+            histogram.add_rgb(&frame.bg_rgb);
+            let image = image::DynamicImage::ImageRgb8(image).into_rgba8();
+            encoder.encode_frame(image::Frame::from_parts(image, 0, 0, delay)).with_context(|| format!("encoding GIF frame {}", index + 1))?;
+        }
+        // The encoder writes the GIF trailer when dropped
+        drop(encoder);
+        writer.flush().with_context(|| format!("writing {}", gif_opts.output_path.display()))?;
+
+        let fit_cell_backgrounds = first.bg_rgb.len() == (first.width * first.height * 3) as usize;
+        Ok(ConversionResult {frame_count: total_frames, columns: first.width, font_ratio: 0.0, luminance: 0, fps: Some(fps), output_mode: if use_colors {"color"} else {"text-only"}.to_string(), audio_extracted: false, output_dir: gif_opts.output_path.parent().unwrap_or(Path::new(".")).to_path_buf(), background_color: "black".to_string(), color: "white".to_string(), fit_cell_backgrounds, cell_background_mode: if fit_cell_backgrounds {"legacy"} else {"off"}.to_string(), bg_fit_quality: default_bg_fit_quality(), bg_luminance: 0, ascii_chars: default_ascii_chars(), audio_sync: None, colors: histogram.palette(), seed: 0, attribution: self.config.attribution.clone()})
+    }
+}
+
+#[cfg(feature = "cli")]
+impl Default for AsciiConverter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Write `script` to `path` as an executable shell script standing in for ffmpeg, ffprobe or ffplay
+#[cfg(all(test, unix, feature = "cli"))]
+pub(crate) fn write_fake_program(path: &Path, script: &str) {
+    use std::os::unix::fs::PermissionsExt;
+    fs::write(path, script).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// A converter running `script`, written to `dir/ffmpeg`, as ffmpeg, with no ffprobe; rewriting
+/// `dir/ffmpeg` later changes what it runs
+#[cfg(all(test, unix, feature = "cli"))]
+pub(crate) fn fake_ffmpeg(dir: &Path, script: &str) -> AsciiConverter {
+    let ffmpeg = dir.join("ffmpeg");
+    write_fake_program(&ffmpeg, script);
+    AsciiConverter::new().with_ffmpeg_config(FfmpegConfig::new().with_ffmpeg(&ffmpeg).with_ffprobe(dir.join("no-ffprobe")))
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+    use crate::{OverallProgress, PhaseWeights, PipeFormat};
+
+    #[test]
+    fn sink_pass_needs_a_sink() {
+        let err = AsciiConverter::new().convert_video_to_sinks(Path::new("missing.mp4"), &VideoOptions::default(), &ConversionOptions::default(), &[], |_| {}).unwrap_err();
+        assert_eq!(err.to_string(), "no output sinks given");
+    }
+
+    #[test]
+    fn progress_channel_disconnects_when_the_conversion_fails() {
+        let dst = tempfile::tempdir().unwrap();
+        let ffmpeg = FfmpegConfig::new().with_ffmpeg(dst.path().join("no-ffmpeg")).with_ffprobe(dst.path().join("no-ffprobe"));
+        let (handle, progress) = AsciiConverter::new().with_ffmpeg_config(ffmpeg).convert_video_channel(dst.path().join("missing.mp4"), dst.path().join("frames"), VideoOptions::default(), ConversionOptions::default(), false, 1);
+        assert!(handle.join().unwrap().is_err());
+        assert!(progress.iter().all(|progress| progress.phase == ProgressPhase::ExtractingFrames && progress.percentage < 100.0));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_spans_mark_conversion_steps() {
+        use std::sync::{Arc, Mutex};
+        use tracing::span::{Attributes, Id, Record};
+
+        /// Collects the names of the spans opened
+        struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+        impl tracing::Subscriber for SpanNames {
+            fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut names = self.0.lock().unwrap();
+                names.push(span.metadata().name());
+                Id::from_u64(names.len() as u64)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &tracing::Event<'_>) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.png");
+        image::RgbImage::from_pixel(8, 8, image::Rgb([200, 100, 50])).save(&input).unwrap();
+        fs::write(dir.path().join("frame_0001.txt"), "#@\n").unwrap();
+        let names = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(SpanNames(names.clone()), || {
+            AsciiConverter::new().convert_image(&input, &dir.path().join("out.txt"), &ConversionOptions::default().with_columns(4)).unwrap();
+            AsciiConverter::new().render_frames_to_gif(dir.path(), 10, &GifOptions {output_path: dir.path().join("out.gif"), ..GifOptions::default()}).unwrap();
+        });
+        assert_eq!(names.lock().unwrap()[..5], ["decode", "convert", "conversion", "decode", "render"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_frames_streams_frames_from_ffmpeg_to_frames_and_video() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.png");
+        image::RgbImage::from_pixel(8, 4, image::Rgb([255, 255, 255])).save(&first).unwrap();
+        // Stands in for ffmpeg: the first frame as a PNG for the size probe, the encoder's input copied
+        // to its output file, and otherwise three white 8x4 raw frames
+        let converter = fake_ffmpeg(dir.path(), &format!("#!/bin/sh\ncase \"$*\" in *image2pipe*) cat '{}' ;; *pipe:0*) for arg; do out=$arg; done; cat > \"$out\" ;; *) head -c 288 /dev/zero | tr '\\000' '\\377' ;; esac\n", first.display()));
+
+        let out = dir.path().join("out");
+        let video_opts = VideoOptions {fps: 3, end: Some("1".into()), columns: 8, pipe_frames: true, ..VideoOptions::default()};
+        let result = converter.convert_video(Path::new("in.mp4"), &out, &video_opts, &ConversionOptions::default().with_columns(8), true).unwrap();
+        assert_eq!(result.frame_count, 3);
+        assert!(fs::read_to_string(out.join("frame_0003.txt")).unwrap().starts_with("$$$$$$$$"));
+        assert!(out.join("frame_0003.png").is_file() && !out.join("frame_0004.txt").exists());
+
+        // Video to video always streams, whatever pipe_frames says
+        let video = dir.path().join("out.mp4");
+        let video_opts = VideoOptions {pipe_frames: false, ..video_opts};
+        let to_video_opts = ToVideoOptions {output_path: video.clone(), ..ToVideoOptions::default()};
+        let result = converter.convert_video_to_video(Path::new("in.mp4"), &video_opts, &ConversionOptions::default().with_columns(8), &to_video_opts, |_| {}).unwrap();
+        assert_eq!(result.frame_count, 3);
+        let encoded = fs::read(&video).unwrap();
+        let frame_len = encoded.len() / 3;
+        assert!(frame_len > 0 && encoded.len() == 3 * frame_len && encoded[..frame_len] == encoded[frame_len..2 * frame_len]);
+
+        fs::write(dir.path().join("ffmpeg"), "#!/bin/sh\nexit 1\n").unwrap();
+        assert!(converter.convert_video(Path::new("in.mp4"), &out, &video_opts, &ConversionOptions::default(), false).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn two_stage_conversion_makes_the_preview_then_the_full_quality_current() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.png");
+        image::RgbImage::from_pixel(8, 4, image::Rgb([255, 255, 255])).save(&first).unwrap();
+        let converter = fake_ffmpeg(dir.path(), &format!("#!/bin/sh\ncase \"$*\" in *image2pipe*) cat '{}' ;; *) head -c 288 /dev/zero | tr '\\000' '\\377' ;; esac\n", first.display()));
+
+        let out = dir.path().join("out");
+        let (sender, events) = std::sync::mpsc::channel();
+        let sender = std::sync::Mutex::new(sender);
+        let video_opts = VideoOptions {fps: 3, end: Some("1".into()), columns: 8, pipe_frames: true, ..VideoOptions::default()};
+        let overall = OverallProgress::wrap(PhaseWeights::for_frames(), move |progress, overall| {
+            let _ = sender.lock().unwrap().send((progress.phase, progress.pass, overall));
+        });
+        let conversion = converter.convert_video_two_stage(Path::new("in.mp4"), &out, &video_opts, &ConversionOptions::default().with_columns(8), PreviewOptions::default(), overall).unwrap();
+        assert_eq!((conversion.preview.frame_count, conversion.preview_dir.clone()), (3, out.join(refine::PREVIEW_DIR)));
+        assert_eq!(conversion.full.join().unwrap().unwrap().frame_count, 3);
+        assert_eq!(refine::current_version(&out).unwrap(), out.join(refine::FULL_DIR));
+        assert!(out.join("v1_preview/frame_0003.txt").is_file() && out.join("v2_full/frame_0003.txt").is_file());
+        let (phases, rest): (Vec<ProgressPhase>, Vec<(Option<ConversionPass>, f64)>) = events.iter().map(|(phase, pass, overall)| (phase, (pass, overall))).unzip();
+        let position = |phase: ProgressPhase| phases.iter().position(|seen| *seen == phase).unwrap();
+        assert!(position(ProgressPhase::PreviewReady) < position(ProgressPhase::FullQualityReady));
+        assert_eq!((phases.iter().filter(|phase| **phase == ProgressPhase::Complete).count(), phases.last()), (1, Some(&ProgressPhase::Complete)));
+        // Both passes fill one bar: it never drops for the full-quality pass and only completing reaches 100%
+        let (passes, percentages): (Vec<Option<ConversionPass>>, Vec<f64>) = rest.into_iter().unzip();
+        assert!(percentages.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", percentages);
+        assert!(percentages[..percentages.len() - 1].iter().all(|&overall| overall < 100.0) && percentages.last() == Some(&100.0));
+        assert_eq!((passes[0], passes[position(ProgressPhase::PreviewReady) + 1]), (Some(ConversionPass::Preview), Some(ConversionPass::FullQuality)));
+        assert!(converter.convert_video_two_stage(&first, &out, &video_opts, &ConversionOptions::default(), PreviewOptions::default(), |_| {}).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn png_pipe_splits_frames_from_a_single_ffmpeg_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let (white, black) = (dir.path().join("white.png"), dir.path().join("black.png"));
+        image::RgbImage::from_pixel(8, 4, image::Rgb([255, 255, 255])).save(&white).unwrap();
+        image::RgbImage::from_pixel(8, 4, image::Rgb([0, 0, 0])).save(&black).unwrap();
+        // Only the PNG stream is answered, so neither a size probe nor raw frames can be relied on
+        let converter = fake_ffmpeg(dir.path(), &format!("#!/bin/sh\ncase \"$*\" in *-frames:v*) exit 1 ;; *image2pipe*) cat '{0}' '{1}' '{0}' ;; *) exit 1 ;; esac\n", white.display(), black.display()));
+
+        let out = dir.path().join("out");
+        let video_opts = VideoOptions {fps: 3, end: Some("1".into()), columns: 8, pipe_frames: true, pipe_format: PipeFormat::Png, ..VideoOptions::default()};
+        let result = converter.convert_video(Path::new("in.mp4"), &out, &video_opts, &ConversionOptions::default().with_columns(8), false).unwrap();
+        assert_eq!(result.frame_count, 3);
+        let frames: Vec<String> = (1..=3).map(|index| fs::read_to_string(out.join(format!("frame_{:04}.txt", index))).unwrap()).collect();
+        assert!(frames[0].starts_with("$$$$$$$$") && frames[2] == frames[0] && frames[1] != frames[0]);
+
+        // A stream cut inside a PNG is an error, not a shorter video
+        let cut = dir.path().join("cut.png");
+        fs::write(&cut, &fs::read(&white).unwrap()[..40]).unwrap();
+        fs::write(dir.path().join("ffmpeg"), format!("#!/bin/sh\ncat '{}' '{}'\n", white.display(), cut.display())).unwrap();
+        let err = converter.convert_video(Path::new("in.mp4"), &out, &video_opts, &ConversionOptions::default().with_columns(8), false).unwrap_err();
+        assert!(err.to_string().contains("reading frames from ffmpeg"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn piped_ffmpeg_can_fill_stderr_before_its_first_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        let white = dir.path().join("white.png");
+        image::RgbImage::from_pixel(8, 4, image::Rgb([255, 255, 255])).save(&white).unwrap();
+        // Far more warnings than a pipe buffer holds, written before any frame
+        let converter = fake_ffmpeg(dir.path(), &format!("#!/bin/sh
+head -c 1000000 /dev/zero | tr '\\000' w >&2
+cat '{}' '{}'
+", white.display(), white.display()));
+
+        let video_opts = VideoOptions {fps: 3, end: Some("1".into()), columns: 8, pipe_frames: true, pipe_format: PipeFormat::Png, ..VideoOptions::default()};
+        let result = converter.convert_video(Path::new("in.mp4"), &dir.path().join("out"), &video_opts, &ConversionOptions::default().with_columns(8), false).unwrap();
+        assert_eq!(result.frame_count, 2);
+
+        // What ffmpeg printed still explains a failure
+        fs::write(dir.path().join("ffmpeg"), "#!/bin/sh
+echo 'no such filter' >&2
+exit 1
+").unwrap();
+        let err = converter.convert_video(Path::new("in.mp4"), &dir.path().join("failed"), &video_opts, &ConversionOptions::default().with_columns(8), false).unwrap_err();
+        assert!(err.to_string().contains("no such filter"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn y4m_pipe_converts_yuv_frames_from_the_stream_header() {
+        let dir = tempfile::tempdir().unwrap();
+        // Limited-range white, black and red, as ffmpeg writes RGB sources; the frames carry parameters of their own
+        let mut stream = b"YUV4MPEG2 W8 H4 F3:1 Ip A1:1 C444 XYSCSS=444\n".to_vec();
+        for (y, u, v) in [(235u8, 128u8, 128u8), (16, 128, 128), (81, 90, 240)] {
+            stream.extend(b"FRAME Ixyz\n");
+            stream.extend([y; 32].iter().chain(&[u; 32]).chain(&[v; 32]));
+        }
+        let y4m = dir.path().join("in.y4m");
+        fs::write(&y4m, &stream).unwrap();
+        let converter = fake_ffmpeg(dir.path(), &format!("#!/bin/sh\ncase \"$*\" in *yuv4mpegpipe*) cat '{}' ;; *) exit 1 ;; esac\n", y4m.display()));
+
+        let out = dir.path().join("out");
+        let video_opts = VideoOptions {fps: 3, end: Some("1".into()), columns: 8, pipe_frames: true, pipe_format: PipeFormat::Y4m, ..VideoOptions::default()};
+        let conv_opts = ConversionOptions::default().with_columns(8).with_output_mode(OutputMode::TextAndColor);
+        assert_eq!(converter.convert_video(Path::new("in.mp4"), &out, &video_opts, &conv_opts, false).unwrap().frame_count, 3);
+        let white = convert::read_cframe_to_frame_data(&out.join("frame_0001.cframe")).unwrap();
+        assert!(white.ascii_text.starts_with("$$$$$$$$") && white.rgb_colors.iter().all(|&value| value == 255));
+        let black = convert::read_cframe_to_frame_data(&out.join("frame_0002.cframe")).unwrap();
+        assert!(black.rgb_colors.iter().all(|&value| value == 0));
+        let red = convert::read_cframe_to_frame_data(&out.join("frame_0003.cframe")).unwrap();
+        assert_eq!(&red.rgb_colors[..3], &[255, 0, 0]);
+
+        // Subsampled chroma is read at its own size; samples of more than 8 bits aren't read at all
+        let mut stream = b"YUV4MPEG2 W8 H4 F3:1 C420mpeg2\nFRAME\n".to_vec();
+        stream.extend([235u8; 32].iter().chain(&[128; 16]));
+        fs::write(&y4m, &stream).unwrap();
+        let out420 = dir.path().join("out420");
+        assert_eq!(converter.convert_video(Path::new("in.mp4"), &out420, &video_opts, &conv_opts, false).unwrap().frame_count, 1);
+        assert!(convert::read_cframe_to_frame_data(&out420.join("frame_0001.cframe")).unwrap().rgb_colors.iter().all(|&value| value == 255));
+        fs::write(&y4m, b"YUV4MPEG2 W8 H4 F3:1 C420p10\n").unwrap();
+        assert!(converter.convert_video(Path::new("in.mp4"), &out, &video_opts, &conv_opts, false).unwrap_err().to_string().contains("C420p10"));
+    }
+
+    #[test]
+    fn builder_validates_and_enforces_limits() {
+        let src = tempfile::tempdir().unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        for i in 0..3 {
+            image::RgbImage::from_pixel(8, 8, image::Rgb([40 * i as u8, 90, 200])).save(src.path().join(format!("frame_{:04}.png", i))).unwrap();
+        }
+        assert!(AsciiConverter::builder().with_threads(0).build().is_err());
+        assert!(AsciiConverter::builder().with_config(AppConfig::default()).with_config_file("cascii.json").build().is_err());
+        assert!(AsciiConverter::builder().with_config_file(dst.path().join("missing.json")).build().is_err());
+
+        let cache = dst.path().join("cache");
+        let converter = AsciiConverter::builder().with_threads(2).with_cache_dir(&cache).with_limits(ConverterLimits::default().with_max_frames(2).with_max_input_bytes(64)).build().unwrap();
+        assert!(cache.is_dir());
+        let options = ConversionOptions::default().with_columns(4);
+        let err = converter.convert_directory(src.path(), &dst.path().join("out"), &options, true).unwrap_err();
+        assert_eq!(err.to_string(), "3 frames exceed the limit of 2");
+        assert!(converter.convert_image(&src.path().join("frame_0000.png"), &dst.path().join("out.txt"), &options).unwrap_err().to_string().contains("byte input limit"));
+
+        let converter = AsciiConverter::builder().with_threads(1).with_limits(ConverterLimits::default().with_max_frames(3)).build().unwrap();
+        assert_eq!(converter.convert_directory(src.path(), &dst.path().join("out"), &options, true).unwrap(), 3);
+    }
+
+    #[test]
+    fn memory_budget_sizes_render_batches() {
+        const MB: u64 = 1024 * 1024;
+        assert_eq!(ConverterLimits::default().batch_size(100_000, 20 * MB, PIPELINE_BATCHES), MAX_BATCH_FRAMES);
+        // 800 columns drawn at 14 px: about 75 MB of pixels, so a 256 MB budget keeps a few frames per batch
+        let limits = ConverterLimits::default().with_memory_budget(256 * MB);
+        assert_eq!(limits.batch_size(25 * MB, 75 * MB, PIPELINE_BATCHES), 1);
+        assert_eq!(limits.batch_size(5 * MB, 75 * MB, PIPELINE_BATCHES), 9);
+        assert_eq!(limits.batch_size(5 * MB, 300 * MB, 1), 1);
+        assert!(AsciiConverter::builder().with_limits(ConverterLimits::default().with_memory_budget(0)).build().is_err());
+    }
+
+    #[test]
+    fn image_bytes_convert_like_image_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gradient.png");
+        image::RgbImage::from_fn(32, 16, |x, _| image::Rgb([(x * 8) as u8; 3])).save(&path).unwrap();
+        let options = ConversionOptions::default().with_columns(16);
+        let converter = AsciiConverter::new();
+        assert_eq!(converter.image_bytes_to_string(&fs::read(&path).unwrap(), &options).unwrap(), converter.image_to_string(&path, &options).unwrap());
+        assert!(converter.image_bytes_to_string(b"not an image", &options).is_err());
+    }
+
+    #[test]
+    fn image_to_frame_holds_what_convert_image_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gradient.png");
+        image::RgbImage::from_fn(32, 16, |x, y| image::Rgb([(x * 8) as u8, (y * 16) as u8, 90])).save(&path).unwrap();
+        let options = ConversionOptions::default().with_columns(16).with_output_mode(OutputMode::TextAndColor).with_cell_color_mode(CellColorMode::FitForegroundBackground);
+        let converter = AsciiConverter::new();
+        converter.convert_image(&path, &dir.path().join("frame.txt"), &options).unwrap();
+
+        let frame = converter.image_to_frame(&path, &options).unwrap();
+        assert_eq!(frame.cframe_bytes(), fs::read(dir.path().join("frame.cframe")).unwrap());
+        assert_eq!(frame.text(), fs::read_to_string(dir.path().join("frame.txt")).unwrap());
+        let cell = frame.cell(15, frame.height() - 1).unwrap();
+        assert_eq!(cell.fg.unwrap()[..], frame.rgb()[frame.rgb().len() - 3..]);
+        assert!(cell.bg.is_some());
+        assert_eq!(frame.cells().count(), (frame.width() * frame.height()) as usize);
+        assert!(frame.cell(16, 0).is_none());
+    }
+
+    #[test]
+    fn frames_render_to_an_animated_gif() {
+        use image::AnimationDecoder;
+
+        let src = tempfile::tempdir().unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        for i in 0..3u8 {
+            image::RgbImage::from_fn(12, 8, |x, _| image::Rgb([80 * i, 20 * x as u8, 200])).save(src.path().join(format!("frame_{:04}.png", i + 1))).unwrap();
+        }
+        let converter = AsciiConverter::new();
+        converter.convert_directory(src.path(), dst.path(), &ConversionOptions::default().with_font_ratio(1.0).with_output_mode(OutputMode::TextAndColor), false).unwrap();
+
+        let gif_opts = GifOptions {output_path: dst.path().join("clip.gif"), font_size: 8.0, loop_count: Some(2), ..GifOptions::default()};
+        let result = converter.render_frames_to_gif(dst.path(), 20, &gif_opts).unwrap();
+        assert_eq!((result.frame_count, result.output_mode.as_str()), (3, "color"));
+
+        let atlas = render::build_glyph_atlas(8.0).unwrap();
+        let decoder = image::codecs::gif::GifDecoder::new(std::io::BufReader::new(fs::File::open(&gif_opts.output_path).unwrap())).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].buffer().dimensions(), (12 * atlas.cell_width, 8 * atlas.cell_height));
+        assert_eq!(frames[2].delay().numer_denom_ms(), (50, 1));
+
+        let err = converter.render_frames_to_gif(src.path(), 20, &gif_opts).unwrap_err();
+        assert!(err.to_string().contains("no frame_*.cframe"), "{}", err);
+    }
+
+    #[test]
+    fn attribution_stamps_reports_and_renders() {
+        let src = tempfile::tempdir().unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        image::RgbImage::new(40, 20).save(src.path().join("frame_0001.png")).unwrap();
+        let config = AppConfig {attribution: Some(Attribution::default().with_creator("Ada").with_license("CC0")), ..AppConfig::default()};
+        let converter = AsciiConverter::with_config(config).unwrap();
+        converter.convert_directory(src.path(), dst.path(), &ConversionOptions::default().with_columns(20).with_font_ratio(1.0), false).unwrap();
+
+        // The frame is blank, so only the stamp in the bottom-right corner draws anything
+        let gif_opts = GifOptions {output_path: dst.path().join("clip.gif"), font_size: 8.0, ..GifOptions::default()};
+        let details = converter.render_frames_to_gif(dst.path(), 10, &gif_opts).unwrap().to_details_string();
+        assert!(details.contains("[attribution]") && details.contains("creator = \"Ada\""), "{}", details);
+        let image = image::open(&gif_opts.output_path).unwrap().to_luma8();
+        let (width, height) = image.dimensions();
+        let lit: Vec<(u32, u32)> = image.enumerate_pixels().filter(|(_, _, pixel)| pixel[0] > 32).map(|(x, y, _)| (x, y)).collect();
+        assert!(!lit.is_empty() && lit.iter().all(|&(x, y)| x > width / 2 && y > height / 2), "{:?}", lit);
+    }
+
+    #[test]
+    fn converter_writes_event_log() {
+        use events::{read_events, Event, EventLog};
+
+        let src = tempfile::tempdir().unwrap();
+        let dst = tempfile::tempdir().unwrap();
+        for i in 0..3 {
+            image::RgbImage::from_pixel(8, 8, image::Rgb([40 * i as u8, 90, 200])).save(src.path().join(format!("frame_{:04}.png", i))).unwrap();
+        }
+        let log_path = dst.path().join("events.jsonl");
+        let log = EventLog::open_with_checkpoint_every(&log_path, 2).unwrap();
+        let converter = AsciiConverter::new().with_event_log(log.clone());
+        assert_eq!(converter.convert_directory(src.path(), dst.path(), &ConversionOptions::default().with_columns(4), true).unwrap(), 3);
+        let token = CancelToken::new();
+        token.cancel();
+        assert!(AsciiConverter::new().with_event_log(log).with_cancel_token(token).convert_directory(src.path(), dst.path(), &ConversionOptions::default(), true).is_err());
+
+        let events: Vec<Event> = read_events(&log_path).unwrap().into_iter().map(|record| record.event).collect();
+        assert!(matches!(&events[0], Event::Started {operation, ..} if operation == "convert_directory"));
+        assert!(events.iter().any(|event| matches!(event, Event::Checkpoint {completed: 3, total: 3, ..})));
+        assert!(events.contains(&Event::Completed {frames: Some(3)}));
+        assert_eq!(events.last(), Some(&Event::Cancelled));
+    }
+
+    #[test]
+    fn json_output_mode_writes_cell_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.png");
+        image::RgbImage::from_fn(4, 2, |x, _| if x < 2 {image::Rgb([255, 0, 0])} else {image::Rgb([0, 0, 0])}).save(&input).unwrap();
+        let options = ConversionOptions::default().with_columns(4).with_font_ratio(0.5).with_output_mode(OutputMode::Json);
+        AsciiConverter::new().convert_image(&input, &dir.path().join("frame_0001.txt"), &options).unwrap();
+        assert!(!dir.path().join("frame_0001.txt").exists());
+
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path().join("frame_0001.json")).unwrap()).unwrap();
+        assert_eq!((json["width"].as_u64(), json["height"].as_u64()), (Some(4), Some(1)));
+        let cells = json["cells"].as_array().unwrap();
+        assert_eq!(cells.len(), 4);
+        assert_eq!(cells[0]["color"], serde_json::json!([255, 0, 0]));
+        assert!(cells[0]["char"].as_str().is_some_and(|ch| ch.chars().count() == 1) && cells[0].get("background").is_none());
+
+        let frame = AsciiFrame {text: "ab\n".into(), width: 2, height: 1, rgb: vec![1, 2, 3, 4, 5, 6], bg_rgb: vec![0; 6]};
+        assert_eq!(frame.to_json(), r#"{"width":2,"height":1,"cells":[{"char":"a","color":[1,2,3],"background":[0,0,0]},{"char":"b","color":[4,5,6],"background":[0,0,0]}]}"#);
+        assert_eq!(serde_json::from_str::<OutputMode>("\"json\"").unwrap(), OutputMode::Json);
+    }
+
+    #[test]
+    fn convert_spritesheet_writes_tiles_as_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        // Two rows of two 8x8 tiles: white, black, white, and an empty last slot
+        let mut sheet = image::RgbaImage::new(16, 16);
+        for (x, y, pixel) in sheet.enumerate_pixels_mut() {
+            let tile = (y / 8) * 2 + x / 8;
+            *pixel = match tile {0 | 2 => image::Rgba([255; 4]), 1 => image::Rgba([0, 0, 0, 255]), _ => image::Rgba([0; 4])};
+        }
+        let input = dir.path().join("sheet.png");
+        sheet.save(&input).unwrap();
+
+        let out = dir.path().join("frames");
+        let options = ConversionOptions::default().with_columns(4).with_font_ratio(1.0);
+        let result = AsciiConverter::new().convert_spritesheet(&input, 8, 8, &out, 12, &options).unwrap();
+        assert_eq!((result.frame_count, result.columns, result.fps), (3, 4, Some(12)));
+        assert_eq!(fs::read_to_string(out.join("frame_0001.txt")).unwrap().lines().next(), Some("$$$$"));
+        assert_eq!(fs::read_to_string(out.join("frame_0002.txt")).unwrap().lines().next(), Some("    "));
+        assert!(out.join("frame_0003.txt").is_file() && !out.join("frame_0004.txt").exists());
+        assert!(fs::read_to_string(out.join("details.toml")).unwrap().contains("fps = 12"));
+        assert!(AsciiConverter::new().convert_spritesheet(&input, 8, 8, &out, 0, &options).is_err());
+    }
+}
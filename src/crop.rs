@@ -1,9 +1,12 @@
-use anyhow::{anyhow, Context, Result};
+use crate::error::{Context, Error, Result};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 use walkdir::WalkDir;
 
+use crate::backup::BACKUP_DIR_NAME;
 use crate::convert::{read_cframe_to_frame_data, write_cframe_binary};
 
 /// Result of a crop operation
@@ -28,7 +31,7 @@ pub struct CropResult {
 /// Frames are re-indexed starting from `frame_0001` in the output directory.
 pub fn crop_frames(source_dir: &Path, top: usize, bottom: usize, left: usize, right: usize, output_dir: &Path) -> Result<CropResult> {
     if !source_dir.exists() {
-        return Err(anyhow!("Source directory does not exist: {}", source_dir.display()));
+        return Err(Error::NotFound(format!("Source directory does not exist: {}", source_dir.display())));
     }
 
     fs::create_dir_all(output_dir).with_context(|| format!("creating output directory {}", output_dir.display()))?;
@@ -48,23 +51,23 @@ pub fn crop_frames(source_dir: &Path, top: usize, bottom: usize, left: usize, ri
     txt_frames.sort();
 
     if txt_frames.is_empty() {
-        return Err(anyhow!("No frame_*.txt files found in {}", source_dir.display()));
+        return Err(Error::NotFound(format!("No frame_*.txt files found in {}", source_dir.display())));
     }
 
     // Validate dimensions on the first frame
     let first_content = fs::read_to_string(&txt_frames[0]).with_context(|| format!("reading {}", txt_frames[0].display()))?;
     let first_lines: Vec<&str> = first_content.lines().collect();
     if first_lines.is_empty() {
-        return Err(anyhow!("First frame is empty: {}", txt_frames[0].display()));
+        return Err(Error::InvalidCframe(format!("First frame is empty: {}", txt_frames[0].display())));
     }
     let frame_height = first_lines.len();
-    let frame_width = first_lines[0].chars().count();
+    let frame_width = display_width(first_lines[0]);
 
     if top + bottom >= frame_height {
-        return Err(anyhow!("Crop rows ({} top + {} bottom = {}) exceed frame height ({})", top, bottom, top + bottom, frame_height));
+        return Err(Error::InvalidOptions(format!("Crop rows ({} top + {} bottom = {}) exceed frame height ({})", top, bottom, top + bottom, frame_height)));
     }
     if left + right >= frame_width {
-        return Err(anyhow!("Crop columns ({} left + {} right = {}) exceed frame width ({})", left, right, left + right, frame_width));
+        return Err(Error::InvalidOptions(format!("Crop columns ({} left + {} right = {}) exceed frame width ({})", left, right, left + right, frame_width)));
     }
 
     let new_width = (frame_width - left - right) as u32;
@@ -80,8 +83,7 @@ pub fn crop_frames(source_dir: &Path, top: usize, bottom: usize, left: usize, ri
 
         let mut cropped_lines: Vec<String> = Vec::with_capacity(new_height as usize);
         for line in lines.iter().skip(top).take(new_height as usize) {
-            let slice: String = line.chars().skip(left).take(new_width as usize).collect();
-            cropped_lines.push(slice);
+            cropped_lines.push(slice_columns(line, left, new_width as usize));
         }
         let cropped_text = cropped_lines.join("\n") + "\n";
 
@@ -94,6 +96,7 @@ pub fn crop_frames(source_dir: &Path, top: usize, bottom: usize, left: usize, ri
         if cframe_path.exists() {
             let frame_data = read_cframe_to_frame_data(&cframe_path)?;
             let orig_w = frame_data.width_chars as usize;
+            let cells: Vec<char> = frame_data.ascii_text.chars().filter(|&ch| ch != '\n').collect();
 
             let mut cropped_ascii = String::with_capacity((new_width as usize + 1) * new_height as usize);
             let mut cropped_rgb: Vec<u8> = Vec::with_capacity((new_width * new_height * 3) as usize);
@@ -103,9 +106,8 @@ pub fn crop_frames(source_dir: &Path, top: usize, bottom: usize, left: usize, ri
             for row in top..(frame_height - bottom) {
                 for col in left..(frame_width - right) {
                     let src_idx = row * orig_w + col;
-                    let char_offset = row * (orig_w + 1) + col;
-                    if let Some(ch) = frame_data.ascii_text.as_bytes().get(char_offset) {
-                        cropped_ascii.push(*ch as char);
+                    if let Some(&ch) = cells.get(src_idx) {
+                        cropped_ascii.push(ch);
                     }
                     let rgb_offset = src_idx * 3;
                     cropped_rgb.push(frame_data.rgb_colors[rgb_offset]);
@@ -132,24 +134,33 @@ pub fn crop_frames(source_dir: &Path, top: usize, bottom: usize, left: usize, ri
 /// Trim frames in-place. If `path` is a file, trims that single file.
 /// If `path` is a directory, trims all `frame_*.txt` files inside it.
 pub fn run_trim(path: &Path, trim_left: usize, trim_right: usize, trim_top: usize, trim_bottom: usize) -> Result<()> {
+    for file in trim_targets(path)? {
+        trim_file(&file, trim_left, trim_right, trim_top, trim_bottom)?;
+    }
+    Ok(())
+}
+
+/// The files [`run_trim`] rewrites for `path`: the file itself, or every `frame_*.txt` under a
+/// directory (outside its [backup](crate::backup)).
+pub fn trim_targets(path: &Path) -> Result<Vec<PathBuf>> {
     if path.is_file() {
-        trim_file(path, trim_left, trim_right, trim_top, trim_bottom)?;
-    } else if path.is_dir() {
-        // Find all frame_*.txt recursively and process them
-        for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
-            let p = entry.path();
-            if p.is_file() {
-                if let Some(name) = p.file_name().and_then(|s| s.to_str()) {
-                    if name.starts_with("frame_") && name.ends_with(".txt") {
-                        trim_file(p, trim_left, trim_right, trim_top, trim_bottom)?;
-                    }
+        return Ok(vec![path.to_path_buf()]);
+    } else if !path.is_dir() {
+        return Err(Error::NotFound(format!("Path does not exist: {}", path.display())));
+    }
+    // Find all frame_*.txt recursively
+    let mut targets = Vec::new();
+    for entry in WalkDir::new(path).into_iter().filter_entry(|e| e.file_name() != BACKUP_DIR_NAME).filter_map(|e| e.ok()) {
+        let p = entry.path();
+        if p.is_file() {
+            if let Some(name) = p.file_name().and_then(|s| s.to_str()) {
+                if name.starts_with("frame_") && name.ends_with(".txt") {
+                    targets.push(p.to_path_buf());
                 }
             }
         }
-    } else {
-        return Err(anyhow!("Path does not exist: {}", path.display()));
     }
-    Ok(())
+    Ok(targets)
 }
 
 fn trim_file(path: &Path, trim_left: usize, trim_right: usize, trim_top: usize, trim_bottom: usize) -> Result<()> {
@@ -157,24 +168,24 @@ fn trim_file(path: &Path, trim_left: usize, trim_right: usize, trim_top: usize,
     let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
 
     if lines.is_empty() {
-        return Err(anyhow!("Cannot trim empty file: {}", path.display()));
+        return Err(Error::InvalidCframe(format!("Cannot trim empty file: {}", path.display())));
     }
 
     let height = lines.len();
-    let width = lines[0].chars().count();
+    let width = display_width(&lines[0]);
 
     // Validate rectangular and strip potential trailing \r
     for (idx, line) in lines.iter().enumerate() {
-        if line.chars().count() != width {
-            return Err(anyhow!("Non-rectangular frame at {} line {}", path.display(), idx + 1));
+        if display_width(line) != width {
+            return Err(Error::InvalidCframe(format!("Non-rectangular frame at {} line {}", path.display(), idx + 1)));
         }
     }
 
     if trim_top + trim_bottom >= height {
-        return Err(anyhow!("Trim rows exceed or equal file height ({} >= {}) for {}", trim_top + trim_bottom, height, path.display()));
+        return Err(Error::InvalidOptions(format!("Trim rows exceed or equal file height ({} >= {}) for {}", trim_top + trim_bottom, height, path.display())));
     }
     if trim_left + trim_right >= width {
-        return Err(anyhow!("Trim columns exceed or equal file width ({} >= {}) for {}", trim_left + trim_right, width, path.display()));
+        return Err(Error::InvalidOptions(format!("Trim columns exceed or equal file width ({} >= {}) for {}", trim_left + trim_right, width, path.display())));
     }
 
     // Apply vertical trims
@@ -183,15 +194,66 @@ fn trim_file(path: &Path, trim_left: usize, trim_right: usize, trim_top: usize,
     let mut trimmed: Vec<String> = Vec::with_capacity(end_row_exclusive - start_row);
 
     for line in lines.iter().take(end_row_exclusive).skip(start_row) {
-        // Apply horizontal trims using char indices (to handle unicode safely)
-        let left = trim_left;
-        let right = trim_right;
-        let take_len = width - left - right;
-        let slice: String = line.chars().skip(left).take(take_len).collect();
-        trimmed.push(slice);
+        trimmed.push(slice_columns(line, trim_left, width - trim_left - trim_right));
     }
 
     let new_content = trimmed.join("\n") + "\n";
     fs::write(path, new_content).with_context(|| format!("writing {}", path.display()))?;
     Ok(())
 }
+
+/// Terminal columns a grapheme cluster takes: 2 for East Asian wide and fullwidth characters, 1 otherwise
+/// (combining marks and other zero-width clusters still hold a cell of their own in a frame).
+fn cluster_width(cluster: &str) -> usize {
+    cluster.width().clamp(1, 2)
+}
+
+/// Terminal columns `line` takes, counting grapheme clusters rather than chars.
+fn display_width(line: &str) -> usize {
+    line.graphemes(true).map(cluster_width).sum()
+}
+
+/// The `width` columns of `line` starting at column `left`. A wide character cut by either edge
+/// becomes spaces for its columns inside the slice, so every slice of a rectangular frame keeps
+/// the same width.
+fn slice_columns(line: &str, left: usize, width: usize) -> String {
+    let right = left + width;
+    let mut slice = String::with_capacity(width);
+    let mut column = 0;
+    for cluster in line.graphemes(true) {
+        let end = column + cluster_width(cluster);
+        if column >= left && end <= right {
+            slice.push_str(cluster);
+        } else if end > left && column < right {
+            slice.extend(std::iter::repeat_n(' ', end.min(right) - column.max(left)));
+        }
+        column = end;
+        if column >= right {
+            break;
+        }
+    }
+    slice
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_counts_wide_characters_as_two_columns() -> Result<()> {
+        assert_eq!((display_width("a漢b"), display_width("e\u{301}x")), (4, 2));
+        // A wide character cut by either edge leaves spaces, keeping rows the same width
+        assert_eq!(slice_columns("a漢b", 0, 2), "a ");
+        assert_eq!(slice_columns("a漢b", 2, 2), " b");
+        assert_eq!(slice_columns("e\u{301}x漢", 0, 2), "e\u{301}x");
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("frame_0001.txt");
+        fs::write(&path, "ab漢cd\n漢字ef\n......\n")?;
+        run_trim(&path, 1, 1, 0, 1)?;
+        assert_eq!(fs::read_to_string(&path)?, "b漢c\n 字e\n");
+        fs::write(&path, "ab漢\nabc\n")?;
+        assert!(run_trim(&path, 1, 0, 0, 0).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+//! Single-file animations: `.cvid` files holding a whole frame sequence with its audio.
+//!
+//! A clip converted to a directory is thousands of `frame_N` files, awkward to ship to an embedded
+//! player. [`write_cvid`] packs the frames of a directory (or a `.casc` archive) into one file with
+//! the clip's frame rate and size up front, a table of where every frame is, and the directory's
+//! `audio.mp3` at the end. [`CvidReader`] opens it and reads any frame by index without reading the
+//! others, and [`FrameSequence::open`](crate::FrameSequence::open) plays it like a directory.
+//!
+//! Layout, little-endian:
+//! - bytes 0..4: magic `CVID`
+//! - bytes 4..8: version (`u32`, currently `1`)
+//! - bytes 8..12: frame count (`u32`)
+//! - bytes 12..16: frames per second (`u32`, `0` when the clip recorded none)
+//! - bytes 16..24: width and height of the first frame in cells (`u32` each)
+//! - bytes 24..40: offset and length of the audio (`u64` each, both `0` without audio)
+//! - the frame table: offset (`u64`) and length (`u32`) of every frame
+//! - the frames as complete `.cframe` files, then the audio
+//!
+//! ```no_run
+//! use cascii::cvid::{write_cvid, CvidReader};
+//!
+//! # fn main() -> cascii::Result<()> {
+//! write_cvid("out/clip", "clip.cvid")?;
+//! let clip = CvidReader::open("clip.cvid")?;
+//! let last = clip.frame(clip.len() - 1)?;
+//! println!("{} frames at {:?} fps, {}x{}", clip.len(), clip.fps(), clip.width(), clip.height());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::convert::cframe_bytes_to_frame_data;
+use crate::error::{Context, Error, Result};
+use crate::frame::AsciiFrame;
+use crate::packed::ReadSeek;
+use crate::reader::FrameSequence;
+
+/// File extension of single-file animations
+pub const CVID_EXTENSION: &str = "cvid";
+
+const CVID_MAGIC: &[u8; 4] = b"CVID";
+const CVID_VERSION: u32 = 1;
+const CVID_HEADER_SIZE: usize = 40;
+/// Bytes of one frame table entry: the offset and the length of a frame
+const CVID_INDEX_ENTRY_SIZE: usize = 12;
+
+/// Pack the frames of `dir`, a frame directory or a `.casc` archive, into the `.cvid` file `path`
+/// with the fps of its `details.toml` and its `audio.mp3`, when it has them. `.cframe` frames are
+/// stored byte for byte; text-only frames become `.cframe` frames in white. Returns the number of
+/// frames written.
+pub fn write_cvid(dir: impl AsRef<Path>, path: impl AsRef<Path>) -> Result<usize> {
+    let (dir, path) = (dir.as_ref(), path.as_ref());
+    let sequence = FrameSequence::open(dir)?;
+    let count = u32::try_from(sequence.len()).map_err(|_| Error::Other("too many frames for a .cvid file".into()))?;
+    let first = sequence.read(0)?;
+    let fps = crate::loop_detect::recorded_fps(dir).unwrap_or(0);
+    let audio_path = dir.join("audio.mp3");
+    let audio = if audio_path.is_file() {Some(std::fs::read(&audio_path).with_context(|| format!("reading {}", audio_path.display()))?)} else {None};
+
+    let write = || -> Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        // The table goes in once the frames are written and their offsets known
+        let mut offset = (CVID_HEADER_SIZE + CVID_INDEX_ENTRY_SIZE * count as usize) as u64;
+        out.seek(SeekFrom::Start(offset))?;
+        let mut table = Vec::with_capacity(CVID_INDEX_ENTRY_SIZE * count as usize);
+        for index in 0..sequence.len() {
+            let frame = sequence.cframe_bytes(index)?;
+            let len = u32::try_from(frame.len()).map_err(|_| Error::InvalidCframe(format!("frame {} is too large for a .cframe", index + 1)))?;
+            out.write_all(&frame)?;
+            table.extend(offset.to_le_bytes());
+            table.extend(len.to_le_bytes());
+            offset += len as u64;
+        }
+        let (audio_offset, audio_len) = match &audio {
+            Some(audio) => {
+                out.write_all(audio)?;
+                (offset, audio.len() as u64)
+            }
+            None => (0, 0),
+        };
+
+        let mut header = Vec::with_capacity(CVID_HEADER_SIZE);
+        header.extend(CVID_MAGIC);
+        for value in [CVID_VERSION, count, fps, first.width, first.height] {
+            header.extend(value.to_le_bytes());
+        }
+        header.extend(audio_offset.to_le_bytes());
+        header.extend(audio_len.to_le_bytes());
+        out.seek(SeekFrom::Start(0))?;
+        out.write_all(&header)?;
+        out.write_all(&table)?;
+        out.flush()?;
+        Ok(())
+    };
+    write().with_context(|| format!("writing {}", path.display()))?;
+    Ok(count as usize)
+}
+
+/// A `.cvid` file opened for reading, frames read from the file by index as they are asked for.
+pub struct CvidReader {
+    source: Mutex<Box<dyn ReadSeek + Send>>,
+    fps: u32,
+    width: u32,
+    height: u32,
+    /// Offset and length of every frame
+    frames: Vec<(u64, u32)>,
+    audio: Option<(u64, u64)>,
+}
+
+impl CvidReader {
+    /// Open a `.cvid` file, reading its header and frame table but no frames
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        Self::from_reader(file).with_context(|| format!("reading {}", path.display()))
+    }
+
+    /// Read the header and the frame table of a `.cvid` file, checking every span lies in it
+    fn from_reader(mut source: impl Read + Seek + Send + 'static) -> Result<Self> {
+        let total = source.seek(SeekFrom::End(0))?;
+        source.seek(SeekFrom::Start(0))?;
+        let mut header = [0u8; CVID_HEADER_SIZE];
+        source.read_exact(&mut header).map_err(|_| Error::InvalidCframe(".cvid file is too small".into()))?;
+        if &header[0..4] != CVID_MAGIC {
+            return Err(Error::InvalidCframe(".cvid file has invalid magic".into()));
+        }
+        let word = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        let long = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap());
+        if word(4) != CVID_VERSION {
+            return Err(Error::InvalidCframe(format!("unsupported .cvid version: {}", word(4))));
+        }
+        let count = word(8) as usize;
+        if count == 0 {
+            return Err(Error::InvalidCframe(".cvid file contains no frames".into()));
+        }
+        let in_file = |offset: u64, len: u64| offset.checked_add(len).is_some_and(|end| end <= total);
+        if !in_file(CVID_HEADER_SIZE as u64, (CVID_INDEX_ENTRY_SIZE * count) as u64) {
+            return Err(Error::InvalidCframe(".cvid file is truncated inside its frame table".into()));
+        }
+        let mut table = vec![0u8; CVID_INDEX_ENTRY_SIZE * count];
+        source.read_exact(&mut table)?;
+        let frames: Vec<(u64, u32)> = table.chunks_exact(CVID_INDEX_ENTRY_SIZE).map(|entry| (u64::from_le_bytes(entry[0..8].try_into().unwrap()), u32::from_le_bytes(entry[8..12].try_into().unwrap()))).collect();
+        if let Some(index) = frames.iter().position(|&(offset, len)| !in_file(offset, len as u64)) {
+            return Err(Error::InvalidCframe(format!(".cvid file is truncated inside frame {}", index + 1)));
+        }
+        let audio = (long(32) > 0).then_some((long(24), long(32)));
+        if audio.is_some_and(|(offset, len)| !in_file(offset, len)) {
+            return Err(Error::InvalidCframe(".cvid file is truncated inside its audio".into()));
+        }
+        Ok(Self {source: Mutex::new(Box::new(source)), fps: word(12), width: word(16), height: word(20), frames, audio})
+    }
+
+    /// Number of frames in the file
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether the file holds no frames
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The frame rate the clip was converted at, when it recorded one
+    pub fn fps(&self) -> Option<u32> {
+        (self.fps > 0).then_some(self.fps)
+    }
+
+    /// Width of the first frame in cells
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the first frame in cells
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The complete `.cframe` bytes of frame `index` (from 0)
+    pub fn frame_bytes(&self, index: usize) -> Result<Vec<u8>> {
+        let &(offset, len) = self.frames.get(index).ok_or_else(|| Error::InvalidOptions(format!("frame {} is past the last of {} frames", index, self.frames.len())))?;
+        self.read_span(offset, len as u64).with_context(|| format!("reading frame {}", index + 1))
+    }
+
+    /// Frame `index` (from 0)
+    pub fn frame(&self, index: usize) -> Result<AsciiFrame> {
+        cframe_bytes_to_frame_data(&self.frame_bytes(index)?, &format!("frame {} of the .cvid file", index + 1)).map(AsciiFrame::from)
+    }
+
+    /// The bytes of the clip's `audio.mp3`, if it had one
+    pub fn audio(&self) -> Result<Option<Vec<u8>>> {
+        self.audio.map(|(offset, len)| self.read_span(offset, len).context("reading the audio")).transpose()
+    }
+
+    fn read_span(&self, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut bytes = vec![0u8; len as usize];
+        let mut source = self.source.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        source.seek(SeekFrom::Start(offset))?;
+        source.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cvid_packs_frames_and_audio_for_random_access() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let clip = dir.path().join("clip");
+        std::fs::create_dir(&clip)?;
+        let first = AsciiFrame {text: "AB\n".into(), width: 2, height: 1, rgb: vec![10, 20, 30, 40, 50, 60], bg_rgb: vec![1, 2, 3, 4, 5, 6]}.cframe_bytes();
+        std::fs::write(clip.join("frame_0001.cframe"), &first)?;
+        std::fs::write(clip.join("frame_0002.txt"), "xy\n")?;
+        std::fs::write(clip.join("details.toml"), "fps = 12\n")?;
+        std::fs::write(clip.join("audio.mp3"), b"ID3 audio")?;
+
+        let path = dir.path().join("clip.cvid");
+        assert_eq!(write_cvid(&clip, &path)?, 2);
+        let reader = CvidReader::open(&path)?;
+        assert_eq!((reader.len(), reader.fps(), reader.width(), reader.height()), (2, Some(12), 2, 1));
+        assert_eq!(reader.frame_bytes(0)?, first);
+        assert_eq!((reader.frame(1)?.text, reader.frame(0)?.bg_rgb), ("xy\n".to_string(), vec![1, 2, 3, 4, 5, 6]));
+        assert_eq!(reader.audio()?, Some(b"ID3 audio".to_vec()));
+        assert!(reader.frame(2).is_err());
+        let sequence = FrameSequence::open(&path)?;
+        assert_eq!(sequence.reader().map(|frame| frame.map(|frame| frame.text)).collect::<Result<Vec<_>>>()?, ["AB\n", "xy\n"]);
+
+        std::fs::remove_file(clip.join("audio.mp3"))?;
+        write_cvid(&clip, &path)?;
+        assert_eq!(CvidReader::open(&path)?.audio()?, None);
+        let bytes = std::fs::read(&path)?;
+        std::fs::write(&path, &bytes[..bytes.len() - 1])?;
+        assert!(CvidReader::open(&path).is_err());
+        Ok(())
+    }
+}
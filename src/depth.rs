@@ -0,0 +1,150 @@
+//! Depth-map modulation of converted frames.
+//!
+//! A depth map is a grayscale image aligned with the source where white is near and black is far.
+//! Once a frame has been converted, each cell's nearness thins its character towards the sparse
+//! start of the ramp and/or darkens its colors, so distant areas fade out and the picture gains a
+//! pseudo-3D look. Videos take either a single depth image for every frame or a depth video that is
+//! extracted alongside the source with the same timing.
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::codepage::{cp437_byte, ramp_char};
+
+/// What a depth map changes about each cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DepthMode {
+    /// Far cells move towards the sparse start of the character ramp
+    #[default]
+    Density,
+    /// Far cells have their foreground and background colors darkened
+    Color,
+    /// Both thin the characters and darken the colors
+    Both,
+}
+
+impl DepthMode {
+    /// All modes, in the order they are listed by the CLI.
+    pub const ALL: [DepthMode; 3] = [DepthMode::Density, DepthMode::Color, DepthMode::Both];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Density => "density",
+            Self::Color => "color",
+            Self::Both => "both",
+        }
+    }
+
+    fn density(self) -> bool {
+        self != Self::Color
+    }
+
+    fn color(self) -> bool {
+        self != Self::Density
+    }
+}
+
+impl FromStr for DepthMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|mode| mode.as_str() == s).ok_or_else(|| format!("expected one of {}", Self::ALL.map(DepthMode::as_str).join(", ")))
+    }
+}
+
+/// How a depth map is read and applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepthOptions {
+    /// What the depth changes
+    pub mode: DepthMode,
+    /// Treat black as near and white as far, for maps that store distance rather than nearness
+    pub invert: bool,
+}
+
+impl DepthOptions {
+    /// Modulate a converted `width` × `height` grid with `depth`, which is resized to one sample per cell.
+    /// `ascii_chars` is the ramp the text was converted with; characters outside it (such as the
+    /// spaces below the luminance threshold) are left alone. Color buffers that don't hold 3 bytes
+    /// per cell (an absent background layer) are skipped.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn apply(&self, depth: &DynamicImage, text: &mut String, width: u32, height: u32, ascii_chars: &[u8], rgb: &mut [u8], bg_rgb: &mut [u8]) {
+        let mut nearness = depth.to_luma8();
+        if nearness.dimensions() != (width, height) {
+            nearness = image::imageops::resize(&nearness, width, height, FilterType::Triangle);
+        }
+        let nearness: Vec<u32> = nearness.into_raw().into_iter().map(|value| if self.invert {255 - value as u32} else {value as u32}).collect();
+
+        if self.mode.density() && !ascii_chars.is_empty() {
+            let mut rank = [None; 256];
+            for (idx, &ch) in ascii_chars.iter().enumerate().rev() {
+                rank[ch as usize] = Some(idx as u32);
+            }
+            let mut cells = nearness.iter();
+            *text = text.chars().map(|ch| {
+                if ch == '\n' {
+                    return ch;
+                }
+                let near = cells.next().copied().unwrap_or(255);
+                match cp437_byte(ch).and_then(|byte| rank[byte as usize]) {
+                    Some(idx) => ramp_char(ascii_chars[((idx * near + 127) / 255) as usize]),
+                    None => ch,
+                }
+            }).collect();
+        }
+
+        if self.mode.color() {
+            for colors in [rgb, bg_rgb] {
+                if colors.len() != nearness.len() * 3 {
+                    continue;
+                }
+                for (cell, &near) in colors.chunks_exact_mut(3).zip(&nearness) {
+                    for channel in cell {
+                        *channel = ((*channel as u32 * near + 127) / 255) as u8;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A depth map read from disk for a video conversion.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepthMap {
+    /// A grayscale image applied to every frame, or a depth video extracted with the source's fps, start and end
+    pub path: PathBuf,
+    /// How the map is applied
+    pub options: DepthOptions,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    #[test]
+    fn test_depth_thins_and_darkens_far_cells() {
+        // Left column near, right column far, bottom row half way
+        let depth = DynamicImage::ImageLuma8(GrayImage::from_fn(2, 2, |x, y| Luma([if y == 1 {128} else if x == 0 {255} else {0}])));
+        let ramp = b".:-=+*#%@";
+        let apply = |options: DepthOptions| {
+            let mut text = "@@\n@ \n".to_string();
+            let mut rgb = vec![200; 12];
+            options.apply(&depth, &mut text, 2, 2, ramp, &mut rgb, &mut []);
+            (text, rgb)
+        };
+
+        let (text, rgb) = apply(DepthOptions::default());
+        assert_eq!(text, "@.\n+ \n");
+        assert_eq!(rgb, vec![200; 12]);
+
+        let (text, rgb) = apply(DepthOptions {mode: DepthMode::Both, invert: true});
+        assert_eq!(text, ".@\n+ \n");
+        assert_eq!(&rgb[..6], &[0, 0, 0, 200, 200, 200]);
+        assert_eq!(rgb[6], 100);
+
+        assert_eq!("color".parse::<DepthMode>(), Ok(DepthMode::Color));
+        assert!("far".parse::<DepthMode>().unwrap_err().contains("density, color, both"));
+    }
+}
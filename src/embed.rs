@@ -0,0 +1,333 @@
+//! Frames as source code, to bake an animation into a binary.
+//!
+//! [`embed_frames`] turns the text of a converted clip into a Rust module (a `&[&str]` of frames)
+//! or a C header (an array of strings), one source line per row of art. With
+//! [`EmbedOptions::compress`] the frames are run-length encoded instead: Rust reads the encoded bytes
+//! with `include_bytes!` from the [`EmbeddedSource::data`] file written next to the module, C gets
+//! them as a byte array, and both get a small decoder, `<name>_decode(index, out)`, that expands a
+//! frame into a caller-provided buffer of `<NAME>_MAX_LEN` bytes without allocating, so it runs on
+//! `no_std` targets and microcontrollers too. The runs of spaces that fill much of a typical frame
+//! shrink to three bytes each.
+//!
+//! ```
+//! use cascii::embed::{embed_frames, EmbedLanguage, EmbedOptions};
+//!
+//! let frames = ["  ##  \n #### \n".to_string(), " #### \n  ##  \n".to_string()];
+//! let embedded = embed_frames(&frames, &EmbedOptions::default().with_fps(Some(12))).unwrap();
+//! assert!(embedded.source.contains("pub static FRAMES: &[&str] = &["));
+//! let header = embed_frames(&frames, &EmbedOptions::default().with_language(EmbedLanguage::C)).unwrap();
+//! assert!(header.source.contains("static const char *const frames[2] = {"));
+//! ```
+
+use crate::error::{Error, Result};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::FrameSequence;
+
+/// Byte starting a run in the encoded frames; it can't appear in frame text
+const RUN_MARKER: u8 = 0;
+
+/// Shortest run worth encoding: a run takes three bytes
+const MIN_RUN: usize = 4;
+
+/// Source language of [`embed_frames`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmbedLanguage {
+    /// A Rust module, `.rs`
+    #[default]
+    Rust,
+    /// A C header, `.h`
+    C,
+}
+
+impl EmbedLanguage {
+    /// Extension of the generated file
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Rust => "rs",
+            Self::C => "h",
+        }
+    }
+}
+
+/// How [`embed_frames`] writes the frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbedOptions {
+    pub language: EmbedLanguage,
+    /// Identifier the generated items are named after: `FRAMES`/`frames_decode` in Rust,
+    /// `frames`/`FRAMES_COUNT` in C
+    pub name: String,
+    /// Frame rate to record as `<NAME>_FPS`, or `None` to leave it out
+    pub fps: Option<u32>,
+    /// Run-length encode the frames and add a decoder
+    pub compress: bool,
+    /// File the Rust module `include_bytes!`s the encoded frames from, relative to the module
+    pub data_file: String,
+}
+
+impl Default for EmbedOptions {
+    fn default() -> Self {
+        Self {language: EmbedLanguage::Rust, name: "frames".to_string(), fps: None, compress: false, data_file: "frames.bin".to_string()}
+    }
+}
+
+impl EmbedOptions {
+    pub fn with_language(mut self, language: EmbedLanguage) -> Self {
+        self.language = language;
+        self
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_fps(mut self, fps: Option<u32>) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    pub fn with_compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    pub fn with_data_file(mut self, data_file: impl Into<String>) -> Self {
+        self.data_file = data_file.into();
+        self
+    }
+}
+
+/// Generated source, and the encoded frames a compressed Rust module includes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedSource {
+    pub source: String,
+    /// Contents of [`EmbedOptions::data_file`], to write next to the module; `None` unless it's needed
+    pub data: Option<Vec<u8>>,
+}
+
+/// Write `frames` (the text of each frame, rows ending in `\n`) as source code.
+pub fn embed_frames(frames: &[String], options: &EmbedOptions) -> Result<EmbeddedSource> {
+    let name = &options.name;
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(Error::InvalidOptions(format!("'{}' is not a valid identifier for the embedded frames", name)));
+    }
+    if frames.is_empty() {
+        return Err(Error::NotFound("no frames to embed".into()));
+    }
+    let (lower, upper) = (name.to_ascii_lowercase(), name.to_ascii_uppercase());
+    let rate = options.fps.map_or_else(String::new, |fps| format!(" at {} fps", fps));
+    let mut out = String::new();
+
+    if !options.compress {
+        match options.language {
+            EmbedLanguage::Rust => {
+                writeln!(out, "// Generated by cascii {}: {} frames{}", env!("CARGO_PKG_VERSION"), frames.len(), rate)?;
+                if let Some(fps) = options.fps {
+                    writeln!(out, "pub const {}_FPS: u32 = {};", upper, fps)?;
+                }
+                writeln!(out, "pub static {}: &[&str] = &[", upper)?;
+                for frame in frames {
+                    writeln!(out, "    concat!(")?;
+                    for row in frame.split_inclusive('\n') {
+                        writeln!(out, "        {:?},", row)?;
+                    }
+                    writeln!(out, "    ),")?;
+                }
+                writeln!(out, "];")?;
+            }
+            EmbedLanguage::C => {
+                writeln!(out, "/* Generated by cascii {}: {} frames{} */", env!("CARGO_PKG_VERSION"), frames.len(), rate)?;
+                writeln!(out, "#ifndef {}_H\n#define {0}_H\n", upper)?;
+                writeln!(out, "#define {}_COUNT {}", upper, frames.len())?;
+                if let Some(fps) = options.fps {
+                    writeln!(out, "#define {}_FPS {}", upper, fps)?;
+                }
+                writeln!(out, "\nstatic const char *const {}[{}] = {{", lower, frames.len())?;
+                for frame in frames {
+                    if frame.is_empty() {
+                        writeln!(out, "    \"\"")?;
+                    }
+                    for row in frame.split_inclusive('\n') {
+                        writeln!(out, "    {}", c_string(row))?;
+                    }
+                    writeln!(out, "    ,")?;
+                }
+                writeln!(out, "}};\n\n#endif")?;
+            }
+        }
+        return Ok(EmbeddedSource {source: out, data: None});
+    }
+
+    // Encoded frames back to back, with the offset of each one and of the end
+    let mut data = Vec::new();
+    let mut offsets = vec![0];
+    for (index, frame) in frames.iter().enumerate() {
+        if frame.as_bytes().contains(&RUN_MARKER) {
+            return Err(Error::InvalidOptions(format!("frame {} contains a NUL byte, which can't be run-length encoded", index + 1)));
+        }
+        encode_runs(frame.as_bytes(), &mut data);
+        offsets.push(data.len());
+    }
+    let max_len = frames.iter().map(String::len).max().unwrap_or(0);
+    let offsets = offsets.iter().map(usize::to_string).collect::<Vec<_>>().join(", ");
+    match options.language {
+        EmbedLanguage::Rust => {
+            writeln!(out, "// Generated by cascii {}: {} run-length encoded frames{}", env!("CARGO_PKG_VERSION"), frames.len(), rate)?;
+            writeln!(out, "pub const {}_COUNT: usize = {};", upper, frames.len())?;
+            if let Some(fps) = options.fps {
+                writeln!(out, "pub const {}_FPS: u32 = {};", upper, fps)?;
+            }
+            writeln!(out, "/// Bytes of the longest frame, the buffer size `{}_decode` needs", lower)?;
+            writeln!(out, "pub const {}_MAX_LEN: usize = {};", upper, max_len)?;
+            writeln!(out, "static {}_DATA: &[u8] = include_bytes!({:?});", upper, options.data_file)?;
+            writeln!(out, "static {}_OFFSETS: [usize; {}] = [{}];", upper, frames.len() + 1, offsets)?;
+            writeln!(out, r#"
+/// Expand frame `index` into `out`, which must hold `{upper}_MAX_LEN` bytes
+pub fn {lower}_decode(index: usize, out: &mut [u8]) -> &str {{
+    let data = &{upper}_DATA[{upper}_OFFSETS[index]..{upper}_OFFSETS[index + 1]];
+    let (mut read, mut len) = (0, 0);
+    while read < data.len() {{
+        if data[read] == 0 {{
+            let count = data[read + 1] as usize;
+            out[len..len + count].fill(data[read + 2]);
+            (read, len) = (read + 3, len + count);
+        }} else {{
+            out[len] = data[read];
+            (read, len) = (read + 1, len + 1);
+        }}
+    }}
+    core::str::from_utf8(&out[..len]).unwrap()
+}}"#)?;
+            Ok(EmbeddedSource {source: out, data: Some(data)})
+        }
+        EmbedLanguage::C => {
+            writeln!(out, "/* Generated by cascii {}: {} run-length encoded frames{} */", env!("CARGO_PKG_VERSION"), frames.len(), rate)?;
+            writeln!(out, "#ifndef {}_H\n#define {0}_H\n\n#include <stddef.h>\n", upper)?;
+            writeln!(out, "#define {}_COUNT {}", upper, frames.len())?;
+            if let Some(fps) = options.fps {
+                writeln!(out, "#define {}_FPS {}", upper, fps)?;
+            }
+            writeln!(out, "/* Bytes of the longest frame; {}_decode needs one more for the terminating NUL */", lower)?;
+            writeln!(out, "#define {}_MAX_LEN {}", upper, max_len)?;
+            writeln!(out, "\nstatic const unsigned char {}_data[{}] = {{", lower, data.len().max(1))?;
+            for chunk in data.chunks(16) {
+                writeln!(out, "    {},", chunk.iter().map(|byte| format!("0x{:02x}", byte)).collect::<Vec<_>>().join(", "))?;
+            }
+            writeln!(out, "}};\nstatic const size_t {}_offsets[{}] = {{{}}};", lower, frames.len() + 1, offsets)?;
+            writeln!(out, r#"
+/* Expand frame `index` into `out` as a NUL-terminated string, returning its length */
+static size_t {lower}_decode(size_t index, char *out) {{
+    const unsigned char *data = {lower}_data + {lower}_offsets[index];
+    const unsigned char *end = {lower}_data + {lower}_offsets[index + 1];
+    size_t len = 0;
+    while (data < end) {{
+        if (data[0] == 0) {{
+            for (unsigned char count = data[1]; count > 0; count--) {{
+                out[len++] = (char)data[2];
+            }}
+            data += 3;
+        }} else {{
+            out[len++] = (char)*data++;
+        }}
+    }}
+    out[len] = '\0';
+    return len;
+}}
+
+#endif"#)?;
+            Ok(EmbeddedSource {source: out, data: None})
+        }
+    }
+}
+
+/// [`embed_frames`] on the frames of a converted directory or `.casc` archive. Without a rate in
+/// `options`, the one recorded in the directory's `details.toml` is used.
+pub fn embed_frame_dir(dir: impl AsRef<Path>, options: &EmbedOptions) -> Result<EmbeddedSource> {
+    let dir = dir.as_ref();
+    let sequence = FrameSequence::open(dir)?;
+    let frames = sequence.reader().map(|frame| frame.map(|frame| frame.text)).collect::<Result<Vec<_>>>()?;
+    let options = EmbedOptions {fps: options.fps.or_else(|| crate::loop_detect::recorded_fps(dir)), ..options.clone()};
+    embed_frames(&frames, &options)
+}
+
+/// Append `bytes` to `out`, with runs of [`MIN_RUN`] or more of one byte as `0, count, byte`
+fn encode_runs(bytes: &[u8], out: &mut Vec<u8>) {
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        let run = bytes[index..].iter().take(u8::MAX as usize).take_while(|&&next| next == byte).count();
+        if run >= MIN_RUN {
+            out.extend([RUN_MARKER, run as u8, byte]);
+        } else {
+            out.extend(std::iter::repeat_n(byte, run));
+        }
+        index += run;
+    }
+}
+
+/// `row` as a C string literal: printable ASCII as is, everything else as octal escapes, which
+/// unlike hex escapes can't swallow the characters after them
+fn c_string(row: &str) -> String {
+    let mut literal = String::from("\"");
+    for &byte in row.as_bytes() {
+        match byte {
+            b'\n' => literal.push_str("\\n"),
+            b'"' | b'\\' | b'?' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            0x20..=0x7e => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Expand frames encoded by [`encode_runs`], as the generated decoders do
+    fn decode_runs(data: &[u8]) -> Vec<u8> {
+        let (mut out, mut read) = (Vec::new(), 0);
+        while read < data.len() {
+            if data[read] == RUN_MARKER {
+                out.extend(std::iter::repeat_n(data[read + 2], data[read + 1] as usize));
+                read += 3;
+            } else {
+                out.push(data[read]);
+                read += 1;
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn embed_frames_writes_rust_and_c_sources() {
+        let frames = vec![format!("{}##\n", " ".repeat(300)), "a\"b\\?░\n".to_string()];
+        let rust = embed_frames(&frames, &EmbedOptions::default().with_name("intro").with_fps(Some(24))).unwrap();
+        assert!(rust.source.contains("pub const INTRO_FPS: u32 = 24;\npub static INTRO: &[&str] = &[\n    concat!(\n"));
+        assert!(rust.source.contains("        \"a\\\"b\\\\?░\\n\",\n    ),\n];"), "{}", rust.source);
+        assert_eq!(rust.data, None);
+
+        let c = embed_frames(&frames, &EmbedOptions::default().with_language(EmbedLanguage::C)).unwrap();
+        assert!(c.source.contains("#define FRAMES_COUNT 2\n") && !c.source.contains("FRAMES_FPS"));
+        assert!(c.source.contains("    \"a\\\"b\\\\\\?\\342\\226\\221\\n\"\n    ,\n};"), "{}", c.source);
+
+        // Encoded frames are shorter and decode back to the text
+        let compressed = embed_frames(&frames, &EmbedOptions::default().with_compress(true).with_data_file("intro.bin")).unwrap();
+        let data = compressed.data.unwrap();
+        assert!(data.len() < frames.concat().len() / 10);
+        assert_eq!(decode_runs(&data), frames.concat().into_bytes());
+        assert!(compressed.source.contains("include_bytes!(\"intro.bin\")") && compressed.source.contains("pub const FRAMES_MAX_LEN: usize = 303;"));
+        let header = embed_frames(&frames, &EmbedOptions::default().with_language(EmbedLanguage::C).with_compress(true)).unwrap();
+        assert!(header.data.is_none() && header.source.contains("static size_t frames_decode(size_t index, char *out)"));
+
+        assert!(embed_frames(&frames, &EmbedOptions::default().with_name("2d")).is_err());
+        assert!(embed_frames(&[], &EmbedOptions::default()).is_err());
+        assert!(embed_frames(&["a\0b".to_string()], &EmbedOptions::default().with_compress(true)).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! The error type of the library.
+//!
+//! Every fallible function of the crate returns [`Result`], whose [`Error`] tells the kinds of failure
+//! apart, so an application can say "install ffmpeg" rather than print whatever went wrong:
+//!
+//! ```
+//! use cascii::{image_bytes_to_frame, ConversionOptions, Error};
+//!
+//! match image_bytes_to_frame(b"GIF89a, or so it says", &ConversionOptions::default()) {
+//!     Ok(frame) => print!("{}", frame.text),
+//!     Err(Error::ImageDecode {..}) => eprintln!("that file is not an image cascii can read"),
+//!     Err(Error::Io {source, ..}) if source.kind() == std::io::ErrorKind::PermissionDenied => eprintln!("no permission to read it"),
+//!     Err(err) => eprintln!("conversion failed: {}", err),
+//! }
+//! ```
+//!
+//! It implements [`std::error::Error`] and is `Send + Sync`, so `?` turns it into an
+//! `anyhow::Error` or a `Box<dyn Error>` where a program doesn't need to branch on it.
+
+use std::fmt;
+use std::io;
+
+/// `Result` with the crate's [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// What went wrong in a library call.
+///
+/// The message variants carry a sentence meant for people; match on the variant, not on the text.
+/// Context added on the way up (the file being read, the step that ran) is part of that sentence,
+/// or the `context` field of the variants wrapping another error.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// ffmpeg or ffprobe could not be started: it is not installed, not in `PATH`, or not at the
+    /// path set with [`FfmpegConfig`](crate::FfmpegConfig)
+    FfmpegNotFound {
+        program: String,
+    },
+    /// ffmpeg or ffprobe ran but failed, with what it wrote to stderr when that was captured
+    FfmpegFailed {
+        context: String,
+        stderr: String,
+    },
+    /// ffmpeg runs but was built without an encoder the output needs, such as `libx264`
+    MissingEncoder {
+        encoder: String,
+        ffmpeg_version: String,
+    },
+    /// An image could not be decoded or encoded
+    ImageDecode {
+        context: String,
+        source: image::ImageError,
+    },
+    /// A `.cframe`, `.casc` archive or other frame file is damaged or not a frame at all
+    InvalidCframe(String),
+    /// Options, presets or arguments that are out of range or can't be used together
+    InvalidOptions(String),
+    /// Reading or writing a file, or talking to a child process, failed
+    Io {
+        context: String,
+        source: io::Error,
+    },
+    /// An input the call needs isn't there: a missing path, a directory without frames, a video
+    /// without the requested frames or audio
+    NotFound(String),
+    /// A config file, caption track or report template didn't parse
+    Parse(String),
+    /// A [`ConverterLimits`](crate::ConverterLimits) limit was exceeded
+    LimitExceeded(String),
+    /// A `.casc` archive could not be encrypted or decrypted, e.g. with the wrong key
+    Encryption(String),
+    /// A [`CancelToken`](crate::CancelToken) stopped the operation; not a real failure
+    Cancelled,
+    /// Any other failure, such as a worker thread panicking
+    Other(String),
+}
+
+impl Error {
+    /// The error starting `program` failed with; a missing binary is [`Error::FfmpegNotFound`]
+    #[cfg(feature = "cli")]
+    pub(crate) fn spawn(program: &std::ffi::OsStr, source: io::Error) -> Self {
+        let program = program.to_string_lossy().into_owned();
+        if source.kind() == io::ErrorKind::NotFound {
+            Self::FfmpegNotFound {program}
+        } else {
+            Self::Io {context: format!("running {}", program), source}
+        }
+    }
+
+    /// An ffmpeg failure described by `context`, with the `stderr` it printed
+    #[cfg(feature = "cli")]
+    pub(crate) fn ffmpeg(context: impl Into<String>, stderr: &[u8]) -> Self {
+        Self::FfmpegFailed {context: context.into(), stderr: String::from_utf8_lossy(stderr).trim().to_string()}
+    }
+
+    /// This error with `context` in front of its message
+    pub(crate) fn context(self, context: impl fmt::Display) -> Self {
+        let prefix = |message: String| if message.is_empty() {context.to_string()} else {format!("{}: {}", context, message)};
+        match self {
+            Self::FfmpegFailed {context: message, stderr} => Self::FfmpegFailed {context: prefix(message), stderr},
+            Self::ImageDecode {context: message, source} => Self::ImageDecode {context: prefix(message), source},
+            Self::Io {context: message, source} => Self::Io {context: prefix(message), source},
+            Self::InvalidCframe(message) => Self::InvalidCframe(prefix(message)),
+            Self::InvalidOptions(message) => Self::InvalidOptions(prefix(message)),
+            Self::NotFound(message) => Self::NotFound(prefix(message)),
+            Self::Parse(message) => Self::Parse(prefix(message)),
+            Self::LimitExceeded(message) => Self::LimitExceeded(prefix(message)),
+            Self::Encryption(message) => Self::Encryption(prefix(message)),
+            Self::Other(message) => Self::Other(prefix(message)),
+            // The program name and the cancellation say it all
+            Self::FfmpegNotFound {..} | Self::MissingEncoder {..} | Self::Cancelled => self,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FfmpegNotFound {program} => write!(f, "{} is not installed or not in PATH", program),
+            Self::MissingEncoder {encoder, ffmpeg_version} => write!(f, "ffmpeg {} was built without the {} encoder", ffmpeg_version, encoder),
+            Self::FfmpegFailed {context, stderr} if stderr.is_empty() => write!(f, "{}", context),
+            Self::FfmpegFailed {context, stderr} => write!(f, "{}: {}", context, stderr),
+            Self::ImageDecode {context, source} if context.is_empty() => write!(f, "{}", source),
+            Self::Io {context, source} if context.is_empty() => write!(f, "{}", source),
+            Self::ImageDecode {context, source} => write!(f, "{}: {}", context, source),
+            Self::Io {context, source} => write!(f, "{}: {}", context, source),
+            Self::InvalidCframe(message) | Self::InvalidOptions(message) | Self::NotFound(message) | Self::Parse(message) | Self::LimitExceeded(message) | Self::Encryption(message) | Self::Other(message) => write!(f, "{}", message),
+            Self::Cancelled => write!(f, "operation cancelled"),
+        }
+    }
+}
+
+/// The message already includes the wrapped error, so [`source`](std::error::Error::source) stays
+/// `None` and reporters that walk the chain (`anyhow`'s `{:#}`, say) print it once; match the
+/// `source` fields to inspect it
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Self::Io {context: String::new(), source}
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(source: image::ImageError) -> Self {
+        match source {
+            image::ImageError::IoError(source) => Self::from(source),
+            source => Self::ImageDecode {context: String::new(), source},
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Parse(err.to_string())
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Parse(err.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::Other(err.to_string())
+    }
+}
+
+/// Prompts fail only when the terminal can't be read or written
+#[cfg(feature = "cli")]
+impl From<dialoguer::Error> for Error {
+    fn from(err: dialoguer::Error) -> Self {
+        match err {
+            dialoguer::Error::IO(source) => Self::from(source),
+        }
+    }
+}
+
+/// From `write!` into a `String`, which doesn't fail in practice
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Other(err.to_string())
+    }
+}
+
+impl From<crate::Cancelled> for Error {
+    fn from(_: crate::Cancelled) -> Self {
+        Self::Cancelled
+    }
+}
+
+/// `.context(..)` and `.with_context(|| ..)` for results and options inside the crate
+pub(crate) trait Context<T> {
+    fn context(self, context: impl fmt::Display) -> Result<T>;
+    fn with_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl fmt::Display) -> Result<T> {
+        self.map_err(|err| err.into().context(context))
+    }
+
+    fn with_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|err| err.into().context(context()))
+    }
+}
+
+/// A missing value is something [`Error::NotFound`]
+impl<T> Context<T> for Option<T> {
+    fn context(self, context: impl fmt::Display) -> Result<T> {
+        self.ok_or_else(|| Error::NotFound(context.to_string()))
+    }
+
+    fn with_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.ok_or_else(|| Error::NotFound(context().to_string()))
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_keep_their_kind_through_context() {
+        let missing = Error::spawn(std::ffi::OsStr::new("/opt/ffmpeg"), io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(&missing, Error::FfmpegNotFound {program} if program == "/opt/ffmpeg"));
+        assert_eq!(missing.to_string(), "/opt/ffmpeg is not installed or not in PATH");
+
+        let read: Result<()> = Err(io::Error::from(io::ErrorKind::PermissionDenied)).context("reading frame_0001.cframe");
+        let err = read.context("loading clip").unwrap_err();
+        assert!(matches!(&err, Error::Io {source, ..} if source.kind() == io::ErrorKind::PermissionDenied));
+        assert!(err.to_string().starts_with("loading clip: reading frame_0001.cframe: "));
+
+        assert_eq!(Error::ffmpeg("ffmpeg failed", b" Invalid data\n").to_string(), "ffmpeg failed: Invalid data");
+        assert!(matches!(image::load_from_memory(b"not an image").map_err(Error::from), Err(Error::ImageDecode {..})));
+
+        // The chain repeats no message: the wrapped error's text appears once, however it's reported
+        let inner = io::Error::from(io::ErrorKind::PermissionDenied).to_string();
+        let chain: Vec<String> = std::iter::successors(Some(&err as &dyn std::error::Error), |err| err.source()).map(|err| err.to_string()).collect();
+        assert_eq!(chain.iter().map(|message| message.matches(&inner).count()).sum::<usize>(), 1);
+        assert_eq!(format!("{:#}", anyhow::Error::from(err)).matches(&inner).count(), 1);
+        assert!(matches!(None::<u8>.context("no frames"), Err(Error::NotFound(message)) if message == "no frames"));
+        assert_eq!(Error::from(crate::Cancelled).context("converting").to_string(), "operation cancelled");
+    }
+}
@@ -0,0 +1,197 @@
+//! Dry-run estimates of a conversion's output size and time, measured on sample frames.
+//!
+//! How many bytes a frame takes depends on the options (`.cframe` color layers, ANSI escapes) and
+//! on the picture itself, so [`AsciiConverter::estimate_conversion`](crate::AsciiConverter::estimate_conversion)
+//! converts up to [`SAMPLE_FRAMES`] frames spread evenly across the source with the selected options,
+//! measures the files they produce and how long they took, and extrapolates to every frame.
+//!
+//! [`AsciiConverter::convert_with_time_budget`](crate::AsciiConverter::convert_with_time_budget)
+//! measures a video the same way and lowers the columns and the frame rate, down to the floors of a
+//! [`TimeBudget`], until the conversion is predicted to finish in time.
+
+use crate::error::{Context, Error, Result};
+use crate::ConversionResult;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Most frames converted to measure an estimate
+pub const SAMPLE_FRAMES: usize = 5;
+
+/// Share of the time left after measuring that a budgeted conversion is planned to take; the rest
+/// absorbs ffmpeg's decoding and the writing of frames, which the samples don't measure
+pub(crate) const BUDGET_SHARE: f64 = 0.75;
+
+/// Output size and conversion time extrapolated from converted sample frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConversionEstimate {
+    /// Frames the conversion would write
+    pub frames: usize,
+    /// Frames converted to measure this estimate
+    pub sampled_frames: usize,
+    /// Mean size of the files written per frame
+    pub bytes_per_frame: u64,
+    /// Mean time one thread took to convert a sample frame
+    pub seconds_per_frame: f64,
+    /// Threads the conversion spreads frames across
+    pub threads: usize,
+}
+
+impl ConversionEstimate {
+    /// Size of every file the conversion would write
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes_per_frame * self.frames as u64
+    }
+
+    /// Time to convert every frame with one frame per thread at a time, excluding frame extraction by ffmpeg
+    pub fn total_seconds(&self) -> f64 {
+        self.seconds_per_frame * self.frames as f64 / self.threads.min(self.frames).max(1) as f64
+    }
+}
+
+/// A time limit for a conversion, with the fewest columns and the lowest frame rate it may fall back
+/// to to stay within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeBudget {
+    /// Wall-clock time the conversion should finish in, measuring included
+    pub duration: Duration,
+    /// Fewest columns to convert at (default 40)
+    pub min_columns: u32,
+    /// Lowest frame rate to convert at (default 5)
+    pub min_fps: u32,
+}
+
+impl TimeBudget {
+    pub fn new(duration: Duration) -> Self {
+        Self {duration, min_columns: 40, min_fps: 5}
+    }
+
+    pub fn with_min_columns(mut self, min_columns: u32) -> Self {
+        self.min_columns = min_columns;
+        self
+    }
+
+    pub fn with_min_fps(mut self, min_fps: u32) -> Self {
+        self.min_fps = min_fps;
+        self
+    }
+}
+
+/// The settings a budgeted conversion chose, and the conversion it ran with them.
+#[derive(Debug, Clone)]
+pub struct BudgetedConversion {
+    pub result: ConversionResult,
+    /// Columns converted at
+    pub columns: u32,
+    /// Frame rate converted at
+    pub fps: u32,
+    /// Conversion time the samples predicted for `columns` and `fps`, without ffmpeg's decoding
+    pub predicted_seconds: f64,
+    /// Whether the prediction fit the budget; `false` when even the floors were predicted to run over
+    pub within_budget: bool,
+    /// Time the whole conversion took, measuring included
+    pub elapsed: Duration,
+}
+
+/// Columns and fps, no lower than `budget`'s floors, that `estimate` (measured at `columns` and
+/// `fps`) predicts convert within `seconds`, with the predicted time. A frame takes time in
+/// proportion to its cells, the square of the columns, and the frame count follows the fps; both
+/// are lowered by the same factor until one reaches its floor, and the other takes the rest.
+pub(crate) fn plan_for_budget(estimate: &ConversionEstimate, columns: u32, fps: u32, seconds: f64, budget: &TimeBudget) -> (u32, u32, f64) {
+    let (columns, fps) = (columns.max(1), fps.max(1));
+    let predict = |c: u32, f: u32| estimate.seconds_per_frame * (c as f64 / columns as f64).powi(2) * estimate.frames as f64 * (f as f64 / fps as f64) / estimate.threads.max(1) as f64;
+    let full = predict(columns, fps);
+    if full <= seconds {
+        return (columns, fps, full);
+    }
+    let ratio = (seconds / full).max(0.0);
+    let (min_columns, min_fps) = (budget.min_columns.clamp(1, columns), budget.min_fps.clamp(1, fps));
+    let even = ((columns as f64 * ratio.cbrt()) as u32).max(min_columns);
+    let fps = ((fps as f64 * ratio / (even as f64 / columns as f64).powi(2)) as u32).clamp(min_fps, fps);
+    let columns = ((columns as f64 * (ratio * full / predict(columns, fps)).sqrt()) as u32).clamp(min_columns, columns);
+    (columns, fps, predict(columns, fps))
+}
+
+/// Up to `count` indices into `0..len`, each in the middle of one of `count` equal slices
+pub(crate) fn sample_indices(len: usize, count: usize) -> Vec<usize> {
+    let count = count.min(len);
+    (0..count).map(|slice| (2 * slice + 1) * len / (2 * count)).collect()
+}
+
+/// The PNG frames of `dir` in conversion order
+pub(crate) fn frame_images(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut pngs: Vec<PathBuf> = fs::read_dir(dir).with_context(|| format!("reading directory {}", dir.display()))?.flatten().map(|entry| entry.path()).filter(|path| path.extension().is_some_and(|e| e == "png")).collect();
+    pngs.sort();
+    Ok(pngs)
+}
+
+/// Convert every sample with `convert(sample, out_txt)` into its own directory under `work_dir`,
+/// extrapolating the bytes written and the time taken to `frames` frames converted on `threads` threads.
+pub(crate) fn measure<F: Fn(&Path, &Path) -> Result<()>>(samples: &[PathBuf], frames: usize, threads: usize, work_dir: &Path, convert: F) -> Result<ConversionEstimate> {
+    if samples.is_empty() {
+        return Err(Error::NotFound("no frames to sample".into()));
+    }
+    let (mut bytes, mut seconds) = (0u64, 0f64);
+    for (index, sample) in samples.iter().enumerate() {
+        let out_dir = work_dir.join(format!("sample_{}", index));
+        fs::create_dir_all(&out_dir).with_context(|| format!("creating {}", out_dir.display()))?;
+        let started = Instant::now();
+        convert(sample, &out_dir.join("frame.txt")).with_context(|| format!("converting sample frame {}", sample.display()))?;
+        seconds += started.elapsed().as_secs_f64();
+        for entry in fs::read_dir(&out_dir)?.flatten() {
+            bytes += entry.metadata()?.len();
+        }
+    }
+    let sampled = samples.len();
+    Ok(ConversionEstimate {frames, sampled_frames: sampled, bytes_per_frame: bytes / sampled as u64, seconds_per_frame: seconds / sampled as f64, threads})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AsciiConverter, ConversionOptions, OutputMode, VideoOptions};
+
+    #[test]
+    fn test_estimate_measures_sampled_frames_per_output_mode() {
+        assert_eq!(sample_indices(10, SAMPLE_FRAMES), vec![1, 3, 5, 7, 9]);
+        assert_eq!(sample_indices(2, SAMPLE_FRAMES), vec![0, 1]);
+
+        let dir = tempfile::tempdir().unwrap();
+        for index in 1..=7u8 {
+            let image = image::RgbImage::from_fn(40, 20, |x, y| image::Rgb([x as u8 * 6, y as u8 * 12, index * 30]));
+            image.save(dir.path().join(format!("frame_{:04}.png", index))).unwrap();
+        }
+        let converter = AsciiConverter::new();
+        let text_options = ConversionOptions::default().with_font_ratio(0.5);
+        let text = converter.estimate_conversion(dir.path(), &VideoOptions::default(), &text_options).unwrap();
+        assert_eq!((text.frames, text.sampled_frames), (7, SAMPLE_FRAMES));
+        // Directory frames convert one column per pixel: 10 rows of 40 characters and a newline
+        assert_eq!(text.bytes_per_frame, 10 * 41);
+        assert_eq!(text.total_bytes(), 7 * 10 * 41);
+
+        let color = converter.estimate_conversion(dir.path(), &VideoOptions::default(), &ConversionOptions {output_mode: OutputMode::TextAndColor, ..text_options}).unwrap();
+        assert!(color.bytes_per_frame > text.bytes_per_frame + 40 * 10 * 3, "{:?}", color);
+        assert!(converter.estimate_conversion(&dir.path().join("missing"), &VideoOptions::default(), &ConversionOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_time_budget_lowers_columns_and_fps_down_to_their_floors() {
+        // 300 frames at 0.08s each on 4 threads: 6s at 200 columns and 30 fps
+        let estimate = ConversionEstimate {frames: 300, sampled_frames: SAMPLE_FRAMES, bytes_per_frame: 0, seconds_per_frame: 0.08, threads: 4};
+        let budget = TimeBudget::new(Duration::from_secs(10));
+        assert_eq!(plan_for_budget(&estimate, 200, 30, 8.0, &budget), (200, 30, 6.0));
+
+        // An eighth of the time halves both
+        let (columns, fps, seconds) = plan_for_budget(&estimate, 200, 30, 0.75, &budget);
+        assert_eq!((columns, fps), (100, 15));
+        assert!(seconds <= 0.75);
+        // The fps floor leaves the rest to the columns
+        let (columns, fps, seconds) = plan_for_budget(&estimate, 200, 30, 0.75, &budget.with_min_fps(24));
+        assert_eq!(fps, 24);
+        assert!(columns < 100 && seconds <= 0.75);
+        // Both at their floors may still run over
+        let (columns, fps, seconds) = plan_for_budget(&estimate, 200, 30, 0.01, &budget.with_min_columns(80));
+        assert_eq!((columns, fps), (80, 5));
+        assert!(seconds > 0.01);
+    }
+}
@@ -0,0 +1,233 @@
+//! Append-only conversion event log (`events.jsonl`).
+//!
+//! Every event is one JSON object per line, written straight to the file (no buffering) so the
+//! log survives a crash or kill up to the last completed write. It records phase transitions,
+//! periodic frame checkpoints, warnings and the exact ffmpeg command lines, which is enough to
+//! tell after the fact where a run stopped.
+//!
+//! ```no_run
+//! use cascii::events::EventLog;
+//! use cascii::AsciiConverter;
+//! use std::path::Path;
+//!
+//! let log = EventLog::open(Path::new("output/events.jsonl"))?;
+//! let converter = AsciiConverter::new().with_event_log(log);
+//! # Ok::<(), cascii::Error>(())
+//! ```
+
+use crate::error::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command as ProcCommand;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{Progress, ProgressPhase};
+
+/// Default number of frames between two [`Event::Checkpoint`] entries.
+pub const DEFAULT_CHECKPOINT_EVERY: usize = 100;
+
+/// A single entry of the event log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A conversion started
+    Started {operation: String, input: PathBuf, output: PathBuf},
+    /// The conversion entered a new phase
+    Phase {phase: ProgressPhase},
+    /// Frames completed so far in the current phase
+    Checkpoint {phase: ProgressPhase, completed: usize, total: usize},
+    /// An ffmpeg/ffprobe process is about to be spawned
+    Ffmpeg {args: Vec<String>},
+    /// Something worth knowing that did not stop the run
+    Warning {message: String},
+    /// The conversion finished
+    Completed {frames: Option<usize>},
+    /// The conversion was cancelled through its [`CancelToken`](crate::CancelToken)
+    Cancelled,
+    /// The conversion failed
+    Failed {error: String},
+}
+
+/// An [`Event`] with its timestamp, as stored on each line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventRecord {
+    /// Milliseconds since the Unix epoch
+    pub ts_ms: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Debug, Default)]
+struct ProgressState {
+    phase: Option<ProgressPhase>,
+    last_checkpoint: usize,
+}
+
+#[derive(Debug)]
+struct Inner {
+    path: PathBuf,
+    file: Mutex<File>,
+    progress: Mutex<ProgressState>,
+    checkpoint_every: usize,
+}
+
+/// Handle to an open `events.jsonl`. Clones share the same file.
+///
+/// Writing is best-effort: an I/O error while logging never fails the conversion itself.
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    inner: Arc<Inner>,
+}
+
+impl EventLog {
+    /// Open (or create) `path` for appending, creating parent directories as needed.
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with_checkpoint_every(path, DEFAULT_CHECKPOINT_EVERY)
+    }
+
+    /// Like [`open`](Self::open), writing a checkpoint every `frames` frames (at least 1).
+    pub fn open_with_checkpoint_every(path: &Path, frames: usize) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).with_context(|| format!("creating event log directory {}", parent.display()))?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).read(true).open(path).with_context(|| format!("opening event log {}", path.display()))?;
+        // A crash can leave a torn last line; terminate it so the next record starts on a fresh line.
+        if ends_mid_line(&mut file)? {
+            file.write_all(b"\n").with_context(|| format!("writing event log {}", path.display()))?;
+        }
+        Ok(Self {inner: Arc::new(Inner {path: path.to_path_buf(), file: Mutex::new(file), progress: Mutex::new(ProgressState::default()), checkpoint_every: frames.max(1)})})
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// Append `event` to the log.
+    pub fn record(&self, event: Event) {
+        let ts_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        let Ok(mut line) = serde_json::to_string(&EventRecord {ts_ms, event}) else {return};
+        line.push('\n');
+        if let Ok(mut file) = self.inner.file.lock() {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+
+    /// Record a warning.
+    pub fn warn(&self, message: impl Into<String>) {
+        self.record(Event::Warning {message: message.into()});
+    }
+
+    /// Record the command line of an ffmpeg/ffprobe invocation.
+    pub fn record_command(&self, command: &ProcCommand) {
+        let args = std::iter::once(command.get_program()).chain(command.get_args()).map(|arg| arg.to_string_lossy().into_owned()).collect();
+        self.record(Event::Ffmpeg {args});
+    }
+
+    /// Derive phase transitions and frame checkpoints from a progress update.
+    ///
+    /// Checkpoints are only written for frame-counting phases (conversion and rendering).
+    pub fn record_progress(&self, progress: &Progress) {
+        let Ok(mut state) = self.inner.progress.lock() else {return};
+        if state.phase.as_ref() != Some(&progress.phase) {
+            state.phase = Some(progress.phase.clone());
+            state.last_checkpoint = 0;
+            if progress.phase != ProgressPhase::Complete {
+                self.record(Event::Phase {phase: progress.phase.clone()});
+            }
+        }
+        let counts_frames = matches!(progress.phase, ProgressPhase::ConvertingFrames | ProgressPhase::RenderingVideo);
+        if counts_frames && progress.completed > 0 && (progress.completed >= state.last_checkpoint + self.inner.checkpoint_every || progress.completed == progress.total) {
+            state.last_checkpoint = progress.completed;
+            self.record(Event::Checkpoint {phase: progress.phase.clone(), completed: progress.completed, total: progress.total});
+        }
+    }
+}
+
+fn ends_mid_line(file: &mut File) -> Result<bool> {
+    let len = file.metadata().context("reading event log metadata")?.len();
+    if len == 0 {
+        return Ok(false);
+    }
+    let mut last = [0u8; 1];
+    file.seek(SeekFrom::Start(len - 1)).context("seeking event log")?;
+    file.read_exact(&mut last).context("reading event log")?;
+    Ok(last[0] != b'\n')
+}
+
+/// Read all records of an event log, skipping a torn final line left by a crash.
+pub fn read_events(path: &Path) -> Result<Vec<EventRecord>> {
+    let text = fs::read_to_string(path).with_context(|| format!("reading event log {}", path.display()))?;
+    Ok(text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+/// Wrap `progress_callback` so every update is also recorded in `events`.
+pub(crate) fn observe<F: Fn(Progress) + Send + Sync>(events: Option<&EventLog>, progress_callback: F) -> impl Fn(Progress) + Send + Sync {
+    let events = events.cloned();
+    move |progress: Progress| {
+        if let Some(events) = events.as_ref() {
+            events.record_progress(&progress);
+        }
+        progress_callback(progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_survive_reopen_and_torn_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("events.jsonl");
+        let log = EventLog::open(&path).unwrap();
+        log.record(Event::Started {operation: "convert_video".to_string(), input: PathBuf::from("in.mp4"), output: PathBuf::from("out")});
+        log.warn("careful");
+        drop(log);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"ts_ms\":1,\"event\":\"comp").unwrap();
+        drop(file);
+
+        let log = EventLog::open(&path).unwrap();
+        log.record(Event::Completed {frames: Some(3)});
+        let events: Vec<Event> = read_events(&path).unwrap().into_iter().map(|record| record.event).collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1], Event::Warning {message: "careful".to_string()});
+        assert_eq!(events[2], Event::Completed {frames: Some(3)});
+    }
+
+    #[test]
+    fn progress_becomes_phases_and_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let log = EventLog::open_with_checkpoint_every(&path, 10).unwrap();
+        log.record_progress(&Progress::extracting_frames());
+        log.record_progress(&Progress::extracting_frames_progress(5_000_000, 10_000_000));
+        for frame in 1..=25 {
+            log.record_progress(&Progress::converting_frames(frame, 25));
+        }
+        log.record_progress(&Progress::complete(25));
+
+        let events: Vec<Event> = read_events(&path).unwrap().into_iter().map(|record| record.event).collect();
+        let checkpoints: Vec<usize> = events.iter().filter_map(|event| if let Event::Checkpoint {completed, ..} = event {Some(*completed)} else {None}).collect();
+        assert_eq!(events[0], Event::Phase {phase: ProgressPhase::ExtractingFrames});
+        assert_eq!(events[1], Event::Phase {phase: ProgressPhase::ConvertingFrames});
+        assert_eq!(checkpoints, vec![10, 20, 25]);
+        assert_eq!(events.len(), 5);
+    }
+
+    #[test]
+    fn commands_are_logged_verbatim() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = EventLog::open(&dir.path().join("events.jsonl")).unwrap();
+        let mut command = ProcCommand::new("ffmpeg");
+        command.args(["-i", "in file.mp4", "-y"]);
+        log.record_command(&command);
+        let line = fs::read_to_string(log.path()).unwrap();
+        assert!(line.contains(r#""event":"ffmpeg","args":["ffmpeg","-i","in file.mp4","-y"]"#), "{}", line);
+    }
+}
@@ -0,0 +1,192 @@
+//! FlatBuffers frames for game engines and real-time players (the `flatbuffers` feature).
+//!
+//! A `.cfb` file is one [`AsciiFrame`] in the FlatBuffers format described by `schema/frame.fbs`:
+//! the cells of its `.cframe`, with every character stored as a whole `u32` and the foreground and
+//! background colors as plain byte vectors. [`FrameView`] reads one in place, without copying or
+//! parsing the cells, after a single bounds check of the buffer; `flatc` generates the same reader
+//! for C++, C# or TypeScript from the schema.
+//!
+//! ```
+//! use cascii::fbs::FrameView;
+//! use cascii::AsciiFrame;
+//!
+//! let frame = AsciiFrame {text: "#.\n".into(), width: 2, height: 1, rgb: vec![255, 0, 0, 0, 0, 255], bg_rgb: Vec::new()};
+//! let bytes = frame.to_flatbuffer();
+//! let view = FrameView::from_bytes(&bytes).unwrap();
+//! assert_eq!((view.width(), view.char_at(1, 0), view.fg()), (2, Some('.'), &[255, 0, 0, 0, 0, 255][..]));
+//! assert_eq!(view.to_frame(), frame);
+//! ```
+
+use crate::error::{Error, Result};
+use flatbuffers::{FlatBufferBuilder, Follow, ForwardsUOffset, InvalidFlatbuffer, Table, VOffsetT, Vector, Verifiable, Verifier};
+
+use crate::AsciiFrame;
+
+/// The `file_identifier` of the schema, bytes 4..8 of every frame
+pub const FILE_IDENTIFIER: &str = "CASF";
+/// The `file_extension` of the schema
+pub const FILE_EXTENSION: &str = "cfb";
+
+// Field offsets in the vtable of `table Frame`, in schema order
+const VT_WIDTH: VOffsetT = 4;
+const VT_HEIGHT: VOffsetT = 6;
+const VT_CHARS: VOffsetT = 8;
+const VT_FG: VOffsetT = 10;
+const VT_BG: VOffsetT = 12;
+
+/// A verified `.cfb` frame, read in place from its bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameView<'a> {
+    table: Table<'a>,
+}
+
+impl<'a> FrameView<'a> {
+    /// Check that `bytes` hold a whole frame with as many cells and colors as its size says, and read it
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        if !flatbuffers::buffer_has_identifier(bytes, FILE_IDENTIFIER, false) {
+            return Err(Error::InvalidCframe(format!("not a FlatBuffers frame: missing the {} identifier", FILE_IDENTIFIER)));
+        }
+        let view = flatbuffers::root::<FrameView>(bytes).map_err(|err| Error::InvalidCframe(format!("invalid FlatBuffers frame: {}", err)))?;
+        let cells = view.width() as usize * view.height() as usize;
+        if view.chars().len() != cells {
+            return Err(Error::InvalidCframe(format!("FlatBuffers frame of {}x{} cells holds {} characters", view.width(), view.height(), view.chars().len())));
+        }
+        if ![0, cells * 3].contains(&view.fg().len()) || view.bg().is_some_and(|bg| bg.len() != cells * 3) {
+            return Err(Error::InvalidCframe(format!("FlatBuffers frame colors don't match its {}x{} cells", view.width(), view.height())));
+        }
+        Ok(view)
+    }
+
+    /// Width in characters
+    pub fn width(&self) -> u32 {
+        // Safety: the buffer was verified against the schema, where `width` is a uint
+        unsafe { self.table.get::<u32>(VT_WIDTH, Some(0)) }.unwrap_or_default()
+    }
+
+    /// Height in characters (rows)
+    pub fn height(&self) -> u32 {
+        // Safety: as for `width`
+        unsafe { self.table.get::<u32>(VT_HEIGHT, Some(0)) }.unwrap_or_default()
+    }
+
+    /// The Unicode scalar value of every cell, row-major
+    pub fn chars(&self) -> Vector<'a, u32> {
+        // Safety: `chars` is a [uint] in the verified buffer
+        unsafe { self.table.get::<ForwardsUOffset<Vector<'a, u32>>>(VT_CHARS, None) }.unwrap_or_default()
+    }
+
+    /// Foreground colors, 3 bytes per cell, row-major; empty for frames without colors
+    pub fn fg(&self) -> &'a [u8] {
+        // Safety: `fg` is a [ubyte] in the verified buffer
+        unsafe { self.table.get::<ForwardsUOffset<Vector<'a, u8>>>(VT_FG, None) }.map_or(&[], |fg| fg.bytes())
+    }
+
+    /// Background colors, 3 bytes per cell, row-major, when the frame has a background layer
+    pub fn bg(&self) -> Option<&'a [u8]> {
+        // Safety: `bg` is a [ubyte] in the verified buffer
+        unsafe { self.table.get::<ForwardsUOffset<Vector<'a, u8>>>(VT_BG, None) }.map(|bg| bg.bytes())
+    }
+
+    /// The character at `column`, `row`, or `None` outside the frame
+    pub fn char_at(&self, column: u32, row: u32) -> Option<char> {
+        if column >= self.width() || row >= self.height() {
+            return None;
+        }
+        char::from_u32(self.chars().get((row * self.width() + column) as usize))
+    }
+
+    /// Copy the frame out into an [`AsciiFrame`]
+    pub fn to_frame(&self) -> AsciiFrame {
+        let width = self.width().max(1) as usize;
+        let mut text = String::with_capacity(self.chars().len() + self.height() as usize);
+        for (index, code) in self.chars().iter().enumerate() {
+            text.push(char::from_u32(code).unwrap_or('?'));
+            if (index + 1) % width == 0 {
+                text.push('\n');
+            }
+        }
+        AsciiFrame {text, width: self.width(), height: self.height(), rgb: self.fg().to_vec(), bg_rgb: self.bg().unwrap_or_default().to_vec()}
+    }
+}
+
+impl<'a> Follow<'a> for FrameView<'a> {
+    type Inner = Self;
+
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self {
+        Self {table: unsafe { Table::new(buf, loc) }}
+    }
+}
+
+impl Verifiable for FrameView<'_> {
+    fn run_verifier(verifier: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        verifier.visit_table(pos)?
+            .visit_field::<u32>("width", VT_WIDTH, false)?
+            .visit_field::<u32>("height", VT_HEIGHT, false)?
+            .visit_field::<ForwardsUOffset<Vector<u32>>>("chars", VT_CHARS, false)?
+            .visit_field::<ForwardsUOffset<Vector<u8>>>("fg", VT_FG, false)?
+            .visit_field::<ForwardsUOffset<Vector<u8>>>("bg", VT_BG, false)?
+            .finish();
+        Ok(())
+    }
+}
+
+/// Encode a frame as `.cfb` bytes (see [`AsciiFrame::to_flatbuffer`])
+pub(crate) fn encode_frame(frame: &AsciiFrame) -> Vec<u8> {
+    let chars: Vec<u32> = frame.cells().map(|cell| cell.ch as u32).collect();
+    let mut builder = FlatBufferBuilder::with_capacity(chars.len() * 10 + 64);
+    let chars = builder.create_vector(&chars);
+    let fg = builder.create_vector(&frame.rgb);
+    let bg = frame.bg_rgb().map(|bg| builder.create_vector(bg));
+    let table = builder.start_table();
+    builder.push_slot(VT_WIDTH, frame.width, 0);
+    builder.push_slot(VT_HEIGHT, frame.height, 0);
+    builder.push_slot_always(VT_CHARS, chars);
+    builder.push_slot_always(VT_FG, fg);
+    if let Some(bg) = bg {
+        builder.push_slot_always(VT_BG, bg);
+    }
+    let root = builder.end_table(table);
+    builder.finish(root, Some(FILE_IDENTIFIER));
+    builder.finished_data().to_vec()
+}
+
+/// Write every frame of a converted directory or `.casc` archive to `output_dir` as
+/// `frame_0001.cfb` and on, in playback order. Returns the number of frames written.
+#[cfg(feature = "cli")]
+pub fn export_frame_dir(input: impl AsRef<std::path::Path>, output_dir: impl AsRef<std::path::Path>) -> Result<usize> {
+    let output_dir = output_dir.as_ref();
+    std::fs::create_dir_all(output_dir)?;
+    let mut count = 0;
+    for frame in crate::reader::FrameSequence::open(input)?.reader() {
+        count += 1;
+        std::fs::write(output_dir.join(format!("frame_{:04}.{}", count, FILE_EXTENSION)), frame?.to_flatbuffer())?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flatbuffer_frames_round_trip_and_reject_damaged_buffers() {
+        let frame = AsciiFrame {text: "⠿▀\nab\n".into(), width: 2, height: 2, rgb: (0..12).collect(), bg_rgb: vec![9; 12]};
+        let bytes = frame.to_flatbuffer();
+        assert_eq!(&bytes[4..8], FILE_IDENTIFIER.as_bytes());
+        let view = FrameView::from_bytes(&bytes).unwrap();
+        assert_eq!((view.width(), view.height(), view.char_at(0, 0), view.char_at(1, 1), view.char_at(2, 0)), (2, 2, Some('⠿'), Some('b'), None));
+        assert_eq!((view.fg().len(), view.bg()), (12, Some(&[9u8; 12][..])));
+        assert_eq!(view.to_frame(), frame);
+
+        let plain = AsciiFrame {text: "xy\n".into(), width: 2, height: 1, ..AsciiFrame::default()};
+        let plain_bytes = plain.to_flatbuffer();
+        let view = FrameView::from_bytes(&plain_bytes).unwrap();
+        assert_eq!((view.fg(), view.bg()), (&[][..], None));
+        assert_eq!(view.to_frame(), plain);
+
+        assert!(FrameView::from_bytes(&bytes[..bytes.len() - 8]).is_err());
+        assert!(FrameView::from_bytes(&frame.cframe_bytes()).is_err());
+        let short = AsciiFrame {rgb: vec![0; 3], ..plain};
+        assert!(FrameView::from_bytes(&short.to_flatbuffer()).is_err());
+    }
+}
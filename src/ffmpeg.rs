@@ -0,0 +1,141 @@
+//! Where the ffmpeg and ffprobe binaries are, and which versions and encoders they have.
+
+#[cfg(feature = "cli")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "cli")]
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+#[cfg(feature = "cli")]
+use crate::error::{Error, Result};
+#[cfg(feature = "cli")]
+use crate::video;
+
+/// Configuration for ffmpeg/ffprobe binary paths
+///
+/// Use this to specify custom paths for ffmpeg and ffprobe binaries,
+/// for example when bundling them with your application.
+#[derive(Debug, Clone, Default)]
+pub struct FfmpegConfig {
+    /// Custom path to ffmpeg binary. If None, uses system PATH.
+    pub ffmpeg_path: Option<PathBuf>,
+    /// Custom path to ffprobe binary. If None, uses system PATH.
+    pub ffprobe_path: Option<PathBuf>,
+}
+
+impl FfmpegConfig {
+    /// Create a new FfmpegConfig with default settings (use system PATH)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a config with custom ffmpeg path
+    pub fn with_ffmpeg<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.ffmpeg_path = Some(path.into());
+        self
+    }
+
+    /// Create a config with custom ffprobe path
+    pub fn with_ffprobe<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.ffprobe_path = Some(path.into());
+        self
+    }
+
+    /// Get the ffmpeg command name or path
+    #[cfg(feature = "cli")]
+    pub(crate) fn ffmpeg_cmd(&self) -> &OsStr {
+        self.ffmpeg_path.as_ref().map(|p| p.as_os_str()).unwrap_or(OsStr::new("ffmpeg"))
+    }
+
+    /// Get the ffprobe command name or path
+    #[cfg(feature = "cli")]
+    pub(crate) fn ffprobe_cmd(&self) -> &OsStr {
+        self.ffprobe_path.as_ref().map(|p| p.as_os_str()).unwrap_or(OsStr::new("ffprobe"))
+    }
+
+    /// Check which of ffmpeg and ffprobe can be run, their versions and the encoders ffmpeg has.
+    ///
+    /// A binary that isn't installed is reported as such rather than failing, so this can be called
+    /// before a conversion to fail early with a clear message instead of halfway through:
+    ///
+    /// ```no_run
+    /// use cascii::FfmpegConfig;
+    ///
+    /// # fn main() -> cascii::Result<()> {
+    /// let capabilities = FfmpegConfig::new().probe()?;
+    /// println!("ffmpeg {}", capabilities.ffmpeg_version.as_deref().unwrap_or("not found"));
+    /// capabilities.require_encoders(&["libx264", "aac"])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "cli")]
+    pub fn probe(&self) -> Result<FfmpegCapabilities> {
+        let ffmpeg_version = video::probe_version(self.ffmpeg_cmd())?;
+        let ffprobe_version = video::probe_version(self.ffprobe_cmd())?;
+        let (video_encoders, audio_encoders) = if ffmpeg_version.is_some() {video::probe_encoders(self.ffmpeg_cmd())?} else {Default::default()};
+        Ok(FfmpegCapabilities {ffmpeg_version, ffprobe_version, video_encoders, audio_encoders})
+    }
+}
+
+/// What the ffmpeg and ffprobe of an [`FfmpegConfig`] can do, from [`FfmpegConfig::probe`]
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FfmpegCapabilities {
+    /// ffmpeg's version as it reports it (e.g. `6.1.1` or `N-113406-g5be3e8b`), `None` when it isn't installed
+    pub ffmpeg_version: Option<String>,
+    /// ffprobe's version, `None` when it isn't installed
+    pub ffprobe_version: Option<String>,
+    /// Video encoders ffmpeg was built with, such as `libx264` or `libvpx-vp9`, sorted
+    pub video_encoders: Vec<String>,
+    /// Audio encoders ffmpeg was built with, such as `aac` or `libopus`, sorted
+    pub audio_encoders: Vec<String>,
+}
+
+#[cfg(feature = "cli")]
+impl FfmpegCapabilities {
+    /// Whether ffmpeg and ffprobe both run; video input needs both
+    pub fn is_complete(&self) -> bool {
+        self.ffmpeg_version.is_some() && self.ffprobe_version.is_some()
+    }
+
+    /// Whether ffmpeg has the video or audio encoder `name`
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.video_encoders.iter().chain(&self.audio_encoders).any(|encoder| encoder == name)
+    }
+
+    /// Fail with [`Error::FfmpegNotFound`] without ffmpeg, or [`Error::MissingEncoder`] for the first
+    /// of `encoders` it lacks
+    pub fn require_encoders(&self, encoders: &[&str]) -> Result<()> {
+        let Some(version) = &self.ffmpeg_version else {
+            return Err(Error::FfmpegNotFound {program: "ffmpeg".into()});
+        };
+        match encoders.iter().find(|encoder| !self.has_encoder(encoder)) {
+            Some(encoder) => Err(Error::MissingEncoder {encoder: encoder.to_string(), ffmpeg_version: version.clone()}),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn ffmpeg_probe_reports_versions_encoders_and_missing_binaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let ffmpeg = dir.path().join("ffmpeg");
+        crate::converter::write_fake_program(&ffmpeg, "#!/bin/sh\ncase \"$*\" in *-encoders*) printf 'Encoders:\\n V..... = Video\\n A..... = Audio\\n ------\\n V....D libx264              libx264 H.264\\n A....D aac                  AAC\\n S..... srt                  SubRip\\n' ;; *) echo 'ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023' ;; esac\n");
+
+        let capabilities = FfmpegConfig::new().with_ffmpeg(&ffmpeg).with_ffprobe(dir.path().join("no-ffprobe")).probe().unwrap();
+        assert_eq!(capabilities, FfmpegCapabilities {ffmpeg_version: Some("6.1.1-3ubuntu5".into()), ffprobe_version: None, video_encoders: vec!["libx264".into()], audio_encoders: vec!["aac".into()]});
+        assert!(!capabilities.is_complete() && capabilities.has_encoder("aac") && !capabilities.has_encoder("srt"));
+        assert!(capabilities.require_encoders(&["libx264", "aac"]).is_ok());
+        let missing = capabilities.require_encoders(&["libx264", "libvpx-vp9"]).unwrap_err();
+        assert_eq!(missing.to_string(), "ffmpeg 6.1.1-3ubuntu5 was built without the libvpx-vp9 encoder");
+
+        let nothing = FfmpegConfig::new().with_ffmpeg(dir.path().join("no-ffmpeg")).with_ffprobe(dir.path().join("no-ffprobe")).probe().unwrap();
+        assert_eq!(nothing, FfmpegCapabilities::default());
+        assert!(matches!(nothing.require_encoders(&[]), Err(Error::FfmpegNotFound {..})));
+    }
+}
@@ -0,0 +1,142 @@
+//! Playing a clip's `audio.mp3` through `ffplay`, for builds without the `audio` feature.
+//!
+//! An [`FfplayAudio`] follows the [`AudioCue`]s of a [`PlaybackClock`](crate::playback::PlaybackClock)
+//! by starting `ffplay` at the cue's position whenever the playhead jumps or the speed changes
+//! (resampled with `atempo`), and stopping it while playback is paused or muted. ffplay reports
+//! nothing back, so its position is the clip time it started at plus the time it has played since;
+//! [`PlaybackClock::follow_audio`](crate::playback::PlaybackClock::follow_audio) locks the frames to that.
+
+use crate::error::{Error, Result};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::playback::{AudioCue, AudioOutput};
+
+/// An audio track playing in an `ffplay` process.
+#[derive(Debug)]
+pub struct FfplayAudio {
+    program: OsString,
+    path: PathBuf,
+    child: Option<Child>,
+    /// [`AudioCue::jumps`] of the last cue followed
+    jumps: Option<u64>,
+    rate: Option<f64>,
+    /// Clip time ffplay was started at, and when
+    anchor: (Duration, Instant),
+}
+
+impl FfplayAudio {
+    /// Play `path` with the `ffplay` on the PATH, stopped until the first [`follow`](Self::follow).
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_program(path, "ffplay")
+    }
+
+    /// Play `path` with the ffplay binary `program`.
+    pub fn with_program(path: impl AsRef<Path>, program: impl AsRef<OsStr>) -> Result<Self> {
+        let (path, program) = (path.as_ref(), program.as_ref());
+        if !path.is_file() {
+            return Err(Error::NotFound(format!("{} not found", path.display())));
+        }
+        let available = Command::new(program).arg("-version").stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).status().is_ok_and(|status| status.success());
+        if !available {
+            return Err(Error::FfmpegNotFound {program: program.to_string_lossy().into_owned()});
+        }
+        Ok(Self {program: program.to_os_string(), path: path.to_path_buf(), child: None, jumps: None, rate: None, anchor: (Duration::ZERO, Instant::now())})
+    }
+
+    /// Restart at the cue's position when the playhead jumped or the rate changed, or stop without a rate.
+    pub fn follow(&mut self, cue: AudioCue) {
+        let jumped = self.jumps.replace(cue.jumps) != Some(cue.jumps);
+        match cue.rate {
+            Some(rate) if jumped || self.rate != cue.rate => {
+                self.stop();
+                // Without ffplay the clip plays silently, as the track ending would
+                self.child = Command::new(&self.program).args(ffplay_args(&self.path, cue.position, rate)).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn().ok();
+                self.anchor = (cue.position, Instant::now());
+            }
+            Some(_) => {}
+            None => self.stop(),
+        }
+        self.rate = cue.rate;
+    }
+
+    /// Clip time the audio has reached, or `None` while it is stopped or after the track ended.
+    pub fn position(&mut self) -> Option<Duration> {
+        let running = matches!(self.child.as_mut()?.try_wait(), Ok(None));
+        let (clip, started) = self.anchor;
+        running.then(|| clip + started.elapsed().mul_f64(self.rate.unwrap_or(0.0)))
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl AudioOutput for FfplayAudio {
+    fn follow(&mut self, cue: AudioCue) {
+        FfplayAudio::follow(self, cue);
+    }
+
+    fn position(&mut self) -> Option<Duration> {
+        FfplayAudio::position(self)
+    }
+}
+
+impl Drop for FfplayAudio {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// [`FfplayAudio::open`] on the `audio.mp3` of a converted frame directory, or `None` when the
+/// conversion extracted no audio.
+pub fn open_clip_audio(dir: impl AsRef<Path>) -> Result<Option<FfplayAudio>> {
+    let path = dir.as_ref().join("audio.mp3");
+    if path.is_file() {FfplayAudio::open(path).map(Some)} else {Ok(None)}
+}
+
+/// Arguments playing `path` from `position` at `rate` without a window, exiting at the end
+fn ffplay_args(path: &Path, position: Duration, rate: f64) -> Vec<OsString> {
+    let mut args: Vec<OsString> = ["-nodisp", "-autoexit", "-loglevel", "quiet", "-ss"].map(OsString::from).to_vec();
+    args.push(format!("{:.3}", position.as_secs_f64()).into());
+    args.push("-af".into());
+    args.push(format!("atempo={}", rate).into());
+    args.push(path.into());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffplay_restarts_on_jumps_and_stops_when_paused() -> Result<()> {
+        let args = ffplay_args(Path::new("a.mp3"), Duration::from_millis(2500), 1.5);
+        assert_eq!(args.iter().map(|arg| arg.to_string_lossy()).collect::<Vec<_>>().join(" "), "-nodisp -autoexit -loglevel quiet -ss 2.500 -af atempo=1.5 a.mp3");
+
+        let dir = tempfile::tempdir()?;
+        assert!(FfplayAudio::open(dir.path().join("audio.mp3")).is_err());
+        assert!(open_clip_audio(dir.path())?.is_none());
+        std::fs::write(dir.path().join("audio.mp3"), b"")?;
+        assert!(FfplayAudio::with_program(dir.path().join("audio.mp3"), dir.path().join("no-ffplay")).is_err());
+
+        #[cfg(unix)]
+        {
+            // Stands in for ffplay: answers -version and otherwise plays for a while
+            let program = dir.path().join("ffplay");
+            crate::converter::write_fake_program(&program, "#!/bin/sh\n[ \"$1\" = -version ] || sleep 5\n");
+            let mut audio = FfplayAudio::with_program(dir.path().join("audio.mp3"), &program)?;
+            assert_eq!(audio.position(), None);
+            audio.follow(AudioCue {position: Duration::from_secs(3), rate: Some(1.0), jumps: 0});
+            assert!(audio.position().is_some_and(|position| position >= Duration::from_secs(3)));
+            audio.follow(AudioCue {position: Duration::from_secs(3), rate: None, jumps: 0});
+            assert_eq!((audio.position(), audio.child.is_none()), (None, true));
+        }
+        Ok(())
+    }
+}
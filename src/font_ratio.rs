@@ -0,0 +1,100 @@
+//! Font aspect ratios for the places converted output is viewed.
+//!
+//! The font ratio (character cell width / height) decides how many rows a frame gets, so it must
+//! match the font the output is finally shown in or the picture comes out squashed or stretched.
+//! [`FontRatio::Auto`] picks the ratio for a [`FontTarget`] instead of a hand-tuned number.
+
+use std::str::FromStr;
+
+/// Where the converted output will be viewed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FontTarget {
+    /// A terminal emulator; typical monospace cells are about twice as tall as wide
+    Terminal,
+    /// A video rendered by cascii with its bundled DejaVu Sans Mono glyphs
+    Video,
+    /// A `<pre>` block in a web page, whose default line height leaves taller cells than a terminal
+    Html,
+}
+
+impl FontTarget {
+    /// All targets, in the order they are listed by the CLI.
+    pub const ALL: [FontTarget; 3] = [FontTarget::Terminal, FontTarget::Video, FontTarget::Html];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Terminal => "terminal",
+            Self::Video => "video",
+            Self::Html => "html",
+        }
+    }
+
+    /// Cell width / height of a typical font on this target.
+    pub fn font_ratio(self) -> f32 {
+        match self {
+            Self::Terminal => 0.5,
+            Self::Video => 0.55,
+            Self::Html => 0.6,
+        }
+    }
+}
+
+/// Font aspect ratio used to size the character grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontRatio {
+    /// An explicit cell width / height
+    Fixed(f32),
+    /// The ratio that suits the given target
+    Auto(FontTarget),
+}
+
+impl FontRatio {
+    /// The cell width / height to convert with.
+    pub fn resolve(self) -> f32 {
+        match self {
+            Self::Fixed(ratio) => ratio,
+            Self::Auto(target) => target.font_ratio(),
+        }
+    }
+}
+
+impl From<f32> for FontRatio {
+    fn from(ratio: f32) -> Self {
+        Self::Fixed(ratio)
+    }
+}
+
+impl From<FontTarget> for FontRatio {
+    fn from(target: FontTarget) -> Self {
+        Self::Auto(target)
+    }
+}
+
+/// Parses either a positive number (`0.55`) or a target name (`terminal`, `video`, `html`).
+impl FromStr for FontRatio {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(target) = FontTarget::ALL.into_iter().find(|target| target.as_str() == s) {
+            return Ok(Self::Auto(target));
+        }
+        match s.parse::<f32>() {
+            Ok(ratio) if ratio > 0.0 && ratio.is_finite() => Ok(Self::Fixed(ratio)),
+            _ => Err(format!("expected a positive number or one of {}", FontTarget::ALL.map(FontTarget::as_str).join(", "))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ratios_and_targets() {
+        assert_eq!("0.45".parse::<FontRatio>().unwrap().resolve(), 0.45);
+        assert_eq!("video".parse::<FontRatio>(), Ok(FontRatio::Auto(FontTarget::Video)));
+        assert_eq!(FontRatio::from(FontTarget::Terminal).resolve(), 0.5);
+        assert!("0".parse::<FontRatio>().unwrap_err().contains("terminal, video, html"));
+        assert!("wide".parse::<FontRatio>().is_err());
+    }
+}
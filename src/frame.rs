@@ -4,20 +4,86 @@
 //! subprocesses, no threads — so it compiles and runs on any target, including
 //! `wasm32-unknown-unknown` (build the crate with `default-features = false`).
 
-use anyhow::{bail, Context, Result};
-use image::{DynamicImage, RgbImage};
+use crate::error::{Context, Error, Result};
+use serde::Serialize;
+use std::borrow::Cow;
+use image::{DynamicImage, GrayImage, ImageBuffer, Rgb, RgbImage};
 
-use crate::cell_filter::luminance_rgb;
-use crate::{CellColorMode, ConversionOptions};
+use crate::alpha::{self, AlphaMode};
+use crate::captions::CaptionTrack;
+use crate::cframe::CframeFormat;
+use crate::codepage::{ramp_char, Codepage};
+use crate::cell_filter::Luminance;
+use crate::depth::DepthOptions;
+use crate::markers;
+use crate::reflow::LineLimits;
+use crate::rng::SeededRng;
+use crate::stereo::Anaglyph;
+use crate::yuv::YuvFrame;
+use crate::{CellColorMode, ConversionOptions, GlyphMatch};
 
 /// Trailing payload flag bits.
 ///
 /// Stored as the first byte of the optional extension area that follows the legacy `8 + w*h*4` block. Each bit announces an optional payload that
 /// follows in a fixed order (lowest bit = earliest payload). Adding a new payload is a forward-compatible change as long as the new bit is appended.
 pub(crate) const CFRAME_EXT_FLAG_HAS_BG: u8 = 0b0000_0001;
+/// Every cell byte is a braille dot pattern, the offset of its character from U+2800 (see [`CharMode::Braille`]).
+/// Announces no payload of its own.
+pub(crate) const CFRAME_EXT_FLAG_BRAILLE: u8 = 0b0000_0010;
+/// Cell bytes below 0x20 are block elements, the offset of their character from U+2580 (see
+/// [`CharMode::HalfBlock`]); the other bytes are characters as usual. Announces no payload of its own.
+pub(crate) const CFRAME_EXT_FLAG_BLOCKS: u8 = 0b0000_0100;
+/// Every cell byte is a code page 437 character (see [`Codepage::Cp437`]), for frames with box
+/// drawing or other characters neither Latin-1 nor block elements. Announces no payload of its own.
+pub(crate) const CFRAME_EXT_FLAG_CP437: u8 = 0b0000_1000;
+/// Every cell byte is a sextant mask (see [`CharMode::Sextant`]). Announces no payload of its own.
+pub(crate) const CFRAME_EXT_FLAG_SEXTANTS: u8 = 0b0001_0000;
 
-/// A single converted ASCII frame held in memory.
-pub struct ImageFrame {
+/// The blank braille pattern; every braille character is this plus its dot bits
+const BRAILLE_BASE: u32 = 0x2800;
+/// The first block element, '▀'
+const BLOCKS_BASE: u32 = 0x2580;
+/// Block characters indexed by the quadrants they fill: bit 0 top left, 1 top right, 2 bottom left, 3 bottom right
+pub(crate) const QUADRANT_BLOCKS: [char; 16] = [' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█'];
+/// BLOCK SEXTANT-1, the first of the sextant characters of Symbols for Legacy Computing
+const SEXTANTS_BASE: u32 = 0x1FB00;
+/// The sextant masks drawn by the older block elements '▌' and '▐' instead of a sextant character
+const SEXTANT_LEFT: u8 = 0b01_0101;
+const SEXTANT_RIGHT: u8 = 0b10_1010;
+
+/// The character filling the sextants of `mask`: bit 0 top left, 1 top right, 2 middle left, 3
+/// middle right, 4 bottom left, 5 bottom right
+pub(crate) fn sextant_char(mask: u8) -> char {
+    match mask & 0x3F {
+        0 => ' ',
+        SEXTANT_LEFT => '▌',
+        SEXTANT_RIGHT => '▐',
+        0x3F => '█',
+        mask => char::from_u32(SEXTANTS_BASE + mask as u32 - 1 - (mask > SEXTANT_LEFT) as u32 - (mask > SEXTANT_RIGHT) as u32).expect("sextants are valid characters"),
+    }
+}
+
+/// The mask of a character [`sextant_char`] returns
+pub(crate) fn sextant_mask(ch: char) -> Option<u8> {
+    match ch {
+        ' ' => Some(0),
+        '▌' => Some(SEXTANT_LEFT),
+        '▐' => Some(SEXTANT_RIGHT),
+        '█' => Some(0x3F),
+        _ => {
+            // Step over the two masks without a sextant character of their own
+            let mask = (ch as u32).checked_sub(SEXTANTS_BASE).filter(|&index| index < 60)? as u8 + 1;
+            let mask = mask + (mask >= SEXTANT_LEFT) as u8;
+            Some(mask + (mask >= SEXTANT_RIGHT) as u8)
+        }
+    }
+}
+
+/// A single converted ASCII frame held in memory: the characters with their per-cell colors.
+///
+/// The fields are public for building and editing frames; the accessors read them cell by cell.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AsciiFrame {
     /// The ASCII text, rows separated by `\n`
     pub text: String,
     /// Width in characters
@@ -26,17 +92,218 @@ pub struct ImageFrame {
     pub height: u32,
     /// Flat RGB color data, 3 bytes per character, row-major
     pub rgb: Vec<u8>,
+    /// Flat RGB background colors, 3 bytes per character, row-major; empty without a background layer
+    pub bg_rgb: Vec<u8>,
 }
 
-impl ImageFrame {
-    /// Encode this frame as `.cframe` bytes (foreground colors only).
+impl AsciiFrame {
+    /// The ASCII text, rows separated by `\n`
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Width in characters
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height in characters (rows)
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Foreground colors, 3 bytes per cell, row-major; empty for frames read from plain text
+    pub fn rgb(&self) -> &[u8] {
+        &self.rgb
+    }
+
+    /// Background colors, 3 bytes per cell, row-major, when the frame has a background layer
+    pub fn bg_rgb(&self) -> Option<&[u8]> {
+        (!self.bg_rgb.is_empty()).then_some(self.bg_rgb.as_slice())
+    }
+
+    /// The rows of the text, without their `\n`.
+    pub fn rows(&self) -> impl Iterator<Item = &str> {
+        self.text.lines()
+    }
+
+    /// The cell at `column`, `row`, or `None` outside the frame.
+    pub fn cell(&self, column: u32, row: u32) -> Option<AsciiCell> {
+        if column >= self.width || row >= self.height {
+            return None;
+        }
+        let index = (row * self.width + column) as usize;
+        // One byte per cell unless the text holds multi-byte characters, such as braille
+        let ch = if self.text.len() == ((self.width + 1) * self.height) as usize && self.text.ends_with('\n') {self.text.as_bytes().get(index + row as usize).map(|&byte| byte as char)} else {self.rows().nth(row as usize).and_then(|line| line.chars().nth(column as usize))}?;
+        Some(self.cell_at(index, ch))
+    }
+
+    /// Every cell, row-major.
+    pub fn cells(&self) -> impl Iterator<Item = AsciiCell> + '_ {
+        let width = self.width as usize;
+        self.rows().take(self.height as usize).enumerate().flat_map(move |(row, line)| line.chars().take(width).enumerate().map(move |(column, ch)| self.cell_at(row * width + column, ch)))
+    }
+
+    fn cell_at(&self, index: usize, ch: char) -> AsciiCell {
+        let color = |colors: &[u8]| colors.get(index * 3..index * 3 + 3).map(|rgb| [rgb[0], rgb[1], rgb[2]]);
+        AsciiCell {ch, fg: color(&self.rgb), bg: color(&self.bg_rgb)}
+    }
+
+    /// This frame as JSON: `{"width": 2, "height": 1, "cells": [{"char": "#", "color": [255, 128, 0]}, ...]}`,
+    /// the cells row-major, each with its `color` when the frame has colors and its `background`
+    /// when it has a background layer. What [`OutputMode::Json`](crate::OutputMode::Json) writes.
+    pub fn to_json(&self) -> String {
+        encode_json(self.width, self.height, &self.text, &self.rgb, &self.bg_rgb)
+    }
+
+    /// Encode this frame as a `.cfb` FlatBuffer, read back in place with [`FrameView`](crate::fbs::FrameView).
+    #[cfg(feature = "flatbuffers")]
+    pub fn to_flatbuffer(&self) -> Vec<u8> {
+        crate::fbs::encode_frame(self)
+    }
+
+    /// Encode this frame as `.cframe` bytes, with the background layer when it has one.
     pub fn cframe_bytes(&self) -> Vec<u8> {
-        encode_cframe(self.width, self.height, &self.text, &self.rgb, None)
+        let background = (self.bg_rgb.len() == self.rgb.len() && !self.bg_rgb.is_empty()).then_some(self.bg_rgb.as_slice());
+        encode_cframe(self.width, self.height, &self.text, &self.rgb, background)
+    }
+
+    /// This frame resampled to `columns` × `rows` cells in ASCII space: every cell takes the
+    /// character and colors of the source cell under its center, so no source image is needed.
+    pub fn resized(&self, columns: u32, rows: u32) -> AsciiFrame {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        let source_rows: Vec<Vec<char>> = self.rows().map(|line| line.chars().collect()).collect();
+        let cells = (self.width * self.height) as usize;
+        let nearest = |index: u32, target: u32, source: u32| (((2 * index + 1) as u64 * source as u64) / (2 * target as u64)) as u32;
+        let mut frame = AsciiFrame {text: String::with_capacity(((columns + 1) * rows) as usize), width: columns, height: rows, rgb: Vec::new(), bg_rgb: Vec::new()};
+        for row in 0..rows {
+            let source_row = nearest(row, rows, self.height.max(1));
+            for column in 0..columns {
+                let source_column = nearest(column, columns, self.width.max(1));
+                let line = source_rows.get(source_row as usize).map_or(&[][..], Vec::as_slice);
+                frame.text.push(line.get(source_column as usize).copied().unwrap_or(' '));
+                let index = (source_row * self.width + source_column) as usize * 3;
+                for (colors, resized) in [(&self.rgb, &mut frame.rgb), (&self.bg_rgb, &mut frame.bg_rgb)] {
+                    if colors.len() == cells * 3 {
+                        resized.extend_from_slice(&colors[index..index + 3]);
+                    }
+                }
+            }
+            frame.text.push('\n');
+        }
+        frame
+    }
+
+    /// This frame scaled down, keeping its aspect ratio, to fit within `columns` × `rows` cells;
+    /// borrowed when it already fits.
+    pub fn fit_within(&self, columns: u32, rows: u32) -> Cow<'_, AsciiFrame> {
+        if self.width <= columns && self.height <= rows {
+            return Cow::Borrowed(self);
+        }
+        let scale = (columns as f64 / self.width as f64).min(rows as f64 / self.height as f64);
+        Cow::Owned(self.resized((self.width as f64 * scale) as u32, (self.height as f64 * scale) as u32))
+    }
+
+    /// Thin and/or darken this frame's cells with an aligned depth map (see [`DepthMap`](crate::DepthMap)).
+    /// `ascii_chars` must be the ramp the frame was converted with.
+    pub fn apply_depth(&mut self, depth: &DynamicImage, ascii_chars: &str, options: DepthOptions) {
+        options.apply(depth, &mut self.text, self.width, self.height, &crate::codepage::ramp_bytes(ascii_chars), &mut self.rgb, &mut self.bg_rgb);
+    }
+
+    /// Draw the captions of `captions` visible `seconds` into the clip over this frame's cells.
+    pub fn apply_captions(&mut self, captions: &CaptionTrack, seconds: f64) {
+        captions.apply(seconds, &mut self.text, self.width, self.height, &mut self.rgb, &mut self.bg_rgb);
     }
 }
 
+/// One cell of an [`AsciiFrame`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AsciiCell {
+    /// The character
+    pub ch: char,
+    /// Foreground color, when the frame has colors
+    pub fg: Option<[u8; 3]>,
+    /// Background color, when the frame has a background layer
+    pub bg: Option<[u8; 3]>,
+}
+
+/// Color escapes used for ANSI terminal output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AnsiColor {
+    /// 24-bit `38;2;r;g;b` escapes, exact colors on modern terminals
+    #[default]
+    TrueColor,
+    /// `38;5;n` escapes of the nearest xterm 256-color entry, for terminals without truecolor
+    Xterm256,
+}
+
+impl AnsiColor {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TrueColor => "truecolor",
+            Self::Xterm256 => "256",
+        }
+    }
+}
+
+/// Render `frame` as text with an ANSI color escape before every cell (foreground, and background
+/// when the frame has a background layer), resetting at the end of each row, so it can be printed
+/// or `cat`-ed to a terminal in color. Frames without colors come out as their plain text.
+pub fn frame_to_ansi_string(frame: &AsciiFrame, colors: AnsiColor) -> String {
+    let mut out = String::with_capacity(frame.text.len() * if frame.rgb.is_empty() {1} else {20});
+    push_ansi_rows(&frame.text, frame.width, &frame.rgb, &frame.bg_rgb, colors, &mut out);
+    out
+}
+
+/// Append the rows of a `width` cell wide frame to `out` as [`frame_to_ansi_string`] renders them.
+pub(crate) fn push_ansi_rows(text: &str, width: u32, rgb: &[u8], bg_rgb: &[u8], colors: AnsiColor, out: &mut String) {
+    use std::fmt::Write;
+
+    let width = width as usize;
+    let escape = |out: &mut String, layer: u8, cell: &[u8]| {
+        let _ = match colors {
+            AnsiColor::TrueColor => write!(out, "\x1b[{};2;{};{};{}m", layer, cell[0], cell[1], cell[2]),
+            AnsiColor::Xterm256 => write!(out, "\x1b[{};5;{}m", layer, xterm_256_index([cell[0], cell[1], cell[2]])),
+        };
+    };
+    for (row, line) in text.lines().enumerate() {
+        if rgb.is_empty() {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+        for (col, ch) in line.chars().enumerate() {
+            let cell = (row * width + col) * 3;
+            if let Some(fg) = rgb.get(cell..cell + 3) {
+                escape(out, 38, fg);
+            }
+            if let Some(bg) = bg_rgb.get(cell..cell + 3) {
+                escape(out, 48, bg);
+            }
+            out.push(ch);
+        }
+        out.push_str("\x1b[0m\n");
+    }
+}
+
+/// The xterm 256-color index closest to `rgb`: an entry of the 6×6×6 color cube or of the 24 step
+/// gray ramp, whichever is nearer. The 16 system colors are skipped as terminals theme them.
+fn xterm_256_index(rgb: [u8; 3]) -> u8 {
+    const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let nearest_level = |channel: u8| (0..6).min_by_key(|&level| (CUBE[level] as i32 - channel as i32).abs()).unwrap_or(0);
+    let distance = |candidate: [u8; 3]| candidate.iter().zip(rgb).map(|(&a, b)| (a as i32 - b as i32).pow(2)).sum::<i32>();
+
+    let [r, g, b] = rgb.map(nearest_level);
+    let cube = [CUBE[r], CUBE[g], CUBE[b]];
+    let mean = (rgb.iter().map(|&channel| channel as u32).sum::<u32>() / 3) as i32;
+    let step = ((mean - 8 + 5) / 10).clamp(0, 23);
+    let gray = (8 + step * 10) as u8;
+    if distance([gray; 3]) < distance(cube) {232 + step as u8} else {(16 + 36 * r + 6 * g + b) as u8}
+}
+
 /// Convert encoded image bytes (PNG or JPEG) into an in-memory ASCII frame.
-pub fn image_bytes_to_frame(bytes: &[u8], options: &ConversionOptions) -> Result<ImageFrame> {
+pub fn image_bytes_to_frame(bytes: &[u8], options: &ConversionOptions) -> Result<AsciiFrame> {
     let image = image::load_from_memory(bytes).context("decoding image bytes")?;
     image_to_frame(&image, options)
 }
@@ -44,62 +311,759 @@ pub fn image_bytes_to_frame(bytes: &[u8], options: &ConversionOptions) -> Result
 /// Convert an already-decoded image into an in-memory ASCII frame.
 ///
 /// Only `CellColorMode::ForegroundOnly` is supported here; the background-fitting modes live in the filesystem pipeline.
-pub fn image_to_frame(image: &DynamicImage, options: &ConversionOptions) -> Result<ImageFrame> {
+/// Matching glyph shapes ([`GlyphMatch`]) needs the `cli` feature.
+pub fn image_to_frame(image: &DynamicImage, options: &ConversionOptions) -> Result<AsciiFrame> {
+    cells_to_frame(image, None, options)
+}
+
+/// Convert a frame of YUV into an in-memory ASCII frame, like [`image_to_frame`]. When the luminance
+/// model measures the luma of the frame's [`YuvMatrix`](crate::yuv::YuvMatrix) (`Luma` with the
+/// Rec.709 weights for BT.709, `Rec601` for BT.601), characters are picked from the Y plane as it is,
+/// and only the cell colors are turned into RGB.
+pub fn yuv_to_frame(frame: &YuvFrame, options: &ConversionOptions) -> Result<AsciiFrame> {
+    let luma = frame.luma_for(Sampling::from_options(options).luminance);
+    cells_to_frame(&DynamicImage::ImageRgb8(frame.to_rgb()), luma.as_ref(), options)
+}
+
+fn cells_to_frame(image: &DynamicImage, luma: Option<&GrayImage>, options: &ConversionOptions) -> Result<AsciiFrame> {
     if options.cell_color_mode != CellColorMode::ForegroundOnly {
-        bail!("in-memory conversion supports only CellColorMode::ForegroundOnly");
+        return Err(Error::InvalidOptions("in-memory conversion supports only CellColorMode::ForegroundOnly".into()));
     }
     if options.ascii_chars.is_empty() {
-        bail!("ascii_chars must not be empty");
+        return Err(Error::InvalidOptions("ascii_chars must not be empty".into()));
+    }
+    if options.glyph_match != GlyphMatch::Luminance {
+        #[cfg(feature = "cli")]
+        return crate::convert::image_to_ascii_frame_data(image, options.font_ratio, options.luminance, options.resolve_bg_threshold(), options.columns, &options.ramp(), options.cell_color_mode, options.bg_fit_quality, options.palette, Sampling::from_options(options)).map(AsciiFrame::from);
+        #[cfg(not(feature = "cli"))]
+        return Err(Error::InvalidOptions("matching glyph shapes needs the cli feature".into()));
+    }
+    let mut frame = image_to_cells(image, luma, options.font_ratio, options.luminance, options.columns, &options.ramp(), Sampling::from_options(options));
+    options.palette.apply_rgb_triplets(&mut frame.rgb);
+    options.palette.apply_background_triplets(&mut frame.bg_rgb);
+    Ok(frame)
+}
+
+/// The cells of `img`: text, a flat RGB triplet per cell in row-major order, and for the block
+/// modes a background triplet per cell.
+/// With `square_pixels`, every sample is written as two identical cells (see [`ConversionOptions::square_pixels`]).
+/// 16-bit images are resized and measured at full precision and only the cell colors are reduced to
+/// 8 bits, so gradients finer than 256 levels still spread across the ramp.
+/// ASCII characters are picked by `measured`, the brightness of every pixel of `img` measured
+/// beforehand (a Y plane), when it is given; tone adjustments and anaglyphs, which change the
+/// brightness, measure the pixels instead.
+pub(crate) fn image_to_cells(img: &DynamicImage, measured: Option<&GrayImage>, font_ratio: f32, threshold: u8, columns: Option<u32>, ascii_chars: &[u8], sampling: Sampling) -> AsciiFrame {
+    let transparent = |target_w, target_h| if sampling.anaglyph.is_none() {sampling.alpha.transparent_cells(img, target_w, target_h)} else {None};
+    let img = sampling.source(img);
+    let (orig_w, orig_h) = (img.width(), img.height());
+    let (target_w, target_h) = sampling.cell_grid(orig_w, orig_h, font_ratio, columns);
+    let filter = sampling.resize_filter(target_w, orig_w);
+    let luma = sampling.luminance;
+    let repeat = sampling.repeat();
+    let mut out = String::with_capacity((target_w as usize * repeat + 1) * (target_h as usize));
+
+    let mut bg_rgb = Vec::new();
+    let rgb_data = if sampling.char_mode != CharMode::Ascii {
+        let (dots_w, dots_h) = sampling.char_mode.dots_per_cell();
+        let mut img = img.to_rgb8();
+        if (target_w * dots_w, target_h * dots_h) != (orig_w, orig_h) {
+            img = image::imageops::resize(&img, target_w * dots_w, target_h * dots_h, sampling.resize_filter(target_w * dots_w, orig_w));
+        }
+        sampling.tone.apply_rgb8(&mut img);
+        let (chars, rgb, bg) = dot_cells(&img, target_w, target_h, threshold, luma, sampling.char_mode);
+        push_ascii_rows(chars.into_iter().map(|ch| sampling.codepage.restrict(ch)), target_w, repeat, &mut out);
+        bg_rgb = bg;
+        rgb
+    } else if has_16_bit_channels(&img) {
+        let mut img = img.to_rgb16();
+        if (target_w, target_h) != (orig_w, orig_h) {
+            img = image::imageops::resize(&img, target_w, target_h, filter);
+        }
+        sampling.tone.apply_rgb16(&mut img);
+        let lumas = img.pixels().map(|px| luma.luminance16(px[0], px[1], px[2]));
+        push_ascii_rows(ramp_chars(lumas, target_w, threshold, ascii_chars, sampling.dither, sampling.seed).into_iter(), target_w, repeat, &mut out);
+        img.into_raw().into_iter().map(|channel| ((channel as u32 + 128) / 257) as u8).collect()
+    } else if let Some(measured) = measured.filter(|measured| measured.dimensions() == (orig_w, orig_h) && sampling.tone.is_identity() && sampling.anaglyph.is_none()) {
+        let mut img = img.to_rgb8();
+        let mut measured = Cow::Borrowed(measured);
+        if (target_w, target_h) != (orig_w, orig_h) {
+            img = image::imageops::resize(&img, target_w, target_h, filter);
+            measured = Cow::Owned(image::imageops::resize(measured.as_ref(), target_w, target_h, filter));
+        }
+        let lumas = measured.pixels().map(|px| px[0] as u16 * 257);
+        push_ascii_rows(ramp_chars(lumas, target_w, threshold, ascii_chars, sampling.dither, sampling.seed).into_iter(), target_w, repeat, &mut out);
+        img.into_raw()
+    } else {
+        let mut img = img.to_rgb8();
+        if (target_w, target_h) != (orig_w, orig_h) {
+            img = image::imageops::resize(&img, target_w, target_h, filter);
+        }
+        sampling.tone.apply_rgb8(&mut img);
+        let lumas = img.pixels().map(|px| luma.luminance(px[0], px[1], px[2]) as u16 * 257);
+        push_ascii_rows(ramp_chars(lumas, target_w, threshold, ascii_chars, sampling.dither, sampling.seed).into_iter(), target_w, repeat, &mut out);
+        img.into_raw()
+    };
+    let mut rgb_data = if sampling.square_pixels {double_rgb_cells(&rgb_data)} else {rgb_data};
+    if sampling.square_pixels {
+        bg_rgb = double_rgb_cells(&bg_rgb);
+    }
+    if let Some(transparent) = transparent(target_w, target_h) {
+        alpha::blank_cells(&mut out, &mut rgb_data, &mut bg_rgb, &transparent, repeat);
+    }
+    if sampling.preserve_markers {
+        markers::preserve_markers(&img.to_rgb8(), &mut out, target_w * repeat as u32, target_h, ascii_chars, &mut rgb_data, &mut []);
+    }
+    let (mut width, mut height) = (target_w * repeat as u32, target_h);
+    sampling.line_limits.apply(&mut out, &mut width, &mut height, &mut rgb_data, &mut bg_rgb);
+    AsciiFrame {text: out, width, height, rgb: rgb_data, bg_rgb}
+}
+
+fn has_16_bit_channels(img: &DynamicImage) -> bool {
+    matches!(img, DynamicImage::ImageLuma16(_) | DynamicImage::ImageLumaA16(_) | DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_))
+}
+
+/// What to do when more columns are requested than the source image is wide.
+///
+/// Upscaling invents no detail: a 320 pixel wide GIF blown up to 800 columns only comes out blurrier
+/// and slower to convert.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UpscalePolicy {
+    /// Upscale to the requested columns
+    #[default]
+    Allow,
+    /// Upscale to the requested columns, but record a warning
+    Warn,
+    /// Use the source width instead, one pixel per column
+    Cap,
+    /// Use the largest whole multiple of the source width that fits, repeating each pixel with nearest-neighbor
+    Integer,
+}
+
+impl UpscalePolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Warn => "warn",
+            Self::Cap => "cap",
+            Self::Integer => "integer",
+        }
+    }
+
+    /// Columns to sample `requested` columns of a `source_width` pixel wide image at.
+    ///
+    /// ```
+    /// use cascii::UpscalePolicy;
+    ///
+    /// assert_eq!(UpscalePolicy::Allow.columns(800, 320), 800);
+    /// assert_eq!(UpscalePolicy::Cap.columns(800, 320), 320);
+    /// assert_eq!(UpscalePolicy::Integer.columns(800, 320), 640);
+    /// assert_eq!(UpscalePolicy::Integer.columns(200, 320), 200);
+    /// ```
+    pub fn columns(self, requested: u32, source_width: u32) -> u32 {
+        let source_width = source_width.max(1);
+        if requested <= source_width {
+            return requested;
+        }
+        match self {
+            Self::Allow | Self::Warn => requested,
+            Self::Cap => source_width,
+            Self::Integer => requested / source_width * source_width,
+        }
+    }
+
+    /// Warning to record when `requested` columns upscale a `source_width` pixel wide source under [`Warn`](Self::Warn).
+    pub fn warning(self, requested: u32, source_width: u32) -> Option<String> {
+        (self == Self::Warn && requested > source_width).then(|| format!("{} columns upscale the {} pixel wide source, which only blurs it; cap or integer upscaling keeps it sharp", requested, source_width))
+    }
+}
+
+/// What each cell of the character grid draws.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CharMode {
+    /// One character of `ascii_chars` per cell, picked by the cell's luminance
+    #[default]
+    Ascii,
+    /// One braille character (U+2800–U+28FF) per cell, each of its 2×4 dots a pixel raised by
+    /// ordered dithering, for 8 times the detail of the same grid. `ascii_chars` is unused, and so
+    /// is `preserve_markers`, since dots keep hard edges anyway. The cell takes the mean color of its
+    /// pixels. Only `CellColorMode::ForegroundOnly` is supported.
+    Braille,
+    /// '▀' in every cell, its foreground the top pixel and its background the bottom one, for twice
+    /// the rows of colored output. Cells whose pixels both fall below the luminance threshold are
+    /// blank, and dark pixels come out black. Writes a background layer; the background-fitting
+    /// modes don't apply.
+    HalfBlock,
+    /// One of the 16 quadrant block characters per cell, splitting its 2×2 pixels into the two
+    /// color groups that match them best: the lighter group is the foreground and the other the
+    /// background. Thresholds and layers as for [`HalfBlock`](Self::HalfBlock).
+    QuarterBlock,
+    /// One of the 64 sextant characters (U+1FB00–U+1FB3B, plus `▌▐█`) per cell, splitting its 2×3
+    /// pixels like [`QuarterBlock`](Self::QuarterBlock) splits 2×2. The cell shape of teletext
+    /// mosaics (see [`crate::teletext`]).
+    Sextant,
+}
+
+impl CharMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ascii => "ascii",
+            Self::Braille => "braille",
+            Self::HalfBlock => "half-block",
+            Self::QuarterBlock => "quarter-block",
+            Self::Sextant => "sextant",
+        }
+    }
+
+    /// Pixels sampled across and down every cell: 2×4 braille dots, block halves, quadrants or sextants, or the cell itself.
+    pub(crate) fn dots_per_cell(self) -> (u32, u32) {
+        match self {
+            Self::Ascii => (1, 1),
+            Self::Braille => (2, 4),
+            Self::HalfBlock => (1, 2),
+            Self::QuarterBlock => (2, 2),
+            Self::Sextant => (2, 3),
+        }
+    }
+}
+
+/// How cell luminances are spread across the ramp before they pick their characters.
+///
+/// Without dithering a smooth gradient spanning a few ramp steps turns into flat bands at low
+/// column counts. Dithering mixes the neighbouring characters of each band edge so their average
+/// follows the gradient. Only [`CharMode::Ascii`] cells are dithered; cells darker than the
+/// luminance threshold stay blank either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DitherMode {
+    /// Every cell takes the character of its own luminance
+    #[default]
+    None,
+    /// Floyd–Steinberg error diffusion: every cell's rounding error is carried over to its right and
+    /// lower neighbours. The closest match to the source, but patterns shift between video frames.
+    FloydSteinberg,
+    /// 4×4 Bayer ordered dithering: a fixed threshold pattern, so still areas of a video stay still
+    Ordered,
+    /// Random thresholds drawn from [`ConversionOptions::seed`]: grain without a visible pattern.
+    /// Every frame of a conversion gets the same grain.
+    Noise,
+}
+
+impl DitherMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::FloydSteinberg => "floyd-steinberg",
+            Self::Ordered => "ordered",
+            Self::Noise => "noise",
+        }
+    }
+}
+
+/// How source pixels are sampled onto the character grid and measured, taken from [`ConversionOptions`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Sampling {
+    pub(crate) square_pixels: bool,
+    pub(crate) pixel_art: bool,
+    pub(crate) upscale: UpscalePolicy,
+    pub(crate) luminance: Luminance,
+    pub(crate) anaglyph: Option<Anaglyph>,
+    pub(crate) preserve_markers: bool,
+    pub(crate) line_limits: LineLimits,
+    pub(crate) char_mode: CharMode,
+    pub(crate) dither: DitherMode,
+    pub(crate) seed: u64,
+    pub(crate) tone: Tone,
+    pub(crate) alpha: AlphaMode,
+    pub(crate) glyph_match: GlyphMatch,
+    pub(crate) codepage: Codepage,
+    pub(crate) cframe_format: CframeFormat,
+    /// Frame rate of the clip being converted, recorded in v2 `.cframe` files
+    pub(crate) fps: Option<u32>,
+    /// Keep the frames an interrupted run already wrote (see [`AsciiConverter::with_resume`](crate::AsciiConverter::with_resume))
+    pub(crate) resume: bool,
+}
+
+impl Sampling {
+    pub(crate) fn from_options(options: &ConversionOptions) -> Self {
+        Self {square_pixels: options.square_pixels, pixel_art: options.pixel_art, upscale: options.upscale, luminance: Luminance::new(options.luminance_model, options.luminance_weights), anaglyph: options.anaglyph, preserve_markers: options.preserve_markers && options.char_mode == CharMode::Ascii, line_limits: options.line_limits, char_mode: options.char_mode, dither: options.dither, seed: options.seed, tone: Tone::from_options(options), alpha: options.alpha_mode, glyph_match: options.glyph_match, codepage: options.codepage, cframe_format: options.cframe_format, fps: None, resume: false}
+    }
+
+    /// These settings for the frames of a clip at `fps`
+    #[cfg(feature = "cli")]
+    pub(crate) fn with_fps(mut self, fps: u32) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+
+    /// These settings, skipping frames an earlier run already wrote when `resume` is set
+    #[cfg(feature = "cli")]
+    pub(crate) fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Font ratio and column count to sample at. Square pixels sample half the columns at 1:1, so each
+    /// sample spread over two cells of a ~0.5 ratio font comes out square.
+    pub(crate) fn grid(self, font_ratio: f32, columns: Option<u32>) -> (f32, Option<u32>) {
+        if self.square_pixels {(1.0, columns.map(|columns| (columns / 2).max(1)))} else {(font_ratio, columns)}
+    }
+
+    /// Cell grid size (before square pixels are doubled) for an `orig_w`×`orig_h` image.
+    pub(crate) fn cell_grid(self, orig_w: u32, orig_h: u32, font_ratio: f32, columns: Option<u32>) -> (u32, u32) {
+        let (font_ratio, columns) = self.grid(font_ratio, columns);
+        self.grid_size(orig_w, orig_h, font_ratio, columns)
+    }
+
+    /// Cell grid size for an `orig_w`×`orig_h` image at a font ratio and column count [`Self::grid`] already adjusted.
+    pub(crate) fn grid_size(self, orig_w: u32, orig_h: u32, font_ratio: f32, columns: Option<u32>) -> (u32, u32) {
+        let columns = self.line_limits.columns(self.source_columns(columns, orig_w), orig_w, orig_h, font_ratio, self.repeat() as u32);
+        target_dimensions(orig_w, orig_h, font_ratio, columns)
+    }
+
+    /// The upscale policy in effect. Pixel art never samples more columns than the source has
+    /// pixels unless integer scaling was asked for, so a small sprite maps one pixel to one cell.
+    pub(crate) fn upscale_policy(self) -> UpscalePolicy {
+        match self.upscale {
+            UpscalePolicy::Allow | UpscalePolicy::Warn if self.pixel_art => UpscalePolicy::Cap,
+            policy => policy,
+        }
+    }
+
+    /// Sampled column count for a `source_width` pixel wide image.
+    pub(crate) fn source_columns(self, columns: Option<u32>, source_width: u32) -> Option<u32> {
+        columns.map(|columns| self.upscale_policy().columns(columns, source_width))
+    }
+
+    /// Whether to resize with nearest-neighbor, so every cell keeps an exact source color instead of
+    /// a blend of its neighbours: always for pixel art, and for whole-multiple upscales.
+    pub(crate) fn nearest(self) -> bool {
+        self.pixel_art || self.upscale == UpscalePolicy::Integer
+    }
+
+    /// Resampling filter for resizing a `source_width` pixel wide image onto `columns` cells.
+    pub(crate) fn resize_filter(self, columns: u32, source_width: u32) -> image::imageops::FilterType {
+        if self.pixel_art || (self.nearest() && columns > source_width) {image::imageops::FilterType::Nearest} else {image::imageops::FilterType::Triangle}
+    }
+
+    /// Number of side-by-side views in each source frame.
+    #[cfg(feature = "cli")]
+    pub(crate) fn views(self) -> u32 {
+        if self.anaglyph.is_some() {2} else {1}
+    }
+
+    /// `img` as it is sampled: transparency is resolved by the alpha mode, then side-by-side stereo
+    /// is combined into a half-width anaglyph.
+    pub(crate) fn source(self, img: &DynamicImage) -> Cow<'_, DynamicImage> {
+        let img = self.alpha.flatten(img).map_or(Cow::Borrowed(img), Cow::Owned);
+        match self.anaglyph {
+            Some(anaglyph) => Cow::Owned(DynamicImage::ImageRgb8(anaglyph.combine(&img.to_rgb8()))),
+            None => img,
+        }
+    }
+
+    fn repeat(self) -> usize {
+        if self.square_pixels {2} else {1}
+    }
+
+}
+
+/// Brightness, contrast, gamma and inversion from [`ConversionOptions`], applied to every channel of
+/// the resized pixels before they are measured, so both the characters and the colors follow them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Tone {
+    brightness: f32,
+    contrast: f32,
+    gamma: f32,
+    invert: bool,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {brightness: 0.0, contrast: 1.0, gamma: 1.0, invert: false}
+    }
+}
+
+impl Tone {
+    /// The options' tone, with non-finite values and a non-positive gamma left neutral.
+    pub(crate) fn from_options(options: &ConversionOptions) -> Self {
+        let neutral = Self::default();
+        Self {
+            brightness: if options.brightness.is_finite() {options.brightness} else {neutral.brightness},
+            contrast: if options.contrast.is_finite() {options.contrast} else {neutral.contrast},
+            gamma: if options.gamma.is_finite() && options.gamma > 0.0 {options.gamma} else {neutral.gamma},
+            invert: options.invert,
+        }
+    }
+
+    pub(crate) fn is_identity(self) -> bool {
+        self == Self::default()
+    }
+
+    /// Adjust a channel value in `0.0..=1.0` the way ffmpeg's `eq` filter does: contrast around mid
+    /// gray, then brightness, then gamma, and finally inversion.
+    fn apply(self, value: f32) -> f32 {
+        let value = ((value - 0.5) * self.contrast + 0.5 + self.brightness).clamp(0.0, 1.0).powf(1.0 / self.gamma);
+        if self.invert {1.0 - value} else {value}
+    }
+
+    pub(crate) fn apply_rgb8(self, img: &mut RgbImage) {
+        if self.is_identity() {
+            return;
+        }
+        let table: [u8; 256] = std::array::from_fn(|value| (self.apply(value as f32 / 255.0) * 255.0).round() as u8);
+        img.iter_mut().for_each(|channel| *channel = table[*channel as usize]);
+    }
+
+    fn apply_rgb16(self, img: &mut ImageBuffer<Rgb<u16>, Vec<u16>>) {
+        if self.is_identity() {
+            return;
+        }
+        img.iter_mut().for_each(|channel| *channel = (self.apply(*channel as f32 / 65535.0) * 65535.0).round() as u16);
+    }
+}
+
+/// Ordered-dither thresholds of the 8 dots of a braille cell, row-major over its 2×4 pixels, in eighths
+const BRAILLE_DITHER: [u32; 8] = [0, 4, 6, 2, 1, 5, 7, 3];
+/// Dot bit of every pixel of a braille cell, row-major over its 2×4 pixels (dots 1 4 / 2 5 / 3 6 / 7 8)
+const BRAILLE_DOTS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+
+/// Characters, foreground and background colors (empty for braille) of the `width`×`height`
+/// cells of `dots`, an image of [`Sampling::dots_per_cell`] pixels per cell, in braille or block mode.
+fn dot_cells(dots: &RgbImage, width: u32, height: u32, threshold: u8, luma: Luminance, char_mode: CharMode) -> (Vec<char>, Vec<u8>, Vec<u8>) {
+    let (dots_w, dots_h) = char_mode.dots_per_cell();
+    let cells = (width * height) as usize;
+    let (mut chars, mut rgb) = (Vec::with_capacity(cells), Vec::with_capacity(cells * 3));
+    let mut bg_rgb = if char_mode == CharMode::Braille {Vec::new()} else {Vec::with_capacity(cells * 3)};
+    let mut pixels: Vec<([u8; 3], u32)> = Vec::with_capacity((dots_w * dots_h) as usize);
+    for row in 0..height {
+        for column in 0..width {
+            pixels.clear();
+            for dot in 0..dots_w * dots_h {
+                let px = dots.get_pixel(column * dots_w + dot % dots_w, row * dots_h + dot / dots_w).0;
+                pixels.push((px, luma.luminance(px[0], px[1], px[2]) as u32));
+            }
+            let (ch, fg, bg) = match char_mode {
+                CharMode::Braille => braille_cell(&pixels, threshold),
+                _ => block_cell(&pixels, threshold, char_mode),
+            };
+            chars.push(ch);
+            rgb.extend(fg);
+            if char_mode != CharMode::Braille {
+                bg_rgb.extend(bg);
+            }
+        }
+    }
+    (chars, rgb, bg_rgb)
+}
+
+/// The braille character of 2×4 pixels, row-major, and their mean color. A dot is raised when its
+/// pixel's luminance clears `threshold` and its dither level above it.
+fn braille_cell(pixels: &[([u8; 3], u32)], threshold: u8) -> (char, [u8; 3], [u8; 3]) {
+    let range = 255 - threshold as u32;
+    let (mut pattern, mut sums) = (0u32, [0u32; 3]);
+    for (&(px, luma), (&level, &bit)) in pixels.iter().zip(BRAILLE_DITHER.iter().zip(&BRAILLE_DOTS)) {
+        // Levels sit in the middle of eighths of the range, so full white raises every dot and the threshold none
+        if luma >= threshold as u32 && (luma - threshold as u32) * 16 > (2 * level + 1) * range {
+            pattern |= bit;
+        }
+        for (sum, channel) in sums.iter_mut().zip(px) {
+            *sum += channel as u32;
+        }
     }
-    let (text, width, height, rgb) = rgb_image_to_ascii_with_colors(image.to_rgb8(), options.font_ratio, options.luminance, options.columns, options.ascii_chars.as_bytes());
-    Ok(ImageFrame {text, width, height, rgb})
+    (char::from_u32(BRAILLE_BASE + pattern).expect("braille patterns are valid characters"), sums.map(|sum| ((sum + 4) / 8) as u8), [0; 3])
 }
 
-/// Returns (ascii_string, width, height, rgb_bytes)
-/// rgb_bytes is a flat Vec<u8> with 3 bytes (R, G, B) per character, row-major order
-pub(crate) fn rgb_image_to_ascii_with_colors(mut img: RgbImage, font_ratio: f32, threshold: u8, columns: Option<u32>, ascii_chars: &[u8]) -> (String, u32, u32, Vec<u8>) {
-    let (orig_w, orig_h) = img.dimensions();
-    let (target_w, target_h) = if let Some(cols) = columns {
-        let w = cols;
+/// The block character of a cell's half-block, quadrant or sextant pixels, row-major, with its
+/// foreground and background colors. Pixels below `threshold` count as black.
+fn block_cell(pixels: &[([u8; 3], u32)], threshold: u8, char_mode: CharMode) -> (char, [u8; 3], [u8; 3]) {
+    let colors: Vec<[u8; 3]> = pixels.iter().map(|&(px, luma)| if luma < threshold as u32 {[0; 3]} else {px}).collect();
+    if pixels.iter().all(|&(_, luma)| luma < threshold as u32) {
+        return (' ', [0; 3], [0; 3]);
+    }
+    if char_mode == CharMode::HalfBlock {
+        return ('▀', colors[0], colors[1]);
+    }
+    // Quadrant or sextant masks: every split of the pixels into foreground and background, scored by
+    // how far the pixels are from their group's mean
+    let full = (1u32 << colors.len()) - 1;
+    let mean = |mask: u32, fg: bool| {
+        let (mut sums, mut count) = ([0u32; 3], 0u32);
+        for color in colors.iter().enumerate().filter(|&(index, _)| (mask >> index & 1 == 1) == fg).map(|(_, color)| color) {
+            for (sum, &channel) in sums.iter_mut().zip(color) {
+                *sum += channel as u32;
+            }
+            count += 1;
+        }
+        (count > 0).then(|| sums.map(|sum| ((sum + count / 2) / count) as u8))
+    };
+    let error = |mask: u32| -> u32 {
+        let (fg, bg) = (mean(mask, true).unwrap_or_default(), mean(mask, false).unwrap_or_default());
+        colors.iter().enumerate().map(|(index, &color)| color.iter().zip(if mask >> index & 1 == 1 {fg} else {bg}).map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32).sum::<u32>()).sum()
+    };
+    let mut mask = (1..=full).min_by_key(|&mask| (error(mask), std::cmp::Reverse(mask))).unwrap_or(full);
+    let luma = |color: Option<[u8; 3]>| color.map_or(0, |[r, g, b]| r as u32 + g as u32 + b as u32);
+    if mask != full && luma(mean(mask, false)) > luma(mean(mask, true)) {
+        mask ^= full;
+    }
+    let fg = mean(mask, true).unwrap_or_default();
+    let ch = if char_mode == CharMode::Sextant {sextant_char(mask as u8)} else {QUADRANT_BLOCKS[mask as usize]};
+    (ch, fg, mean(mask, false).unwrap_or(fg))
+}
+
+/// Repeat every cell of `\n`-separated text rows twice.
+#[cfg(feature = "cli")]
+pub(crate) fn double_text_cells(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    for ch in text.chars() {
+        out.push(ch);
+        if ch != '\n' {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// Repeat every 3-byte color of a row-major cell plane twice.
+pub(crate) fn double_rgb_cells(rgb: &[u8]) -> Vec<u8> {
+    rgb.chunks_exact(3).flat_map(|cell| [cell, cell]).flatten().copied().collect()
+}
+
+/// Character grid size for an `orig_w`×`orig_h` image at `columns` (or the source width) and `font_ratio`.
+pub(crate) fn target_dimensions(orig_w: u32, orig_h: u32, font_ratio: f32, columns: Option<u32>) -> (u32, u32) {
+    if let Some(cols) = columns {
         let h = (orig_h as f32 / orig_w as f32 * cols as f32 * font_ratio).round() as u32;
-        (w, h.max(1))
+        (cols, h.max(1))
     } else {
-        let w = orig_w;
         let h = (orig_h as f32 * font_ratio).round() as u32;
-        (w, h.max(1))
-    };
+        (orig_w, h.max(1))
+    }
+}
 
-    if target_w != orig_w || target_h != orig_h {
-        let dyn_img = DynamicImage::ImageRgb8(img);
-        img = dyn_img.resize_exact(target_w, target_h, image::imageops::FilterType::Triangle).to_rgb8();
+/// Write row-major `cells`, each `repeat` times, as `\n`-terminated rows of `width` cells.
+fn push_ascii_rows(cells: impl Iterator<Item = char>, width: u32, repeat: usize, out: &mut String) {
+    for (index, ch) in cells.enumerate() {
+        out.extend(std::iter::repeat_n(ch, repeat));
+        if (index + 1) % width as usize == 0 {
+            out.push('\n');
+        }
     }
+}
+
+/// The former name of [`AsciiFrame`], kept for existing callers.
+pub type ImageFrame = AsciiFrame;
+
+/// Converts one sample image over and over with changing options, for GUI sliders and live previews.
+///
+/// The sample is decoded to RGB once, and the resized cell grid is cached until `columns`, `font_ratio` or
+/// the sampling filter change, so moving a luminance or charset slider only re-maps characters. The text and color buffers of the
+/// returned frame are reused between calls. Only the foreground pass runs; `cell_color_mode` is ignored, and characters are always picked by luminance (`glyph_match` is ignored).
+///
+/// ```
+/// use cascii::frame::Previewer;
+/// use cascii::test_pattern::{generate_test_pattern, TestPattern};
+/// use cascii::ConversionOptions;
+///
+/// let sample = image::DynamicImage::ImageRgb8(generate_test_pattern(TestPattern::Gradient, 64, 64));
+/// let mut previewer = Previewer::new(&sample);
+/// for luminance in [0, 64, 128] {
+///     let frame = previewer.preview(&ConversionOptions::default().with_columns(32).with_luminance(luminance)).unwrap();
+///     assert_eq!(frame.width, 32);
+/// }
+/// ```
+pub struct Previewer {
+    sample: RgbImage,
+    /// The sample with its alpha channel, when it has one
+    translucent: Option<DynamicImage>,
+    resized: Option<(image::imageops::FilterType, Option<Anaglyph>, AlphaMode, RgbImage)>,
+    frame: AsciiFrame,
+}
+
+impl Previewer {
+    /// Create a previewer for `sample`
+    pub fn new(sample: &DynamicImage) -> Self {
+        Self {sample: sample.to_rgb8(), translucent: sample.color().has_alpha().then(|| sample.clone()), resized: None, frame: AsciiFrame::default()}
+    }
+
+    /// Replace the sample image, dropping the cached resize
+    pub fn set_sample(&mut self, sample: &DynamicImage) {
+        self.sample = sample.to_rgb8();
+        self.translucent = sample.color().has_alpha().then(|| sample.clone());
+        self.resized = None;
+    }
+
+    /// Convert the sample with `options`, reusing cached work where possible.
+    pub fn preview(&mut self, options: &ConversionOptions) -> Result<&AsciiFrame> {
+        if options.ascii_chars.is_empty() {
+            return Err(Error::InvalidOptions("ascii_chars must not be empty".into()));
+        }
+        let sampling = Sampling::from_options(options);
+        let flattened = self.translucent.as_ref().and_then(|translucent| sampling.alpha.flatten(translucent)).map(DynamicImage::into_rgb8);
+        let flat = flattened.as_ref().unwrap_or(&self.sample);
+        let stereo = sampling.anaglyph.map(|anaglyph| anaglyph.combine(flat));
+        let sample = stereo.as_ref().unwrap_or(flat);
+        let (orig_w, orig_h) = sample.dimensions();
+        if orig_w == 0 || orig_h == 0 {
+            return Err(Error::InvalidOptions("sample image is empty".into()));
+        }
+        let (target_w, target_h) = sampling.cell_grid(orig_w, orig_h, options.font_ratio, options.columns);
+        let (dots_w, dots_h) = sampling.char_mode.dots_per_cell();
+        let (grid_w, grid_h) = (target_w * dots_w, target_h * dots_h);
+        let grid = if (grid_w, grid_h) == (orig_w, orig_h) {
+            sample
+        } else {
+            let filter = sampling.resize_filter(grid_w, orig_w);
+            if self.resized.as_ref().is_none_or(|(cached_filter, cached_anaglyph, cached_alpha, cached)| cached.dimensions() != (grid_w, grid_h) || *cached_filter != filter || *cached_anaglyph != sampling.anaglyph || *cached_alpha != sampling.alpha) {
+                self.resized = Some((filter, sampling.anaglyph, sampling.alpha, image::imageops::resize(sample, grid_w, grid_h, filter)));
+            }
+            &self.resized.as_ref().expect("resized grid was just cached").3
+        };
+        let toned;
+        let grid = if sampling.tone.is_identity() {
+            grid
+        } else {
+            toned = {
+                let mut toned = grid.clone();
+                sampling.tone.apply_rgb8(&mut toned);
+                toned
+            };
+            &toned
+        };
 
-    let (w, h) = img.dimensions();
-    let rgb_data = img.into_raw();
-    let mut out = String::with_capacity((w as usize + 1) * (h as usize));
-    for row in rgb_data.chunks_exact(w as usize * 3) {
-        for px in row.chunks_exact(3) {
-            let l = luminance_rgb(px[0], px[1], px[2]);
-            out.push(char_for(l, threshold, ascii_chars));
+        let frame = &mut self.frame;
+        let repeat = sampling.repeat();
+        frame.text.clear();
+        frame.text.reserve((target_w as usize * repeat + 1) * target_h as usize);
+        let (luma, ascii_chars) = (sampling.luminance, &options.ramp()[..]);
+        frame.rgb.clear();
+        frame.bg_rgb.clear();
+        if sampling.char_mode != CharMode::Ascii {
+            let (chars, rgb, bg_rgb) = dot_cells(grid, target_w, target_h, options.luminance, luma, sampling.char_mode);
+            push_ascii_rows(chars.into_iter().map(|ch| sampling.codepage.restrict(ch)), target_w, repeat, &mut frame.text);
+            frame.rgb.extend(rgb.chunks_exact(3).flat_map(|cell| std::iter::repeat_n(cell, repeat)).flatten());
+            frame.bg_rgb.extend(bg_rgb.chunks_exact(3).flat_map(|cell| std::iter::repeat_n(cell, repeat)).flatten());
+        } else {
+            let lumas = grid.pixels().map(|px| luma.luminance(px[0], px[1], px[2]) as u16 * 257);
+            push_ascii_rows(ramp_chars(lumas, target_w, options.luminance, ascii_chars, sampling.dither, sampling.seed).into_iter(), target_w, repeat, &mut frame.text);
+            frame.rgb.extend(grid.as_raw().chunks_exact(3).flat_map(|cell| std::iter::repeat_n(cell, repeat)).flatten());
         }
-        out.push('\n');
+        if let Some(transparent) = self.translucent.as_ref().filter(|_| sampling.anaglyph.is_none()).and_then(|translucent| sampling.alpha.transparent_cells(translucent, target_w, target_h)) {
+            alpha::blank_cells(&mut frame.text, &mut frame.rgb, &mut frame.bg_rgb, &transparent, repeat);
+        }
+        if sampling.preserve_markers {
+            markers::preserve_markers(sample, &mut frame.text, target_w * repeat as u32, target_h, ascii_chars, &mut frame.rgb, &mut []);
+        }
+        frame.width = target_w * repeat as u32;
+        frame.height = target_h;
+        sampling.line_limits.apply(&mut frame.text, &mut frame.width, &mut frame.height, &mut frame.rgb, &mut frame.bg_rgb);
+        options.palette.apply_rgb_triplets(&mut frame.rgb);
+        options.palette.apply_background_triplets(&mut frame.bg_rgb);
+        Ok(&self.frame)
+    }
+}
+
+/// One-shot preview of `sample`; use a [`Previewer`] when converting the same sample repeatedly.
+pub fn preview_with_options(sample: &DynamicImage, options: &ConversionOptions) -> Result<AsciiFrame> {
+    let mut previewer = Previewer::new(sample);
+    previewer.preview(options)?;
+    Ok(previewer.frame)
+}
+
+/// 4×4 Bayer matrix of ordered-dither thresholds, in sixteenths
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Characters for the row-major 16-bit luminances of a grid `width` cells wide, dithered by `dither`
+/// (with noise drawn from `seed`).
+///
+/// Dithered cells treat the ramp as evenly spaced levels from the threshold (first character) to
+/// white (last character), so a luminance between two levels comes out as a mix of both.
+fn ramp_chars(lumas: impl Iterator<Item = u16>, width: u32, threshold: u8, ascii_chars: &[u8], dither: DitherMode, seed: u64) -> Vec<char> {
+    if dither == DitherMode::None || ascii_chars.len() < 2 {
+        return lumas.map(|luma| char_for_luma16(luma, threshold, ascii_chars)).collect();
     }
-    (out, w, h, rgb_data)
+    let width = width.max(1) as usize;
+    let floor = threshold as f32 * 257.0;
+    let top = (ascii_chars.len() - 1) as f32;
+    let step = (65535.0 - floor).max(1.0) / top;
+    let lumas: Vec<u16> = lumas.collect();
+    // Rounding errors carried to the cells not yet visited, in ramp levels
+    let mut errors = if dither == DitherMode::FloydSteinberg {vec![0f32; lumas.len() + width + 1]} else {Vec::new()};
+    let mut rng = SeededRng::new(seed);
+    let mut chars = Vec::with_capacity(lumas.len());
+    for (index, &luma) in lumas.iter().enumerate() {
+        if (luma as f32) < floor {
+            chars.push(' ');
+            continue;
+        }
+        let (x, y) = (index % width, index / width);
+        let level = (luma as f32 - floor) / step;
+        let chosen = match dither {
+            DitherMode::Ordered => (level + (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0).floor().clamp(0.0, top),
+            DitherMode::Noise => (level + rng.next_f32()).floor().clamp(0.0, top),
+            _ => {
+                let level = level + errors[index];
+                let chosen = level.round().clamp(0.0, top);
+                let error = level - chosen;
+                if x + 1 < width {
+                    errors[index + 1] += error * 7.0 / 16.0;
+                }
+                if x > 0 {
+                    errors[index + width - 1] += error * 3.0 / 16.0;
+                }
+                errors[index + width] += error * 5.0 / 16.0;
+                if x + 1 < width {
+                    errors[index + width + 1] += error / 16.0;
+                }
+                chosen
+            }
+        };
+        chars.push(ramp_char(ascii_chars[chosen as usize]));
+    }
+    chars
 }
 
-pub(crate) fn char_for(luma: u8, threshold: u8, ascii_chars: &[u8]) -> char {
-    if luma < threshold {
-        return ' ';
+/// The ramp character of a 16-bit luminance, blank below the 8-bit `threshold` (scaled to match).
+fn char_for_luma16(luma: u16, threshold: u8, ascii_chars: &[u8]) -> char {
+    ramp_index(luma, threshold, ascii_chars.len()).map_or(' ', |idx| ramp_char(ascii_chars[idx]))
+}
+
+/// Position in a `ramp_len` character ramp of a 16-bit luminance, `None` below the 8-bit `threshold`.
+pub(crate) fn ramp_index(luma: u16, threshold: u8, ramp_len: usize) -> Option<usize> {
+    let threshold = threshold as u32 * 257;
+    if (luma as u32) < threshold {
+        return None;
     }
 
-    let effective_luma = (luma as u32).saturating_sub(threshold as u32);
-    let range = (255u32).saturating_sub(threshold as u32).max(1);
-    let num_chars_minus_1 = (ascii_chars.len() as u32).saturating_sub(1);
+    let effective_luma = (luma as u32).saturating_sub(threshold);
+    let range = 65535u32.saturating_sub(threshold).max(1);
+    let num_chars_minus_1 = (ramp_len as u32).saturating_sub(1);
 
     let idx = (effective_luma * num_chars_minus_1) / range;
-    let idx = idx.min(num_chars_minus_1) as usize;
+    Some(idx.min(num_chars_minus_1) as usize)
+}
+
+/// A frame as [`AsciiFrame::to_json`] writes it
+#[derive(Serialize)]
+struct JsonFrame {
+    width: u32,
+    height: u32,
+    cells: Vec<JsonCell>,
+}
+
+#[derive(Serialize)]
+struct JsonCell {
+    char: char,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<[u8; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    background: Option<[u8; 3]>,
+}
 
-    ascii_chars[idx] as char
+/// Encode a frame as JSON, one record per cell (see [`AsciiFrame::to_json`])
+pub(crate) fn encode_json(width: u32, height: u32, text: &str, rgb: &[u8], bg_rgb: &[u8]) -> String {
+    let color = |colors: &[u8], index: usize| colors.get(index * 3..index * 3 + 3).map(|rgb| [rgb[0], rgb[1], rgb[2]]);
+    let cells = text.lines().take(height as usize).enumerate().flat_map(|(row, line)| line.chars().take(width as usize).enumerate().map(move |(column, ch)| (row * width as usize + column, ch))).map(|(index, ch)| JsonCell {char: ch, color: color(rgb, index), background: color(bg_rgb, index)}).collect();
+    serde_json::to_string(&JsonFrame {width, height, cells}).expect("frames serialize to JSON")
 }
 
 /// Encode the combined binary format (.cframe): text + color in one buffer.
@@ -108,52 +1072,211 @@ pub(crate) fn char_for(luma: u8, threshold: u8, ascii_chars: &[u8]) -> char {
 /// 1. Header (8 bytes): `width: u32 LE` + `height: u32 LE`
 /// 2. Body (`width * height * 4` bytes): `char: u8 + r: u8 + g: u8 + b: u8` per cell, row-major
 /// 3. Optional extension area:
-///    - `flags: u8` — bit 0 (`CFRAME_EXT_FLAG_HAS_BG`) announces a background payload
+///    - `flags: u8` — bit 0 (`CFRAME_EXT_FLAG_HAS_BG`) announces a background payload; bit 1
+///      (`CFRAME_EXT_FLAG_BRAILLE`) marks every cell byte as a braille dot pattern, bit 2
+///      (`CFRAME_EXT_FLAG_BLOCKS`) cell bytes below 0x20 as block elements, bit 3
+///      (`CFRAME_EXT_FLAG_CP437`) every cell byte as a CP437 character, and bit 4
+///      (`CFRAME_EXT_FLAG_SEXTANTS`) every cell byte as a sextant mask
 ///    - if `flags & HAS_BG`: `width * height * 3` bytes of background RGB, row-major
 ///
 /// Older readers that don't know about the extension still parse the body correctly and ignore the trailing bytes. New readers detect the extension
 /// by looking past the legacy body for the `flags` byte instead of inferring payload presence from total file length.
 pub(crate) fn encode_cframe(width: u32, height: u32, ascii_content: &str, rgb_data: &[u8], bg_rgb_data: Option<&[u8]>) -> Vec<u8> {
     let cell_count = (width * height) as usize;
-    let mut output = Vec::with_capacity(8 + cell_count * 4 + bg_rgb_data.map_or(0, |background| 1 + background.len()));
+    let mut output = Vec::with_capacity(8 + cell_count * 4 + 1 + bg_rgb_data.map_or(0, <[u8]>::len));
     output.extend_from_slice(&width.to_le_bytes());
     output.extend_from_slice(&height.to_le_bytes());
 
-    for (char_idx, byte) in ascii_content.bytes().filter(|byte| *byte != b'\n').enumerate() {
+    let cell_bytes = CellBytes::of_text(ascii_content);
+    for (char_idx, ch) in ascii_content.chars().filter(|ch| *ch != '\n').enumerate() {
         let rgb_offset = char_idx * 3;
-        output.extend_from_slice(&[byte, rgb_data[rgb_offset], rgb_data[rgb_offset + 1], rgb_data[rgb_offset + 2]]);
+        output.extend_from_slice(&[cell_bytes.byte(ch), rgb_data[rgb_offset], rgb_data[rgb_offset + 1], rgb_data[rgb_offset + 2]]);
+    }
+    let flags = if bg_rgb_data.is_some() {CFRAME_EXT_FLAG_HAS_BG} else {0} | cell_bytes.flag();
+    if flags != 0 {
+        output.push(flags);
     }
     if let Some(bg_rgb_data) = bg_rgb_data {
-        output.push(CFRAME_EXT_FLAG_HAS_BG);
         output.extend_from_slice(bg_rgb_data);
     }
     output
 }
 
+/// How the one-byte cells of a `.cframe` stand for characters, announced by its extension flags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CellBytes {
+    /// Every byte is its Latin-1 character; characters beyond Latin-1 are stored as `?`
+    Latin1,
+    /// Every byte is a braille dot pattern
+    Braille,
+    /// Bytes below 0x20 are block elements, the rest Latin-1
+    Blocks,
+    /// Every byte is its code page 437 character
+    Cp437,
+    /// Every byte is the mask of a sextant character
+    Sextants,
+}
+
+impl CellBytes {
+    /// The encoding that stores every cell of `text`: braille when every cell is braille, else the
+    /// first of Latin-1, blocks, CP437 and sextants that has all its characters. When none does,
+    /// blocks when any cell is a block element.
+    pub(crate) fn of_text(text: &str) -> Self {
+        let mut cells = text.chars().filter(|&ch| ch != '\n').peekable();
+        if cells.peek().is_some() && cells.all(|ch| Self::Braille.stores(ch)) {
+            return Self::Braille;
+        }
+        let cells = || text.chars().filter(|&ch| ch != '\n');
+        [Self::Latin1, Self::Blocks, Self::Cp437, Self::Sextants].into_iter().find(|encoding| cells().all(|ch| encoding.stores(ch))).unwrap_or_else(|| {
+            if text.chars().any(|ch| (BLOCKS_BASE..BLOCKS_BASE + 0x20).contains(&(ch as u32))) {Self::Blocks} else {Self::Latin1}
+        })
+    }
+
+    /// Whether `ch` has a byte of its own in this encoding
+    fn stores(self, ch: char) -> bool {
+        let code = ch as u32;
+        match self {
+            Self::Latin1 => code < 0x100,
+            Self::Braille => (BRAILLE_BASE..BRAILLE_BASE + 0x100).contains(&code),
+            Self::Blocks => (BLOCKS_BASE..BLOCKS_BASE + 0x20).contains(&code) || (0x20..0x100).contains(&code),
+            Self::Cp437 => Codepage::Cp437.contains(ch),
+            Self::Sextants => sextant_mask(ch).is_some(),
+        }
+    }
+
+    /// The encoding announced by extension `flags`
+    pub(crate) fn from_flags(flags: u8) -> Self {
+        if flags & CFRAME_EXT_FLAG_BRAILLE != 0 {
+            Self::Braille
+        } else if flags & CFRAME_EXT_FLAG_BLOCKS != 0 {
+            Self::Blocks
+        } else if flags & CFRAME_EXT_FLAG_CP437 != 0 {
+            Self::Cp437
+        } else if flags & CFRAME_EXT_FLAG_SEXTANTS != 0 {
+            Self::Sextants
+        } else {
+            Self::Latin1
+        }
+    }
+
+    pub(crate) fn flag(self) -> u8 {
+        match self {
+            Self::Latin1 => 0,
+            Self::Braille => CFRAME_EXT_FLAG_BRAILLE,
+            Self::Blocks => CFRAME_EXT_FLAG_BLOCKS,
+            Self::Cp437 => CFRAME_EXT_FLAG_CP437,
+            Self::Sextants => CFRAME_EXT_FLAG_SEXTANTS,
+        }
+    }
+
+    /// The byte that stores `ch`
+    pub(crate) fn byte(self, ch: char) -> u8 {
+        match self {
+            Self::Cp437 => return crate::codepage::cp437_byte(ch).unwrap_or(b'?'),
+            Self::Sextants => return sextant_mask(ch).unwrap_or(0),
+            _ => {}
+        }
+        let code = ch as u32;
+        let code = match self {
+            Self::Braille => code.wrapping_sub(BRAILLE_BASE),
+            Self::Blocks if (BLOCKS_BASE..BLOCKS_BASE + 0x20).contains(&code) => code - BLOCKS_BASE,
+            Self::Blocks if code < 0x20 => b'?' as u32,
+            _ => code,
+        };
+        u8::try_from(code).unwrap_or(b'?')
+    }
+
+    /// The character `byte` stands for
+    pub(crate) fn char(self, byte: u8) -> char {
+        match self {
+            Self::Braille => char::from_u32(BRAILLE_BASE + byte as u32).expect("braille patterns are valid characters"),
+            Self::Blocks if byte < 0x20 => char::from_u32(BLOCKS_BASE + byte as u32).expect("block elements are valid characters"),
+            Self::Cp437 => crate::codepage::cp437_char(byte),
+            Self::Sextants => sextant_char(byte),
+            _ => byte as char,
+        }
+    }
+
+    /// The byte of an empty cell
+    #[cfg(feature = "cli")]
+    pub(crate) fn blank(self) -> u8 {
+        if matches!(self, Self::Braille | Self::Sextants) {0} else {b' '}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::OutputMode;
 
     fn gradient_image(width: u32, height: u32) -> DynamicImage {
-        let mut img = RgbImage::new(width, height);
-        for (x, _y, pixel) in img.enumerate_pixels_mut() {
-            let value = ((x * 255) / width.max(1)) as u8;
-            *pixel = image::Rgb([value, value, value]);
-        }
-        DynamicImage::ImageRgb8(img)
+        DynamicImage::ImageRgb8(crate::test_pattern::generate_test_pattern(crate::test_pattern::TestPattern::Gradient, width, height))
     }
 
     fn options() -> ConversionOptions {
         ConversionOptions {columns: Some(8), font_ratio: 1.0, ..ConversionOptions::default()}
     }
 
+    #[test]
+    fn test_16_bit_input_keeps_sub_8_bit_gradients() {
+        // Both pixels round to 128 in 8 bits but sit on either side of a ramp step in 16 bits
+        let deep = DynamicImage::ImageLuma16(image::ImageBuffer::from_raw(2, 1, vec![32768u16, 33024]).unwrap());
+        let ramp: Vec<u8> = (0..=255).collect();
+        let frame = image_to_cells(&deep, None, 1.0, 0, None, &ramp, Sampling::default());
+        let cells: Vec<char> = frame.text.trim_end().chars().collect();
+        assert_ne!(cells[0], cells[1]);
+        assert_eq!(frame.rgb, vec![128; 6]);
+        let shallow = image_to_cells(&DynamicImage::ImageRgb8(deep.to_rgb8()), None, 1.0, 0, None, &ramp, Sampling::default()).text;
+        assert_eq!(shallow.chars().next(), shallow.chars().nth(1));
+    }
+
+    #[test]
+    fn test_dithering_mixes_ramp_neighbours() {
+        // Mid gray sits halfway between the two characters of the ramp
+        let gray = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, image::Rgb([128; 3])));
+        let options = ConversionOptions {columns: Some(8), font_ratio: 1.0, luminance: 0, ascii_chars: " #".to_string(), ..ConversionOptions::default()};
+        let count = |dither: DitherMode| image_to_frame(&gray, &options.clone().with_dither(dither)).unwrap().text.matches('#').count();
+        assert_eq!(count(DitherMode::None), 0);
+        assert_eq!(count(DitherMode::Ordered), 32);
+        assert!((30..=34).contains(&count(DitherMode::FloydSteinberg)));
+        assert!((20..=44).contains(&count(DitherMode::Noise)));
+        let grain = |seed: u64| image_to_frame(&gray, &options.clone().with_dither(DitherMode::Noise).with_seed(seed)).unwrap().text;
+        assert_eq!(grain(3), grain(3));
+        assert_ne!(grain(3), grain(4));
+
+        // The threshold still blanks dark cells, and the preview dithers like the conversion
+        let dark = DynamicImage::ImageRgb8(RgbImage::from_fn(8, 8, |x, _| image::Rgb([if x < 4 {10} else {128}; 3])));
+        let options = ConversionOptions {luminance: 20, ..options.with_dither(DitherMode::Ordered)};
+        let frame = image_to_frame(&dark, &options).unwrap();
+        assert!(frame.text.lines().all(|line| line.starts_with("    ")));
+        assert_eq!(preview_with_options(&dark, &options).unwrap().text, frame.text);
+    }
+
+    #[test]
+    fn test_tone_adjusts_pixels_before_mapping() {
+        let ramp = DynamicImage::ImageRgb8(RgbImage::from_fn(4, 1, |x, _| image::Rgb([[0, 100, 200, 255][x as usize]; 3])));
+        let options = ConversionOptions {columns: Some(4), font_ratio: 1.0, luminance: 0, ascii_chars: " .:#".to_string(), ..ConversionOptions::default()};
+        let convert = |options: ConversionOptions| image_to_frame(&ramp, &options).unwrap();
+        assert_eq!(convert(options.clone()).text, " .:#\n");
+        let inverted = convert(options.clone().with_invert(true));
+        assert_eq!((inverted.text.as_str(), &inverted.rgb[..6]), ("#.  \n", &[255, 255, 255, 155, 155, 155][..]));
+        assert_eq!(convert(options.clone().with_brightness(1.0)).text, "####\n");
+        assert_eq!(convert(options.clone().with_contrast(0.0)).rgb, [128; 12]);
+        // Gamma above 1 lifts the midtones but keeps black and white
+        let lifted = convert(options.clone().with_gamma(2.0));
+        assert_eq!((lifted.rgb[0], lifted.rgb[3], lifted.rgb[9]), (0, 160, 255));
+        // Nonsense values leave the image alone, and the preview adjusts like the conversion
+        assert_eq!(convert(options.clone().with_gamma(0.0).with_contrast(f32::NAN)).rgb, convert(options.clone()).rgb);
+        let options = options.with_contrast(1.5).with_gamma(0.8).with_invert(true);
+        assert_eq!(preview_with_options(&ramp, &options).unwrap().rgb, convert(options).rgb);
+    }
+
     #[test]
     fn test_char_for_thresholds() {
         let chars = b" .:#";
-        assert_eq!(char_for(0, 10, chars), ' ');
-        assert_eq!(char_for(9, 10, chars), ' ');
-        assert_eq!(char_for(255, 10, chars), '#');
+        assert_eq!(char_for_luma16(0, 10, chars), ' ');
+        assert_eq!(char_for_luma16(9 * 257, 10, chars), ' ');
+        assert_eq!(char_for_luma16(255 * 257, 10, chars), '#');
     }
 
     #[test]
@@ -189,6 +1312,67 @@ mod tests {
         assert!(image_to_frame(&gradient_image(4, 4), &unsupported).is_err());
     }
 
+    #[test]
+    fn test_previewer_matches_image_to_frame() {
+        let image = gradient_image(40, 20);
+        let mut previewer = Previewer::new(&image);
+        for (columns, luminance) in [(8, 20), (8, 120), (16, 0), (8, 0)] {
+            let opts = ConversionOptions {columns: Some(columns), luminance, ..options()};
+            let expected = image_to_frame(&image, &opts).unwrap();
+            let frame = previewer.preview(&opts).unwrap();
+            assert_eq!((frame.width, frame.height), (expected.width, expected.height));
+            assert_eq!(frame.text, expected.text);
+            assert_eq!(frame.rgb, expected.rgb);
+        }
+        assert_eq!(preview_with_options(&image, &options()).unwrap().text, image_to_frame(&image, &options()).unwrap().text);
+    }
+
+    #[test]
+    fn test_square_pixels_double_each_sample() {
+        // 40x20 at 8 columns: 4 square samples across, 2 down, each written twice
+        let opts = ConversionOptions {font_ratio: 0.3, square_pixels: true, ..options()};
+        let frame = image_to_frame(&gradient_image(40, 20), &opts).unwrap();
+        assert_eq!((frame.width, frame.height), (8, 2));
+        assert!(frame.text.lines().all(|line| line.as_bytes().chunks(2).all(|pair| pair[0] == pair[1])));
+        assert!(frame.rgb.chunks(6).all(|pair| pair[..3] == pair[3..]));
+        let preview = Previewer::new(&gradient_image(40, 20)).preview(&opts).unwrap().text.clone();
+        assert_eq!(preview, frame.text);
+    }
+
+    #[test]
+    fn test_pixel_art_maps_small_sources_one_pixel_per_cell() {
+        // 4x4 sprite at 8 columns stays 4 wide and keeps exact source colors
+        let sprite = gradient_image(4, 4);
+        let opts = ConversionOptions {pixel_art: true, ..options()};
+        let frame = image_to_frame(&sprite, &opts).unwrap();
+        assert_eq!((frame.width, frame.height), (4, 4));
+        assert_eq!(frame.rgb, sprite.to_rgb8().into_raw());
+        let preview = Previewer::new(&sprite).preview(&opts).unwrap().rgb.clone();
+        assert_eq!(preview, frame.rgb);
+        // Downscaling picks source pixels instead of blending them
+        let frame = image_to_frame(&gradient_image(40, 20), &ConversionOptions {pixel_art: true, ..options()}).unwrap();
+        let source = gradient_image(40, 20).to_rgb8();
+        assert!(frame.rgb.chunks(3).all(|cell| source.pixels().any(|px| px.0 == cell)));
+    }
+
+    #[test]
+    fn test_upscale_policy_limits_columns() {
+        let sprite = gradient_image(3, 3);
+        let columns = |upscale| image_to_frame(&sprite, &ConversionOptions {upscale, ..options()}).unwrap().width;
+        assert_eq!(columns(UpscalePolicy::Allow), 8);
+        assert_eq!(columns(UpscalePolicy::Cap), 3);
+        // Integer scaling repeats each source pixel exactly twice
+        let frame = image_to_frame(&sprite, &ConversionOptions {upscale: UpscalePolicy::Integer, ..options()}).unwrap();
+        assert_eq!((frame.width, frame.height), (6, 6));
+        assert!(frame.rgb.chunks(6).all(|pair| pair[..3] == pair[3..]));
+    }
+
+    #[test]
+    fn test_previewer_rejects_empty_charset() {
+        let mut previewer = Previewer::new(&gradient_image(4, 4));
+        assert!(previewer.preview(&ConversionOptions {ascii_chars: String::new(), ..options()}).is_err());
+    }
+
     #[test]
     fn test_encode_cframe_with_background_extension() {
         let bytes = encode_cframe(2, 1, "ab\n", &[1, 2, 3, 4, 5, 6], Some(&[7, 8, 9, 10, 11, 12]));
@@ -196,4 +1380,112 @@ mod tests {
         assert_eq!(bytes[8..12], [b'a', 1, 2, 3]);
         assert_eq!(bytes[16], CFRAME_EXT_FLAG_HAS_BG);
     }
+
+    #[test]
+    fn test_braille_cells_pack_2x4_pixels_into_dots() {
+        // Left cell all white, right cell with only its top-left pixel lit
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(4, 4, |x, y| if x < 2 || (x, y) == (2, 0) {image::Rgb([255; 3])} else {image::Rgb([0; 3])}));
+        let options = ConversionOptions {columns: Some(2), font_ratio: 0.5, luminance: 0, ..ConversionOptions::default()}.with_char_mode(CharMode::Braille);
+        let frame = image_to_frame(&image, &options).unwrap();
+        assert_eq!((frame.text(), frame.width(), frame.height()), ("\u{28ff}\u{2801}\n", 2, 1));
+        assert_eq!(frame.rgb(), [255, 255, 255, 32, 32, 32]);
+        assert_eq!(frame.cell(1, 0).unwrap().ch, '\u{2801}');
+        assert_eq!(Previewer::new(&image).preview(&options).unwrap(), &frame);
+
+        // Mid gray dithers to half the dots; the cframe stores the patterns and flags them
+        let gray = image_to_frame(&DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 4, image::Rgb([128; 3]))), &options.with_columns(1)).unwrap();
+        assert_eq!((gray.text.chars().next().unwrap() as u32 - BRAILLE_BASE).count_ones(), 4);
+        let bytes = frame.cframe_bytes();
+        assert_eq!((bytes[8], bytes[12], bytes.len()), (0xff, 0x01, 8 + 2 * 4 + 1));
+        assert_eq!(bytes[16], CFRAME_EXT_FLAG_BRAILLE);
+    }
+
+    #[test]
+    fn test_block_cells_carry_two_colors() {
+        let (yellow, blue, white, black) = (image::Rgb([200, 200, 0]), image::Rgb([0, 0, 200]), image::Rgb([255; 3]), image::Rgb([0; 3]));
+        // Sized to the dot grid so no pixels are resampled: left cells yellow over blue, right cells one
+        // white pixel top-right over black
+        let pixels = |columns: u32| DynamicImage::ImageRgb8(RgbImage::from_fn(2 * columns, 2, |x, y| match (x / columns, x % columns, y) {
+            (0, _, 0) => yellow,
+            (0, _, _) => blue,
+            (_, dx, 0) if dx == columns - 1 => white,
+            _ => black,
+        }));
+        let options = ConversionOptions {columns: Some(2), font_ratio: 0.5, luminance: 10, ..ConversionOptions::default()};
+        let half = image_to_frame(&pixels(1), &options.clone().with_char_mode(CharMode::HalfBlock)).unwrap();
+        assert_eq!((half.text(), half.rgb(), half.bg_rgb()), ("▀▀\n", &[200, 200, 0, 255, 255, 255][..], Some(&[0, 0, 200, 0, 0, 0][..])));
+
+        let quarter = image_to_frame(&pixels(2), &options.clone().with_char_mode(CharMode::QuarterBlock)).unwrap();
+        assert_eq!((quarter.text(), quarter.rgb(), quarter.bg_rgb()), ("▀▝\n", &[200, 200, 0, 255, 255, 255][..], Some(&[0, 0, 200, 0, 0, 0][..])));
+        // Block elements are stored below 0x20 in the .cframe, with a background layer
+        let bytes = quarter.cframe_bytes();
+        assert_eq!((bytes[8], bytes[12], bytes[bytes.len() - 7]), (0x00, 0x1d, CFRAME_EXT_FLAG_HAS_BG | CFRAME_EXT_FLAG_BLOCKS));
+
+        // Sextants split each cell into 2×3; the .cframe stores their masks
+        let tall = DynamicImage::ImageRgb8(RgbImage::from_fn(4, 3, |x, y| match (x, y) {
+            (0..=1, 0) => yellow,
+            (0..=1, _) => blue,
+            (3, 0) => white,
+            _ => black,
+        }));
+        let sextant = image_to_frame(&tall, &options.with_char_mode(CharMode::Sextant)).unwrap();
+        assert_eq!((sextant.text(), sextant.rgb(), sextant.bg_rgb()), ("\u{1fb02}\u{1fb01}\n", &[200, 200, 0, 255, 255, 255][..], Some(&[0, 0, 200, 0, 0, 0][..])));
+        let bytes = sextant.cframe_bytes();
+        assert_eq!((bytes[8], bytes[12], bytes[bytes.len() - 7]), (0b11, 0b10, CFRAME_EXT_FLAG_HAS_BG | CFRAME_EXT_FLAG_SEXTANTS));
+        #[cfg(feature = "cli")]
+        assert_eq!(crate::convert::cframe_bytes_to_frame_data(&bytes, "sextant").unwrap().ascii_text, sextant.text);
+        assert!((0..64).all(|mask| sextant_mask(sextant_char(mask)) == Some(mask)));
+        assert_eq!((sextant_char(0b01_0101), sextant_char(0b10_1010), sextant_char(0b11_1111)), ('▌', '▐', '█'));
+    }
+
+    #[test]
+    fn test_cp437_codepage_restricts_cells_and_stores_cp437_bytes() {
+        // Braille patterns become the shade of as many dots
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(4, 4, |x, y| if x < 2 || (x, y) == (2, 0) {image::Rgb([255; 3])} else {image::Rgb([0; 3])}));
+        let braille = ConversionOptions {columns: Some(2), font_ratio: 0.5, luminance: 0, ..ConversionOptions::default()}.with_char_mode(CharMode::Braille).with_codepage(Codepage::Cp437);
+        let frame = image_to_frame(&image, &braille).unwrap();
+        assert_eq!(frame.text(), "█░\n");
+        assert_eq!(Previewer::new(&image).preview(&braille).unwrap(), &frame);
+
+        // A ramp of CP437 shades maps luminance to them like any other ramp
+        let ramp = ConversionOptions {ascii_chars: crate::codepage::CP437_RAMP.into(), ..options()}.with_codepage(Codepage::Cp437);
+        let shades = image_to_frame(&gradient_image(64, 8), &ramp).unwrap();
+        assert!(shades.text.chars().all(|ch| ch == '\n' || crate::codepage::CP437_RAMP.contains(ch)));
+        assert!(shades.text.contains('░') && shades.text.contains('▓'));
+
+        // Box drawing fits neither Latin-1 nor the block elements; the cframe flags CP437 bytes
+        let boxed = AsciiFrame {text: "╔═╗\n".into(), width: 3, height: 1, rgb: vec![0; 9], bg_rgb: Vec::new()};
+        let bytes = boxed.cframe_bytes();
+        assert_eq!((bytes[8], bytes[12], bytes[16], bytes[20]), (0xc9, 0xcd, 0xbb, CFRAME_EXT_FLAG_CP437));
+        #[cfg(feature = "cli")]
+        assert_eq!(crate::convert::cframe_bytes_to_frame_data(&bytes, "boxed").unwrap().ascii_text, boxed.text);
+    }
+
+    #[test]
+    fn test_resized_frames_sample_cells_in_ascii_space() {
+        let frame = AsciiFrame {text: "abcd\nefgh\n".to_string(), width: 4, height: 2, rgb: (0..24).collect(), bg_rgb: Vec::new()};
+        let half = frame.resized(2, 1);
+        assert_eq!((half.text(), half.width(), half.height()), ("fh\n", 2, 1));
+        assert_eq!(half.rgb(), [15, 16, 17, 21, 22, 23]);
+        assert_eq!(half.bg_rgb(), None);
+        assert_eq!(frame.resized(8, 2).text(), "aabbccdd\neeffgghh\n");
+
+        assert!(matches!(frame.fit_within(4, 10), Cow::Borrowed(_)));
+        let fitted = frame.fit_within(10, 1);
+        assert_eq!((fitted.width(), fitted.height()), (2, 1));
+        assert_eq!(frame.fit_within(0, 0).text(), "g\n");
+    }
+
+    #[test]
+    fn test_frame_to_ansi_string_in_truecolor_and_256_colors() {
+        let mut frame = AsciiFrame {text: "ab\n".to_string(), width: 2, height: 1, rgb: vec![255, 0, 0, 128, 128, 128], bg_rgb: Vec::new()};
+        assert_eq!(frame_to_ansi_string(&frame, AnsiColor::TrueColor), "\x1b[38;2;255;0;0ma\x1b[38;2;128;128;128mb\x1b[0m\n");
+        assert_eq!(frame_to_ansi_string(&frame, AnsiColor::Xterm256), "\x1b[38;5;196ma\x1b[38;5;244mb\x1b[0m\n");
+
+        frame.bg_rgb = vec![0; 6];
+        assert!(frame_to_ansi_string(&frame, AnsiColor::Xterm256).starts_with("\x1b[38;5;196m\x1b[48;5;16ma"));
+        frame.rgb.clear();
+        assert_eq!(frame_to_ansi_string(&frame, AnsiColor::TrueColor), "ab\n");
+        assert_eq!((xterm_256_index([95, 135, 175]), xterm_256_index([8, 8, 8]), xterm_256_index([255, 255, 255])), (67, 232, 231));
+    }
 }
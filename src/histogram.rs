@@ -0,0 +1,183 @@
+//! Color histograms and dominant color palettes of converted frames.
+//!
+//! Conversions with color output fold every cell color of every frame (foreground, and background
+//! when fitted) into a [`ColorHistogram`] while the colors are in hand, and store its
+//! [`ColorPalette`] in the conversion report as `colors`. Players can theme their UI after a clip
+//! without reading its frames.
+//!
+//! Colors are binned at 4 bits per channel. Every bin keeps the sum of the colors that fell into
+//! it, so dominant colors are averages of real cell colors rather than bin corners.
+
+use serde::{Deserialize, Serialize};
+
+/// Bits kept per channel when binning
+const BITS: u32 = 4;
+const BINS: usize = 1 << (3 * BITS);
+/// Dominant colors closer than this (squared RGB distance) are merged into one
+const MIN_DISTANCE_SQUARED: u32 = 48 * 48;
+/// Number of dominant colors in a conversion's palette
+pub const PALETTE_SIZE: usize = 8;
+
+/// Cell color counts of one or more frames.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ColorHistogram {
+    counts: Vec<u64>,
+    sums: Vec<[u64; 3]>,
+}
+
+impl ColorHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count the colors of flat RGB triplets, such as [`AsciiFrame::rgb`](crate::AsciiFrame).
+    pub fn add_rgb(&mut self, rgb: &[u8]) {
+        if rgb.len() < 3 {
+            return;
+        }
+        if self.counts.is_empty() {
+            (self.counts, self.sums) = (vec![0; BINS], vec![[0; 3]; BINS]);
+        }
+        for cell in rgb.chunks_exact(3) {
+            let bin = bin_of([cell[0], cell[1], cell[2]]);
+            self.counts[bin] += 1;
+            for (sum, &channel) in self.sums[bin].iter_mut().zip(cell) {
+                *sum += channel as u64;
+            }
+        }
+    }
+
+    /// Add the counts of `other`.
+    pub fn merge(&mut self, other: &Self) {
+        self.merge_weighted(other, 1);
+    }
+
+    /// Add the counts of `other` `weight` times, for frames repeated in the output.
+    pub(crate) fn merge_weighted(&mut self, other: &Self, weight: u64) {
+        if other.counts.is_empty() || weight == 0 {
+            return;
+        }
+        if self.counts.is_empty() {
+            (self.counts, self.sums) = (vec![0; BINS], vec![[0; 3]; BINS]);
+        }
+        for bin in 0..BINS {
+            self.counts[bin] += other.counts[bin] * weight;
+            for channel in 0..3 {
+                self.sums[bin][channel] += other.sums[bin][channel] * weight;
+            }
+        }
+    }
+
+    /// Number of colors counted.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    /// Mean of every color counted.
+    pub fn average(&self) -> Option<[u8; 3]> {
+        let total = self.total();
+        (total > 0).then(|| std::array::from_fn(|channel| (self.sums.iter().map(|sum| sum[channel]).sum::<u64>() / total) as u8))
+    }
+
+    /// Up to `count` distinct colors covering the histogram, most common first. Every counted color
+    /// is attributed to its nearest dominant color, so the shares add up to 1.
+    pub fn dominant_colors(&self, count: usize) -> Vec<DominantColor> {
+        let total = self.total();
+        if total == 0 || count == 0 {
+            return Vec::new();
+        }
+        let mean = |bin: usize| std::array::from_fn::<u8, 3, _>(|channel| (self.sums[bin][channel] / self.counts[bin]) as u8);
+        let mut bins: Vec<usize> = (0..BINS).filter(|&bin| self.counts[bin] > 0).collect();
+        bins.sort_by_key(|&bin| std::cmp::Reverse(self.counts[bin]));
+
+        // The most common bins that differ visibly from every color picked before them
+        let mut picked: Vec<[u8; 3]> = Vec::with_capacity(count);
+        for &bin in &bins {
+            let color = mean(bin);
+            if picked.iter().all(|&other| distance_squared(color, other) >= MIN_DISTANCE_SQUARED) {
+                picked.push(color);
+                if picked.len() == count {
+                    break;
+                }
+            }
+        }
+
+        // One assignment step: every bin joins its nearest pick, which moves to their mean
+        let mut clusters = vec![(0u64, [0u64; 3]); picked.len()];
+        for &bin in &bins {
+            let nearest = (0..picked.len()).min_by_key(|&index| distance_squared(mean(bin), picked[index])).unwrap_or(0);
+            clusters[nearest].0 += self.counts[bin];
+            for channel in 0..3 {
+                clusters[nearest].1[channel] += self.sums[bin][channel];
+            }
+        }
+        let mut dominant: Vec<DominantColor> = clusters.into_iter().filter(|(cells, _)| *cells > 0).map(|(cells, sums)| DominantColor {rgb: sums.map(|sum| (sum / cells) as u8), share: cells as f32 / total as f32}).collect();
+        dominant.sort_by(|a, b| b.share.total_cmp(&a.share));
+        dominant
+    }
+
+    /// The average and [`PALETTE_SIZE`] dominant colors, or `None` before any color was counted.
+    pub fn palette(&self) -> Option<ColorPalette> {
+        Some(ColorPalette {average: self.average()?, dominant: self.dominant_colors(PALETTE_SIZE)})
+    }
+}
+
+/// A summary of the colors of a conversion.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorPalette {
+    /// Mean of every cell color
+    pub average: [u8; 3],
+    /// The most common distinct colors, most common first
+    pub dominant: Vec<DominantColor>,
+}
+
+/// One color of a [`ColorPalette`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DominantColor {
+    pub rgb: [u8; 3],
+    /// Fraction of the counted colors closest to this one, 0-1
+    pub share: f32,
+}
+
+impl DominantColor {
+    /// The color as `#rrggbb`.
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.rgb[0], self.rgb[1], self.rgb[2])
+    }
+}
+
+fn bin_of([r, g, b]: [u8; 3]) -> usize {
+    let shift = 8 - BITS;
+    ((r as usize >> shift) << (2 * BITS)) | ((g as usize >> shift) << BITS) | (b as usize >> shift)
+}
+
+fn distance_squared(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b).map(|(&x, y)| (x as i32 - y as i32).pow(2) as u32).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dominant_colors_merge_near_shades_and_share_everything() {
+        let mut histogram = ColorHistogram::new();
+        assert_eq!(histogram.palette(), None);
+        // Three reds in neighbouring bins, then a blue half as common
+        histogram.add_rgb(&[200, 10, 10, 210, 20, 10, 220, 10, 20, 200, 10, 10]);
+        let mut blue = ColorHistogram::new();
+        blue.add_rgb(&[0, 0, 250]);
+        histogram.merge_weighted(&blue, 2);
+
+        let dominant = histogram.dominant_colors(4);
+        assert_eq!(dominant.len(), 2);
+        assert_eq!((dominant[0].rgb, dominant[0].share), ([207, 12, 12], 4.0 / 6.0));
+        assert_eq!((dominant[1].hex(), dominant[1].share), ("#0000fa".to_string(), 2.0 / 6.0));
+        assert_eq!(histogram.total(), 6);
+        assert_eq!(histogram.palette().unwrap().average, [138, 8, 91]);
+    }
+}
@@ -0,0 +1,379 @@
+//! Stream frames to LED matrices over the network: DDP and E1.31 (sACN).
+//!
+//! WLED controllers and rpi-rgb-led-matrix bridges such as `ledcat`, xLights or FPP take pixels as
+//! UDP packets. [`LedSink`] turns each frame into one RGB value per LED and sends it in either
+//! protocol: the cell colors scaled onto the matrix ([`LedSource::Cells`]), where a cell lights in
+//! its foreground color unless it is blank, or the frame rendered with its glyphs and scaled down
+//! ([`LedSource::Rendered`]). Rows run left to right, or back and forth on serpentine wiring.
+//!
+//! Matrices draw a lot of current at full white, so [`LedOptions::brightness`] scales every LED and
+//! [`LedOptions::max_average`] dims whole frames whose average level would go over a budget. The
+//! sink sends at most [`LedOptions::fps`] frames a second, waiting between frames that come faster.
+//!
+//! ```no_run
+//! use cascii::led_matrix::{LedOptions, LedProtocol, LedSink};
+//! use cascii::stream::StreamOptions;
+//! use cascii::{AsciiConverter, ConversionOptions};
+//!
+//! # fn main() -> cascii::Result<()> {
+//! let options = LedOptions::default().with_protocol(LedProtocol::Ddp).with_size(64, 32).with_brightness(0.5);
+//! let sink = LedSink::connect("wled.local:4048", &options)?;
+//! AsciiConverter::new().stream_device("/dev/video0", &StreamOptions::default(), &ConversionOptions::default().with_columns(64), sink)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use image::imageops::FilterType;
+use image::{Rgb, RgbImage};
+
+use crate::error::{Context, Error, Result};
+use crate::render::{build_glyph_atlas, render_frame_with_atlas, GlyphAtlas};
+use crate::stream::StreamSink;
+use crate::{AsciiFrame, CancelToken};
+
+/// UDP port DDP receivers listen on
+pub const DDP_PORT: u16 = 4048;
+/// UDP port E1.31 receivers listen on
+pub const E131_PORT: u16 = 5568;
+/// Pixel bytes in one DDP packet, 480 RGB pixels as WLED sends them
+const DDP_CHUNK: usize = 1440;
+/// RGB pixels in one E1.31 universe, 510 of its 512 channels
+const E131_UNIVERSE_PIXELS: usize = 170;
+/// Identifies this sender to E1.31 receivers, which track sequence numbers per source
+const E131_CID: [u8; 16] = *b"cascii-led-sink\0";
+
+/// The protocol [`LedSink`] speaks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LedProtocol {
+    /// Distributed Display Protocol: 480 pixels a packet, the last one pushing the frame to the LEDs
+    #[default]
+    Ddp,
+    /// E1.31 (streaming ACN): 170 pixels a DMX universe, in consecutive universes
+    E131,
+}
+
+impl LedProtocol {
+    /// The UDP port receivers of the protocol listen on
+    pub fn default_port(self) -> u16 {
+        match self {
+            Self::Ddp => DDP_PORT,
+            Self::E131 => E131_PORT,
+        }
+    }
+}
+
+/// What the LEDs show of a frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LedSource {
+    /// One color per cell: the foreground of cells with a character, the background of blank
+    /// ones, scaled to the matrix
+    #[default]
+    Cells,
+    /// The frame drawn with its glyphs, as ASCII videos are, and scaled down to the matrix
+    Rendered,
+}
+
+/// How frames become LED colors and how fast they are sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LedOptions {
+    pub protocol: LedProtocol,
+    pub source: LedSource,
+    /// Matrix size in LEDs
+    pub width: u32,
+    pub height: u32,
+    /// Odd rows are wired right to left
+    pub serpentine: bool,
+    /// Scale of every LED level, from 0 (dark) to 1
+    pub brightness: f32,
+    /// Highest average level of a frame's LEDs, from 0 to 1 (full white); brighter frames are
+    /// dimmed as a whole. `None` sends frames as bright as they are.
+    pub max_average: Option<f32>,
+    /// Frames sent a second at most; `None` sends them as they come
+    pub fps: Option<u32>,
+    /// First E1.31 universe (1 to 63999)
+    pub universe: u16,
+    /// Glyph size in pixels for [`LedSource::Rendered`]
+    pub font_size: f32,
+}
+
+impl Default for LedOptions {
+    fn default() -> Self {
+        Self {protocol: LedProtocol::default(), source: LedSource::default(), width: 64, height: 32, serpentine: false, brightness: 1.0, max_average: None, fps: None, universe: 1, font_size: 8.0}
+    }
+}
+
+impl LedOptions {
+    pub fn with_protocol(mut self, protocol: LedProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    pub fn with_source(mut self, source: LedSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        (self.width, self.height) = (width, height);
+        self
+    }
+
+    pub fn with_serpentine(mut self, serpentine: bool) -> Self {
+        self.serpentine = serpentine;
+        self
+    }
+
+    pub fn with_brightness(mut self, brightness: f32) -> Self {
+        self.brightness = brightness;
+        self
+    }
+
+    pub fn with_max_average(mut self, max_average: Option<f32>) -> Self {
+        self.max_average = max_average;
+        self
+    }
+
+    pub fn with_fps(mut self, fps: Option<u32>) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    pub fn with_universe(mut self, universe: u16) -> Self {
+        self.universe = universe;
+        self
+    }
+
+    pub fn with_font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidOptions(format!("LED matrix size must be positive, got {}x{}", self.width, self.height)));
+        }
+        if !(0.0..=1.0).contains(&self.brightness) {
+            return Err(Error::InvalidOptions(format!("LED brightness must be from 0 to 1, got {}", self.brightness)));
+        }
+        if let Some(max_average) = self.max_average.filter(|&max_average| !(max_average > 0.0 && max_average <= 1.0)) {
+            return Err(Error::InvalidOptions(format!("LED average limit must be above 0 and at most 1, got {}", max_average)));
+        }
+        let last_universe = self.universe as usize + (self.width as usize * self.height as usize).div_ceil(E131_UNIVERSE_PIXELS) - 1;
+        if self.protocol == LedProtocol::E131 && (self.universe == 0 || last_universe > 63999) {
+            return Err(Error::InvalidOptions(format!("E1.31 universes {} to {} are outside 1 to 63999", self.universe, last_universe)));
+        }
+        Ok(())
+    }
+}
+
+/// The LED colors of `frame`, 3 bytes per LED in wiring order, dimmed as `options` say
+pub fn led_pixels(frame: &AsciiFrame, options: &LedOptions) -> Result<Vec<u8>> {
+    options.validate()?;
+    pixels_with_atlas(frame, options, &mut None)
+}
+
+fn pixels_with_atlas(frame: &AsciiFrame, options: &LedOptions, atlas: &mut Option<GlyphAtlas>) -> Result<Vec<u8>> {
+    let image = match options.source {
+        LedSource::Cells => RgbImage::from_fn(frame.width.max(1), frame.height.max(1), |column, row| {
+            let cell = frame.cell(column, row);
+            Rgb(match cell {
+                Some(cell) if !cell.ch.is_whitespace() => cell.fg.unwrap_or([255; 3]),
+                Some(cell) => cell.bg.unwrap_or_default(),
+                None => [0; 3],
+            })
+        }),
+        LedSource::Rendered => {
+            let atlas = match atlas {
+                Some(atlas) => atlas,
+                None => atlas.insert(build_glyph_atlas(options.font_size)?),
+            };
+            atlas.add_chars(&frame.text)?;
+            render_frame_with_atlas(frame, atlas, true)?
+        }
+    };
+    let image = if image.dimensions() == (options.width, options.height) {image} else {image::imageops::resize(&image, options.width, options.height, FilterType::Triangle)};
+
+    let mut scale = options.brightness;
+    if let Some(max_average) = options.max_average {
+        let average = image.as_raw().iter().map(|&level| level as f32).sum::<f32>() * scale / (image.as_raw().len() as f32 * 255.0);
+        if average > max_average {
+            scale *= max_average / average;
+        }
+    }
+    let mut pixels = Vec::with_capacity(image.as_raw().len());
+    for (row, line) in image.as_raw().chunks_exact(options.width as usize * 3).enumerate() {
+        let start = pixels.len();
+        pixels.extend(line.iter().map(|&level| (level as f32 * scale).round() as u8));
+        if options.serpentine && row % 2 == 1 {
+            // Reverse the order of the LEDs, keeping each one's bytes in order
+            let reversed: Vec<u8> = pixels[start..].chunks_exact(3).rev().flatten().copied().collect();
+            pixels[start..].copy_from_slice(&reversed);
+        }
+    }
+    Ok(pixels)
+}
+
+/// DDP packets carrying `pixels`: a 10-byte header with the data offset, then up to 480 RGB pixels.
+/// The last packet has the push flag, which shows the frame. `sequence` runs from 1 to 15.
+pub fn ddp_packets(pixels: &[u8], sequence: u8) -> Vec<Vec<u8>> {
+    let chunks: Vec<&[u8]> = pixels.chunks(DDP_CHUNK).collect();
+    chunks.iter().enumerate().map(|(index, chunk)| {
+        // Version 1, push on the last packet; RGB with 8 bits a channel; the default output
+        let flags = 0x40 | (index + 1 == chunks.len()) as u8;
+        let mut packet = vec![flags, sequence & 0x0F, 0x0B, 0x01];
+        packet.extend(((index * DDP_CHUNK) as u32).to_be_bytes());
+        packet.extend((chunk.len() as u16).to_be_bytes());
+        packet.extend_from_slice(chunk);
+        packet
+    }).collect()
+}
+
+/// E1.31 data packets carrying `pixels`, 170 to a universe from `universe` on
+pub fn e131_packets(pixels: &[u8], universe: u16, sequence: u8) -> Vec<Vec<u8>> {
+    pixels.chunks(E131_UNIVERSE_PIXELS * 3).enumerate().map(|(index, channels)| {
+        let length = 126 + channels.len();
+        // Each layer starts with its length from there on, under the 0x7 flags nibble
+        let flags_length = |offset: usize| (0x7000 | (length - offset) as u16).to_be_bytes();
+        let mut packet = Vec::with_capacity(length);
+        // Root layer
+        packet.extend([0x00, 0x10, 0x00, 0x00]);
+        packet.extend(b"ASC-E1.17\0\0\0");
+        packet.extend(flags_length(16));
+        packet.extend(4u32.to_be_bytes());
+        packet.extend(E131_CID);
+        // Framing layer: source name, priority 100, no sync universe, sequence, options
+        packet.extend(flags_length(38));
+        packet.extend(2u32.to_be_bytes());
+        let mut source_name = [0u8; 64];
+        source_name[..6].copy_from_slice(b"cascii");
+        packet.extend(source_name);
+        packet.extend([100, 0, 0, sequence, 0]);
+        packet.extend((universe + index as u16).to_be_bytes());
+        // DMP layer: the DMX start code, then the channels
+        packet.extend(flags_length(115));
+        packet.extend([0x02, 0xA1, 0x00, 0x00, 0x00, 0x01]);
+        packet.extend((channels.len() as u16 + 1).to_be_bytes());
+        packet.push(0);
+        packet.extend_from_slice(channels);
+        packet
+    }).collect()
+}
+
+/// Sends frames to an LED controller, paced to [`LedOptions::fps`]. Also a [`StreamSink`], so
+/// [`AsciiConverter::stream_device`](crate::AsciiConverter::stream_device) can drive it live.
+pub struct LedSink {
+    socket: UdpSocket,
+    target: SocketAddr,
+    options: LedOptions,
+    sequence: u8,
+    atlas: Option<GlyphAtlas>,
+    next_due: Option<Instant>,
+}
+
+impl LedSink {
+    /// A sink sending to `target`, such as `"192.168.1.50:4048"`
+    pub fn connect(target: impl ToSocketAddrs, options: &LedOptions) -> Result<Self> {
+        options.validate()?;
+        let target = target.to_socket_addrs().context("resolving the LED controller address")?.next().ok_or_else(|| Error::InvalidOptions("the LED controller address resolves to nothing".into()))?;
+        let socket = UdpSocket::bind(if target.is_ipv4() {"0.0.0.0:0"} else {"[::]:0"}).context("opening a UDP socket")?;
+        // Not connected, so a controller that is down for a moment does not end the stream with
+        // the port-unreachable errors a connected socket reports
+        Ok(Self {socket, target, options: options.clone(), sequence: 0, atlas: None, next_due: None})
+    }
+
+    /// Send `frame`, after waiting out the rest of the previous frame's time slot
+    pub fn send(&mut self, frame: &AsciiFrame) -> Result<()> {
+        let pixels = pixels_with_atlas(frame, &self.options, &mut self.atlas)?;
+        if let Some(fps) = self.options.fps.filter(|&fps| fps > 0) {
+            let now = Instant::now();
+            let due = self.next_due.unwrap_or(now);
+            if due > now {
+                std::thread::sleep(due - now);
+            }
+            // Deadlines follow on from each other so the rate does not drift, unless sending fell behind
+            self.next_due = Some(due.max(now) + Duration::from_secs_f64(1.0 / fps as f64));
+        }
+        let packets = match self.options.protocol {
+            LedProtocol::Ddp => {
+                self.sequence = self.sequence % 15 + 1;
+                ddp_packets(&pixels, self.sequence)
+            }
+            LedProtocol::E131 => {
+                self.sequence = self.sequence.wrapping_add(1);
+                e131_packets(&pixels, self.options.universe, self.sequence)
+            }
+        };
+        for packet in packets {
+            self.socket.send_to(&packet, self.target).with_context(|| format!("sending LED packets to {}", self.target))?;
+        }
+        Ok(())
+    }
+}
+
+impl StreamSink for LedSink {
+    fn deliver(&mut self, frame: AsciiFrame) -> bool {
+        self.send(&frame).is_ok()
+    }
+}
+
+/// Send the frames of a converted directory or `.casc` archive to `target` at `options.fps` (or
+/// the rate in its `details.toml`, else [`DEFAULT_PLAY_FPS`](crate::player::DEFAULT_PLAY_FPS)),
+/// over and over with `repeat`, until the clip ends or `cancel` fires. Returns the frames sent.
+pub fn stream_frame_dir(input: impl AsRef<Path>, target: impl ToSocketAddrs, options: &LedOptions, repeat: bool, cancel: Option<&CancelToken>) -> Result<usize> {
+    let input = input.as_ref();
+    let sequence = crate::reader::FrameSequence::open(input)?;
+    let fps = options.fps.filter(|&fps| fps > 0).or_else(|| crate::loop_detect::recorded_fps(input)).unwrap_or(crate::player::DEFAULT_PLAY_FPS);
+    let mut sink = LedSink::connect(target, &options.clone().with_fps(Some(fps)))?;
+    let mut sent = 0;
+    loop {
+        for frame in sequence.reader() {
+            if cancel.is_some_and(CancelToken::is_cancelled) {
+                return Ok(sent);
+            }
+            sink.send(&frame?)?;
+            sent += 1;
+        }
+        if !repeat || sequence.is_empty() {
+            return Ok(sent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn led_sink_sends_dimmed_serpentine_pixels() -> Result<()> {
+        // A red `#` and a blank cell on blue, over a green `@` and a blank cell without a background
+        let frame = AsciiFrame {text: "# \n@ \n".into(), width: 2, height: 2, rgb: [[200, 0, 0], [9, 9, 9], [0, 100, 0], [9, 9, 9]].concat(), bg_rgb: [[0, 0, 50], [0, 0, 50], [0; 3], [0; 3]].concat()};
+        let options = LedOptions::default().with_size(2, 2).with_serpentine(true).with_brightness(0.5);
+        assert_eq!(led_pixels(&frame, &options)?, [100, 0, 0, 0, 0, 25, 0, 0, 0, 0, 50, 0]);
+        // An average level of 350/12 over a budget of 25.5: everything is scaled by 306/350
+        let limited = led_pixels(&frame, &options.clone().with_serpentine(false).with_brightness(1.0).with_max_average(Some(0.1)))?;
+        assert_eq!(limited, [175, 0, 0, 0, 0, 44, 0, 87, 0, 0, 0, 0]);
+        assert!(led_pixels(&frame, &options.clone().with_brightness(1.5)).is_err());
+        assert!(led_pixels(&frame, &options.clone().with_max_average(Some(0.0))).is_err());
+
+        let receiver = UdpSocket::bind("127.0.0.1:0")?;
+        receiver.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut sink = LedSink::connect(receiver.local_addr()?, &options)?;
+        assert!(sink.deliver(frame.clone()));
+        let mut buffer = [0u8; 2048];
+        let length = receiver.recv(&mut buffer)?;
+        assert_eq!(&buffer[..length], &[0x41, 1, 0x0B, 1, 0, 0, 0, 0, 0, 12, 100, 0, 0, 0, 0, 25, 0, 0, 0, 0, 50, 0]);
+
+        let pixels = vec![7u8; 500 * 3];
+        let packets = ddp_packets(&pixels, 3);
+        assert_eq!((packets.len(), packets[0][0], packets[1][0], &packets[1][4..10]), (2, 0x40, 0x41, &[0, 0, 0x05, 0xA0, 0, 60][..]));
+        let packets = e131_packets(&pixels, 7, 9);
+        assert_eq!(packets.iter().map(Vec::len).collect::<Vec<_>>(), [126 + 510, 126 + 510, 126 + 480]);
+        assert_eq!((&packets[2][4..16], &packets[2][16..18], packets[2][111], &packets[2][113..115], &packets[2][123..126]), (&b"ASC-E1.17\0\0\0"[..], &[0x72, 0x4E][..], 9, &[0, 9][..], &[1, 0xE1, 0][..]));
+        assert!(led_pixels(&frame, &LedOptions::default().with_protocol(LedProtocol::E131).with_universe(63999)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+//! Advisory lock on an output directory.
+//!
+//! While a conversion or sequence edit writes into a directory it holds an [`OutputLock`]: an
+//! operating-system lock (`flock` on Unix, `LockFileEx` on Windows) on a `.cascii.lock` file
+//! containing the owner's PID, which is removed when the lock is dropped. A second cascii process
+//! trying to lock the same directory gets an error naming the owner instead of interleaving its
+//! frames with the first. The system releases the lock when its process exits, however it exits,
+//! so a lock file left behind by a crash or `kill -9` is stale: the next process locks it and
+//! writes its own PID.
+//!
+//! The lock is advisory: it only keeps out other processes that also take it.
+
+use crate::error::{Context, Error, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the lock file inside a locked directory.
+pub const LOCK_FILE_NAME: &str = ".cascii.lock";
+
+/// How long to wait for the owner of a lock to write its PID before reporting it without one
+const SETTLE: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Held lock on a directory; released on drop.
+#[derive(Debug)]
+pub struct OutputLock {
+    path: PathBuf,
+    stale_pid: Option<u32>,
+    // Holds the system lock until dropped
+    _file: File,
+}
+
+impl OutputLock {
+    /// Lock `dir`, which must exist. Fails if another running process holds the lock.
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        let path = dir.join(LOCK_FILE_NAME);
+        loop {
+            let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path).with_context(|| format!("opening lock file {}", path.display()))?;
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => {
+                    // The owner may be between locking the file and writing its PID; give it a moment.
+                    let owner = read_owner(&path).or_else(|| {
+                        std::thread::sleep(SETTLE);
+                        read_owner(&path)
+                    });
+                    let owner = owner.map_or_else(|| "another cascii process".to_string(), |pid| format!("another cascii process (pid {})", pid));
+                    return Err(Error::Other(format!("{} is in use by {}; wait for it to finish", dir.display(), owner)));
+                }
+                Err(TryLockError::Error(err)) => return Err(err).with_context(|| format!("locking {}", path.display())),
+            }
+            // A previous owner may have removed the file between our opening and locking it; that
+            // file is no longer the lock, so start over with whatever is at the path now
+            if !is_same_file(&file, &path) {
+                continue;
+            }
+            let mut previous = String::new();
+            file.read_to_string(&mut previous).with_context(|| format!("reading lock file {}", path.display()))?;
+            // Whoever wrote a PID here exited without removing the file
+            let stale_pid = (!previous.trim().is_empty()).then(|| previous.trim().parse().unwrap_or(0));
+            file.set_len(0).and_then(|()| file.rewind()).and_then(|()| writeln!(file, "{}", std::process::id())).with_context(|| format!("writing lock file {}", path.display()))?;
+            return Ok(Self {path, stale_pid, _file: file});
+        }
+    }
+
+    /// Path of the lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// PID recorded in a stale lock this one replaced (`0` if the stale file was unreadable).
+    pub fn stale_pid(&self) -> Option<u32> {
+        self.stale_pid
+    }
+}
+
+impl Drop for OutputLock {
+    fn drop(&mut self) {
+        // Only the file naming this process is ours to remove; the system lock goes with the handle
+        if read_owner(&self.path) == Some(std::process::id()) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn read_owner(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether `file` is still the file at `path`
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(open), Ok(current)) => (open.dev(), open.ino()) == (current.dev(), current.ino()),
+        _ => false,
+    }
+}
+
+/// Whether `file` is still the file at `path`; Windows can't open a file that is being deleted,
+/// so a lock file that was removed is simply gone
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_lock_is_refused_until_release() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = OutputLock::acquire(dir.path()).unwrap();
+        assert_eq!(fs::read_to_string(lock.path()).unwrap().trim(), std::process::id().to_string());
+        let err = OutputLock::acquire(dir.path()).unwrap_err().to_string();
+        assert!(err.contains(&format!("pid {}", std::process::id())), "{}", err);
+        drop(lock);
+        assert!(!dir.path().join(LOCK_FILE_NAME).exists());
+        assert!(OutputLock::acquire(dir.path()).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stale_lock_is_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        fs::write(dir.path().join(LOCK_FILE_NAME), format!("{}\n", dead_pid)).unwrap();
+        let lock = OutputLock::acquire(dir.path()).unwrap();
+        assert_eq!(lock.stale_pid(), Some(dead_pid));
+        assert_eq!(fs::read_to_string(lock.path()).unwrap().trim(), std::process::id().to_string());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        drop(lock);
+        assert_eq!(OutputLock::acquire(dir.path()).unwrap().stale_pid(), None);
+    }
+
+    #[test]
+    fn dropping_a_lock_leaves_a_file_naming_another_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = OutputLock::acquire(dir.path()).unwrap();
+        fs::write(lock.path(), "1\n").unwrap();
+        drop(lock);
+        assert_eq!(fs::read_to_string(dir.path().join(LOCK_FILE_NAME)).unwrap(), "1\n");
+    }
+}
@@ -1,19 +1,38 @@
+use crate::backup::Backup;
+use crate::cell_filter::LuminanceWeights;
 use crate::convert::read_cframe_to_frame_data;
-use anyhow::{anyhow, Context, Result};
+use crate::frame::CellBytes;
+use crate::terminal::{self, TerminalFit};
+use crate::{frame_to_ansi_string, render, AnsiColor, AsciiFrame, FrameReader};
+use crate::error::{Context, Error, Result};
 use dialoguer::Select;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::hash_map::DefaultHasher;
 use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::hash::{Hash, Hasher};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
 const DEFAULT_ASCII_RAMP: &str = " .'`^,:;Il!i><~+_-?][}{1)(|/tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$";
 const QUICK_SAMPLE_CELLS: usize = 64;
 const QUICK_THRESHOLD_MARGIN: f32 = 0.03;
 const FRAME_THRESHOLD_MARGIN: f32 = 0.08;
+/// Side of the square grid cell luminances are averaged onto before a perceptual hash's DCT
+pub(crate) const HASH_GRID: usize = 32;
+/// Side of the block of lowest DCT frequencies whose signs make up a perceptual hash
+const HASH_FREQUENCIES: usize = 8;
+/// Bits of a perceptual hash
+pub const HASH_BITS: u32 = (HASH_FREQUENCIES * HASH_FREQUENCIES) as u32;
+/// Frame rate of loop previews when the directory's `details.toml` records none
+pub const DEFAULT_PREVIEW_FPS: u32 = 24;
+/// Widest loop preview, in cells; wider frames are scaled down in ASCII space
+pub const PREVIEW_COLUMNS: u32 = 80;
+/// Font size of preview GIFs, small enough that they write in a moment
+pub const PREVIEW_FONT_SIZE: f32 = 6.0;
+/// Times a terminal preview plays its loop
+const PREVIEW_PLAYS: usize = 3;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -21,6 +40,17 @@ pub enum LoopMatchMode {
     ExactText,
     VisualText,
     VisualTextAndColor,
+    /// Frames match when their [`perceptual hashes`](frame_hashes) differ in at most
+    /// [`LoopDetectionOptions::max_hash_distance`] bits, so compression noise that changes a few
+    /// cells doesn't break a loop
+    PerceptualHash,
+}
+
+impl LoopMatchMode {
+    /// Whether frames match by degree rather than all or nothing
+    fn is_graded(self) -> bool {
+        matches!(self, Self::VisualText | Self::VisualTextAndColor)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -30,11 +60,36 @@ pub struct LoopDetectionOptions {
     pub validation_window: usize,
     pub similarity_threshold: f32,
     pub ascii_ramp: String,
+    /// Most bits two frames' perceptual hashes may differ in for [`LoopMatchMode::PerceptualHash`]
+    /// to treat them as the same frame, out of [`HASH_BITS`]
+    pub max_hash_distance: u32,
+    /// Back up the frames before repeating a loop, so [`crate::backup::undo`] can restore them
+    #[serde(default = "default_backup")]
+    pub backup: bool,
+}
+
+fn default_backup() -> bool {
+    true
 }
 
 impl Default for LoopDetectionOptions {
     fn default() -> Self {
-        Self {mode: LoopMatchMode::VisualText, minimum_distance: 24, validation_window: 8, similarity_threshold: 0.93, ascii_ramp: DEFAULT_ASCII_RAMP.to_string()}
+        Self {mode: LoopMatchMode::VisualText, minimum_distance: 24, validation_window: 8, similarity_threshold: 0.93, ascii_ramp: DEFAULT_ASCII_RAMP.to_string(), max_hash_distance: 8, backup: true}
+    }
+}
+
+/// Perceptual hash of one frame, from [`frame_hashes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameHash {
+    /// Number of the frame's `frame_*` file
+    pub frame: usize,
+    pub hash: u64,
+}
+
+impl FrameHash {
+    /// Bits in which two hashes differ; near-identical frames differ in few
+    pub fn distance(self, other: FrameHash) -> u32 {
+        hash_distance(self.hash, other.hash)
     }
 }
 
@@ -62,7 +117,7 @@ struct LoadedFrame {
     height: usize,
     glyphs: Vec<u8>,
     exact_text: Vec<u8>,
-    exact_hash: u64,
+    hash: u64,
     foreground: Option<Vec<u8>>,
     background: Option<Vec<u8>>,
 }
@@ -83,15 +138,15 @@ struct SequenceMetrics {
 
 struct FrameComparisonCache<'a> {
     frames: &'a [LoadedFrame],
-    mode: LoopMatchMode,
+    options: &'a LoopDetectionOptions,
     ramp: &'a RampLookup,
     quick: HashMap<usize, FrameMetrics>,
     full: HashMap<usize, FrameMetrics>,
 }
 
 impl<'a> FrameComparisonCache<'a> {
-    fn new(frames: &'a [LoadedFrame], mode: LoopMatchMode, ramp: &'a RampLookup) -> Self {
-        Self {frames, mode, ramp, quick: HashMap::new(), full: HashMap::new()}
+    fn new(frames: &'a [LoadedFrame], options: &'a LoopDetectionOptions, ramp: &'a RampLookup) -> Self {
+        Self {frames, options, ramp, quick: HashMap::new(), full: HashMap::new()}
     }
 
     fn compare(&mut self, left: usize, right: usize, quick: bool) -> FrameMetrics {
@@ -103,24 +158,24 @@ impl<'a> FrameComparisonCache<'a> {
             return *metrics;
         }
 
-        let computed = compare_frames(&self.frames[left], &self.frames[right], self.mode, self.ramp, quick);
+        let computed = compare_frames(&self.frames[left], &self.frames[right], self.options, self.ramp, quick);
         metrics.insert(index, computed);
         computed
     }
 }
 
-struct RampLookup {
+pub(crate) struct RampLookup {
     positions: [i16; 256],
     max_distance: f32,
 }
 
 impl RampLookup {
-    fn new(ramp: &str) -> Result<Self> {
+    pub(crate) fn new(ramp: &str) -> Result<Self> {
         if ramp.is_empty() {
-            return Err(anyhow!("ASCII ramp cannot be empty"));
+            return Err(Error::InvalidOptions("ASCII ramp cannot be empty".into()));
         }
         if !ramp.is_ascii() {
-            return Err(anyhow!("ASCII ramp must contain only ASCII characters"));
+            return Err(Error::InvalidOptions("ASCII ramp must contain only ASCII characters".into()));
         }
 
         let mut positions = [-1; 256];
@@ -144,24 +199,34 @@ impl RampLookup {
 
         (left_position - right_position).unsigned_abs() as f32 / self.max_distance
     }
+
+    /// How much of a cell `glyph` covers, from 0.0 for blank cells to 1.0 for the end of the ramp
+    /// and for glyphs the ramp doesn't know.
+    pub(crate) fn density(&self, glyph: u8) -> f32 {
+        match self.positions[glyph as usize] {
+            position if position >= 0 => position as f32 / self.max_distance,
+            _ if glyph == b' ' || glyph == 0 => 0.0,
+            _ => 1.0,
+        }
+    }
 }
 
 pub fn detect_frame_loops(directory: &Path, options: &LoopDetectionOptions) -> Result<Vec<LoopCandidate>> {
     validate_options(options)?;
-    let frames = load_frames(directory)?;
+    let ramp = RampLookup::new(&options.ascii_ramp)?;
+    let frames = load_frames(directory, &ramp)?;
     let window = options.validation_window;
 
     if frames.len() < options.minimum_distance + window {
         return Ok(Vec::new());
     }
 
-    let ramp = RampLookup::new(&options.ascii_ramp)?;
     let maximum_period = frames.len() - window;
 
     // Periods are scanned independently in parallel; pairs compared at one period recur only at periods dividing it,
     // so the per-period comparison caches lose little reuse.
     let candidates_per_period: Vec<Vec<LoopCandidate>> = (options.minimum_distance..maximum_period + 1).into_par_iter().map(|period| {
-        let mut comparison_cache = FrameComparisonCache::new(&frames, options.mode, &ramp);
+        let mut comparison_cache = FrameComparisonCache::new(&frames, options, &ramp);
         let mut candidates = Vec::new();
         let maximum_start = frames.len() - period - window;
         let mut matching_run = Vec::new();
@@ -184,7 +249,24 @@ pub fn detect_frame_loops(directory: &Path, options: &LoopDetectionOptions) -> R
     }).collect();
 
     let candidates = candidates_per_period.into_iter().flatten().collect();
-    Ok(remove_redundant_candidates(candidates, &frames, options.mode))
+    Ok(remove_redundant_candidates(candidates, &frames, options))
+}
+
+/// Perceptual hash of every frame in `directory` (`frame_*.txt`, `.cframe`, or both), with glyphs
+/// weighted by their position in `ascii_ramp`, for tools that find duplicates or loops themselves.
+///
+/// A hash is the signs of the lowest 8×8 DCT frequencies of the frame's cell luminances (glyph
+/// density, times the foreground luminance when the frame has colors) averaged onto a 32×32 grid.
+/// Frames differing only in a few noisy cells hash within a few bits of each other
+/// ([`FrameHash::distance`]); unrelated frames differ in about half of the [`HASH_BITS`].
+pub fn frame_hashes(directory: &Path, ascii_ramp: &str) -> Result<Vec<FrameHash>> {
+    let ramp = RampLookup::new(ascii_ramp)?;
+    Ok(load_frames(directory, &ramp)?.into_iter().map(|frame| FrameHash {frame: frame.number, hash: frame.hash}).collect())
+}
+
+/// Bits in which two perceptual hashes differ
+pub fn hash_distance(left: u64, right: u64) -> u32 {
+    (left ^ right).count_ones()
 }
 
 fn push_best_candidate(candidates: &mut Vec<LoopCandidate>, frames: &[LoadedFrame], period: usize, window: usize, options: &LoopDetectionOptions, comparison_cache: &mut FrameComparisonCache<'_>, matching_run: &[(usize, SequenceMetrics)]) {
@@ -233,13 +315,16 @@ fn push_best_candidate(candidates: &mut Vec<LoopCandidate>, frames: &[LoadedFram
 
 fn validate_options(options: &LoopDetectionOptions) -> Result<()> {
     if options.minimum_distance == 0 {
-        return Err(anyhow!("minimum_distance must be at least 1"));
+        return Err(Error::InvalidOptions("minimum_distance must be at least 1".into()));
     }
     if options.validation_window == 0 {
-        return Err(anyhow!("validation_window must be at least 1"));
+        return Err(Error::InvalidOptions("validation_window must be at least 1".into()));
+    }
+    if options.max_hash_distance > HASH_BITS {
+        return Err(Error::InvalidOptions(format!("max_hash_distance must be at most {}", HASH_BITS)));
     }
     if !options.similarity_threshold.is_finite() || !(0.0..=1.0).contains(&options.similarity_threshold) {
-        return Err(anyhow!("similarity_threshold must be finite and between 0 and 1"));
+        return Err(Error::InvalidOptions("similarity_threshold must be finite and between 0 and 1".into()));
     }
     Ok(())
 }
@@ -249,7 +334,7 @@ fn compare_sequence(comparison_cache: &mut FrameComparisonCache<'_>, left_start:
         return None;
     }
 
-    let threshold_margin = if quick && options.mode != LoopMatchMode::ExactText {QUICK_THRESHOLD_MARGIN} else {0.0};
+    let threshold_margin = if quick && options.mode.is_graded() {QUICK_THRESHOLD_MARGIN} else {0.0};
     let average_threshold = (options.similarity_threshold - threshold_margin).max(0.0);
     let per_frame_threshold = (average_threshold - FRAME_THRESHOLD_MARGIN).max(0.0);
     let mut combined_total = 0.0;
@@ -280,15 +365,23 @@ fn compare_sequence(comparison_cache: &mut FrameComparisonCache<'_>, left_start:
     Some(SequenceMetrics {combined, text: text_total / divisor, color: (color_count > 0).then_some(color_total / color_count as f32)})
 }
 
-fn compare_frames(left: &LoadedFrame, right: &LoadedFrame, mode: LoopMatchMode, ramp: &RampLookup, quick: bool) -> FrameMetrics {
+fn compare_frames(left: &LoadedFrame, right: &LoadedFrame, options: &LoopDetectionOptions, ramp: &RampLookup, quick: bool) -> FrameMetrics {
     if left.width != right.width || left.height != right.height {
         return FrameMetrics {combined: 0.0, text: 0.0, color: None};
     }
 
-    if mode == LoopMatchMode::ExactText {
-        let equal = left.exact_hash == right.exact_hash && left.exact_text == right.exact_text;
-        let score = if equal {1.0} else {0.0};
-        return FrameMetrics {combined: score, text: score, color: None};
+    match options.mode {
+        LoopMatchMode::ExactText => {
+            let score = if left.hash == right.hash && left.exact_text == right.exact_text {1.0} else {0.0};
+            return FrameMetrics {combined: score, text: score, color: None};
+        }
+        // Matching is all or nothing; the text similarity reports how close the hashes were
+        LoopMatchMode::PerceptualHash => {
+            let distance = hash_distance(left.hash, right.hash);
+            let score = if distance <= options.max_hash_distance {1.0} else {0.0};
+            return FrameMetrics {combined: score, text: 1.0 - distance as f32 / HASH_BITS as f32, color: None};
+        }
+        LoopMatchMode::VisualText | LoopMatchMode::VisualTextAndColor => {}
     }
 
     let cell_count = left.glyphs.len();
@@ -304,7 +397,7 @@ fn compare_frames(left: &LoadedFrame, right: &LoadedFrame, mode: LoopMatchMode,
     let mut foreground_cells = 0usize;
     let mut background_distance = 0.0;
     let mut background_cells = 0usize;
-    let compare_color = mode == LoopMatchMode::VisualTextAndColor;
+    let compare_color = options.mode == LoopMatchMode::VisualTextAndColor;
     let foregrounds = left.foreground.as_ref().zip(right.foreground.as_ref());
     let backgrounds = left.background.as_ref().zip(right.background.as_ref());
     let background_payload_mismatch = left.background.is_some() != right.background.is_some();
@@ -365,26 +458,27 @@ fn rgb_distance(left: &[u8], right: &[u8], cell_index: usize) -> f32 {
     channel_total / (255.0 * 3.0)
 }
 
-fn frames_are_identical(left: &LoadedFrame, right: &LoadedFrame, mode: LoopMatchMode) -> bool {
+fn frames_are_identical(left: &LoadedFrame, right: &LoadedFrame, options: &LoopDetectionOptions) -> bool {
     if left.width != right.width || left.height != right.height {
         return false;
     }
 
-    match mode {
-        LoopMatchMode::ExactText => left.exact_hash == right.exact_hash && left.exact_text == right.exact_text,
+    match options.mode {
+        LoopMatchMode::ExactText => left.hash == right.hash && left.exact_text == right.exact_text,
+        LoopMatchMode::PerceptualHash => hash_distance(left.hash, right.hash) <= options.max_hash_distance,
         LoopMatchMode::VisualText => left.glyphs == right.glyphs,
         LoopMatchMode::VisualTextAndColor => left.glyphs == right.glyphs && left.foreground == right.foreground && left.background == right.background,
     }
 }
 
-fn canonical_duplicate_frames(frames: &[LoadedFrame], mode: LoopMatchMode) -> HashMap<usize, usize> {
+fn canonical_duplicate_frames(frames: &[LoadedFrame], options: &LoopDetectionOptions) -> HashMap<usize, usize> {
     let mut canonical = HashMap::with_capacity(frames.len());
     let mut run_start = None;
 
     for (index, frame) in frames.iter().enumerate() {
         let continues_run = index > 0
             && frames[index - 1].number.checked_add(1) == Some(frame.number)
-            && frames_are_identical(&frames[index - 1], frame, mode);
+            && frames_are_identical(&frames[index - 1], frame, options);
         if !continues_run {
             run_start = Some(frame.number);
         }
@@ -394,10 +488,10 @@ fn canonical_duplicate_frames(frames: &[LoadedFrame], mode: LoopMatchMode) -> Ha
     canonical
 }
 
-fn remove_redundant_candidates(mut candidates: Vec<LoopCandidate>, frames: &[LoadedFrame], mode: LoopMatchMode) -> Vec<LoopCandidate> {
+fn remove_redundant_candidates(mut candidates: Vec<LoopCandidate>, frames: &[LoadedFrame], options: &LoopDetectionOptions) -> Vec<LoopCandidate> {
     candidates.sort_by(|left, right| right.occurrences.len().cmp(&left.occurrences.len()).then_with(|| right.confidence.total_cmp(&left.confidence)).then_with(|| left.period_frames.cmp(&right.period_frames)).then_with(|| left.occurrences.cmp(&right.occurrences)));
 
-    let canonical_frames = canonical_duplicate_frames(frames, mode);
+    let canonical_frames = canonical_duplicate_frames(frames, options);
     let mut retained: Vec<LoopCandidate> = Vec::new();
     for candidate in candidates {
         let candidate_pair = candidate.occurrences.get(0..2).map(|pair| {
@@ -434,7 +528,7 @@ fn remove_redundant_candidates(mut candidates: Vec<LoopCandidate>, frames: &[Loa
     retained
 }
 
-fn load_frames(directory: &Path) -> Result<Vec<LoadedFrame>> {
+fn load_frames(directory: &Path, ramp: &RampLookup) -> Result<Vec<LoadedFrame>> {
     let mut paths_by_number: BTreeMap<usize, FramePaths> = BTreeMap::new();
 
     for entry in WalkDir::new(directory).min_depth(1).max_depth(1).into_iter().filter_map(Result::ok) {
@@ -459,59 +553,113 @@ fn load_frames(directory: &Path) -> Result<Vec<LoadedFrame>> {
     }
 
     if paths_by_number.is_empty() {
-        return Err(anyhow!("No frame_*.txt or frame_*.cframe files found in {}", directory.display()));
+        return Err(Error::NotFound(format!("No frame_*.txt or frame_*.cframe files found in {}", directory.display())));
     }
 
-    paths_by_number.into_iter().map(|(number, paths)| load_frame(number, paths)).collect()
+    paths_by_number.into_iter().map(|(number, paths)| load_frame(number, paths, ramp)).collect()
 }
 
-fn load_frame(number: usize, paths: FramePaths) -> Result<LoadedFrame> {
+fn load_frame(number: usize, paths: FramePaths, ramp: &RampLookup) -> Result<LoadedFrame> {
     let text_bytes = paths.text.as_ref().map(|path| fs::read(path).with_context(|| format!("reading {}", path.display()))).transpose()?;
 
     let (width, height, glyphs, foreground, background) = if let Some(color_path) = paths.color.as_ref() {
         let data = read_cframe_to_frame_data(color_path)?;
-        let glyphs = data.ascii_text.bytes().filter(|byte| *byte != b'\n' && *byte != b'\r').collect::<Vec<_>>();
+        let cell_bytes = CellBytes::of_text(&data.ascii_text);
+        let glyphs = data.ascii_text.chars().filter(|ch| *ch != '\n' && *ch != '\r').map(|ch| cell_bytes.byte(ch)).collect::<Vec<_>>();
         let expected_cells = data.width_chars as usize * data.height_chars as usize;
         if glyphs.len() != expected_cells {
-            return Err(anyhow!("cframe {} contains {} glyphs, expected {}", color_path.display(), glyphs.len(), expected_cells));
+            return Err(Error::InvalidCframe(format!("cframe {} contains {} glyphs, expected {}", color_path.display(), glyphs.len(), expected_cells)));
         }
 
         let foreground = (data.rgb_colors.len() == expected_cells * 3).then_some(data.rgb_colors);
         let background = (data.bg_rgb_colors.len() == expected_cells * 3).then_some(data.bg_rgb_colors);
         (data.width_chars as usize, data.height_chars as usize, glyphs, foreground, background)
     } else {
-        let bytes = text_bytes.as_deref().ok_or_else(|| anyhow!("frame {} has no readable data", number))?;
+        let bytes = text_bytes.as_deref().ok_or_else(|| Error::InvalidCframe(format!("frame {} has no readable data", number)))?;
         let (width, height, glyphs) = normalize_text_frame(bytes)?;
         (width, height, glyphs, None, None)
     };
 
     let exact_text = if let Some(bytes) = text_bytes {bytes} else {glyphs.clone()};
-    let mut hasher = DefaultHasher::new();
-    exact_text.hash(&mut hasher);
+    let lumas = glyphs.iter().enumerate().map(|(index, &glyph)| {
+        let density = ramp.density(glyph);
+        match foreground.as_deref() {
+            Some(colors) => density * LuminanceWeights::REC709.luminance(colors[index * 3], colors[index * 3 + 1], colors[index * 3 + 2]) as f32 / 255.0,
+            None => density,
+        }
+    });
+    let hash = perceptual_hash(&lumas.collect::<Vec<_>>(), width, height);
 
-    Ok(LoadedFrame {number, width, height, glyphs, exact_text, exact_hash: hasher.finish(), foreground, background})
+    Ok(LoadedFrame {number, width, height, glyphs, exact_text, hash, foreground, background})
 }
 
-fn normalize_text_frame(bytes: &[u8]) -> Result<(usize, usize, Vec<u8>)> {
-    if !bytes.is_ascii() {
-        return Err(anyhow!("ASCII frame contains non-ASCII data"));
+/// A `width`×`height` grid of cell luminances averaged onto a square grid of [`HASH_GRID`] cells a
+/// side, or `None` when `lumas` doesn't hold that many cells. Grids larger than the frame repeat cells.
+pub(crate) fn luminance_grid(lumas: &[f32], width: usize, height: usize) -> Option<[[f32; HASH_GRID]; HASH_GRID]> {
+    if lumas.is_empty() || lumas.len() != width * height {
+        return None;
     }
+    // Cells spanned by grid row or column `index`
+    let span = |index: usize, cells: usize| {
+        let start = (index * cells / HASH_GRID).min(cells - 1);
+        (start, ((index + 1) * cells / HASH_GRID).clamp(start + 1, cells))
+    };
+    let mut grid = [[0f32; HASH_GRID]; HASH_GRID];
+    for (grid_y, row) in grid.iter_mut().enumerate() {
+        let (top, bottom) = span(grid_y, height);
+        for (grid_x, value) in row.iter_mut().enumerate() {
+            let (left, right) = span(grid_x, width);
+            let total: f32 = (top..bottom).flat_map(|y| &lumas[y * width + left..y * width + right]).sum();
+            *value = total / ((bottom - top) * (right - left)) as f32;
+        }
+    }
+    Some(grid)
+}
 
-    let text = std::str::from_utf8(bytes).context("decoding ASCII frame")?;
+/// pHash of a `width`×`height` grid of luminances: each bit is whether one of the lowest 8×8 DCT
+/// frequencies of the grid averaged onto 32×32 lies above their median. The constant term is left
+/// out of the median but kept as a bit.
+fn perceptual_hash(lumas: &[f32], width: usize, height: usize) -> u64 {
+    let Some(grid) = luminance_grid(lumas, width, height) else {
+        return 0;
+    };
+    let cosines: [[f32; HASH_GRID]; HASH_FREQUENCIES] = std::array::from_fn(|frequency| std::array::from_fn(|position| ((2 * position + 1) as f32 * frequency as f32 * std::f32::consts::PI / (2 * HASH_GRID) as f32).cos()));
+    // Separable DCT-II: rows first, then the columns of the row results
+    let rows: Vec<[f32; HASH_FREQUENCIES]> = grid.iter().map(|row| std::array::from_fn(|u| row.iter().zip(&cosines[u]).map(|(value, cosine)| value * cosine).sum())).collect();
+    let coefficients: Vec<f32> = (0..HASH_FREQUENCIES).flat_map(|v| {
+        let (rows, cosines) = (&rows, &cosines);
+        (0..HASH_FREQUENCIES).map(move |u| rows.iter().zip(&cosines[v]).map(|(row, cosine)| row[u] * cosine).sum())
+    }).collect();
+
+    let mut ac = coefficients[1..].to_vec();
+    ac.sort_by(f32::total_cmp);
+    let median = ac[ac.len() / 2];
+    coefficients.iter().enumerate().fold(0u64, |hash, (bit, &coefficient)| if coefficient > median {hash | 1 << bit} else {hash})
+}
+
+fn normalize_text_frame(bytes: &[u8]) -> Result<(usize, usize, Vec<u8>)> {
+    let text = std::str::from_utf8(bytes).map_err(|err| Error::InvalidCframe(format!("decoding ASCII frame: {}", err)))?;
+    // Braille and block frames compare by cell byte, as their .cframe files store them
+    let cell_bytes = CellBytes::of_text(text);
+    if !text.is_ascii() && cell_bytes == CellBytes::Latin1 {
+        return Err(Error::InvalidCframe("ASCII frame contains non-ASCII data".into()));
+    }
     let lines = text.lines().collect::<Vec<_>>();
     if lines.is_empty() {
-        return Err(anyhow!("ASCII frame is empty"));
+        return Err(Error::InvalidCframe("ASCII frame is empty".into()));
     }
 
-    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
     if width == 0 {
-        return Err(anyhow!("ASCII frame has zero width"));
+        return Err(Error::InvalidCframe("ASCII frame has zero width".into()));
     }
 
+    let blank = cell_bytes.blank();
     let mut glyphs = Vec::with_capacity(width * lines.len());
     for line in &lines {
-        glyphs.extend_from_slice(line.as_bytes());
-        glyphs.resize(glyphs.len() + width - line.len(), b' ');
+        let start = glyphs.len();
+        glyphs.extend(line.chars().map(|ch| cell_bytes.byte(ch)));
+        glyphs.resize(start + width, blank);
     }
 
     Ok((width, lines.len(), glyphs))
@@ -522,6 +670,68 @@ fn frame_number(path: &Path) -> Option<usize> {
     stem.strip_prefix("frame_")?.parse().ok()
 }
 
+/// One period of `candidate`, from its first occurrence up to the frame before the next, scaled
+/// down to at most [`PREVIEW_COLUMNS`] columns. Played on repeat, these frames are the loop as it
+/// would be exported.
+pub fn loop_preview_frames(directory: &Path, candidate: &LoopCandidate) -> Result<Vec<AsciiFrame>> {
+    let start = *candidate.occurrences.first().ok_or_else(|| Error::NotFound("loop candidate has no occurrences".into()))?;
+    let reader = FrameReader::open(directory)?;
+    let offset = reader.paths().iter().position(|path| frame_number(path) == Some(start)).ok_or_else(|| Error::NotFound(format!("frame {} of the loop is not in {}", start, directory.display())))?;
+    reader.skip(offset).take(candidate.period_frames.max(1)).map(|frame| frame.map(|frame| frame.fit_within(PREVIEW_COLUMNS, u32::MAX).into_owned())).collect()
+}
+
+/// Write `frames` to `path` as an endlessly looping GIF at `fps`, drawn at [`PREVIEW_FONT_SIZE`]
+/// with the fastest palette quantization.
+pub fn write_loop_preview_gif(frames: &[AsciiFrame], fps: u32, path: &Path) -> Result<()> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+
+    let mut atlas = render::build_glyph_atlas(PREVIEW_FONT_SIZE)?;
+    for frame in frames {
+        atlas.add_chars(&frame.text)?;
+    }
+    let colors = frames.first().is_some_and(|frame| !frame.rgb.is_empty());
+    let file = fs::File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut writer = std::io::BufWriter::new(file);
+    let mut encoder = GifEncoder::new_with_speed(&mut writer, 30);
+    encoder.set_repeat(Repeat::Infinite).context("writing the GIF header")?;
+    let delay = image::Delay::from_numer_denom_ms(1000, fps.max(1));
+    for (index, frame) in frames.iter().enumerate() {
+        let image = image::DynamicImage::ImageRgb8(render::render_frame_with_atlas(frame, &atlas, colors)?).into_rgba8();
+        encoder.encode_frame(image::Frame::from_parts(image, 0, 0, delay)).with_context(|| format!("encoding GIF frame {}", index + 1))?;
+    }
+    drop(encoder);
+    writer.flush().with_context(|| format!("writing {}", path.display()))
+}
+
+/// Play `frames` in the terminal `plays` times over at `fps`, scaled to fit it. Frames are drawn in
+/// place and in color when the terminal takes escapes, and printed one after another otherwise.
+pub fn play_loop_preview(frames: &[AsciiFrame], fps: u32, plays: usize) -> Result<()> {
+    let ansi = terminal::enable_ansi();
+    let delay = Duration::from_secs_f64(1.0 / fps.max(1) as f64);
+    let mut fit = TerminalFit::new();
+    let mut out = std::io::stdout().lock();
+    if ansi {
+        write!(out, "\x1b[?25l\x1b[2J")?;
+    }
+    for frame in frames.iter().cycle().take(frames.len() * plays) {
+        let started = Instant::now();
+        let resized = fit.poll();
+        let frame = fit.fit(frame);
+        if ansi {
+            write!(out, "{}\x1b[H{}", if resized {"\x1b[2J"} else {""}, frame_to_ansi_string(&frame, AnsiColor::TrueColor))?;
+        } else {
+            write!(out, "{}", frame.text)?;
+        }
+        out.flush()?;
+        std::thread::sleep(delay.saturating_sub(started.elapsed()));
+    }
+    if ansi {
+        write!(out, "\x1b[0m\x1b[?25h")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
 pub fn run_find_loop(dir: &Path) -> Result<()> {
     run_find_loop_with_options(dir, &LoopDetectionOptions::default())
 }
@@ -535,15 +745,10 @@ pub fn run_find_loop_with_options(dir: &Path, options: &LoopDetectionOptions) ->
 
     println!("Found loops:");
     for (index, candidate) in candidates.iter().enumerate() {
-        println!("{}: frames {} (period {}, {:.1}% confidence)", index + 1, candidate.occurrences.iter().map(usize::to_string).collect::<Vec<_>>().join(", "), candidate.period_frames, candidate.confidence * 100.0);
+        println!("{}: frames {}", index + 1, candidate_label(candidate));
     }
 
     let frames = load_text_frames(dir)?;
-    if frames.is_empty() {
-        println!("Loop editing requires frame_*.txt files; detection completed without editing.");
-        return Ok(());
-    }
-
     let frame_indices = frames.iter().enumerate().map(|(index, (number, _))| (*number, index)).collect::<HashMap<_, _>>();
     let loops = candidates
         .iter()
@@ -554,28 +759,53 @@ pub fn run_find_loop_with_options(dir: &Path, options: &LoopDetectionOptions) ->
         })
         .collect::<Vec<_>>();
 
-    if loops.is_empty() {
-        println!("Detected loops could not be mapped to editable text frames.");
-        return Ok(());
+    if frames.is_empty() {
+        println!("Loop editing requires frame_*.txt files; loops can only be previewed.");
+    } else if loops.is_empty() {
+        println!("Detected loops could not be mapped to editable text frames; loops can only be previewed.");
     }
 
+    let fps = recorded_fps(dir).unwrap_or(DEFAULT_PREVIEW_FPS);
     loop {
-        let choices = vec!["Export loop", "Repeat loop", "Quit"];
+        let choices = if loops.is_empty() {vec!["Preview loop", "Quit"]} else {vec!["Preview loop", "Export loop", "Repeat loop", "Quit"]};
         let selection = Select::new().with_prompt("Choose an action").default(0).items(&choices).interact()?;
-        match selection {
-            0 => {
+        match choices[selection] {
+            "Preview loop" => {
+                let labels = candidates.iter().map(candidate_label).collect::<Vec<_>>();
+                let index = Select::new().with_prompt("Select loop to preview").default(0).items(&labels).interact()?;
+                let candidate = &candidates[index];
+                let preview = loop_preview_frames(dir, candidate)?;
+                let outputs = ["Play in terminal", "Write preview GIF"];
+                if Select::new().with_prompt("Preview as").default(0).items(&outputs).interact()? == 0 {
+                    play_loop_preview(&preview, fps, PREVIEW_PLAYS)?;
+                } else {
+                    let (start, end) = (candidate.occurrences[0], candidate.occurrences.get(1).copied().unwrap_or(candidate.occurrences[0] + candidate.period_frames));
+                    let mut path = loop_output_path(dir, start, end).into_os_string();
+                    path.push(".gif");
+                    let path = PathBuf::from(path);
+                    write_loop_preview_gif(&preview, fps, &path)?;
+                    println!("Wrote {}", path.display());
+                }
+            }
+            "Export loop" => {
                 let labels = loop_labels(&frames, &loops);
                 let index = Select::new().with_prompt("Select loop to export").default(0).items(&labels).interact()?;
                 let (start, end) = loops[index];
                 export_loop(dir, &frames, start, end)?;
                 println!("Exported loop {}..{}", frames[start].0, frames[end].0);
             }
-            1 => {
+            "Repeat loop" => {
                 let labels = loop_labels(&frames, &loops);
                 let index = Select::new().with_prompt("Select loop to repeat").default(0).items(&labels).interact()?;
                 let (start, end) = loops[index];
-                repeat_loop(dir, &frames, start, end)?;
-                println!("Loop repeated");
+                let mut backup = if options.backup {Some(Backup::create(dir, "repeat loop", &text_frame_paths(dir))?)} else {None};
+                let written = repeat_loop(dir, &frames, start, end)?;
+                if let Some(backup) = &mut backup {
+                    backup.record_written(&written)?;
+                    println!("Loop repeated (undo with --undo)");
+                } else {
+                    println!("Loop repeated");
+                }
             }
             _ => break,
         }
@@ -601,6 +831,10 @@ fn load_text_frames(dir: &Path) -> Result<Vec<(usize, String)>> {
     Ok(frames)
 }
 
+fn candidate_label(candidate: &LoopCandidate) -> String {
+    format!("{} (period {}, {:.1}% confidence)", candidate.occurrences.iter().map(usize::to_string).collect::<Vec<_>>().join(", "), candidate.period_frames, candidate.confidence * 100.0)
+}
+
 fn loop_labels(frames: &[(usize, String)], loops: &[(usize, usize)]) -> Vec<String> {
     loops.iter().map(|(start, end)| format!("{}..{}", frames[*start].0, frames[*end].0)).collect()
 }
@@ -608,7 +842,7 @@ fn loop_labels(frames: &[(usize, String)], loops: &[(usize, usize)]) -> Vec<Stri
 fn export_loop(dir: &Path, frames: &[(usize, String)], start_idx: usize, end_idx: usize) -> Result<()> {
     let start_frame = frames[start_idx].0;
     let end_frame = frames[end_idx].0;
-    let out = dir.with_file_name(format!("{}_loop_{}_{}", dir.file_name().and_then(|value| value.to_str()).unwrap_or("frames"), start_frame, end_frame));
+    let out = loop_output_path(dir, start_frame, end_frame);
     fs::create_dir_all(&out)?;
     for (counter, frame) in frames.iter().take(end_idx + 1).skip(start_idx).enumerate() {
         let filename = out.join(format!("frame_{:04}.txt", counter + 1));
@@ -617,7 +851,27 @@ fn export_loop(dir: &Path, frames: &[(usize, String)], start_idx: usize, end_idx
     Ok(())
 }
 
-fn repeat_loop(dir: &Path, frames: &[(usize, String)], start_idx: usize, end_idx: usize) -> Result<()> {
+/// `<dir>_loop_<start>_<end>` next to `dir`, where loops are exported
+fn loop_output_path(dir: &Path, start_frame: usize, end_frame: usize) -> PathBuf {
+    dir.with_file_name(format!("{}_loop_{}_{}", dir.file_name().and_then(|value| value.to_str()).unwrap_or("frames"), start_frame, end_frame))
+}
+
+/// The `fps` recorded in the `details.toml` of a converted directory, or in the header of a `.cvid` file
+pub(crate) fn recorded_fps(dir: &Path) -> Option<u32> {
+    if dir.is_file() && dir.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(crate::cvid::CVID_EXTENSION)) {
+        return crate::cvid::CvidReader::open(dir).ok()?.fps();
+    }
+    let details: toml::Table = fs::read_to_string(dir.join("details.toml")).ok()?.parse().ok()?;
+    details.get("fps")?.as_integer()?.try_into().ok().filter(|&fps: &u32| fps > 0)
+}
+
+/// The `frame_*.txt` files directly in `dir`
+fn text_frame_paths(dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(dir).min_depth(1).max_depth(1).into_iter().filter_map(Result::ok).map(|entry| entry.into_path()).filter(|path| path.is_file() && path.file_name().and_then(|value| value.to_str()).is_some_and(|name| name.starts_with("frame_") && name.ends_with(".txt"))).collect()
+}
+
+/// Rewrite the text frames of `dir` with frames `start_idx..=end_idx` played twice, returning the files written
+fn repeat_loop(dir: &Path, frames: &[(usize, String)], start_idx: usize, end_idx: usize) -> Result<Vec<PathBuf>> {
     let mut new_sequence = Vec::with_capacity(frames.len() + (end_idx - start_idx + 1));
     for (_, content) in frames.iter().take(end_idx + 1) {
         new_sequence.push(content.clone());
@@ -629,17 +883,16 @@ fn repeat_loop(dir: &Path, frames: &[(usize, String)], start_idx: usize, end_idx
         new_sequence.push(content.clone());
     }
 
-    for entry in WalkDir::new(dir).min_depth(1).max_depth(1).into_iter().filter_map(Result::ok) {
-        let path = entry.path();
-        if path.is_file() && path.file_name().and_then(|value| value.to_str()).is_some_and(|name| name.starts_with("frame_") && name.ends_with(".txt")) {
-            let _ = fs::remove_file(path);
-        }
+    for path in text_frame_paths(dir) {
+        let _ = fs::remove_file(path);
     }
+    let mut written = Vec::with_capacity(new_sequence.len());
     for (index, content) in new_sequence.iter().enumerate() {
         let filename = dir.join(format!("frame_{:04}.txt", index + 1));
-        fs::write(filename, content)?;
+        fs::write(&filename, content)?;
+        written.push(filename);
     }
-    Ok(())
+    Ok(written)
 }
 
 #[cfg(test)]
@@ -649,7 +902,7 @@ mod tests {
     use tempfile::TempDir;
 
     fn options(mode: LoopMatchMode, minimum_distance: usize, validation_window: usize, threshold: f32) -> LoopDetectionOptions {
-        LoopDetectionOptions {mode, minimum_distance, validation_window, similarity_threshold: threshold, ascii_ramp: " .:-=+*#@".to_string()}
+        LoopDetectionOptions {mode, minimum_distance, validation_window, similarity_threshold: threshold, ascii_ramp: " .:-=+*#@".to_string(), max_hash_distance: 8, backup: true}
     }
 
     fn write_text(dir: &Path, number: usize, text: &str) {
@@ -723,6 +976,31 @@ mod tests {
         assert!(candidates.iter().any(|candidate| {candidate.period_frames == 2 && candidate.occurrences == vec![1, 3] && candidate.average_color_similarity.unwrap() > 0.99}));
     }
 
+    #[test]
+    fn perceptual_hash_mode_tolerates_noisy_cells() {
+        let temp = TempDir::new().unwrap();
+        // A ball crossing a shaded 32x16 frame, then the same sweep with one cell flipped per frame
+        let sweep = |offset: usize, noise: Option<usize>| (0..16).map(|y: usize| (0..32).map(|x: usize| {
+            let inside = (x as i32 - 4 - offset as i32 * 6).pow(2) + (2 * y as i32 - 16).pow(2) < 36;
+            if noise == Some(y * 32 + x) {'@'} else if inside {'#'} else {b" .:-="[(x + y) / 10] as char}
+        }).chain(['\n']).collect::<String>()).collect::<String>();
+        for offset in 0..5 {
+            write_text(temp.path(), offset + 1, &sweep(offset, None));
+            write_text(temp.path(), offset + 6, &sweep(offset, Some(offset * 37 + 3)));
+        }
+
+        let hashes = frame_hashes(temp.path(), " .:-=+*#@").unwrap();
+        assert_eq!(hashes.len(), 10);
+        assert!((0..5).all(|index| hashes[index].distance(hashes[index + 5]) <= 8), "{:?}", hashes);
+        assert!((0..4).all(|index| hashes[index].distance(hashes[index + 1]) > 16), "{:?}", hashes);
+
+        assert!(detect_frame_loops(temp.path(), &options(LoopMatchMode::ExactText, 3, 3, 1.0)).unwrap().is_empty());
+        let candidates = detect_frame_loops(temp.path(), &options(LoopMatchMode::PerceptualHash, 3, 3, 1.0)).unwrap();
+        assert!(candidates.iter().any(|candidate| candidate.period_frames == 5 && candidate.occurrences == vec![1, 6] && candidate.confidence == 1.0), "{:?}", candidates);
+        let strict = LoopDetectionOptions {max_hash_distance: 0, ..options(LoopMatchMode::PerceptualHash, 3, 3, 1.0)};
+        assert!(detect_frame_loops(temp.path(), &strict).unwrap().is_empty());
+    }
+
     #[test]
     fn later_occurrences_must_match_the_first_occurrence() {
         let temp = TempDir::new().unwrap();
@@ -757,13 +1035,13 @@ mod tests {
         for (index, text) in ["A\n", "B\n", "C\n", "D\n", "A\n", "A\n", "B\n"].iter().enumerate() {
             write_text(temp.path(), index + 1, text);
         }
-        let frames = load_frames(temp.path()).unwrap();
+        let frames = load_frames(temp.path(), &RampLookup::new(" .:-=+*#@").unwrap()).unwrap();
         let candidates = vec![
             LoopCandidate {occurrences: vec![1, 5], period_frames: 4, confidence: 0.98, average_text_similarity: 0.98, average_color_similarity: None},
             LoopCandidate {occurrences: vec![1, 6], period_frames: 5, confidence: 0.99, average_text_similarity: 0.99, average_color_similarity: None},
         ];
 
-        let deduplicated = remove_redundant_candidates(candidates, &frames, LoopMatchMode::VisualText);
+        let deduplicated = remove_redundant_candidates(candidates, &frames, &options(LoopMatchMode::VisualText, 1, 1, 0.9));
 
         assert_eq!(deduplicated.len(), 1);
         assert_eq!(deduplicated[0].occurrences, vec![1, 5]);
@@ -777,17 +1055,37 @@ mod tests {
         }
         write_color(temp.path(), 5, "A\n", &[[20, 20, 20]], None);
         write_color(temp.path(), 6, "A\n", &[[200, 200, 200]], None);
-        let frames = load_frames(temp.path()).unwrap();
+        let frames = load_frames(temp.path(), &RampLookup::new(" .:-=+*#@").unwrap()).unwrap();
         let candidates = vec![
             LoopCandidate {occurrences: vec![1, 5], period_frames: 4, confidence: 0.98, average_text_similarity: 1.0, average_color_similarity: None},
             LoopCandidate {occurrences: vec![1, 6], period_frames: 5, confidence: 0.99, average_text_similarity: 1.0, average_color_similarity: None},
         ];
 
-        let visual_text = remove_redundant_candidates(candidates.clone(), &frames, LoopMatchMode::VisualText);
-        let visual_color = remove_redundant_candidates(candidates, &frames, LoopMatchMode::VisualTextAndColor);
+        let visual_text = remove_redundant_candidates(candidates.clone(), &frames, &options(LoopMatchMode::VisualText, 1, 1, 0.9));
+        let visual_color = remove_redundant_candidates(candidates, &frames, &options(LoopMatchMode::VisualTextAndColor, 1, 1, 0.9));
 
         assert_eq!(visual_text.len(), 1);
         assert_eq!(visual_text[0].occurrences, vec![1, 5]);
         assert_eq!(visual_color.len(), 2);
     }
+
+    #[test]
+    fn loop_preview_plays_one_period_and_writes_a_gif() {
+        let temp = TempDir::new().unwrap();
+        for number in 1..=6 {
+            write_color(temp.path(), number, &format!("{}{}\n", number % 3, number % 3), &[[number as u8 * 40, 0, 0], [0, 0, 90]], None);
+        }
+        fs::write(temp.path().join("details.toml"), "frames = 6\nfps = 12\n").unwrap();
+        let candidate = LoopCandidate {occurrences: vec![2, 5], period_frames: 3, confidence: 1.0, average_text_similarity: 1.0, average_color_similarity: None};
+
+        let frames = loop_preview_frames(temp.path(), &candidate).unwrap();
+        let texts: Vec<&str> = frames.iter().map(|frame| frame.text.as_str()).collect();
+        assert_eq!(texts, ["22\n", "00\n", "11\n"]);
+        assert_eq!(recorded_fps(temp.path()), Some(12));
+
+        let gif = temp.path().join("preview.gif");
+        write_loop_preview_gif(&frames, 12, &gif).unwrap();
+        let decoder = image::codecs::gif::GifDecoder::new(std::io::BufReader::new(fs::File::open(&gif).unwrap())).unwrap();
+        assert_eq!(image::AnimationDecoder::into_frames(decoder).count(), 3);
+    }
 }
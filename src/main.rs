@@ -1,11 +1,35 @@
 use anyhow::{anyhow, Context, Result};
-use cascii::loop_detect::{run_find_loop_with_options, LoopDetectionOptions, LoopMatchMode};
-use cascii::preprocessing::{detect_preprocess_input_kind, preprocess_directory, preprocess_image_to_file, preprocess_image_to_temp, preprocess_video_to_file, resolve_preprocess_filter, resolve_preprocess_output_path, PreprocessInputKind, PREPROCESS_PRESETS};
-use cascii::{crop_frames, run_trim, AppConfig, AsciiConverter, BgFitQuality, CellColorMode, ConversionOptions, OutputMode, Progress, ProgressPhase, ToVideoOptions, VideoOptions};
+use cascii::charset::{build_from_font, calibrate_from_font};
+use cascii::cframe::{CframeCompression, CframeFormat};
+use cascii::codepage::{Codepage, CP437_RAMP};
+use cascii::cvid::{write_cvid, CVID_EXTENSION};
+use cascii::ansi_art::{AnsiArtOptions, Cp437Mapping, LegacyFormat};
+use cascii::teletext::{TeletextFormat, TeletextOptions};
+#[cfg(any(feature = "osc", feature = "mqtt"))]
+use cascii::show_control::{ShowControl, ShowControlOptions, ShowTarget};
+use cascii::led_matrix::{LedOptions, LedProtocol, LedSource};
+use cascii::embed::{embed_frame_dir, EmbedLanguage, EmbedOptions};
+use cascii::events::EventLog;
+use cascii::lock::OutputLock;
+use cascii::loop_detect::{frame_hashes, run_find_loop_with_options, LoopDetectionOptions, LoopMatchMode};
+use cascii::narration::{synthesize_narration, TtsConfig};
+#[cfg(feature = "encryption")]
+use cascii::packed::{encrypt_full_cframes_from_dir, ArchiveKey};
+use cascii::packed::{pack_full_cframes_from_dir, ARCHIVE_EXTENSION};
+use cascii::player::{self, PlayOptions};
+use cascii::stream::StreamOptions;
+use cascii::terminal;
+use cascii::preprocessing::{detect_preprocess_input_kind, find_preprocess_preset, preprocess_directory, preprocess_image_to_file, preprocess_image_to_temp, preprocess_video_to_file, resolve_preprocess_filter, resolve_preprocess_output_path, PreprocessInputKind, TempFileGuard, PREPROCESS_PRESETS};
+use cascii::test_pattern::{generate_test_pattern, TestPattern};
+use cascii::{backup, crop_frames, frame_to_ansi_string, is_cancelled_error, run_trim, trim_targets, AlphaMode, Anaglyph, AnsiColor, AppConfig, AudioPadding, AsciiConverter, AsciiFrame, BgFitQuality, CancelToken, CaptionTrack, CellColorMode, CharMode, ConversionOptions, ConversionResult, ConverterLimits, DepthMap, DepthMode, DepthOptions, DitherMode, FfmpegCapabilities, FfmpegConfig, FontRatio, FontTarget, FpsMode, GlyphMatch, LineFit, LineLimits, LuminanceModel, LuminanceWeights, OutputMode, Palette, PipeFormat, Profile, Progress, ProgressPhase, ReportFormat, ReportTemplate, ToneMap, ToneMapCurve, ToVideoOptions, UpscalePolicy, VideoOptions, YuvMatrix};
+#[cfg(feature = "tui")]
+use cascii::wizard::{run_wizard, SampleFrame, WizardSettings};
 use clap::{Parser, Subcommand, ValueEnum};
 use dialoguer::{Confirm, FuzzySelect, Input};
 use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashSet;
 use std::fs;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use walkdir::WalkDir;
@@ -26,7 +50,7 @@ fn load_config() -> Result<AppConfig> {
             let cfg: AppConfig = serde_json::from_str(&text).context("parsing config json")?;
 
             // Validate that ascii_chars contains only ASCII characters
-            if !cfg.ascii_chars.is_ascii() {
+            if !cfg.uses_only_ascii_chars() {
                 return Err(anyhow!("Config file {} contains non-ASCII characters in ascii_chars field. This will cause corrupted output. Please use only ASCII characters.", p.display()));
             }
 
@@ -42,6 +66,308 @@ fn load_config() -> Result<AppConfig> {
 enum Command {
     /// Uninstall cascii and remove associated data
     Uninstall,
+    /// Print an image in the terminal, converted to fit it, in color unless NO_COLOR is set (truecolor when COLORTERM says so)
+    Show(ShowArgs),
+    /// Play a directory of converted frames or a .casc archive in the terminal (space pauses, h/l and the arrows seek, [ ] change speed, q quits)
+    Play(PlayArgs),
+    /// Pack the frame_*.cframe files of a directory into one .casc archive
+    Pack(PackArgs),
+    /// Show a webcam or capture device live as ASCII in the terminal (Ctrl-C stops)
+    Stream(StreamArgs),
+    /// Write the frames of a directory or .casc archive as Rust or C source, to bake the animation into a binary
+    Embed(EmbedArgs),
+    /// Write the frames of a directory or .casc archive as classic ANSI art (.ans) or XBin (.xb) files with SAUCE records
+    AnsiArt(AnsiArtArgs),
+    /// Write the frames of a directory or .casc archive as teletext pages (.tti or .t42); convert with --sextant --columns 40 to fill a page
+    Teletext(TeletextArgs),
+    /// Send the frames of a directory or .casc archive to an LED matrix controller over DDP or E1.31 (WLED, rpi-rgb-led-matrix bridges; Ctrl-C stops)
+    Led(LedArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct PlayArgs {
+    /// Directory of frame_*.cframe / frame_*.txt files, or a .casc archive
+    dir: PathBuf,
+    /// Frames per second [default: the fps in details.toml, else 30]
+    #[arg(long)]
+    fps: Option<u32>,
+    /// Speed multiplier to start at (0.1-8)
+    #[arg(long, default_value_t = 1.0)]
+    speed: f64,
+    /// Start over at the end instead of stopping
+    #[arg(long, default_value_t = false)]
+    repeat: bool,
+    /// Draw the characters without their colors
+    #[arg(long, default_value_t = false)]
+    no_color: bool,
+    /// Use 256-color escapes for terminals without truecolor
+    #[arg(long, default_value_t = false)]
+    ansi_256: bool,
+    /// Don't play the directory's audio.mp3
+    #[arg(long, default_value_t = false)]
+    no_audio: bool,
+    /// Key of an encrypted archive (64 hex digits)
+    #[cfg(feature = "encryption")]
+    #[arg(long)]
+    key: Option<ArchiveKey>,
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    #[command(flatten)]
+    show: ShowTargetArgs,
+    /// Tempo of the beat events sent to --osc / --mqtt
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    #[arg(long)]
+    bpm: Option<f64>,
+    /// Seconds into the clip of the first beat
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    #[arg(long, default_value_t = 0.0)]
+    beat_offset: f64,
+    /// Share of cells (0-1) that must change between frames to send a scene event
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    #[arg(long, default_value_t = cascii::show_control::DEFAULT_SCENE_THRESHOLD)]
+    scene_threshold: f32,
+}
+
+/// Where show control events go
+#[cfg(any(feature = "osc", feature = "mqtt"))]
+#[derive(clap::Args, Debug)]
+struct ShowTargetArgs {
+    /// Send frame, beat and scene events (progress while converting) as OSC messages to HOST:PORT
+    #[cfg(feature = "osc")]
+    #[arg(long, value_name = "HOST:PORT")]
+    osc: Option<String>,
+    /// Publish frame, beat and scene events (progress while converting) to the MQTT broker at HOST:PORT
+    #[cfg(feature = "mqtt")]
+    #[arg(long, value_name = "HOST:PORT")]
+    mqtt: Option<String>,
+    /// First part of the OSC addresses and MQTT topics of show events
+    #[arg(long, default_value = "cascii")]
+    show_topic: String,
+}
+
+#[cfg(any(feature = "osc", feature = "mqtt"))]
+impl ShowTargetArgs {
+    fn options(&self) -> Option<ShowControlOptions> {
+        #[cfg(feature = "osc")]
+        let target = self.osc.clone().map(ShowTarget::Osc);
+        #[cfg(not(feature = "osc"))]
+        let target = None;
+        #[cfg(feature = "mqtt")]
+        let target = target.or_else(|| self.mqtt.clone().map(ShowTarget::Mqtt));
+        target.map(|target| ShowControlOptions::new(target).with_topic(self.show_topic.clone()))
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct ShowArgs {
+    /// Image to print
+    image: PathBuf,
+    /// Number of columns [default: as many as fit the terminal]
+    #[arg(long)]
+    columns: Option<u32>,
+    /// Draw the characters without their colors
+    #[arg(long, default_value_t = false)]
+    no_color: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct PackArgs {
+    /// Directory of frame_*.cframe files
+    dir: PathBuf,
+    /// Archive to write [default: the directory's path with a .casc extension, or .cvid with --cvid]
+    output: Option<PathBuf>,
+    /// Write a single-file .cvid animation with a frame table, the fps, the frame size and audio.mp3
+    #[arg(long, default_value_t = false)]
+    cvid: bool,
+    /// Encrypt the archive with this key (64 hex digits)
+    #[cfg(feature = "encryption")]
+    #[arg(long, conflicts_with_all = ["generate_key", "cvid"])]
+    key: Option<ArchiveKey>,
+    /// Encrypt the archive with a new random key, printed once; keep it to play the archive
+    #[cfg(feature = "encryption")]
+    #[arg(long, default_value_t = false, conflicts_with = "cvid")]
+    generate_key: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct StreamArgs {
+    /// Capture device: /dev/video0 for v4l2, an index such as 0 for avfoundation, video=<name> for dshow
+    device: String,
+    /// ffmpeg input format of the device [default: v4l2, avfoundation on macOS, dshow on Windows]
+    #[arg(long)]
+    input_format: Option<String>,
+    /// Frames per second to capture at
+    #[arg(long, default_value_t = 30)]
+    fps: u32,
+    /// Capture size, WIDTHxHEIGHT
+    #[arg(long, default_value = "640x480")]
+    size: String,
+    /// Number of columns [default: the terminal's width]
+    #[arg(long)]
+    columns: Option<u32>,
+    /// Draw the characters without their colors
+    #[arg(long, default_value_t = false)]
+    no_color: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct EmbedArgs {
+    /// Directory of frame_*.cframe / frame_*.txt files, or a .casc archive
+    dir: PathBuf,
+    /// Source file to write [default: the directory's path with a .rs or .h extension]
+    output: Option<PathBuf>,
+    /// Language of the generated source
+    #[arg(long, value_enum, default_value = "rust")]
+    lang: EmbedLanguageArg,
+    /// Name of the generated array and constants
+    #[arg(long, default_value = "frames")]
+    name: String,
+    /// Frame rate to record [default: the fps in details.toml, if any]
+    #[arg(long)]
+    fps: Option<u32>,
+    /// Run-length encode the frames and emit a decoder; Rust includes them from a .bin file written next to the source
+    #[arg(long, default_value_t = false)]
+    compress: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct AnsiArtArgs {
+    /// Directory of frame_*.cframe / frame_*.txt files, or a .casc archive
+    dir: PathBuf,
+    /// Directory to write the files to [default: the directory's path with an _ansi suffix]
+    output: Option<PathBuf>,
+    /// File format to write
+    #[arg(long, value_enum, default_value = "ans")]
+    format: LegacyFormatArg,
+    /// Fail on characters code page 437 has no glyph for, instead of substituting lookalikes
+    #[arg(long, default_value_t = false)]
+    strict_cp437: bool,
+    /// Allow bright background colors (iCE colors) instead of only the 8 dark ones
+    #[arg(long, default_value_t = false)]
+    ice_colors: bool,
+    /// SAUCE title (up to 35 characters)
+    #[arg(long, default_value = "")]
+    title: String,
+    /// SAUCE author (up to 20 characters)
+    #[arg(long, default_value = "")]
+    author: String,
+    /// SAUCE group (up to 20 characters)
+    #[arg(long, default_value = "")]
+    group: String,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LegacyFormatArg {
+    Ans,
+    Xbin,
+}
+
+impl From<LegacyFormatArg> for LegacyFormat {
+    fn from(value: LegacyFormatArg) -> Self {
+        match value {
+            LegacyFormatArg::Ans => Self::Ans,
+            LegacyFormatArg::Xbin => Self::XBin,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct TeletextArgs {
+    /// Directory of frame_*.cframe / frame_*.txt files, or a .casc archive
+    dir: PathBuf,
+    /// Directory to write the pages to [default: the directory's path with a _teletext suffix]
+    output: Option<PathBuf>,
+    /// File format to write
+    #[arg(long, value_enum, default_value = "tti")]
+    format: TeletextFormatArg,
+    /// Page number, magazine first, in hex (100 to 8FF)
+    #[arg(long, default_value = "100", value_parser = parse_teletext_page)]
+    page: u16,
+    /// Page description, the header row text in .t42 files
+    #[arg(long, default_value = "")]
+    description: String,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum TeletextFormatArg {
+    Tti,
+    T42,
+}
+
+impl From<TeletextFormatArg> for TeletextFormat {
+    fn from(value: TeletextFormatArg) -> Self {
+        match value {
+            TeletextFormatArg::Tti => Self::Tti,
+            TeletextFormatArg::T42 => Self::T42,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct LedArgs {
+    /// Directory of frame_*.cframe / frame_*.txt files, or a .casc archive
+    dir: PathBuf,
+    /// Host name or IP address of the controller
+    host: String,
+    /// UDP port [default: 4048 for DDP, 5568 for E1.31]
+    #[arg(long)]
+    port: Option<u16>,
+    /// Protocol the controller takes
+    #[arg(long, value_enum, default_value = "ddp")]
+    protocol: LedProtocolArg,
+    /// Matrix size in LEDs, WIDTHxHEIGHT
+    #[arg(long, default_value = "64x32")]
+    size: String,
+    /// Odd rows are wired right to left
+    #[arg(long, default_value_t = false)]
+    serpentine: bool,
+    /// Draw the glyphs and scale them down instead of lighting one color per cell
+    #[arg(long, default_value_t = false)]
+    rendered: bool,
+    /// Scale of every LED level (0-1)
+    #[arg(long, default_value_t = 1.0)]
+    brightness: f32,
+    /// Dim frames whose average LED level goes over this share of full white (0-1)
+    #[arg(long)]
+    max_average: Option<f32>,
+    /// Frames per second [default: the fps in details.toml, else 30]
+    #[arg(long)]
+    fps: Option<u32>,
+    /// First E1.31 universe
+    #[arg(long, default_value_t = 1)]
+    universe: u16,
+    /// Start over at the end instead of stopping
+    #[arg(long, default_value_t = false)]
+    repeat: bool,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LedProtocolArg {
+    Ddp,
+    E131,
+}
+
+impl From<LedProtocolArg> for LedProtocol {
+    fn from(value: LedProtocolArg) -> Self {
+        match value {
+            LedProtocolArg::Ddp => Self::Ddp,
+            LedProtocolArg::E131 => Self::E131,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum EmbedLanguageArg {
+    Rust,
+    C,
+}
+
+impl From<EmbedLanguageArg> for EmbedLanguage {
+    fn from(value: EmbedLanguageArg) -> Self {
+        match value {
+            EmbedLanguageArg::Rust => Self::Rust,
+            EmbedLanguageArg::C => Self::C,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, ValueEnum)]
@@ -49,6 +375,7 @@ enum LoopModeArg {
     ExactText,
     VisualText,
     VisualTextAndColor,
+    PerceptualHash,
 }
 
 impl From<LoopModeArg> for LoopMatchMode {
@@ -57,6 +384,301 @@ impl From<LoopModeArg> for LoopMatchMode {
             LoopModeArg::ExactText => Self::ExactText,
             LoopModeArg::VisualText => Self::VisualText,
             LoopModeArg::VisualTextAndColor => Self::VisualTextAndColor,
+            LoopModeArg::PerceptualHash => Self::PerceptualHash,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum PaletteArg {
+    Truecolor,
+    Deuteranopia,
+    Protanopia,
+    HighContrast,
+}
+
+impl From<PaletteArg> for Palette {
+    fn from(value: PaletteArg) -> Self {
+        match value {
+            PaletteArg::Truecolor => Self::Truecolor,
+            PaletteArg::Deuteranopia => Self::Deuteranopia,
+            PaletteArg::Protanopia => Self::Protanopia,
+            PaletteArg::HighContrast => Self::HighContrast,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum UpscaleArg {
+    Allow,
+    Warn,
+    Cap,
+    Integer,
+}
+
+impl From<UpscaleArg> for UpscalePolicy {
+    fn from(value: UpscaleArg) -> Self {
+        match value {
+            UpscaleArg::Allow => Self::Allow,
+            UpscaleArg::Warn => Self::Warn,
+            UpscaleArg::Cap => Self::Cap,
+            UpscaleArg::Integer => Self::Integer,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum CodepageArg {
+    Unicode,
+    Cp437,
+}
+
+impl From<CodepageArg> for Codepage {
+    fn from(value: CodepageArg) -> Self {
+        match value {
+            CodepageArg::Unicode => Self::Unicode,
+            CodepageArg::Cp437 => Self::Cp437,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum DitherArg {
+    None,
+    FloydSteinberg,
+    Ordered,
+    Noise,
+}
+
+impl From<DitherArg> for DitherMode {
+    fn from(value: DitherArg) -> Self {
+        match value {
+            DitherArg::None => Self::None,
+            DitherArg::FloydSteinberg => Self::FloydSteinberg,
+            DitherArg::Ordered => Self::Ordered,
+            DitherArg::Noise => Self::Noise,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum GlyphMatchArg {
+    Luminance,
+    Mse,
+    Ssim,
+}
+
+impl From<GlyphMatchArg> for GlyphMatch {
+    fn from(value: GlyphMatchArg) -> Self {
+        match value {
+            GlyphMatchArg::Luminance => Self::Luminance,
+            GlyphMatchArg::Mse => Self::Mse,
+            GlyphMatchArg::Ssim => Self::Ssim,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LuminanceModelArg {
+    Luma,
+    Rec601,
+    Rec709,
+    Average,
+    Lightness,
+}
+
+impl From<LuminanceModelArg> for LuminanceModel {
+    fn from(value: LuminanceModelArg) -> Self {
+        match value {
+            LuminanceModelArg::Luma => Self::Luma,
+            LuminanceModelArg::Rec601 => Self::Rec601,
+            LuminanceModelArg::Rec709 => Self::Rec709,
+            LuminanceModelArg::Average => Self::Average,
+            LuminanceModelArg::Lightness => Self::Lightness,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum FpsModeArg {
+    Drop,
+    Blend,
+}
+
+impl From<FpsModeArg> for FpsMode {
+    fn from(value: FpsModeArg) -> Self {
+        match value {
+            FpsModeArg::Drop => Self::Drop,
+            FpsModeArg::Blend => Self::Blend,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum PipeFormatArg {
+    Raw,
+    Png,
+    Y4m,
+}
+
+impl From<PipeFormatArg> for PipeFormat {
+    fn from(value: PipeFormatArg) -> Self {
+        match value {
+            PipeFormatArg::Raw => Self::Raw,
+            PipeFormatArg::Png => Self::Png,
+            PipeFormatArg::Y4m => Self::Y4m,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum YuvMatrixArg {
+    Auto,
+    Bt601,
+    Bt709,
+}
+
+impl YuvMatrixArg {
+    fn matrix(self) -> Option<YuvMatrix> {
+        match self {
+            Self::Auto => None,
+            Self::Bt601 => Some(YuvMatrix::Bt601),
+            Self::Bt709 => Some(YuvMatrix::Bt709),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum AnaglyphArg {
+    Color,
+    Gray,
+    Dubois,
+}
+
+impl From<AnaglyphArg> for Anaglyph {
+    fn from(value: AnaglyphArg) -> Self {
+        match value {
+            AnaglyphArg::Color => Self::Color,
+            AnaglyphArg::Gray => Self::Gray,
+            AnaglyphArg::Dubois => Self::Dubois,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LineFitArg {
+    Downscale,
+    Crop,
+    Wrap,
+    Carve,
+}
+
+impl From<LineFitArg> for LineFit {
+    fn from(value: LineFitArg) -> Self {
+        match value {
+            LineFitArg::Downscale => Self::Downscale,
+            LineFitArg::Crop => Self::Crop,
+            LineFitArg::Wrap => Self::Wrap,
+            LineFitArg::Carve => Self::Carve,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum DepthModeArg {
+    Density,
+    Color,
+    Both,
+}
+
+impl From<DepthModeArg> for DepthMode {
+    fn from(value: DepthModeArg) -> Self {
+        match value {
+            DepthModeArg::Density => Self::Density,
+            DepthModeArg::Color => Self::Color,
+            DepthModeArg::Both => Self::Both,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ToneMapArg {
+    Hable,
+    Reinhard,
+    Mobius,
+    Clip,
+    Off,
+}
+
+impl ToneMapArg {
+    fn tone_map(self, exposure: f32) -> Option<ToneMap> {
+        let curve = match self {
+            Self::Hable => ToneMapCurve::Hable,
+            Self::Reinhard => ToneMapCurve::Reinhard,
+            Self::Mobius => ToneMapCurve::Mobius,
+            Self::Clip => ToneMapCurve::Clip,
+            Self::Off => return None,
+        };
+        Some(ToneMap {curve, exposure})
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum AudioPaddingArg {
+    Auto,
+    Silence,
+    Loop,
+    TrimVideo,
+}
+
+impl From<AudioPaddingArg> for AudioPadding {
+    fn from(value: AudioPaddingArg) -> Self {
+        match value {
+            AudioPaddingArg::Auto => Self::Auto,
+            AudioPaddingArg::Silence => Self::Silence,
+            AudioPaddingArg::Loop => Self::Loop,
+            AudioPaddingArg::TrimVideo => Self::TrimVideo,
+        }
+    }
+}
+
+/// `--font-ratio` value: a number or target name, or `auto` to pick the target from the kind of output
+#[derive(Clone, Copy, Debug)]
+enum FontRatioArg {
+    Auto,
+    Ratio(FontRatio),
+}
+
+impl FontRatioArg {
+    fn resolve(self, to_video: bool) -> f32 {
+        match self {
+            Self::Auto => if to_video {FontTarget::Video} else {FontTarget::Terminal}.font_ratio(),
+            Self::Ratio(ratio) => ratio.resolve(),
+        }
+    }
+}
+
+impl std::str::FromStr for FontRatioArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {Ok(Self::Auto)} else {s.parse().map(Self::Ratio).map_err(|err| format!("{}, or auto", err))}
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ReportFormatArg {
+    Toml,
+    Json,
+    Html,
+}
+
+impl From<ReportFormatArg> for ReportFormat {
+    fn from(value: ReportFormatArg) -> Self {
+        match value {
+            ReportFormatArg::Toml => Self::Toml,
+            ReportFormatArg::Json => Self::Json,
+            ReportFormatArg::Html => Self::Html,
         }
     }
 }
@@ -81,14 +703,60 @@ struct Args {
     #[arg(long)]
     fps: Option<u32>,
 
-    /// Font aspect ratio (character width:height)
+    /// How to reach --fps from a higher source rate: drop surplus frames, or blend neighbouring frames together
+    #[arg(long, value_enum, default_value = "drop")]
+    fps_mode: FpsModeArg,
+
+    /// Deinterlace the video with yadif before converting (interlaced DVD/TV sources)
+    #[arg(long, default_value_t = false)]
+    deinterlace: bool,
+
+    /// Convert video frames as ffmpeg decodes them instead of extracting them to temporary PNGs first (much faster on long videos)
+    #[arg(long, default_value_t = false)]
+    pipe_frames: bool,
+
+    /// What piped frames are read as: raw RGB, PNGs, or a YUV4MPEG2 stream; png and y4m need no probe run and are decoded on the conversion threads in parallel; implies --pipe-frames
+    #[arg(long, value_enum)]
+    pipe_format: Option<PipeFormatArg>,
+
+    /// Matrix of the YUV frames --pipe-format y4m reads: auto takes the source's color space from ffprobe (BT.601 when untagged)
+    #[arg(long, value_enum, default_value = "auto")]
+    yuv_matrix: YuvMatrixArg,
+
+    /// Tone mapping curve for HDR (PQ/HLG) video, detected with ffprobe; off converts HDR as it decodes
+    #[arg(long, value_enum, default_value = "hable")]
+    tonemap: ToneMapArg,
+
+    /// Exposure in stops applied to HDR video before tone mapping (positive brightens)
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    exposure: f32,
+
+    /// Font aspect ratio (character width:height), or the target to suit: terminal (0.5), video (0.55), html (0.6), or auto (video with --to-video, terminal otherwise)
     #[arg(long)]
-    font_ratio: Option<f32>,
+    font_ratio: Option<FontRatioArg>,
 
     /// Use default quality preset
     #[arg(long, default_value_t = false, conflicts_with_all = &["small", "large"])]
     default: bool,
 
+    /// Use a destination profile (e.g. discord, tiktok, terminal-80col; see --list-profiles).
+    /// Skips prompts; explicit flags still override profile values.
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// List available profiles and exit
+    #[arg(long, default_value_t = false)]
+    list_profiles: bool,
+
+    /// Show the ffmpeg and ffprobe versions found and the encoders cascii uses, then exit
+    #[arg(long, default_value_t = false)]
+    check_ffmpeg: bool,
+
+    /// Pick columns, font ratio, luminance, charset and preprocessing in a full-screen wizard with a live preview
+    #[cfg(feature = "tui")]
+    #[arg(long, default_value_t = false)]
+    tui: bool,
+
     /// Use smaller default values for quality settings
     #[arg(long, short, default_value_t = false, conflicts_with_all = &["default", "large"])]
     small: bool,
@@ -105,29 +773,109 @@ struct Args {
     #[arg(long, default_value_t = false)]
     log_details: bool,
 
+    /// Don't write the events.jsonl conversion log
+    #[arg(long, default_value_t = false)]
+    no_events: bool,
+
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    #[command(flatten)]
+    show: ShowTargetArgs,
+
+    /// Memory, in MB, video rendering may hold in frames at once; lower it for very wide frames [default: 512]
+    #[arg(long)]
+    memory_budget: Option<u64>,
+
+    /// Convert a few sample frames to estimate the output size and conversion time, then exit without writing any output
+    #[arg(long, default_value_t = false, conflicts_with = "to_video")]
+    dry_run: bool,
+
+    /// Convert a few sample frames with the selected options and with those of a config preset, write them side by side as compare_NN.png in the output directory with quality scores, then exit
+    #[arg(long, value_name = "PRESET", conflicts_with_all = ["to_video", "dry_run"])]
+    compare_preset: Option<String>,
+
+    /// Format of the details report written next to the frames
+    #[arg(long, value_enum, default_value = "toml", conflicts_with = "report_template")]
+    report_format: ReportFormatArg,
+
+    /// Template file for the details report; `{{placeholder}}` fields are filled from the conversion result
+    #[arg(long)]
+    report_template: Option<PathBuf>,
+
+    /// Keep the frames (or partial video) completed so far when the run is stopped with Ctrl-C/SIGTERM
+    #[arg(long, default_value_t = false)]
+    keep_partial: bool,
+
+    /// Keep the frames an interrupted --keep-partial run already wrote to the output directory and convert only the rest
+    #[arg(long, default_value_t = false)]
+    resume: bool,
+
     /// Keep intermediate image files
     #[arg(long, default_value_t = false)]
     keep_images: bool,
 
     /// Generate both .txt and .cframe (color) files
-    #[arg(long, default_value_t = false, conflicts_with = "color_only")]
+    #[arg(long, default_value_t = false, conflicts_with_all = ["color_only", "ansi", "ansi_256"])]
     colors: bool,
 
     /// Generate only .cframe (color) files, no .txt
-    #[arg(long, default_value_t = false, conflicts_with = "colors")]
+    #[arg(long, default_value_t = false, conflicts_with_all = ["colors", "ansi", "ansi_256"])]
     color_only: bool,
 
+    /// Generate only .ans files: the text with 24-bit color escapes, to `cat` to a terminal
+    #[arg(long, default_value_t = false, conflicts_with = "ansi_256")]
+    ansi: bool,
+
+    /// Generate only .ans files with xterm 256-color escapes, for terminals without truecolor
+    #[arg(long = "ansi-256", default_value_t = false)]
+    ansi_256: bool,
+
+    /// Generate only .json files: each frame's size and every cell with its character and colors, for web apps and data pipelines
+    #[arg(long, default_value_t = false, conflicts_with_all = ["colors", "color_only", "ansi", "ansi_256"])]
+    json: bool,
+
     /// Render ASCII frames into a video file (mp4) instead of frame files
     #[arg(long, default_value_t = false)]
     to_video: bool,
 
-    /// Font size in pixels for --to-video rendering (determines output resolution)
-    #[arg(long, default_value_t = 14.0)]
-    video_font_size: f32,
+    /// Font size in pixels for --to-video rendering (determines output resolution) [default: 14]
+    #[arg(long)]
+    video_font_size: Option<f32>,
+
+    /// CRF quality for --to-video encoding (0-51, lower = better, 18 = visually lossless) [default: 18]
+    #[arg(long)]
+    crf: Option<u8>,
+
+    /// Shift the muxed --to-video audio by this many seconds (positive delays it, negative skips its start)
+    #[arg(long, allow_negative_numbers = true)]
+    audio_offset: Option<f64>,
+
+    /// When the --to-video audio is shorter than the video: retime slightly or pad (auto), pad with silence, loop the audio, or cut the video
+    #[arg(long, value_enum, default_value = "auto")]
+    audio_padding: AudioPaddingArg,
+
+    /// Copy the source audio into the --to-video output instead of re-encoding it
+    #[arg(long, default_value_t = false)]
+    audio_copy: bool,
+
+    /// Speak these captions (JSON or SRT, as for --captions) with a text-to-speech command and mux the narration into the --to-video output as its own audio track
+    #[arg(long, value_name = "CAPTIONS", requires = "to_video")]
+    narration: Option<PathBuf>,
+
+    /// Text-to-speech program narrating captions [default: espeak-ng]
+    #[arg(long, value_name = "PROGRAM", requires = "narration")]
+    tts_command: Option<PathBuf>,
+
+    /// Argument of the text-to-speech program, repeated for each; {text} is the caption and {output} the WAV file to write [default: -w {output} {text}]
+    #[arg(long = "tts-arg", value_name = "ARG", allow_hyphen_values = true, requires = "narration")]
+    tts_args: Vec<String>,
 
-    /// CRF quality for --to-video encoding (0-51, lower = better, 18 = visually lossless)
-    #[arg(long, default_value_t = 18)]
-    crf: u8,
+    /// Background color of --to-video renders as #rrggbb [default: #000000]
+    #[arg(long, value_parser = parse_rgb)]
+    video_background: Option<(u8, u8, u8)>,
+
+    /// Glyph color of text-only --to-video renders as #rrggbb, e.g. #33ff66 for a green terminal [default: #ffffff]
+    #[arg(long, value_parser = parse_rgb)]
+    video_foreground: Option<(u8, u8, u8)>,
 
     /// Experimental option C: fit per-cell foreground/background colors for direct video rendering
     #[arg(long, default_value_t = false, conflicts_with = "fit_cell_backgrounds_optimized")]
@@ -152,6 +900,139 @@ struct Args {
     #[arg(long, default_value_t = false, conflicts_with = "fast")]
     fidelity: bool,
 
+    /// Accessibility palette applied to every cell color (color-blind-safe or high-contrast)
+    #[arg(long, value_enum)]
+    palette: Option<PaletteArg>,
+
+    /// Write every sampled pixel as two identical characters so pixels come out square (--font-ratio is ignored); best for pixel art
+    #[arg(long, default_value_t = false)]
+    square_pixels: bool,
+
+    /// Pixel-art mode: nearest-neighbor resampling for exact colors, and one cell per source pixel when the image is narrower than --columns
+    #[arg(long, default_value_t = false)]
+    pixel_art: bool,
+
+    /// Treat the input image as a sprite sheet of WIDTHxHEIGHT pixel tiles and convert each tile, left to right and top to bottom, into a frame of an animation at --fps
+    #[arg(long, value_name = "WxH")]
+    sprite_sheet: Option<String>,
+
+    /// When --columns is wider than the source: upscale anyway (allow), upscale with a warning, cap at the source width, or scale by a whole multiple (integer)
+    #[arg(long, value_enum)]
+    upscale: Option<UpscaleArg>,
+
+    /// Channel weights of the luminance that picks characters: rec709, red, green, blue, green-screen (ignores green), or R,G,B such as 1,0,0
+    #[arg(long)]
+    luminance_weights: Option<LuminanceWeights>,
+
+    /// Luminance formula picking each character: luma (the --luminance-weights average), rec601, rec709, average or lightness (CIE L*)
+    #[arg(long, value_enum)]
+    luminance_model: Option<LuminanceModelArg>,
+
+    /// Treat the input as side-by-side stereo (left view first) and convert a red/cyan anaglyph: color, gray or dubois
+    #[arg(long, value_enum)]
+    anaglyph: Option<AnaglyphArg>,
+
+    /// What transparent pixels become: ignore (convert the hidden colors), space, luminance (opacity picks the characters), or a #rrggbb background to composite over
+    #[arg(long)]
+    alpha_mode: Option<AlphaMode>,
+
+    /// Detect QR codes and fiducial markers and redraw them as crisp black and white cells so they stay scannable
+    #[arg(long, default_value_t = false)]
+    preserve_markers: bool,
+
+    /// Draw braille dot patterns (2x4 dots per cell) instead of the ASCII ramp, for 8 times the detail at the same --columns
+    #[arg(long, default_value_t = false, conflicts_with_all = ["fit_cell_backgrounds", "fit_cell_backgrounds_optimized", "preserve_markers"])]
+    braille: bool,
+
+    /// Draw '▀' in every cell with the top pixel as foreground and the bottom one as background, for twice the rows of colored output
+    #[arg(long, default_value_t = false, conflicts_with_all = ["fit_cell_backgrounds", "fit_cell_backgrounds_optimized", "preserve_markers", "braille", "quarter_block"])]
+    half_block: bool,
+
+    /// Draw quadrant block characters splitting every cell's 2x2 pixels into a foreground and a background color
+    #[arg(long, default_value_t = false, conflicts_with_all = ["fit_cell_backgrounds", "fit_cell_backgrounds_optimized", "preserve_markers", "braille"])]
+    quarter_block: bool,
+
+    /// Draw sextant characters splitting every cell's 2x3 pixels into a foreground and a background color, the cell shape of teletext mosaics
+    #[arg(long, default_value_t = false, conflicts_with_all = ["fit_cell_backgrounds", "fit_cell_backgrounds_optimized", "preserve_markers", "braille", "half_block", "quarter_block"])]
+    sextant: bool,
+
+    /// Dither luminance across the character ramp so smooth gradients don't band: floyd-steinberg (closest to the source), ordered (stable between video frames) or noise (random grain, from --seed)
+    #[arg(long)]
+    dither: Option<DitherArg>,
+
+    /// Keep the output to a code page: cp437 for DOS and BBS terminals (braille and quadrants become shades, .ans files are CP437 bytes, and the default ramp becomes " .:-=░▒▓█")
+    #[arg(long, value_enum)]
+    codepage: Option<CodepageArg>,
+
+    /// Write .cframe files in the v2 container, which records the fps, ramp and font ratio (older cascii versions and other .cframe readers only read v1)
+    #[arg(long, default_value_t = false)]
+    cframe_v2: bool,
+
+    /// Write zlib-compressed v2 .cframe files, typically a fraction of the size
+    #[cfg(feature = "zlib")]
+    #[arg(long, default_value_t = false)]
+    compress_cframes: bool,
+
+    /// How each character is picked: luminance (its place in the ramp), or by comparing the cell with the glyph shapes, mse or ssim (sharper edges and lines, several times slower)
+    #[arg(long, value_enum, conflicts_with_all = ["fit_cell_backgrounds", "fit_cell_backgrounds_optimized", "braille", "half_block", "quarter_block", "sextant"])]
+    glyph_match: Option<GlyphMatchArg>,
+
+    /// Seed of every random choice the conversion makes, recorded in details.toml so it can be reproduced
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Add to every channel before characters are picked, from -1 (black) to 1 (white)
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    brightness: f32,
+
+    /// Scale every channel's distance from mid gray: above 1 adds contrast, below 1 flattens
+    #[arg(long, default_value_t = 1.0, allow_negative_numbers = true)]
+    contrast: f32,
+
+    /// Gamma-correct every channel after brightness and contrast: above 1 lifts the midtones, below 1 darkens them
+    #[arg(long, default_value_t = 1.0)]
+    gamma: f32,
+
+    /// Invert every channel, so dark areas get the dense characters
+    #[arg(long, default_value_t = false)]
+    invert: bool,
+
+    /// Longest line a frame may have, for platforms that wrap or clip wide preformatted text
+    #[arg(long)]
+    max_line_length: Option<u32>,
+
+    /// Most lines a frame may have
+    #[arg(long)]
+    max_lines: Option<u32>,
+
+    /// How frames over --max-line-length/--max-lines are fitted: downscale (fewer columns), crop (a window centered on the subject), wrap (split rows into several lines) or carve (experimental seam carving that removes low-detail columns and rows)
+    #[arg(long, value_enum, default_value = "downscale")]
+    line_fit: LineFitArg,
+
+    /// Depth map aligned with the input (an image, or a video for video inputs); white is near, black is far
+    #[arg(long)]
+    depth: Option<PathBuf>,
+
+    /// What the depth map changes: density (far cells get sparser characters), color (far cells get darker) or both
+    #[arg(long, value_enum, default_value = "density")]
+    depth_mode: DepthModeArg,
+
+    /// Read the depth map as distance: black is near, white is far
+    #[arg(long, default_value_t = false)]
+    depth_invert: bool,
+
+    /// Captions drawn into video frames: a JSON array of {text, start, end, position} or an SRT file
+    #[arg(long)]
+    captions: Option<PathBuf>,
+
+    /// Font file (TTF/OTF) the output will be shown in: reorder the character ramp by each glyph's measured ink in that font
+    #[arg(long)]
+    calibrate_font: Option<PathBuf>,
+
+    /// Build a character set of this many evenly spaced ink levels from the --calibrate-font font, instead of reordering the configured one
+    #[arg(long, requires = "calibrate_font")]
+    charset_levels: Option<usize>,
+
     /// Extract audio from video to audio.mp3
     #[arg(long, default_value_t = false)]
     audio: bool,
@@ -201,6 +1082,22 @@ struct Args {
     #[arg(long)]
     loop_threshold: Option<f32>,
 
+    /// Most bits (of 64) in which two frames' perceptual hashes may differ for --loop-mode perceptual-hash to match them
+    #[arg(long)]
+    loop_hash_distance: Option<u32>,
+
+    /// Print the perceptual hash of every frame in a frames directory as JSON lines and exit
+    #[arg(long, default_value_t = false)]
+    frame_hashes: bool,
+
+    /// Write a reference image to the input path (a .png) and exit: gradient, bars or wedge. Convert it to tune the charset, luminance and font ratio
+    #[arg(long)]
+    test_pattern: Option<TestPattern>,
+
+    /// Size of the --test-pattern image in pixels
+    #[arg(long, num_args = 2, value_names = ["WIDTH", "HEIGHT"], default_values_t = [1280, 720])]
+    test_pattern_size: Vec<u32>,
+
     /// Trim equally from all sides (overridden by directional trims)
     #[arg(long)]
     trim: Option<usize>,
@@ -224,6 +1121,193 @@ struct Args {
     /// Output directory for trim: copy frames here before cropping instead of trimming in-place
     #[arg(long)]
     trim_output: Option<PathBuf>,
+
+    /// Trim or repeat loops without first backing up the frames for --undo
+    #[arg(long, default_value_t = false, conflicts_with = "trim_output")]
+    in_place: bool,
+
+    /// Restore the frames of the input directory as they were before its last trim or loop repeat
+    #[arg(long, default_value_t = false)]
+    undo: bool,
+}
+
+/// Print a warning and record it in the event log.
+fn warn(event_log: Option<&EventLog>, message: &str) {
+    eprintln!("warning: {}", message);
+    if let Some(event_log) = event_log {
+        event_log.warn(message);
+    }
+}
+
+/// Lock `dir` against other cascii processes, reporting a stale lock that had to be replaced.
+fn lock_dir(dir: &Path, event_log: Option<&EventLog>) -> Result<OutputLock> {
+    let lock = OutputLock::acquire(dir)?;
+    match lock.stale_pid() {
+        Some(0) => warn(event_log, &format!("replaced an unreadable stale lock on {}; a previous run may have left incomplete output", dir.display())),
+        Some(pid) => warn(event_log, &format!("replaced a stale lock on {} left by process {}, which is no longer running; its output may be incomplete", dir.display(), pid)),
+        None => {}
+    }
+    Ok(lock)
+}
+
+/// Cancel the returned token on the first Ctrl-C/SIGTERM; a second signal exits immediately.
+fn install_signal_handler() -> Result<CancelToken> {
+    let token = CancelToken::new();
+    let handler_token = token.clone();
+    ctrlc::set_handler(move || {
+        if handler_token.is_cancelled() {
+            std::process::exit(130);
+        }
+        handler_token.cancel();
+        eprintln!("\nStopping... (press Ctrl-C again to quit immediately)");
+    })
+    .context("installing signal handler")?;
+    Ok(token)
+}
+
+/// Print an image sized to the terminal, or `--columns` wide
+fn run_show(args: &ShowArgs) -> Result<()> {
+    let image = image::open(&args.image).with_context(|| format!("opening {}", args.image.display()))?;
+    let colors = if args.no_color {None} else {terminal::color_support().filter(|_| terminal::enable_ansi())};
+    // A given width is kept however tall the image comes out
+    let (columns, rows) = match args.columns {
+        Some(columns) => (columns, u32::MAX),
+        None => terminal::size().unwrap_or((80, 24)),
+    };
+    print!("{}", terminal::image_to_terminal_string(&image, &ConversionOptions::default(), columns, rows, colors)?);
+    Ok(())
+}
+
+/// Draw the device's frames in place until Ctrl-C, sized to the terminal unless `--columns` is given.
+fn run_stream(args: &StreamArgs) -> Result<()> {
+    let (width, height) = args.size.split_once('x').and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?))).ok_or_else(|| anyhow!("--size must be WIDTHxHEIGHT, got '{}'", args.size))?;
+    let mut stream_opts = StreamOptions::default().with_fps(args.fps).with_size(width, height);
+    if let Some(format) = &args.input_format {
+        stream_opts = stream_opts.with_input_format(format.clone());
+    }
+    let columns = args.columns.or_else(|| terminal::size().map(|(columns, _)| columns.saturating_sub(1).max(1))).unwrap_or(100);
+    let conv_opts = ConversionOptions::default().with_columns(columns);
+    let converter = AsciiConverter::new().with_cancel_token(install_signal_handler()?);
+
+    let ansi = terminal::enable_ansi();
+    let colors = (ansi && !args.no_color).then_some(AnsiColor::TrueColor);
+    if ansi {
+        print!("\x1b[?25l\x1b[2J");
+    }
+    let result = converter.stream_device(&args.device, &stream_opts, &conv_opts, |frame: AsciiFrame| {
+        let text = match colors {
+            Some(colors) => frame_to_ansi_string(&frame, colors),
+            None => frame.text,
+        };
+        let mut out = std::io::stdout().lock();
+        write!(out, "{}{}", if ansi {"\x1b[H"} else {""}, text).and_then(|_| out.flush()).is_ok()
+    });
+    if ansi {
+        println!("\x1b[0m\x1b[?25h");
+    }
+    let stats = result?;
+    eprintln!("Streamed {} frames ({} dropped while converting)", stats.delivered, stats.dropped);
+    Ok(())
+}
+
+/// Remove `frame_*` frame files, including any cut short by a second Ctrl-C (and, with `images`,
+/// `frame_*.png`), from `dir`, returning how many were removed.
+fn remove_frame_files(dir: &Path, images: bool) -> Result<usize> {
+    let mut removed = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
+            if name.starts_with("frame_") && ((images && name.ends_with(".png")) || name.ends_with(".txt") || name.ends_with(".cframe") || name.ends_with(".ans") || name.ends_with(".json") || name.ends_with(".colors") || name.ends_with(".part")) {
+                fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Number of converted frames in `dir` (a `.txt`/`.cframe` pair counts once).
+fn count_converted_frames(dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {return 0};
+    let stems: HashSet<String> = entries.filter_map(|e| e.ok()).filter_map(|e| e.file_name().to_str().map(str::to_string)).filter(|name| name.starts_with("frame_") && (name.ends_with(".txt") || name.ends_with(".cframe"))).map(|name| name.rsplit_once('.').map_or(name.clone(), |(stem, _)| stem.to_string())).collect();
+    stems.len()
+}
+
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c)) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Parse a teletext page number from `100` to `8FF`, read as hex the way teletext numbers pages,
+/// so `100` is page 0x100 and decimal-looking numbers keep their familiar meaning
+fn parse_teletext_page(s: &str) -> Result<u16, String> {
+    match u16::from_str_radix(s, 16) {
+        Ok(page) if (0x100..0x900).contains(&page) => Ok(page),
+        _ => Err("expected a hex page number from 100 to 8FF".to_string()),
+    }
+}
+
+/// Parse a `#rrggbb` color (the `#` is optional)
+fn parse_rgb(s: &str) -> Result<(u8, u8, u8), String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let channel = |index: usize| hex.get(index..index + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok());
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok((r, g, b)),
+        _ => Err("expected a #rrggbb color".to_string()),
+    }
+}
+
+/// Pass `result` through unless the conversion was stopped by a signal. In that case tidy the
+/// partial `output` (a frame directory or a video file), record what happened, print the command
+/// line that resumes the run (with `--resume`, when it is `resumable` and the frames were kept) or
+/// reruns it, and exit with status 130. `extracted_images` says whether `frame_*.png` files in
+/// `output` were extracted by this run (rather than being the user's input). `lock` is released
+/// before exiting.
+fn exit_if_interrupted<T>(result: cascii::Result<T>, output: &Path, extracted_images: bool, resumable: bool, keep_partial: bool, event_log: Option<&EventLog>, lock: &mut Option<OutputLock>) -> Result<T> {
+    match result {
+        Err(err) if is_cancelled_error(&err) => {}
+        result => return Ok(result?),
+    }
+    let summary = if output.is_dir() {
+        if keep_partial {
+            format!("kept {} completed frames in {}", count_converted_frames(output), output.display())
+        } else {
+            let removed = remove_frame_files(output, extracted_images).unwrap_or(0);
+            format!("removed {} partial frame files from {} (pass --keep-partial to keep them)", removed, output.display())
+        }
+    } else if keep_partial {
+        format!("kept the partial video {} (it may not be playable)", output.display())
+    } else {
+        let _ = fs::remove_file(output);
+        format!("removed the partial video {} (pass --keep-partial to keep it)", output.display())
+    };
+    eprintln!("\nInterrupted: {}.", summary);
+    if let Some(event_log) = event_log {
+        event_log.warn(format!("interrupted by signal: {}", summary));
+        eprintln!("The event log at {} records where the run stopped.", event_log.path().display());
+    }
+    let mut command: Vec<String> = std::env::args().map(|arg| shell_quote(&arg)).collect();
+    if resumable && keep_partial && output.is_dir() {
+        if !command.iter().any(|arg| arg == "--resume") {
+            command.push("--resume".into());
+        }
+        eprintln!("To resume it: {}", command.join(" "));
+    } else {
+        eprintln!("To rerun it from the start: {}", command.join(" "));
+    }
+    drop(lock.take());
+    std::process::exit(130);
+}
+
+/// Tell the user when the muxed audio had to be padded, cut or retimed to match the frames.
+fn report_audio_sync(result: &ConversionResult) {
+    if let Some(message) = result.audio_sync.and_then(|sync| sync.describe()) {
+        eprintln!("note: {}", message);
+    }
 }
 
 fn print_preprocess_presets() {
@@ -234,9 +1318,67 @@ fn print_preprocess_presets() {
     }
 }
 
+fn print_profiles(cfg: &AppConfig) {
+    println!("Available profiles:");
+    let mut names: Vec<&String> = cfg.profiles.keys().collect();
+    names.sort();
+    for name in names {
+        println!("  {:<16} {}", name, cfg.profiles[name].description);
+    }
+}
+
+fn print_ffmpeg_capabilities(capabilities: &FfmpegCapabilities) {
+    let found = |version: &Option<String>| version.as_deref().map_or("not found".to_string(), |version| format!("version {}", version));
+    println!("ffmpeg:  {}", found(&capabilities.ffmpeg_version));
+    println!("ffprobe: {}", found(&capabilities.ffprobe_version));
+    if capabilities.ffmpeg_version.is_some() {
+        for (encoder, used_for) in [("libx264", "--to-video"), ("aac", "--to-video --audio")] {
+            println!("  {:<8} {:<10} {}", encoder, if capabilities.has_encoder(encoder) {"available"} else {"missing"}, used_for);
+        }
+        println!("{} video and {} audio encoders in total", capabilities.video_encoders.len(), capabilities.audio_encoders.len());
+    }
+}
+
+/// Fill every setting the user did not pass explicitly from the selected profile.
+fn apply_profile(args: &mut Args, profile: &Profile) {
+    args.columns = args.columns.or(profile.columns);
+    args.fps = args.fps.or(profile.fps);
+    args.font_ratio = args.font_ratio.or(profile.font_ratio.map(|ratio| FontRatioArg::Ratio(FontRatio::Fixed(ratio))));
+    args.luminance = args.luminance.or(profile.luminance);
+    args.video_font_size = args.video_font_size.or(profile.font_size);
+    args.crf = args.crf.or(profile.crf);
+    args.to_video |= profile.to_video;
+    args.audio |= profile.audio;
+    if !args.colors && !args.color_only && !args.ansi && !args.ansi_256 && !args.json {
+        match profile.output_mode {
+            Some(OutputMode::TextAndColor) => args.colors = true,
+            Some(OutputMode::ColorOnly) => args.color_only = true,
+            Some(OutputMode::Ansi) => args.ansi = true,
+            Some(OutputMode::Ansi256) => args.ansi_256 = true,
+            Some(OutputMode::Json) => args.json = true,
+            _ => {}
+        }
+    }
+}
+
+/// `bytes` in the largest binary unit that keeps it at or above 1
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
 fn main() -> Result<()> {
     let mut args = Args::parse();
-    let is_interactive = !(args.default || args.small || args.large);
+    let is_interactive = !(args.default || args.small || args.large || args.profile.is_some());
 
     // Handle subcommands early
     if let Some(Command::Uninstall) = &args.cmd {
@@ -244,14 +1386,131 @@ fn main() -> Result<()> {
         println!("cascii uninstalled.");
         return Ok(());
     }
+    if let Some(Command::Play(play)) = &args.cmd {
+        let colors = (!play.no_color).then_some(if play.ansi_256 {AnsiColor::Xterm256} else {AnsiColor::TrueColor});
+        let options = PlayOptions::default().with_speed(play.speed).with_repeat(play.repeat).with_colors(colors).with_audio(!play.no_audio);
+        #[cfg(feature = "encryption")]
+        let options = options.with_key(play.key.clone());
+        #[cfg(any(feature = "osc", feature = "mqtt"))]
+        let options = options.with_show_control(play.show.options().map(|show| show.with_bpm(play.bpm).with_beat_offset(std::time::Duration::from_secs_f64(play.beat_offset.max(0.0))).with_scene_threshold(Some(play.scene_threshold))));
+        player::play_frames(&play.dir, play.fps, &options)?;
+        return Ok(());
+    }
+    if let Some(Command::Pack(pack)) = &args.cmd {
+        if pack.cvid {
+            let output = pack.output.clone().unwrap_or_else(|| pack.dir.with_extension(CVID_EXTENSION));
+            let count = write_cvid(&pack.dir, &output)?;
+            println!("Packed {} frames of {} into {}", count, pack.dir.display(), output.display());
+            return Ok(());
+        }
+        let output = pack.output.clone().unwrap_or_else(|| pack.dir.with_extension(ARCHIVE_EXTENSION));
+        #[cfg(feature = "encryption")]
+        if let Some(key) = if pack.generate_key {Some(ArchiveKey::generate())} else {pack.key.clone()} {
+            fs::write(&output, encrypt_full_cframes_from_dir(&pack.dir, &key)?).with_context(|| format!("writing {}", output.display()))?;
+            println!("Packed {} into the encrypted archive {}", pack.dir.display(), output.display());
+            if pack.generate_key {
+                println!("Key (needed to play it): {}", key.to_hex());
+            }
+            return Ok(());
+        }
+        fs::write(&output, pack_full_cframes_from_dir(&pack.dir)?).with_context(|| format!("writing {}", output.display()))?;
+        println!("Packed {} into {}", pack.dir.display(), output.display());
+        return Ok(());
+    }
+    if let Some(Command::Show(show)) = &args.cmd {
+        run_show(show)?;
+        return Ok(());
+    }
+    if let Some(Command::Stream(stream)) = &args.cmd {
+        run_stream(stream)?;
+        return Ok(());
+    }
+    if let Some(Command::Embed(embed)) = &args.cmd {
+        let language = EmbedLanguage::from(embed.lang);
+        let output = embed.output.clone().unwrap_or_else(|| embed.dir.with_extension(language.extension()));
+        let data_path = output.with_extension("bin");
+        let data_file = data_path.file_name().and_then(|name| name.to_str()).ok_or_else(|| anyhow!("{} has no usable file name", output.display()))?;
+        let options = EmbedOptions::default().with_language(language).with_name(embed.name.clone()).with_fps(embed.fps).with_compress(embed.compress).with_data_file(data_file);
+        let embedded = embed_frame_dir(&embed.dir, &options)?;
+        fs::write(&output, &embedded.source).with_context(|| format!("writing {}", output.display()))?;
+        if let Some(data) = &embedded.data {
+            fs::write(&data_path, data).with_context(|| format!("writing {}", data_path.display()))?;
+            println!("Wrote {} and {}", output.display(), data_path.display());
+        } else {
+            println!("Wrote {}", output.display());
+        }
+        return Ok(());
+    }
+    if let Some(Command::AnsiArt(ansi)) = &args.cmd {
+        let output = ansi.output.clone().unwrap_or_else(|| {
+            let mut name = ansi.dir.file_stem().unwrap_or(ansi.dir.as_os_str()).to_os_string();
+            name.push("_ansi");
+            ansi.dir.with_file_name(name)
+        });
+        let mapping = if ansi.strict_cp437 {Cp437Mapping::Strict} else {Cp437Mapping::Nearest};
+        let options = AnsiArtOptions::default().with_format(ansi.format.into()).with_mapping(mapping).with_ice_colors(ansi.ice_colors).with_title(ansi.title.clone()).with_author(ansi.author.clone()).with_group(ansi.group.clone());
+        let count = cascii::ansi_art::export_frame_dir(&ansi.dir, &output, &options)?;
+        println!("Wrote {} .{} files to {}", count, options.format.extension(), output.display());
+        return Ok(());
+    }
+    if let Some(Command::Teletext(teletext)) = &args.cmd {
+        let output = teletext.output.clone().unwrap_or_else(|| {
+            let mut name = teletext.dir.file_stem().unwrap_or(teletext.dir.as_os_str()).to_os_string();
+            name.push("_teletext");
+            teletext.dir.with_file_name(name)
+        });
+        let options = TeletextOptions::default().with_format(teletext.format.into()).with_page(teletext.page).with_description(teletext.description.clone());
+        let count = cascii::teletext::export_frame_dir(&teletext.dir, &output, &options)?;
+        println!("Wrote {} .{} files to {}", count, options.format.extension(), output.display());
+        return Ok(());
+    }
+    if let Some(Command::Led(led)) = &args.cmd {
+        let (width, height) = led.size.split_once('x').and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?))).ok_or_else(|| anyhow!("--size must be WIDTHxHEIGHT, got '{}'", led.size))?;
+        let protocol = LedProtocol::from(led.protocol);
+        let source = if led.rendered {LedSource::Rendered} else {LedSource::Cells};
+        let options = LedOptions::default().with_protocol(protocol).with_source(source).with_size(width, height).with_serpentine(led.serpentine).with_brightness(led.brightness).with_max_average(led.max_average).with_fps(led.fps).with_universe(led.universe);
+        let cancel = install_signal_handler()?;
+        let sent = cascii::led_matrix::stream_frame_dir(&led.dir, (led.host.as_str(), led.port.unwrap_or(protocol.default_port())), &options, led.repeat, Some(&cancel))?;
+        println!("Sent {} frames to {}", sent, led.host);
+        return Ok(());
+    }
 
     if args.list_preprocess_presets {
         print_preprocess_presets();
         return Ok(());
     }
 
+    if args.list_profiles {
+        print_profiles(&load_config()?);
+        return Ok(());
+    }
+
+    if args.check_ffmpeg {
+        print_ffmpeg_capabilities(&FfmpegConfig::new().probe()?);
+        return Ok(());
+    }
+
+    let profile = match args.profile.as_deref() {
+        Some(name) => {
+            let cfg = load_config()?;
+            let profile = cfg.profiles.get(name).cloned().ok_or_else(|| anyhow!("Unknown profile '{}' (see --list-profiles)", name))?;
+            apply_profile(&mut args, &profile);
+            Some(profile)
+        }
+        None => None,
+    };
+
     let preprocess_filter = resolve_preprocess_filter(args.preprocess.as_deref(), args.preprocess_preset.as_deref())?;
 
+    if args.undo {
+        let input_path = args.input.clone().ok_or_else(|| anyhow!("Input path must be provided when using --undo"))?;
+        let dir = if input_path.is_file() {input_path.parent().map(Path::to_path_buf).unwrap_or_default()} else {input_path};
+        let _lock = lock_dir(&dir, None)?;
+        let manifest = backup::undo(&dir)?;
+        println!("Undid {}: restored {} files, removed {}", manifest.operation, manifest.files.len(), manifest.added.len());
+        return Ok(());
+    }
+
     // Handle trimming early and exit
     let any_trim = args.trim.unwrap_or(0) > 0 || args.trim_left.unwrap_or(0) > 0 || args.trim_right.unwrap_or(0) > 0 || args.trim_top.unwrap_or(0) > 0 || args.trim_bottom.unwrap_or(0) > 0;
     if any_trim {
@@ -269,11 +1528,33 @@ fn main() -> Result<()> {
             if !input_path.is_dir() {
                 return Err(anyhow!("--trim-output requires the input to be a directory"));
             }
+            fs::create_dir_all(output_dir).with_context(|| format!("creating output directory {}", output_dir.display()))?;
+            let _lock = lock_dir(output_dir, None)?;
             let result = crop_frames(&input_path, trim_top, trim_bottom, trim_left, trim_right, output_dir)?;
             println!("Trim completed: left={}, right={}, top={}, bottom={} → {} frames written to {} ({}×{})", trim_left, trim_right, trim_top, trim_bottom, result.frame_count, output_dir.display(), result.new_width, result.new_height);
         } else {
+            let _lock = if input_path.is_dir() {Some(lock_dir(&input_path, None)?)} else {None};
+            if !args.in_place {
+                let dir = if input_path.is_dir() {input_path.clone()} else {input_path.parent().map(Path::to_path_buf).unwrap_or_default()};
+                backup::Backup::create(&dir, "trim", &trim_targets(&input_path)?)?;
+            }
             run_trim(&input_path, trim_left, trim_right, trim_top, trim_bottom)?;
-            println!("Trim completed: left={}, right={}, top={}, bottom={}", trim_left, trim_right, trim_top, trim_bottom);
+            println!("Trim completed: left={}, right={}, top={}, bottom={}{}", trim_left, trim_right, trim_top, trim_bottom, if args.in_place {""} else {" (undo with --undo)"});
+        }
+        return Ok(());
+    }
+
+    if let Some(pattern) = args.test_pattern {
+        let output_path = args.input.clone().ok_or_else(|| anyhow!("--test-pattern needs the path of the PNG to write as input"))?;
+        generate_test_pattern(pattern, args.test_pattern_size[0], args.test_pattern_size[1]).save(&output_path).with_context(|| format!("writing {}", output_path.display()))?;
+        println!("Wrote the {} test pattern to {}", pattern.as_str(), output_path.display());
+        return Ok(());
+    }
+
+    if args.frame_hashes {
+        let input_path = args.input.clone().filter(|path| path.is_dir()).ok_or_else(|| anyhow!("--frame-hashes expects a directory containing frame_*.txt or frame_*.cframe files"))?;
+        for frame in frame_hashes(&input_path, &LoopDetectionOptions::default().ascii_ramp)? {
+            println!("{}", serde_json::json!({"frame": frame.frame, "hash": format!("{:016x}", frame.hash)}));
         }
         return Ok(());
     }
@@ -287,7 +1568,7 @@ fn main() -> Result<()> {
         if !input_path.is_dir() {
             return Err(anyhow!("--find-loop expects a directory containing frame_*.txt or frame_*.cframe files"));
         }
-        let mut loop_options = LoopDetectionOptions {mode: args.loop_mode.into(), ..LoopDetectionOptions::default()};
+        let mut loop_options = LoopDetectionOptions {mode: args.loop_mode.into(), backup: !args.in_place, ..LoopDetectionOptions::default()};
         if let Some(minimum_distance) = args.loop_min_distance {
             loop_options.minimum_distance = minimum_distance;
         }
@@ -297,6 +1578,11 @@ fn main() -> Result<()> {
         if let Some(similarity_threshold) = args.loop_threshold {
             loop_options.similarity_threshold = similarity_threshold;
         }
+        if let Some(max_hash_distance) = args.loop_hash_distance {
+            loop_options.max_hash_distance = max_hash_distance;
+        }
+        // Loop editing rewrites the frames in place
+        let _lock = lock_dir(&input_path, None)?;
         run_find_loop_with_options(&input_path, &loop_options)?;
         return Ok(());
     }
@@ -376,7 +1662,32 @@ fn main() -> Result<()> {
 
     // Load config and decide preset
     let cfg = load_config()?;
-    let converter = AsciiConverter::with_config(cfg.clone())?;
+    let report_format = match args.report_template.as_deref() {
+        Some(path) => ReportFormat::Template(ReportTemplate::from_file(path)?),
+        None => ReportFormat::from(args.report_format),
+    };
+    let limits = args.memory_budget.map_or_else(ConverterLimits::default, |mb| ConverterLimits::default().with_memory_budget(mb.saturating_mul(1024 * 1024)));
+    let mut converter = AsciiConverter::builder().with_config(cfg.clone()).with_report_format(report_format.clone()).with_limits(limits).build()?;
+    if args.to_video {
+        // Fail before extracting anything when ffmpeg can't encode the result
+        let encoders: &[&str] = if args.audio {&["libx264", "aac"]} else {&["libx264"]};
+        converter.ffmpeg_config().probe()?.require_encoders(encoders)?;
+    }
+
+    // Image inputs finish in one step; everything else gets an events.jsonl next to its output
+    let event_log = if args.no_events || is_image_input || args.dry_run || args.compare_preset.is_some() {
+        None
+    } else {
+        let events_path = if args.to_video {video_output_path.with_extension("events.jsonl")} else {output_path.join("events.jsonl")};
+        Some(EventLog::open(&events_path)?)
+    };
+    if let Some(event_log) = event_log.as_ref() {
+        converter = converter.with_event_log(event_log.clone());
+    }
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    if let Some(show) = args.show.options() {
+        converter = converter.with_show_control(Arc::new(ShowControl::connect(&show)?));
+    }
 
     let active_preset_name = if args.small {
         "small"
@@ -384,8 +1695,14 @@ fn main() -> Result<()> {
         "large"
     } else if args.default {
         cfg.default_preset.as_str()
+    } else if let Some(profile) = profile.as_ref() {
+        profile.preset.as_deref().unwrap_or(cfg.default_preset.as_str())
+    } else if let Some(info) = input_path.is_file().then(|| converter.probe_media(input_path).ok()).flatten() {
+        // No preset flag: suggest one from the source, keeping the configured default if it can't be probed
+        let name = cfg.suggest_preset_name(&info);
+        println!("Using the '{}' preset for this {}×{} source", name, info.width, info.height);
+        name
     } else {
-        // interactive default uses the configured default preset
         cfg.default_preset.as_str()
     };
 
@@ -394,13 +1711,32 @@ fn main() -> Result<()> {
     let default_fps = active.fps;
     let default_ratio = active.font_ratio;
 
+    // Charset and preprocessing preset chosen in the full-screen wizard
+    #[cfg(feature = "tui")]
+    let (wizard_charset, wizard_preprocess) = if is_interactive && args.tui {
+        let sample = SampleFrame::for_input(input_path, args.start.as_deref(), converter.ffmpeg_config())?;
+        let initial = WizardSettings {columns: args.columns.unwrap_or(default_cols), font_ratio: args.font_ratio.map_or(default_ratio, |ratio| ratio.resolve(args.to_video)), luminance: args.luminance.unwrap_or(active.luminance), ascii_chars: active.ascii_chars.clone().unwrap_or_else(|| cfg.ascii_chars.clone()), preprocess_preset: args.preprocess_preset.clone().or_else(|| active.preprocess_preset.clone())};
+        let Some(chosen) = run_wizard(&sample, &initial, converter.ffmpeg_config())? else {
+            println!("Operation cancelled.");
+            return Ok(());
+        };
+        args.columns = Some(chosen.columns);
+        args.font_ratio = Some(FontRatioArg::Ratio(FontRatio::Fixed(chosen.font_ratio)));
+        args.luminance = Some(chosen.luminance);
+        (Some(chosen.ascii_chars), Some(chosen.preprocess_preset))
+    } else {
+        (None, None)
+    };
+    #[cfg(not(feature = "tui"))]
+    let (wizard_charset, wizard_preprocess): (Option<String>, Option<Option<String>>) = (None, None);
+
     if is_interactive {
         if args.columns.is_none() {
             args.columns = Some(Input::new().with_prompt("Columns (width)").default(default_cols).interact()?);
         }
 
         if args.font_ratio.is_none() {
-            args.font_ratio = Some(Input::new().with_prompt("Font Ratio").default(default_ratio).interact()?);
+            args.font_ratio = Some(FontRatioArg::Ratio(FontRatio::Fixed(Input::new().with_prompt("Font Ratio").default(default_ratio).interact()?)));
         }
 
         if args.luminance.is_none() {
@@ -423,12 +1759,14 @@ fn main() -> Result<()> {
 
     let columns = args.columns.unwrap_or(default_cols);
     let fps = args.fps.unwrap_or(default_fps);
-    let font_ratio = args.font_ratio.unwrap_or(default_ratio);
+    let font_ratio = args.font_ratio.map_or(default_ratio, |ratio| ratio.resolve(args.to_video));
     let luminance = args.luminance.unwrap_or(active.luminance);
 
     // --- Execution ---
-    if !args.to_video {
+    let mut output_lock = None;
+    if !args.to_video && !args.dry_run && args.compare_preset.is_none() {
         fs::create_dir_all(&output_path).context("creating output dir")?;
+        output_lock = Some(lock_dir(&output_path, event_log.as_ref())?);
 
         // Check if output directory already contains frames.
         let has_frames = WalkDir::new(&output_path).min_depth(1).max_depth(1).into_iter().filter_map(Result::ok).any(|e| e.file_name().to_str().is_some_and(|s| s.starts_with("frame_")));
@@ -440,15 +1778,7 @@ fn main() -> Result<()> {
             }
 
             // Clean up existing frames
-            for entry in fs::read_dir(&output_path)? {
-                let entry = entry?;
-                let path = entry.path();
-                if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
-                    if name.starts_with("frame_") && (name.ends_with(".png") || name.ends_with(".txt") || name.ends_with(".cframe") || name.ends_with(".colors")) {
-                        fs::remove_file(path)?;
-                    }
-                }
-            }
+            remove_frame_files(&output_path, true)?;
         }
     }
 
@@ -457,8 +1787,25 @@ fn main() -> Result<()> {
         OutputMode::ColorOnly
     } else if args.colors {
         OutputMode::TextAndColor
+    } else if args.ansi {
+        OutputMode::Ansi
+    } else if args.ansi_256 {
+        OutputMode::Ansi256
+    } else if args.json {
+        OutputMode::Json
+    } else {
+        active.output_mode.clone().unwrap_or(OutputMode::TextOnly)
+    };
+
+    // An explicit --preprocess wins, then the wizard's choice, then --preprocess-preset, then the preset's own preprocessing
+    let preprocess_filter = if args.preprocess.is_some() {
+        preprocess_filter
+    } else if let Some(choice) = wizard_preprocess {
+        choice.as_deref().and_then(find_preprocess_preset).map(|preset| preset.filter.to_string())
+    } else if preprocess_filter.is_some() || !input_path.is_file() {
+        preprocess_filter
     } else {
-        OutputMode::TextOnly
+        resolve_preprocess_filter(active.preprocess.as_deref(), active.preprocess_preset.as_deref()).with_context(|| format!("resolving preprocessing for preset '{}'", active_preset_name))?
     };
 
     let cell_color_mode = if args.fit_cell_backgrounds_optimized {
@@ -470,19 +1817,99 @@ fn main() -> Result<()> {
     };
 
     if cell_color_mode.fits_cell_backgrounds() && matches!(output_mode, OutputMode::TextOnly) && !args.to_video {
-        eprintln!("warning: cell-background fitting has no effect with text-only output; pass --colors or --to-video to use the generated backgrounds.");
+        warn(event_log.as_ref(), "cell-background fitting has no effect with text-only output; pass --colors or --to-video to use the generated backgrounds.");
     }
 
     let bg_fit_quality = if args.fast {BgFitQuality::Fast} else {BgFitQuality::Fidelity};
     if (args.fast || args.fidelity) && !cell_color_mode.fits_cell_backgrounds() {
-        eprintln!("warning: --fast/--fidelity only affect cell-background fitting; pass --fit-cell-backgrounds or --fit-cell-backgrounds-optimized to use them.");
+        warn(event_log.as_ref(), "--fast/--fidelity only affect cell-background fitting; pass --fit-cell-backgrounds or --fit-cell-backgrounds-optimized to use them.");
+    }
+
+    let codepage = args.codepage.map(Codepage::from).unwrap_or_default();
+    #[cfg(feature = "zlib")]
+    let compressed = args.compress_cframes.then_some(CframeCompression::Zlib);
+    #[cfg(not(feature = "zlib"))]
+    let compressed: Option<CframeCompression> = None;
+    let cframe_format = match compressed {
+        Some(compression) => CframeFormat::V2 {compression},
+        None if args.cframe_v2 => CframeFormat::V2 {compression: CframeCompression::None},
+        None => CframeFormat::V1,
+    };
+    let default_ramp = if codepage == Codepage::Cp437 {CP437_RAMP.to_string()} else {cfg.ascii_chars.clone()};
+    let mut ascii_chars = wizard_charset.or_else(|| profile.as_ref().and_then(|p| p.ascii_chars.clone())).or_else(|| active.ascii_chars.clone()).unwrap_or(default_ramp);
+    if let Some(font_path) = &args.calibrate_font {
+        let font = fs::read(font_path).with_context(|| format!("reading font {}", font_path.display()))?;
+        ascii_chars = match args.charset_levels {
+            Some(levels) => build_from_font(&font, levels)?,
+            None => calibrate_from_font(&font, &ascii_chars)?,
+        };
     }
 
     // Create conversion options
-    let conv_opts = ConversionOptions {columns: Some(columns), font_ratio, luminance, bg_luminance: args.bg_luminance, ascii_chars: cfg.ascii_chars.clone(), output_mode: output_mode.clone(), cell_color_mode, bg_fit_quality};
+    let conv_opts = ConversionOptions {columns: Some(columns), font_ratio, luminance, bg_luminance: args.bg_luminance, ascii_chars, output_mode: output_mode.clone(), cell_color_mode, bg_fit_quality, glyph_match: args.glyph_match.map(GlyphMatch::from).unwrap_or_default(), palette: args.palette.map(Palette::from).unwrap_or_default(), square_pixels: args.square_pixels, pixel_art: args.pixel_art, upscale: args.upscale.map(UpscalePolicy::from).unwrap_or_default(), luminance_weights: args.luminance_weights.unwrap_or_default(), luminance_model: args.luminance_model.map(LuminanceModel::from).unwrap_or_default(), anaglyph: args.anaglyph.map(Anaglyph::from), alpha_mode: args.alpha_mode.unwrap_or_default(), preserve_markers: args.preserve_markers, line_limits: LineLimits {max_columns: args.max_line_length, max_rows: args.max_lines, fit: args.line_fit.into()}, char_mode: if args.braille {CharMode::Braille} else if args.half_block {CharMode::HalfBlock} else if args.quarter_block {CharMode::QuarterBlock} else if args.sextant {CharMode::Sextant} else {CharMode::Ascii}, dither: args.dither.map(DitherMode::from).unwrap_or_default(), codepage, cframe_format, brightness: args.brightness, contrast: args.contrast, gamma: args.gamma, invert: args.invert, seed: args.seed};
+    if input_path.is_file() {
+        // The converter only records this in the event log
+        if let Some(message) = converter.upscale_warning(input_path, columns, &conv_opts) {
+            eprintln!("warning: {}", message);
+        }
+    }
+
+    if args.dry_run {
+        let video_opts = VideoOptions {fps, start: args.start.clone(), end: args.end.clone(), columns, preprocess_filter: preprocess_filter.clone(), deinterlace: args.deinterlace, fps_mode: args.fps_mode.into(), tone_map: args.tonemap.tone_map(args.exposure), ..VideoOptions::default()};
+        let estimate = converter.estimate_conversion(input_path, &video_opts, &conv_opts)?;
+        println!("Dry run ({}): {} frame(s), about {} ({} per frame), about {:.1}s to convert; measured on {} sample frame(s), {} worker thread(s)", output_mode.as_str(), estimate.frames, format_bytes(estimate.total_bytes()), format_bytes(estimate.bytes_per_frame), estimate.total_seconds(), estimate.sampled_frames, estimate.threads);
+        return Ok(());
+    }
+
+    if let Some(preset_name) = &args.compare_preset {
+        let preset = cfg.presets.get(preset_name).ok_or_else(|| anyhow!("Unknown preset '{}' for --compare-preset", preset_name))?;
+        let other_opts = ConversionOptions {columns: Some(preset.columns), font_ratio: preset.font_ratio, luminance: preset.luminance, ascii_chars: preset.ascii_chars.clone().unwrap_or_else(|| conv_opts.ascii_chars.clone()), output_mode: preset.output_mode.clone().unwrap_or_else(|| output_mode.clone()), ..conv_opts.clone()};
+        let video_opts = VideoOptions {fps, start: args.start.clone(), end: args.end.clone(), columns, preprocess_filter: preprocess_filter.clone(), deinterlace: args.deinterlace, fps_mode: args.fps_mode.into(), tone_map: args.tonemap.tone_map(args.exposure), ..VideoOptions::default()};
+        let comparison = converter.compare_conversions(input_path, &video_opts, &conv_opts, &other_opts)?;
+        fs::create_dir_all(&output_path).context("creating output dir")?;
+        for (index, frame) in comparison.frames.iter().enumerate() {
+            if std::io::stdout().is_terminal() {
+                print!("{}", frame.side_by_side_ansi(output_mode.ansi_color().unwrap_or_default()));
+            }
+            let path = output_path.join(format!("compare_{:02}.png", index + 1));
+            frame.side_by_side_image(output_mode != OutputMode::TextOnly).with_context(|| format!("drawing {}", path.display()))?.save(&path).with_context(|| format!("writing {}", path.display()))?;
+        }
+        let (current, other) = comparison.mean_metrics()?;
+        println!("Compared on {} sample frame(s), written to {}/compare_*.png (left: selected options, right: preset '{}')", comparison.frames.len(), output_path.display(), preset_name);
+        for (label, metrics) in [("selected", current), (preset_name.as_str(), other)] {
+            println!("  {:<12} PSNR {:>5.2} dB  SSIM {:.3}  {} distinct characters", label, metrics.psnr, metrics.ssim, metrics.distinct_chars);
+        }
+        return Ok(());
+    }
+
+    if args.sprite_sheet.is_some() && !is_image_input {
+        return Err(anyhow!("--sprite-sheet needs a PNG or JPEG image as input"));
+    }
+
+    // From here on Ctrl-C/SIGTERM stop the conversion cleanly instead of killing the process
+    converter = converter.with_cancel_token(install_signal_handler()?).with_resume(args.resume);
+    let depth_options = DepthOptions {mode: args.depth_mode.into(), invert: args.depth_invert};
+    let captions = args.captions.as_deref().map(CaptionTrack::load).transpose()?;
+    // Removed once the video is rendered
+    let narration_file = match args.narration.as_deref() {
+        Some(path) => {
+            println!("Narrating captions...");
+            let tts = TtsConfig {command_path: args.tts_command.clone(), args: args.tts_args.clone()};
+            let guard = TempFileGuard::new(std::env::temp_dir().join(format!("cascii_narration_{}.m4a", std::process::id())));
+            synthesize_narration(&CaptionTrack::load(path)?, &tts, converter.ffmpeg_config(), guard.path())?;
+            Some(guard)
+        }
+        None => None,
+    };
+    let narration = narration_file.as_ref().map(|file| file.path().to_path_buf());
 
     if input_path.is_file() {
-        if is_image_input {
+        if let Some(tile) = args.sprite_sheet.as_deref() {
+            let (tile_width, tile_height) = tile.split_once('x').and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?))).ok_or_else(|| anyhow!("--sprite-sheet must be WIDTHxHEIGHT, got '{}'", tile))?;
+            println!("Converting sprite sheet to ASCII frames...");
+            let result = converter.convert_spritesheet(input_path, tile_width, tile_height, &output_path, fps, &conv_opts)?;
+            println!("Wrote {} frames at {} fps to {}", result.frame_count, fps, output_path.display());
+        } else if is_image_input {
             println!("Converting image to ASCII...");
             let preprocessed_image = if let Some(filter) = preprocess_filter.as_deref() {
                 println!("Applying preprocessing filter before ASCII conversion...");
@@ -491,10 +1918,14 @@ fn main() -> Result<()> {
                 None
             };
             let image_input = preprocessed_image.as_ref().map_or(input_path.as_path(), |f| f.path());
-            converter.convert_image(image_input, &output_path.join(format!("{}.txt", input_path.file_stem().unwrap().to_str().unwrap())), &conv_opts)?;
+            let image_output = output_path.join(format!("{}.txt", input_path.file_stem().unwrap().to_str().unwrap()));
+            match args.depth.as_deref() {
+                Some(depth) => converter.convert_image_with_depth(image_input, depth, &image_output, &conv_opts, depth_options)?,
+                None => converter.convert_image(image_input, &image_output, &conv_opts)?,
+            }
         } else if args.to_video {
-            let video_opts = VideoOptions {fps, start: args.start.clone(), end: args.end.clone(), columns, extract_audio: args.audio, preprocess_filter: preprocess_filter.clone()};
-            let to_video_opts = ToVideoOptions {output_path: video_output_path.clone(), font_size: args.video_font_size, crf: args.crf, mux_audio: args.audio, use_colors: None, text_stroke_width: 0.0};
+            let video_opts = VideoOptions {fps, start: args.start.clone(), end: args.end.clone(), columns, extract_audio: args.audio, preprocess_filter: preprocess_filter.clone(), deinterlace: args.deinterlace, fps_mode: args.fps_mode.into(), tone_map: args.tonemap.tone_map(args.exposure), depth_map: args.depth.clone().map(|path| DepthMap {path, options: depth_options}), captions: captions.clone(), pipe_frames: args.pipe_frames || args.pipe_format.is_some(), pipe_format: args.pipe_format.map_or(PipeFormat::Raw, Into::into), yuv_matrix: args.yuv_matrix.matrix()};
+            let to_video_opts = ToVideoOptions {output_path: video_output_path.clone(), font_size: args.video_font_size.unwrap_or(14.0), crf: args.crf.unwrap_or(18), mux_audio: args.audio, use_colors: None, text_stroke_width: 0.0, audio_offset: args.audio_offset, audio_padding: args.audio_padding.into(), audio_passthrough: args.audio_copy, background: args.video_background.unwrap_or((0, 0, 0)), foreground: args.video_foreground, narration: narration.clone()};
 
             // Create progress bar for multi-phase progress
             let progress_bar: Arc<Mutex<Option<ProgressBar>>> = Arc::new(Mutex::new(None));
@@ -502,7 +1933,7 @@ fn main() -> Result<()> {
             let pb_clone = Arc::clone(&progress_bar);
             let spinner_clone = Arc::clone(&spinner);
 
-            converter.convert_video_to_video(input_path, &video_opts, &conv_opts, &to_video_opts, move |progress: Progress| {
+            let result = converter.convert_video_to_video(input_path, &video_opts, &conv_opts, &to_video_opts, move |progress: Progress| {
                 match progress.phase {
                     ProgressPhase::ExtractingFrames => {
                         let mut sp_guard = spinner_clone.lock().unwrap();
@@ -544,9 +1975,11 @@ fn main() -> Result<()> {
                             pb.set_position(progress.completed as u64);
                         }
                     }
-                    ProgressPhase::ConvertingFrames | ProgressPhase::Complete => {}
+                    _ => {}
                 }
-            })?;
+            });
+            let result = exit_if_interrupted(result, &video_output_path, false, false, args.keep_partial, event_log.as_ref(), &mut output_lock)?;
+            report_audio_sync(&result);
 
             let pb_opt = progress_bar.lock().unwrap().take();
             if let Some(pb) = pb_opt {
@@ -556,14 +1989,14 @@ fn main() -> Result<()> {
             println!("\nASCII video saved to {}", video_output_path.display());
             return Ok(());
         } else {
-            let video_opts = VideoOptions {fps, start: args.start.clone(), end: args.end.clone(), columns, extract_audio: args.audio, preprocess_filter: preprocess_filter.clone()};
+            let video_opts = VideoOptions {fps, start: args.start.clone(), end: args.end.clone(), columns, extract_audio: args.audio, preprocess_filter: preprocess_filter.clone(), deinterlace: args.deinterlace, fps_mode: args.fps_mode.into(), tone_map: args.tonemap.tone_map(args.exposure), depth_map: args.depth.clone().map(|path| DepthMap {path, options: depth_options}), captions: captions.clone(), pipe_frames: args.pipe_frames || args.pipe_format.is_some(), pipe_format: args.pipe_format.map_or(PipeFormat::Raw, Into::into), yuv_matrix: args.yuv_matrix.matrix()};
             // Create progress bar for multi-phase progress
             let progress_bar: Arc<Mutex<Option<ProgressBar>>> = Arc::new(Mutex::new(None));
             let spinner: Arc<Mutex<Option<ProgressBar>>> = Arc::new(Mutex::new(None));
             let pb_clone = Arc::clone(&progress_bar);
             let spinner_clone = Arc::clone(&spinner);
 
-            converter.convert_video_with_detailed_progress(input_path, &output_path, &video_opts, &conv_opts, args.keep_images, move |progress: Progress| {
+            let result = converter.convert_video_with_detailed_progress(input_path, &output_path, &video_opts, &conv_opts, args.keep_images, move |progress: Progress| {
                 match progress.phase {
                     ProgressPhase::ExtractingFrames => {
                         // Show spinner for indeterminate extraction phase
@@ -608,11 +2041,11 @@ fn main() -> Result<()> {
                             pb.set_position(progress.completed as u64);
                         }
                     }
-                    ProgressPhase::RenderingVideo | ProgressPhase::Complete => {
-                        // Not used in non-to-video mode
-                    }
+                    // Rendering isn't used in non-to-video mode
+                    _ => {}
                 }
-            })?;
+            });
+            exit_if_interrupted(result, &output_path, true, video_opts.resumable(), args.keep_partial, event_log.as_ref(), &mut output_lock)?;
 
             // Finish the progress bar
             let pb_opt = progress_bar.lock().unwrap().take();
@@ -625,11 +2058,11 @@ fn main() -> Result<()> {
             if cell_color_mode.fits_cell_backgrounds() {
                 eprintln!("note: cell-background fitting flags have no effect when rendering an existing frame directory; backgrounds already stored in .cframe files are preserved automatically.");
             }
-            let to_video_opts = ToVideoOptions {output_path: video_output_path.clone(), font_size: args.video_font_size, crf: args.crf, mux_audio: args.audio, use_colors: None, text_stroke_width: 0.0};
+            let to_video_opts = ToVideoOptions {output_path: video_output_path.clone(), font_size: args.video_font_size.unwrap_or(14.0), crf: args.crf.unwrap_or(18), mux_audio: args.audio, use_colors: None, text_stroke_width: 0.0, audio_offset: args.audio_offset, audio_padding: args.audio_padding.into(), audio_passthrough: args.audio_copy, background: args.video_background.unwrap_or((0, 0, 0)), foreground: args.video_foreground, narration: narration.clone()};
             let progress_bar: Arc<Mutex<Option<ProgressBar>>> = Arc::new(Mutex::new(None));
             let pb_clone = Arc::clone(&progress_bar);
 
-            converter.render_frames_to_video(input_path, fps, &to_video_opts, move |progress: Progress| {
+            let result = converter.render_frames_to_video(input_path, fps, &to_video_opts, move |progress: Progress| {
                 if progress.phase == ProgressPhase::RenderingVideo {
                     let mut pb_guard = pb_clone.lock().unwrap();
                     if pb_guard.is_none() && progress.total > 0 {
@@ -642,7 +2075,9 @@ fn main() -> Result<()> {
                         pb.set_position(progress.completed as u64);
                     }
                 }
-            })?;
+            });
+            let result = exit_if_interrupted(result, &video_output_path, false, false, args.keep_partial, event_log.as_ref(), &mut output_lock)?;
+            report_audio_sync(&result);
 
             let pb_opt = progress_bar.lock().unwrap().take();
             if let Some(pb) = pb_opt {
@@ -653,22 +2088,23 @@ fn main() -> Result<()> {
             return Ok(());
         } else {
             println!("Converting directory of images...");
-            converter.convert_directory(input_path, &output_path, &conv_opts, args.keep_images)?;
-
-            // For directory conversion, create details.toml manually since it doesn't go through video conversion
-            let frame_ext = if output_mode == OutputMode::ColorOnly {"cframe"} else {"txt"};
+            exit_if_interrupted(converter.convert_directory(input_path, &output_path, &conv_opts, args.keep_images), &output_path, false, true, args.keep_partial, event_log.as_ref(), &mut output_lock)?;
+
+            // For directory conversion, write the details report manually since it doesn't go through video conversion
+            let frame_ext = match output_mode {
+                OutputMode::ColorOnly => "cframe",
+                OutputMode::Ansi | OutputMode::Ansi256 => "ans",
+                OutputMode::Json => "json",
+                _ => "txt",
+            };
             let frame_count = WalkDir::new(&output_path).min_depth(1).max_depth(1).into_iter().filter_map(|e| e.ok()).filter(|e| e.path().extension().is_some_and(|ext| ext == frame_ext)).count();
 
-            let mode_str = match output_mode {
-                OutputMode::TextOnly => "text-only",
-                OutputMode::ColorOnly => "color-only",
-                OutputMode::TextAndColor => "text+color",
-            };
+            let mode_str = output_mode.as_str();
 
-            let result = cascii::ConversionResult {frame_count, columns, font_ratio, luminance, fps: None, output_mode: mode_str.to_string(), audio_extracted: false, output_dir: output_path.clone(), background_color: "black".to_string(), color: "white".to_string(), fit_cell_backgrounds: cell_color_mode.fits_cell_backgrounds(), cell_background_mode: cell_color_mode.as_str().to_string(), bg_fit_quality: bg_fit_quality.as_str().to_string(), bg_luminance: args.bg_luminance.unwrap_or(luminance), ascii_chars: conv_opts.ascii_chars.clone()};
+            let result = ConversionResult {frame_count, columns, font_ratio, luminance, fps: None, output_mode: mode_str.to_string(), audio_extracted: false, output_dir: output_path.clone(), background_color: "black".to_string(), color: "white".to_string(), fit_cell_backgrounds: cell_color_mode.fits_cell_backgrounds(), cell_background_mode: cell_color_mode.as_str().to_string(), bg_fit_quality: bg_fit_quality.as_str().to_string(), bg_luminance: args.bg_luminance.unwrap_or(luminance), ascii_chars: conv_opts.ascii_chars.clone(), audio_sync: None, colors: None, seed: conv_opts.seed, attribution: cfg.attribution.clone()};
 
-            result.write_details_file().context("writing details file")?;
-            let details = result.to_details_string();
+            result.write_report(&report_format).context("writing details file")?;
+            let details = result.render_report(&report_format)?;
 
             if args.log_details {
                 println!("\n--- Generation Details ---");
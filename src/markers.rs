@@ -0,0 +1,208 @@
+//! Preservation of QR codes and fiducial markers.
+//!
+//! The luminance ramp turns the black and white modules of a QR code into a blur of in-between
+//! characters that no scanner can read. With marker preservation, roughly square regions of
+//! high-contrast, colorless cells are found on the character grid and redrawn binarized: every cell
+//! becomes the densest character in white or a blank in black, decided by a majority vote of its
+//! source pixels rather than a blended resample.
+
+use image::RgbImage;
+
+use crate::cell_filter::LuminanceWeights;
+use crate::codepage::ramp_char;
+
+/// Minimum luminance range around a cell for it to look like part of a marker
+const MIN_CONTRAST: u8 = 96;
+/// Maximum mean chroma (largest minus smallest channel) around a marker cell
+const MAX_CHROMA: u32 = 48;
+/// Share of the pixels around a marker cell that sit near its darkest or lightest value
+const MIN_BIMODAL: f32 = 0.75;
+/// Smallest marker side, in cells
+const MIN_CELLS: u32 = 3;
+/// Largest ratio between the sides of a marker, measured in source pixels
+const MAX_ASPECT: f32 = 1.5;
+/// Share of a marker's bounding box made of marker-like cells
+const MIN_FILL: f32 = 0.5;
+
+/// A detected marker, in cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct MarkerRegion {
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+/// Redraw the markers found in `source` over a converted `width` × `height` grid. Color buffers
+/// that don't hold 3 bytes per cell (an absent background layer) are skipped.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn preserve_markers(source: &RgbImage, text: &mut String, width: u32, height: u32, ascii_chars: &[u8], rgb: &mut [u8], bg_rgb: &mut [u8]) {
+    let regions = find_markers(source, width, height);
+    if regions.is_empty() {
+        return;
+    }
+    let luma = luma_plane(source);
+    let (src_w, src_h) = source.dimensions();
+
+    // Per cell: None outside markers, otherwise whether the module is light
+    let mut modules = vec![None; (width * height) as usize];
+    for region in regions {
+        let (x0, x1) = (block(region.x, width, src_w).0, block(region.x + region.width - 1, width, src_w).1);
+        let (y0, y1) = (block(region.y, height, src_h).0, block(region.y + region.height - 1, height, src_h).1);
+        let (mut darkest, mut lightest) = (u8::MAX, u8::MIN);
+        for y in y0..y1 {
+            for &value in &luma[(y * src_w + x0) as usize..(y * src_w + x1) as usize] {
+                darkest = darkest.min(value);
+                lightest = lightest.max(value);
+            }
+        }
+        let threshold = ((darkest as u32 + lightest as u32) / 2) as u8;
+        for cy in region.y..region.y + region.height {
+            let (y0, y1) = block(cy, height, src_h);
+            for cx in region.x..region.x + region.width {
+                let (x0, x1) = block(cx, width, src_w);
+                let dark = (y0..y1).flat_map(|y| &luma[(y * src_w + x0) as usize..(y * src_w + x1) as usize]).filter(|&&value| value < threshold).count();
+                modules[(cy * width + cx) as usize] = Some(dark * 2 <= ((x1 - x0) * (y1 - y0)) as usize);
+            }
+        }
+    }
+
+    let dense = ascii_chars.last().map_or('@', |&byte| ramp_char(byte));
+    let mut cells = modules.iter();
+    *text = text.chars().map(|ch| {
+        if ch == '\n' {
+            return ch;
+        }
+        match cells.next().copied().flatten() {
+            Some(true) => dense,
+            Some(false) => ' ',
+            None => ch,
+        }
+    }).collect();
+    for colors in [rgb, bg_rgb] {
+        if colors.len() != modules.len() * 3 {
+            continue;
+        }
+        for (cell, module) in colors.chunks_exact_mut(3).zip(&modules) {
+            if let Some(light) = module {
+                cell.fill(if *light {255} else {0});
+            }
+        }
+    }
+}
+
+/// Find roughly square clusters of high-contrast, colorless cells on a `width` × `height` grid laid over `source`.
+pub(crate) fn find_markers(source: &RgbImage, width: u32, height: u32) -> Vec<MarkerRegion> {
+    let (src_w, src_h) = source.dimensions();
+    if src_w == 0 || src_h == 0 || width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let luma = luma_plane(source);
+
+    // A cell is judged with its neighbours, so the uniform inside of a module larger than a cell still counts
+    let candidate: Vec<bool> = (0..height).flat_map(|cy| (0..width).map(move |cx| (cx, cy))).map(|(cx, cy)| {
+        let (x0, x1) = (block(cx.saturating_sub(1), width, src_w).0, block((cx + 1).min(width - 1), width, src_w).1);
+        let (y0, y1) = (block(cy.saturating_sub(1), height, src_h).0, block((cy + 1).min(height - 1), height, src_h).1);
+        looks_like_marker(source, &luma, x0..x1, y0..y1)
+    }).collect();
+
+    // Group candidates, bridging one-cell gaps, and keep the groups shaped like a marker
+    let mut seen = vec![false; candidate.len()];
+    let mut regions = Vec::new();
+    for start in 0..candidate.len() {
+        if !candidate[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut stack = vec![start];
+        let (mut min_x, mut min_y, mut max_x, mut max_y, mut count) = (u32::MAX, u32::MAX, 0, 0, 0u32);
+        while let Some(idx) = stack.pop() {
+            let (cx, cy) = (idx as u32 % width, idx as u32 / width);
+            (min_x, min_y, max_x, max_y, count) = (min_x.min(cx), min_y.min(cy), max_x.max(cx), max_y.max(cy), count + 1);
+            for ny in cy.saturating_sub(2)..=(cy + 2).min(height - 1) {
+                for nx in cx.saturating_sub(2)..=(cx + 2).min(width - 1) {
+                    let neighbour = (ny * width + nx) as usize;
+                    if candidate[neighbour] && !seen[neighbour] {
+                        seen[neighbour] = true;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+        }
+        let region = MarkerRegion {x: min_x, y: min_y, width: max_x - min_x + 1, height: max_y - min_y + 1};
+        let (px_w, px_h) = (region.width as f32 * src_w as f32 / width as f32, region.height as f32 * src_h as f32 / height as f32);
+        let fill = count as f32 / (region.width * region.height) as f32;
+        if region.width.min(region.height) >= MIN_CELLS && px_w.max(px_h) <= px_w.min(px_h) * MAX_ASPECT && fill >= MIN_FILL {
+            regions.push(region);
+        }
+    }
+    regions
+}
+
+fn looks_like_marker(source: &RgbImage, luma: &[u8], xs: std::ops::Range<u32>, ys: std::ops::Range<u32>) -> bool {
+    let src_w = source.width();
+    let (mut darkest, mut lightest, mut chroma, mut pixels) = (u8::MAX, u8::MIN, 0u32, 0u32);
+    for y in ys.clone() {
+        for x in xs.clone() {
+            let value = luma[(y * src_w + x) as usize];
+            darkest = darkest.min(value);
+            lightest = lightest.max(value);
+            let [r, g, b] = source.get_pixel(x, y).0;
+            chroma += (r.max(g).max(b) - r.min(g).min(b)) as u32;
+            pixels += 1;
+        }
+    }
+    let range = lightest - darkest;
+    if range < MIN_CONTRAST || chroma > MAX_CHROMA * pixels {
+        return false;
+    }
+    let near_extreme = ys.flat_map(|y| luma[(y * src_w + xs.start) as usize..(y * src_w + xs.end) as usize].iter()).filter(|&&value| value - darkest < range / 4 || lightest - value < range / 4).count();
+    near_extreme as f32 >= pixels as f32 * MIN_BIMODAL
+}
+
+fn luma_plane(source: &RgbImage) -> Vec<u8> {
+    source.pixels().map(|px| LuminanceWeights::REC709.luminance(px[0], px[1], px[2])).collect()
+}
+
+/// Source pixel span covered by cell `cell` of `cells` laid over `pixels`; never empty.
+fn block(cell: u32, cells: u32, pixels: u32) -> (u32, u32) {
+    let start = (cell as u64 * pixels as u64 / cells as u64) as u32;
+    let end = ((cell as u64 + 1) * pixels as u64 / cells as u64) as u32;
+    (start.min(pixels - 1), end.clamp(start + 1, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_markers_are_redrawn_binarized() {
+        // A soft gradient on the left, then a white quiet zone and a 6×6 module checkerboard with 8 px modules
+        let source = RgbImage::from_fn(96, 48, |x, y| {
+            if x < 40 {
+                let value = (64 + x) as u8;
+                Rgb([value, value, value])
+            } else if x < 48 || ((x - 48) / 8 + y / 8) % 2 == 0 {
+                Rgb([250, 250, 250])
+            } else {
+                Rgb([10, 10, 10])
+            }
+        });
+        let (width, height) = (24, 12);
+        let regions = find_markers(&source, width, height);
+        // The quiet zone and the edge of the gradient next to it are high contrast too
+        assert_eq!(regions, vec![MarkerRegion {x: 9, y: 0, width: 15, height: 12}]);
+
+        let mut text: String = (0..height).map(|_| "x".repeat(width as usize) + "\n").collect();
+        let mut rgb = vec![128; (width * height * 3) as usize];
+        preserve_markers(&source, &mut text, width, height, b" .:#", &mut rgb, &mut []);
+        let rows: Vec<&str> = text.lines().collect();
+        assert_eq!(&rows[0][..12], "xxxxxxxxx ##");
+        assert_eq!(&rows[0][12..], "##  ##  ##  ");
+        assert_eq!(&rows[2][12..], "  ##  ##  ##");
+        assert_eq!(&rgb[..3], &[128, 128, 128]);
+        assert_eq!(&rgb[12 * 3..14 * 3], &[255; 6]);
+        assert_eq!(&rgb[14 * 3..15 * 3], &[0; 3]);
+    }
+}
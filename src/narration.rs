@@ -0,0 +1,140 @@
+//! Spoken narration of caption tracks, for ASCII videos that can be followed without seeing them.
+//!
+//! [`synthesize_narration`] speaks every caption of a [`CaptionTrack`] with an external
+//! text-to-speech command, set up with a [`TtsConfig`] the way [`FfmpegConfig`] sets up ffmpeg,
+//! and mixes the spoken cues into one audio file with each cue starting at its caption's start.
+//! [`ToVideoOptions::narration`](crate::ToVideoOptions::narration) muxes that file into a rendered
+//! video as an audio track of its own, after the clip's audio.
+//!
+//! ```no_run
+//! use cascii::narration::{synthesize_narration, TtsConfig};
+//! use cascii::{CaptionTrack, FfmpegConfig, ToVideoOptions};
+//!
+//! # fn main() -> cascii::Result<()> {
+//! let captions = CaptionTrack::load("captions.srt".as_ref())?;
+//! let tts = TtsConfig::new().with_command("piper").with_args(["--output_file", "{output}", "--text", "{text}"]);
+//! synthesize_narration(&captions, &tts, &FfmpegConfig::new(), "narration.m4a".as_ref())?;
+//! let to_video = ToVideoOptions {narration: Some("narration.m4a".into()), ..ToVideoOptions::default()};
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::captions::CaptionTrack;
+use crate::error::{Context, Error, Result};
+use crate::FfmpegConfig;
+
+/// Arguments of `espeak-ng`, the text-to-speech command used when none is configured
+const DEFAULT_TTS_ARGS: [&str; 3] = ["-w", "{output}", "{text}"];
+
+/// Configuration for the text-to-speech command narrating captions
+///
+/// The command is run once per caption with `args`, in which `{text}` is replaced by the caption
+/// and `{output}` by the path of the audio file it has to write (a WAV file).
+#[derive(Debug, Clone, Default)]
+pub struct TtsConfig {
+    /// Custom path to the text-to-speech binary. If None, uses `espeak-ng` from the system PATH.
+    pub command_path: Option<PathBuf>,
+    /// Arguments of one caption, with the `{text}` and `{output}` placeholders. If empty, espeak-ng's
+    /// `-w {output} {text}`.
+    pub args: Vec<String>,
+}
+
+impl TtsConfig {
+    /// Create a new TtsConfig with default settings (espeak-ng from the system PATH)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a config with a custom text-to-speech binary
+    pub fn with_command<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.command_path = Some(path.into());
+        self
+    }
+
+    /// Create a config with custom arguments, using the `{text}` and `{output}` placeholders
+    pub fn with_args<I: IntoIterator<Item = S>, S: Into<String>>(mut self, args: I) -> Self {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Get the text-to-speech command name or path
+    pub(crate) fn tts_cmd(&self) -> &OsStr {
+        self.command_path.as_ref().map(|p| p.as_os_str()).unwrap_or(OsStr::new("espeak-ng"))
+    }
+
+    /// The arguments speaking `text` into `output`; the lines of a caption are read as one sentence
+    pub(crate) fn args_for(&self, text: &str, output: &Path) -> Vec<String> {
+        let text = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join(" ");
+        let output = output.to_string_lossy();
+        let template: Vec<&str> = if self.args.is_empty() {DEFAULT_TTS_ARGS.to_vec()} else {self.args.iter().map(String::as_str).collect()};
+        template.iter().map(|arg| arg.replace("{output}", &output).replace("{text}", &text)).collect()
+    }
+}
+
+/// Speak every caption of `captions` with `tts` and mix the cues into the audio file `output`, its
+/// format picked by ffmpeg from the extension (e.g. `.m4a`, `.mp3` or `.wav`). Every cue starts at
+/// its caption's start; a cue running past the next caption's start overlaps it.
+pub fn synthesize_narration(captions: &CaptionTrack, tts: &TtsConfig, ffmpeg_config: &FfmpegConfig, output: &Path) -> Result<()> {
+    let cues: Vec<_> = captions.captions.iter().filter(|caption| !caption.text.trim().is_empty()).collect();
+    if cues.is_empty() {
+        return Err(Error::InvalidOptions("the caption track has no text to narrate".into()));
+    }
+    let work_dir = std::env::temp_dir().join(format!("cascii_narration_{}", std::process::id()));
+    fs::create_dir_all(&work_dir).context("creating temp directory")?;
+    let result = (|| -> Result<()> {
+        let mut starts = Vec::with_capacity(cues.len());
+        let mut command = Command::new(ffmpeg_config.ffmpeg_cmd());
+        command.args(["-y", "-loglevel", "error", "-nostdin"]);
+        for (index, caption) in cues.iter().enumerate() {
+            let cue = work_dir.join(format!("cue_{:04}.wav", index + 1));
+            let spoken = Command::new(tts.tts_cmd()).args(tts.args_for(&caption.text, &cue)).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::piped()).output().map_err(|err| Error::spawn(tts.tts_cmd(), err))?;
+            if !spoken.status.success() || !cue.is_file() {
+                return Err(Error::Other(format!("{} failed to speak caption {}: {}", tts.tts_cmd().to_string_lossy(), index + 1, String::from_utf8_lossy(&spoken.stderr).trim())));
+            }
+            command.arg("-i").arg(&cue);
+            starts.push(caption.start);
+        }
+        let mixed = command.args(["-filter_complex", &narration_filter(&starts), "-map", "[narration]"]).arg(output).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::piped()).output().map_err(|err| Error::spawn(ffmpeg_config.ffmpeg_cmd(), err))?;
+        if !mixed.status.success() {
+            return Err(Error::ffmpeg("mixing the narration", &mixed.stderr));
+        }
+        Ok(())
+    })();
+    let _ = fs::remove_dir_all(&work_dir);
+    result.with_context(|| format!("narrating into {}", output.display()))
+}
+
+/// The ffmpeg filter graph delaying input `i` to `starts[i]` seconds and mixing them all, at full
+/// volume, into `[narration]`
+fn narration_filter(starts: &[f64]) -> String {
+    let delays = starts.iter().enumerate().map(|(index, start)| format!("[{}:a]adelay=delays={}:all=1[cue{}]", index, (start.max(0.0) * 1000.0).round() as u64, index));
+    let inputs: String = (0..starts.len()).map(|index| format!("[cue{}]", index)).collect();
+    format!("{};{}amix=inputs={}:normalize=0:dropout_transition=0[narration]", delays.collect::<Vec<_>>().join(";"), inputs, starts.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::narration_args;
+
+    #[test]
+    fn narration_speaks_each_caption_and_mixes_them_at_their_starts() {
+        let cue = Path::new("/tmp/cue.wav");
+        assert_eq!(TtsConfig::new().args_for("Hello\nthere ", cue), ["-w", "/tmp/cue.wav", "Hello there"]);
+        let piper = TtsConfig::new().with_command("piper").with_args(["--output_file={output}", "--text", "{text}"]);
+        assert_eq!((piper.tts_cmd(), piper.args_for("Hi", cue)), (OsStr::new("piper"), vec!["--output_file=/tmp/cue.wav".to_string(), "--text".into(), "Hi".into()]));
+
+        assert_eq!(narration_filter(&[0.5, 2.0]), "[0:a]adelay=delays=500:all=1[cue0];[1:a]adelay=delays=2000:all=1[cue1];[cue0][cue1]amix=inputs=2:normalize=0:dropout_transition=0[narration]");
+        let alone = narration_args(Path::new("narration.m4a"), false).join(" ");
+        assert_eq!(alone, "-i narration.m4a -map 0:v:0 -map 1:a:0 -filter:a:0 apad -c:a:0 aac -b:a:0 192k -metadata:s:a:0 title=Narration -shortest");
+        assert!(narration_args(Path::new("narration.m4a"), true).join(" ").starts_with("-i narration.m4a -map 2:a:0 -filter:a:1 apad -c:a:1 aac"));
+
+        let silent = CaptionTrack::new(vec![crate::Caption {text: " ".into(), start: 0.0, end: 1.0, position: Default::default()}]);
+        assert!(matches!(synthesize_narration(&silent, &TtsConfig::new(), &FfmpegConfig::new(), Path::new("narration.m4a")), Err(Error::InvalidOptions(_))));
+    }
+}
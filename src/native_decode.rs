@@ -0,0 +1,155 @@
+//! Frame extraction without ffmpeg, for animations the `image` crate decodes itself.
+//!
+//! When ffmpeg isn't installed, [`extract_frames`] stands in for it on animated GIF and PNG (APNG)
+//! inputs: every frame is decoded in Rust, scaled to the extraction width and resampled to the
+//! requested frame rate as the `scale` and `fps`/`framerate` filters would. Filters only ffmpeg has
+//! (preprocessing filtergraphs, deinterlacing) still need it, and so do MP4, WebM and other video
+//! codecs.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, ImageFormat, RgbaImage};
+
+use crate::error::{Context, Error, Result};
+use crate::frame::Sampling;
+use crate::video::parse_timestamp;
+use crate::{CancelToken, FpsMode, VideoOptions};
+
+/// How long a frame without a delay of its own is shown, as in browsers
+const DEFAULT_DELAY_SECS: f64 = 0.1;
+
+/// Whether [`decode`] can extract `input` with `video_opts`: an animated GIF or PNG, and no filter
+/// that needs ffmpeg
+pub(crate) fn supports(input: &Path, video_opts: &VideoOptions) -> bool {
+    let filtered = video_opts.deinterlace || video_opts.preprocess_filter.as_deref().is_some_and(|filter| !filter.trim().trim_end_matches(',').is_empty());
+    !filtered && animation_format(input).is_some()
+}
+
+/// GIF, or PNG with an animation control chunk, judged by the content rather than the extension
+fn animation_format(input: &Path) -> Option<ImageFormat> {
+    match image::ImageReader::open(input).ok()?.with_guessed_format().ok()?.format()? {
+        ImageFormat::Gif => Some(ImageFormat::Gif),
+        ImageFormat::Png => PngDecoder::new(BufReader::new(File::open(input).ok()?)).ok()?.is_apng().ok()?.then_some(ImageFormat::Png),
+        _ => None,
+    }
+}
+
+/// The frames of `input` from `video_opts.start` to `end` at `video_opts.fps`, each as wide as the
+/// ffmpeg extraction would make it
+pub(crate) fn decode(input: &Path, video_opts: &VideoOptions, sampling: Sampling, cancel: Option<&CancelToken>) -> Result<Vec<RgbaImage>> {
+    profile_span!("decode");
+    let open = || File::open(input).map(BufReader::new).with_context(|| format!("opening {}", input.display()));
+    let frames = match animation_format(input) {
+        Some(ImageFormat::Gif) => GifDecoder::new(open()?)?.into_frames(),
+        Some(_) => PngDecoder::new(open()?)?.apng()?.into_frames(),
+        None => return Err(Error::InvalidOptions(format!("{} is not an animated GIF or PNG, which is all cascii decodes without ffmpeg", input.display()))),
+    };
+
+    // Every source frame, scaled, with the time it appears
+    let columns = video_opts.columns * sampling.views();
+    let mut source = Vec::new();
+    let mut duration = 0.0;
+    for frame in frames {
+        if cancel.is_some_and(|c| c.is_cancelled()) {
+            return Err(crate::Cancelled.into());
+        }
+        let frame = frame.with_context(|| format!("decoding {}", input.display()))?;
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let delay = numer as f64 / denom.max(1) as f64 / 1000.0;
+        source.push((duration, scale(&frame.into_buffer(), columns, sampling)));
+        duration += if delay > 0.0 {delay} else {DEFAULT_DELAY_SECS};
+    }
+    if source.is_empty() {
+        return Err(Error::NotFound(format!("No frames extracted from {}", input.display())));
+    }
+
+    let from = video_opts.start.as_deref().filter(|start| !start.is_empty()).map_or(0.0, parse_timestamp);
+    let to = video_opts.end.as_deref().filter(|end| !end.is_empty()).map_or(duration, |end| parse_timestamp(end).min(duration));
+    let fps = video_opts.fps.max(1) as f64;
+    let count = ((to - from) * fps).ceil().max(0.0) as usize;
+    Ok((0..count).map(|tick| sample(&source, from + tick as f64 / fps, video_opts.fps_mode)).collect())
+}
+
+/// [`decode`] `input` into `out_dir` as `frame_0001.png` and on, like an ffmpeg extraction
+pub(crate) fn extract_frames(input: &Path, out_dir: &Path, video_opts: &VideoOptions, sampling: Sampling, cancel: Option<&CancelToken>) -> Result<()> {
+    for (index, frame) in decode(input, video_opts, sampling, cancel)?.into_iter().enumerate() {
+        let path = out_dir.join(format!("frame_{:04}.png", index + 1));
+        frame.save(&path).with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// `frame` at the width ffmpeg's `scale` filter gives it for `columns`, and the even height keeping its aspect ratio
+fn scale(frame: &RgbaImage, columns: u32, sampling: Sampling) -> RgbaImage {
+    let (width, height) = frame.dimensions();
+    let target_width = sampling.upscale_policy().columns(columns, width).max(1);
+    let target_height = ((target_width as f64 * height as f64 / width.max(1) as f64 / 2.0).round() as u32 * 2).max(2);
+    let filter = if sampling.nearest() {FilterType::Nearest} else {FilterType::CatmullRom};
+    image::imageops::resize(frame, target_width, target_height, filter)
+}
+
+/// The picture at `time`: the frame showing then, or with [`FpsMode::Blend`] that frame mixed with
+/// the next one by how far between them `time` is
+fn sample(source: &[(f64, RgbaImage)], time: f64, fps_mode: FpsMode) -> RgbaImage {
+    let index = source.partition_point(|(start, _)| *start <= time).saturating_sub(1);
+    let (start, frame) = &source[index];
+    match (fps_mode, source.get(index + 1)) {
+        (FpsMode::Blend, Some((next_start, next))) => {
+            let weight = ((time - start) / (next_start - start)).clamp(0.0, 1.0) as f32;
+            let mixed = frame.as_raw().iter().zip(next.as_raw()).map(|(&a, &b)| (a as f32 + (b as f32 - a as f32) * weight).round() as u8).collect();
+            RgbaImage::from_raw(frame.width(), frame.height(), mixed).expect("frames of one animation share their size")
+        }
+        _ => frame.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Frame, Rgba};
+
+    #[test]
+    fn gif_frames_are_scaled_and_resampled_without_ffmpeg() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("clip.gif");
+        // Black for 0.5s, then white for 0.5s, on a 20x10 canvas
+        let mut encoder = GifEncoder::new(File::create(&input).unwrap());
+        for shade in [0, 255] {
+            encoder.encode_frame(Frame::from_parts(RgbaImage::from_pixel(20, 10, Rgba([shade, shade, shade, 255])), 0, 0, Delay::from_numer_denom_ms(500, 1))).unwrap();
+        }
+        drop(encoder);
+
+        let video_opts = VideoOptions {fps: 4, columns: 8, ..VideoOptions::default()};
+        assert!(supports(&input, &video_opts));
+        assert!(!supports(&input, &VideoOptions {deinterlace: true, ..video_opts.clone()}));
+        let sampling = Sampling::from_options(&crate::ConversionOptions::default());
+        let frames = decode(&input, &video_opts, sampling, None).unwrap();
+        assert_eq!((frames.len(), frames[0].dimensions()), (4, (8, 4)));
+        assert_eq!(frames.iter().map(|frame| frame.get_pixel(0, 0)[0]).collect::<Vec<_>>(), [0, 0, 255, 255]);
+
+        // Blending crossfades towards the next frame; start and end trim like ffmpeg's -ss and -t
+        let blended = decode(&input, &VideoOptions {fps_mode: FpsMode::Blend, start: Some("0.25".into()), end: Some("0.75".into()), ..video_opts.clone()}, sampling, None).unwrap();
+        assert_eq!(blended.iter().map(|frame| frame.get_pixel(0, 0)[0]).collect::<Vec<_>>(), [128, 255]);
+
+        extract_frames(&input, dir.path(), &video_opts, sampling, None).unwrap();
+        assert!(dir.path().join("frame_0004.png").is_file() && !dir.path().join("frame_0005.png").exists());
+        assert!(!supports(&dir.path().join("frame_0001.png"), &video_opts));
+
+        // A converter whose ffmpeg is missing falls back to it, extracting or piping
+        let missing = dir.path().join("missing");
+        let converter = crate::AsciiConverter::new().with_ffmpeg_config(crate::FfmpegConfig::new().with_ffmpeg(&missing).with_ffprobe(&missing));
+        let conv_opts = crate::ConversionOptions::default().with_columns(8);
+        for pipe_frames in [false, true] {
+            let out = dir.path().join(format!("out_{}", pipe_frames));
+            let result = converter.convert_video(&input, &out, &VideoOptions {pipe_frames, ..video_opts.clone()}, &conv_opts, false).unwrap();
+            assert_eq!(result.frame_count, 4);
+            assert!(std::fs::read_to_string(out.join("frame_0004.txt")).unwrap().starts_with("$$$$$$$$"));
+        }
+    }
+}
@@ -0,0 +1,665 @@
+//! Options of conversions: what to convert, how, and what to write.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::config::default_ascii_chars;
+use crate::{codepage, AlphaMode, Anaglyph, AnsiColor, AudioPadding, CaptionTrack, CframeFormat, CharMode, Codepage, DepthMap, DitherMode, FontRatio, LineLimits, LuminanceModel, LuminanceWeights, Palette, Preset, UpscalePolicy, YuvMatrix};
+
+/// Controls what output files are generated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum OutputMode {
+    /// Only generate .txt files (plain ASCII)
+    #[serde(rename = "text-only")]
+    TextOnly,
+    /// Only generate .cframe files (combined text + color binary)
+    #[serde(rename = "color-only")]
+    ColorOnly,
+    /// Generate both .txt and .cframe files
+    #[serde(rename = "text+color")]
+    TextAndColor,
+    /// Generate only .ans files: the text with 24-bit color escapes, to `cat` to a terminal
+    #[serde(rename = "ansi")]
+    Ansi,
+    /// Generate only .ans files with xterm 256-color escapes, for terminals without truecolor
+    #[serde(rename = "ansi-256")]
+    Ansi256,
+    /// Generate only .json files: the size and every cell with its character and colors, for web
+    /// apps and data pipelines (see [`AsciiFrame::to_json`](crate::AsciiFrame::to_json))
+    #[serde(rename = "json")]
+    Json,
+}
+
+impl OutputMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TextOnly => "text-only",
+            Self::ColorOnly => "color-only",
+            Self::TextAndColor => "text+color",
+            Self::Ansi => "ansi",
+            Self::Ansi256 => "ansi-256",
+            Self::Json => "json",
+        }
+    }
+
+    /// The escapes of the `.ans` output modes, `None` for the others.
+    pub fn ansi_color(&self) -> Option<AnsiColor> {
+        match self {
+            Self::Ansi => Some(AnsiColor::TrueColor),
+            Self::Ansi256 => Some(AnsiColor::Xterm256),
+            Self::TextOnly | Self::ColorOnly | Self::TextAndColor | Self::Json => None,
+        }
+    }
+}
+
+/// Controls how per-cell colors are modeled during conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CellColorMode {
+    /// Current behavior: a single foreground color per character cell.
+    ForegroundOnly,
+    /// Experimental option C: fit both foreground and background colors per cell.
+    FitForegroundBackground,
+    /// Optimized foreground/background fitter with a small atlas and candidate shortlist.
+    FitForegroundBackgroundOptimized,
+}
+
+impl CellColorMode {
+    pub fn fits_cell_backgrounds(self) -> bool {
+        self != Self::ForegroundOnly
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ForegroundOnly => "off",
+            Self::FitForegroundBackground => "legacy",
+            Self::FitForegroundBackgroundOptimized => "optimized",
+        }
+    }
+}
+
+/// Controls the analysis resolution of the cell-background fitting passes.
+///
+/// [`Fidelity`](Self::Fidelity) (the default) fits glyphs against full-resolution cell patches. [`Fast`](Self::Fast) rasterizes the
+/// analysis atlas at a smaller size, shrinking both the pre-fit image resize and the per-glyph solver work several-fold at the cost
+/// of slightly coarser glyph and color choices. Has no effect on [`CellColorMode::ForegroundOnly`] conversions unless they
+/// match glyph shapes (see [`GlyphMatch`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BgFitQuality {
+    #[default]
+    Fidelity,
+    Fast,
+}
+
+impl BgFitQuality {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fidelity => "fidelity",
+            Self::Fast => "fast",
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    pub(crate) fn analysis_font_size(self) -> f32 {
+        match self {
+            Self::Fidelity => 16.0,
+            Self::Fast => 7.0,
+        }
+    }
+}
+
+/// How each cell's character is picked from `ascii_chars`.
+///
+/// The shape matching modes rasterize the ramp with the embedded DejaVu Sans Mono and compare every
+/// cell's pixels against each glyph, so edges and thin lines get characters of matching shape
+/// (`/`, `|`, `_`) instead of ones of matching brightness. They are several times slower, need the
+/// `cli` feature, and only apply to [`CellColorMode::ForegroundOnly`] ASCII conversions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GlyphMatch {
+    /// The character whose position in the ramp matches the cell's mean luminance
+    #[default]
+    Luminance,
+    /// The glyph with the least squared difference from the cell's luminances
+    Mse,
+    /// The glyph most structurally similar to the cell (SSIM), which favors shape over brightness
+    Ssim,
+}
+
+impl GlyphMatch {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Luminance => "luminance",
+            Self::Mse => "mse",
+            Self::Ssim => "ssim",
+        }
+    }
+}
+
+/// Options for ASCII conversion
+#[derive(Debug, Clone)]
+pub struct ConversionOptions {
+    /// Target width in characters (columns)
+    pub columns: Option<u32>,
+    /// Font aspect ratio (width/height of character); [`FontTarget::font_ratio`](crate::FontTarget::font_ratio) gives typical values
+    pub font_ratio: f32,
+    /// Luminance threshold (0-255) for the foreground glyph pass.
+    /// Cells whose average luminance falls below this value emit a space
+    /// instead of a glyph.
+    pub luminance: u8,
+    /// Optional override for the background pass's luminance threshold.
+    ///
+    /// `None` means "track the foreground value", which preserves the
+    /// historical single-threshold behaviour. `Some(n)` lets callers decide
+    /// independently whether to emit a per-cell background.
+    pub bg_luminance: Option<u8>,
+    /// Character ramp to use (from darkest to lightest): ASCII, or any character of code page 437
+    /// such as the shades `░▒▓█` (see [`codepage::CP437_RAMP`])
+    pub ascii_chars: String,
+    /// What output files to generate
+    pub output_mode: OutputMode,
+    /// How per-cell colors should be modeled during conversion
+    pub cell_color_mode: CellColorMode,
+    /// Analysis resolution for the cell-background fitting passes
+    pub bg_fit_quality: BgFitQuality,
+    /// How each cell's character is picked: by luminance, or by glyph shape (see [`GlyphMatch`])
+    pub glyph_match: GlyphMatch,
+    /// Accessibility remapping applied to every cell color
+    pub palette: Palette,
+    /// Write every sampled pixel as two identical characters. The image is sampled at half of
+    /// `columns` with square samples and `font_ratio` is ignored, so with the usual ~0.5 cell ratio
+    /// each pixel comes out square; crisper than resizing for pixel art.
+    pub square_pixels: bool,
+    /// Resize with nearest-neighbor instead of blending, so every cell takes an exact source color,
+    /// and never sample more columns than the source has pixels, so a sprite narrower than `columns`
+    /// maps one pixel to one cell. Nothing is dithered either way.
+    pub pixel_art: bool,
+    /// What to do when `columns` is wider than the source image
+    pub upscale: UpscalePolicy,
+    /// Channel weights of the luminance that picks each cell's character (Rec.709 by default)
+    pub luminance_weights: LuminanceWeights,
+    /// Formula for the luminance that picks each cell's character; [`LuminanceModel::Luma`] applies `luminance_weights`
+    pub luminance_model: LuminanceModel,
+    /// Treat the input as side-by-side stereo (left view first) and convert a red/cyan anaglyph of its two halves
+    pub anaglyph: Option<Anaglyph>,
+    /// What transparent pixels of images with an alpha channel become (see [`AlphaMode`](crate::AlphaMode))
+    pub alpha_mode: AlphaMode,
+    /// Redraw QR codes and other high-contrast square markers as crisp black and white cells so they stay scannable
+    pub preserve_markers: bool,
+    /// Maximum line length and line count of every frame, for platforms that mangle wide preformatted text
+    pub line_limits: LineLimits,
+    /// Draw cells as `ascii_chars` or as braille dot patterns
+    pub char_mode: CharMode,
+    /// Dither cell luminances across the ramp so smooth gradients don't band at low column counts
+    pub dither: DitherMode,
+    /// Characters the output may use; [`Codepage::Cp437`] maps the rest to lookalikes and writes `.ans` files in CP437
+    pub codepage: Codepage,
+    /// Layout of the `.cframe` files written: v1, or the v2 container with metadata and optional compression (see [`cframe`](crate::cframe))
+    pub cframe_format: CframeFormat,
+    /// Added to every channel after resizing, in `-1.0..=1.0` of full scale (0 leaves it unchanged)
+    pub brightness: f32,
+    /// Scales every channel's distance from mid gray after resizing (1 leaves it unchanged, 0 flattens to gray)
+    pub contrast: f32,
+    /// Gamma applied after brightness and contrast; above 1 lifts the midtones, below 1 darkens them
+    pub gamma: f32,
+    /// Invert every channel after the other adjustments, for light-on-dark sources on a light background
+    pub invert: bool,
+    /// Seed of every random choice a conversion makes (such as [`DitherMode::Noise`]), recorded in
+    /// `details.toml` so the conversion can be reproduced exactly
+    pub seed: u64,
+}
+
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        Self {columns: Some(400), font_ratio: 0.7, luminance: 20, bg_luminance: None, ascii_chars: default_ascii_chars(), output_mode: OutputMode::TextOnly, cell_color_mode: CellColorMode::ForegroundOnly, bg_fit_quality: BgFitQuality::Fidelity, glyph_match: GlyphMatch::Luminance, palette: Palette::Truecolor, square_pixels: false, pixel_art: false, upscale: UpscalePolicy::Allow, luminance_weights: LuminanceWeights::REC709, luminance_model: LuminanceModel::Luma, anaglyph: None, alpha_mode: AlphaMode::Ignore, preserve_markers: false, line_limits: LineLimits::default(), char_mode: CharMode::Ascii, dither: DitherMode::None, codepage: Codepage::Unicode, cframe_format: CframeFormat::V1, brightness: 0.0, contrast: 1.0, gamma: 1.0, invert: false, seed: 0}
+    }
+}
+
+impl ConversionOptions {
+    /// Create options with a specific width
+    pub fn with_columns(mut self, columns: u32) -> Self {
+        self.columns = Some(columns);
+        self
+    }
+
+    /// Create options with a specific font ratio: a number, or a [`FontTarget`](crate::FontTarget) to use its typical ratio
+    pub fn with_font_ratio(mut self, font_ratio: impl Into<FontRatio>) -> Self {
+        self.font_ratio = font_ratio.into().resolve();
+        self
+    }
+
+    /// Create options with a specific luminance threshold
+    pub fn with_luminance(mut self, luminance: u8) -> Self {
+        self.luminance = luminance;
+        self
+    }
+
+    /// Set an explicit background luminance threshold for the bg-fit pass.
+    ///
+    /// Without this override the bg pass uses the same threshold as the
+    /// foreground glyph pass.
+    pub fn with_bg_luminance(mut self, bg_luminance: u8) -> Self {
+        self.bg_luminance = Some(bg_luminance);
+        self
+    }
+
+    /// Set the background luminance override from an `Option`.
+    ///
+    /// `Some(n)` overrides the bg threshold; `None` clears the override and
+    /// makes the bg pass track the foreground luminance again.
+    pub fn with_bg_luminance_opt(mut self, bg_luminance: Option<u8>) -> Self {
+        self.bg_luminance = bg_luminance;
+        self
+    }
+
+    /// Resolve the effective background luminance threshold actually used by
+    /// the bg-fit pass: the override when set, otherwise the foreground value.
+    pub fn resolve_bg_threshold(&self) -> u8 {
+        self.bg_luminance.unwrap_or(self.luminance)
+    }
+
+    /// Create options with custom ASCII character set
+    pub fn with_ascii_chars(mut self, ascii_chars: String) -> Self {
+        self.ascii_chars = ascii_chars;
+        self
+    }
+
+    /// Set the output mode
+    pub fn with_output_mode(mut self, mode: OutputMode) -> Self {
+        self.output_mode = mode;
+        self
+    }
+
+    /// Set the per-cell color mode
+    pub fn with_cell_color_mode(mut self, mode: CellColorMode) -> Self {
+        self.cell_color_mode = mode;
+        self
+    }
+
+    /// Set the analysis resolution for the cell-background fitting passes
+    pub fn with_bg_fit_quality(mut self, quality: BgFitQuality) -> Self {
+        self.bg_fit_quality = quality;
+        self
+    }
+
+    /// Pick characters by comparing cells with the glyph shapes instead of by luminance
+    pub fn with_glyph_match(mut self, glyph_match: GlyphMatch) -> Self {
+        self.glyph_match = glyph_match;
+        self
+    }
+
+    /// Set the accessibility palette applied to cell colors
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Write every sampled pixel as two identical characters
+    pub fn with_square_pixels(mut self, square_pixels: bool) -> Self {
+        self.square_pixels = square_pixels;
+        self
+    }
+
+    /// Sample with nearest-neighbor and at most one cell per source pixel
+    pub fn with_pixel_art(mut self, pixel_art: bool) -> Self {
+        self.pixel_art = pixel_art;
+        self
+    }
+
+    /// Set what happens when `columns` is wider than the source image
+    pub fn with_upscale(mut self, upscale: UpscalePolicy) -> Self {
+        self.upscale = upscale;
+        self
+    }
+
+    /// Set the channel weights of the luminance that picks each cell's character
+    pub fn with_luminance_weights(mut self, luminance_weights: LuminanceWeights) -> Self {
+        self.luminance_weights = luminance_weights;
+        self
+    }
+
+    /// Measure luminance with `luminance_model` instead of the plain weighted average
+    pub fn with_luminance_model(mut self, luminance_model: LuminanceModel) -> Self {
+        self.luminance_model = luminance_model;
+        self
+    }
+
+    /// Convert side-by-side stereo input to a red/cyan anaglyph (see [`Anaglyph`](crate::Anaglyph))
+    pub fn with_anaglyph(mut self, anaglyph: Anaglyph) -> Self {
+        self.anaglyph = Some(anaglyph);
+        self
+    }
+
+    /// Convert transparent pixels as spaces, over a background color or by opacity
+    pub fn with_alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// Keep QR codes and fiducial markers scannable by redrawing them binarized
+    pub fn with_preserve_markers(mut self, preserve_markers: bool) -> Self {
+        self.preserve_markers = preserve_markers;
+        self
+    }
+
+    /// Fit every frame within a maximum line length and line count (see [`LineLimits`](crate::LineLimits))
+    pub fn with_line_limits(mut self, line_limits: LineLimits) -> Self {
+        self.line_limits = line_limits;
+        self
+    }
+
+    /// Draw cells as braille dot patterns instead of `ascii_chars` (see [`CharMode::Braille`])
+    pub fn with_char_mode(mut self, char_mode: CharMode) -> Self {
+        self.char_mode = char_mode;
+        self
+    }
+
+    /// Dither luminance before it picks characters (see [`DitherMode`])
+    pub fn with_dither(mut self, dither: DitherMode) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Restrict the output to a code page (see [`Codepage`])
+    pub fn with_codepage(mut self, codepage: Codepage) -> Self {
+        self.codepage = codepage;
+        self
+    }
+
+    /// Write `.cframe` files in the v2 container, or compressed (see [`cframe`](crate::cframe))
+    pub fn with_cframe_format(mut self, cframe_format: CframeFormat) -> Self {
+        self.cframe_format = cframe_format;
+        self
+    }
+
+    /// The ramp as the one byte per character the converters index (see [`codepage::ramp_bytes`])
+    pub(crate) fn ramp(&self) -> std::borrow::Cow<'_, [u8]> {
+        codepage::ramp_bytes(&self.ascii_chars)
+    }
+
+    /// Shift every channel by `brightness` (`-1.0..=1.0`) before characters are picked
+    pub fn with_brightness(mut self, brightness: f32) -> Self {
+        self.brightness = brightness;
+        self
+    }
+
+    /// Stretch (above 1) or flatten (below 1) every channel around mid gray
+    pub fn with_contrast(mut self, contrast: f32) -> Self {
+        self.contrast = contrast;
+        self
+    }
+
+    /// Gamma-correct every channel; non-positive values are ignored
+    pub fn with_gamma(mut self, gamma: f32) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// Invert every channel, so dark areas get the dense characters
+    pub fn with_invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+
+    /// Seed the conversion's random choices, so the same seed reproduces the same output
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Create options from a preset
+    ///
+    /// `ascii_chars` is used unless the preset carries its own ramp.
+    pub fn from_preset(preset: &Preset, ascii_chars: String) -> Self {
+        Self {columns: Some(preset.columns), font_ratio: preset.font_ratio, luminance: preset.luminance, ascii_chars: preset.ascii_chars.clone().unwrap_or(ascii_chars), output_mode: preset.output_mode.clone().unwrap_or(OutputMode::TextOnly), ..Self::default()}
+    }
+}
+
+/// How frames are picked when extracting at a lower frame rate than the source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FpsMode {
+    /// Keep the nearest source frame and drop the others (ffmpeg `fps`)
+    #[default]
+    Drop,
+    /// Blend neighbouring source frames into each output frame (ffmpeg `framerate`); smoother motion, softer frames
+    Blend,
+}
+
+impl FpsMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Drop => "drop",
+            Self::Blend => "blend",
+        }
+    }
+}
+
+/// How [`VideoOptions::pipe_frames`] reads decoded frames from ffmpeg.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PipeFormat {
+    /// Raw RGB (`-f rawvideo`): free to read, but ffmpeg is first run for one frame to learn the
+    /// size the filters produce
+    #[default]
+    Raw,
+    /// A stream of uncompressed PNGs (`-f image2pipe -c:v png`) split apart as they arrive and decoded
+    /// on the conversion workers, frames in parallel. Each PNG carries its size, so ffmpeg is run once
+    /// and conversion starts with its first frame.
+    Png,
+    /// A YUV4MPEG2 stream of 4:4:4 frames (`-f yuv4mpegpipe`), turned into RGB on the conversion
+    /// workers. Neither PNG encoding nor a probe run: the stream header carries the size. Characters
+    /// are picked from the Y plane when the luminance model measures the luma of
+    /// [`VideoOptions::yuv_matrix`]; colors go through YUV and back, so they may be off by a step or two.
+    Y4m,
+}
+
+impl PipeFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Raw => "raw",
+            Self::Png => "png",
+            Self::Y4m => "y4m",
+        }
+    }
+}
+
+/// Curve that fits HDR brightness into the SDR range, one of ffmpeg's `tonemap` algorithms.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToneMapCurve {
+    /// Filmic curve that keeps detail in highlights and shadows
+    #[default]
+    Hable,
+    /// Simple curve, a little flatter than Hable
+    Reinhard,
+    /// Leaves everything below the knee untouched and rolls off above it
+    Mobius,
+    /// Hard clip at SDR white
+    Clip,
+}
+
+impl ToneMapCurve {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hable => "hable",
+            Self::Reinhard => "reinhard",
+            Self::Mobius => "mobius",
+            Self::Clip => "clip",
+        }
+    }
+}
+
+/// Tone mapping applied to HDR (PQ or HLG) video before conversion.
+///
+/// Without it, HDR frames decode as washed-out near-white images and the luminance ramp collapses
+/// onto its brightest characters.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ToneMap {
+    /// Tone mapping curve
+    pub curve: ToneMapCurve,
+    /// Exposure adjustment in stops applied before the curve; positive brightens, 0.0 keeps the source level
+    pub exposure: f32,
+}
+
+impl ToneMap {
+    /// ffmpeg filters converting PQ/HLG video to tone-mapped BT.709. Exposure scales the nominal peak
+    /// luminance (100 nits at 0 stops) that linear light is normalized against. Needs ffmpeg built with zimg.
+    #[cfg(feature = "cli")]
+    pub(crate) fn ffmpeg_filter(&self) -> String {
+        let peak_nits = (100.0 / 2f32.powf(self.exposure)).round().max(1.0);
+        format!("zscale=t=linear:npl={},format=gbrpf32le,zscale=p=bt709,tonemap=tonemap={}:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p", peak_nits, self.curve.as_str())
+    }
+}
+
+/// Options for video conversion
+#[derive(Debug, Clone)]
+pub struct VideoOptions {
+    /// Frames per second to extract
+    pub fps: u32,
+    /// Start time (e.g., "00:01:23.456" or "83.456")
+    pub start: Option<String>,
+    /// End time (e.g., "00:01:23.456" or "83.456")
+    pub end: Option<String>,
+    /// Target width in characters
+    pub columns: u32,
+    /// Whether to extract audio from the video
+    pub extract_audio: bool,
+    /// Optional ffmpeg filtergraph applied before cascii's scale/fps extraction
+    ///
+    /// Example: `"format=gray,edgedetect=mode=colormix:high=0.2:low=0.05"`
+    pub preprocess_filter: Option<String>,
+    /// Deinterlace with yadif before any other filter, for interlaced DVD and TV sources
+    pub deinterlace: bool,
+    /// How the source frame rate is brought down to `fps`
+    pub fps_mode: FpsMode,
+    /// Tone mapping for sources detected as HDR with ffprobe; `None` converts HDR sources as they decode
+    pub tone_map: Option<ToneMap>,
+    /// Aligned depth map modulating every frame (see [`DepthMap`](crate::DepthMap))
+    pub depth_map: Option<DepthMap>,
+    /// Captions drawn into the converted frames, timed from the first extracted frame (see [`CaptionTrack`](crate::CaptionTrack))
+    pub captions: Option<CaptionTrack>,
+    /// Read decoded frames straight from ffmpeg's stdout as raw RGB and convert them as they come,
+    /// instead of extracting every frame to a temporary PNG first. Much faster on long videos;
+    /// progress totals are then estimated from the duration.
+    pub pipe_frames: bool,
+    /// What piped frames are sent as
+    pub pipe_format: PipeFormat,
+    /// Matrix of the YUV frames [`PipeFormat::Y4m`] reads; `None` takes the source's `color_space`
+    /// from ffprobe, BT.709 for tone-mapped HDR and BT.601 (ffmpeg's own for RGB sources) otherwise
+    pub yuv_matrix: Option<YuvMatrix>,
+}
+
+impl VideoOptions {
+    /// Whether frames are piped from ffmpeg or get a per-frame pass (a depth map or captions), which only the sink pipeline runs
+    #[cfg(feature = "cli")]
+    pub(crate) fn needs_sink_pipeline(&self) -> bool {
+        self.pipe_frames || self.depth_map.is_some() || self.captions.is_some()
+    }
+
+    /// Whether a frame directory conversion with these options can pick up where an interrupted one
+    /// stopped (see [`AsciiConverter::with_resume`](crate::AsciiConverter::with_resume)); piped
+    /// frames, depth maps and captions always start over
+    #[cfg(feature = "cli")]
+    pub fn resumable(&self) -> bool {
+        !self.needs_sink_pipeline()
+    }
+}
+
+impl Default for VideoOptions {
+    fn default() -> Self {
+        Self {fps: 30, start: None, end: None, columns: 400, extract_audio: false, preprocess_filter: None, deinterlace: false, fps_mode: FpsMode::Drop, tone_map: Some(ToneMap::default()), depth_map: None, captions: None, pipe_frames: false, pipe_format: PipeFormat::Raw, yuv_matrix: None}
+    }
+}
+
+/// Options for rendering ASCII frames to a video file
+#[derive(Debug, Clone)]
+pub struct ToVideoOptions {
+    /// Output video file path (e.g., "output.mp4")
+    pub output_path: PathBuf,
+    /// Font size in pixels for rendering characters (determines output resolution)
+    pub font_size: f32,
+    /// CRF quality for H.264 encoding (0-51, lower is better quality, 18 is visually lossless)
+    pub crf: u8,
+    /// Whether to mux audio from the source video into the output
+    pub mux_audio: bool,
+    /// Override color rendering: `Some(true)` forces per-character colors from .cframe data,
+    /// `Some(false)` forces monochrome white-on-black, `None` auto-detects from file types.
+    pub use_colors: Option<bool>,
+    /// Text stroke width in pixels for rendering thicker glyphs.
+    pub text_stroke_width: f32,
+    /// Shift the muxed audio by this many seconds: positive delays it behind leading silence,
+    /// negative skips its beginning.
+    pub audio_offset: Option<f64>,
+    /// What to do when the muxed audio is shorter than the video (longer audio is always cut).
+    pub audio_padding: AudioPadding,
+    /// Copy the audio stream into the output instead of re-encoding it: straight from the source
+    /// video in [`AsciiConverter::convert_video_to_video`](crate::AsciiConverter::convert_video_to_video), or the `audio.mp3` of a frame directory.
+    /// When the container can't hold the codec, or the audio has to be padded or looped, it is
+    /// encoded to AAC once from the original instead.
+    pub audio_passthrough: bool,
+    /// Color behind the glyphs, black by default. Cell backgrounds fitted into `.cframe` files
+    /// are drawn over it.
+    pub background: (u8, u8, u8),
+    /// Glyph color when rendering without per-character colors; `None` draws them white.
+    /// Together with `background` this gives text-only renders a look, e.g. green on black.
+    pub foreground: Option<(u8, u8, u8)>,
+    /// Audio file muxed as an extra track titled "Narration", after the muxed audio if any, e.g.
+    /// one written by [`narration::synthesize_narration`](crate::narration::synthesize_narration). Padded with silence to the video's length.
+    pub narration: Option<PathBuf>,
+}
+
+impl Default for ToVideoOptions {
+    fn default() -> Self {
+        Self {output_path: PathBuf::from("output.mp4"), font_size: 14.0, crf: 18, mux_audio: false, use_colors: None, text_stroke_width: 0.0, audio_offset: None, audio_padding: AudioPadding::Auto, audio_passthrough: false, background: (0, 0, 0), foreground: None, narration: None}
+    }
+}
+
+/// Options for rendering ASCII frames to an animated GIF
+#[derive(Debug, Clone)]
+pub struct GifOptions {
+    /// Output GIF file path (e.g., "output.gif")
+    pub output_path: PathBuf,
+    /// Font size in pixels for rendering characters (determines output resolution)
+    pub font_size: f32,
+    /// Override color rendering: `Some(true)` forces per-character colors, `Some(false)` forces
+    /// monochrome white-on-black, `None` uses colors when the frames have them.
+    pub use_colors: Option<bool>,
+    /// Times the animation plays after the first; `None` loops forever.
+    pub loop_count: Option<u16>,
+    /// Palette quantization sampling factor, 1 (best colors, slowest) to 30 (fastest).
+    /// Every frame gets its own 256-color palette.
+    pub quantize_speed: u8,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        Self {output_path: PathBuf::from("output.gif"), font_size: 14.0, use_colors: None, loop_count: None, quantize_speed: 10}
+    }
+}
+
+/// One output of [`AsciiConverter::convert_video_to_sinks`](crate::AsciiConverter::convert_video_to_sinks)
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum OutputSink {
+    /// ASCII frame files in `dir`, written as [`ConversionOptions::output_mode`] asks, with the
+    /// details report and (if [`VideoOptions::extract_audio`] is set) `audio.mp3`, like
+    /// [`AsciiConverter::convert_video`](crate::AsciiConverter::convert_video). `keep_images` keeps the extracted PNG frames next to them.
+    Frames {dir: PathBuf, keep_images: bool},
+    /// An encoded ASCII-art video, like [`AsciiConverter::convert_video_to_video`](crate::AsciiConverter::convert_video_to_video)
+    Video(ToVideoOptions),
+    /// A single ANSI text file holding every frame in 24-bit color (256 colors for [`OutputMode::Ansi256`], plain text for
+    /// [`OutputMode::TextOnly`]); each frame starts with a cursor-home escape, so `cat`-ing the
+    /// file in a terminal plays the animation.
+    Ansi(PathBuf),
+}
+
+impl OutputSink {
+    /// Directory or file this sink writes to
+    pub fn path(&self) -> &std::path::Path {
+        match self {
+            Self::Frames {dir, ..} => dir,
+            Self::Video(to_video_opts) => &to_video_opts.output_path,
+            Self::Ansi(path) => path,
+        }
+    }
+}
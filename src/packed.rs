@@ -1,12 +1,27 @@
-use anyhow::{anyhow, Context, Result};
-use std::fs;
+use crate::error::{Context, Error, Result};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use walkdir::WalkDir;
 
+#[cfg(feature = "encryption")]
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+#[cfg(feature = "encryption")]
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+
+/// File extension of packed frame archives, as read by [`PackedArchive`] and
+/// [`FrameSequence::open`](crate::FrameSequence::open)
+pub const ARCHIVE_EXTENSION: &str = "casc";
+
 const FULL_CFRAME_PACK_MAGIC: &[u8; 4] = b"CFPK";
 const FULL_CFRAME_PACK_VERSION: u32 = 1;
 const FULL_CFRAME_PACK_HEADER_SIZE: usize = 12;
 
+const ENCRYPTED_PACK_MAGIC: &[u8; 4] = b"CFPE";
+const ENCRYPTED_PACK_VERSION: u32 = 1;
+const ENCRYPTED_PACK_HEADER_SIZE: usize = 20;
+
 /// A packed archive containing complete `.cframe` files.
 ///
 /// Unlike the legacy packed cframe transport, this format stores each source
@@ -37,7 +52,7 @@ fn collect_cframe_paths(source_dir: &Path) -> Result<Vec<PathBuf>> {
     paths.sort_by(|left, right| left.file_name().and_then(|name| name.to_str()).cmp(&right.file_name().and_then(|name| name.to_str())));
 
     if paths.is_empty() {
-        return Err(anyhow!("No .cframe files found in {}", source_dir.display()));
+        return Err(Error::NotFound(format!("No .cframe files found in {}", source_dir.display())));
     }
 
     Ok(paths)
@@ -53,15 +68,14 @@ fn collect_cframe_paths(source_dir: &Path) -> Result<Vec<PathBuf>> {
 ///   - byte length (`u32`)
 ///   - complete `.cframe` bytes
 pub fn pack_full_cframes_from_dir(source_dir: &Path) -> Result<Vec<u8>> {
-    let paths = collect_cframe_paths(source_dir)?;
-    let mut frames = Vec::with_capacity(paths.len());
-    for path in paths {
-        let data = fs::read(&path).with_context(|| format!("reading cframe {}", path.display()))?;
-        frames.push(data);
-    }
+    let frames = read_cframes_from_dir(source_dir)?;
     pack_full_cframes(frames.iter().map(Vec::as_slice))
 }
 
+fn read_cframes_from_dir(source_dir: &Path) -> Result<Vec<Vec<u8>>> {
+    collect_cframe_paths(source_dir)?.into_iter().map(|path| fs::read(&path).with_context(|| format!("reading cframe {}", path.display()))).collect()
+}
+
 /// Pack complete `.cframe` byte slices into one full-fidelity blob.
 pub fn pack_full_cframes<'a, I>(frames: I) -> Result<Vec<u8>>
 where
@@ -69,13 +83,13 @@ where
 {
     let frames: Vec<&[u8]> = frames.into_iter().collect();
     if frames.is_empty() {
-        return Err(anyhow!("No .cframe files provided"));
+        return Err(Error::NotFound("No .cframe files provided".into()));
     }
-    let frame_count = u32::try_from(frames.len()).map_err(|_| anyhow!("Too many frames to pack"))?;
+    let frame_count = u32::try_from(frames.len()).map_err(|_| Error::Other("Too many frames to pack".into()))?;
 
     let payload_len = frames.iter().try_fold(0usize, |acc, frame| {
-        let frame_len = u32::try_from(frame.len()).map_err(|_| anyhow!("A .cframe file is too large to pack"))?;
-        acc.checked_add(4).and_then(|value| value.checked_add(frame_len as usize)).ok_or_else(|| anyhow!("Packed cframe payload is too large"))
+        let frame_len = u32::try_from(frame.len()).map_err(|_| Error::InvalidCframe("A .cframe file is too large to pack".into()))?;
+        acc.checked_add(4).and_then(|value| value.checked_add(frame_len as usize)).ok_or_else(|| Error::InvalidCframe("Packed cframe payload is too large".into()))
     })?;
 
     let mut out = Vec::with_capacity(FULL_CFRAME_PACK_HEADER_SIZE + payload_len);
@@ -83,7 +97,7 @@ where
     out.extend_from_slice(&FULL_CFRAME_PACK_VERSION.to_le_bytes());
     out.extend_from_slice(&frame_count.to_le_bytes());
     for frame in frames {
-        let frame_len = u32::try_from(frame.len()).map_err(|_| anyhow!("A .cframe file is too large to pack"))?;
+        let frame_len = u32::try_from(frame.len()).map_err(|_| Error::InvalidCframe("A .cframe file is too large to pack".into()))?;
         out.extend_from_slice(&frame_len.to_le_bytes());
         out.extend_from_slice(frame);
     }
@@ -93,44 +107,325 @@ where
 /// Parse a full-fidelity packed `.cframe` blob.
 pub fn unpack_full_cframes(data: &[u8]) -> Result<FullCFramePack> {
     if data.len() < FULL_CFRAME_PACK_HEADER_SIZE {
-        return Err(anyhow!("packed cframe blob is too small"));
+        return Err(Error::InvalidCframe("packed cframe blob is too small".into()));
+    }
+    if &data[0..4] == ENCRYPTED_PACK_MAGIC {
+        return Err(Error::Encryption("packed cframe blob is encrypted; decrypt it with its key".into()));
     }
     if &data[0..4] != FULL_CFRAME_PACK_MAGIC {
-        return Err(anyhow!("packed cframe blob has invalid magic"));
+        return Err(Error::InvalidCframe("packed cframe blob has invalid magic".into()));
     }
 
     let version = u32::from_le_bytes(data[4..8].try_into().unwrap());
     if version != FULL_CFRAME_PACK_VERSION {
-        return Err(anyhow!("unsupported packed cframe version: {}", version));
+        return Err(Error::InvalidCframe(format!("unsupported packed cframe version: {}", version)));
     }
 
     let frame_count = u32::from_le_bytes(data[8..12].try_into().unwrap()) as usize;
-    if frame_count == 0 {
-        return Err(anyhow!("packed cframe blob contains no frames"));
-    }
+    check_frame_count(frame_count, (data.len() - FULL_CFRAME_PACK_HEADER_SIZE) as u64)?;
 
     let mut offset = FULL_CFRAME_PACK_HEADER_SIZE;
     let mut frames = Vec::with_capacity(frame_count);
     for _ in 0..frame_count {
         if offset + 4 > data.len() {
-            return Err(anyhow!("packed cframe blob is truncated before a frame length"));
+            return Err(Error::InvalidCframe("packed cframe blob is truncated before a frame length".into()));
         }
         let frame_len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
         offset += 4;
         if offset + frame_len > data.len() {
-            return Err(anyhow!("packed cframe blob is truncated inside a frame payload"));
+            return Err(Error::InvalidCframe("packed cframe blob is truncated inside a frame payload".into()));
         }
         frames.push(data[offset..offset + frame_len].to_vec());
         offset += frame_len;
     }
 
     if offset != data.len() {
-        return Err(anyhow!("packed cframe blob has {} trailing bytes", data.len() - offset));
+        return Err(Error::InvalidCframe(format!("packed cframe blob has {} trailing bytes", data.len() - offset)));
     }
 
     Ok(FullCFramePack::new(frames))
 }
 
+/// A key for encrypted archives: exactly 32 bytes, written as 64 hex digits (either case, surrounding
+/// whitespace ignored) and read back with [`str::parse`].
+#[cfg(feature = "encryption")]
+#[derive(Clone, PartialEq, Eq)]
+pub struct ArchiveKey([u8; 32]);
+
+#[cfg(feature = "encryption")]
+impl ArchiveKey {
+    /// A new random key from the operating system's generator
+    pub fn generate() -> Self {
+        Self(ChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    /// A key from its 32 raw bytes, e.g. from a key store
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// The key as 64 lowercase hex digits, the form `--key` and [`str::parse`] take
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(&self.0.into())
+    }
+}
+
+/// Keeps keys out of logs and panic messages.
+#[cfg(feature = "encryption")]
+impl std::fmt::Debug for ArchiveKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ArchiveKey(..)")
+    }
+}
+
+/// Parses the 64 hex digits of [`ArchiveKey::to_hex`].
+#[cfg(feature = "encryption")]
+impl std::str::FromStr for ArchiveKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut bytes = [0u8; 32];
+        if s.len() != 64 || !s.is_ascii() {
+            return Err("expected a key of 64 hex digits".to_string());
+        }
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[index * 2..index * 2 + 2], 16).map_err(|_| "expected a key of 64 hex digits".to_string())?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+/// Seals and opens the frames of one encrypted archive.
+#[cfg(feature = "encryption")]
+struct FrameCipher {
+    cipher: ChaCha20Poly1305,
+    /// The archive header, bound to every frame as associated data
+    header: [u8; ENCRYPTED_PACK_HEADER_SIZE],
+}
+
+#[cfg(feature = "encryption")]
+impl FrameCipher {
+    /// Frame `index` is sealed under the header's nonce prefix followed by its index
+    fn nonce(&self, index: u32) -> Nonce {
+        let mut nonce = [0u8; 12];
+        nonce[..8].copy_from_slice(&self.header[12..20]);
+        nonce[8..].copy_from_slice(&index.to_le_bytes());
+        nonce.into()
+    }
+
+    fn seal(&self, index: u32, frame: &[u8]) -> Result<Vec<u8>> {
+        self.cipher.encrypt(&self.nonce(index), Payload {msg: frame, aad: &self.header}).map_err(|_| Error::Encryption(format!("encrypting frame {}", index + 1)))
+    }
+
+    fn open(&self, index: u32, sealed: &[u8]) -> Result<Vec<u8>> {
+        self.cipher.decrypt(&self.nonce(index), Payload {msg: sealed, aad: &self.header}).map_err(|_| Error::Encryption(format!("frame {} does not decrypt: wrong key, or the archive was modified", index + 1)))
+    }
+}
+
+/// Pack complete `.cframe` byte slices like [`pack_full_cframes`], encrypting and authenticating
+/// every frame with ChaCha20-Poly1305 under `key`.
+///
+/// Format:
+/// - bytes 0..4: magic `CFPE`
+/// - bytes 4..8: version (`u32`, currently `1`)
+/// - bytes 8..12: frame count (`u32`)
+/// - bytes 12..20: random nonce prefix
+/// - repeated per frame:
+///   - sealed length (`u32`): the frame's length plus the 16-byte tag
+///   - the encrypted `.cframe` bytes and their tag
+///
+/// Frame `i` (from 0) is sealed with the nonce prefix followed by `i` (`u32`) and the 20 header
+/// bytes as associated data, so every frame decrypts on its own, while reordered, dropped or
+/// swapped-in frames fail to. Frame lengths are not hidden.
+#[cfg(feature = "encryption")]
+pub fn encrypt_full_cframes<'a, I>(frames: I, key: &ArchiveKey) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let frames: Vec<&[u8]> = frames.into_iter().collect();
+    if frames.is_empty() {
+        return Err(Error::NotFound("No .cframe files provided".into()));
+    }
+    let frame_count = u32::try_from(frames.len()).map_err(|_| Error::Other("Too many frames to pack".into()))?;
+
+    let mut header = [0u8; ENCRYPTED_PACK_HEADER_SIZE];
+    header[0..4].copy_from_slice(ENCRYPTED_PACK_MAGIC);
+    header[4..8].copy_from_slice(&ENCRYPTED_PACK_VERSION.to_le_bytes());
+    header[8..12].copy_from_slice(&frame_count.to_le_bytes());
+    header[12..20].copy_from_slice(&ChaCha20Poly1305::generate_nonce(&mut OsRng)[..8]);
+    let cipher = FrameCipher {cipher: key.cipher(), header};
+
+    let mut out = header.to_vec();
+    for (index, frame) in frames.into_iter().enumerate() {
+        let sealed = cipher.seal(index as u32, frame)?;
+        let sealed_len = u32::try_from(sealed.len()).map_err(|_| Error::InvalidCframe("A .cframe file is too large to pack".into()))?;
+        out.extend_from_slice(&sealed_len.to_le_bytes());
+        out.extend_from_slice(&sealed);
+    }
+    Ok(out)
+}
+
+/// [`encrypt_full_cframes`] on every `frame_*.cframe` file in a directory.
+#[cfg(feature = "encryption")]
+pub fn encrypt_full_cframes_from_dir(source_dir: &Path, key: &ArchiveKey) -> Result<Vec<u8>> {
+    let frames = read_cframes_from_dir(source_dir)?;
+    encrypt_full_cframes(frames.iter().map(Vec::as_slice), key)
+}
+
+/// Decrypt and parse a blob from [`encrypt_full_cframes`].
+#[cfg(feature = "encryption")]
+pub fn decrypt_full_cframes(data: &[u8], key: &ArchiveKey) -> Result<FullCFramePack> {
+    let archive = PackedArchive::from_reader(std::io::Cursor::new(data.to_vec()), Some(key))?;
+    (0..archive.len()).map(|index| archive.frame(index)).collect::<Result<Vec<_>>>().map(FullCFramePack::new)
+}
+
+/// A packed archive (`.casc`) read one frame at a time: opening it reads only the frame lengths,
+/// and each frame is read, and decrypted for encrypted archives, when it is asked for. Handy for
+/// playing or post-processing long clips from a single file.
+///
+/// ```no_run
+/// use cascii::packed::PackedArchive;
+///
+/// # fn main() -> cascii::Result<()> {
+/// let archive = PackedArchive::open("clip.casc")?;
+/// let last = archive.frame(archive.len() - 1)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PackedArchive {
+    source: Mutex<Box<dyn ReadSeek + Send>>,
+    frames: FrameSpans,
+    #[cfg(feature = "encryption")]
+    cipher: Option<FrameCipher>,
+}
+
+/// Stands in for the key type in builds without the `encryption` feature, where no key exists
+#[cfg(not(feature = "encryption"))]
+enum ArchiveKey {}
+
+/// Where each frame's payload starts in an archive, and its length
+type FrameSpans = Vec<(u64, u32)>;
+
+pub(crate) trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+impl PackedArchive {
+    /// Open an unencrypted archive.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("opening archive {}", path.display()))?;
+        Self::from_reader(file, None).with_context(|| format!("reading archive {}", path.display()))
+    }
+
+    /// Open an archive encrypted with `key`; unencrypted archives open too.
+    #[cfg(feature = "encryption")]
+    pub fn open_with_key(path: impl AsRef<Path>, key: &ArchiveKey) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("opening archive {}", path.display()))?;
+        Self::from_reader(file, Some(key)).with_context(|| format!("reading archive {}", path.display()))
+    }
+
+    fn from_reader(mut source: impl Read + Seek + Send + 'static, key: Option<&ArchiveKey>) -> Result<Self> {
+        let (header, frames) = scan_archive(&mut source)?;
+        let source: Mutex<Box<dyn ReadSeek + Send>> = Mutex::new(Box::new(source));
+        if header.len() != ENCRYPTED_PACK_HEADER_SIZE {
+            return Ok(Self {source, frames, #[cfg(feature = "encryption")] cipher: None});
+        }
+        match key {
+            #[cfg(feature = "encryption")]
+            Some(key) => Ok(Self {source, frames, cipher: Some(FrameCipher {cipher: key.cipher(), header: header.try_into().expect("encrypted header length")})}),
+            #[cfg(not(feature = "encryption"))]
+            Some(key) => match *key {},
+            None if cfg!(feature = "encryption") => Err(Error::Encryption("the archive is encrypted; open it with its key".into())),
+            None => Err(Error::Encryption("the archive is encrypted; reading it needs cascii built with the `encryption` feature".into())),
+        }
+    }
+
+    /// Number of frames in the archive
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether the archive holds no frames
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The complete `.cframe` bytes of frame `index` (from 0).
+    pub fn frame(&self, index: usize) -> Result<Vec<u8>> {
+        let &(offset, len) = self.frames.get(index).ok_or_else(|| Error::InvalidOptions(format!("frame {} is past the last of {} frames", index, self.frames.len())))?;
+        let mut payload = vec![0u8; len as usize];
+        {
+            let mut source = self.source.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            source.seek(SeekFrom::Start(offset))?;
+            source.read_exact(&mut payload).with_context(|| format!("reading frame {}", index + 1))?;
+        }
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = &self.cipher {
+            return cipher.open(index as u32, &payload);
+        }
+        Ok(payload)
+    }
+}
+
+/// Read the header and the frame lengths of an archive, skipping over the frames. Returns the
+/// header bytes (12 for plain archives, 20 for encrypted ones) and where every frame is.
+/// Reject a header frame count of zero, or one the `body_len` bytes after the header can't hold
+/// even as empty frames, before anything is allocated for it
+fn check_frame_count(frame_count: usize, body_len: u64) -> Result<()> {
+    if frame_count == 0 {
+        return Err(Error::InvalidCframe("packed cframe blob contains no frames".into()));
+    }
+    if frame_count as u64 > body_len / 4 {
+        return Err(Error::InvalidCframe(format!("packed cframe blob claims {} frames but has room for at most {}", frame_count, body_len / 4)));
+    }
+    Ok(())
+}
+
+fn scan_archive(source: &mut impl ReadSeek) -> Result<(Vec<u8>, FrameSpans)> {
+    let total = source.seek(SeekFrom::End(0))?;
+    source.seek(SeekFrom::Start(0))?;
+    let mut header = vec![0u8; FULL_CFRAME_PACK_HEADER_SIZE];
+    source.read_exact(&mut header).map_err(|_| Error::InvalidCframe("packed cframe blob is too small".into()))?;
+    let magic = &header[0..4];
+    if magic == ENCRYPTED_PACK_MAGIC {
+        header.resize(ENCRYPTED_PACK_HEADER_SIZE, 0);
+        source.read_exact(&mut header[FULL_CFRAME_PACK_HEADER_SIZE..]).map_err(|_| Error::InvalidCframe("packed cframe blob is too small".into()))?;
+    } else if magic != FULL_CFRAME_PACK_MAGIC {
+        return Err(Error::InvalidCframe("packed cframe blob has invalid magic".into()));
+    }
+    let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    if version != if header.len() == ENCRYPTED_PACK_HEADER_SIZE {ENCRYPTED_PACK_VERSION} else {FULL_CFRAME_PACK_VERSION} {
+        return Err(Error::InvalidCframe(format!("unsupported packed cframe version: {}", version)));
+    }
+    let frame_count = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+    check_frame_count(frame_count, total - header.len() as u64)?;
+
+    let mut offset = header.len() as u64;
+    let mut frames = Vec::with_capacity(frame_count);
+    for _ in 0..frame_count {
+        let mut len = [0u8; 4];
+        source.read_exact(&mut len).map_err(|_| Error::InvalidCframe("packed cframe blob is truncated before a frame length".into()))?;
+        let len = u32::from_le_bytes(len);
+        offset += 4;
+        if offset + len as u64 > total {
+            return Err(Error::InvalidCframe("packed cframe blob is truncated inside a frame payload".into()));
+        }
+        frames.push((offset, len));
+        offset = source.seek(SeekFrom::Current(len as i64))?;
+    }
+    if offset != total {
+        return Err(Error::InvalidCframe(format!("packed cframe blob has {} trailing bytes", total - offset)));
+    }
+    Ok((header, frames))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -173,4 +468,56 @@ mod tests {
 
         assert_eq!(unpacked.frames, vec![first, second]);
     }
+
+    #[test]
+    fn archive_reads_frames_on_demand() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.casc");
+        let (first, second) = (cframe_with_background(), vec![1, 0, 0, 0, 1, 0, 0, 0, b'x', 1, 2, 3]);
+        fs::write(&path, pack_full_cframes([first.as_slice(), second.as_slice()]).unwrap()).unwrap();
+
+        let archive = PackedArchive::open(&path).unwrap();
+        assert_eq!((archive.len(), archive.frame(1).unwrap()), (2, second));
+        assert!(archive.frame(2).is_err());
+        let sequence = crate::FrameSequence::open(&path).unwrap();
+        assert_eq!(sequence.read(0).unwrap().text, "AB\n");
+
+        fs::write(&path, &pack_full_cframes([first.as_slice()]).unwrap()[..20]).unwrap();
+        assert!(PackedArchive::open(&path).is_err());
+    }
+
+    #[test]
+    fn header_frame_count_is_checked_against_the_file_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("huge.casc");
+        let mut header = FULL_CFRAME_PACK_MAGIC.to_vec();
+        header.extend_from_slice(&FULL_CFRAME_PACK_VERSION.to_le_bytes());
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &header).unwrap();
+
+        assert!(PackedArchive::open(&path).is_err_and(|err| err.to_string().contains("room for at most 0")));
+        assert!(unpack_full_cframes(&header).is_err());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypted_archive_needs_its_key_and_detects_tampering() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.casc");
+        let frame = cframe_with_background();
+        let key = ArchiveKey::generate();
+        let mut data = encrypt_full_cframes([frame.as_slice(), frame.as_slice()], &key).unwrap();
+        assert_eq!(decrypt_full_cframes(&data, &key).unwrap().frames, vec![frame.clone(), frame.clone()]);
+        assert_eq!(key.to_hex().parse::<ArchiveKey>().unwrap(), key);
+
+        fs::write(&path, &data).unwrap();
+        assert!(PackedArchive::open(&path).is_err() && unpack_full_cframes(&data).is_err());
+        assert!(decrypt_full_cframes(&data, &ArchiveKey::generate()).is_err());
+        let sequence = crate::FrameSequence::open_with_key(&path, &key).unwrap();
+        assert_eq!((sequence.len(), sequence.read(1).unwrap().text.as_str()), (2, "AB\n"));
+
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(decrypt_full_cframes(&data, &key).is_err());
+    }
 }
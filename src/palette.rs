@@ -0,0 +1,165 @@
+//! Accessibility palettes applied to per-cell colors.
+//!
+//! A [`Palette`] remaps every foreground and background color a conversion produces, before it is
+//! written to `.cframe` files or rendered to video; [`Palette::HighContrast`] draws on black backgrounds. [`Palette::Truecolor`] keeps the sampled colors
+//! untouched; the others trade color fidelity for legibility.
+
+use serde::{Deserialize, Serialize};
+
+const RGB_SIZE: usize = 3;
+
+/// Color remapping applied to converted cells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum Palette {
+    /// Keep the sampled colors (the default)
+    #[default]
+    Truecolor,
+    /// Daltonized for deuteranopia (green-weak): red/green differences are moved into blue and brightness
+    Deuteranopia,
+    /// Daltonized for protanopia (red-weak): red/green differences are moved into blue and brightness
+    Protanopia,
+    /// Snap every color to a small set of saturated, bright colors that stay legible on a black background
+    HighContrast,
+}
+
+/// Bright colors used by [`Palette::HighContrast`]; each has at least 7:1 contrast against black.
+const HIGH_CONTRAST_COLORS: [[u8; 3]; 8] = [[255, 255, 255], [255, 255, 0], [0, 255, 255], [0, 255, 0], [255, 85, 255], [255, 165, 0], [255, 96, 96], [128, 176, 255]];
+
+/// Channel spread below which a color counts as grey and maps to white in [`Palette::HighContrast`].
+const HIGH_CONTRAST_GREY_SPREAD: u8 = 48;
+
+impl Palette {
+    /// All palettes, in the order they are listed by the CLI.
+    pub const ALL: [Palette; 4] = [Palette::Truecolor, Palette::Deuteranopia, Palette::Protanopia, Palette::HighContrast];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Truecolor => "truecolor",
+            Self::Deuteranopia => "deuteranopia",
+            Self::Protanopia => "protanopia",
+            Self::HighContrast => "high-contrast",
+        }
+    }
+
+    /// Look up a palette by its [`as_str`](Self::as_str) name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|palette| palette.as_str() == name)
+    }
+
+    /// Remap a single color.
+    pub fn map_rgb(self, rgb: [u8; 3]) -> [u8; 3] {
+        match self {
+            Self::Truecolor => rgb,
+            Self::Deuteranopia => daltonize(rgb, simulate_deuteranopia),
+            Self::Protanopia => daltonize(rgb, simulate_protanopia),
+            Self::HighContrast => high_contrast(rgb),
+        }
+    }
+
+    /// Remap a flat `R, G, B, R, G, B, ...` buffer in place. A trailing partial triplet is left untouched.
+    pub fn apply_rgb_triplets(self, rgb: &mut [u8]) {
+        if self == Self::Truecolor {
+            return;
+        }
+        for color in rgb.chunks_exact_mut(RGB_SIZE) {
+            let mapped = self.map_rgb([color[0], color[1], color[2]]);
+            color.copy_from_slice(&mapped);
+        }
+    }
+
+    /// Remap a flat buffer of cell background colors in place. [`Palette::HighContrast`] turns every
+    /// background black, the color its bright glyph colors are picked to stand out against; the other
+    /// palettes remap backgrounds like [`apply_rgb_triplets`](Self::apply_rgb_triplets).
+    pub fn apply_background_triplets(self, rgb: &mut [u8]) {
+        match self {
+            Self::HighContrast => rgb.fill(0),
+            _ => self.apply_rgb_triplets(rgb),
+        }
+    }
+}
+
+fn rgb_to_lms([r, g, b]: [f32; 3]) -> [f32; 3] {
+    [17.8824 * r + 43.5161 * g + 4.11935 * b, 3.45565 * r + 27.1554 * g + 3.86714 * b, 0.0299566 * r + 0.184309 * g + 1.46709 * b]
+}
+
+fn lms_to_rgb([l, m, s]: [f32; 3]) -> [f32; 3] {
+    [0.080_944_45 * l - 0.130_504_41 * m + 0.116_721_07 * s, -0.010_248_533 * l + 0.054_019_33 * m - 0.113_614_71 * s, -0.000_365_296_94 * l - 0.004_121_614_7 * m + 0.693_511_4 * s]
+}
+
+fn simulate_protanopia([_, m, s]: [f32; 3]) -> [f32; 3] {
+    [2.02344 * m - 2.52581 * s, m, s]
+}
+
+fn simulate_deuteranopia([l, _, s]: [f32; 3]) -> [f32; 3] {
+    [l, 0.494207 * l + 1.24827 * s, s]
+}
+
+/// Fidaner-style daltonization: simulate the deficiency, then shift the lost signal into the channels that are still perceived.
+fn daltonize(rgb: [u8; 3], simulate: fn([f32; 3]) -> [f32; 3]) -> [u8; 3] {
+    let original = rgb.map(|c| c as f32);
+    let simulated = lms_to_rgb(simulate(rgb_to_lms(original)));
+    let err_r = original[0] - simulated[0];
+    let err_g = original[1] - simulated[1];
+    let err_b = original[2] - simulated[2];
+    let corrected = [original[0], original[1] + 0.7 * err_r + err_g, original[2] + 0.7 * err_r + err_b];
+    corrected.map(|c| c.round().clamp(0.0, 255.0) as u8)
+}
+
+fn high_contrast(rgb: [u8; 3]) -> [u8; 3] {
+    let max = rgb.into_iter().max().unwrap_or(0);
+    let min = rgb.into_iter().min().unwrap_or(0);
+    if max - min < HIGH_CONTRAST_GREY_SPREAD {
+        return HIGH_CONTRAST_COLORS[0];
+    }
+    // Compare hues at full brightness so dark and light shades of the same color snap to the same entry.
+    let scale = 255.0 / max as f32;
+    let normalized = rgb.map(|c| c as f32 * scale);
+    let distance = |color: &[u8; 3]| -> f32 { color.iter().zip(normalized).map(|(&a, b)| (a as f32 - b).powi(2)).sum() };
+    *HIGH_CONTRAST_COLORS[1..].iter().min_by(|a, b| distance(a).total_cmp(&distance(b))).expect("palette has saturated entries")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for palette in Palette::ALL {
+            assert_eq!(Palette::from_name(palette.as_str()), Some(palette));
+            assert_eq!(serde_json::to_string(&palette).unwrap(), format!("\"{}\"", palette.as_str()));
+        }
+        assert_eq!(Palette::from_name("sepia"), None);
+    }
+
+    #[test]
+    fn truecolor_is_identity() {
+        let mut rgb = vec![1, 2, 3, 200, 100, 50];
+        Palette::Truecolor.apply_rgb_triplets(&mut rgb);
+        assert_eq!(rgb, vec![1, 2, 3, 200, 100, 50]);
+    }
+
+    #[test]
+    fn daltonized_palettes_separate_red_and_green() {
+        for palette in [Palette::Deuteranopia, Palette::Protanopia] {
+            let red = palette.map_rgb([200, 40, 40]);
+            let green = palette.map_rgb([40, 160, 40]);
+            assert!(red[2].abs_diff(green[2]) > 40, "{:?}: {:?} vs {:?}", palette, red, green);
+            // Greys carry no red/green signal and stay (almost) unchanged.
+            let grey = palette.map_rgb([128, 128, 128]);
+            assert!(grey.iter().all(|c| c.abs_diff(128) <= 2), "{:?}: {:?}", palette, grey);
+        }
+    }
+
+    #[test]
+    fn high_contrast_snaps_to_bright_colors() {
+        assert_eq!(Palette::HighContrast.map_rgb([90, 90, 95]), [255, 255, 255]);
+        assert_eq!(Palette::HighContrast.map_rgb([10, 60, 10]), [0, 255, 0]);
+        assert_eq!(Palette::HighContrast.map_rgb([120, 120, 10]), [255, 255, 0]);
+        let mut rgb = vec![30, 30, 120, 250, 240, 5, 7];
+        Palette::HighContrast.apply_rgb_triplets(&mut rgb);
+        assert!(HIGH_CONTRAST_COLORS.contains(&[rgb[0], rgb[1], rgb[2]]));
+        assert_eq!(&rgb[3..], &[255, 255, 0, 7]);
+    }
+}
@@ -0,0 +1,365 @@
+//! Playback timing for players of converted frames: speed, seeking, A-B loops and frame stepping.
+//!
+//! A [`PlaybackClock`] turns the wall-clock time between drawn frames into the frame to draw. It
+//! keeps the playhead as a fractional frame position, so any speed multiplier plays smoothly at any
+//! refresh rate, and wraps it inside an A-B loop range when one is set. Players map keys to
+//! [`PlaybackCommand`]s with [`PlaybackCommand::from_key`] and apply them to the clock.
+//!
+//! Audio follows the playhead through [`PlaybackClock::audio`]: between [`AUDIO_SPEED_RANGE`] the
+//! audio is resampled to the playback speed, outside it (and while paused) it is muted. Every seek,
+//! step and loop wrap bumps [`AudioCue::jumps`], telling the player to restart audio at the new
+//! position instead of letting it drift. While audio plays, it is the master clock:
+//! [`PlaybackClock::lock_to_audio`] drops or repeats frames whenever the video drifts from it.
+//! [`PlaybackClock::follow_audio`] does both for any [`AudioOutput`].
+
+use std::ops::{Range, RangeInclusive};
+use std::time::Duration;
+
+/// Speed multipliers [`PlaybackCommand::Faster`] and [`PlaybackCommand::Slower`] step through
+pub const SPEED_STEPS: [f64; 9] = [0.25, 0.5, 0.75, 1.0, 1.25, 1.5, 2.0, 3.0, 4.0];
+/// Speeds [`PlaybackClock::set_speed`] accepts; others are clamped into it
+pub const SPEED_RANGE: RangeInclusive<f64> = 0.1..=8.0;
+/// Speeds at which audio is resampled to stay in sync; audio is muted outside it
+pub const AUDIO_SPEED_RANGE: RangeInclusive<f64> = 0.5..=2.0;
+/// Seconds the seek keys jump by
+pub const SEEK_STEP_SECS: f64 = 5.0;
+/// Frames the video may drift from the audio before [`PlaybackClock::lock_to_audio`] corrects it
+pub const MAX_AUDIO_DRIFT_FRAMES: f64 = 1.0;
+
+/// An action on a [`PlaybackClock`], usually from a key press.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackCommand {
+    TogglePause,
+    /// Next speed of [`SPEED_STEPS`]
+    Faster,
+    /// Previous speed of [`SPEED_STEPS`]
+    Slower,
+    /// Back to 1×
+    ResetSpeed,
+    /// Pause and show the next frame
+    StepForward,
+    /// Pause and show the previous frame
+    StepBackward,
+    /// Jump by this many seconds of the clip (negative for backwards)
+    SeekBy(f64),
+    /// Jump to this fraction (0-1) of the clip
+    SeekToFraction(f64),
+    /// Set the loop start, then the loop end, then clear the loop
+    CycleLoop,
+}
+
+impl PlaybackCommand {
+    /// The command bound to `key`:
+    ///
+    /// | Key | Command |
+    /// |-----|---------|
+    /// | space | pause / resume |
+    /// | `[` / `]` | slower / faster |
+    /// | `=` | normal speed |
+    /// | `,` / `.` | step one frame back / forward |
+    /// | `h` / `l` | seek [`SEEK_STEP_SECS`] back / forward |
+    /// | `0`-`9` | seek to 0%-90% |
+    /// | `a` | set A, set B, clear the A-B loop |
+    pub fn from_key(key: char) -> Option<Self> {
+        Some(match key {
+            ' ' => Self::TogglePause,
+            '[' => Self::Slower,
+            ']' => Self::Faster,
+            '=' => Self::ResetSpeed,
+            ',' => Self::StepBackward,
+            '.' => Self::StepForward,
+            'h' => Self::SeekBy(-SEEK_STEP_SECS),
+            'l' => Self::SeekBy(SEEK_STEP_SECS),
+            '0'..='9' => Self::SeekToFraction(key.to_digit(10)? as f64 / 10.0),
+            'a' => Self::CycleLoop,
+            _ => return None,
+        })
+    }
+}
+
+/// Where and how fast audio should play to match the video.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioCue {
+    /// Position in the audio track (clip time, not wall time)
+    pub position: Duration,
+    /// Playback rate to resample the audio to, or `None` while paused or muted
+    pub rate: Option<f64>,
+    /// Count of seeks, steps and loop wraps so far; restart the audio at `position` when it changes
+    pub jumps: u64,
+}
+
+/// Something that plays a clip's audio by following [`AudioCue`]s, such as
+/// `audio_playback::AudioPlayer` (feature `audio`) or [`FfplayAudio`](crate::ffplay::FfplayAudio).
+pub trait AudioOutput {
+    /// Seek, resample or pause to match `cue`
+    fn follow(&mut self, cue: AudioCue);
+    /// Clip time the audio has reached, or `None` while it is silent
+    fn position(&mut self) -> Option<Duration>;
+}
+
+/// The playhead of a clip of `frames` frames at `fps`.
+///
+/// ```
+/// use cascii::playback::{PlaybackClock, PlaybackCommand};
+/// use std::time::Duration;
+///
+/// let mut clock = PlaybackClock::new(240, 24.0).with_speed(2.0);
+/// assert_eq!(clock.advance(Duration::from_secs(1)), 48);
+/// clock.apply(PlaybackCommand::from_key('h').unwrap());
+/// assert_eq!(clock.frame(), 0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaybackClock {
+    frames: usize,
+    fps: f64,
+    speed: f64,
+    /// Playhead in frames; `frames` once a clip without loop or repeat has ended
+    position: f64,
+    paused: bool,
+    repeat: bool,
+    loop_range: Option<Range<usize>>,
+    /// A loop start set by [`PlaybackCommand::CycleLoop`], waiting for its end
+    loop_start: Option<usize>,
+    jumps: u64,
+}
+
+impl PlaybackClock {
+    /// A clock at the first frame, playing at 1×.
+    pub fn new(frames: usize, fps: f64) -> Self {
+        Self {frames, fps: if fps > 0.0 {fps} else {1.0}, speed: 1.0, position: 0.0, paused: false, repeat: false, loop_range: None, loop_start: None, jumps: 0}
+    }
+
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.set_speed(speed);
+        self
+    }
+
+    /// Start over from the first frame at the end of the clip instead of stopping.
+    pub fn with_repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn with_loop_range(mut self, range: Range<usize>) -> Self {
+        self.set_loop_range(Some(range));
+        self
+    }
+
+    /// Index of the frame to draw.
+    pub fn frame(&self) -> usize {
+        (self.position as usize).min(self.frames.saturating_sub(1))
+    }
+
+    /// Clip time of the playhead.
+    pub fn time(&self) -> Duration {
+        Duration::from_secs_f64(self.position / self.fps)
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Set the speed multiplier, clamped to [`SPEED_RANGE`].
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = if speed.is_finite() {speed.clamp(*SPEED_RANGE.start(), *SPEED_RANGE.end())} else {1.0};
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Whether a clip without loop or repeat has played to its end.
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.frames as f64
+    }
+
+    /// The A-B loop range of frame indices, if any.
+    pub fn loop_range(&self) -> Option<Range<usize>> {
+        self.loop_range.clone()
+    }
+
+    /// Loop playback over `range` (clamped to the clip), or play the whole clip again with `None`
+    /// or an empty range. A playhead outside the new range moves to its start.
+    pub fn set_loop_range(&mut self, range: Option<Range<usize>>) {
+        self.loop_start = None;
+        self.loop_range = range.map(|range| range.start.min(self.frames)..range.end.min(self.frames)).filter(|range| !range.is_empty());
+        if let Some(range) = &self.loop_range {
+            if !range.contains(&self.frame()) || self.is_finished() {
+                self.position = range.start as f64;
+                self.jumps += 1;
+            }
+        }
+    }
+
+    /// Move the playhead to frame `index`, kept inside the loop range when one is set.
+    pub fn seek_to_frame(&mut self, index: usize) {
+        let (start, end) = self.bounds();
+        self.position = index.clamp(start, end.saturating_sub(1).max(start)) as f64;
+        self.jumps += 1;
+    }
+
+    /// Move the playhead to clip time `time`.
+    pub fn seek_to_time(&mut self, time: Duration) {
+        self.seek_to_frame((time.as_secs_f64() * self.fps + 1e-9) as usize);
+    }
+
+    /// Move the playhead by `secs` seconds of clip time (backwards when negative).
+    pub fn seek_by(&mut self, secs: f64) {
+        let target = self.position.min(self.frames as f64) + secs * self.fps;
+        self.seek_to_frame(target.max(0.0) as usize);
+    }
+
+    /// Pause and move `frames` frames forward (backwards when negative), wrapping around the loop
+    /// range or the clip.
+    pub fn step(&mut self, frames: isize) {
+        self.paused = true;
+        let (start, end) = self.bounds();
+        if end > start {
+            let len = (end - start) as isize;
+            self.position = (start as isize + (self.frame() as isize - start as isize + frames).rem_euclid(len)) as f64;
+            self.jumps += 1;
+        }
+    }
+
+    /// Advance the playhead by `elapsed` wall time at the current speed and return the frame to draw.
+    pub fn advance(&mut self, elapsed: Duration) -> usize {
+        if self.paused || self.is_finished() || self.frames == 0 {
+            return self.frame();
+        }
+        self.position += elapsed.as_secs_f64() * self.fps * self.speed;
+        let (start, end) = self.bounds();
+        if self.position >= end as f64 {
+            if self.loop_range.is_some() || self.repeat {
+                self.position = start as f64 + (self.position - start as f64) % (end - start) as f64;
+                self.jumps += 1;
+            } else {
+                self.position = self.frames as f64;
+            }
+        }
+        self.frame()
+    }
+
+    /// Move the playhead to `audio`, the clip time the audio has reached, when it drifted more than
+    /// [`MAX_AUDIO_DRIFT_FRAMES`] from it. Returns the frames skipped (dropped) when the video fell
+    /// behind, or minus the frames that will show again (repeated) when it ran ahead.
+    pub fn lock_to_audio(&mut self, audio: Duration) -> isize {
+        if self.paused || self.is_finished() || self.frames == 0 {
+            return 0;
+        }
+        let target = audio.as_secs_f64() * self.fps;
+        if (target - self.position).abs() <= MAX_AUDIO_DRIFT_FRAMES {
+            return 0;
+        }
+        let (start, end) = self.bounds();
+        let before = self.frame() as isize;
+        // Audio past the end of a loop catches up when the next advance wraps and restarts it
+        self.position = target.clamp(start as f64, if self.loop_range.is_some() {end as f64 - 1.0} else {end as f64});
+        self.frame() as isize - before
+    }
+
+    /// Keep the clock and `output` together for one drawn frame: `output` follows [`audio`](Self::audio),
+    /// then the clock locks to its position. Returns what [`lock_to_audio`](Self::lock_to_audio) does.
+    pub fn follow_audio(&mut self, output: &mut (impl AudioOutput + ?Sized)) -> isize {
+        output.follow(self.audio());
+        output.position().map_or(0, |position| self.lock_to_audio(position))
+    }
+
+    /// Carry out `command`.
+    pub fn apply(&mut self, command: PlaybackCommand) {
+        match command {
+            PlaybackCommand::TogglePause => self.paused = !self.paused,
+            PlaybackCommand::Faster => self.speed = SPEED_STEPS.into_iter().find(|&speed| speed > self.speed + 1e-9).unwrap_or(self.speed),
+            PlaybackCommand::Slower => self.speed = SPEED_STEPS.into_iter().rev().find(|&speed| speed < self.speed - 1e-9).unwrap_or(self.speed),
+            PlaybackCommand::ResetSpeed => self.speed = 1.0,
+            PlaybackCommand::StepForward => self.step(1),
+            PlaybackCommand::StepBackward => self.step(-1),
+            PlaybackCommand::SeekBy(secs) => self.seek_by(secs),
+            PlaybackCommand::SeekToFraction(fraction) => self.seek_to_frame((fraction.clamp(0.0, 1.0) * self.frames as f64) as usize),
+            PlaybackCommand::CycleLoop => match (self.loop_range.is_some(), self.loop_start) {
+                (true, _) => self.set_loop_range(None),
+                (false, None) => self.loop_start = Some(self.frame()),
+                (false, Some(start)) => {
+                    let frame = self.frame();
+                    self.set_loop_range(Some(start.min(frame)..start.max(frame) + 1));
+                }
+            },
+        }
+    }
+
+    /// Where the audio track should be and how fast it should play.
+    pub fn audio(&self) -> AudioCue {
+        let rate = (!self.paused && !self.is_finished() && AUDIO_SPEED_RANGE.contains(&self.speed)).then_some(self.speed);
+        AudioCue {position: self.time(), rate, jumps: self.jumps}
+    }
+
+    /// First and one-past-last frame the playhead moves between.
+    fn bounds(&self) -> (usize, usize) {
+        self.loop_range.as_ref().map_or((0, self.frames), |range| (range.start, range.end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_loops_steps_and_keeps_audio_in_step() {
+        let secs = Duration::from_secs_f64;
+        let mut clock = PlaybackClock::new(100, 10.0);
+        assert_eq!(clock.advance(secs(0.55)), 5);
+        clock.apply(PlaybackCommand::Faster);
+        assert_eq!((clock.speed(), clock.advance(secs(1.0))), (1.25, 18));
+        assert_eq!(clock.audio().rate, Some(1.25));
+
+        // A-B loop from frame 18 to 22 wraps the playhead and counts the jump for the audio
+        clock.apply(PlaybackCommand::CycleLoop);
+        clock.seek_to_frame(22);
+        clock.apply(PlaybackCommand::CycleLoop);
+        assert_eq!(clock.loop_range(), Some(18..23));
+        let jumps = clock.audio().jumps;
+        assert_eq!(clock.advance(secs(0.1)), 18);
+        assert_eq!(clock.audio().jumps, jumps + 1);
+        clock.seek_to_time(secs(9.0));
+        assert_eq!(clock.frame(), 22);
+
+        // Steps pause and wrap inside the loop
+        clock.apply(PlaybackCommand::from_key('.').unwrap());
+        assert_eq!((clock.frame(), clock.is_paused(), clock.audio().rate), (18, true, None));
+        clock.apply(PlaybackCommand::from_key('a').unwrap());
+        clock.apply(PlaybackCommand::from_key(' ').unwrap());
+        clock.apply(PlaybackCommand::from_key('l').unwrap());
+        assert_eq!((clock.frame(), clock.loop_range()), (68, None));
+
+        // Audio mutes beyond AUDIO_SPEED_RANGE; the clip stops at its end without repeat
+        clock.set_speed(4.0);
+        assert_eq!(clock.audio().rate, None);
+        assert_eq!(clock.advance(secs(1.0)), 99);
+        assert!(clock.is_finished());
+        assert_eq!(PlaybackClock::new(100, 10.0).with_repeat(true).advance(secs(10.5)), 5);
+    }
+
+    #[test]
+    fn test_lock_to_audio_drops_and_repeats_frames() {
+        let mut clock = PlaybackClock::new(100, 10.0);
+        clock.advance(Duration::from_secs(2));
+        // Within a frame of the audio nothing changes
+        assert_eq!(clock.lock_to_audio(Duration::from_millis(2050)), 0);
+        assert_eq!(clock.lock_to_audio(Duration::from_millis(2500)), 5);
+        assert_eq!(clock.lock_to_audio(Duration::from_millis(2200)), -3);
+        assert_eq!(clock.frame(), 22);
+        clock.set_paused(true);
+        assert_eq!(clock.lock_to_audio(Duration::from_secs(5)), 0);
+    }
+}
@@ -0,0 +1,309 @@
+//! Terminal playback of converted frame directories.
+//!
+//! [`play_frames`] draws the `.cframe`/`.txt` frames of a directory in place, in ANSI color, at the
+//! clip's frame rate, to preview a conversion without encoding a video. Frames are read from disk as
+//! the playhead reaches them and scaled down to the terminal as it is resized ([`TerminalFit`]).
+//! While playing, the keys of [`PlaybackCommand::from_key`] pause, step, seek and change the speed,
+//! the left and right arrows seek by [`SEEK_STEP_SECS`], and `q`, Esc or Ctrl-C stop. The
+//! directory's `audio.mp3` plays along and keeps the frames in sync: on the output device when
+//! built with the `audio` feature, through `ffplay` otherwise (or when no output device opens).
+//! Packed `.casc` archives play too, decrypted frame by frame with [`PlayOptions::key`] when encrypted.
+//! With the `osc` or `mqtt` feature, [`PlayOptions::show_control`] sends frame, beat and scene
+//! events to lighting and installation controllers as the frames play (see [`crate::show_control`]).
+//!
+//! ```no_run
+//! use cascii::player::{play_frames, PlayOptions};
+//!
+//! # fn main() -> cascii::Result<()> {
+//! play_frames("out/clip", None, &PlayOptions::default().with_repeat(true))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::Result;
+use std::borrow::Cow;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "encryption")]
+use crate::packed::ArchiveKey;
+use crate::playback::{AudioOutput, PlaybackClock, PlaybackCommand, SEEK_STEP_SECS};
+#[cfg(any(feature = "osc", feature = "mqtt"))]
+use crate::show_control::{ShowControl, ShowControlOptions, ShowEvent};
+use crate::terminal::{self, KeyInput, TerminalFit};
+use crate::{frame_to_ansi_string, AnsiColor, AsciiFrame, FrameSequence};
+
+/// Frame rate when neither the caller nor the directory's `details.toml` gives one
+pub const DEFAULT_PLAY_FPS: u32 = 30;
+
+/// Longest wait between two looks at the keyboard, so keys take effect at low frame rates too
+const KEY_POLL: Duration = Duration::from_millis(20);
+
+/// How [`play_frames`] plays a directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayOptions {
+    /// Speed multiplier to start at, clamped to [`SPEED_RANGE`](crate::playback::SPEED_RANGE)
+    pub speed: f64,
+    /// Start over from the first frame at the end instead of stopping
+    pub repeat: bool,
+    /// Escapes for the cell colors, or `None` to draw the characters only
+    pub colors: Option<AnsiColor>,
+    /// Scale frames down to the terminal, following it as it is resized
+    pub fit: bool,
+    /// React to playback keys typed into the terminal
+    pub keyboard: bool,
+    /// Play the directory's `audio.mp3` along; without an audio device or `ffplay` the frames play silently
+    pub audio: bool,
+    /// Show the time, speed and frame number below the frame
+    pub status_line: bool,
+    /// Key of an encrypted `.casc` archive
+    #[cfg(feature = "encryption")]
+    pub key: Option<ArchiveKey>,
+    /// Controller to send show events to while playing
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    pub show_control: Option<ShowControlOptions>,
+}
+
+impl Default for PlayOptions {
+    fn default() -> Self {
+        Self {speed: 1.0, repeat: false, colors: Some(AnsiColor::TrueColor), fit: true, keyboard: true, audio: true, status_line: true, #[cfg(feature = "encryption")] key: None, #[cfg(any(feature = "osc", feature = "mqtt"))] show_control: None}
+    }
+}
+
+impl PlayOptions {
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn with_colors(mut self, colors: Option<AnsiColor>) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    pub fn with_fit(mut self, fit: bool) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    pub fn with_keyboard(mut self, keyboard: bool) -> Self {
+        self.keyboard = keyboard;
+        self
+    }
+
+    pub fn with_audio(mut self, audio: bool) -> Self {
+        self.audio = audio;
+        self
+    }
+
+    pub fn with_status_line(mut self, status_line: bool) -> Self {
+        self.status_line = status_line;
+        self
+    }
+
+    #[cfg(feature = "encryption")]
+    pub fn with_key(mut self, key: Option<ArchiveKey>) -> Self {
+        self.key = key;
+        self
+    }
+
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    pub fn with_show_control(mut self, show_control: Option<ShowControlOptions>) -> Self {
+        self.show_control = show_control;
+        self
+    }
+}
+
+/// Play the frames of `dir`, a frame directory or a `.casc` archive, in the terminal at `fps` (`None`
+/// takes the rate recorded in its `details.toml`, or [`DEFAULT_PLAY_FPS`]) until the clip ends or a
+/// quit key is pressed. Frames are drawn in place when the terminal takes escapes, and printed one
+/// after another otherwise. Returns the number of frames drawn.
+pub fn play_frames(dir: impl AsRef<Path>, fps: Option<u32>, options: &PlayOptions) -> Result<usize> {
+    let dir = dir.as_ref();
+    #[cfg(feature = "encryption")]
+    let sequence = match &options.key {
+        Some(key) => FrameSequence::open_with_key(dir, key)?,
+        None => FrameSequence::open(dir)?,
+    };
+    #[cfg(not(feature = "encryption"))]
+    let sequence = FrameSequence::open(dir)?;
+    let fps = fps.filter(|&fps| fps > 0).or_else(|| crate::loop_detect::recorded_fps(dir)).unwrap_or(DEFAULT_PLAY_FPS);
+    let ansi = terminal::enable_ansi();
+    let mut keys = if options.keyboard {KeyInput::open()} else {None};
+    let mut audio = if options.audio {open_audio(dir)} else {None};
+    let follow_audio = |clock: &mut PlaybackClock| {
+        if let Some(output) = audio.as_mut() {
+            clock.follow_audio(output.as_mut());
+        }
+    };
+
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    let show = options.show_control.as_ref().map(ShowControl::connect).transpose()?;
+    // Events that fail to send are dropped, so a controller going away does not stop playback
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    let on_frame = |index: usize, clock: &PlaybackClock, frame: &AsciiFrame| {
+        if let Some(show) = &show {
+            let _ = show.frame(index, clock.frames(), clock.time(), frame);
+        }
+    };
+    #[cfg(not(any(feature = "osc", feature = "mqtt")))]
+    let on_frame = |_: usize, _: &PlaybackClock, _: &AsciiFrame| {};
+
+    let mut out = std::io::stdout().lock();
+    if ansi {
+        write!(out, "\x1b[?25l\x1b[2J")?;
+    }
+    let result = play_sequence(&sequence, fps, options, ansi, &mut out, || keys.as_mut().map_or_else(Vec::new, KeyInput::read), follow_audio, on_frame);
+    #[cfg(any(feature = "osc", feature = "mqtt"))]
+    if let (Some(show), Ok(frames)) = (&show, &result) {
+        let _ = show.emit(&ShowEvent::Finished {frames: *frames});
+    }
+    if ansi {
+        writeln!(out, "\x1b[0m\x1b[?25h")?;
+    }
+    out.flush()?;
+    result
+}
+
+/// The `audio.mp3` of `dir` on the output device with the `audio` feature, else through ffplay
+fn open_audio(dir: &Path) -> Option<Box<dyn AudioOutput>> {
+    #[cfg(feature = "audio")]
+    if let Ok(Some(player)) = crate::audio_playback::open_clip_audio(dir) {
+        return Some(Box::new(player));
+    }
+    crate::ffplay::open_clip_audio(dir).ok().flatten().map(|player| Box::new(player) as Box<dyn AudioOutput>)
+}
+
+/// What a key does during playback
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeyAction {
+    Command(PlaybackCommand),
+    Quit,
+}
+
+/// The actions of the keys in `bytes`, as typed into the terminal
+fn key_actions(bytes: &[u8]) -> Vec<KeyAction> {
+    let mut actions = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            0x1b if bytes.get(index + 1) == Some(&b'[') => {
+                match bytes.get(index + 2) {
+                    Some(b'C') => actions.push(KeyAction::Command(PlaybackCommand::SeekBy(SEEK_STEP_SECS))),
+                    Some(b'D') => actions.push(KeyAction::Command(PlaybackCommand::SeekBy(-SEEK_STEP_SECS))),
+                    _ => {}
+                }
+                index += 3;
+                continue;
+            }
+            b'q' | 0x1b | 0x03 => actions.push(KeyAction::Quit),
+            key => actions.extend(PlaybackCommand::from_key(key as char).map(KeyAction::Command)),
+        }
+        index += 1;
+    }
+    actions
+}
+
+/// The playback loop of [`play_frames`], drawing into `out` and taking keys from `keys`;
+/// `follow_audio` gets the clock after every advance, and `on_frame` every frame drawn with its index.
+#[allow(clippy::too_many_arguments)]
+fn play_sequence<W: Write>(sequence: &FrameSequence, fps: u32, options: &PlayOptions, ansi: bool, out: &mut W, mut keys: impl FnMut() -> Vec<u8>, mut follow_audio: impl FnMut(&mut PlaybackClock), mut on_frame: impl FnMut(usize, &PlaybackClock, &AsciiFrame)) -> Result<usize> {
+    let mut clock = PlaybackClock::new(sequence.len(), fps as f64).with_speed(options.speed).with_repeat(options.repeat);
+    let mut fit = TerminalFit::new();
+    let (mut drawn, mut shown, mut status_row) = (0, None, 1);
+    let mut status = String::new();
+    let mut last = Instant::now();
+    loop {
+        for action in key_actions(&keys()) {
+            match action {
+                KeyAction::Quit => return Ok(drawn),
+                KeyAction::Command(command) => clock.apply(command),
+            }
+        }
+        let now = Instant::now();
+        clock.advance(now - last);
+        last = now;
+        follow_audio(&mut clock);
+        if clock.is_finished() {
+            return Ok(drawn);
+        }
+
+        let resized = options.fit && fit.poll();
+        let index = clock.frame();
+        if shown != Some(index) || resized {
+            let frame = sequence.read(index)?;
+            if shown != Some(index) {
+                on_frame(index, &clock, &frame);
+            }
+            let frame = if options.fit {fit.fit(&frame)} else {Cow::Borrowed(&frame)};
+            if ansi {
+                let text = match options.colors {
+                    Some(colors) => Cow::Owned(frame_to_ansi_string(&frame, colors)),
+                    None => Cow::Borrowed(frame.text.as_str()),
+                };
+                write!(out, "{}\x1b[H{}", if resized {"\x1b[2J"} else {""}, text)?;
+            } else {
+                write!(out, "{}", frame.text)?;
+            }
+            (shown, status_row, status) = (Some(index), frame.height + 1, String::new());
+            drawn += 1;
+        }
+        if ansi && options.status_line {
+            let line = status_line(&clock);
+            if line != status {
+                write!(out, "\x1b[{};1H\x1b[0m\x1b[2K{}", status_row, line)?;
+                status = line;
+            }
+        }
+        out.flush()?;
+
+        // Sleep until the next frame is due, looking at the keys in between
+        let rate = fps as f64 * clock.speed();
+        let position = clock.time().as_secs_f64() * fps as f64;
+        let until_next = if clock.is_paused() {KEY_POLL} else {Duration::from_secs_f64((position.floor() + 1.0 - position) / rate)};
+        std::thread::sleep(until_next.min(KEY_POLL).saturating_sub(now.elapsed()));
+    }
+}
+
+/// `playing 00:12.4 / 01:00.0  1.5x  frame 310/1500`, with the A-B loop when one is set
+fn status_line(clock: &PlaybackClock) -> String {
+    let timestamp = |secs: f64| format!("{:02}:{:04.1}", (secs / 60.0) as u64, secs % 60.0);
+    let total = clock.frames() as f64 / clock.fps();
+    let mut line = format!("{} {} / {}  {}x  frame {}/{}", if clock.is_paused() {"paused "} else {"playing"}, timestamp(clock.time().as_secs_f64()), timestamp(total), clock.speed(), clock.frame() + 1, clock.frames());
+    if let Some(range) = clock.loop_range() {
+        line.push_str(&format!("  loop {}-{}", range.start + 1, range.end));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_play_sequence_draws_frames_and_follows_keys() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for (number, text) in ["a\n", "b\n", "c\n"].iter().enumerate() {
+            fs::write(dir.path().join(format!("frame_{:04}.txt", number + 1)), text)?;
+        }
+        let sequence = FrameSequence::open(dir.path())?;
+        let options = PlayOptions::default().with_fit(false).with_colors(None);
+
+        // At 1 fps the first frame is still up when '.' steps to the second, then 'q' quits
+        let mut typed = vec![b"q".to_vec(), b".".to_vec(), Vec::new()];
+        let mut out = Vec::new();
+        let drawn = play_sequence(&sequence, 1, &options, false, &mut out, || typed.pop().unwrap_or_default(), |_| {}, |_, _, _| {})?;
+        assert_eq!((drawn, String::from_utf8(out).unwrap().as_str()), (2, "a\nb\n"));
+
+        assert_eq!(key_actions(b"\x1b[C \x1b"), [KeyAction::Command(PlaybackCommand::SeekBy(SEEK_STEP_SECS)), KeyAction::Command(PlaybackCommand::TogglePause), KeyAction::Quit]);
+        assert_eq!(key_actions(b"x\x03"), [KeyAction::Quit]);
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+//! The stable public API in one import.
+//!
+//! Everything re-exported here follows semver: breaking changes to these types only land in a new
+//! minor version while the crate is below 1.0. Items reached through other modules (and helpers
+//! such as the cframe editing functions) may still change between patch releases as new
+//! subsystems land. The enums here are `#[non_exhaustive]`, so new modes and phases can be added
+//! in a minor release; matches on them need a catch-all arm.
+//!
+//! ```
+//! use cascii::prelude::*;
+//!
+//! fn convert(img: &image::DynamicImage) -> Result<AsciiFrame> {
+//!     image_to_frame(img, &ConversionOptions::default().with_columns(80))
+//! }
+//! # let frame = convert(&image::DynamicImage::new_rgb8(160, 80)).unwrap();
+//! # assert_eq!(frame.width, 80);
+//! ```
+
+pub use crate::frame::{frame_to_ansi_string, image_bytes_to_frame, image_to_frame, AnsiColor, AsciiCell, AsciiFrame, CharMode, DitherMode, ImageFrame};
+pub use crate::{is_cancelled_error, BgFitQuality, Error, Result, CancelToken, CellColorMode, ConversionOptions, ConversionResult, ConverterLimits, GifOptions, GlyphMatch, OutputMode, OutputSink, OverallProgress, Palette, PhaseWeights, Progress, ProgressPhase, ToVideoOptions, VideoOptions};
+
+#[cfg(feature = "cli")]
+pub use crate::{render_frame_to_image, AsciiConverter, AsciiConverterBuilder, FrameReader, FrameSequence};
@@ -1,12 +1,13 @@
-use anyhow::{anyhow, Context, Result};
+use crate::error::{Context, Error, Result};
 use rayon::prelude::*;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command as ProcCommand;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::frame::Sampling;
 use crate::video::parse_timestamp;
-use crate::FfmpegConfig;
+use crate::{FfmpegConfig, FpsMode, ToneMap, UpscalePolicy, VideoOptions};
 
 #[derive(Debug, Clone, Copy)]
 pub struct PreprocessPreset {
@@ -32,7 +33,7 @@ pub fn resolve_preprocess_filter(preprocess: Option<&str>, preprocess_preset: Op
     if let Some(filter) = preprocess {
         let filter = filter.trim();
         if filter.is_empty() {
-            return Err(anyhow!("--preprocess cannot be empty"));
+            return Err(Error::InvalidOptions("--preprocess cannot be empty".into()));
         }
         return Ok(Some(filter.to_string()));
     }
@@ -40,7 +41,7 @@ pub fn resolve_preprocess_filter(preprocess: Option<&str>, preprocess_preset: Op
     if let Some(name) = preprocess_preset {
         let preset = find_preprocess_preset(name.trim()).ok_or_else(|| {
             let available = PREPROCESS_PRESETS.iter().map(|p| p.name).collect::<Vec<_>>().join(", ");
-            anyhow!("Unknown preprocessing preset '{}'. Available presets: {}", name, available)
+            Error::InvalidOptions(format!("Unknown preprocessing preset '{}'. Available presets: {}", name, available))
         })?;
         return Ok(Some(preset.filter.to_string()));
     }
@@ -48,13 +49,31 @@ pub fn resolve_preprocess_filter(preprocess: Option<&str>, preprocess_preset: Op
     Ok(None)
 }
 
-pub(crate) fn build_frame_extraction_vf(columns: u32, fps: u32, preprocess_filter: Option<&str>) -> String {
-    let base = format!("scale={}:-2,fps={}", columns, fps);
-    let preprocess = preprocess_filter.and_then(normalize_filter);
-    match preprocess {
-        Some(filter) => format!("{},{}", filter, base),
-        None => base,
+/// Deinterlaces first, then tone-maps with `tone_map` (for HDR sources), then applies the preprocessing
+/// filter, then scales and resamples the frame rate.
+/// The scale width follows the sampling's upscale policy against the input width (`iw`), so frames
+/// come out at the same column count a still image would get.
+pub(crate) fn build_frame_extraction_vf(video_opts: &VideoOptions, sampling: Sampling, tone_map: Option<ToneMap>) -> String {
+    // Side-by-side stereo is extracted at a width per view, both views then combining into one anaglyph
+    let columns = video_opts.columns * sampling.views();
+    let width = match sampling.upscale_policy() {
+        UpscalePolicy::Allow | UpscalePolicy::Warn => columns.to_string(),
+        UpscalePolicy::Cap => format!("'min({},iw)'", columns),
+        UpscalePolicy::Integer => format!("'if(gt({0},iw),iw*floor({0}/iw),{0})'", columns),
+    };
+    let flags = if sampling.nearest() {":flags=neighbor"} else {""};
+    let rate = match video_opts.fps_mode {
+        FpsMode::Drop => format!("fps={}", video_opts.fps),
+        FpsMode::Blend => format!("framerate=fps={}", video_opts.fps),
+    };
+    let mut filters = Vec::new();
+    if video_opts.deinterlace {
+        filters.push("yadif".to_string());
     }
+    filters.extend(tone_map.map(|tone_map| tone_map.ffmpeg_filter()));
+    filters.extend(video_opts.preprocess_filter.as_deref().and_then(normalize_filter).map(str::to_string));
+    filters.push(format!("scale={}:-2{},{}", width, flags, rate));
+    filters.join(",")
 }
 
 fn normalize_filter(filter: &str) -> Option<&str> {
@@ -73,7 +92,7 @@ pub fn detect_preprocess_input_kind(input: &Path) -> Result<PreprocessInputKind>
     }
 
     if !input.is_file() {
-        return Err(anyhow!("Input path does not exist: {}", input.display()));
+        return Err(Error::NotFound(format!("Input path does not exist: {}", input.display())));
     }
 
     let ext = input.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase()).unwrap_or_default();
@@ -95,7 +114,7 @@ pub fn resolve_preprocess_output_path(input: &Path, output_target: &Path, kind:
         return Ok(output_target.to_path_buf());
     }
 
-    let stem = input.file_stem().and_then(|s| s.to_str()).filter(|s| !s.is_empty()).ok_or_else(|| anyhow!("Could not derive an output filename from {}", input.display()))?;
+    let stem = input.file_stem().and_then(|s| s.to_str()).filter(|s| !s.is_empty()).ok_or_else(|| Error::Other(format!("Could not derive an output filename from {}", input.display())))?;
 
     let ext = match kind {
         PreprocessInputKind::Image => "png",
@@ -116,7 +135,7 @@ fn ensure_output_parent(output: &Path) -> Result<()> {
 }
 
 fn build_standalone_filter_complex(filter: &str, final_format: &str) -> Result<String> {
-    let filter = normalize_filter(filter).ok_or_else(|| anyhow!("preprocess filter cannot be empty"))?;
+    let filter = normalize_filter(filter).ok_or_else(|| Error::InvalidOptions("preprocess filter cannot be empty".into()))?;
     Ok(format!("[0:v]{filter},format=rgba[fg];color=c=black:s=16x16,format=rgba[bg0];[bg0][fg]scale2ref[bg][fg1];[bg][fg1]overlay=shortest=1:format=auto,format={final_format}[v]"))
 }
 
@@ -148,10 +167,10 @@ pub fn preprocess_image_to_file(input: &Path, filter: &str, output: &Path, ffmpe
     ensure_output_parent(output)?;
     let filter_complex = build_standalone_filter_complex(filter, "rgb24")?;
 
-    let status = ProcCommand::new(ffmpeg_config.ffmpeg_cmd()).arg("-loglevel").arg("error").arg("-y").arg("-i").arg(input).arg("-filter_complex").arg(&filter_complex).arg("-map").arg("[v]").arg("-frames:v").arg("1").arg(output).status().with_context(|| format!("running ffmpeg preprocessing on {}", input.display()))?;
+    let status = ProcCommand::new(ffmpeg_config.ffmpeg_cmd()).arg("-loglevel").arg("error").arg("-y").arg("-i").arg(input).arg("-filter_complex").arg(&filter_complex).arg("-map").arg("[v]").arg("-frames:v").arg("1").arg(output).status().map_err(|err| Error::spawn(ffmpeg_config.ffmpeg_cmd(), err))?;
 
     if !status.success() {
-        return Err(anyhow!("ffmpeg preprocessing failed for {}", input.display()));
+        return Err(Error::ffmpeg(format!("ffmpeg preprocessing failed for {}", input.display()), &[]));
     }
 
     Ok(())
@@ -180,14 +199,14 @@ pub fn preprocess_video_to_file(input: &Path, filter: &str, output: &Path, start
             command.arg("-c:v").arg("libvpx-vp9").arg("-crf").arg("30").arg("-b:v").arg("0").arg("-pix_fmt").arg("yuv420p").arg("-c:a").arg("libopus");
         }
         _ => {
-            return Err(anyhow!("Unsupported preprocess video output format '{}'. Use .mp4, .mov, .m4v, .mkv, or .webm.", output.display()));
+            return Err(Error::InvalidOptions(format!("Unsupported preprocess video output format '{}'. Use .mp4, .mov, .m4v, .mkv, or .webm.", output.display())));
         }
     }
 
-    let status = command.arg(output).status().with_context(|| format!("running ffmpeg preprocessing on {}", input.display()))?;
+    let status = command.arg(output).status().map_err(|err| Error::spawn(ffmpeg_config.ffmpeg_cmd(), err))?;
 
     if !status.success() {
-        return Err(anyhow!("ffmpeg preprocessing failed for {}", input.display()));
+        return Err(Error::ffmpeg(format!("ffmpeg preprocessing failed for {}", input.display()), &[]));
     }
 
     Ok(())
@@ -220,7 +239,7 @@ impl Drop for TempFileGuard {
 /// original file stem.
 pub fn preprocess_directory(source_dir: &Path, filter: &str, output_dir: &Path, ffmpeg_config: &FfmpegConfig) -> Result<usize> {
     if !source_dir.exists() {
-        return Err(anyhow!("Source directory does not exist: {}", source_dir.display()));
+        return Err(Error::NotFound(format!("Source directory does not exist: {}", source_dir.display())));
     }
 
     fs::create_dir_all(output_dir).with_context(|| format!("creating output directory {}", output_dir.display()))?;
@@ -239,7 +258,7 @@ pub fn preprocess_directory(source_dir: &Path, filter: &str, output_dir: &Path,
     images.sort();
 
     if images.is_empty() {
-        return Err(anyhow!("No image files found in {}", source_dir.display()));
+        return Err(Error::NotFound(format!("No image files found in {}", source_dir.display())));
     }
 
     images.par_iter().try_for_each(|img_path| {
@@ -255,10 +274,10 @@ pub fn preprocess_image_to_temp(input: &Path, filter: &str, ffmpeg_config: &Ffmp
     let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
     let out_path = std::env::temp_dir().join(format!("cascii_preprocessed_{}_{}.png", std::process::id(), stamp));
 
-    let status = ProcCommand::new(ffmpeg_config.ffmpeg_cmd()).arg("-loglevel").arg("error").arg("-y").arg("-i").arg(input).arg("-vf").arg(filter).arg("-frames:v").arg("1").arg(&out_path).status().context("running ffmpeg preprocessing for image input")?;
+    let status = ProcCommand::new(ffmpeg_config.ffmpeg_cmd()).arg("-loglevel").arg("error").arg("-y").arg("-i").arg(input).arg("-vf").arg(filter).arg("-frames:v").arg("1").arg(&out_path).status().map_err(|err| Error::spawn(ffmpeg_config.ffmpeg_cmd(), err))?;
 
     if !status.success() {
-        return Err(anyhow!("ffmpeg image preprocessing failed"));
+        return Err(Error::ffmpeg("ffmpeg image preprocessing failed", &[]));
     }
 
     Ok(TempFileGuard::new(out_path))
@@ -267,6 +286,7 @@ pub fn preprocess_image_to_temp(input: &Path, filter: &str, ffmpeg_config: &Ffmp
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ToneMapCurve;
     use std::path::{Path, PathBuf};
     use std::process::{Command, Stdio};
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -291,6 +311,21 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn frame_extraction_vf_follows_sampling() {
+        let video_opts = VideoOptions {fps: 24, columns: 80, ..VideoOptions::default()};
+        assert_eq!(build_frame_extraction_vf(&VideoOptions {preprocess_filter: Some("hflip,".to_string()), ..video_opts.clone()}, Sampling::default(), None), "hflip,scale=80:-2,fps=24");
+        assert_eq!(build_frame_extraction_vf(&video_opts, Sampling {pixel_art: true, ..Sampling::default()}, None), "scale='min(80,iw)':-2:flags=neighbor,fps=24");
+        assert_eq!(build_frame_extraction_vf(&video_opts, Sampling {upscale: UpscalePolicy::Integer, ..Sampling::default()}, None), "scale='if(gt(80,iw),iw*floor(80/iw),80)':-2:flags=neighbor,fps=24");
+        assert_eq!(build_frame_extraction_vf(&video_opts, Sampling {anaglyph: Some(crate::Anaglyph::Color), ..Sampling::default()}, None), "scale=160:-2,fps=24");
+        let interlaced = VideoOptions {deinterlace: true, fps_mode: FpsMode::Blend, preprocess_filter: Some("hflip".to_string()), ..video_opts};
+        assert_eq!(build_frame_extraction_vf(&interlaced, Sampling::default(), None), "yadif,hflip,scale=80:-2,framerate=fps=24");
+        let tone_map = ToneMap {curve: ToneMapCurve::Mobius, exposure: 1.0};
+        let hdr = build_frame_extraction_vf(&interlaced, Sampling::default(), Some(tone_map));
+        assert!(hdr.starts_with("yadif,zscale=t=linear:npl=50,"), "{}", hdr);
+        assert!(hdr.contains("tonemap=tonemap=mobius:desat=0") && hdr.ends_with("format=yuv420p,hflip,scale=80:-2,framerate=fps=24"), "{}", hdr);
+    }
+
     #[test]
     fn standalone_filter_complex_wraps_filter_on_black_background() -> Result<()> {
         let filter_complex = build_standalone_filter_complex("colorkey=0xFFFFFF:0.1:0.02", "rgb24")?;
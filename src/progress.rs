@@ -0,0 +1,417 @@
+//! Progress events of conversions, and the overall percentage they add up to.
+
+use serde::{Deserialize, Serialize};
+
+/// Represents the current phase of a conversion operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum ProgressPhase {
+    /// Extracting frames from video using ffmpeg
+    ExtractingFrames,
+    /// Extracting audio from video
+    ExtractingAudio,
+    /// Converting extracted frames to ASCII art
+    ConvertingFrames,
+    /// Rendering ASCII frames to video and encoding with ffmpeg
+    RenderingVideo,
+    /// The preview of a two-stage conversion is ready to play
+    PreviewReady,
+    /// The full-quality pass of a two-stage conversion replaced the preview
+    FullQualityReady,
+    /// Conversion completed successfully
+    Complete,
+}
+
+/// Which pass of a two-stage conversion (see [`refine`](crate::refine)) an event belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConversionPass {
+    /// The fast low-resolution preview
+    Preview,
+    /// The full-quality conversion that replaces the preview
+    FullQuality,
+}
+
+/// Progress information for conversion operations
+///
+/// This struct provides detailed progress information that can be used
+/// to display progress in UI applications.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Progress {
+    /// Current phase of the conversion
+    pub phase: ProgressPhase,
+    /// Number of items completed in the current phase
+    pub completed: usize,
+    /// Total number of items in the current phase (0 if unknown/indeterminate)
+    pub total: usize,
+    /// Percentage complete (0.0 to 100.0)
+    pub percentage: f64,
+    /// Human-readable message describing current status
+    pub message: String,
+    /// The pass of a two-stage conversion the event comes from; `None` for single-pass conversions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pass: Option<ConversionPass>,
+}
+
+impl Progress {
+    /// Create a new progress update for extracting frames
+    pub fn extracting_frames() -> Self {
+        Self {phase: ProgressPhase::ExtractingFrames, completed: 0, total: 0, percentage: 0.0, message: "Extracting frames from video...".to_string(), pass: None}
+    }
+
+    /// Create a progress update for extracting frames with percentage
+    pub fn extracting_frames_progress(current_time_us: u64, total_duration_us: u64) -> Self {
+        let completed = current_time_us.min(total_duration_us) as usize;
+        let percentage = percentage_of(completed, total_duration_us as usize);
+        Self {phase: ProgressPhase::ExtractingFrames, completed, total: total_duration_us as usize, percentage, message: format!("Extracting frames: {:.1}%", percentage), pass: None}
+    }
+
+    /// Create a new progress update for extracting audio
+    pub fn extracting_audio() -> Self {
+        Self {phase: ProgressPhase::ExtractingAudio, completed: 0, total: 0, percentage: 0.0, message: "Extracting audio from video...".to_string(), pass: None}
+    }
+
+    /// Create the final update of frame extraction
+    pub fn extracting_frames_done() -> Self {
+        Self {phase: ProgressPhase::ExtractingFrames, completed: 0, total: 0, percentage: 100.0, message: "Extracted frames from video".to_string(), pass: None}
+    }
+
+    /// Create the final update of audio extraction
+    pub fn extracting_audio_done() -> Self {
+        Self {phase: ProgressPhase::ExtractingAudio, completed: 0, total: 0, percentage: 100.0, message: "Extracted audio from video".to_string(), pass: None}
+    }
+
+    /// Create a new progress update for frame conversion (`completed` is capped at `total`)
+    pub fn converting_frames(completed: usize, total: usize) -> Self {
+        let completed = completed.min(total);
+        Self {phase: ProgressPhase::ConvertingFrames, completed, total, percentage: percentage_of(completed, total), message: format!("Converting frame {} of {}", completed, total), pass: None}
+    }
+
+    /// Create a progress update for rendering video frames (`completed` is capped at `total`)
+    pub fn rendering_video(completed: usize, total: usize) -> Self {
+        let completed = completed.min(total);
+        Self {phase: ProgressPhase::RenderingVideo, completed, total, percentage: percentage_of(completed, total), message: format!("Rendering frame {} of {}", completed, total), pass: None}
+    }
+
+    /// Create a completion progress update
+    pub fn complete(total_frames: usize) -> Self {
+        Self {phase: ProgressPhase::Complete, completed: total_frames, total: total_frames, percentage: 100.0, message: format!("Conversion complete: {} frames", total_frames), pass: None}
+    }
+
+    /// Create a progress update announcing the preview of a two-stage conversion in `dir`
+    pub fn preview_ready(frames: usize, dir: &std::path::Path) -> Self {
+        Self {phase: ProgressPhase::PreviewReady, completed: frames, total: frames, percentage: 100.0, message: format!("Preview ready: {} frames in {}", frames, dir.display()), pass: Some(ConversionPass::Preview)}
+    }
+
+    /// Create a progress update announcing the full-quality conversion in `dir` replaced the preview
+    pub fn full_quality_ready(frames: usize, dir: &std::path::Path) -> Self {
+        Self {phase: ProgressPhase::FullQualityReady, completed: frames, total: frames, percentage: 100.0, message: format!("Full quality ready: {} frames in {}", frames, dir.display()), pass: Some(ConversionPass::FullQuality)}
+    }
+
+    /// Tag the update with the pass of a two-stage conversion it comes from
+    pub fn with_pass(mut self, pass: ConversionPass) -> Self {
+        self.pass = Some(pass);
+        self
+    }
+}
+
+fn percentage_of(completed: usize, total: usize) -> f64 {
+    if total > 0 {(completed.min(total) as f64 / total as f64) * 100.0} else {0.0}
+}
+
+/// Counts the completed items of one phase and decides which counts are reported.
+///
+/// Parallel workers finish out of order, so a count taken from an atomic increment can reach the
+/// callback after a larger one. The counter instead increments and reports under one lock: reported
+/// counts only ever grow, never exceed the total, and [`finish`](Self::finish) reports the total
+/// exactly once if the last completion didn't.
+#[cfg(feature = "cli")]
+pub(crate) struct ProgressCounter {
+    total: usize,
+    throttled: bool,
+    // (completed, last reported count)
+    state: std::sync::Mutex<(usize, Option<usize>)>,
+}
+
+#[cfg(feature = "cli")]
+impl ProgressCounter {
+    /// A counter reporting every completion
+    pub(crate) fn new(total: usize) -> Self {
+        Self {total, throttled: false, state: std::sync::Mutex::new((0, None))}
+    }
+
+    /// A counter reporting only completions that move the whole percentage (about 100 reports)
+    pub(crate) fn throttled(total: usize) -> Self {
+        Self {throttled: true, ..Self::new(total)}
+    }
+
+    /// Report the starting count of 0.
+    pub(crate) fn start(&self, report: impl FnOnce(usize)) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.1.is_none() {
+            state.1 = Some(state.0);
+            report(state.0);
+        }
+    }
+
+    /// Count one completed item, passing the new count to `report` when it is due.
+    pub(crate) fn complete_one(&self, report: impl FnOnce(usize)) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let completed = (state.0 + 1).min(self.total);
+        state.0 = completed;
+        let percent = |count: usize| count * 100 / self.total.max(1);
+        let due = !self.throttled || completed == self.total || state.1.is_none_or(|last| percent(completed) > percent(last));
+        if due && state.1.is_none_or(|last| completed > last) {
+            state.1 = Some(completed);
+            report(completed);
+        }
+    }
+
+    /// Report the total, unless it already was; call once the phase is done.
+    pub(crate) fn finish(&self, report: impl FnOnce(usize)) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.1 != Some(self.total) {
+            (state.0, state.1) = (self.total, Some(self.total));
+            report(self.total);
+        }
+    }
+}
+
+/// Relative share of the overall progress bar given to each conversion phase.
+///
+/// Weights are relative to each other and do not need to sum to 1.0. A phase with a weight of 0.0
+/// is skipped, which is how pipelines without a given phase (e.g. no rendering step) are modeled.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhaseWeights {
+    /// Weight of [`ProgressPhase::ExtractingFrames`]
+    pub extraction: f64,
+    /// Weight of [`ProgressPhase::ExtractingAudio`]
+    pub audio: f64,
+    /// Weight of [`ProgressPhase::ConvertingFrames`]
+    pub conversion: f64,
+    /// Weight of [`ProgressPhase::RenderingVideo`]
+    pub rendering: f64,
+}
+
+impl Default for PhaseWeights {
+    fn default() -> Self {
+        Self::for_frames()
+    }
+}
+
+impl PhaseWeights {
+    /// Weights for video → frame directory conversions, which never enter the rendering phase.
+    pub fn for_frames() -> Self {
+        Self {extraction: 0.35, audio: 0.05, conversion: 0.6, rendering: 0.0}
+    }
+
+    /// Weights for video → video conversions, where frames are converted inside the rendering phase.
+    pub fn for_video() -> Self {
+        Self {extraction: 0.3, audio: 0.05, conversion: 0.0, rendering: 0.65}
+    }
+
+    /// Set the extraction weight
+    pub fn with_extraction(mut self, weight: f64) -> Self {
+        self.extraction = weight;
+        self
+    }
+
+    /// Set the audio extraction weight
+    pub fn with_audio(mut self, weight: f64) -> Self {
+        self.audio = weight;
+        self
+    }
+
+    /// Set the frame conversion weight
+    pub fn with_conversion(mut self, weight: f64) -> Self {
+        self.conversion = weight;
+        self
+    }
+
+    /// Set the rendering weight
+    pub fn with_rendering(mut self, weight: f64) -> Self {
+        self.rendering = weight;
+        self
+    }
+
+    /// Weight of a single phase (negative weights count as 0.0). [`ProgressPhase::Complete`] and the
+    /// two-stage announcements have no weight of their own.
+    pub fn weight_for(&self, phase: &ProgressPhase) -> f64 {
+        let weight = match phase {
+            ProgressPhase::ExtractingFrames => self.extraction,
+            ProgressPhase::ExtractingAudio => self.audio,
+            ProgressPhase::ConvertingFrames => self.conversion,
+            ProgressPhase::RenderingVideo => self.rendering,
+            ProgressPhase::PreviewReady | ProgressPhase::FullQualityReady | ProgressPhase::Complete => 0.0,
+        };
+        weight.max(0.0)
+    }
+
+    fn total(&self) -> f64 {
+        PHASE_ORDER.iter().map(|phase| self.weight_for(phase)).sum()
+    }
+}
+
+const PHASE_ORDER: [ProgressPhase; 4] = [ProgressPhase::ExtractingFrames, ProgressPhase::ExtractingAudio, ProgressPhase::ConvertingFrames, ProgressPhase::RenderingVideo];
+
+/// Overall percentage at which the preview pass of a two-stage conversion ends
+const PREVIEW_PASS_END: f64 = 25.0;
+/// Overall percentage at which the full-quality pass ends; switching to it and completing take the rest
+const FULL_QUALITY_PASS_END: f64 = 99.0;
+
+/// Folds the per-phase [`Progress`] events of a conversion into a single 0–100% figure.
+///
+/// Each phase owns a slice of the bar proportional to its [`PhaseWeights`] entry, so the overall percentage
+/// keeps climbing across phase changes instead of resetting to 0% at the start of every phase. The reported
+/// value never goes backwards, and [`ProgressPhase::Complete`] always maps to 100%. Events tagged with a
+/// [`ConversionPass`] fill their pass's part of the bar: the preview the first 25%, the full-quality pass
+/// up to 99%.
+///
+/// ```
+/// use cascii::{OverallProgress, PhaseWeights, Progress};
+///
+/// let overall = OverallProgress::new(PhaseWeights::for_frames());
+/// overall.update(&Progress::converting_frames(50, 100));
+/// assert!((overall.percentage() - 70.0).abs() < 1e-9);
+/// ```
+#[derive(Debug, Default)]
+pub struct OverallProgress {
+    weights: PhaseWeights,
+    last: std::sync::Mutex<f64>,
+}
+
+impl OverallProgress {
+    /// Create a tracker using the given phase weights
+    pub fn new(weights: PhaseWeights) -> Self {
+        Self {weights, last: std::sync::Mutex::new(0.0)}
+    }
+
+    /// The phase weights used by this tracker
+    pub fn weights(&self) -> &PhaseWeights {
+        &self.weights
+    }
+
+    /// Feed a per-phase progress event and return the updated overall percentage (0.0 to 100.0).
+    pub fn update(&self, progress: &Progress) -> f64 {
+        let computed = self.overall_for(progress);
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        if computed > *last {
+            *last = computed;
+        }
+        *last
+    }
+
+    /// The most recent overall percentage
+    pub fn percentage(&self) -> f64 {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wrap a callback so it receives every [`Progress`] event together with the overall percentage.
+    ///
+    /// The returned closure can be passed directly to any of the `*_with_detailed_progress` methods
+    /// or to [`AsciiConverter::convert_video_to_video`](crate::AsciiConverter::convert_video_to_video).
+    pub fn wrap<F: Fn(Progress, f64) + Send + Sync>(weights: PhaseWeights, callback: F) -> impl Fn(Progress) + Send + Sync {
+        let tracker = Self::new(weights);
+        move |progress: Progress| {
+            let overall = tracker.update(&progress);
+            callback(progress, overall);
+        }
+    }
+
+    fn overall_for(&self, progress: &Progress) -> f64 {
+        match progress.phase {
+            ProgressPhase::Complete => return 100.0,
+            ProgressPhase::PreviewReady => return PREVIEW_PASS_END,
+            ProgressPhase::FullQualityReady => return FULL_QUALITY_PASS_END,
+            ProgressPhase::ExtractingFrames | ProgressPhase::ExtractingAudio | ProgressPhase::ConvertingFrames | ProgressPhase::RenderingVideo => {}
+        }
+        let total = self.weights.total();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let before: f64 = PHASE_ORDER.iter().take_while(|phase| **phase != progress.phase).map(|phase| self.weights.weight_for(phase)).sum();
+        let fraction = (progress.percentage / 100.0).clamp(0.0, 1.0);
+        let within_pass = ((before + self.weights.weight_for(&progress.phase) * fraction) / total).clamp(0.0, 1.0);
+        match progress.pass {
+            None => within_pass * 100.0,
+            Some(ConversionPass::Preview) => within_pass * PREVIEW_PASS_END,
+            Some(ConversionPass::FullQuality) => PREVIEW_PASS_END + within_pass * (FULL_QUALITY_PASS_END - PREVIEW_PASS_END),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overall_progress_accumulates_across_phases() {
+        let overall = OverallProgress::new(PhaseWeights::for_frames());
+        assert_eq!(overall.update(&Progress::extracting_frames()), 0.0);
+        assert!((overall.update(&Progress::extracting_frames_progress(50, 100)) - 17.5).abs() < 1e-9);
+        assert!((overall.update(&Progress::converting_frames(0, 10)) - 40.0).abs() < 1e-9);
+        assert!((overall.update(&Progress::converting_frames(10, 10)) - 100.0).abs() < 1e-9);
+        assert_eq!(overall.update(&Progress::complete(10)), 100.0);
+    }
+
+    #[test]
+    fn two_stage_passes_share_the_bar() {
+        let overall = OverallProgress::new(PhaseWeights::for_frames());
+        let dir = std::path::Path::new("out");
+        assert!((overall.update(&Progress::converting_frames(10, 10).with_pass(ConversionPass::Preview)) - PREVIEW_PASS_END).abs() < 1e-9);
+        assert_eq!(overall.update(&Progress::preview_ready(10, dir)), PREVIEW_PASS_END);
+        assert_eq!(overall.update(&Progress::extracting_frames().with_pass(ConversionPass::FullQuality)), PREVIEW_PASS_END);
+        assert!((overall.update(&Progress::converting_frames(10, 10).with_pass(ConversionPass::FullQuality)) - FULL_QUALITY_PASS_END).abs() < 1e-9);
+        assert_eq!(overall.update(&Progress::full_quality_ready(10, dir)), FULL_QUALITY_PASS_END);
+        assert_eq!(overall.update(&Progress::complete(10)), 100.0);
+    }
+
+    #[test]
+    fn overall_progress_never_goes_backwards() {
+        let overall = OverallProgress::new(PhaseWeights::for_frames());
+        overall.update(&Progress::converting_frames(5, 10));
+        let before = overall.percentage();
+        assert_eq!(overall.update(&Progress::converting_frames(3, 10)), before);
+        assert_eq!(overall.update(&Progress::extracting_audio()), before);
+    }
+
+    #[test]
+    fn overall_progress_skips_zero_weight_phases() {
+        let overall = OverallProgress::new(PhaseWeights::for_video().with_audio(0.0));
+        assert!((overall.update(&Progress::rendering_video(0, 4)) - 30.0 / 0.95).abs() < 1e-9);
+        let overall = OverallProgress::new(PhaseWeights::for_frames().with_extraction(0.0).with_audio(0.0).with_conversion(0.0));
+        assert_eq!(overall.update(&Progress::converting_frames(1, 2)), 0.0);
+    }
+
+    #[test]
+    fn wrapped_callback_receives_overall_percentage() {
+        let seen = std::sync::Mutex::new(Vec::new());
+        let callback = OverallProgress::wrap(PhaseWeights::for_video(), |progress, overall| seen.lock().unwrap().push((progress.phase, overall)));
+        callback(Progress::extracting_frames_progress(100, 100));
+        callback(Progress::complete(1));
+        drop(callback);
+        let seen = seen.into_inner().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert!((seen[0].1 - 30.0).abs() < 1e-9);
+        assert_eq!(seen[1], (ProgressPhase::Complete, 100.0));
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn progress_counter_reports_in_order_and_finishes_once() {
+        use rayon::prelude::*;
+        let counter = ProgressCounter::throttled(1000);
+        let seen = std::sync::Mutex::new(Vec::new());
+        let report = |count: usize| seen.lock().unwrap().push(count);
+        counter.start(report);
+        (0..1000).into_par_iter().for_each(|_| counter.complete_one(report));
+        // Extra completions (such as a retried item) never push the count past the total
+        counter.complete_one(report);
+        counter.finish(report);
+        let seen = seen.into_inner().unwrap();
+        assert!(seen.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", seen);
+        assert_eq!((seen[0], seen.last().copied(), seen.len()), (0, Some(1000), 101));
+        assert_eq!(Progress::converting_frames(12, 10).completed, 10);
+        assert_eq!(Progress::extracting_frames_progress(200, 100).percentage, 100.0);
+    }
+}
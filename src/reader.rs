@@ -0,0 +1,382 @@
+//! Lazy, ordered reading and parallel post-processing of converted frame directories.
+//!
+//! [`FrameReader`] walks the `frame_N.cframe` / `frame_N.txt` files a conversion wrote, one frame
+//! per [`Iterator::next`], so long clips play or post-process without holding every frame in
+//! memory. Frames are ordered by their parsed number rather than their file name, so unpadded or
+//! re-padded numbering (`frame_9999` before `frame_10000`) still plays in order. When a frame has
+//! both files the `.cframe` wins, keeping its colors and background layer.
+//!
+//! A directory without frames of its own is read as a set of shards: every subdirectory holding
+//! frames is read in turn, ordered by the number at the end of its name (`part_2` before
+//! `part_10`), then by name.
+//!
+//! A packed archive (a `.casc` file, see [`PackedArchive`]) reads like a directory of `.cframe`
+//! files, one frame at a time from the file. Encrypted archives are opened with
+//! [`FrameSequence::open_with_key`] and each frame is decrypted as it is read. A single-file
+//! animation (a `.cvid` file, see [`CvidReader`]) reads the same way.
+//!
+//! [`FrameSequence::par_map`] runs a transformation over every frame on the rayon pool and writes
+//! a renumbered copy, the building block for batch post-processing.
+
+use crate::error::{Context, Error, Result};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+use crate::convert::{cframe_bytes_to_frame_data, read_cframe_to_frame_data, read_txt_to_frame_data};
+use crate::cvid::{CvidReader, CVID_EXTENSION};
+use crate::frame::AsciiFrame;
+#[cfg(feature = "encryption")]
+use crate::packed::ArchiveKey;
+use crate::packed::{PackedArchive, ARCHIVE_EXTENSION};
+
+/// The frames of a converted directory, in playback order, as listed by [`FrameSequence::open`].
+///
+/// ```no_run
+/// use cascii::FrameSequence;
+///
+/// # fn main() -> cascii::Result<()> {
+/// // Mirror every frame, writing frame_0001… to a new directory with the same .txt/.cframe files
+/// FrameSequence::open("out/clip")?.par_map("out/mirrored", |mut frame| {
+///     frame.text = frame.text.lines().map(|line| line.chars().rev().collect::<String>() + "\n").collect();
+///     for row in frame.rgb.chunks_exact_mut(frame.width as usize * 3) {
+///         let mut cells: Vec<[u8; 3]> = row.chunks_exact(3).map(|cell| [cell[0], cell[1], cell[2]]).collect();
+///         cells.reverse();
+///         row.copy_from_slice(cells.as_flattened());
+///     }
+///     Ok(frame)
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct FrameSequence {
+    frames: Frames,
+}
+
+/// Where the frames of a [`FrameSequence`] are read from.
+enum Frames {
+    Files(Vec<FrameFiles>),
+    Archive(Archive),
+}
+
+/// A file holding every frame: a `.casc` archive or a `.cvid` animation.
+#[derive(Clone)]
+enum Archive {
+    Casc(Arc<PackedArchive>),
+    Cvid(Arc<CvidReader>),
+}
+
+impl Archive {
+    fn len(&self) -> usize {
+        match self {
+            Self::Casc(archive) => archive.len(),
+            Self::Cvid(cvid) => cvid.len(),
+        }
+    }
+
+    /// The complete `.cframe` bytes of frame `index`
+    fn frame(&self, index: usize) -> Result<Vec<u8>> {
+        match self {
+            Self::Casc(archive) => archive.frame(index),
+            Self::Cvid(cvid) => cvid.frame_bytes(index),
+        }
+    }
+}
+
+/// The `.cframe` and/or `.txt` file of one frame.
+struct FrameFiles {
+    cframe: Option<PathBuf>,
+    txt: Option<PathBuf>,
+}
+
+impl FrameFiles {
+    /// The `.cframe` when there is one, keeping colors and backgrounds.
+    fn path(&self) -> &Path {
+        self.cframe.as_deref().or(self.txt.as_deref()).expect("a listed frame has at least one file")
+    }
+}
+
+impl FrameSequence {
+    /// List the frames of `dir` (or of its shards), or of a `.casc` archive or `.cvid` file, without
+    /// reading them.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        if is_archive(dir) {
+            return Ok(Self {frames: Frames::Archive(Archive::Casc(Arc::new(PackedArchive::open(dir)?)))});
+        }
+        if dir.is_file() && dir.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(CVID_EXTENSION)) {
+            return Ok(Self {frames: Frames::Archive(Archive::Cvid(Arc::new(CvidReader::open(dir)?)))});
+        }
+        if !dir.is_dir() {
+            return Err(Error::NotFound(format!("frame directory not found: {}", dir.display())));
+        }
+        let mut frames = frame_files(dir);
+        if frames.is_empty() {
+            let mut shards: Vec<PathBuf> = entries(dir).filter(|path| path.is_dir()).collect();
+            shards.sort_by_cached_key(|shard| shard_key(shard));
+            frames = shards.iter().flat_map(|shard| frame_files(shard)).collect();
+        }
+        if frames.is_empty() {
+            return Err(Error::NotFound(format!("no frame_*.cframe or frame_*.txt files found in {}", dir.display())));
+        }
+        Ok(Self {frames: Frames::Files(frames)})
+    }
+
+    /// [`open`](Self::open), decrypting an encrypted `.casc` archive with `key` frame by frame as it
+    /// is read. Directories and unencrypted archives open as usual.
+    #[cfg(feature = "encryption")]
+    pub fn open_with_key(path: impl AsRef<Path>, key: &ArchiveKey) -> Result<Self> {
+        let path = path.as_ref();
+        if !is_archive(path) {
+            return Self::open(path);
+        }
+        Ok(Self {frames: Frames::Archive(Archive::Casc(Arc::new(PackedArchive::open_with_key(path, key)?)))})
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.frames {
+            Frames::Files(frames) => frames.len(),
+            Frames::Archive(archive) => archive.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Read frame `index` (counted in playback order), for players that seek.
+    pub fn read(&self, index: usize) -> Result<AsciiFrame> {
+        match &self.frames {
+            Frames::Files(frames) => read_frame(frames.get(index).ok_or_else(|| Error::InvalidOptions(format!("frame {} is past the last of {} frames", index, frames.len())))?.path()),
+            Frames::Archive(archive) => read_archive_frame(archive, index),
+        }
+    }
+
+    /// The `.cframe` bytes of frame `index` as stored, or encoded from a `.txt` frame in white, the
+    /// color frames without colors are rendered in
+    pub(crate) fn cframe_bytes(&self, index: usize) -> Result<Vec<u8>> {
+        match &self.frames {
+            Frames::Files(frames) => match frames.get(index).and_then(|files| files.cframe.as_deref()) {
+                Some(path) => fs::read(path).with_context(|| format!("reading cframe {}", path.display())),
+                None => self.read(index).map(|mut frame| {
+                    frame.rgb = vec![255; frame.text.chars().filter(|&ch| ch != '\n').count() * 3];
+                    frame.cframe_bytes()
+                }),
+            },
+            Frames::Archive(archive) => archive.frame(index),
+        }
+    }
+
+    /// Read the frames lazily, in order.
+    pub fn reader(&self) -> FrameReader {
+        match &self.frames {
+            Frames::Files(frames) => FrameReader {paths: frames.iter().map(|files| files.path().to_path_buf()).collect::<Vec<_>>().into_iter(), archive: None},
+            Frames::Archive(archive) => FrameReader {paths: Vec::new().into_iter(), archive: Some((archive.clone(), 0..archive.len()))},
+        }
+    }
+
+    /// Apply `map` to every frame in parallel and write the results to `output_dir`, renumbered
+    /// from `frame_0001` in playback order (closing gaps and flattening shards). Each output frame
+    /// gets the same files as its source: a `.txt`, a `.cframe`, or both (a `.cframe` for frames of an
+    /// archive). Returns the number of frames written.
+    ///
+    /// `map` receives the `.cframe` contents when a frame has one, so colors and backgrounds can be
+    /// transformed too; a frame written as `.cframe` must keep 3 color bytes per cell. `output_dir`
+    /// must not be the source directory, as renumbering could overwrite frames not read yet.
+    pub fn par_map<F>(&self, output_dir: impl AsRef<Path>, map: F) -> Result<usize>
+    where
+        F: Fn(AsciiFrame) -> Result<AsciiFrame> + Sync,
+    {
+        let output_dir = output_dir.as_ref();
+        fs::create_dir_all(output_dir).with_context(|| format!("creating output directory {}", output_dir.display()))?;
+        let output = output_dir.canonicalize().with_context(|| format!("resolving {}", output_dir.display()))?;
+        if let Frames::Files(frames) = &self.frames {
+            if frames.iter().any(|files| files.path().parent().and_then(|dir| dir.canonicalize().ok()).is_some_and(|dir| dir == output)) {
+                return Err(Error::InvalidOptions(format!("par_map output directory {} holds source frames; write to another directory", output_dir.display())));
+            }
+        }
+
+        (0..self.len()).into_par_iter().try_for_each(|index| {
+            let (source, write_txt, write_cframe) = match &self.frames {
+                Frames::Files(frames) => (frames[index].path().display().to_string(), frames[index].txt.is_some(), frames[index].cframe.is_some()),
+                Frames::Archive(_) => (format!("{} of the archive", index + 1), false, true),
+            };
+            let frame = self.read(index).and_then(&map).with_context(|| format!("mapping frame {}", source))?;
+            let out_txt = output_dir.join(format!("frame_{:04}.txt", index + 1));
+            if write_txt {
+                fs::write(&out_txt, &frame.text).with_context(|| format!("writing {}", out_txt.display()))?;
+            }
+            if write_cframe {
+                let cells = frame.text.chars().filter(|&ch| ch != '\n').count();
+                if cells != (frame.width * frame.height) as usize || frame.rgb.len() != cells * 3 {
+                    return Err(Error::Other(format!("mapped frame {} has {} cells and {} color bytes, expected {} cells ({}x{}) with 3 bytes each", source, cells, frame.rgb.len(), frame.width * frame.height, frame.width, frame.height)));
+                }
+                let out_cframe = out_txt.with_extension("cframe");
+                fs::write(&out_cframe, frame.cframe_bytes()).with_context(|| format!("writing {}", out_cframe.display()))?;
+            }
+            Ok(())
+        })?;
+        Ok(self.len())
+    }
+}
+
+/// An iterator over the frames of a converted directory, in playback order.
+///
+/// ```no_run
+/// use cascii::FrameReader;
+///
+/// # fn main() -> cascii::Result<()> {
+/// for frame in FrameReader::open("out/clip")? {
+///     let frame = frame?;
+///     println!("{}", frame.text);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct FrameReader {
+    paths: std::vec::IntoIter<PathBuf>,
+    /// For archives and `.cvid` files: the file and the indices of the frames not read yet
+    archive: Option<(Archive, std::ops::Range<usize>)>,
+}
+
+impl FrameReader {
+    /// List the frames of `dir` (or of its shards), or of a `.casc` archive. Nothing is read until
+    /// the iterator advances.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        FrameSequence::open(dir).map(|sequence| sequence.reader())
+    }
+
+    /// Paths of the frames not read yet, in order; empty for an archive.
+    pub fn paths(&self) -> &[PathBuf] {
+        self.paths.as_slice()
+    }
+}
+
+impl Iterator for FrameReader {
+    type Item = Result<AsciiFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.archive {
+            Some((archive, indices)) => indices.next().map(|index| read_archive_frame(archive, index)),
+            None => self.paths.next().map(|path| read_frame(&path)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.archive.as_ref().map_or_else(|| self.paths.size_hint(), |(_, indices)| indices.size_hint())
+    }
+}
+
+impl ExactSizeIterator for FrameReader {}
+
+fn read_frame(path: &Path) -> Result<AsciiFrame> {
+    let is_cframe = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cframe"));
+    let data = if is_cframe {read_cframe_to_frame_data(path)} else {read_txt_to_frame_data(path)};
+    data.map(AsciiFrame::from)
+}
+
+fn read_archive_frame(archive: &Archive, index: usize) -> Result<AsciiFrame> {
+    let data = archive.frame(index)?;
+    cframe_bytes_to_frame_data(&data, &format!("frame {} of the archive", index + 1)).map(AsciiFrame::from)
+}
+
+/// Whether `path` names a packed archive file rather than a frame directory
+fn is_archive(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(ARCHIVE_EXTENSION))
+}
+
+fn entries(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir).min_depth(1).max_depth(1).into_iter().filter_map(|entry| entry.ok()).map(|entry| entry.into_path())
+}
+
+/// The frames directly inside `dir`, by frame number.
+fn frame_files(dir: &Path) -> Vec<FrameFiles> {
+    let mut frames: BTreeMap<u64, FrameFiles> = BTreeMap::new();
+    for path in entries(dir).filter(|path| path.is_file()) {
+        let Some(number) = frame_number(&path) else {
+            continue;
+        };
+        let files = frames.entry(number).or_insert(FrameFiles {cframe: None, txt: None});
+        let is_cframe = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cframe"));
+        if is_cframe {files.cframe = Some(path)} else {files.txt = Some(path)}
+    }
+    frames.into_values().collect()
+}
+
+/// The `N` of a `frame_N.cframe` or `frame_N.txt` file.
+fn frame_number(path: &Path) -> Option<u64> {
+    let ext = path.extension()?.to_str()?;
+    if !ext.eq_ignore_ascii_case("cframe") && !ext.eq_ignore_ascii_case("txt") {
+        return None;
+    }
+    path.file_stem()?.to_str()?.strip_prefix("frame_")?.parse().ok()
+}
+
+/// Shards sort by the number ending their name, then by name; unnumbered shards come last.
+fn shard_key(shard: &Path) -> (u64, String) {
+    let name = shard.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_string();
+    let prefix_len = name.trim_end_matches(|ch: char| ch.is_ascii_digit()).len();
+    (name[prefix_len..].parse().unwrap_or(u64::MAX), name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::encode_cframe;
+
+    #[test]
+    fn test_frame_reader_orders_by_number_across_shards() {
+        let dir = tempfile::tempdir().unwrap();
+        let (part_2, part_10) = (dir.path().join("part_2"), dir.path().join("part_10"));
+        fs::create_dir(&part_2).unwrap();
+        fs::create_dir(&part_10).unwrap();
+        fs::write(part_2.join("frame_10.txt"), "b\n").unwrap();
+        fs::write(part_2.join("frame_9.txt"), "a\n").unwrap();
+        fs::write(part_2.join("frame_9.cframe"), encode_cframe(1, 1, "c", &[1, 2, 3], Some(&[4, 5, 6]))).unwrap();
+        fs::write(part_2.join("notes.txt"), "skipped").unwrap();
+        fs::write(part_10.join("frame_0001.txt"), "d\n").unwrap();
+
+        let reader = FrameReader::open(dir.path()).unwrap();
+        assert_eq!(reader.len(), 3);
+        let frames: Vec<AsciiFrame> = reader.collect::<Result<_>>().unwrap();
+        let texts: Vec<&str> = frames.iter().map(|frame| frame.text.as_str()).collect();
+        assert_eq!(texts, ["c\n", "b\n", "d\n"]);
+        assert_eq!((frames[0].rgb.as_slice(), frames[0].bg_rgb.as_slice()), (&[1, 2, 3][..], &[4, 5, 6][..]));
+        assert_eq!(frames[0].cframe_bytes(), encode_cframe(1, 1, "c", &[1, 2, 3], Some(&[4, 5, 6])));
+
+        assert!(FrameReader::open(dir.path().join("missing")).is_err());
+        assert!(FrameReader::open(tempfile::tempdir().unwrap().path()).is_err());
+    }
+
+    #[test]
+    fn test_par_map_renumbers_and_keeps_file_pairing() {
+        let src = tempfile::tempdir().unwrap();
+        fs::write(src.path().join("frame_3.txt"), "ab\n").unwrap();
+        fs::write(src.path().join("frame_3.cframe"), encode_cframe(2, 1, "ab", &[1, 1, 1, 2, 2, 2], None)).unwrap();
+        fs::write(src.path().join("frame_7.txt"), "cd\n").unwrap();
+
+        let sequence = FrameSequence::open(src.path()).unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let upper = |mut frame: AsciiFrame| {
+            frame.text = frame.text.to_uppercase();
+            frame.rgb.iter_mut().for_each(|channel| *channel *= 10);
+            Ok(frame)
+        };
+        assert_eq!(sequence.par_map(out.path(), upper).unwrap(), 2);
+        let mut names: Vec<String> = fs::read_dir(out.path()).unwrap().map(|entry| entry.unwrap().file_name().into_string().unwrap()).collect();
+        names.sort();
+        assert_eq!(names, ["frame_0001.cframe", "frame_0001.txt", "frame_0002.txt"]);
+        assert_eq!(fs::read_to_string(out.path().join("frame_0002.txt")).unwrap(), "CD\n");
+        assert_eq!(fs::read(out.path().join("frame_0001.cframe")).unwrap(), encode_cframe(2, 1, "AB", &[10, 10, 10, 20, 20, 20], None));
+
+        assert!(sequence.par_map(src.path(), Ok).is_err());
+        let shrink = |mut frame: AsciiFrame| {
+            frame.rgb.clear();
+            Ok(frame)
+        };
+        assert!(sequence.par_map(out.path(), shrink).unwrap_err().to_string().contains("color bytes"));
+    }
+}
@@ -0,0 +1,146 @@
+//! Preview-first conversions: a fast low-resolution pass to play right away, then the full quality.
+//!
+//! [`AsciiConverter::convert_video_two_stage`](crate::AsciiConverter::convert_video_two_stage)
+//! converts a video twice into versioned subdirectories of the output directory: first into
+//! [`PREVIEW_DIR`] at the columns and frame rate of a [`PreviewOptions`], then, on a background
+//! thread, into [`FULL_DIR`] at the requested settings. A `current` file in the output directory
+//! names the version to play; it is switched once a pass is complete, so a player reading it through
+//! [`current_version`] never sees a half-written version. [`ProgressPhase::PreviewReady`](crate::ProgressPhase::PreviewReady)
+//! and [`ProgressPhase::FullQualityReady`](crate::ProgressPhase::FullQualityReady) events announce
+//! each switch.
+//!
+//! ```no_run
+//! use cascii::refine::{current_version, PreviewOptions};
+//! use cascii::{AsciiConverter, ConversionOptions, ProgressPhase, VideoOptions};
+//! use std::path::Path;
+//!
+//! # fn main() -> cascii::Result<()> {
+//! let video_opts = VideoOptions {columns: 200, fps: 30, ..VideoOptions::default()};
+//! let conversion = AsciiConverter::new().convert_video_two_stage(Path::new("input.mp4"), Path::new("out"), &video_opts, &ConversionOptions::default(), PreviewOptions::default(), |progress| {
+//!     if progress.phase == ProgressPhase::FullQualityReady {
+//!         println!("{}", progress.message);
+//!     }
+//! })?;
+//! println!("playing {}", current_version(Path::new("out"))?.display());
+//! let full = conversion.full.join().expect("full-quality pass panicked")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+
+use crate::error::{Context, Error, Result};
+use crate::{ConversionOptions, ConversionResult, VideoOptions};
+
+/// Subdirectory of the output directory holding the preview
+pub const PREVIEW_DIR: &str = "v1_preview";
+/// Subdirectory of the output directory holding the full-quality conversion
+pub const FULL_DIR: &str = "v2_full";
+/// File in the output directory naming the subdirectory to play
+const CURRENT_FILE: &str = "current";
+
+/// Settings of the preview pass of a two-stage conversion; each is a ceiling, never raising what
+/// the full-quality pass asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreviewOptions {
+    /// Most columns of the preview (default 80)
+    pub columns: u32,
+    /// Highest frame rate of the preview (default 12)
+    pub fps: u32,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {columns: 80, fps: 12}
+    }
+}
+
+impl PreviewOptions {
+    /// The default preview: at most 80 columns and 12 frames per second
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the most columns of the preview
+    pub fn with_columns(mut self, columns: u32) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Set the highest frame rate of the preview
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    /// The options of the preview pass of a conversion asking for `video_opts` and `conv_opts`:
+    /// fewer columns and frames, and a single foreground color per cell
+    pub(crate) fn apply(&self, video_opts: &VideoOptions, conv_opts: &ConversionOptions) -> (VideoOptions, ConversionOptions) {
+        let columns = conv_opts.columns.unwrap_or(video_opts.columns).min(self.columns).max(1);
+        let video_opts = VideoOptions {columns, fps: video_opts.fps.min(self.fps).max(1), ..video_opts.clone()};
+        let conv_opts = conv_opts.clone().with_columns(columns).with_cell_color_mode(crate::CellColorMode::ForegroundOnly);
+        (video_opts, conv_opts)
+    }
+}
+
+/// A two-stage conversion whose preview is ready and whose full-quality pass runs in the background.
+#[derive(Debug)]
+pub struct TwoStageConversion {
+    /// The preview conversion
+    pub preview: ConversionResult,
+    /// Directory holding the preview
+    pub preview_dir: PathBuf,
+    /// Directory the full-quality pass writes into
+    pub full_dir: PathBuf,
+    /// The full-quality pass; the preview stays current if it fails
+    pub full: JoinHandle<Result<ConversionResult>>,
+}
+
+/// The version directory of `output_dir` to play: the preview until the full-quality pass is
+/// complete, then the full-quality conversion
+pub fn current_version(output_dir: &Path) -> Result<PathBuf> {
+    let pointer = output_dir.join(CURRENT_FILE);
+    let name = fs::read_to_string(&pointer).with_context(|| format!("reading {}", pointer.display()))?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(['/', '\\']) || name == ".." {
+        return Err(Error::Other(format!("{} doesn't name a version directory: {:?}", pointer.display(), name)));
+    }
+    Ok(output_dir.join(name))
+}
+
+/// Make `version`, a subdirectory of `output_dir`, the current one; the pointer is replaced by a
+/// rename, so readers see the old version or the new one, never an empty file
+pub(crate) fn set_current(output_dir: &Path, version: &str) -> Result<()> {
+    let staged = output_dir.join(format!(".{}.tmp", CURRENT_FILE));
+    fs::write(&staged, format!("{}\n", version)).with_context(|| format!("writing {}", staged.display()))?;
+    fs::rename(&staged, output_dir.join(CURRENT_FILE)).with_context(|| format!("switching {} to {}", output_dir.display(), version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CellColorMode;
+
+    #[test]
+    fn preview_caps_the_settings_and_the_pointer_switches_versions() -> Result<()> {
+        let video_opts = VideoOptions {columns: 200, fps: 30, ..VideoOptions::default()};
+        let conv_opts = ConversionOptions {cell_color_mode: CellColorMode::FitForegroundBackgroundOptimized, ..ConversionOptions::default()};
+        let (preview_video, preview_conv) = PreviewOptions::new().with_fps(10).apply(&video_opts, &conv_opts);
+        assert_eq!((preview_video.columns, preview_video.fps, preview_conv.columns), (80, 10, Some(80)));
+        assert_eq!(preview_conv.cell_color_mode, CellColorMode::ForegroundOnly);
+        let (small, _) = PreviewOptions::new().apply(&VideoOptions {columns: 40, fps: 8, ..VideoOptions::default()}, &ConversionOptions::default().with_columns(40));
+        assert_eq!((small.columns, small.fps), (40, 8));
+
+        let dir = tempfile::tempdir()?;
+        assert!(current_version(dir.path()).is_err());
+        set_current(dir.path(), PREVIEW_DIR)?;
+        assert_eq!(current_version(dir.path())?, dir.path().join(PREVIEW_DIR));
+        set_current(dir.path(), FULL_DIR)?;
+        assert_eq!(current_version(dir.path())?, dir.path().join(FULL_DIR));
+        fs::write(dir.path().join(CURRENT_FILE), "../elsewhere\n")?;
+        assert!(current_version(dir.path()).is_err());
+        Ok(())
+    }
+}
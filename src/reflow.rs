@@ -0,0 +1,213 @@
+//! Fitting frames to line length and line count limits.
+//!
+//! Chat apps and social media wrap or clip preformatted text wider or taller than they allow,
+//! which scrambles ASCII art. [`LineLimits`] makes every frame fit instead: by converting at fewer
+//! columns, by cropping a window centered on the subject rather than the frame, by hard-wrapping
+//! each row into several lines, or (experimentally) by seam carving the cell grid.
+
+use crate::cell_filter::LuminanceWeights;
+
+/// How a frame larger than its [`LineLimits`] is made to fit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineFit {
+    /// Convert at fewer columns, keeping the whole picture
+    #[default]
+    Downscale,
+    /// Keep the resolution and crop a window centered on the subject (the non-blank cells)
+    Crop,
+    /// Split every row into lines of at most the maximum length, padding the last one with blanks
+    Wrap,
+    /// Experimental: remove the lowest-energy seams of cells (seam carving on the luminance grid),
+    /// keeping detailed subjects whole while flat background shrinks. Seams are chosen per frame, so
+    /// video can shimmer
+    Carve,
+}
+
+impl LineFit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Downscale => "downscale",
+            Self::Crop => "crop",
+            Self::Wrap => "wrap",
+            Self::Carve => "carve",
+        }
+    }
+}
+
+/// Maximum line length and line count of converted frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LineLimits {
+    /// Longest line, in characters
+    pub max_columns: Option<u32>,
+    /// Most lines per frame
+    pub max_rows: Option<u32>,
+    /// How frames exceeding the limits are fitted
+    pub fit: LineFit,
+}
+
+impl LineLimits {
+    /// Sampled column count keeping a `source_width`×`source_height` image within the limits when
+    /// downscaling, given `repeat` cells per sample. Other fits keep `columns`.
+    pub(crate) fn columns(self, columns: Option<u32>, source_width: u32, source_height: u32, font_ratio: f32, repeat: u32) -> Option<u32> {
+        if self.fit != LineFit::Downscale || source_width == 0 {
+            return columns;
+        }
+        let mut limited = columns.unwrap_or(source_width);
+        if let Some(max_columns) = self.max_columns {
+            limited = limited.min((max_columns / repeat).max(1));
+        }
+        let rows_per_column = source_height as f32 / source_width as f32 * font_ratio;
+        if let Some(max_rows) = self.max_rows.filter(|&max_rows| limited as f32 * rows_per_column > max_rows as f32) {
+            limited = ((max_rows as f32 / rows_per_column) as u32).max(1);
+        }
+        if limited == columns.unwrap_or(source_width) {columns} else {Some(limited)}
+    }
+
+    /// Wrap, carve and/or crop a converted `width` × `height` frame to the limits. Downscaled frames only
+    /// ever need the crop when rounding leaves them a line over. Color buffers that don't hold 3
+    /// bytes per cell (an absent background layer) are left empty.
+    pub(crate) fn apply(self, text: &mut String, width: &mut u32, height: &mut u32, rgb: &mut Vec<u8>, bg_rgb: &mut Vec<u8>) {
+        let (w, h) = (*width as usize, *height as usize);
+        let (max_w, max_h) = (self.max_columns.map_or(usize::MAX, |max| max.max(1) as usize), self.max_rows.map_or(usize::MAX, |max| max.max(1) as usize));
+        if w <= max_w && h <= max_h {
+            return;
+        }
+
+        let chars: Vec<char> = text.chars().filter(|&ch| ch != '\n').collect();
+
+        // Source cell of every cell in the wrapped, carved or untouched grid; None pads a wrapped row
+        let (grid_w, grid_h, grid): (usize, usize, Vec<Option<usize>>) = match self.fit {
+            LineFit::Wrap if w > max_w => {
+                let chunks = w.div_ceil(max_w);
+                let cells = (0..h * chunks).flat_map(|row| (0..max_w).map(move |x| (row, x))).map(|(row, x)| {
+                    let src_x = (row % chunks) * max_w + x;
+                    (src_x < w).then_some((row / chunks) * w + src_x)
+                });
+                (max_w, h * chunks, cells.collect())
+            }
+            LineFit::Carve => {
+                let luma: Vec<u8> = if rgb.len() == chars.len() * 3 {rgb.chunks_exact(3).map(|px| LuminanceWeights::REC709.luminance(px[0], px[1], px[2])).collect()} else {chars.iter().map(|&ch| if ch == ' ' {0} else {255}).collect()};
+                let (carved_w, carved_h, cells) = carve(&luma, w, h, max_w, max_h);
+                (carved_w, carved_h, cells.into_iter().map(Some).collect())
+            }
+            _ => (w, h, (0..w * h).map(Some).collect()),
+        };
+
+        let (crop_w, crop_h) = (grid_w.min(max_w), grid_h.min(max_h));
+        let (x0, y0) = subject_window(&grid, &chars, grid_w, grid_h, crop_w, crop_h);
+        let window: Vec<Option<usize>> = (y0..y0 + crop_h).flat_map(|y| grid[y * grid_w + x0..y * grid_w + x0 + crop_w].iter().copied()).collect();
+
+        let trailing_newline = text.ends_with('\n');
+        let mut out = String::with_capacity((crop_w + 1) * crop_h);
+        for (row, cells) in window.chunks(crop_w).enumerate() {
+            if row > 0 {
+                out.push('\n');
+            }
+            out.extend(cells.iter().map(|cell| cell.and_then(|idx| chars.get(idx)).copied().unwrap_or(' ')));
+        }
+        if trailing_newline {
+            out.push('\n');
+        }
+        *text = out;
+        for colors in [rgb, bg_rgb] {
+            *colors = if colors.len() == chars.len() * 3 {window.iter().flat_map(|cell| cell.map_or([0; 3], |idx| [colors[idx * 3], colors[idx * 3 + 1], colors[idx * 3 + 2]])).collect()} else {Vec::new()};
+        }
+        (*width, *height) = (crop_w as u32, crop_h as u32);
+    }
+}
+
+/// Shrink a `width` × `height` luminance grid to at most `max_w` × `max_h` by repeatedly removing
+/// the connected seam of cells (one per row, or one per column) with the least gradient energy.
+/// Returns the carved size and the source index of every remaining cell.
+fn carve(luma: &[u8], width: usize, height: usize, max_w: usize, max_h: usize) -> (usize, usize, Vec<usize>) {
+    let mut cells: Vec<usize> = (0..width * height).collect();
+    let (mut w, mut h) = (width, height);
+    while w > max_w {
+        remove_vertical_seam(&mut cells, w, h, luma);
+        w -= 1;
+    }
+    if h > max_h {
+        // Horizontal seams are vertical seams of the transposed grid
+        let mut transposed = transpose(&cells, w, h);
+        while h > max_h {
+            remove_vertical_seam(&mut transposed, h, w, luma);
+            h -= 1;
+        }
+        cells = transpose(&transposed, h, w);
+    }
+    (w, h, cells)
+}
+
+fn remove_vertical_seam(cells: &mut Vec<usize>, w: usize, h: usize, luma: &[u8]) {
+    let at = |x: usize, y: usize| luma[cells[y * w + x]] as i32;
+    let energy = |x: usize, y: usize| (at((x + 1).min(w - 1), y) - at(x.saturating_sub(1), y)).abs() + (at(x, (y + 1).min(h - 1)) - at(x, y.saturating_sub(1))).abs();
+    let mut cost: Vec<i32> = (0..w).map(|x| energy(x, 0)).collect();
+    for y in 1..h {
+        let above = cost[(y - 1) * w..y * w].to_vec();
+        cost.extend((0..w).map(|x| energy(x, y) + above[x.saturating_sub(1)..=(x + 1).min(w - 1)].iter().min().copied().unwrap_or(0)));
+    }
+    let cheapest = |row: usize, from: usize, to: usize| (from..=to).min_by_key(|&x| cost[row * w + x]).unwrap_or(from);
+    let mut seam = vec![cheapest(h - 1, 0, w - 1); h];
+    for y in (0..h - 1).rev() {
+        let below = seam[y + 1];
+        seam[y] = cheapest(y, below.saturating_sub(1), (below + 1).min(w - 1));
+    }
+    let mut idx = 0;
+    cells.retain(|_| {
+        let (x, y) = (idx % w, idx / w);
+        idx += 1;
+        x != seam[y]
+    });
+}
+
+fn transpose(cells: &[usize], w: usize, h: usize) -> Vec<usize> {
+    (0..w).flat_map(|x| (0..h).map(move |y| cells[y * w + x])).collect()
+}
+
+/// Top-left corner of the `crop_w` × `crop_h` window centered on the non-blank cells of `grid`,
+/// or on the grid itself when it is blank.
+fn subject_window(grid: &[Option<usize>], chars: &[char], grid_w: usize, grid_h: usize, crop_w: usize, crop_h: usize) -> (usize, usize) {
+    let (mut sum_x, mut sum_y, mut count) = (0usize, 0usize, 0usize);
+    for (idx, cell) in grid.iter().enumerate() {
+        if cell.and_then(|src| chars.get(src)).is_some_and(|&ch| ch != ' ') {
+            (sum_x, sum_y, count) = (sum_x + idx % grid_w, sum_y + idx / grid_w, count + 1);
+        }
+    }
+    let center = |sum: usize, size: usize| (sum + count / 2).checked_div(count).unwrap_or(size / 2);
+    let (center_x, center_y) = (center(sum_x, grid_w), center(sum_y, grid_h));
+    (center_x.saturating_sub(crop_w / 2).min(grid_w - crop_w), center_y.saturating_sub(crop_h / 2).min(grid_h - crop_h))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_limits_crop_around_subject_and_wrap() {
+        let frame = || ("      abcd\n      efgh\n".to_string(), 10u32, 2u32, (0..60).collect::<Vec<u8>>());
+
+        let (mut text, mut width, mut height, mut rgb) = frame();
+        LineLimits {max_columns: Some(4), max_rows: None, fit: LineFit::Crop}.apply(&mut text, &mut width, &mut height, &mut rgb, &mut Vec::new());
+        assert_eq!((text.as_str(), width, height), ("abcd\nefgh\n", 4, 2));
+        assert_eq!(rgb, (18..30).chain(48..60).collect::<Vec<u8>>());
+
+        let (mut text, mut width, mut height, mut rgb) = frame();
+        let mut bg = vec![7; 60];
+        LineLimits {max_columns: Some(4), max_rows: None, fit: LineFit::Wrap}.apply(&mut text, &mut width, &mut height, &mut rgb, &mut bg);
+        assert_eq!((text.as_str(), width, height), ("    \n  ab\ncd  \n    \n  ef\ngh  \n", 4, 6));
+        assert_eq!(&rgb[27..33], &[27, 28, 29, 0, 0, 0]);
+        assert_eq!(bg.len(), 72);
+
+        // Carving drops the flat background around the subject instead of cropping one side of it
+        let (mut text, mut width, mut height, mut rgb) = ("  ##    \n  ##    \n        \n".to_string(), 8, 3, Vec::new());
+        LineLimits {max_columns: Some(4), max_rows: Some(2), fit: LineFit::Carve}.apply(&mut text, &mut width, &mut height, &mut rgb, &mut Vec::new());
+        assert_eq!((width, height), (4, 2));
+        assert!(text.lines().all(|line| line.contains("##")), "{:?}", text);
+
+        let downscale = LineLimits {max_columns: Some(80), max_rows: Some(20), fit: LineFit::Downscale};
+        assert_eq!(downscale.columns(Some(120), 200, 100, 0.5, 1), Some(80));
+        assert_eq!(downscale.columns(Some(120), 100, 100, 0.5, 1), Some(40));
+        assert_eq!(downscale.columns(Some(30), 100, 100, 0.5, 1), Some(30));
+        assert_eq!(downscale.columns(None, 100, 100, 0.5, 2), Some(40));
+    }
+}
@@ -1,13 +1,16 @@
 use ab_glyph::{FontRef, PxScale, ScaleFont};
-use anyhow::{anyhow, Context, Result};
-use image::{DynamicImage, Rgb};
+use crate::error::{Error, Result};
+use image::{DynamicImage, Rgb, RgbImage};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command as ProcCommand, Stdio};
 use std::sync::OnceLock;
 
+use crate::codepage::{ramp_char, Codepage};
 use crate::convert::AsciiFrameData;
-use crate::{BgFitQuality, FfmpegConfig};
+use crate::events::EventLog;
+use crate::frame::{sextant_mask, Sampling, QUADRANT_BLOCKS};
+use crate::{Attribution, AudioPadding, BgFitQuality, FfmpegConfig, GlyphMatch, ToVideoOptions};
 
 /// Embedded monospace font for video rendering
 const FONT_DATA: &[u8] = include_bytes!("../resources/DejaVuSansMono.ttf");
@@ -25,16 +28,63 @@ struct GlyphBitmap {
     s_bb: f64,
     det: f64,
     degenerate: bool,
+    /// Coverage in [`SHAPE_BLOCKS`] where the glyph sits in its cell, for glyph matching
+    blocks: Vec<f32>,
 }
 
 /// Pre-rasterized monospace glyph atlas for fast frame rendering
 pub(crate) struct GlyphAtlas {
-    /// Rasterized glyph bitmaps keyed by ASCII byte value
-    glyphs: HashMap<u8, GlyphBitmap>,
+    /// Rasterized glyph bitmaps of printable ASCII and of every other character added with [`Self::add_chars`]
+    glyphs: HashMap<char, GlyphBitmap>,
+    /// Rasterized braille patterns, indexed by dot bits (the offset from U+2800)
+    braille: Vec<GlyphBitmap>,
+    /// Quadrant block characters, indexed like [`QUADRANT_BLOCKS`]
+    quadrants: Vec<GlyphBitmap>,
+    /// Sextant characters, indexed by their mask (see [`crate::frame::sextant_char`])
+    sextants: Vec<GlyphBitmap>,
     /// Width of each character cell in pixels
     pub(crate) cell_width: u32,
     /// Height of each character cell in pixels
     pub(crate) cell_height: u32,
+    font_size: f32,
+    text_stroke_width: f32,
+}
+
+impl GlyphAtlas {
+    /// The bitmap of a character, if the atlas has one: printable ASCII, braille, quadrant blocks,
+    /// sextants and the characters added with [`Self::add_chars`]
+    fn glyph(&self, ch: char) -> Option<&GlyphBitmap> {
+        match ch {
+            '\u{2800}'..='\u{28ff}' => self.braille.get(ch as usize - 0x2800),
+            '\u{1fb00}'..='\u{1fb3b}' => sextant_mask(ch).map(|mask| &self.sextants[mask as usize]),
+            _ => QUADRANT_BLOCKS.iter().position(|&block| block == ch).map(|mask| &self.quadrants[mask]).or_else(|| self.glyphs.get(&ch)),
+        }
+    }
+
+    /// Rasterize the characters of `text` the atlas has no bitmap for yet, such as the shades of a
+    /// `░▒▓█` ramp. The shades are drawn as the dot patterns of the VGA font rather than taken from
+    /// the embedded one, so they tile without seams; characters the font lacks stay blank.
+    pub(crate) fn add_chars(&mut self, text: &str) -> Result<()> {
+        use ab_glyph::Font;
+
+        if text.is_ascii() {
+            return Ok(());
+        }
+        let missing: Vec<char> = text.chars().filter(|&ch| !ch.is_control() && self.glyph(ch).is_none()).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let font = FontRef::try_from_slice(FONT_DATA).map_err(|e| Error::Other(format!("failed to load embedded font: {}", e)))?;
+        for ch in missing {
+            if let Some(level) = SHADES.iter().position(|&shade| shade == ch) {
+                self.glyphs.insert(ch, GlyphBitmap::from_alpha(shade_alpha(level, self.cell_width, self.cell_height)).with_blocks(self.cell_width, self.cell_height));
+            } else if font.glyph_id(ch).0 != 0 {
+                let bitmap = rasterize_glyph(&font, ch, self.font_size, self.cell_width, self.cell_height, self.text_stroke_width);
+                self.glyphs.insert(ch, bitmap);
+            }
+        }
+        Ok(())
+    }
 }
 
 pub(crate) struct BackgroundAnalysisContext {
@@ -49,7 +99,7 @@ pub(crate) fn build_glyph_atlas(font_size: f32) -> Result<GlyphAtlas> {
 pub(crate) fn build_glyph_atlas_with_stroke(font_size: f32, text_stroke_width: f32) -> Result<GlyphAtlas> {
     use ab_glyph::Font;
 
-    let font = FontRef::try_from_slice(FONT_DATA).map_err(|e| anyhow!("failed to load embedded font: {}", e))?;
+    let font = FontRef::try_from_slice(FONT_DATA).map_err(|e| Error::Other(format!("failed to load embedded font: {}", e)))?;
 
     let scale = PxScale::from(font_size);
     let scaled_font = font.as_scaled(scale);
@@ -59,29 +109,93 @@ pub(crate) fn build_glyph_atlas_with_stroke(font_size: f32, text_stroke_width: f
     let h_advance = scaled_font.h_advance(font.glyph_id('M'));
     let cell_width = h_advance.ceil() as u32;
     let cell_height = (scaled_font.ascent() - scaled_font.descent()).ceil() as u32;
-    let ascent = scaled_font.ascent();
 
-    let mut glyphs = HashMap::new();
+    let glyphs = (' '..='~').map(|ch| (ch, rasterize_glyph(&font, ch, font_size, cell_width, cell_height, text_stroke_width))).collect();
+    // The embedded font has no braille, so the patterns are drawn as round dots on a 2×4 grid
+    let braille = (0..=255u8).map(|pattern| GlyphBitmap::from_alpha(braille_alpha(pattern, cell_width, cell_height)).with_blocks(cell_width, cell_height)).collect();
+    // Blocks are drawn exactly to the cell edges so neighbouring cells join without seams
+    let quadrants = (0..16u32).map(|mask| GlyphBitmap::from_alpha((0..cell_height).flat_map(|y| (0..cell_width).map(move |x| (mask >> ((2 * y >= cell_height) as u32 * 2 + (2 * x >= cell_width) as u32) & 1) as f32)).collect()).with_blocks(cell_width, cell_height)).collect();
+    let sextants = (0..64u32).map(|mask| GlyphBitmap::from_alpha((0..cell_height).flat_map(|y| (0..cell_width).map(move |x| (mask >> ((3 * y / cell_height.max(1)).min(2) * 2 + (2 * x >= cell_width) as u32) & 1) as f32)).collect()).with_blocks(cell_width, cell_height)).collect();
 
-    for byte in 32u8..=126u8 {
-        let ch = byte as char;
-        let glyph_id = font.glyph_id(ch);
-        let glyph = glyph_id.with_scale_and_position(scale, ab_glyph::point(0.0, ascent));
+    Ok(GlyphAtlas {glyphs, braille, quadrants, sextants, cell_width, cell_height, font_size, text_stroke_width})
+}
 
-        let mut alpha = vec![0.0f32; (cell_width * cell_height) as usize];
+/// The bitmap of `ch` in `font` for a `cell_width` × `cell_height` cell at `font_size` pixels
+fn rasterize_glyph(font: &FontRef, ch: char, font_size: f32, cell_width: u32, cell_height: u32, text_stroke_width: f32) -> GlyphBitmap {
+    use ab_glyph::Font;
 
-        if let Some(outlined) = font.outline_glyph(glyph) {
-            outlined.draw(|gx, gy, coverage| {
-                let px = gx;
-                let py = gy;
-                if px < cell_width && py < cell_height {
-                    alpha[(py * cell_width + px) as usize] = coverage;
-                }
-            });
-        }
+    let scale = PxScale::from(font_size);
+    let glyph = font.glyph_id(ch).with_scale_and_position(scale, ab_glyph::point(0.0, font.as_scaled(scale).ascent()));
+
+    let mut alpha = vec![0.0f32; (cell_width * cell_height) as usize];
+    // The bitmaps are drawn from the top left of the glyph's bounds; matching needs it where it sits in the cell
+    let mut placed = vec![0.0f32; (cell_width * cell_height) as usize];
+
+    if let Some(outlined) = font.outline_glyph(glyph) {
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = gx;
+            let py = gy;
+            if px < cell_width && py < cell_height {
+                alpha[(py * cell_width + px) as usize] = coverage;
+            }
+            let (px, py) = (gx as i32 + bounds.min.x as i32, gy as i32 + bounds.min.y as i32);
+            if (0..cell_width as i32).contains(&px) && (0..cell_height as i32).contains(&py) {
+                placed[(py as u32 * cell_width + px as u32) as usize] = coverage;
+            }
+        });
+    }
+
+    thicken_glyph_alpha(&mut alpha, cell_width, cell_height, text_stroke_width);
+    let mut bitmap = GlyphBitmap::from_alpha(alpha);
+    bitmap.blocks = shape_blocks(placed.into_iter(), cell_width, cell_height);
+    bitmap
+}
 
-        thicken_glyph_alpha(&mut alpha, cell_width, cell_height, text_stroke_width);
+/// The light, medium and dark shade characters
+const SHADES: [char; 3] = ['░', '▒', '▓'];
+
+/// Coverage of the shade `SHADES[level]` in a `cell_width` × `cell_height` cell: the 8×16 pixel
+/// patterns of the VGA font (a quarter, half and three quarters of the pixels lit) scaled to the cell
+fn shade_alpha(level: usize, cell_width: u32, cell_height: u32) -> Vec<f32> {
+    (0..cell_height).flat_map(|y| (0..cell_width).map(move |x| {
+        let (vga_x, vga_y) = (x * 8 / cell_width.max(1), y * 16 / cell_height.max(1));
+        let lit = match level {
+            0 => (vga_x + 2 * (vga_y % 2)) % 4 == 2,
+            1 => (vga_x + vga_y) % 2 == 1,
+            _ => (vga_x + 2 * (vga_y % 2)) % 4 != 2,
+        };
+        lit as u8 as f32
+    })).collect()
+}
 
+/// Coverage of the braille dot pattern `pattern` (bit 0 = dot 1) in a `cell_width` × `cell_height` cell
+fn braille_alpha(pattern: u8, cell_width: u32, cell_height: u32) -> Vec<f32> {
+    // Dot bits in the order of the 2×4 grid, row-major: dots 1 4 / 2 5 / 3 6 / 7 8
+    const DOTS: [u8; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+    let (dot_w, dot_h) = (cell_width as f32 / 2.0, cell_height as f32 / 4.0);
+    let radius = dot_w.min(dot_h) * 0.35;
+    let mut alpha = vec![0.0f32; (cell_width * cell_height) as usize];
+    for (dot, &bit) in DOTS.iter().enumerate() {
+        if pattern & bit == 0 {
+            continue;
+        }
+        let (center_x, center_y) = ((dot % 2) as f32 * dot_w + dot_w / 2.0, (dot / 2) as f32 * dot_h + dot_h / 2.0);
+        for y in 0..cell_height {
+            for x in 0..cell_width {
+                let distance = ((x as f32 + 0.5 - center_x).powi(2) + (y as f32 + 0.5 - center_y).powi(2)).sqrt();
+                let coverage = (radius + 0.5 - distance).clamp(0.0, 1.0);
+                let value = &mut alpha[(y * cell_width + x) as usize];
+                *value = value.max(coverage);
+            }
+        }
+    }
+    alpha
+}
+
+impl GlyphBitmap {
+    /// A bitmap of `alpha` with the coverage sums the background fitters solve with
+    fn from_alpha(alpha: Vec<f32>) -> Self {
         let mut s_aa = 0.0f64;
         let mut s_ab = 0.0f64;
         let mut s_bb = 0.0f64;
@@ -99,10 +213,14 @@ pub(crate) fn build_glyph_atlas_with_stroke(font_size: f32, text_stroke_width: f
         let degenerate = mean_alpha <= 1e-6 || mean_alpha >= 1.0 - 1e-6 || det.abs() <= 1e-9;
         let alpha_u8 = alpha.iter().map(|value| (value * 255.0).round().clamp(0.0, 255.0) as u8).collect();
 
-        glyphs.insert(byte, GlyphBitmap {alpha, alpha_u8, s_aa, s_ab, s_bb, det, degenerate});
+        GlyphBitmap {alpha, alpha_u8, s_aa, s_ab, s_bb, det, degenerate, blocks: Vec::new()}
     }
 
-    Ok(GlyphAtlas {glyphs, cell_width, cell_height})
+    /// This bitmap with shape blocks of its own coverage, for bitmaps drawn in place
+    fn with_blocks(mut self, cell_width: u32, cell_height: u32) -> Self {
+        self.blocks = shape_blocks(self.alpha.iter().copied(), cell_width, cell_height);
+        self
+    }
 }
 
 fn thicken_glyph_alpha(alpha: &mut [f32], cell_width: u32, cell_height: u32, text_stroke_width: f32) {
@@ -157,9 +275,15 @@ fn analysis_glyph_atlas(quality: BgFitQuality) -> Result<&'static GlyphAtlas> {
         BgFitQuality::Fidelity => &ANALYSIS_GLYPH_ATLAS_FIDELITY,
         BgFitQuality::Fast => &ANALYSIS_GLYPH_ATLAS_FAST,
     };
-    match cache.get_or_init(|| build_glyph_atlas(quality.analysis_font_size()).map_err(|e| e.to_string())) {
+    let build = || -> Result<GlyphAtlas> {
+        // Ramps may use any CP437 character, so their shapes are measured too
+        let mut atlas = build_glyph_atlas(quality.analysis_font_size())?;
+        atlas.add_chars(&Codepage::Cp437.decode(&(0x80..=0xFF).collect::<Vec<u8>>()))?;
+        Ok(atlas)
+    };
+    match cache.get_or_init(|| build().map_err(|e| e.to_string())) {
         Ok(atlas) => Ok(atlas),
-        Err(message) => Err(anyhow!(message.clone())),
+        Err(message) => Err(Error::Other(message.clone())),
     }
 }
 
@@ -176,47 +300,173 @@ pub(crate) fn background_analysis_context(ascii_chars: &[u8], quality: BgFitQual
     Ok(BackgroundAnalysisContext {atlas: analysis_glyph_atlas(quality)?, candidate_bytes: candidate_bytes_for_ascii_chars(ascii_chars)})
 }
 
-pub(crate) fn render_ascii_frame_into_rgb(frame: &AsciiFrameData, atlas: &GlyphAtlas, use_colors: bool, buffer: &mut Vec<u8>) {
-    let mut pixel_w = frame.width_chars * atlas.cell_width;
-    let mut pixel_h = frame.height_chars * atlas.cell_height;
+/// How strongly an attribution stamp covers the frame under it
+const STAMP_OPACITY: f32 = 0.6;
 
-    // H.264 requires even dimensions
-    if !pixel_w.is_multiple_of(2) {
-        pixel_w += 1;
+/// Colors a rendered frame gets where its cells bring none of their own
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Theme {
+    /// Behind every cell without a fitted background color
+    pub background: (u8, u8, u8),
+    /// Glyph color when rendering without per-character colors
+    pub foreground: (u8, u8, u8),
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {background: (0, 0, 0), foreground: (255, 255, 255)}
     }
-    if !pixel_h.is_multiple_of(2) {
-        pixel_h += 1;
+}
+
+impl Theme {
+    pub(crate) fn of(to_video_opts: &ToVideoOptions) -> Self {
+        Self {background: to_video_opts.background, foreground: to_video_opts.foreground.unwrap_or(Self::default().foreground)}
     }
 
+    /// Names of the background and foreground colors for the details report: `black` and `white`
+    /// for the defaults, `#rrggbb` otherwise
+    pub(crate) fn names(&self) -> (String, String) {
+        let name = |(r, g, b): (u8, u8, u8)| match (r, g, b) {
+            (0, 0, 0) => "black".to_string(),
+            (255, 255, 255) => "white".to_string(),
+            _ => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        };
+        (name(self.background), name(self.foreground))
+    }
+}
+
+/// Draw `frame` into `buffer` as one raw RGB video frame, padded to even dimensions, with `stamp`
+/// in its bottom-right corner when set (its characters must be in the atlas)
+pub(crate) fn render_ascii_frame_into_rgb(frame: &AsciiFrameData, atlas: &GlyphAtlas, use_colors: bool, theme: Theme, stamp: Option<&str>, buffer: &mut Vec<u8>) {
+    let (pixel_w, pixel_h) = frame_pixel_size(frame, atlas);
+    draw_cells_into_rgb(&frame.ascii_text, &frame.rgb_colors, &frame.bg_rgb_colors, atlas, use_colors, theme, pixel_w, pixel_h, buffer);
+    if let Some(text) = stamp {
+        draw_corner_text(buffer, pixel_w, pixel_h, atlas, text, theme.foreground);
+    }
+}
+
+/// Pipe a drawn frame to the ffmpeg encoder; the time spent waiting here is ffmpeg's encoding
+pub(crate) fn encode_frame(stdin: &mut impl std::io::Write, rgb: &[u8]) -> std::io::Result<()> {
+    profile_span!("encode");
+    stdin.write_all(rgb)
+}
+
+/// Pixel size `frame` is drawn at with `atlas`, rounded up to even sides as H.264 requires
+fn frame_pixel_size(frame: &AsciiFrameData, atlas: &GlyphAtlas) -> (u32, u32) {
+    let (pixel_w, pixel_h) = (frame.width_chars * atlas.cell_width, frame.height_chars * atlas.cell_height);
+    (pixel_w + pixel_w % 2, pixel_h + pixel_h % 2)
+}
+
+/// Bytes of the RGB buffer [`render_ascii_frame_into_rgb`] draws `frame` into
+pub(crate) fn frame_buffer_len(frame: &AsciiFrameData, atlas: &GlyphAtlas) -> usize {
+    let (pixel_w, pixel_h) = frame_pixel_size(frame, atlas);
+    pixel_w as usize * pixel_h as usize * 3
+}
+
+/// Draw `text` over the bottom-right corner of an RGB `buffer` of `pixel_w` × `pixel_h` pixels in
+/// `color` at [`STAMP_OPACITY`], half a cell in from the edges. Characters past the width of the
+/// frame are cut from the end.
+pub(crate) fn draw_corner_text(buffer: &mut [u8], pixel_w: u32, pixel_h: u32, atlas: &GlyphAtlas, text: &str, color: (u8, u8, u8)) {
+    let (cell_w, cell_h) = (atlas.cell_width, atlas.cell_height);
+    let margin = cell_w / 2;
+    if cell_w == 0 || pixel_h < cell_h + margin {
+        return;
+    }
+    let fits = (pixel_w.saturating_sub(2 * margin) / cell_w) as usize;
+    let chars: Vec<char> = text.chars().take(fits).collect();
+    let (top, left) = (pixel_h - cell_h - margin, pixel_w - margin - chars.len() as u32 * cell_w);
+    for (index, ch) in chars.into_iter().enumerate() {
+        let Some(glyph) = atlas.glyph(ch) else {continue};
+        for y in 0..cell_h {
+            for x in 0..cell_w {
+                let alpha = glyph.alpha[(y * cell_w + x) as usize] * STAMP_OPACITY;
+                if alpha <= 0.0 {
+                    continue;
+                }
+                let offset = (((top + y) * pixel_w + left + index as u32 * cell_w + x) * 3) as usize;
+                for (channel, value) in buffer[offset..offset + 3].iter_mut().zip([color.0, color.1, color.2]) {
+                    *channel = (*channel as f32 + (value as f32 - *channel as f32) * alpha).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+/// Rasterize `frame` with the embedded DejaVu Sans Mono at `font_size` pixels, the way ASCII videos
+/// are rendered: every glyph in its cell color over its background color, or white on black without
+/// `colors` (or when the frame has no colors). The image is exactly `width` × `height` cells.
+///
+/// ```
+/// use cascii::{image_to_frame, render_frame_to_image, ConversionOptions};
+///
+/// let frame = image_to_frame(&image::DynamicImage::new_rgb8(64, 32), &ConversionOptions::default().with_columns(16)).unwrap();
+/// let image = render_frame_to_image(&frame, 14.0, true).unwrap();
+/// assert_eq!(image.width() % frame.width, 0);
+/// ```
+pub fn render_frame_to_image(frame: &crate::AsciiFrame, font_size: f32, colors: bool) -> Result<RgbImage> {
+    if !(font_size.is_finite() && font_size > 0.0) {
+        return Err(Error::InvalidOptions(format!("font size must be a positive number of pixels, got {}", font_size)));
+    }
+    let mut atlas = build_glyph_atlas(font_size)?;
+    atlas.add_chars(&frame.text)?;
+    render_frame_with_atlas(frame, &atlas, colors)
+}
+
+/// [`render_frame_to_image`] with an atlas built once for many frames
+pub(crate) fn render_frame_with_atlas(frame: &crate::AsciiFrame, atlas: &GlyphAtlas, colors: bool) -> Result<RgbImage> {
+    let (pixel_w, pixel_h) = (frame.width * atlas.cell_width, frame.height * atlas.cell_height);
+    let mut buffer = Vec::new();
+    draw_cells_into_rgb(&frame.text, &frame.rgb, &frame.bg_rgb, atlas, colors, Theme::default(), pixel_w, pixel_h, &mut buffer);
+    RgbImage::from_raw(pixel_w, pixel_h, buffer).ok_or_else(|| Error::Other(format!("rendered frame does not fill {}x{} pixels", pixel_w, pixel_h)))
+}
+
+/// Draw the rows of `text` into `buffer`, resized to a `pixel_w` × `pixel_h` RGB image, one atlas
+/// cell per character; `rgb` and `bg_rgb` hold 3 bytes per character. Cells without colors of their
+/// own are drawn in the `theme` colors.
+#[allow(clippy::too_many_arguments)]
+fn draw_cells_into_rgb(text: &str, rgb: &[u8], bg_rgb: &[u8], atlas: &GlyphAtlas, use_colors: bool, theme: Theme, pixel_w: u32, pixel_h: u32, buffer: &mut Vec<u8>) {
+    profile_span!("render");
     buffer.clear();
     buffer.resize((pixel_w * pixel_h * 3) as usize, 0);
+    if theme.background != (0, 0, 0) {
+        let (r, g, b) = theme.background;
+        for pixel in buffer.chunks_exact_mut(3) {
+            pixel.copy_from_slice(&[r, g, b]);
+        }
+    }
 
     let mut char_idx: usize = 0;
     let mut row: u32 = 0;
     let mut col: u32 = 0;
 
-    for &byte in frame.ascii_text.as_bytes() {
-        if byte == b'\n' {
+    for ch in text.chars() {
+        if ch == '\n' {
             row += 1;
             col = 0;
             continue;
         }
 
         // Get color for this character
-        let (r, g, b) = if use_colors && char_idx * 3 + 2 < frame.rgb_colors.len() {
-            (frame.rgb_colors[char_idx * 3], frame.rgb_colors[char_idx * 3 + 1], frame.rgb_colors[char_idx * 3 + 2])
+        let (r, g, b) = if use_colors && char_idx * 3 + 2 < rgb.len() {
+            (rgb[char_idx * 3], rgb[char_idx * 3 + 1], rgb[char_idx * 3 + 2])
         } else {
-            (255, 255, 255) // white for text-only mode
+            theme.foreground
         };
 
         let base_x = col * atlas.cell_width;
         let base_y = row * atlas.cell_height;
+        // Rows longer (or more of them) than the frame declares fall outside the image
+        if base_x >= pixel_w || base_y >= pixel_h {
+            char_idx += 1;
+            col += 1;
+            continue;
+        }
         let x_end = (base_x + atlas.cell_width).min(pixel_w);
         let y_end = (base_y + atlas.cell_height).min(pixel_h);
         let cell_cols = (x_end - base_x) as usize;
 
-        if char_idx * 3 + 2 < frame.bg_rgb_colors.len() {
-            let bg = [frame.bg_rgb_colors[char_idx * 3], frame.bg_rgb_colors[char_idx * 3 + 1], frame.bg_rgb_colors[char_idx * 3 + 2]];
+        if char_idx * 3 + 2 < bg_rgb.len() {
+            let bg = [bg_rgb[char_idx * 3], bg_rgb[char_idx * 3 + 1], bg_rgb[char_idx * 3 + 2]];
             for py in base_y..y_end {
                 let offset = ((py * pixel_w + base_x) * 3) as usize;
                 for pixel in buffer[offset..offset + cell_cols * 3].chunks_exact_mut(3) {
@@ -226,7 +476,7 @@ pub(crate) fn render_ascii_frame_into_rgb(frame: &AsciiFrameData, atlas: &GlyphA
         }
 
         // Look up glyph bitmap
-        if let Some(glyph_bitmap) = atlas.glyphs.get(&byte) {
+        if let Some(glyph_bitmap) = atlas.glyph(ch) {
             for py in base_y..y_end {
                 let alpha_row = ((py - base_y) * atlas.cell_width) as usize;
                 let offset = ((py * pixel_w + base_x) * 3) as usize;
@@ -254,30 +504,26 @@ pub(crate) fn render_ascii_frame_into_rgb(frame: &AsciiFrameData, atlas: &GlyphA
     }
 }
 
-pub(crate) fn fit_image_to_ascii_with_cell_backgrounds(img_path: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, ascii_chars: &[u8], quality: BgFitQuality) -> Result<AsciiFrameData> {
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn fit_image_to_ascii_with_cell_backgrounds(img: &DynamicImage, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, sampling: Sampling, ascii_chars: &[u8], quality: BgFitQuality) -> Result<AsciiFrameData> {
     let background_analysis = background_analysis_context(ascii_chars, quality)?;
-    fit_image_to_ascii_with_cell_backgrounds_with_context(img_path, font_ratio, threshold, bg_threshold, columns, &background_analysis)
+    fit_image_to_ascii_with_cell_backgrounds_with_context(img, font_ratio, threshold, bg_threshold, columns, sampling, &background_analysis)
 }
 
-pub(crate) fn fit_image_to_ascii_with_cell_backgrounds_with_context(img_path: &Path, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, background_analysis: &BackgroundAnalysisContext) -> Result<AsciiFrameData> {
+pub(crate) fn fit_image_to_ascii_with_cell_backgrounds_with_context(img: &DynamicImage, font_ratio: f32, threshold: u8, bg_threshold: u8, columns: Option<u32>, sampling: Sampling, background_analysis: &BackgroundAnalysisContext) -> Result<AsciiFrameData> {
     let atlas = background_analysis.atlas;
-    let mut img = image::open(img_path).with_context(|| format!("opening {}", img_path.display()))?.to_rgb8();
+    let mut img = sampling.source(img).to_rgb8();
 
     let (orig_w, orig_h) = img.dimensions();
-    let (width_chars, height_chars) = if let Some(cols) = columns {
-        let h = (orig_h as f32 / orig_w as f32 * cols as f32 * font_ratio).round() as u32;
-        (cols, h.max(1))
-    } else {
-        let h = (orig_h as f32 * font_ratio).round() as u32;
-        (orig_w, h.max(1))
-    };
+    let (width_chars, height_chars) = sampling.grid_size(orig_w, orig_h, font_ratio, columns);
 
     let target_w = width_chars * atlas.cell_width;
     let target_h = height_chars * atlas.cell_height;
     if target_w != orig_w || target_h != orig_h {
         let dyn_img = DynamicImage::ImageRgb8(img);
-        img = dyn_img.resize_exact(target_w, target_h, image::imageops::FilterType::Triangle).to_rgb8();
+        img = dyn_img.resize_exact(target_w, target_h, sampling.resize_filter(width_chars, orig_w)).to_rgb8();
     }
+    sampling.tone.apply_rgb8(&mut img);
 
     let cell_pixels = (atlas.cell_width * atlas.cell_height) as usize;
     let mut ascii_text = String::with_capacity((width_chars as usize + 1) * height_chars as usize);
@@ -297,7 +543,7 @@ pub(crate) fn fit_image_to_ascii_with_cell_backgrounds_with_context(img_path: &P
             for py in 0..atlas.cell_height {
                 for px in 0..atlas.cell_width {
                     let pixel = *img.get_pixel(base_x + px, base_y + py);
-                    total_luma += luminance(pixel) as u64;
+                    total_luma += sampling.luminance.luminance(pixel[0], pixel[1], pixel[2]) as u64;
                     sum_rgb[0] += pixel[0] as u64;
                     sum_rgb[1] += pixel[1] as u64;
                     sum_rgb[2] += pixel[2] as u64;
@@ -342,7 +588,7 @@ pub(crate) fn fit_image_to_ascii_with_cell_backgrounds_with_context(img_path: &P
             let mut best_error = f64::INFINITY;
 
             for &byte in &background_analysis.candidate_bytes {
-                if let Some(glyph) = atlas.glyphs.get(&byte) {
+                if let Some(glyph) = atlas.glyphs.get(&ramp_char(byte)) {
                     let (fg, bg, error) = fit_colors_for_glyph(&patch, glyph, avg_rgb, sum_p, sum_p_sq);
                     if error < best_error {
                         best_byte = byte;
@@ -353,7 +599,7 @@ pub(crate) fn fit_image_to_ascii_with_cell_backgrounds_with_context(img_path: &P
                 }
             }
 
-            ascii_text.push(best_byte as char);
+            ascii_text.push(ramp_char(best_byte));
             rgb_colors.extend_from_slice(&best_fg);
             // Quadrant 2: glyph emitted but bg suppressed → black bg.
             if emit_bg {
@@ -368,6 +614,106 @@ pub(crate) fn fit_image_to_ascii_with_cell_backgrounds_with_context(img_path: &P
     Ok(AsciiFrameData {ascii_text, width_chars, height_chars, rgb_colors, bg_rgb_colors})
 }
 
+/// Blocks per cell (columns, rows) that [`shape_match_cells`] compares cells and glyphs in
+const SHAPE_BLOCKS: (u32, u32) = (3, 5);
+
+/// Variance of a cell's block luminances (0.0 to 1.0) below which it counts as flat and keeps its ramp character
+const FLAT_CELL_VARIANCE: f32 = 0.002;
+
+/// Mean of `values` (one per pixel of a `cell_width` wide cell) over each of the [`SHAPE_BLOCKS`]
+fn shape_blocks(values: impl Iterator<Item = f32>, cell_width: u32, cell_height: u32) -> Vec<f32> {
+    let (columns, rows) = (SHAPE_BLOCKS.0.min(cell_width), SHAPE_BLOCKS.1.min(cell_height));
+    let mut sums = vec![(0.0f32, 0u32); (columns * rows) as usize];
+    for (index, value) in values.enumerate() {
+        let (x, y) = (index as u32 % cell_width, index as u32 / cell_width);
+        let block = &mut sums[((y * rows / cell_height) * columns + x * columns / cell_width) as usize];
+        block.0 += value;
+        block.1 += 1;
+    }
+    sums.into_iter().map(|(sum, count)| sum / count.max(1) as f32).collect()
+}
+
+/// Mean and variance of `values`
+fn mean_variance(values: &[f32]) -> (f32, f32) {
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    (mean, values.iter().map(|value| (value - mean).powi(2)).sum::<f32>() / values.len() as f32)
+}
+
+/// Convert `img` picking every cell's character by comparing the cell with the glyph bitmaps of
+/// the analysis atlas (see [`GlyphMatch`]). Brightness still comes from the ramp: each cell picks
+/// among the characters near its luminance's place in `ascii_chars`, so flat areas look as they
+/// would by luminance and edges get the glyphs shaped like them. Cells and glyphs are compared as a
+/// few blocks of mean coverage; cells darker than `threshold` are spaces, and every cell's color is
+/// its mean color.
+pub(crate) fn shape_match_cells(img: &DynamicImage, font_ratio: f32, threshold: u8, columns: Option<u32>, sampling: Sampling, ascii_chars: &[u8], quality: BgFitQuality) -> Result<AsciiFrameData> {
+    let atlas = analysis_glyph_atlas(quality)?;
+    let mut img = sampling.source(img).to_rgb8();
+    let (orig_w, orig_h) = img.dimensions();
+    let (width_chars, height_chars) = sampling.grid_size(orig_w, orig_h, font_ratio, columns);
+    let (cell_w, cell_h) = (atlas.cell_width, atlas.cell_height);
+    if (width_chars * cell_w, height_chars * cell_h) != (orig_w, orig_h) {
+        img = image::imageops::resize(&img, width_chars * cell_w, height_chars * cell_h, sampling.resize_filter(width_chars, orig_w));
+    }
+    sampling.tone.apply_rgb8(&mut img);
+
+    // The ramp's glyphs with the mean and variance of their blocks; a white cell asks for as much
+    // coverage as the densest of them has
+    let ramp: Vec<(u8, &[f32], (f32, f32))> = ascii_chars.iter().filter_map(|&byte| atlas.glyphs.get(&ramp_char(byte)).map(|glyph| (byte, glyph.blocks.as_slice(), mean_variance(&glyph.blocks)))).collect();
+    if ramp.is_empty() {
+        return Err(Error::Other("none of the characters have a glyph to match".into()));
+    }
+    let densest = ramp.iter().map(|(_, _, (mean, _))| *mean).fold(0.0f32, f32::max);
+    let window = (ramp.len() / 8).max(1);
+
+    let cell_pixels = cell_w * cell_h;
+    let mut ascii_text = String::with_capacity((width_chars as usize + 1) * height_chars as usize);
+    let mut rgb_colors = Vec::with_capacity((width_chars * height_chars * 3) as usize);
+    for row in 0..height_chars {
+        for col in 0..width_chars {
+            let pixels = (0..cell_pixels).map(|index| img.get_pixel(col * cell_w + index % cell_w, row * cell_h + index / cell_w));
+            let mut sum_rgb = [0u32; 3];
+            let lumas: Vec<f32> = pixels.map(|pixel| {
+                for (sum, &channel) in sum_rgb.iter_mut().zip(pixel.0.iter()) {
+                    *sum += channel as u32;
+                }
+                sampling.luminance.luminance(pixel[0], pixel[1], pixel[2]) as f32 / 255.0
+            }).collect();
+            rgb_colors.extend(sum_rgb.map(|sum| (sum / cell_pixels) as u8));
+            let mean_luma = lumas.iter().sum::<f32>() / cell_pixels as f32;
+            let Some(index) = crate::frame::ramp_index((mean_luma * 65535.0).round() as u16, threshold, ramp.len()) else {
+                ascii_text.push(' ');
+                continue;
+            };
+            // Flat cells have no shape to match, and would only pick the faintest glyphs nearby
+            let blocks = shape_blocks(lumas.into_iter(), cell_w, cell_h);
+            let (mean, variance) = mean_variance(&blocks);
+            if variance < FLAT_CELL_VARIANCE {
+                ascii_text.push(ramp_char(ramp[index].0));
+                continue;
+            }
+            // Shape and contrast only: the window already keeps the brightness, and a ramp tuned
+            // by eye doesn't climb evenly through the atlas glyphs' coverage
+            let cost = |&(_, glyph, (glyph_mean, glyph_variance)): &(u8, &[f32], (f32, f32))| -> f32 {
+                let centered = blocks.iter().zip(glyph).map(|(value, coverage)| ((value - mean) * densest, coverage - glyph_mean));
+                match sampling.glyph_match {
+                    GlyphMatch::Ssim => {
+                        const C2: f32 = 0.03 * 0.03;
+                        let covariance = centered.map(|(value, coverage)| value * coverage).sum::<f32>() / blocks.len() as f32;
+                        -(2.0 * covariance + C2) / (variance * densest * densest + glyph_variance + C2)
+                    }
+                    _ => centered.map(|(value, coverage)| (value - coverage).powi(2)).sum(),
+                }
+            };
+            // The luminance pick first, so it wins ties
+            let nearby = std::iter::once(index).chain((index.saturating_sub(window)..(index + window + 1).min(ramp.len())).filter(|&near| near != index));
+            let best = nearby.map(|near| (ramp[near].0, cost(&ramp[near]))).min_by(|left, right| left.1.total_cmp(&right.1)).map_or(b' ', |(byte, _)| byte);
+            ascii_text.push(ramp_char(best));
+        }
+        ascii_text.push('\n');
+    }
+    Ok(AsciiFrameData {ascii_text, width_chars, height_chars, rgb_colors, bg_rgb_colors: Vec::new()})
+}
+
 fn blend_channel(background: u8, foreground: u8, alpha: u32) -> u8 {
     ((background as u32 * (255 - alpha) + foreground as u32 * alpha + 127) / 255) as u8
 }
@@ -415,51 +761,215 @@ fn constant_patch_error(cell_pixels: usize, color: [u8; 3], sum_p: [f64; 3], sum
     error
 }
 
-fn luminance(rgb: Rgb<u8>) -> u8 {
-    ((2126 * rgb[0] as u32 + 7152 * rgb[1] as u32 + 722 * rgb[2] as u32) / 10000) as u8
+/// Audio muxed into an encoded video.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AudioSource {
+    /// File holding the audio: an extracted `audio.mp3` or the source video itself
+    pub path: PathBuf,
+    /// Seconds to skip at the start of `path`
+    pub start_secs: f64,
+    /// Seconds to read from `path` (after `start_secs`), or everything
+    pub duration_secs: Option<f64>,
+    /// Copy the stream as is instead of re-encoding it to AAC. Copied audio can't be padded,
+    /// looped or delayed by a filter, so the caller turns this off when those are needed.
+    pub copy: bool,
+}
+
+impl AudioSource {
+    /// The whole of an audio file
+    pub(crate) fn file(path: &Path, copy: bool) -> Self {
+        Self {path: path.to_path_buf(), start_secs: 0.0, duration_secs: None, copy}
+    }
+}
+
+/// Whether an audio stream in `codec` can be copied into a file with `output`'s container.
+pub(crate) fn container_accepts_audio(output: &Path, codec: &str) -> bool {
+    match output.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("mkv") => true,
+        Some("webm") => matches!(codec, "opus" | "vorbis"),
+        _ => matches!(codec, "aac" | "mp3" | "opus" | "ac3" | "eac3" | "alac"),
+    }
+}
+
+/// ffmpeg input and output arguments that mux `audio` into the encoded video (input 0),
+/// honouring the offset and padding settings of `to_video_opts`.
+fn audio_args(audio: &AudioSource, to_video_opts: &ToVideoOptions) -> Vec<String> {
+    let offset = to_video_opts.audio_offset.unwrap_or(0.0);
+    let advance = (-offset).max(0.0);
+    let mut args: Vec<String> = Vec::new();
+    if to_video_opts.audio_padding == AudioPadding::Loop && !audio.copy {
+        args.extend(["-stream_loop".into(), "-1".into()]);
+    }
+    if audio.start_secs + advance > 0.0 {
+        args.extend(["-ss".into(), format!("{:.3}", audio.start_secs + advance)]);
+    }
+    if let Some(duration) = audio.duration_secs {
+        args.extend(["-t".into(), format!("{:.3}", (duration - advance).max(0.0))]);
+    }
+    if audio.copy && offset > 0.0 {
+        args.extend(["-itsoffset".into(), format!("{:.3}", offset)]);
+    }
+    args.extend(["-i".into(), audio.path.to_str().unwrap_or("audio.mp3").to_string()]);
+    // The audio input may be the source video; never let ffmpeg pick its picture over the rendered frames.
+    args.extend(["-map".into(), "0:v:0".into(), "-map".into(), "1:a:0".into()]);
+
+    if audio.copy {
+        args.extend(["-c:a".into(), "copy".into(), "-shortest".into()]);
+        return args;
+    }
+    let mut filters = Vec::new();
+    if offset > 0.0 {
+        filters.push(format!("adelay=delays={}:all=1", (offset * 1000.0).round() as u64));
+    }
+    // Padding with silence indefinitely makes `-shortest` stop at the end of the video, so short
+    // audio never truncates the frames; without it the video is cut where the audio ends.
+    if to_video_opts.audio_padding != AudioPadding::TrimVideo {
+        filters.push("apad".to_string());
+    }
+    if !filters.is_empty() {
+        args.extend(["-af".into(), filters.join(",")]);
+    }
+    args.extend(["-c:a".into(), "aac".into(), "-b:a".into(), "192k".into(), "-shortest".into()]);
+    args
 }
 
-pub(crate) fn spawn_ffmpeg_encoder(pixel_width: u32, pixel_height: u32, fps: u32, crf: u8, audio_path: Option<&Path>, output_path: &Path, ffmpeg_config: &FfmpegConfig) -> Result<std::process::Child> {
+/// ffmpeg input and output arguments that mux the `narration` audio file into the encoded video
+/// (input 0) as its own track, after the track of the clip's audio when `after_audio`
+pub(crate) fn narration_args(narration: &Path, after_audio: bool) -> Vec<String> {
+    let (input, track) = if after_audio {(2, 1)} else {(1, 0)};
+    let mut args = vec!["-i".to_string(), narration.to_string_lossy().into_owned()];
+    if !after_audio {
+        args.extend(["-map".into(), "0:v:0".into()]);
+    }
+    args.push("-map".into());
+    args.push(format!("{}:a:0", input));
+    // Stream-specific options come last so they win over the ones of the clip's audio
+    args.extend([format!("-filter:a:{}", track), "apad".into(), format!("-c:a:{}", track), "aac".into(), format!("-b:a:{}", track), "192k".into(), format!("-metadata:s:a:{}", track), "title=Narration".into(), "-shortest".into()]);
+    args
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn spawn_ffmpeg_encoder(pixel_width: u32, pixel_height: u32, frame_rate: f64, audio: Option<&AudioSource>, to_video_opts: &ToVideoOptions, attribution: Option<&Attribution>, ffmpeg_config: &FfmpegConfig, events: Option<&EventLog>) -> Result<std::process::Child> {
     let size = format!("{}x{}", pixel_width, pixel_height);
 
-    let mut args: Vec<String> = vec!["-y".into(), "-loglevel".into(), "error".into(), "-f".into(), "rawvideo".into(), "-pix_fmt".into(), "rgb24".into(), "-s:v".into(), size, "-r".into(), fps.to_string(), "-i".into(), "pipe:0".into()];
+    let mut args: Vec<String> = vec!["-y".into(), "-loglevel".into(), "error".into(), "-f".into(), "rawvideo".into(), "-pix_fmt".into(), "rgb24".into(), "-s:v".into(), size, "-r".into(), frame_rate.to_string(), "-i".into(), "pipe:0".into()];
 
-    if let Some(audio) = audio_path {
-        args.push("-i".into());
-        args.push(audio.to_str().unwrap_or("audio.mp3").to_string());
-        args.push("-c:a".into());
-        args.push("aac".into());
-        args.push("-b:a".into());
-        args.push("192k".into());
-        args.push("-shortest".into());
+    if let Some(audio) = audio {
+        args.extend(audio_args(audio, to_video_opts));
+    }
+    if let Some(narration) = &to_video_opts.narration {
+        args.extend(narration_args(narration, audio.is_some()));
     }
 
     args.push("-c:v".into());
     args.push("libx264".into());
     args.push("-crf".into());
-    args.push(crf.to_string());
+    args.push(to_video_opts.crf.to_string());
     args.push("-preset".into());
     args.push("medium".into());
     args.push("-g".into());
-    args.push(fps.to_string());
+    args.push((frame_rate.round() as u32).max(1).to_string());
     args.push("-pix_fmt".into());
     args.push("yuv420p".into());
-    args.push(output_path.to_str().ok_or_else(|| anyhow!("output path is not valid UTF-8"))?.to_string());
+    for (key, value) in attribution.map(Attribution::video_metadata).unwrap_or_default() {
+        args.push("-metadata".into());
+        args.push(format!("{}={}", key, value));
+    }
+    args.push(to_video_opts.output_path.to_str().ok_or_else(|| Error::Other("output path is not valid UTF-8".into()))?.to_string());
 
-    let child = ProcCommand::new(ffmpeg_config.ffmpeg_cmd()).args(&args).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::piped()).spawn().context("spawning ffmpeg encoder")?;
-    Ok(child)
+    let mut command = ProcCommand::new(ffmpeg_config.ffmpeg_cmd());
+    command.args(&args).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::piped());
+    if let Some(events) = events {
+        events.record_command(&command);
+    }
+    command.spawn().map_err(|err| Error::spawn(ffmpeg_config.ffmpeg_cmd(), err))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn audio_args_follow_offset_and_padding() {
+        let audio = AudioSource::file(Path::new("audio.mp3"), false);
+        let defaults = audio_args(&audio, &ToVideoOptions::default()).join(" ");
+        assert_eq!(defaults, "-i audio.mp3 -map 0:v:0 -map 1:a:0 -af apad -c:a aac -b:a 192k -shortest");
+
+        let delayed = ToVideoOptions {audio_offset: Some(1.5), audio_padding: AudioPadding::TrimVideo, ..ToVideoOptions::default()};
+        assert_eq!(audio_args(&audio, &delayed).join(" "), "-i audio.mp3 -map 0:v:0 -map 1:a:0 -af adelay=delays=1500:all=1 -c:a aac -b:a 192k -shortest");
+
+        let looped = ToVideoOptions {audio_offset: Some(-0.25), audio_padding: AudioPadding::Loop, ..ToVideoOptions::default()};
+        assert!(audio_args(&audio, &looped).join(" ").starts_with("-stream_loop -1 -ss 0.250 -i audio.mp3 -map 0:v:0 -map 1:a:0 -af apad"));
+    }
+
+    #[test]
+    fn rendered_frame_covers_every_cell_in_its_colors() {
+        let frame = crate::AsciiFrame {text: "@ \n #\n".to_string(), width: 2, height: 2, rgb: vec![250, 10, 10, 0, 0, 0, 0, 0, 0, 10, 250, 10], bg_rgb: vec![0, 0, 90, 0, 0, 90, 0, 0, 90, 0, 0, 90]};
+        let image = render_frame_to_image(&frame, 14.0, true).unwrap();
+        let atlas = build_glyph_atlas(14.0).unwrap();
+        assert_eq!(image.dimensions(), (2 * atlas.cell_width, 2 * atlas.cell_height));
+        // The blank cell shows its background only; the glyphs draw in their own colors over theirs
+        let blank: Vec<_> = (0..atlas.cell_height).flat_map(|y| (atlas.cell_width..2 * atlas.cell_width).map(move |x| (x, y))).map(|(x, y)| *image.get_pixel(x, y)).collect();
+        assert!(blank.iter().all(|pixel| pixel.0 == [0, 0, 90]));
+        assert!(image.pixels().any(|pixel| pixel[0] > 200 && pixel[1] < 40) && image.pixels().any(|pixel| pixel[1] > 200 && pixel[0] < 40));
+
+        let plain = render_frame_to_image(&frame, 14.0, false).unwrap();
+        assert!(plain.pixels().all(|pixel| pixel[0] == pixel[1]) && plain.pixels().any(|pixel| pixel[0] > 200));
+        assert!(render_frame_to_image(&frame, 0.0, true).is_err());
+    }
+
+    #[test]
+    fn braille_cells_draw_their_dots() {
+        let frame = crate::AsciiFrame {text: "\u{28ff}\u{2801}\u{2800}▄\n".to_string(), width: 4, height: 1, rgb: vec![255; 12], bg_rgb: Vec::new()};
+        let image = render_frame_to_image(&frame, 14.0, true).unwrap();
+        let atlas = build_glyph_atlas(14.0).unwrap();
+        let lit = |x0: u32, x1: u32, y0: u32, y1: u32| (y0..y1).flat_map(|y| (x0..x1).map(move |x| (x, y))).filter(|&(x, y)| image.get_pixel(x, y)[0] > 64).count();
+        let (w, h) = (atlas.cell_width, atlas.cell_height);
+        // Dot 1 is the top-left of the cell; the blank pattern draws nothing
+        assert!(lit(0, w, 0, h) > lit(w, 2 * w, 0, h));
+        assert!(lit(w, w + w / 2, 0, h / 2) > 0 && lit(w + w / 2, 2 * w, 0, h) == 0 && lit(w, 2 * w, h / 2, h) == 0);
+        assert_eq!(lit(2 * w, 3 * w, 0, h), 0);
+        // Blocks fill their half of the cell exactly
+        assert_eq!((lit(3 * w, 4 * w, 0, h / 2), lit(3 * w, 4 * w, h.div_ceil(2), h)), (0, (w * (h - h.div_ceil(2))) as usize));
+    }
+
+    #[test]
+    fn unicode_ramp_characters_are_rasterized_on_demand() {
+        let frame = crate::AsciiFrame {text: "░▓█\n".to_string(), width: 3, height: 1, rgb: Vec::new(), bg_rgb: Vec::new()};
+        let mut atlas = build_glyph_atlas(14.0).unwrap();
+        assert!(atlas.glyph('░').is_none());
+        atlas.add_chars(&frame.text).unwrap();
+        let image = render_frame_with_atlas(&frame, &atlas, false).unwrap();
+        let (w, h) = (atlas.cell_width, atlas.cell_height);
+        let ink = |cell: u32| (cell * w..(cell + 1) * w).flat_map(|x| (0..h).map(move |y| (x, y))).map(|(x, y)| image.get_pixel(x, y)[0] as u32).sum::<u32>();
+        // The shades climb like a ramp, and render_frame_to_image adds them itself
+        assert!(0 < ink(0) && ink(0) < ink(1) && ink(1) < ink(2));
+        assert_eq!(render_frame_to_image(&frame, 14.0, false).unwrap(), image);
+    }
+
+    #[test]
+    fn copyable_audio_depends_on_container() {
+        assert!(container_accepts_audio(Path::new("out.mp4"), "opus"));
+        assert!(!container_accepts_audio(Path::new("out.mp4"), "vorbis"));
+        assert!(!container_accepts_audio(Path::new("out.MP4"), "pcm_s16le"));
+        assert!(container_accepts_audio(Path::new("out.mkv"), "pcm_s16le"));
+    }
+
+    #[test]
+    fn passthrough_copies_the_source_range() {
+        let source = AudioSource {path: PathBuf::from("in.mkv"), start_secs: 10.0, duration_secs: Some(5.0), copy: true};
+        let delayed = ToVideoOptions {audio_offset: Some(0.5), ..ToVideoOptions::default()};
+        assert_eq!(audio_args(&source, &delayed).join(" "), "-ss 10.000 -t 5.000 -itsoffset 0.500 -i in.mkv -map 0:v:0 -map 1:a:0 -c:a copy -shortest");
+        let advanced = ToVideoOptions {audio_offset: Some(-1.0), ..ToVideoOptions::default()};
+        assert!(audio_args(&source, &advanced).join(" ").starts_with("-ss 11.000 -t 4.000 -i in.mkv"));
+    }
+
     #[test]
     fn renders_background_for_space_cells() -> Result<()> {
         let atlas = build_glyph_atlas(12.0)?;
         let frame = AsciiFrameData {ascii_text: " \n".to_string(), width_chars: 1, height_chars: 1, rgb_colors: Vec::new(), bg_rgb_colors: vec![255, 0, 0]};
         let mut buffer = Vec::new();
-        render_ascii_frame_into_rgb(&frame, &atlas, false, &mut buffer);
+        render_ascii_frame_into_rgb(&frame, &atlas, false, Theme::default(), None, &mut buffer);
         assert!(buffer.chunks_exact(3).any(|pixel| pixel[0] > 200 && pixel[1] < 16 && pixel[2] < 16));
         Ok(())
     }
@@ -469,12 +979,31 @@ mod tests {
         let atlas = build_glyph_atlas(12.0)?;
         let frame = AsciiFrameData {ascii_text: "M\n".to_string(), width_chars: 1, height_chars: 1, rgb_colors: vec![0, 255, 0], bg_rgb_colors: vec![0, 0, 255]};
         let mut buffer = Vec::new();
-        render_ascii_frame_into_rgb(&frame, &atlas, true, &mut buffer);
+        render_ascii_frame_into_rgb(&frame, &atlas, true, Theme::default(), None, &mut buffer);
         assert!(buffer.chunks_exact(3).any(|pixel| pixel[1] == 0 && pixel[2] > 200));
         assert!(buffer.chunks_exact(3).any(|pixel| pixel[1] > 0 && pixel[2] < 255));
         Ok(())
     }
 
+    #[test]
+    fn theme_colors_background_and_monochrome_glyphs() -> Result<()> {
+        let atlas = build_glyph_atlas(12.0)?;
+        let frame = AsciiFrameData {ascii_text: " M\n".to_string(), width_chars: 2, height_chars: 1, rgb_colors: vec![255, 0, 0, 255, 0, 0], bg_rgb_colors: Vec::new()};
+        let options = ToVideoOptions {background: (10, 20, 30), foreground: Some((51, 255, 102)), ..ToVideoOptions::default()};
+        let theme = Theme::of(&options);
+        let mut buffer = Vec::new();
+        render_ascii_frame_into_rgb(&frame, &atlas, false, theme, None, &mut buffer);
+        assert_eq!(&buffer[..3], &[10, 20, 30]);
+        assert!(buffer.chunks_exact(3).any(|pixel| pixel[1] > 200 && pixel[0] < 60));
+        assert!(buffer.chunks_exact(3).all(|pixel| pixel[0] <= 51));
+        // Per-character colors still win over the theme foreground
+        render_ascii_frame_into_rgb(&frame, &atlas, true, theme, None, &mut buffer);
+        assert!(buffer.chunks_exact(3).any(|pixel| pixel[0] > 200 && pixel[1] < 60));
+        assert_eq!(theme.names(), ("#0a141e".to_string(), "#33ff66".to_string()));
+        assert_eq!(Theme::default().names(), ("black".to_string(), "white".to_string()));
+        Ok(())
+    }
+
     /// Helper: writes a uniform mid-gray image (luminance ≈ 128) to a temp PNG.
     fn write_uniform_test_image(luma_target: u8) -> (tempfile::TempDir, std::path::PathBuf) {
         let dir = tempfile::tempdir().expect("tempdir");
@@ -498,7 +1027,7 @@ mod tests {
     fn bg_fit_quadrant_both_thresholds_met() -> Result<()> {
         // Uniform gray ≈ 128; thresholds well below it on both axes → glyph + bg.
         let (_dir, path) = write_uniform_test_image(128);
-        let frame = fit_image_to_ascii_with_cell_backgrounds(&path, 0.5, 30, 30, Some(4), b" .M", BgFitQuality::Fidelity)?;
+        let frame = fit_image_to_ascii_with_cell_backgrounds(&image::open(&path)?, 0.5, 30, 30, Some(4), Sampling::default(), b" .M", BgFitQuality::Fidelity)?;
         let bg = last_cell_bg(&frame);
         // bg should be non-black (matches mid-gray-ish).
         assert!(bg[0] > 5 || bg[1] > 5 || bg[2] > 5, "expected coloured bg, got {:?}", bg);
@@ -511,7 +1040,7 @@ mod tests {
     fn bg_fit_quadrant_glyph_only_bg_suppressed() -> Result<()> {
         // fg threshold passes, bg threshold doesn't → glyph + black bg.
         let (_dir, path) = write_uniform_test_image(128);
-        let frame = fit_image_to_ascii_with_cell_backgrounds(&path, 0.5, 30, 200, Some(4), b" .M", BgFitQuality::Fidelity)?;
+        let frame = fit_image_to_ascii_with_cell_backgrounds(&image::open(&path)?, 0.5, 30, 200, Some(4), Sampling::default(), b" .M", BgFitQuality::Fidelity)?;
         let bg = last_cell_bg(&frame);
         assert_eq!(bg, [0, 0, 0], "bg should be black when bg threshold not met");
         // Glyph still emitted (not all spaces).
@@ -523,7 +1052,7 @@ mod tests {
     fn bg_fit_quadrant_bg_only_glyph_suppressed() -> Result<()> {
         // fg threshold fails, bg threshold passes → space + coloured bg ("mosaic" cell).
         let (_dir, path) = write_uniform_test_image(128);
-        let frame = fit_image_to_ascii_with_cell_backgrounds(&path, 0.5, 200, 30, Some(4), b" .M", BgFitQuality::Fidelity)?;
+        let frame = fit_image_to_ascii_with_cell_backgrounds(&image::open(&path)?, 0.5, 200, 30, Some(4), Sampling::default(), b" .M", BgFitQuality::Fidelity)?;
         let bg = last_cell_bg(&frame);
         assert!(bg[0] > 5 || bg[1] > 5 || bg[2] > 5, "expected coloured bg, got {:?}", bg);
         // Every glyph should be a space.
@@ -537,7 +1066,7 @@ mod tests {
     fn bg_fit_quadrant_neither_threshold_met() -> Result<()> {
         // Both thresholds above luminance → empty cells.
         let (_dir, path) = write_uniform_test_image(64);
-        let frame = fit_image_to_ascii_with_cell_backgrounds(&path, 0.5, 200, 200, Some(4), b" .M", BgFitQuality::Fidelity)?;
+        let frame = fit_image_to_ascii_with_cell_backgrounds(&image::open(&path)?, 0.5, 200, 200, Some(4), Sampling::default(), b" .M", BgFitQuality::Fidelity)?;
         assert!(frame.bg_rgb_colors.iter().all(|&b| b == 0), "expected all-black bg");
         assert!(frame.rgb_colors.iter().all(|&b| b == 0), "expected all-black fg");
         assert_eq!(first_glyph(&frame), ' ');
@@ -549,7 +1078,7 @@ mod tests {
         // With bg_threshold == threshold, behaviour reduces to the legacy
         // single-threshold output: cells either fully present or fully empty.
         let (_dir, path) = write_uniform_test_image(128);
-        let frame = fit_image_to_ascii_with_cell_backgrounds(&path, 0.5, 50, 50, Some(4), b" .M", BgFitQuality::Fidelity)?;
+        let frame = fit_image_to_ascii_with_cell_backgrounds(&image::open(&path)?, 0.5, 50, 50, Some(4), Sampling::default(), b" .M", BgFitQuality::Fidelity)?;
         // For every cell, bg is either entirely the cell colour or entirely black —
         // never partial.
         for chunk in frame.bg_rgb_colors.chunks_exact(3) {
@@ -559,4 +1088,24 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn shape_matching_keeps_flat_cells_and_reshapes_edges() -> Result<()> {
+        use crate::test_pattern::{generate_test_pattern, TestPattern};
+        use crate::{image_to_frame, CharMode, ConversionOptions, GlyphMatch};
+
+        let wedge = DynamicImage::ImageRgb8(generate_test_pattern(TestPattern::Wedge, 320, 160));
+        let options = ConversionOptions::default().with_columns(40);
+        let by_luminance = image_to_frame(&wedge, &options)?;
+        for glyph_match in [GlyphMatch::Mse, GlyphMatch::Ssim] {
+            let by_shape = image_to_frame(&wedge, &options.clone().with_glyph_match(glyph_match))?;
+            assert_eq!((by_shape.width, by_shape.height), (by_luminance.width, by_luminance.height));
+            // The flat gray surround keeps its ramp character; the spokes change
+            let margins = |frame: &crate::AsciiFrame| frame.text.lines().map(|line| line[..4].to_string()).collect::<Vec<_>>();
+            assert_eq!(margins(&by_shape), margins(&by_luminance));
+            assert_ne!(by_shape.text, by_luminance.text);
+        }
+        assert!(image_to_frame(&wedge, &options.with_glyph_match(GlyphMatch::Mse).with_char_mode(CharMode::Braille)).is_err());
+        Ok(())
+    }
 }
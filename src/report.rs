@@ -0,0 +1,261 @@
+//! Conversion reports: the `details.*` file written next to converted frames.
+//!
+//! Reports come in three built-in formats ([`ReportFormat::Toml`], the historical `details.toml`,
+//! plus JSON and HTML) or from a user-supplied [`ReportTemplate`] with `{{placeholder}}` fields.
+//! Every [`ConversionResult`] field is available as a placeholder, along with `version`; the color
+//! palette is available as `average_color` and `dominant_colors` (space-separated `#rrggbb`, empty
+//! for outputs without colors), and the [attribution](crate::Attribution) as `creator`, `license` and
+//! `attribution_url` (empty when unset):
+//!
+//! ```
+//! use cascii::report::render_template;
+//! # let result = cascii::ConversionResult {frame_count: 12, columns: 80, font_ratio: 0.5, luminance: 20, fps: Some(24), output_mode: "text-only".into(), audio_extracted: false, output_dir: "out".into(), background_color: "black".into(), color: "white".into(), fit_cell_backgrounds: false, cell_background_mode: "off".into(), bg_fit_quality: "fidelity".into(), bg_luminance: 20, ascii_chars: " .#".into(), audio_sync: None, colors: None, seed: 0, attribution: None};
+//!
+//! let text = render_template("{{frame_count}} frames at {{columns}} columns", &result)?;
+//! assert_eq!(text, "12 frames at 80 columns");
+//! # Ok::<(), cascii::Error>(())
+//! ```
+
+use crate::error::{Context, Error, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{ConversionResult, DominantColor};
+
+/// Names accepted inside `{{...}}` in report templates.
+pub const TEMPLATE_PLACEHOLDERS: &[&str] = &["version", "frame_count", "columns", "font_ratio", "luminance", "fps", "output_mode", "audio_extracted", "output_dir", "background_color", "color", "fit_cell_backgrounds", "cell_background_mode", "bg_fit_quality", "bg_luminance", "ascii_chars", "seed", "average_color", "dominant_colors", "creator", "license", "attribution_url"];
+
+const HTML_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>cascii conversion report</title>
+<style>body{font-family:sans-serif}th{text-align:left;padding-right:1em}td{font-family:monospace}</style>
+</head>
+<body>
+<h1>cascii conversion report</h1>
+<table>
+<tr><th>Version</th><td>{{version}}</td></tr>
+<tr><th>Frames</th><td>{{frame_count}}</td></tr>
+<tr><th>Columns</th><td>{{columns}}</td></tr>
+<tr><th>Font ratio</th><td>{{font_ratio}}</td></tr>
+<tr><th>Luminance</th><td>{{luminance}}</td></tr>
+<tr><th>FPS</th><td>{{fps}}</td></tr>
+<tr><th>Output</th><td>{{output_mode}}</td></tr>
+<tr><th>Audio</th><td>{{audio_extracted}}</td></tr>
+<tr><th>Output directory</th><td>{{output_dir}}</td></tr>
+<tr><th>Background color</th><td>{{background_color}}</td></tr>
+<tr><th>Color</th><td>{{color}}</td></tr>
+<tr><th>Cell backgrounds</th><td>{{cell_background_mode}}</td></tr>
+<tr><th>Background fit quality</th><td>{{bg_fit_quality}}</td></tr>
+<tr><th>Background luminance</th><td>{{bg_luminance}}</td></tr>
+<tr><th>Characters</th><td>{{ascii_chars}}</td></tr>
+<tr><th>Seed</th><td>{{seed}}</td></tr>
+<tr><th>Dominant colors</th><td>{{dominant_colors}}</td></tr>
+<tr><th>Creator</th><td>{{creator}}</td></tr>
+<tr><th>License</th><td>{{license}}</td></tr>
+</table>
+</body>
+</html>
+"#;
+
+/// A user-supplied report template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportTemplate {
+    /// Template text with `{{placeholder}}` fields
+    pub text: String,
+    /// Extension of the written report (`details.<extension>`)
+    pub extension: String,
+}
+
+impl ReportTemplate {
+    pub fn new(text: impl Into<String>, extension: impl Into<String>) -> Self {
+        Self {text: text.into(), extension: extension.into()}
+    }
+
+    /// Load a template file. The report extension comes from the file name, ignoring a trailing
+    /// `.tmpl`/`.template` (`report.md.tmpl` writes `details.md`); it falls back to `txt`.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("reading report template {}", path.display()))?;
+        let mut name = Path::new(path.file_name().unwrap_or_default());
+        if name.extension().is_some_and(|ext| ext == "tmpl" || ext == "template") {
+            name = Path::new(name.file_stem().unwrap_or_default());
+        }
+        let extension = name.extension().and_then(|ext| ext.to_str()).unwrap_or("txt").to_string();
+        Ok(Self {text, extension})
+    }
+}
+
+/// Output format of the conversion report.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// `details.toml` (the default)
+    #[default]
+    Toml,
+    /// `details.json`
+    Json,
+    /// `details.html`, a standalone page with a settings table
+    Html,
+    /// A user template; values are inserted verbatim
+    Template(ReportTemplate),
+}
+
+impl ReportFormat {
+    /// Look up a built-in format by name (`toml`, `json`, `html`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "toml" => Some(Self::Toml),
+            "json" => Some(Self::Json),
+            "html" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    /// Extension of the report file.
+    pub fn extension(&self) -> &str {
+        match self {
+            Self::Toml => "toml",
+            Self::Json => "json",
+            Self::Html => "html",
+            Self::Template(template) => &template.extension,
+        }
+    }
+
+    /// File name of the report inside the output directory.
+    pub fn file_name(&self) -> String {
+        format!("details.{}", self.extension())
+    }
+}
+
+fn placeholder_value(result: &ConversionResult, name: &str) -> Option<String> {
+    Some(match name {
+        "version" => env!("CARGO_PKG_VERSION").to_string(),
+        "frame_count" => result.frame_count.to_string(),
+        "columns" => result.columns.to_string(),
+        "font_ratio" => result.font_ratio.to_string(),
+        "luminance" => result.luminance.to_string(),
+        "fps" => result.fps.map(|fps| fps.to_string()).unwrap_or_default(),
+        "output_mode" => result.output_mode.clone(),
+        "audio_extracted" => result.audio_extracted.to_string(),
+        "output_dir" => result.output_dir.display().to_string(),
+        "background_color" => result.background_color.clone(),
+        "color" => result.color.clone(),
+        "fit_cell_backgrounds" => result.fit_cell_backgrounds.to_string(),
+        "cell_background_mode" => result.cell_background_mode.clone(),
+        "bg_fit_quality" => result.bg_fit_quality.clone(),
+        "bg_luminance" => result.bg_luminance.to_string(),
+        "ascii_chars" => result.ascii_chars.clone(),
+        "seed" => result.seed.to_string(),
+        "average_color" => result.colors.as_ref().map(|colors| DominantColor {rgb: colors.average, share: 1.0}.hex()).unwrap_or_default(),
+        "dominant_colors" => result.colors.as_ref().map(|colors| colors.dominant.iter().map(DominantColor::hex).collect::<Vec<_>>().join(" ")).unwrap_or_default(),
+        "creator" => result.attribution.as_ref().and_then(|attribution| attribution.creator.clone()).unwrap_or_default(),
+        "license" => result.attribution.as_ref().and_then(|attribution| attribution.license.clone()).unwrap_or_default(),
+        "attribution_url" => result.attribution.as_ref().and_then(|attribution| attribution.url.clone()).unwrap_or_default(),
+        _ => return None,
+    })
+}
+
+fn escape_html(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+fn render_with(template: &str, result: &ConversionResult, escape: fn(&str) -> String) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or_else(|| Error::Parse(format!("unterminated placeholder in report template: {}", &rest[start..rest.len().min(start + 32)])))?;
+        let name = after[..end].trim();
+        let Some(value) = placeholder_value(result, name) else {
+            return Err(Error::Parse(format!("unknown report placeholder {{{{{}}}}} (available: {})", name, TEMPLATE_PLACEHOLDERS.join(", "))));
+        };
+        out.push_str(&escape(&value));
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Fill `{{placeholder}}` fields in `template` from `result`. Unknown placeholders are an error.
+pub fn render_template(template: &str, result: &ConversionResult) -> Result<String> {
+    render_with(template, result, str::to_string)
+}
+
+/// Render `result` as a report in `format`.
+pub fn render_report(result: &ConversionResult, format: &ReportFormat) -> Result<String> {
+    match format {
+        ReportFormat::Toml => toml::to_string_pretty(&result.to_details()).context("serializing details to TOML"),
+        ReportFormat::Json => serde_json::to_string_pretty(&result.to_details()).context("serializing details to JSON"),
+        ReportFormat::Html => render_with(HTML_TEMPLATE, result, escape_html),
+        ReportFormat::Template(template) => render_template(&template.text, result),
+    }
+}
+
+/// Write the report for `result` to `details.<ext>` in its output directory.
+pub fn write_report(result: &ConversionResult, format: &ReportFormat) -> Result<PathBuf> {
+    let report = render_report(result, format)?;
+    let path = result.output_dir.join(format.file_name());
+    fs::write(&path, report).with_context(|| format!("writing report to {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result() -> ConversionResult {
+        ConversionResult {frame_count: 3, columns: 120, font_ratio: 0.5, luminance: 10, fps: None, output_mode: "text+color".to_string(), audio_extracted: true, output_dir: PathBuf::from("out"), background_color: "black".to_string(), color: "white".to_string(), fit_cell_backgrounds: false, cell_background_mode: "off".to_string(), bg_fit_quality: "fast".to_string(), bg_luminance: 12, ascii_chars: " <&>".to_string(), audio_sync: None, colors: None, seed: 7, attribution: None}
+    }
+
+    #[test]
+    fn every_placeholder_renders() {
+        for name in TEMPLATE_PLACEHOLDERS {
+            assert!(render_template(&format!("{{{{ {} }}}}", name), &result()).is_ok(), "{}", name);
+        }
+        assert_eq!(render_template("{{fps}}|{{output_mode}}|{{audio_extracted}}|{{dominant_colors}}", &result()).unwrap(), "|text+color|true|");
+
+        let colors = crate::ColorPalette {average: [16, 32, 48], dominant: vec![DominantColor {rgb: [255, 0, 0], share: 0.75}, DominantColor {rgb: [0, 0, 255], share: 0.25}]};
+        let colored = ConversionResult {colors: Some(colors), ..result()};
+        assert_eq!(render_template("{{average_color}} {{dominant_colors}}", &colored).unwrap(), "#102030 #ff0000 #0000ff");
+        assert!(colored.to_details_string().contains("[[colors.dominant]]"));
+    }
+
+    #[test]
+    fn template_errors_are_reported() {
+        assert!(render_template("{{frames}}", &result()).unwrap_err().to_string().contains("unknown report placeholder {{frames}}"));
+        assert!(render_template("a {{columns", &result()).is_err());
+        assert_eq!(render_template("no placeholders }}", &result()).unwrap(), "no placeholders }}");
+    }
+
+    #[test]
+    fn built_in_formats() {
+        let json: serde_json::Value = serde_json::from_str(&render_report(&result(), &ReportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["frames"], 3);
+        assert_eq!(render_report(&result(), &ReportFormat::Toml).unwrap(), result().to_details_string());
+        let html = render_report(&result(), &ReportFormat::Html).unwrap();
+        assert!(html.contains("<td> &lt;&amp;&gt;</td>"));
+        assert_eq!(ReportFormat::from_name("html").unwrap().file_name(), "details.html");
+    }
+
+    #[test]
+    fn template_extension_comes_from_file_name() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, extension) in [("report.md.tmpl", "md"), ("report.csv", "csv"), ("report", "txt")] {
+            let path = dir.path().join(name);
+            fs::write(&path, "{{columns}}").unwrap();
+            assert_eq!(ReportTemplate::from_file(&path).unwrap().extension, extension);
+        }
+    }
+}
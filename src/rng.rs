@@ -0,0 +1,49 @@
+//! The one source of randomness in conversions.
+//!
+//! Stochastic features draw from a [`SeededRng`] seeded with
+//! [`ConversionOptions::seed`](crate::ConversionOptions::seed), which conversions record in
+//! `details.toml`, so converting the same input with the same options and seed gives the same
+//! output byte for byte, on any platform.
+
+/// SplitMix64: small, fast, and the same sequence everywhere for a given seed.
+#[derive(Clone, Debug)]
+pub(crate) struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {state: seed}
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0.0..1.0`
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_rng_repeats_per_seed() {
+        let draw = |seed: u64| {
+            let mut rng = SeededRng::new(seed);
+            (0..64).map(|_| rng.next_f32()).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+        assert!(draw(0).iter().all(|value| (0.0..1.0).contains(value)));
+        // Pinned so the sequence, and every recorded seed, stays valid across releases
+        assert_eq!(SeededRng::new(0).next_u64(), 0xE220_A839_7B1D_CDAF);
+    }
+}
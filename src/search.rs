@@ -0,0 +1,173 @@
+//! Reverse search: where a picture appears in a converted sequence.
+//!
+//! [`find_frame`] compares a query, a source image or an ASCII frame, with every frame of a
+//! converted directory and returns the frames that look like it with their timestamps, for lining
+//! up edits or external events with a conversion. Query and frames are both reduced to a 32×32
+//! grid of luminances (cell glyph density, times the cell color's luminance when the frame has
+//! colors), so queries match at any size or column count, and compared by correlation, so the
+//! luminance threshold and character ramp of the conversion don't count against a match.
+//!
+//! ```no_run
+//! use cascii::search::find_frame;
+//!
+//! # fn main() -> cascii::Result<()> {
+//! let still = image::open("screenshot.png")?;
+//! for found in find_frame("out/clip", &still, 0.9)?.iter().take(3) {
+//!     println!("frame {} at {:.2}s ({:.0}%)", found.index + 1, found.timestamp.as_secs_f64(), found.similarity * 100.0);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Error, Result};
+use image::DynamicImage;
+use rayon::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::cell_filter::LuminanceWeights;
+use crate::frame::{AsciiFrame, CellBytes};
+use crate::loop_detect::{luminance_grid, recorded_fps, RampLookup, DEFAULT_PREVIEW_FPS, HASH_GRID};
+use crate::FrameSequence;
+
+/// What to look for with [`find_frame`].
+#[derive(Clone, Copy, Debug)]
+pub enum FrameQuery<'a> {
+    /// A source picture, such as a still of the converted video
+    Image(&'a DynamicImage),
+    /// A converted frame, from this sequence or another conversion of the same source
+    Frame(&'a AsciiFrame),
+}
+
+impl<'a> From<&'a DynamicImage> for FrameQuery<'a> {
+    fn from(image: &'a DynamicImage) -> Self {
+        Self::Image(image)
+    }
+}
+
+impl<'a> From<&'a AsciiFrame> for FrameQuery<'a> {
+    fn from(frame: &'a AsciiFrame) -> Self {
+        Self::Frame(frame)
+    }
+}
+
+/// A frame of the sequence resembling the query.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameMatch {
+    /// Position of the frame in playback order, from 0
+    pub index: usize,
+    /// When the frame shows, at the frame rate in the directory's `details.toml` (24 fps without one)
+    pub timestamp: Duration,
+    /// How alike the frame and the query are, from 0.0 to 1.0
+    pub similarity: f32,
+}
+
+/// The frames of the converted directory `dir` at least `threshold` (0.0 to 1.0) similar to
+/// `query`, best first (earliest first among equals).
+///
+/// Glyphs are weighed by the `ascii_chars` ramp recorded in `details.toml`, or the default ramp.
+pub fn find_frame<'a>(dir: impl AsRef<Path>, query: impl Into<FrameQuery<'a>>, threshold: f32) -> Result<Vec<FrameMatch>> {
+    if !threshold.is_finite() || !(0.0..=1.0).contains(&threshold) {
+        return Err(Error::InvalidOptions("threshold must be finite and between 0 and 1".into()));
+    }
+    let dir = dir.as_ref();
+    let sequence = FrameSequence::open(dir)?;
+    let ramp = RampLookup::new(&recorded_ascii_chars(dir).unwrap_or_else(crate::config::default_ascii_chars))?;
+    // Colors only count when both sides have them; an image stands for a colored frame
+    let (plain, colored) = match query.into() {
+        FrameQuery::Image(image) => {
+            let grid = image_grid(image);
+            (grid, grid)
+        }
+        FrameQuery::Frame(frame) => (frame_grid(frame, &ramp, false), has_colors(frame).then(|| frame_grid(frame, &ramp, true)).flatten()),
+    };
+    let plain = plain.ok_or_else(|| Error::InvalidOptions("the query is empty".into()))?;
+    let fps = recorded_fps(dir).unwrap_or(DEFAULT_PREVIEW_FPS);
+
+    let mut matches = sequence.reader().enumerate().par_bridge().map(|(index, frame)| {
+        let frame = frame?;
+        let target = colored.as_ref().filter(|_| has_colors(&frame));
+        let similarity = frame_grid(&frame, &ramp, target.is_some()).map_or(0.0, |grid| similarity(target.unwrap_or(&plain), &grid));
+        Ok(FrameMatch {index, timestamp: Duration::from_secs_f64(index as f64 / fps as f64), similarity})
+    }).filter(|found: &Result<FrameMatch>| found.as_ref().map_or(true, |found| found.similarity >= threshold)).collect::<Result<Vec<_>>>()?;
+    matches.sort_by(|left, right| right.similarity.total_cmp(&left.similarity).then(left.index.cmp(&right.index)));
+    Ok(matches)
+}
+
+type Grid = [[f32; HASH_GRID]; HASH_GRID];
+
+fn image_grid(image: &DynamicImage) -> Option<Grid> {
+    let rgb = image.to_rgb8();
+    let lumas: Vec<f32> = rgb.pixels().map(|px| LuminanceWeights::REC709.luminance(px[0], px[1], px[2]) as f32 / 255.0).collect();
+    luminance_grid(&lumas, rgb.width() as usize, rgb.height() as usize)
+}
+
+fn has_colors(frame: &AsciiFrame) -> bool {
+    frame.rgb.len() == frame.width as usize * frame.height as usize * 3
+}
+
+/// Glyph densities of `frame`, weighted by the cell colors' luminance when `colored`
+fn frame_grid(frame: &AsciiFrame, ramp: &RampLookup, colored: bool) -> Option<Grid> {
+    let cell_bytes = CellBytes::of_text(&frame.text);
+    let glyphs = frame.text.lines().flat_map(|line| line.chars()).map(|ch| ramp.density(cell_bytes.byte(ch)));
+    let lumas: Vec<f32> = glyphs.enumerate().map(|(index, density)| {
+        if colored {density * LuminanceWeights::REC709.luminance(frame.rgb[index * 3], frame.rgb[index * 3 + 1], frame.rgb[index * 3 + 2]) as f32 / 255.0} else {density}
+    }).collect();
+    luminance_grid(&lumas, frame.width as usize, frame.height as usize)
+}
+
+/// Correlation of two grids clamped to 0.0..=1.0; flat grids, which have none, compare by their means.
+fn similarity(left: &Grid, right: &Grid) -> f32 {
+    let count = (HASH_GRID * HASH_GRID) as f32;
+    let mean = |grid: &Grid| grid.as_flattened().iter().sum::<f32>() / count;
+    let (left_mean, right_mean) = (mean(left), mean(right));
+    let (mut covariance, mut left_variance, mut right_variance) = (0.0, 0.0, 0.0);
+    for (l, r) in left.as_flattened().iter().zip(right.as_flattened()) {
+        let (l, r) = (l - left_mean, r - right_mean);
+        covariance += l * r;
+        left_variance += l * l;
+        right_variance += r * r;
+    }
+    if left_variance < 1e-6 || right_variance < 1e-6 {
+        return 1.0 - (left_mean - right_mean).abs();
+    }
+    (covariance / (left_variance * right_variance).sqrt()).clamp(0.0, 1.0)
+}
+
+fn recorded_ascii_chars(dir: &Path) -> Option<String> {
+    let details: toml::Table = fs::read_to_string(dir.join("details.toml")).ok()?.parse().ok()?;
+    details.get("ascii_chars")?.as_str().filter(|chars| !chars.is_empty() && chars.is_ascii()).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image_to_frame, ConversionOptions};
+
+    #[test]
+    fn test_find_frame_locates_images_and_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let images: Vec<DynamicImage> = (0..4u32).map(|index| DynamicImage::ImageRgb8(image::RgbImage::from_fn(160, 90, |x, y| {
+            let ball = (x as i32 - 20 - 40 * index as i32).pow(2) + (y as i32 - 45).pow(2) < 400;
+            image::Rgb([if ball {250} else {(y / 3) as u8}; 3])
+        }))).collect();
+        let options = ConversionOptions::default().with_columns(40).with_font_ratio(0.5);
+        let frames: Vec<AsciiFrame> = images.iter().map(|image| image_to_frame(image, &options).unwrap()).collect();
+        for (index, frame) in frames.iter().enumerate() {
+            fs::write(dir.path().join(format!("frame_{:04}.txt", index + 1)), &frame.text).unwrap();
+        }
+        fs::write(dir.path().join("details.toml"), "fps = 10\n").unwrap();
+
+        // A larger render of the third image still finds the third frame first
+        let still = images[2].resize_exact(320, 180, image::imageops::FilterType::Triangle);
+        let found = find_frame(dir.path(), &still, 0.5).unwrap();
+        assert_eq!((found[0].index, found[0].timestamp), (2, Duration::from_millis(200)));
+        assert!(found[0].similarity > 0.9 && found.iter().skip(1).all(|other| other.similarity < found[0].similarity), "{:?}", found);
+
+        let exact = find_frame(dir.path(), &frames[3], 0.99).unwrap();
+        assert_eq!(exact.len(), 1);
+        assert_eq!((exact[0].index, exact[0].similarity), (3, 1.0));
+        assert!(find_frame(dir.path(), &frames[3], 1.5).is_err());
+    }
+}
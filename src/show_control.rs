@@ -0,0 +1,377 @@
+//! Show control: frame, beat and scene events over OSC or MQTT, to sync lights to playback.
+//!
+//! Lighting desks, media servers and installation controllers follow a show through cues sent over
+//! the network. A [`ShowControl`] connects to one, over OSC (UDP, with the `osc` feature) or MQTT
+//! (an MQTT 3.1.1 broker over TCP, with the `mqtt` feature), and sends [`ShowEvent`]s:
+//!
+//! - `frame` every time the player shows a new frame
+//! - `beat` on every beat of [`ShowControlOptions::bpm`], counted from the playhead
+//! - `scene` when a frame differs from the one before in at least
+//!   [`ShowControlOptions::scene_threshold`] of its cells, and on the first frame
+//! - `progress` while a converter with [`AsciiConverter::with_show_control`](crate::AsciiConverter::with_show_control) converts
+//! - `finished` when playback ends
+//!
+//! OSC messages go to `/<topic>/<event>` with integer arguments (`progress` starts with the phase
+//! as a string). MQTT messages are published to `<topic>/<event>` at QoS 0 with the event as JSON,
+//! such as `{"event":"scene","number":2,"frame":118}`. The player sets the options with
+//! [`PlayOptions::with_show_control`](crate::player::PlayOptions::with_show_control); events that fail
+//! to send are dropped so a controller going away does not stop the show.
+//!
+//! ```no_run
+//! use cascii::player::{play_frames, PlayOptions};
+//! use cascii::show_control::{ShowControlOptions, ShowTarget};
+//!
+//! # fn main() -> cascii::Result<()> {
+//! let show = ShowControlOptions::new(ShowTarget::Osc("192.168.1.20:7000".into())).with_bpm(Some(128.0));
+//! play_frames("out/clip", None, &PlayOptions::default().with_show_control(Some(show)))?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "mqtt")]
+use std::io::{Read, Write};
+#[cfg(feature = "mqtt")]
+use std::net::TcpStream;
+#[cfg(feature = "osc")]
+use std::net::UdpSocket;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::error::{Context, Error, Result};
+use crate::frame::AsciiCell;
+use crate::{AsciiFrame, Progress, ProgressPhase};
+
+/// Share of changed cells that starts a new scene unless set otherwise
+pub const DEFAULT_SCENE_THRESHOLD: f32 = 0.5;
+/// Summed channel difference above which a cell's color counts as changed
+const COLOR_CHANGE: u32 = 96;
+
+/// Where show events go.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShowTarget {
+    /// OSC messages over UDP to `host:port`
+    #[cfg(feature = "osc")]
+    Osc(String),
+    /// MQTT messages through the broker at `host:port` (usually port 1883)
+    #[cfg(feature = "mqtt")]
+    Mqtt(String),
+}
+
+/// Where show events go and which ones are sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShowControlOptions {
+    pub target: ShowTarget,
+    /// First part of every OSC address and MQTT topic
+    pub topic: String,
+    /// Tempo to send `beat` events at; `None` sends none
+    pub bpm: Option<f64>,
+    /// Playhead time of the first beat
+    pub beat_offset: Duration,
+    /// Share of cells (0 to 1) that must change for a new `scene`; `None` sends only the first
+    pub scene_threshold: Option<f32>,
+}
+
+impl ShowControlOptions {
+    pub fn new(target: ShowTarget) -> Self {
+        Self {target, topic: "cascii".to_string(), bpm: None, beat_offset: Duration::ZERO, scene_threshold: Some(DEFAULT_SCENE_THRESHOLD)}
+    }
+
+    pub fn with_topic(mut self, topic: impl Into<String>) -> Self {
+        self.topic = topic.into();
+        self
+    }
+
+    pub fn with_bpm(mut self, bpm: Option<f64>) -> Self {
+        self.bpm = bpm;
+        self
+    }
+
+    pub fn with_beat_offset(mut self, beat_offset: Duration) -> Self {
+        self.beat_offset = beat_offset;
+        self
+    }
+
+    pub fn with_scene_threshold(mut self, scene_threshold: Option<f32>) -> Self {
+        self.scene_threshold = scene_threshold;
+        self
+    }
+}
+
+/// An event sent to the show controller.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ShowEvent {
+    /// Frame `index` (from 0) of `total` is on screen
+    Frame {index: usize, total: usize},
+    /// Beat `number` (from 0) started
+    Beat {number: u64},
+    /// Scene `number` (from 1) starts at frame `frame`
+    Scene {number: usize, frame: usize},
+    /// `completed` of `total` items of a conversion phase are done
+    Progress {phase: &'static str, completed: usize, total: usize},
+    /// Playback ended after showing `frames` frames
+    Finished {frames: usize},
+}
+
+impl ShowEvent {
+    /// The event's name: the last part of its OSC address and MQTT topic
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Frame {..} => "frame",
+            Self::Beat {..} => "beat",
+            Self::Scene {..} => "scene",
+            Self::Progress {..} => "progress",
+            Self::Finished {..} => "finished",
+        }
+    }
+
+    /// A `progress` event for a conversion update
+    pub fn progress(progress: &Progress) -> Self {
+        let phase = match progress.phase {
+            ProgressPhase::ExtractingFrames => "extracting_frames",
+            ProgressPhase::ExtractingAudio => "extracting_audio",
+            ProgressPhase::ConvertingFrames => "converting_frames",
+            ProgressPhase::RenderingVideo => "rendering_video",
+            ProgressPhase::PreviewReady => "preview_ready",
+            ProgressPhase::FullQualityReady => "full_quality_ready",
+            ProgressPhase::Complete => "complete",
+        };
+        Self::Progress {phase, completed: progress.completed, total: progress.total}
+    }
+
+    /// The event as an OSC message to `/<topic>/<name>`: int32 arguments, the phase of `progress` a string
+    pub fn to_osc(&self, topic: &str) -> Vec<u8> {
+        let (phase, ints): (Option<&str>, Vec<i64>) = match *self {
+            Self::Frame {index, total} => (None, vec![index as i64, total as i64]),
+            Self::Beat {number} => (None, vec![number as i64]),
+            Self::Scene {number, frame} => (None, vec![number as i64, frame as i64]),
+            Self::Progress {phase, completed, total} => (Some(phase), vec![completed as i64, total as i64]),
+            Self::Finished {frames} => (None, vec![frames as i64]),
+        };
+        let mut packet = Vec::new();
+        push_osc_string(&mut packet, &format!("/{}/{}", topic.trim_matches('/'), self.name()));
+        push_osc_string(&mut packet, &format!(",{}{}", if phase.is_some() {"s"} else {""}, "i".repeat(ints.len())));
+        if let Some(phase) = phase {
+            push_osc_string(&mut packet, phase);
+        }
+        for value in ints {
+            packet.extend((value.clamp(i32::MIN as i64, i32::MAX as i64) as i32).to_be_bytes());
+        }
+        packet
+    }
+
+    /// The event as JSON, the payload of MQTT messages
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("show events serialize")
+    }
+}
+
+/// An OSC string: its bytes, then 1 to 4 NULs up to a multiple of 4 bytes
+fn push_osc_string(packet: &mut Vec<u8>, text: &str) {
+    packet.extend(text.as_bytes());
+    packet.extend(std::iter::repeat_n(0, 4 - text.len() % 4));
+}
+
+/// Turns the frames a player shows into `frame`, `beat` and `scene` events.
+pub struct ShowTracker {
+    bpm: Option<f64>,
+    beat_offset: Duration,
+    scene_threshold: Option<f32>,
+    last_frame: Option<usize>,
+    last_beat: Option<u64>,
+    scenes: usize,
+    cells: Vec<AsciiCell>,
+}
+
+impl ShowTracker {
+    pub fn new(options: &ShowControlOptions) -> Self {
+        Self {bpm: options.bpm.filter(|&bpm| bpm > 0.0), beat_offset: options.beat_offset, scene_threshold: options.scene_threshold, last_frame: None, last_beat: None, scenes: 0, cells: Vec::new()}
+    }
+
+    /// The events of frame `index` of `total` being shown at playhead `time`; a frame shown
+    /// again gives no `frame` event, and only a change of beat gives a `beat`
+    pub fn events(&mut self, index: usize, total: usize, time: Duration, frame: &AsciiFrame) -> Vec<ShowEvent> {
+        let mut events = Vec::new();
+        if self.last_frame != Some(index) {
+            let cells: Vec<AsciiCell> = frame.cells().collect();
+            let changed = if self.cells.len() == cells.len() {self.cells.iter().zip(&cells).filter(|(before, after)| cell_changed(before, after)).count() as f32 / cells.len().max(1) as f32} else {1.0};
+            if self.scenes == 0 || self.scene_threshold.is_some_and(|threshold| changed >= threshold) {
+                self.scenes += 1;
+                events.push(ShowEvent::Scene {number: self.scenes, frame: index});
+            }
+            events.insert(0, ShowEvent::Frame {index, total});
+            (self.last_frame, self.cells) = (Some(index), cells);
+        }
+        if let Some(bpm) = self.bpm {
+            let beat = time.checked_sub(self.beat_offset).map(|since| (since.as_secs_f64() * bpm / 60.0) as u64);
+            if beat.is_some() && beat != self.last_beat {
+                events.push(ShowEvent::Beat {number: beat.unwrap_or_default()});
+            }
+            self.last_beat = beat;
+        }
+        events
+    }
+}
+
+fn cell_changed(before: &AsciiCell, after: &AsciiCell) -> bool {
+    let distance = |left: Option<[u8; 3]>, right: Option<[u8; 3]>| left.unwrap_or_default().iter().zip(right.unwrap_or_default()).map(|(&a, b)| a.abs_diff(b) as u32).sum::<u32>();
+    before.ch != after.ch || distance(before.fg, after.fg) > COLOR_CHANGE || distance(before.bg, after.bg) > COLOR_CHANGE
+}
+
+/// The connection events travel over
+enum Transport {
+    #[cfg(feature = "osc")]
+    Osc {socket: UdpSocket, target: SocketAddr},
+    #[cfg(feature = "mqtt")]
+    Mqtt(Mutex<TcpStream>),
+}
+
+/// A connection to a show controller, sending [`ShowEvent`]s.
+pub struct ShowControl {
+    transport: Transport,
+    topic: String,
+    tracker: Mutex<ShowTracker>,
+}
+
+impl ShowControl {
+    /// Open the connection `options` describe: a UDP socket for OSC, or a session with the MQTT broker
+    pub fn connect(options: &ShowControlOptions) -> Result<Self> {
+        let resolve = |address: &str| -> Result<SocketAddr> {
+            address.to_socket_addrs().with_context(|| format!("resolving {}", address))?.next().ok_or_else(|| Error::InvalidOptions(format!("{} resolves to no address", address)))
+        };
+        let transport = match &options.target {
+            #[cfg(feature = "osc")]
+            ShowTarget::Osc(address) => {
+                let target = resolve(address)?;
+                let socket = std::net::UdpSocket::bind(if target.is_ipv4() {"0.0.0.0:0"} else {"[::]:0"}).context("opening a UDP socket")?;
+                Transport::Osc {socket, target}
+            }
+            #[cfg(feature = "mqtt")]
+            ShowTarget::Mqtt(address) => Transport::Mqtt(Mutex::new(mqtt_connect(resolve(address)?).with_context(|| format!("connecting to the MQTT broker {}", address))?)),
+        };
+        Ok(Self {transport, topic: options.topic.trim_matches('/').to_string(), tracker: Mutex::new(ShowTracker::new(options))})
+    }
+
+    /// Send `event`
+    pub fn emit(&self, event: &ShowEvent) -> Result<()> {
+        match &self.transport {
+            #[cfg(feature = "osc")]
+            Transport::Osc {socket, target} => {
+                socket.send_to(&event.to_osc(&self.topic), target).with_context(|| format!("sending OSC to {}", target))?;
+            }
+            #[cfg(feature = "mqtt")]
+            Transport::Mqtt(stream) => {
+                let packet = mqtt_publish(&format!("{}/{}", self.topic, event.name()), event.to_json().as_bytes());
+                stream.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).write_all(&packet).context("publishing to the MQTT broker")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the events of frame `index` of `total` being shown at playhead `time` (see [`ShowTracker::events`])
+    pub fn frame(&self, index: usize, total: usize, time: Duration, frame: &AsciiFrame) -> Result<()> {
+        let events = self.tracker.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).events(index, total, time, frame);
+        events.iter().try_for_each(|event| self.emit(event))
+    }
+
+    /// Send a `progress` event for a conversion update, dropping it if it fails to send
+    pub fn observe(&self, progress: &Progress) {
+        let _ = self.emit(&ShowEvent::progress(progress));
+    }
+}
+
+impl std::fmt::Debug for ShowControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShowControl").field("topic", &self.topic).finish_non_exhaustive()
+    }
+}
+
+/// An MQTT 3.1.1 session with a clean start and no keep-alive, once the broker accepts it
+#[cfg(feature = "mqtt")]
+fn mqtt_connect(broker: SocketAddr) -> Result<TcpStream> {
+    let mut stream = TcpStream::connect_timeout(&broker, Duration::from_secs(5))?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let client_id = format!("cascii-{}", std::process::id());
+    // Protocol name, level 4, the clean session flag and a keep-alive of 0 (none), then the client id
+    let mut body = vec![0, 4, b'M', b'Q', b'T', b'T', 4, 0x02, 0, 0];
+    body.extend((client_id.len() as u16).to_be_bytes());
+    body.extend(client_id.as_bytes());
+    stream.write_all(&mqtt_packet(0x10, &body))?;
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack).context("waiting for the broker to accept the connection")?;
+    match connack {
+        [0x20, 2, _, 0] => Ok(stream),
+        [0x20, 2, _, code] => Err(Error::Other(format!("the MQTT broker refused the connection (code {})", code))),
+        _ => Err(Error::Other("the MQTT broker answered with something other than CONNACK".into())),
+    }
+}
+
+/// A QoS 0 PUBLISH of `payload` to `topic`
+#[cfg(feature = "mqtt")]
+fn mqtt_publish(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(2 + topic.len() + payload.len());
+    body.extend((topic.len() as u16).to_be_bytes());
+    body.extend(topic.as_bytes());
+    body.extend(payload);
+    mqtt_packet(0x30, &body)
+}
+
+/// An MQTT control packet: its type byte, the body length in 7-bit groups, then the body
+#[cfg(feature = "mqtt")]
+fn mqtt_packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind];
+    let mut length = body.len();
+    loop {
+        let byte = (length % 128) as u8;
+        length /= 128;
+        packet.push(if length > 0 {byte | 0x80} else {byte});
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend(body);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn show_events_follow_frames_beats_and_scenes() -> Result<()> {
+        let frame = |text: &str| AsciiFrame {text: format!("{}\n", text), width: 4, height: 1, rgb: vec![200; 12], bg_rgb: Vec::new()};
+        #[cfg(feature = "osc")]
+        let target = ShowTarget::Osc("127.0.0.1:9".into());
+        #[cfg(not(feature = "osc"))]
+        let target = ShowTarget::Mqtt("127.0.0.1:1883".into());
+        let options = ShowControlOptions::new(target).with_bpm(Some(120.0)).with_beat_offset(Duration::from_millis(100));
+        let mut tracker = ShowTracker::new(&options);
+        assert_eq!(tracker.events(0, 9, Duration::ZERO, &frame("abcd")), [ShowEvent::Frame {index: 0, total: 9}, ShowEvent::Scene {number: 1, frame: 0}]);
+        assert_eq!(tracker.events(1, 9, Duration::from_millis(200), &frame("abcx")), [ShowEvent::Frame {index: 1, total: 9}, ShowEvent::Beat {number: 0}]);
+        assert_eq!(tracker.events(1, 9, Duration::from_millis(400), &frame("abcx")), []);
+        assert_eq!(tracker.events(2, 9, Duration::from_millis(600), &frame("wxyx")), [ShowEvent::Frame {index: 2, total: 9}, ShowEvent::Scene {number: 2, frame: 2}, ShowEvent::Beat {number: 1}]);
+
+        assert_eq!(ShowEvent::Scene {number: 2, frame: 118}.to_json(), r#"{"event":"scene","number":2,"frame":118}"#);
+        assert_eq!(ShowEvent::Beat {number: 3}.to_osc("/cascii/"), b"/cascii/beat\0\0\0\0,i\0\0\0\0\0\x03");
+        let progress = ShowEvent::progress(&Progress {phase: ProgressPhase::ConvertingFrames, completed: 1, total: 2, percentage: 50.0, message: String::new(), pass: None}).to_osc("c");
+        assert_eq!(progress, b"/c/progress\0,sii\0\0\0\0converting_frames\0\0\0\0\0\0\x01\0\0\0\x02");
+
+        #[cfg(feature = "osc")]
+        {
+            let receiver = std::net::UdpSocket::bind("127.0.0.1:0")?;
+            receiver.set_read_timeout(Some(Duration::from_secs(5)))?;
+            let show = ShowControl::connect(&ShowControlOptions::new(ShowTarget::Osc(receiver.local_addr()?.to_string())))?;
+            show.frame(4, 9, Duration::ZERO, &frame("abcd"))?;
+            let mut buffer = [0u8; 64];
+            let length = receiver.recv(&mut buffer)?;
+            assert_eq!(&buffer[..length], ShowEvent::Frame {index: 4, total: 9}.to_osc("cascii").as_slice());
+        }
+        #[cfg(feature = "mqtt")]
+        assert_eq!(mqtt_publish("a/b", b"{}"), [0x30, 7, 0, 3, b'a', b'/', b'b', b'{', b'}']);
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+//! Sprite sheets: the frames of an animation laid out in a grid on one image.
+//!
+//! [`slice_tiles`] cuts a sheet into its tiles, left to right and top to bottom, which is the order
+//! [`AsciiConverter::convert_spritesheet`](crate::AsciiConverter::convert_spritesheet) numbers the
+//! converted frames in. Pixels past the last whole tile on the right or bottom edge are ignored, and
+//! fully transparent tiles at the end of the sheet (the empty slots of a partly filled last row) are
+//! dropped; transparent tiles between two frames are kept as blank frames.
+//!
+//! ```
+//! use image::{DynamicImage, RgbaImage};
+//!
+//! let sheet = DynamicImage::ImageRgba8(RgbaImage::from_pixel(64, 32, image::Rgba([255, 0, 0, 255])));
+//! let tiles = cascii::spritesheet::slice_tiles(&sheet, 16, 16).unwrap();
+//! assert_eq!(tiles.len(), 8);
+//! ```
+
+use crate::error::{Error, Result};
+use image::DynamicImage;
+
+/// The `tile_width`×`tile_height` tiles of `sheet` in reading order, without the trailing empty ones
+pub fn slice_tiles(sheet: &DynamicImage, tile_width: u32, tile_height: u32) -> Result<Vec<DynamicImage>> {
+    if tile_width == 0 || tile_height == 0 {
+        return Err(Error::InvalidOptions(format!("sprite tiles must be at least 1x1 pixels, got {}x{}", tile_width, tile_height)));
+    }
+    let (columns, rows) = (sheet.width() / tile_width, sheet.height() / tile_height);
+    if columns == 0 || rows == 0 {
+        return Err(Error::InvalidOptions(format!("{}x{} tiles don't fit on a {}x{} sprite sheet", tile_width, tile_height, sheet.width(), sheet.height())));
+    }
+    let mut tiles: Vec<DynamicImage> = (0..rows).flat_map(|row| (0..columns).map(move |column| (column, row))).map(|(column, row)| sheet.crop_imm(column * tile_width, row * tile_height, tile_width, tile_height)).collect();
+    while tiles.len() > 1 && tiles.last().is_some_and(is_empty) {
+        tiles.pop();
+    }
+    Ok(tiles)
+}
+
+/// Whether every pixel of `tile` is fully transparent
+fn is_empty(tile: &DynamicImage) -> bool {
+    tile.color().has_alpha() && tile.to_rgba8().pixels().all(|pixel| pixel[3] == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn slice_tiles_reads_rows_and_drops_trailing_empty_tiles() {
+        // A 3x2 grid of 4x4 tiles plus a 2 pixel margin; tile 2 is empty, and so are the last two
+        let mut sheet = RgbaImage::new(14, 10);
+        for (index, shade) in [(0, 10), (2, 30), (3, 40)] {
+            for y in 0..4 {
+                for x in 0..4 {
+                    sheet.put_pixel((index % 3) * 4 + x, (index / 3) * 4 + y, Rgba([shade, shade, shade, 255]));
+                }
+            }
+        }
+        let tiles = slice_tiles(&DynamicImage::ImageRgba8(sheet), 4, 4).unwrap();
+        let shades: Vec<u8> = tiles.iter().map(|tile| tile.to_rgba8().get_pixel(0, 0)[0]).collect();
+        assert_eq!(shades, [10, 0, 30, 40]);
+        assert!(tiles.iter().all(|tile| tile.width() == 4 && tile.height() == 4));
+
+        assert!(slice_tiles(&DynamicImage::new_rgb8(8, 8), 0, 4).is_err());
+        assert!(slice_tiles(&DynamicImage::new_rgb8(8, 8), 16, 4).is_err());
+        assert_eq!(slice_tiles(&DynamicImage::new_rgba8(8, 8), 4, 4).unwrap().len(), 1);
+    }
+}
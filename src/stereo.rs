@@ -0,0 +1,83 @@
+//! Red/cyan anaglyphs from side-by-side stereo input.
+//!
+//! A side-by-side frame holds the left eye's view in its left half and the right eye's in its right
+//! half. Combining them into one half-width anaglyph before conversion gives every cell a red
+//! component seen by the left eye and a cyan one seen by the right, so the ASCII output (and any
+//! video rendered from it) plays in 3D through red/cyan glasses.
+
+use image::{Rgb, RgbImage};
+
+/// How the two views are mixed into red and cyan.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Anaglyph {
+    /// Red from the left view, green and blue from the right; keeps the most color
+    #[default]
+    Color,
+    /// Each view's luminance on its own channel; no color, but the least ghosting
+    Gray,
+    /// Dubois least-squares projection; close to the source colors with less retinal rivalry than `Color`
+    Dubois,
+}
+
+impl Anaglyph {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Color => "color",
+            Self::Gray => "gray",
+            Self::Dubois => "dubois",
+        }
+    }
+
+    /// Combine the halves of a side-by-side frame (left view first) into a half-width anaglyph.
+    /// With an odd width the middle column is dropped; a one pixel wide frame is its own pair.
+    pub fn combine(self, side_by_side: &RgbImage) -> RgbImage {
+        let (width, height) = side_by_side.dimensions();
+        let half = (width / 2).max(1).min(width);
+        let right_start = width - half;
+        RgbImage::from_fn(half, height, |x, y| {
+            let left = side_by_side.get_pixel(x, y).0;
+            let right = side_by_side.get_pixel(right_start + x, y).0;
+            Rgb(self.mix(left, right))
+        })
+    }
+
+    fn mix(self, left: [u8; 3], right: [u8; 3]) -> [u8; 3] {
+        match self {
+            Self::Color => [left[0], right[1], right[2]],
+            Self::Gray => {
+                let right = luma(right);
+                [luma(left), right, right]
+            }
+            Self::Dubois => {
+                const LEFT: [[f32; 3]; 3] = [[0.456, 0.500, 0.176], [-0.040, -0.038, -0.016], [-0.015, -0.021, -0.005]];
+                const RIGHT: [[f32; 3]; 3] = [[-0.043, -0.088, -0.002], [0.378, 0.734, -0.018], [-0.072, -0.113, 1.226]];
+                let dot = |row: [f32; 3], px: [u8; 3]| row[0] * px[0] as f32 + row[1] * px[1] as f32 + row[2] * px[2] as f32;
+                std::array::from_fn(|channel| (dot(LEFT[channel], left) + dot(RIGHT[channel], right)).round().clamp(0.0, 255.0) as u8)
+            }
+        }
+    }
+}
+
+fn luma([r, g, b]: [u8; 3]) -> u8 {
+    ((r as u32 * 2126 + g as u32 * 7152 + b as u32 * 722 + 5000) / 10000) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anaglyph_takes_red_from_left_and_cyan_from_right() {
+        let side_by_side = RgbImage::from_fn(5, 1, |x, _| if x < 2 {Rgb([200, 10, 20])} else {Rgb([30, 100, 150])});
+
+        let color = Anaglyph::Color.combine(&side_by_side);
+        assert_eq!(color.dimensions(), (2, 1));
+        assert_eq!(color.get_pixel(0, 0).0, [200, 100, 150]);
+
+        let gray = Anaglyph::Gray.combine(&side_by_side);
+        assert_eq!(gray.get_pixel(1, 0).0, [luma([200, 10, 20]), luma([30, 100, 150]), luma([30, 100, 150])]);
+
+        let dubois = Anaglyph::Dubois.combine(&RgbImage::from_pixel(2, 1, Rgb([255, 255, 255])));
+        assert!(dubois.get_pixel(0, 0).0.iter().all(|&channel| channel > 200));
+    }
+}
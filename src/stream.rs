@@ -0,0 +1,187 @@
+//! Live ASCII from a webcam or capture device.
+//!
+//! [`AsciiConverter::stream_device`](crate::AsciiConverter::stream_device) runs ffmpeg on a capture
+//! device (`v4l2` on Linux, `avfoundation` on macOS, `dshow` on Windows), reads the raw RGB frames
+//! it writes to stdout, converts each one and hands it to a [`StreamSink`]: a closure, or the
+//! sending half of a channel. Frames are read on their own thread, and when conversion falls behind
+//! the camera the frames it had no time for are dropped, so the picture stays live instead of lagging.
+//!
+//! ```no_run
+//! use cascii::stream::StreamOptions;
+//! use cascii::{AsciiConverter, ConversionOptions};
+//!
+//! # fn main() -> cascii::Result<()> {
+//! let options = ConversionOptions::default().with_columns(100);
+//! let (sender, receiver) = std::sync::mpsc::sync_channel(2);
+//! std::thread::spawn(move || AsciiConverter::new().stream_device("/dev/video0", &StreamOptions::default(), &options, sender));
+//! for frame in receiver {
+//!     print!("\x1b[H{}", frame.text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Error, Result};
+use image::{DynamicImage, RgbImage};
+use std::io::{ErrorKind, Read};
+use std::sync::mpsc::{sync_channel, Sender, SyncSender, TrySendError};
+
+use crate::{AsciiFrame, CancelToken};
+
+/// How [`AsciiConverter::stream_device`](crate::AsciiConverter::stream_device) captures from a device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamOptions {
+    /// ffmpeg input format of the device: `v4l2`, `avfoundation` or `dshow` by platform. `lavfi`
+    /// takes a test source such as `testsrc` as the device, handy without a camera.
+    pub input_format: String,
+    /// Frame rate to capture at; it must be one the device supports
+    pub fps: u32,
+    /// Capture size in pixels, which frames are also scaled to before conversion
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        let input_format = if cfg!(target_os = "macos") {"avfoundation"} else if cfg!(windows) {"dshow"} else {"v4l2"};
+        Self {input_format: input_format.to_string(), fps: 30, width: 640, height: 480}
+    }
+}
+
+impl StreamOptions {
+    pub fn with_input_format(mut self, input_format: impl Into<String>) -> Self {
+        self.input_format = input_format.into();
+        self
+    }
+
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        (self.width, self.height) = (width, height);
+        self
+    }
+}
+
+/// Where streamed frames go.
+pub trait StreamSink {
+    /// Take the next frame; `false` stops the stream
+    fn deliver(&mut self, frame: AsciiFrame) -> bool;
+}
+
+impl<F: FnMut(AsciiFrame) -> bool> StreamSink for F {
+    fn deliver(&mut self, frame: AsciiFrame) -> bool {
+        self(frame)
+    }
+}
+
+/// Streams until the receiver is dropped
+impl StreamSink for Sender<AsciiFrame> {
+    fn deliver(&mut self, frame: AsciiFrame) -> bool {
+        self.send(frame).is_ok()
+    }
+}
+
+/// Streams until the receiver is dropped; a full channel holds up conversion, not capture
+impl StreamSink for SyncSender<AsciiFrame> {
+    fn deliver(&mut self, frame: AsciiFrame) -> bool {
+        self.send(frame).is_ok()
+    }
+}
+
+/// How a stream went.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// Frames converted and delivered to the sink
+    pub delivered: usize,
+    /// Frames captured while the previous one was still converting, and skipped
+    pub dropped: usize,
+}
+
+/// ffmpeg arguments capturing `device` as raw RGB frames of the options' size on stdout
+pub(crate) fn capture_args(device: &str, options: &StreamOptions) -> Result<Vec<String>> {
+    if options.fps == 0 || options.width == 0 || options.height == 0 {
+        return Err(Error::InvalidOptions(format!("stream fps and size must be positive, got {} fps at {}x{}", options.fps, options.width, options.height)));
+    }
+    let mut args: Vec<String> = vec!["-loglevel".into(), "error".into(), "-nostdin".into(), "-f".into(), options.input_format.clone()];
+    // The lavfi demuxer takes the rate and size inside the source description instead
+    if options.input_format != "lavfi" {
+        args.extend(["-framerate".into(), options.fps.to_string(), "-video_size".into(), format!("{}x{}", options.width, options.height)]);
+    }
+    args.extend(["-i".into(), device.to_string(), "-an".into(), "-vf".into(), format!("scale={}:{}", options.width, options.height), "-pix_fmt".into(), "rgb24".into(), "-f".into(), "rawvideo".into(), "pipe:1".into()]);
+    Ok(args)
+}
+
+/// Split `source` into `width`×`height` RGB frames, convert them with `convert` and deliver them to
+/// `sink` until the source ends, the sink refuses a frame or `cancel` fires. Frames that arrive
+/// while one is converting replace each other, so only the newest waits.
+pub(crate) fn stream_frames(mut source: impl Read + Send, width: u32, height: u32, convert: impl Fn(&DynamicImage) -> Result<AsciiFrame>, sink: &mut impl StreamSink, cancel: Option<&CancelToken>) -> Result<StreamStats> {
+    let frame_len = width as usize * height as usize * 3;
+    let (sender, receiver) = sync_channel::<RgbImage>(1);
+    std::thread::scope(|scope| {
+        let reader = scope.spawn(move || -> Result<usize> {
+            let mut dropped = 0;
+            loop {
+                let mut buffer = vec![0u8; frame_len];
+                match source.read_exact(&mut buffer) {
+                    Ok(()) => {}
+                    Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(dropped),
+                    Err(err) => return Err(Error::from(err).context("reading captured frames")),
+                }
+                let image = RgbImage::from_raw(width, height, buffer).expect("buffer holds one frame");
+                match sender.try_send(image) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => dropped += 1,
+                    Err(TrySendError::Disconnected(_)) => return Ok(dropped),
+                }
+            }
+        });
+
+        let mut delivered = 0;
+        for image in receiver {
+            if cancel.is_some_and(CancelToken::is_cancelled) {
+                break;
+            }
+            if !sink.deliver(convert(&DynamicImage::ImageRgb8(image))?) {
+                break;
+            }
+            delivered += 1;
+        }
+        let dropped = reader.join().map_err(|_| Error::Other("the frame reader panicked".into()))??;
+        Ok(StreamStats {delivered, dropped})
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image_to_frame, ConversionOptions};
+
+    #[test]
+    fn test_stream_frames_converts_until_the_sink_stops() -> Result<()> {
+        let args = capture_args("/dev/video0", &StreamOptions::default().with_input_format("v4l2").with_fps(15).with_size(320, 240))?;
+        assert_eq!(args.join(" "), "-loglevel error -nostdin -f v4l2 -framerate 15 -video_size 320x240 -i /dev/video0 -an -vf scale=320:240 -pix_fmt rgb24 -f rawvideo pipe:1");
+        assert!(!capture_args("testsrc", &StreamOptions::default().with_input_format("lavfi"))?.contains(&"-framerate".to_string()));
+        assert!(capture_args("/dev/video0", &StreamOptions::default().with_fps(0)).is_err());
+
+        // Three white 4x2 frames, then half a frame the stream ends inside
+        let raw = vec![255u8; 4 * 2 * 3 * 3 + 12];
+        let options = ConversionOptions::default().with_columns(4).with_font_ratio(1.0);
+        let mut frames = Vec::new();
+        let stats = stream_frames(raw.as_slice(), 4, 2, |image| image_to_frame(image, &options), &mut |frame| {
+            frames.push(frame);
+            true
+        }, None)?;
+        assert_eq!(stats.delivered + stats.dropped, 3);
+        assert_eq!(frames.len(), stats.delivered);
+        assert!(frames[0].text.starts_with("$$$$"), "{:?}", frames[0].text);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        drop(receiver);
+        let stats = stream_frames(raw.as_slice(), 4, 2, |image| image_to_frame(image, &options), &mut sender.clone(), None)?;
+        assert_eq!(stats.delivered, 0);
+        Ok(())
+    }
+}
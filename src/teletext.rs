@@ -0,0 +1,312 @@
+//! Teletext level 1 pages: `.tti` page files and `.t42` packet streams.
+//!
+//! A teletext page is 24 rows of 40 cells in 8 colors. Besides text, a cell can hold a mosaic: 2×3
+//! blocks, the shape of [`CharMode::Sextant`](crate::CharMode::Sextant) cells, so a frame converted
+//! with `--sextant --columns 40` maps onto a page cell for cell. [`encode_teletext`] quantizes every
+//! cell to the teletext colors, turns sextants, quadrant and half blocks into mosaics, and keeps
+//! printable ASCII as text; wider or taller frames are cropped to the page.
+//!
+//! Colors and the switch between text and mosaics only change through control codes, each taking a
+//! cell of its own that shows as a space. Codes go into the blank cells before a change where there
+//! are some, and over the frame's own cells otherwise. Level 1 has no black foreground, so black
+//! mosaics are drawn inverted on a black background and black text is dropped.
+//!
+//! ```
+//! use cascii::teletext::{encode_teletext, page_rows, TeletextOptions};
+//! use cascii::AsciiFrame;
+//!
+//! // A red mosaic with its top row lit, after a blank cell that takes the color code
+//! let frame = AsciiFrame {text: " \u{1fb02}\n".into(), width: 2, height: 1, rgb: vec![0, 0, 0, 255, 0, 0], bg_rgb: Vec::new()};
+//! assert_eq!(&page_rows(&frame)[0][..3], &[0x11, 0x23, 0x20]);
+//! let tti = encode_teletext(&frame, &TeletextOptions::default()).unwrap();
+//! assert!(String::from_utf8_lossy(&tti).contains("PN,10000\r\n"));
+//! ```
+
+use crate::error::{Error, Result};
+use crate::frame::{sextant_mask, AsciiFrame, QUADRANT_BLOCKS};
+
+/// Cells in a page row
+pub const PAGE_COLUMNS: usize = 40;
+/// Rows of a page below the header row
+pub const PAGE_ROWS: usize = 24;
+
+/// The 8 teletext colors, in color code order
+pub const TELETEXT_PALETTE: [[u8; 3]; 8] = [[0, 0, 0], [255, 0, 0], [0, 255, 0], [255, 255, 0], [0, 0, 255], [255, 0, 255], [0, 255, 255], [255, 255, 255]];
+
+/// Mosaic color codes are the alphanumeric ones plus this
+const MOSAIC_CODES: u8 = 0x10;
+const BLACK_BACKGROUND: u8 = 0x1C;
+const NEW_BACKGROUND: u8 = 0x1D;
+const WHITE: u8 = 7;
+
+/// Hamming 8/4 codes of the nibbles 0 to 15, protecting the addresses and page header of `.t42` packets
+const HAMMING_8_4: [u8; 16] = [0x15, 0x02, 0x49, 0x5E, 0x64, 0x73, 0x38, 0x2F, 0xD0, 0xC7, 0x8C, 0x9B, 0xA1, 0xB6, 0xFD, 0xEA];
+
+/// Which teletext file to write.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TeletextFormat {
+    /// The MRG `.tti` page file of teletext editors and inserters such as wxTED and vbit2
+    #[default]
+    Tti,
+    /// `.t42`: the 42 byte packets of the broadcast signal, a header and the 24 rows
+    T42,
+}
+
+impl TeletextFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Tti => "tti",
+            Self::T42 => "t42",
+        }
+    }
+}
+
+/// How [`encode_teletext`] writes a page.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TeletextOptions {
+    pub format: TeletextFormat,
+    /// Page number as three hex digits, magazine first: `0x100` is page 100
+    pub page: u16,
+    /// Description line of a `.tti` file, and the header row text of a `.t42` page
+    pub description: String,
+}
+
+impl Default for TeletextOptions {
+    fn default() -> Self {
+        Self {format: TeletextFormat::Tti, page: 0x100, description: String::new()}
+    }
+}
+
+impl TeletextOptions {
+    pub fn with_format(mut self, format: TeletextFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_page(mut self, page: u16) -> Self {
+        self.page = page;
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+}
+
+/// The index in [`TELETEXT_PALETTE`] of the color nearest to `rgb`
+pub fn nearest_teletext_color(rgb: [u8; 3]) -> u8 {
+    let distance = |candidate: &[u8; 3]| candidate.iter().zip(rgb).map(|(&a, b)| (a as i32 - b as i32).pow(2)).sum::<i32>();
+    (0..8).min_by_key(|&index| distance(&TELETEXT_PALETTE[index])).unwrap_or(0) as u8
+}
+
+/// What a page cell has to show
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cell {
+    /// Nothing but its background, whatever the foreground and mode
+    Blank {bg: u8},
+    /// A mosaic of the sextants in `mask`
+    Mosaic {mask: u8, fg: u8, bg: u8},
+    /// A 7-bit character
+    Text {byte: u8, fg: u8, bg: u8},
+}
+
+impl Cell {
+    fn of(ch: char, fg: u8, bg: u8) -> Self {
+        if let Some(mask) = mosaic_mask(ch).filter(|&mask| mask != 0) {
+            // Without a black foreground, black mosaics become the background showing through an inverted one
+            let (mask, fg, bg) = if fg == 0 {(mask ^ 0x3F, bg, 0)} else {(mask, fg, bg)};
+            if mask == 0 || fg == bg {Self::Blank {bg: if mask == 0 {bg} else {fg}}} else {Self::Mosaic {mask, fg, bg}}
+        } else if ch.is_ascii_graphic() && fg != 0 && fg != bg {
+            Self::Text {byte: ch as u8, fg, bg}
+        } else {
+            Self::Blank {bg}
+        }
+    }
+
+    fn bg(self) -> u8 {
+        match self {
+            Self::Blank {bg} | Self::Mosaic {bg, ..} | Self::Text {bg, ..} => bg,
+        }
+    }
+
+    /// The mode and foreground the cell must be drawn in; `None` for blanks. Capital letters show
+    /// as text in mosaic mode too, so they keep the `current` mode.
+    fn needs(self, current: bool) -> Option<(bool, u8)> {
+        match self {
+            Self::Blank {..} => None,
+            Self::Mosaic {fg, ..} => Some((true, fg)),
+            Self::Text {byte: 0x40..=0x5F, fg, ..} => Some((current, fg)),
+            Self::Text {fg, ..} => Some((false, fg)),
+        }
+    }
+
+    /// The page byte: mosaic bytes are 0x20 plus the mask, its bottom right sextant at 0x40
+    fn byte(self) -> u8 {
+        match self {
+            Self::Blank {..} => b' ',
+            Self::Mosaic {mask, ..} => 0x20 | (mask & 0x1F) | (mask & 0x20) << 1,
+            Self::Text {byte, ..} => byte,
+        }
+    }
+}
+
+/// The sextants `ch` fills: sextants and `▌▐█` as they are, quadrant blocks with the middle row
+/// lit on each side where either of its quadrants is
+fn mosaic_mask(ch: char) -> Option<u8> {
+    sextant_mask(ch).or_else(|| {
+        let quadrants = QUADRANT_BLOCKS.iter().position(|&block| block == ch)? as u8;
+        let (top, bottom) = (quadrants & 0b11, quadrants >> 2);
+        Some(top | (top | bottom) << 2 | bottom << 4)
+    })
+}
+
+/// The 24 rows of 40 page bytes `frame` becomes, control codes included
+pub fn page_rows(frame: &AsciiFrame) -> [[u8; PAGE_COLUMNS]; PAGE_ROWS] {
+    let mut rows = [[b' '; PAGE_COLUMNS]; PAGE_ROWS];
+    for (row, bytes) in rows.iter_mut().enumerate().take(frame.height as usize) {
+        let cells: Vec<Cell> = (0..PAGE_COLUMNS as u32).map(|column| match frame.cell(column, row as u32) {
+            Some(cell) => Cell::of(cell.ch, cell.fg.map_or(WHITE, nearest_teletext_color), cell.bg.map_or(0, nearest_teletext_color)),
+            None => Cell::Blank {bg: 0},
+        }).collect();
+        encode_row(&cells, bytes);
+    }
+    rows
+}
+
+/// Place `cells` in a row, with the control codes they need. Every row starts as white text on black.
+fn encode_row(cells: &[Cell], bytes: &mut [u8; PAGE_COLUMNS]) {
+    let (mut mosaic, mut fg, mut bg) = (false, WHITE, 0);
+    let color_code = |mosaic: bool, color: u8| if mosaic {MOSAIC_CODES | color} else {color};
+    for (column, cell) in cells.iter().enumerate() {
+        // A new background is the current foreground (set with a code first), or black
+        if cell.bg() != bg {
+            bytes[column] = if cell.bg() == 0 {
+                bg = 0;
+                BLACK_BACKGROUND
+            } else if fg != cell.bg() {
+                fg = cell.bg();
+                color_code(mosaic, fg)
+            } else {
+                bg = fg;
+                NEW_BACKGROUND
+            };
+            continue;
+        }
+        // The next cell drawing anything on this background sets the mode and foreground, in a blank before it if there is one
+        let next = cells[column..].iter().take_while(|next| next.bg() == bg).find_map(|next| next.needs(mosaic));
+        match next {
+            Some(needs) if needs != (mosaic, fg) => {
+                (mosaic, fg) = needs;
+                bytes[column] = color_code(mosaic, fg);
+            }
+            _ => bytes[column] = cell.byte(),
+        }
+    }
+}
+
+/// Encode `frame` as a `.tti` or `.t42` page.
+pub fn encode_teletext(frame: &AsciiFrame, options: &TeletextOptions) -> Result<Vec<u8>> {
+    if !(0x100..0x900).contains(&options.page) {
+        return Err(Error::InvalidOptions(format!("teletext page {:X} is not between 100 and 8FF", options.page)));
+    }
+    let rows = page_rows(frame);
+    Ok(match options.format {
+        TeletextFormat::Tti => encode_tti(&rows, options),
+        TeletextFormat::T42 => encode_t42(&rows, options),
+    })
+}
+
+/// The page in the `.tti` line format, control codes escaped as ESC and the code plus 0x40
+fn encode_tti(rows: &[[u8; PAGE_COLUMNS]; PAGE_ROWS], options: &TeletextOptions) -> Vec<u8> {
+    let description: String = options.description.chars().filter(|ch| ch.is_ascii() && !ch.is_ascii_control()).collect();
+    let mut out = format!("DE,{}\r\nPN,{:03X}00\r\nSC,0000\r\nPS,8000\r\n", description, options.page).into_bytes();
+    for (row, bytes) in rows.iter().enumerate() {
+        out.extend(format!("OL,{},", row + 1).bytes());
+        for &byte in bytes {
+            if byte < 0x20 {
+                out.extend_from_slice(&[0x1B, byte + 0x40]);
+            } else {
+                out.push(byte);
+            }
+        }
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+/// The page header packet and its 24 row packets, 42 bytes each: the Hamming coded magazine and
+/// row, then 40 bytes with odd parity
+fn encode_t42(rows: &[[u8; PAGE_COLUMNS]; PAGE_ROWS], options: &TeletextOptions) -> Vec<u8> {
+    let ham = |nibble: u16| HAMMING_8_4[(nibble & 0xF) as usize];
+    let parity = |byte: u8| if byte.count_ones().is_multiple_of(2) {byte | 0x80} else {byte};
+    // Magazine 8 is sent as 0
+    let magazine = options.page >> 8 & 7;
+    let address = |row: u16| [ham(magazine | (row & 1) << 3), ham(row >> 1)];
+
+    let mut out = Vec::with_capacity((PAGE_ROWS + 1) * 42);
+    out.extend_from_slice(&address(0));
+    // Page units and tens, subcode 0000 with C4 (erase page) set so every frame replaces the last, no other control bits
+    out.extend_from_slice(&[ham(options.page), ham(options.page >> 4), ham(0), ham(0b1000), ham(0), ham(0), ham(0), ham(0)]);
+    let header = options.description.bytes().filter(|byte| byte.is_ascii_graphic() || *byte == b' ').chain(std::iter::repeat(b' ')).take(32);
+    out.extend(header.map(parity));
+    for (row, bytes) in rows.iter().enumerate() {
+        out.extend_from_slice(&address(row as u16 + 1));
+        out.extend(bytes.iter().map(|&byte| parity(byte)));
+    }
+    out
+}
+
+/// Write every frame of a converted directory or `.casc` archive to `output_dir` as
+/// `frame_0001.tti` (or `.t42`) and on, in playback order. Returns the number of pages written.
+#[cfg(feature = "cli")]
+pub fn export_frame_dir(input: impl AsRef<std::path::Path>, output_dir: impl AsRef<std::path::Path>, options: &TeletextOptions) -> Result<usize> {
+    use crate::error::Context;
+
+    let output_dir = output_dir.as_ref();
+    std::fs::create_dir_all(output_dir).with_context(|| format!("creating {}", output_dir.display()))?;
+    let mut count = 0;
+    for frame in crate::reader::FrameSequence::open(input)?.reader() {
+        count += 1;
+        let path = output_dir.join(format!("frame_{:04}.{}", count, options.format.extension()));
+        std::fs::write(&path, encode_teletext(&frame?, options)?).with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn teletext_pages_place_control_codes_and_mosaics() {
+        // Row 1: a red sextant and a capital letter after a blank, a blank, then a green block.
+        // Row 2: white text on blue, which needs a color code, a new background and the white code.
+        let frame = AsciiFrame {
+            text: " \u{1fb02}A █\n   x \n".into(),
+            width: 5,
+            height: 2,
+            rgb: [[0, 0, 0], [250, 10, 0], [250, 10, 0], [0, 0, 0], [0, 200, 0], [0; 3], [0; 3], [0; 3], [255; 3], [0; 3]].concat(),
+            bg_rgb: [[[0; 3]; 5], [[0, 0, 230]; 5]].concat().concat(),
+        };
+        let rows = page_rows(&frame);
+        assert_eq!(&rows[0][..6], &[0x11, 0x23, b'A', 0x12, 0x7F, b' ']);
+        assert_eq!(&rows[1][..7], &[0x04, NEW_BACKGROUND, 0x07, b'x', b' ', BLACK_BACKGROUND, b' ']);
+        assert_eq!(rows[2], [b' '; PAGE_COLUMNS]);
+        assert_eq!((mosaic_mask('▀'), mosaic_mask('▖'), mosaic_mask('▌')), (Some(0b00_1111), Some(0b01_0100), Some(0b01_0101)));
+
+        let options = TeletextOptions::default().with_page(0x123).with_description("Test");
+        let tti = String::from_utf8(encode_teletext(&frame, &options).unwrap()).unwrap();
+        assert!(tti.starts_with("DE,Test\r\nPN,12300\r\nSC,0000\r\nPS,8000\r\nOL,1,\x1bQ#A\x1bR\x7f "));
+        assert_eq!(tti.lines().count(), 4 + PAGE_ROWS);
+
+        let t42 = encode_teletext(&frame, &options.clone().with_format(TeletextFormat::T42)).unwrap();
+        assert_eq!(t42.len(), 25 * 42);
+        // Magazine 1 row 0, page 23; row 1 starts with the odd parity mosaic red code
+        assert_eq!(&t42[..4], &[HAMMING_8_4[1], HAMMING_8_4[0], HAMMING_8_4[3], HAMMING_8_4[2]]);
+        assert_eq!(&t42[42..45], &[HAMMING_8_4[9], HAMMING_8_4[0], 0x91]);
+        assert!(t42.iter().skip(10).take(32).all(|byte| byte.count_ones() % 2 == 1));
+
+        assert!(encode_teletext(&frame, &options.with_page(0x950)).is_err());
+    }
+}
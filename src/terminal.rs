@@ -0,0 +1,495 @@
+//! Terminal setup for printing ANSI-colored frames.
+//!
+//! Unix terminals interpret the escapes written by [`frame_to_ansi_string`](crate::frame_to_ansi_string)
+//! and `.ans` files as they are. Windows consoles leave virtual terminal processing off unless the
+//! program asks for it and start in the OEM code page, so colored frames print as raw `←[38;2;…m`
+//! bytes; [`enable_ansi`] switches both on for the current process.
+//!
+//! Frames wider or taller than the terminal wrap and scroll, corrupting every frame after them.
+//! [`TerminalFit`] polls the terminal [`size`] between frames and scales frames down in ASCII space
+//! ([`AsciiFrame::fit_within`]) to whatever the terminal currently holds, so playback keeps fitting
+//! while the window is resized.
+//!
+//! [`KeyInput`] reads the keys players react to as they are typed, without waiting for Enter.
+//!
+//! [`image_to_terminal`] prints an image converted to fill the terminal, in the colors
+//! [`color_support`] reads from `NO_COLOR` and `COLORTERM`.
+
+use std::borrow::Cow;
+use std::io::Write;
+
+use image::DynamicImage;
+
+use crate::error::{Context, Result};
+use crate::{frame_to_ansi_string, image_to_frame, AnsiColor, AsciiFrame, ConversionOptions};
+
+/// Prepare standard output for ANSI escapes: on Windows 10+ consoles, enable virtual terminal
+/// processing and the UTF-8 code page. Safe to call repeatedly.
+///
+/// Returns `false` only when stdout is a console that can't interpret escapes (Windows before 10),
+/// in which case print plain text instead. Redirected output takes the escapes as they are.
+///
+/// ```no_run
+/// use cascii::{frame_to_ansi_string, image_to_frame, terminal, AnsiColor, ConversionOptions};
+///
+/// let frame = image_to_frame(&image::open("input.png").unwrap(), &ConversionOptions::default().with_columns(80)).unwrap();
+/// if terminal::enable_ansi() {
+///     print!("{}", frame_to_ansi_string(&frame, AnsiColor::TrueColor));
+/// } else {
+///     print!("{}", frame.text());
+/// }
+/// ```
+pub fn enable_ansi() -> bool {
+    #[cfg(windows)]
+    {
+        windows::enable_virtual_terminal()
+    }
+    #[cfg(not(windows))]
+    {
+        true
+    }
+}
+
+/// Columns and rows of the terminal on standard output, or of `COLUMNS`/`LINES` when stdout is not
+/// a terminal (or its size can't be read on this platform).
+pub fn size() -> Option<(u32, u32)> {
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    if let Some(size) = unix::stdout_size() {
+        return Some(size);
+    }
+    #[cfg(windows)]
+    if let Some(size) = windows::stdout_size() {
+        return Some(size);
+    }
+    let env = |name: &str| std::env::var(name).ok()?.trim().parse::<u32>().ok().filter(|&value| value > 0);
+    Some((env("COLUMNS")?, env("LINES")?))
+}
+
+/// The color escapes to print with, from the environment: none when `NO_COLOR` is set (to anything
+/// but an empty string) or `TERM` is `dumb`, truecolor when `COLORTERM` is `truecolor` or `24bit`,
+/// and 256 colors otherwise.
+pub fn color_support() -> Option<AnsiColor> {
+    let env = |name: &str| std::env::var(name).ok();
+    colors_from_env(env("NO_COLOR").as_deref(), env("COLORTERM").as_deref(), env("TERM").as_deref())
+}
+
+fn colors_from_env(no_color: Option<&str>, colorterm: Option<&str>, term: Option<&str>) -> Option<AnsiColor> {
+    if no_color.is_some_and(|value| !value.is_empty()) || term == Some("dumb") {
+        return None;
+    }
+    Some(if matches!(colorterm, Some("truecolor" | "24bit")) {AnsiColor::TrueColor} else {AnsiColor::Xterm256})
+}
+
+/// Convert `image` to fill the terminal on standard output and print it, in the colors of
+/// [`color_support`]. The terminal [`size`] (80×24 when it is unknown) replaces `options.columns`,
+/// and the bottom row is kept free so printing the image doesn't scroll its top away.
+///
+/// ```no_run
+/// use cascii::{terminal, ConversionOptions};
+///
+/// # fn main() -> cascii::Result<()> {
+/// terminal::image_to_terminal(&image::open("input.png").unwrap(), &ConversionOptions::default())?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn image_to_terminal(image: &DynamicImage, options: &ConversionOptions) -> Result<()> {
+    let (columns, rows) = size().unwrap_or((80, 24));
+    let colors = color_support().filter(|_| enable_ansi());
+    let text = image_to_terminal_string(image, options, columns, rows, colors)?;
+    let mut out = std::io::stdout().lock();
+    out.write_all(text.as_bytes()).and_then(|_| out.flush()).context("printing to the terminal")
+}
+
+/// `image` as [`image_to_terminal`] prints it on a `columns`×`rows` terminal: with `colors` escapes,
+/// or as plain text without them. Images too tall for the width get the columns their height allows.
+pub fn image_to_terminal_string(image: &DynamicImage, options: &ConversionOptions, columns: u32, rows: u32, colors: Option<AnsiColor>) -> Result<String> {
+    let (columns, rows) = (columns.max(1), rows.saturating_sub(1).max(1));
+    let mut frame = image_to_frame(image, &options.clone().with_columns(columns))?;
+    if frame.height > rows {
+        let narrower = (columns as u64 * rows as u64 / frame.height as u64).max(1) as u32;
+        frame = image_to_frame(image, &options.clone().with_columns(narrower))?;
+    }
+    // Rounding can leave a row too many
+    let frame = frame.fit_within(columns, rows);
+    Ok(match colors {
+        Some(colors) => frame_to_ansi_string(&frame, colors),
+        None => frame.into_owned().text,
+    })
+}
+
+/// Keeps played frames inside a terminal that may be resized between them.
+///
+/// Call [`poll`](Self::poll) before drawing each frame; when it reports a change, clear the screen
+/// (rows left over from a larger frame would otherwise stay behind) and draw [`fit`](Self::fit)'s frame.
+///
+/// ```no_run
+/// use cascii::terminal::TerminalFit;
+/// use cascii::AsciiFrame;
+///
+/// fn play(frames: &[AsciiFrame]) {
+///     let mut fit = TerminalFit::new();
+///     for frame in frames {
+///         print!("{}", if fit.poll() {"\x1b[2J\x1b[H"} else {"\x1b[H"});
+///         print!("{}", fit.fit(frame).text());
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TerminalFit {
+    size: Option<(u32, u32)>,
+}
+
+impl TerminalFit {
+    /// A fit that has not read the terminal size yet; frames pass through until [`poll`](Self::poll).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A fit for a terminal of a known size, e.g. one reported by a TUI library.
+    pub fn with_size(columns: u32, rows: u32) -> Self {
+        Self {size: Some((columns, rows))}
+    }
+
+    /// Re-read the terminal size; `true` when it changed since the last poll.
+    pub fn poll(&mut self) -> bool {
+        let size = size();
+        let changed = size != self.size;
+        self.size = size;
+        changed
+    }
+
+    /// The terminal size from the last poll
+    pub fn size(&self) -> Option<(u32, u32)> {
+        self.size
+    }
+
+    /// `frame` scaled down to the terminal, keeping the bottom row free so printing the last row's
+    /// newline doesn't scroll. Frames that fit, or any frame while the size is unknown, are borrowed.
+    pub fn fit<'a>(&self, frame: &'a AsciiFrame) -> Cow<'a, AsciiFrame> {
+        match self.size {
+            Some((columns, rows)) => frame.fit_within(columns, rows.saturating_sub(1).max(1)),
+            None => Cow::Borrowed(frame),
+        }
+    }
+}
+
+/// Key presses typed into the terminal on standard input, read as they come instead of line by
+/// line and not echoed, for players. Ctrl-C arrives as the byte `0x03` rather than interrupting, so
+/// a player can quit cleanly; arrow keys arrive as `ESC [ A` to `ESC [ D`. The terminal's mode is
+/// restored on drop.
+///
+/// ```no_run
+/// use cascii::terminal::KeyInput;
+///
+/// if let Some(mut keys) = KeyInput::open() {
+///     loop {
+///         if keys.read().contains(&b'q') {
+///             break;
+///         }
+///         std::thread::sleep(std::time::Duration::from_millis(20));
+///     }
+/// }
+/// ```
+pub struct KeyInput {
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    saved: unix::Termios,
+}
+
+impl KeyInput {
+    /// Switch the terminal to key-by-key input; `None` when standard input is not a terminal or the
+    /// platform is unsupported.
+    pub fn open() -> Option<Self> {
+        use std::io::IsTerminal;
+
+        if !std::io::stdin().is_terminal() {
+            return None;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+        {
+            unix::enter_raw_input().map(|saved| Self {saved})
+        }
+        #[cfg(windows)]
+        {
+            Some(Self {})
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd", windows)))]
+        {
+            None
+        }
+    }
+
+    /// The bytes typed since the last call, without waiting for more.
+    pub fn read(&mut self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+        unix::read_available(&mut bytes);
+        #[cfg(windows)]
+        windows::read_available(&mut bytes);
+        bytes
+    }
+}
+
+impl Drop for KeyInput {
+    fn drop(&mut self) {
+        #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+        unix::restore_input(&self.saved);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+mod unix {
+    use std::ffi::{c_int, c_ulong, c_void};
+
+    #[repr(C)]
+    struct Winsize {
+        ws_row: u16,
+        ws_col: u16,
+        ws_xpixel: u16,
+        ws_ypixel: u16,
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    const TIOCGWINSZ: c_ulong = 0x4008_7468;
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    type TcFlag = std::ffi::c_uint;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    type TcFlag = c_ulong;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const NCCS: usize = 32;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    const NCCS: usize = 20;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const ISIG: TcFlag = 0o1;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const ICANON: TcFlag = 0o2;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const ECHO: TcFlag = 0o10;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const VTIME: usize = 5;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const VMIN: usize = 6;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    const ISIG: TcFlag = 0x80;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    const ICANON: TcFlag = 0x100;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    const ECHO: TcFlag = 0x8;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    const VMIN: usize = 16;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    const VTIME: usize = 17;
+
+    const TCSANOW: c_int = 0;
+
+    /// `struct termios` as the C library lays it out (Android's is a prefix of this one)
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    pub(super) struct Termios {
+        c_iflag: TcFlag,
+        c_oflag: TcFlag,
+        c_cflag: TcFlag,
+        c_lflag: TcFlag,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        c_line: u8,
+        c_cc: [u8; NCCS],
+        c_ispeed: TcFlag,
+        c_ospeed: TcFlag,
+    }
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+        fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+        fn tcsetattr(fd: c_int, optional_actions: c_int, termios: *const Termios) -> c_int;
+        fn read(fd: c_int, buf: *mut c_void, count: usize) -> isize;
+    }
+
+    /// Turn off line buffering, echo and signal keys on stdin, with reads returning at once;
+    /// returns the mode to restore.
+    pub(super) fn enter_raw_input() -> Option<Termios> {
+        let mut saved = Termios::default();
+        // SAFETY: tcgetattr writes one `termios` through the pointer, which outlives the call
+        if unsafe { tcgetattr(0, &mut saved) } != 0 {
+            return None;
+        }
+        let mut raw = saved;
+        raw.c_lflag &= !(ICANON | ECHO | ISIG);
+        raw.c_cc[VMIN] = 0;
+        raw.c_cc[VTIME] = 0;
+        // SAFETY: tcsetattr only reads the `termios` behind the pointer
+        (unsafe { tcsetattr(0, TCSANOW, &raw) } == 0).then_some(saved)
+    }
+
+    pub(super) fn restore_input(saved: &Termios) {
+        // SAFETY: tcsetattr only reads the `termios` behind the pointer
+        unsafe { tcsetattr(0, TCSANOW, saved) };
+    }
+
+    pub(super) fn read_available(bytes: &mut Vec<u8>) {
+        let mut buf = [0u8; 64];
+        loop {
+            // SAFETY: read writes at most `buf.len()` bytes into `buf`
+            let count = unsafe { read(0, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+            if count <= 0 {
+                break;
+            }
+            bytes.extend_from_slice(&buf[..count as usize]);
+        }
+    }
+
+    pub(super) fn stdout_size() -> Option<(u32, u32)> {
+        let mut size = Winsize {ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0};
+        // SAFETY: TIOCGWINSZ writes one `winsize` through the pointer, which outlives the call
+        let result = unsafe { ioctl(1, TIOCGWINSZ, &mut size as *mut Winsize) };
+        (result == 0 && size.ws_col > 0 && size.ws_row > 0).then_some((size.ws_col as u32, size.ws_row as u32))
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const INVALID_HANDLE_VALUE: *mut c_void = -1isize as *mut c_void;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    const CP_UTF8: u32 = 65001;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+    }
+
+    #[repr(C)]
+    struct Coord {
+        x: i16,
+        y: i16,
+    }
+
+    #[repr(C)]
+    struct SmallRect {
+        left: i16,
+        top: i16,
+        right: i16,
+        bottom: i16,
+    }
+
+    #[repr(C)]
+    struct ConsoleScreenBufferInfo {
+        size: Coord,
+        cursor_position: Coord,
+        attributes: u16,
+        window: SmallRect,
+        maximum_window_size: Coord,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleScreenBufferInfo(console: *mut c_void, info: *mut ConsoleScreenBufferInfo) -> i32;
+    }
+
+    extern "C" {
+        fn _kbhit() -> i32;
+        fn _getch() -> i32;
+    }
+
+    /// Keys waiting in the console input, with arrow keys translated to their ANSI sequences
+    pub(super) fn read_available(bytes: &mut Vec<u8>) {
+        // SAFETY: CRT console reads without arguments
+        unsafe {
+            while _kbhit() != 0 {
+                match _getch() {
+                    // Extended keys come as a prefix followed by a scan code
+                    0 | 0xE0 => match _getch() {
+                        0x48 => bytes.extend_from_slice(b"\x1b[A"),
+                        0x50 => bytes.extend_from_slice(b"\x1b[B"),
+                        0x4D => bytes.extend_from_slice(b"\x1b[C"),
+                        0x4B => bytes.extend_from_slice(b"\x1b[D"),
+                        _ => {}
+                    },
+                    key => bytes.push(key as u8),
+                }
+            }
+        }
+    }
+
+    pub(super) fn stdout_size() -> Option<(u32, u32)> {
+        // SAFETY: plain Win32 calls on the process's own stdout handle; `info` outlives the call writing it
+        unsafe {
+            let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+            if handle.is_null() || handle == INVALID_HANDLE_VALUE {
+                return None;
+            }
+            let mut info = ConsoleScreenBufferInfo {size: Coord {x: 0, y: 0}, cursor_position: Coord {x: 0, y: 0}, attributes: 0, window: SmallRect {left: 0, top: 0, right: 0, bottom: 0}, maximum_window_size: Coord {x: 0, y: 0}};
+            if GetConsoleScreenBufferInfo(handle, &mut info) == 0 {
+                return None;
+            }
+            let (columns, rows) = (info.window.right - info.window.left + 1, info.window.bottom - info.window.top + 1);
+            (columns > 0 && rows > 0).then_some((columns as u32, rows as u32))
+        }
+    }
+
+    pub(super) fn enable_virtual_terminal() -> bool {
+        // SAFETY: plain Win32 calls on the process's own stdout handle; `mode` outlives the call writing it
+        unsafe {
+            let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+            if handle.is_null() || handle == INVALID_HANDLE_VALUE {
+                return true;
+            }
+            let mut mode = 0u32;
+            if GetConsoleMode(handle, &mut mode) == 0 {
+                // Not a console: a pipe or file takes the escapes verbatim
+                return true;
+            }
+            SetConsoleOutputCP(CP_UTF8);
+            mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0 || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enable_ansi_is_repeatable() {
+        // Test runs capture stdout, so this never sees a legacy console
+        assert!(enable_ansi());
+        assert!(enable_ansi());
+    }
+
+    #[test]
+    fn test_terminal_fit_leaves_the_last_row_free() {
+        let frame = AsciiFrame {text: "abcd\nefgh\nijkl\nmnop\n".to_string(), width: 4, height: 4, rgb: Vec::new(), bg_rgb: Vec::new()};
+        assert!(matches!(TerminalFit::new().fit(&frame), Cow::Borrowed(_)));
+        assert!(matches!(TerminalFit::with_size(80, 5).fit(&frame), Cow::Borrowed(_)));
+        let fitted = TerminalFit::with_size(80, 3).fit(&frame);
+        assert_eq!((fitted.width(), fitted.height()), (2, 2));
+    }
+
+    #[test]
+    fn test_image_to_terminal_fills_the_terminal_in_the_colors_it_takes() -> Result<()> {
+        assert_eq!(colors_from_env(None, Some("truecolor"), Some("xterm-256color")), Some(AnsiColor::TrueColor));
+        assert_eq!(colors_from_env(Some(""), Some("24bit"), None), Some(AnsiColor::TrueColor));
+        assert_eq!(colors_from_env(None, None, Some("xterm")), Some(AnsiColor::Xterm256));
+        assert_eq!(colors_from_env(Some("1"), Some("truecolor"), None), None);
+        assert_eq!(colors_from_env(None, None, Some("dumb")), None);
+
+        let options = ConversionOptions::default().with_font_ratio(0.5);
+        let wide = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(200, 50, image::Rgb([200, 40, 40])));
+        let text = image_to_terminal_string(&wide, &options, 40, 25, None)?;
+        assert_eq!((text.lines().count(), text.lines().map(|line| line.chars().count()).max()), (5, Some(40)));
+        // A square image is as tall as the terminal, less its bottom row, and narrower than it
+        let square = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(100, 100, image::Rgb([200, 40, 40])));
+        let text = image_to_terminal_string(&square, &options, 80, 11, Some(AnsiColor::TrueColor))?;
+        assert_eq!(text.lines().count(), 10);
+        assert!(text.starts_with("\x1b[38;2;200;40;40m"));
+        Ok(())
+    }
+}
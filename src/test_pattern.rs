@@ -0,0 +1,124 @@
+//! Reference images with known content, for tuning a conversion before pointing it at real footage.
+//!
+//! Convert a pattern with the options you mean to use and check the output against what the
+//! pattern holds: a gradient should climb through the whole character set without bands, color bars
+//! should keep their hues and order, and the round resolution wedge should come out round (otherwise
+//! `font_ratio` is off) with its spokes resolved down to a small circle.
+//!
+//! ```
+//! use cascii::test_pattern::{generate_test_pattern, TestPattern};
+//! use cascii::{image_to_frame, ConversionOptions};
+//!
+//! let pattern = image::DynamicImage::ImageRgb8(generate_test_pattern(TestPattern::Gradient, 256, 64));
+//! let frame = image_to_frame(&pattern, &ConversionOptions::default().with_columns(64)).unwrap();
+//! // Black is below the threshold, and the white step gets the densest character
+//! assert!(frame.text.lines().all(|line| line.starts_with(' ')));
+//! assert!(frame.text.lines().last().unwrap().ends_with('$'));
+//! ```
+
+use image::{Rgb, RgbImage};
+
+/// Which reference image [`generate_test_pattern`] draws.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TestPattern {
+    /// Black to white from left to right: smooth in the top half, in 16 even steps in the bottom half
+    #[default]
+    Gradient,
+    /// SMPTE-style color bars: seven 75% bars, a strip of reversed bars, then full white, black and near-black steps
+    Bars,
+    /// A round resolution wedge: black and white spokes converging on the center of a circle
+    Wedge,
+}
+
+impl TestPattern {
+    pub const ALL: [Self; 3] = [Self::Gradient, Self::Bars, Self::Wedge];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Gradient => "gradient",
+            Self::Bars => "bars",
+            Self::Wedge => "wedge",
+        }
+    }
+}
+
+impl std::str::FromStr for TestPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|pattern| pattern.as_str() == s).ok_or_else(|| format!("expected one of {}", Self::ALL.map(TestPattern::as_str).join(", ")))
+    }
+}
+
+/// Steps of the bottom half of [`TestPattern::Gradient`]
+const GRADIENT_STEPS: u32 = 16;
+
+/// Spokes of [`TestPattern::Wedge`], half of them white
+const WEDGE_SPOKES: f32 = 48.0;
+
+/// The 75% bars of [`TestPattern::Bars`], left to right
+const BARS: [[u8; 3]; 7] = [[191, 191, 191], [191, 191, 0], [0, 191, 191], [0, 191, 0], [191, 0, 191], [191, 0, 0], [0, 0, 191]];
+
+/// Draw `kind` at `width`×`height` pixels (an empty image when either is 0).
+pub fn generate_test_pattern(kind: TestPattern, width: u32, height: u32) -> RgbImage {
+    match kind {
+        TestPattern::Gradient => RgbImage::from_fn(width, height, |x, y| {
+            let value = if y < height / 2 {x * 255 / width.saturating_sub(1).max(1)} else {(x * GRADIENT_STEPS / width) * 255 / (GRADIENT_STEPS - 1)};
+            Rgb([value as u8; 3])
+        }),
+        TestPattern::Bars => RgbImage::from_fn(width, height, |x, y| {
+            let bar = (x * 7 / width) as usize;
+            Rgb(if y < height * 2 / 3 {
+                BARS[bar]
+            } else if y < height * 3 / 4 {
+                // Reversed castellations: the blue bar's neighbours under every other bar
+                if bar.is_multiple_of(2) {BARS[6 - bar]} else {[0; 3]}
+            } else {
+                // -I, white, +Q, black, then near-black steps for setting the threshold
+                match x * 28 / width {
+                    0..=4 => [0, 33, 76],
+                    5..=9 => [255; 3],
+                    10..=14 => [50, 0, 106],
+                    15..=19 => [0; 3],
+                    20 | 21 => [10; 3],
+                    22 | 23 => [20; 3],
+                    _ => [0; 3],
+                }
+            })
+        }),
+        TestPattern::Wedge => {
+            let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+            let radius = cx.min(cy);
+            RgbImage::from_fn(width, height, |x, y| {
+                let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                let distance = (dx * dx + dy * dy).sqrt();
+                let spoke = ((dy.atan2(dx) / std::f32::consts::TAU + 0.5) * WEDGE_SPOKES) as u32;
+                Rgb(if distance > radius {[96; 3]} else if spoke.is_multiple_of(2) {[255; 3]} else {[0; 3]})
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns_hold_their_references() {
+        let gradient = generate_test_pattern(TestPattern::Gradient, 64, 8);
+        assert_eq!((gradient.get_pixel(0, 0)[0], gradient.get_pixel(63, 0)[0]), (0, 255));
+        // Each of the 16 steps is flat and the steps climb evenly from black to white
+        assert_eq!((gradient.get_pixel(4, 7)[0], gradient.get_pixel(7, 7)[0], gradient.get_pixel(63, 7)[0]), (17, 17, 255));
+
+        let bars = generate_test_pattern(TestPattern::Bars, 70, 12);
+        assert_eq!(bars.get_pixel(15, 0).0, [191, 191, 0]);
+        assert_eq!(bars.get_pixel(5, 8).0, [0, 0, 191]);
+        assert_eq!(bars.get_pixel(15, 11).0, [255; 3]);
+
+        let wedge = generate_test_pattern(TestPattern::Wedge, 40, 20);
+        assert_eq!(wedge.get_pixel(0, 0).0, [96; 3]);
+        assert!(wedge.pixels().any(|px| px.0 == [255; 3]) && wedge.pixels().any(|px| px.0 == [0; 3]));
+        assert!(generate_test_pattern(TestPattern::Wedge, 0, 0).is_empty());
+        assert_eq!("bars".parse(), Ok(TestPattern::Bars));
+    }
+}
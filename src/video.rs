@@ -1,19 +1,39 @@
-use anyhow::{anyhow, Context, Result};
+use crate::error::{Context, Error, Result};
+use image::{GrayImage, RgbImage};
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
 use std::path::Path;
-use std::process::{Command as ProcCommand, Stdio};
+use std::process::{Child, ChildStdout, Command as ProcCommand, ExitStatus, Stdio};
+use std::thread::JoinHandle;
 
 use crate::preprocessing::build_frame_extraction_vf;
-use crate::{CancelToken, FfmpegConfig, Progress, VideoOptions};
+use crate::events::EventLog;
+use crate::cell_filter::Luminance;
+use crate::frame::Sampling;
+use crate::native_decode;
+use crate::yuv::{ChromaSubsampling, YuvFrame, YuvMatrix};
+use crate::{CancelToken, FfmpegConfig, MediaInfo, PipeFormat, Progress, ToneMap, VideoOptions};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
 
 /// Spawn a configured ffmpeg command and wait for it, polling an optional
 /// cancellation token. If cancellation is requested the child process is killed
-/// and `Cancelled` is returned; otherwise behaves like a blocking wait.
-fn run_ffmpeg_cancellable(mut command: ProcCommand, cancel: Option<&CancelToken>, what: &str) -> Result<()> {
-    let mut child = command.spawn().with_context(|| format!("spawning {}", what))?;
+/// and `Cancelled` is returned; otherwise behaves like a blocking wait. The command
+/// line is recorded in `events` before spawning.
+fn run_ffmpeg_cancellable(mut command: ProcCommand, cancel: Option<&CancelToken>, events: Option<&EventLog>, what: &str) -> Result<()> {
+    profile_span!("ffmpeg", what);
+    if let Some(events) = events {
+        events.record_command(&command);
+    }
+    let mut child = command.spawn().map_err(|err| Error::spawn(command.get_program(), err))?;
     loop {
         if let Some(status) = child.try_wait().with_context(|| format!("waiting for {}", what))? {
+            // A terminal Ctrl-C reaches ffmpeg too; when it dies from that, report the cancellation rather than a failure.
+            if !status.success() && cancel.is_some_and(|c| c.is_cancelled()) {
+                return Err(crate::Cancelled.into());
+            }
             if !status.success() {
-                return Err(anyhow!("{} failed", what));
+                return Err(Error::ffmpeg(format!("{} failed", what), &[]));
             }
             return Ok(());
         }
@@ -26,70 +46,531 @@ fn run_ffmpeg_cancellable(mut command: ProcCommand, cancel: Option<&CancelToken>
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-pub(crate) fn extract_video_frames(input: &Path, out_dir: &Path, columns: u32, fps: u32, start: Option<&str>, end: Option<&str>, preprocess_filter: Option<&str>, ffmpeg_config: &FfmpegConfig, cancel: Option<&CancelToken>) -> Result<()> {
-    let out_pattern = out_dir.join("frame_%04d.png");
-    let mut ffmpeg_args: Vec<String> = vec!["-loglevel".into(), "error".into()];
-
+/// `-ss`, `-i` and `-t` arguments reading `input` from `start` to `end`
+fn input_args(input: &Path, start: Option<&str>, end: Option<&str>) -> Result<Vec<String>> {
+    let start = start.filter(|s| !s.is_empty() && *s != "0");
+    let mut args = Vec::new();
     if let Some(s) = start {
-        if !s.is_empty() && s != "0" {
-            ffmpeg_args.push("-ss".into());
-            ffmpeg_args.push(s.to_string());
-        }
-    }
-
-    ffmpeg_args.push("-i".into());
-    ffmpeg_args.push(input.to_str().unwrap().to_string());
-
-    if let Some(e) = end {
-        if !e.is_empty() {
-            if let Some(s) = start {
-                if !s.is_empty() && s != "0" {
-                    let start_secs = parse_timestamp(s);
-                    let end_secs = parse_timestamp(e);
-                    let duration = end_secs - start_secs;
-                    if duration > 0.0 {
-                        ffmpeg_args.push("-t".into());
-                        ffmpeg_args.push(duration.to_string());
-                    }
-                } else {
-                    ffmpeg_args.push("-t".into());
-                    ffmpeg_args.push(e.to_string());
+        args.push("-ss".into());
+        args.push(s.to_string());
+    }
+    args.push("-i".into());
+    args.push(input.to_str().ok_or_else(|| Error::Other("input path is not valid UTF-8".into()))?.to_string());
+    if let Some(e) = end.filter(|e| !e.is_empty()) {
+        match start {
+            Some(s) => {
+                let duration = parse_timestamp(e) - parse_timestamp(s);
+                if duration > 0.0 {
+                    args.push("-t".into());
+                    args.push(duration.to_string());
                 }
-            } else {
-                ffmpeg_args.push("-t".into());
-                ffmpeg_args.push(e.to_string());
+            }
+            None => {
+                args.push("-t".into());
+                args.push(e.to_string());
             }
         }
     }
+    Ok(args)
+}
+
+pub(crate) fn extract_video_frames(input: &Path, out_dir: &Path, video_opts: &VideoOptions, sampling: Sampling, ffmpeg_config: &FfmpegConfig, cancel: Option<&CancelToken>, events: Option<&EventLog>) -> Result<()> {
+    let start = video_opts.start.as_deref();
+    let end = video_opts.end.as_deref();
+    let out_pattern = out_dir.join("frame_%04d.png");
+    let mut ffmpeg_args: Vec<String> = vec!["-loglevel".into(), "error".into()];
+
+    ffmpeg_args.extend(input_args(input, start, end)?);
 
-    let vf_option = build_frame_extraction_vf(columns, fps, preprocess_filter);
+    let vf_option = build_frame_extraction_vf(video_opts, sampling, hdr_tone_map(input, video_opts, ffmpeg_config));
     ffmpeg_args.push("-vf".into());
     ffmpeg_args.push(vf_option);
     ffmpeg_args.push(out_pattern.to_str().unwrap().to_string());
 
     let mut command = ProcCommand::new(ffmpeg_config.ffmpeg_cmd());
     command.args(&ffmpeg_args);
-    run_ffmpeg_cancellable(command, cancel, "ffmpeg")
+    or_native_extraction(run_ffmpeg_cancellable(command, cancel, events, "ffmpeg"), input, out_dir, video_opts, sampling, cancel)
+}
+
+/// `result`, unless ffmpeg is missing and [`native_decode`] can extract `input` in its place
+fn or_native_extraction(result: Result<()>, input: &Path, out_dir: &Path, video_opts: &VideoOptions, sampling: Sampling, cancel: Option<&CancelToken>) -> Result<()> {
+    match result {
+        Err(Error::FfmpegNotFound {..}) if native_decode::supports(input, video_opts) => native_decode::extract_frames(input, out_dir, video_opts, sampling, cancel),
+        result => result,
+    }
+}
+
+/// Frames of `input` decoded by ffmpeg and read from its stdout in the [`PipeFormat`] of the
+/// options, filtered and scaled like [`extract_video_frames`] but never written to disk.
+pub(crate) struct FramePipe {
+    source: PipeSource,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+enum PipeSource {
+    Ffmpeg {
+        child: Option<Decoder>,
+        stdout: ChildStdout,
+    },
+    /// PNG files ffmpeg writes one after another, the first read ahead for the frame size
+    Png {
+        child: Option<Decoder>,
+        stdout: BufReader<ChildStdout>,
+        first: Option<Vec<u8>>,
+    },
+    /// A YUV4MPEG2 stream, its size and chroma layout read from the stream header
+    Y4m {
+        child: Option<Decoder>,
+        stdout: BufReader<ChildStdout>,
+        layout: Y4mLayout,
+        matrix: YuvMatrix,
+    },
+    /// Frames [`native_decode`] decoded up front, without ffmpeg
+    Decoded(std::vec::IntoIter<image::RgbaImage>),
+}
+
+/// A running ffmpeg whose stderr is read on a helper thread, so it can't stall on a full stderr
+/// pipe while frames are read from its stdout
+struct Decoder {
+    child: Child,
+    stderr: Option<JoinHandle<Vec<u8>>>,
+}
+
+impl Decoder {
+    /// Start `command` with stdout and stderr piped, returning its stdout
+    fn spawn(command: &mut ProcCommand, ffmpeg_config: &FfmpegConfig) -> Result<(Self, ChildStdout)> {
+        let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().map_err(|err| Error::spawn(ffmpeg_config.ffmpeg_cmd(), err))?;
+        let stdout = child.stdout.take().ok_or_else(|| Error::Other("failed to open ffmpeg stdout pipe".into()))?;
+        let stderr = child.stderr.take().map(|mut stderr| std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = stderr.read_to_end(&mut output);
+            output
+        }));
+        Ok((Self {child, stderr}, stdout))
+    }
+
+    /// Wait for ffmpeg to exit, with what it wrote to stderr
+    fn wait(mut self) -> Result<(ExitStatus, Vec<u8>)> {
+        let status = self.child.wait().context("waiting for ffmpeg")?;
+        let stderr = self.stderr.take().and_then(|reader| reader.join().ok()).unwrap_or_default();
+        Ok((status, stderr))
+    }
+}
+
+impl FramePipe {
+    /// Start decoding. ffmpeg is asked for the first frame as a PNG beforehand to learn the size the
+    /// filters produce, which the raw stream doesn't carry. Without ffmpeg, animations
+    /// [`native_decode`] supports are decoded in full instead.
+    pub(crate) fn open(input: &Path, video_opts: &VideoOptions, sampling: Sampling, ffmpeg_config: &FfmpegConfig, events: Option<&EventLog>) -> Result<Self> {
+        match Self::open_ffmpeg(input, video_opts, sampling, ffmpeg_config, events) {
+            Err(Error::FfmpegNotFound {..}) if native_decode::supports(input, video_opts) => {
+                let frames = native_decode::decode(input, video_opts, sampling, None)?;
+                let (width, height) = frames.first().map(|frame| frame.dimensions()).ok_or_else(|| Error::NotFound("No frames extracted from video".into()))?;
+                Ok(Self {source: PipeSource::Decoded(frames.into_iter()), width, height})
+            }
+            result => result,
+        }
+    }
+
+    fn open_ffmpeg(input: &Path, video_opts: &VideoOptions, sampling: Sampling, ffmpeg_config: &FfmpegConfig, events: Option<&EventLog>) -> Result<Self> {
+        let input_args = input_args(input, video_opts.start.as_deref(), video_opts.end.as_deref())?;
+        let tone_map = hdr_tone_map(input, video_opts, ffmpeg_config);
+        let vf_option = build_frame_extraction_vf(video_opts, sampling, tone_map);
+        if video_opts.pipe_format == PipeFormat::Png {
+            // Every PNG carries its size, so the stream needs no probe and conversion starts with its first frame
+            let mut command = ProcCommand::new(ffmpeg_config.ffmpeg_cmd());
+            command.args(["-loglevel", "error", "-nostdin"]).args(&input_args).args(["-vf", &vf_option, "-c:v", "png", "-compression_level", "0", "-f", "image2pipe", "pipe:1"]);
+            if let Some(events) = events {
+                events.record_command(&command);
+            }
+            let (child, stdout) = Decoder::spawn(&mut command, ffmpeg_config)?;
+            let mut stdout = BufReader::new(stdout);
+            let Some(first) = read_png(&mut stdout).context("reading frames from ffmpeg")? else {
+                let (status, stderr) = child.wait()?;
+                return Err(if status.success() {Error::NotFound("No frames extracted from video".into())} else {Error::ffmpeg("ffmpeg failed", &stderr)});
+            };
+            let width = u32::from_be_bytes(first[16..20].try_into().unwrap());
+            let height = u32::from_be_bytes(first[20..24].try_into().unwrap());
+            return Ok(Self {source: PipeSource::Png {child: Some(child), stdout, first: Some(first)}, width, height});
+        }
+        if video_opts.pipe_format == PipeFormat::Y4m {
+            let mut command = ProcCommand::new(ffmpeg_config.ffmpeg_cmd());
+            command.args(["-loglevel", "error", "-nostdin"]).args(&input_args).args(["-vf", &vf_option, "-pix_fmt", "yuv444p", "-f", "yuv4mpegpipe", "pipe:1"]);
+            if let Some(events) = events {
+                events.record_command(&command);
+            }
+            let (child, stdout) = Decoder::spawn(&mut command, ffmpeg_config)?;
+            let mut stdout = BufReader::new(stdout);
+            let header = match read_y4m_line(&mut stdout).context("reading frames from ffmpeg")? {
+                Some(header) => header,
+                None => {
+                    let (status, stderr) = child.wait()?;
+                    return Err(if status.success() {Error::NotFound("No frames extracted from video".into())} else {Error::ffmpeg("ffmpeg failed", &stderr)});
+                }
+            };
+            let (width, height, layout) = parse_y4m_header(&header)?;
+            // Tone mapping writes BT.709; otherwise ffmpeg keeps the matrix of YUV sources and converts RGB ones with BT.601
+            let matrix = video_opts.yuv_matrix.or(tone_map.map(|_| YuvMatrix::Bt709)).unwrap_or_else(|| probe_yuv_matrix(input, ffmpeg_config).ok().flatten().unwrap_or_default());
+            return Ok(Self {source: PipeSource::Y4m {child: Some(child), stdout, layout, matrix}, width, height});
+        }
+
+        let mut probe = ProcCommand::new(ffmpeg_config.ffmpeg_cmd());
+        probe.args(["-loglevel", "error", "-nostdin"]).args(&input_args).args(["-vf", &vf_option, "-frames:v", "1", "-c:v", "png", "-f", "image2pipe", "pipe:1"]);
+        if let Some(events) = events {
+            events.record_command(&probe);
+        }
+        let output = probe.output().map_err(|err| Error::spawn(ffmpeg_config.ffmpeg_cmd(), err))?;
+        if !output.status.success() {
+            return Err(Error::ffmpeg("ffmpeg failed", &output.stderr));
+        }
+        let (width, height) = image::load_from_memory(&output.stdout).map(|frame| (frame.width(), frame.height())).map_err(|_| Error::NotFound("No frames extracted from video".into()))?;
+
+        let mut command = ProcCommand::new(ffmpeg_config.ffmpeg_cmd());
+        command.args(["-loglevel", "error", "-nostdin"]).args(&input_args).args(["-vf", &vf_option, "-pix_fmt", "rgb24", "-f", "rawvideo", "pipe:1"]);
+        if let Some(events) = events {
+            events.record_command(&command);
+        }
+        let (child, stdout) = Decoder::spawn(&mut command, ffmpeg_config)?;
+        Ok(Self {source: PipeSource::Ffmpeg {child: Some(child), stdout}, width, height})
+    }
+
+    /// How many frames there are, when they were decoded up front
+    pub(crate) fn frame_count(&self) -> Option<usize> {
+        match &self.source {
+            PipeSource::Ffmpeg {..} | PipeSource::Png {..} | PipeSource::Y4m {..} => None,
+            PipeSource::Decoded(frames) => Some(frames.len()),
+        }
+    }
+
+    /// The next frame, or `None` once ffmpeg has written the last one
+    pub(crate) fn next_frame(&mut self) -> Result<Option<PipedFrame>> {
+        profile_span!("decode");
+        let stdout = match &mut self.source {
+            PipeSource::Ffmpeg {stdout, ..} => stdout,
+            PipeSource::Png {stdout, first, ..} => return first.take().map_or_else(|| read_png(stdout), |first| Ok(Some(first))).map(|png| png.map(PipedFrame::Png)).context("reading frames from ffmpeg"),
+            PipeSource::Y4m {stdout, layout, matrix, ..} => {
+                let Some(planes) = read_y4m_frame(stdout, layout.frame_len(self.width, self.height)).context("reading frames from ffmpeg")? else {return Ok(None)};
+                let frame = YuvFrame::new(self.width, self.height, layout.subsampling, planes)?.with_matrix(*matrix).with_full_range(layout.full_range).with_cosited_chroma(layout.cosited);
+                return Ok(Some(PipedFrame::Yuv(frame)));
+            }
+            PipeSource::Decoded(frames) => return Ok(frames.next().map(|frame| PipedFrame::Rgb(image::DynamicImage::ImageRgba8(frame).into_rgb8()))),
+        };
+        let mut buffer = vec![0u8; self.width as usize * self.height as usize * 3];
+        match stdout.read_exact(&mut buffer) {
+            Ok(()) => Ok(RgbImage::from_raw(self.width, self.height, buffer).map(PipedFrame::Rgb)),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err).context("reading frames from ffmpeg"),
+        }
+    }
+
+    fn child(&mut self) -> Option<&mut Option<Decoder>> {
+        match &mut self.source {
+            PipeSource::Ffmpeg {child, ..} | PipeSource::Png {child, ..} | PipeSource::Y4m {child, ..} => Some(child),
+            PipeSource::Decoded(_) => None,
+        }
+    }
+
+    /// Wait for ffmpeg to exit, failing when it didn't decode the whole input
+    pub(crate) fn finish(mut self) -> Result<()> {
+        let Some(child) = self.child() else { return Ok(()) };
+        let (status, stderr) = child.take().expect("ffmpeg is running until finished").wait()?;
+        if !status.success() {
+            return Err(Error::ffmpeg("ffmpeg failed", &stderr));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FramePipe {
+    fn drop(&mut self) {
+        if let Some(Some(decoder)) = self.child() {
+            let _ = decoder.child.kill();
+            let _ = decoder.child.wait();
+        }
+    }
+}
+
+/// A frame read from a [`FramePipe`]: decoded already, or a PNG or YUV frame left for a conversion
+/// worker to decode
+pub(crate) enum PipedFrame {
+    Rgb(RgbImage),
+    Png(Vec<u8>),
+    Yuv(YuvFrame),
+}
+
+impl PipedFrame {
+    /// The frame in RGB, with its luma when that is the brightness `luminance` measures
+    pub(crate) fn decode(self, luminance: Luminance) -> Result<(RgbImage, Option<GrayImage>)> {
+        match self {
+            Self::Rgb(image) => Ok((image, None)),
+            Self::Png(png) => {
+                profile_span!("decode");
+                Ok((image::load_from_memory_with_format(&png, image::ImageFormat::Png).context("decoding a frame from ffmpeg")?.into_rgb8(), None))
+            }
+            Self::Yuv(frame) => {
+                profile_span!("decode");
+                Ok((frame.to_rgb(), frame.luma_for(luminance)))
+            }
+        }
+    }
+}
+
+/// Chroma layout and sample range of the frames of a YUV4MPEG2 stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Y4mLayout {
+    subsampling: ChromaSubsampling,
+    /// Chroma sited on the left luma column of those it covers
+    cosited: bool,
+    full_range: bool,
+}
+
+impl Y4mLayout {
+    /// Bytes of the planes of a `width`×`height` frame
+    fn frame_len(self, width: u32, height: u32) -> usize {
+        let (chroma_w, chroma_h) = self.subsampling.chroma_size(width, height);
+        width as usize * height as usize + 2 * chroma_w as usize * chroma_h as usize
+    }
+}
+
+/// The next line of a YUV4MPEG2 stream, without its newline; `None` at the end of the stream
+fn read_y4m_line(reader: &mut impl BufRead) -> std::io::Result<Option<String>> {
+    let mut line = Vec::new();
+    // Headers are short; a stream without newlines isn't YUV4MPEG2
+    reader.by_ref().take(1024).read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.pop() != Some(b'\n') {
+        return Err(std::io::Error::new(ErrorKind::InvalidData, "damaged YUV4MPEG2 header from ffmpeg"));
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+/// Width, height and chroma layout from a YUV4MPEG2 stream header of 8-bit frames
+fn parse_y4m_header(header: &str) -> Result<(u32, u32, Y4mLayout)> {
+    let mut fields = header.split(' ');
+    if fields.next() != Some("YUV4MPEG2") {
+        return Err(Error::Other("ffmpeg wrote something other than a YUV4MPEG2 stream".into()));
+    }
+    let (mut width, mut height, mut colorspace, mut full_range) = (0, 0, "420jpeg", false);
+    for field in fields {
+        match field.split_at(field.len().min(1)) {
+            ("W", value) => width = value.parse().unwrap_or(0),
+            ("H", value) => height = value.parse().unwrap_or(0),
+            ("C", value) => colorspace = value,
+            ("X", "COLORRANGE=FULL") => full_range = true,
+            _ => {}
+        }
+    }
+    if width == 0 || height == 0 {
+        return Err(Error::Other(format!("YUV4MPEG2 header without a frame size: {}", header)));
+    }
+    let (subsampling, cosited) = match colorspace {
+        "444" => (ChromaSubsampling::Yuv444, false),
+        "422" => (ChromaSubsampling::Yuv422, true),
+        "420jpeg" | "420" => (ChromaSubsampling::Yuv420, false),
+        "420mpeg2" | "420paldv" => (ChromaSubsampling::Yuv420, true),
+        _ => return Err(Error::Other(format!("expected 8-bit 4:2:0, 4:2:2 or 4:4:4 YUV4MPEG2 frames from ffmpeg, got C{}", colorspace))),
+    };
+    Ok((width, height, Y4mLayout {subsampling, cosited, full_range}))
+}
+
+/// The `len` bytes of planes of the next frame of a YUV4MPEG2 stream; `None` at the end of the stream
+fn read_y4m_frame(reader: &mut impl BufRead, len: usize) -> std::io::Result<Option<Vec<u8>>> {
+    let Some(marker) = read_y4m_line(reader)? else {return Ok(None)};
+    if !marker.starts_with("FRAME") {
+        return Err(std::io::Error::new(ErrorKind::InvalidData, "damaged YUV4MPEG2 frame from ffmpeg"));
+    }
+    let mut planes = vec![0u8; len];
+    reader.read_exact(&mut planes)?;
+    Ok(Some(planes))
+}
+
+/// The next PNG file of a stream of them, split at its `IEND` chunk without decoding it; `None` at
+/// the end of the stream
+fn read_png(reader: &mut impl Read) -> std::io::Result<Option<Vec<u8>>> {
+    let mut png = vec![0u8; PNG_SIGNATURE.len()];
+    match reader.read_exact(&mut png) {
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    if png != PNG_SIGNATURE {
+        return Err(std::io::Error::new(ErrorKind::InvalidData, "ffmpeg wrote something other than a PNG"));
+    }
+    loop {
+        // Length and type, then the data and its CRC
+        let start = png.len();
+        png.resize(start + 8, 0);
+        reader.read_exact(&mut png[start..])?;
+        let len = u32::from_be_bytes(png[start..start + 4].try_into().unwrap());
+        if len > i32::MAX as u32 || (start == PNG_SIGNATURE.len() && (&png[start + 4..start + 8] != b"IHDR" || len < 8)) {
+            return Err(std::io::Error::new(ErrorKind::InvalidData, "damaged PNG from ffmpeg"));
+        }
+        let iend = &png[start + 4..start + 8] == b"IEND";
+        png.resize(start + 8 + len as usize + 4, 0);
+        reader.read_exact(&mut png[start + 8..])?;
+        if iend {
+            return Ok(Some(png));
+        }
+    }
+}
+
+/// Start and length in seconds of the part of `input` that `video_opts` extracts
+pub(crate) fn extraction_span(input: &Path, video_opts: &VideoOptions, ffmpeg_config: &FfmpegConfig) -> Result<(f64, f64)> {
+    let start = video_opts.start.as_deref().map_or(0.0, parse_timestamp);
+    let end = match video_opts.end.as_deref().filter(|end| !end.is_empty()) {
+        Some(end) => parse_timestamp(end),
+        None => get_media_duration_secs(input, ffmpeg_config)?,
+    };
+    Ok((start, end - start))
 }
 
 /// Get video duration in microseconds using ffprobe
 pub(crate) fn get_video_duration_us(input: &Path, ffmpeg_config: &FfmpegConfig) -> Result<u64> {
-    let output = ProcCommand::new(ffmpeg_config.ffprobe_cmd()).args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1", input.to_str().unwrap()]).output().context("running ffprobe")?;
+    Ok((get_media_duration_secs(input, ffmpeg_config)? * 1_000_000.0) as u64)
+}
+
+/// Get the container duration of any media file (video or audio) in seconds using ffprobe
+pub(crate) fn get_media_duration_secs(input: &Path, ffmpeg_config: &FfmpegConfig) -> Result<f64> {
+    let output = ProcCommand::new(ffmpeg_config.ffprobe_cmd()).args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1", input.to_str().unwrap()]).output().map_err(|err| Error::spawn(ffmpeg_config.ffprobe_cmd(), err))?;
 
     if !output.status.success() {
-        return Err(anyhow!("ffprobe failed to get duration"));
+        return Err(Error::ffmpeg("ffprobe failed to get duration", &output.stderr));
     }
 
     let duration_str = String::from_utf8_lossy(&output.stdout);
-    let duration_secs: f64 = duration_str.trim().parse().unwrap_or(0.0);
-    Ok((duration_secs * 1_000_000.0) as u64)
+    Ok(duration_str.trim().parse().unwrap_or(0.0))
+}
+
+/// Pixel width of the first video stream of `input`, or `None` if it has no video
+pub(crate) fn probe_video_width(input: &Path, ffmpeg_config: &FfmpegConfig) -> Result<Option<u32>> {
+    let output = ProcCommand::new(ffmpeg_config.ffprobe_cmd()).args(["-v", "error", "-select_streams", "v:0", "-show_entries", "stream=width", "-of", "default=noprint_wrappers=1:nokey=1"]).arg(input).output().map_err(|err| Error::spawn(ffmpeg_config.ffprobe_cmd(), err))?;
+
+    if !output.status.success() {
+        return Err(Error::ffmpeg("ffprobe failed to read the video stream", &output.stderr));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().parse().ok())
+}
+
+/// Frame size and rate of the first video stream of `input`, and the container duration
+pub(crate) fn probe_media_info(input: &Path, ffmpeg_config: &FfmpegConfig) -> Result<MediaInfo> {
+    let output = ProcCommand::new(ffmpeg_config.ffprobe_cmd()).args(PROBE_MEDIA_ARGS).arg(input).output().map_err(|err| Error::spawn(ffmpeg_config.ffprobe_cmd(), err))?;
+
+    if !output.status.success() {
+        return Err(Error::ffmpeg("ffprobe failed to read the video stream", &output.stderr));
+    }
+
+    parse_media_info(input, &String::from_utf8_lossy(&output.stdout))
+}
+
+/// ffprobe arguments printing the size, frame rate and duration [`parse_media_info`] reads, before the input
+pub(crate) const PROBE_MEDIA_ARGS: [&str; 8] = ["-v", "error", "-select_streams", "v:0", "-show_entries", "stream=width,height,avg_frame_rate:format=duration", "-of", "default=noprint_wrappers=1"];
+
+/// The [`MediaInfo`] in the `key=value` lines ffprobe printed for [`PROBE_MEDIA_ARGS`]
+pub(crate) fn parse_media_info(input: &Path, stdout: &str) -> Result<MediaInfo> {
+    let value = |key: &str| stdout.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix('=')).map(str::trim);
+    let (Some(width), Some(height)) = (value("width").and_then(|w| w.parse().ok()), value("height").and_then(|h| h.parse().ok())) else {
+        return Err(Error::NotFound(format!("{} has no video stream", input.display())));
+    };
+    // avg_frame_rate is a fraction such as 30000/1001, or 0/0 when unknown
+    let fps = value("avg_frame_rate").and_then(|rate| {
+        let (num, den) = rate.split_once('/').unwrap_or((rate, "1"));
+        let fps = num.parse::<f64>().ok()? / den.parse::<f64>().ok()?;
+        (fps.is_finite() && fps > 0.0).then_some(fps)
+    });
+    let duration = value("duration").and_then(|d| d.parse::<f64>().ok()).filter(|d| *d > 0.0);
+    Ok(MediaInfo {width, height, fps, duration})
+}
+
+/// The version `program -version` reports, or `None` when `program` isn't installed
+pub(crate) fn probe_version(program: &OsStr) -> Result<Option<String>> {
+    let output = match ProcCommand::new(program).arg("-version").output() {
+        Ok(output) => output,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::spawn(program, err)),
+    };
+    if !output.status.success() {
+        return Err(Error::ffmpeg(format!("{} -version failed", program.to_string_lossy()), &output.stderr));
+    }
+    Ok(Some(parse_version(&String::from_utf8_lossy(&output.stdout))))
+}
+
+/// The version in the first line of `-version` output, `ffmpeg version 6.1.1-3ubuntu5 Copyright ...`
+pub(crate) fn parse_version(stdout: &str) -> String {
+    let line = stdout.lines().next().unwrap_or_default();
+    line.split_once(" version ").and_then(|(_, rest)| rest.split_whitespace().next()).unwrap_or("unknown").to_string()
+}
+
+/// The video and audio encoders `ffmpeg -encoders` lists
+pub(crate) fn probe_encoders(ffmpeg: &OsStr) -> Result<(Vec<String>, Vec<String>)> {
+    let output = ProcCommand::new(ffmpeg).args(["-hide_banner", "-encoders"]).output().map_err(|err| Error::spawn(ffmpeg, err))?;
+    if !output.status.success() {
+        return Err(Error::ffmpeg("ffmpeg failed to list its encoders", &output.stderr));
+    }
+    Ok(parse_encoders(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The encoder table after the ` ------` line: capability flags led by `V` or `A`, then the name
+pub(crate) fn parse_encoders(stdout: &str) -> (Vec<String>, Vec<String>) {
+    let (mut video, mut audio) = (Vec::new(), Vec::new());
+    for line in stdout.lines().skip_while(|line| !line.trim_start().starts_with("---")).skip(1) {
+        let mut fields = line.split_whitespace();
+        let (Some(flags), Some(name)) = (fields.next(), fields.next()) else { continue };
+        match flags.chars().next() {
+            Some('V') => video.push(name.to_string()),
+            Some('A') => audio.push(name.to_string()),
+            _ => {}
+        }
+    }
+    video.sort();
+    audio.sort();
+    (video, audio)
+}
+
+/// Whether the first video stream of `input` uses an HDR transfer function (PQ or HLG)
+pub(crate) fn probe_is_hdr(input: &Path, ffmpeg_config: &FfmpegConfig) -> Result<bool> {
+    let output = ProcCommand::new(ffmpeg_config.ffprobe_cmd()).args(["-v", "error", "-select_streams", "v:0", "-show_entries", "stream=color_transfer", "-of", "default=noprint_wrappers=1:nokey=1"]).arg(input).output().map_err(|err| Error::spawn(ffmpeg_config.ffprobe_cmd(), err))?;
+
+    if !output.status.success() {
+        return Err(Error::ffmpeg("ffprobe failed to read the video stream", &output.stderr));
+    }
+
+    Ok(matches!(String::from_utf8_lossy(&output.stdout).trim(), "smpte2084" | "arib-std-b67"))
+}
+
+/// Matrix of the YUV ffmpeg decodes `input` to, from the `color_space` its video stream is tagged with
+fn probe_yuv_matrix(input: &Path, ffmpeg_config: &FfmpegConfig) -> Result<Option<YuvMatrix>> {
+    let output = ProcCommand::new(ffmpeg_config.ffprobe_cmd()).args(["-v", "error", "-select_streams", "v:0", "-show_entries", "stream=color_space", "-of", "default=noprint_wrappers=1:nokey=1"]).arg(input).output().map_err(|err| Error::spawn(ffmpeg_config.ffprobe_cmd(), err))?;
+
+    if !output.status.success() {
+        return Err(Error::ffmpeg("ffprobe failed to read the video stream", &output.stderr));
+    }
+
+    Ok(YuvMatrix::from_color_space(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// Tone mapping to apply while extracting `input`: the configured one if the source is HDR.
+fn hdr_tone_map(input: &Path, video_opts: &VideoOptions, ffmpeg_config: &FfmpegConfig) -> Option<ToneMap> {
+    video_opts.tone_map.filter(|_| probe_is_hdr(input, ffmpeg_config).unwrap_or(false))
+}
+
+/// Codec name of the first audio stream of `input` (e.g. `aac`, `opus`), or `None` if it has no audio
+pub(crate) fn probe_audio_codec(input: &Path, ffmpeg_config: &FfmpegConfig) -> Result<Option<String>> {
+    let output = ProcCommand::new(ffmpeg_config.ffprobe_cmd()).args(["-v", "error", "-select_streams", "a:0", "-show_entries", "stream=codec_name", "-of", "default=noprint_wrappers=1:nokey=1"]).arg(input).output().map_err(|err| Error::spawn(ffmpeg_config.ffprobe_cmd(), err))?;
+
+    if !output.status.success() {
+        return Err(Error::ffmpeg("ffprobe failed to read the audio stream", &output.stderr));
+    }
+
+    let codec = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(if codec.is_empty() {None} else {Some(codec)})
 }
 
 /// Extract video frames with progress reporting
-pub(crate) fn extract_video_frames_with_progress<F: Fn(Progress) + Send + Sync>(input: &Path, out_dir: &Path, video_opts: &VideoOptions, ffmpeg_config: &FfmpegConfig, progress_callback: &F, cancel: Option<&CancelToken>) -> Result<()> {
-    let columns = video_opts.columns;
-    let fps = video_opts.fps;
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn extract_video_frames_with_progress<F: Fn(Progress) + Send + Sync>(input: &Path, out_dir: &Path, video_opts: &VideoOptions, sampling: Sampling, ffmpeg_config: &FfmpegConfig, progress_callback: &F, cancel: Option<&CancelToken>, events: Option<&EventLog>) -> Result<()> {
     let start = video_opts.start.as_deref();
     let end = video_opts.end.as_deref();
 
@@ -100,84 +581,26 @@ pub(crate) fn extract_video_frames_with_progress<F: Fn(Progress) + Send + Sync>(
 
     let mut ffmpeg_args: Vec<String> = vec!["-loglevel".into(), "error".into(), "-progress".into(), "pipe:1".into(), "-nostats".into()];
 
-    if let Some(s) = start {
-        if !s.is_empty() && s != "0" {
-            ffmpeg_args.push("-ss".into());
-            ffmpeg_args.push(s.to_string());
-        }
-    }
-
-    ffmpeg_args.push("-i".into());
-    ffmpeg_args.push(input.to_str().ok_or_else(|| anyhow!("input path is not valid UTF-8"))?.to_string());
-
-    if let Some(e) = end {
-        if !e.is_empty() {
-            if let Some(s) = start {
-                if !s.is_empty() && s != "0" {
-                    let start_secs = parse_timestamp(s);
-                    let end_secs = parse_timestamp(e);
-                    let duration = end_secs - start_secs;
-                    if duration > 0.0 {
-                        ffmpeg_args.push("-t".into());
-                        ffmpeg_args.push(duration.to_string());
-                    }
-                } else {
-                    ffmpeg_args.push("-t".into());
-                    ffmpeg_args.push(e.to_string());
-                }
-            } else {
-                ffmpeg_args.push("-t".into());
-                ffmpeg_args.push(e.to_string());
-            }
-        }
-    }
+    ffmpeg_args.extend(input_args(input, start, end)?);
 
-    let vf_option = build_frame_extraction_vf(columns, fps, video_opts.preprocess_filter.as_deref());
+    let vf_option = build_frame_extraction_vf(video_opts, sampling, hdr_tone_map(input, video_opts, ffmpeg_config));
     ffmpeg_args.push("-vf".into());
     ffmpeg_args.push(vf_option);
-    ffmpeg_args.push(out_pattern.to_str().ok_or_else(|| anyhow!("output path is not valid UTF-8"))?.to_string());
+    ffmpeg_args.push(out_pattern.to_str().ok_or_else(|| Error::Other("output path is not valid UTF-8".into()))?.to_string());
     progress_callback(Progress::extracting_frames());
 
     let mut command = ProcCommand::new(ffmpeg_config.ffmpeg_cmd());
     command.args(&ffmpeg_args).stdout(Stdio::piped()).stderr(Stdio::null());
-    run_ffmpeg_cancellable(command, cancel, "ffmpeg")
+    or_native_extraction(run_ffmpeg_cancellable(command, cancel, events, "ffmpeg"), input, out_dir, video_opts, sampling, cancel)?;
+    progress_callback(Progress::extracting_frames_done());
+    Ok(())
 }
 
-pub(crate) fn extract_audio(input: &Path, out_dir: &Path, start: Option<&str>, end: Option<&str>, ffmpeg_config: &FfmpegConfig, cancel: Option<&CancelToken>) -> Result<()> {
+pub(crate) fn extract_audio(input: &Path, out_dir: &Path, start: Option<&str>, end: Option<&str>, ffmpeg_config: &FfmpegConfig, cancel: Option<&CancelToken>, events: Option<&EventLog>) -> Result<()> {
     let out_audio = out_dir.join("audio.mp3");
     let mut ffmpeg_args: Vec<String> = vec!["-loglevel".into(), "error".into(), "-y".into()];
 
-    if let Some(s) = start {
-        if !s.is_empty() && s != "0" {
-            ffmpeg_args.push("-ss".into());
-            ffmpeg_args.push(s.to_string());
-        }
-    }
-
-    ffmpeg_args.push("-i".into());
-    ffmpeg_args.push(input.to_str().unwrap().to_string());
-
-    if let Some(e) = end {
-        if !e.is_empty() {
-            if let Some(s) = start {
-                if !s.is_empty() && s != "0" {
-                    let start_secs = parse_timestamp(s);
-                    let end_secs = parse_timestamp(e);
-                    let duration = end_secs - start_secs;
-                    if duration > 0.0 {
-                        ffmpeg_args.push("-t".into());
-                        ffmpeg_args.push(duration.to_string());
-                    }
-                } else {
-                    ffmpeg_args.push("-t".into());
-                    ffmpeg_args.push(e.to_string());
-                }
-            } else {
-                ffmpeg_args.push("-t".into());
-                ffmpeg_args.push(e.to_string());
-            }
-        }
-    }
+    ffmpeg_args.extend(input_args(input, start, end)?);
 
     // Extract audio only, no video
     ffmpeg_args.push("-vn".into());
@@ -189,10 +612,38 @@ pub(crate) fn extract_audio(input: &Path, out_dir: &Path, start: Option<&str>, e
 
     let mut command = ProcCommand::new(ffmpeg_config.ffmpeg_cmd());
     command.args(&ffmpeg_args);
-    run_ffmpeg_cancellable(command, cancel, "ffmpeg audio extraction")?;
+    run_ffmpeg_cancellable(command, cancel, events, "ffmpeg audio extraction")?;
     Ok(())
 }
 
+/// Extract the frame at each of `times` (in seconds) to `out_dir`, filtered and scaled like a full extraction.
+pub(crate) fn extract_sample_frames(input: &Path, out_dir: &Path, video_opts: &VideoOptions, sampling: Sampling, times: &[f64], ffmpeg_config: &FfmpegConfig, cancel: Option<&CancelToken>) -> Result<Vec<std::path::PathBuf>> {
+    let vf_option = build_frame_extraction_vf(video_opts, sampling, hdr_tone_map(input, video_opts, ffmpeg_config));
+    let mut samples = Vec::with_capacity(times.len());
+    for (index, time) in times.iter().enumerate() {
+        let output = out_dir.join(format!("sample_{:04}.png", index + 1));
+        let mut command = ProcCommand::new(ffmpeg_config.ffmpeg_cmd());
+        command.arg("-loglevel").arg("error").arg("-y").arg("-ss").arg(time.to_string()).arg("-i").arg(input).arg("-vf").arg(&vf_option).arg("-frames:v").arg("1").arg(&output).stdout(Stdio::null());
+        run_ffmpeg_cancellable(command, cancel, None, "ffmpeg frame sampling")?;
+        if output.is_file() {
+            samples.push(output);
+        }
+    }
+    Ok(samples)
+}
+
+/// Extract a single full-resolution frame at `at` (or the first frame) to `output` as PNG.
+#[cfg(feature = "tui")]
+pub(crate) fn extract_single_frame(input: &Path, output: &Path, at: Option<&str>, ffmpeg_config: &FfmpegConfig) -> Result<()> {
+    let mut command = ProcCommand::new(ffmpeg_config.ffmpeg_cmd());
+    command.arg("-loglevel").arg("error").arg("-y");
+    if let Some(at) = at.filter(|s| !s.is_empty() && *s != "0") {
+        command.arg("-ss").arg(at);
+    }
+    command.arg("-i").arg(input).arg("-frames:v").arg("1").arg(output).stdout(Stdio::null());
+    run_ffmpeg_cancellable(command, None, None, "ffmpeg frame sampling")
+}
+
 pub(crate) fn parse_timestamp(s: &str) -> f64 {
     s.split(':').rev().enumerate().fold(0.0, |acc, (i, v)| acc + v.parse::<f64>().unwrap_or(0.0) * 60f64.powi(i as i32))
 }
@@ -0,0 +1,303 @@
+//! Full-screen parameter wizard with a live ASCII preview (`cascii --tui`, feature `tui`).
+//!
+//! The wizard converts one sample frame in memory every time a parameter changes, so users can see the
+//! effect of columns, font ratio, luminance, charset and preprocessing before committing to a full run.
+
+use crate::error::{Context, Error, Result};
+use image::RgbImage;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::frame::Previewer;
+use crate::preprocessing::{find_preprocess_preset, preprocess_image_to_temp, TempFileGuard, PREPROCESS_PRESETS};
+use crate::{video, ConversionOptions, FfmpegConfig};
+
+/// Built-in character ramps offered next to the configured one, darkest to lightest.
+pub const WIZARD_CHARSETS: &[(&str, &str)] = &[("classic", " .'`^,:;Il!i><~+_-?][}{1)(|/tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$"), ("short", " .:-=+*#%@"), ("dots", " .:oO0@"), ("binary", " #")];
+
+/// Sample images wider than this are downscaled once up front; the preview never needs more detail.
+const SAMPLE_MAX_WIDTH: u32 = 640;
+
+/// Parameters chosen in the wizard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WizardSettings {
+    pub columns: u32,
+    pub font_ratio: f32,
+    pub luminance: u8,
+    pub ascii_chars: String,
+    /// Name of a built-in preprocessing preset, `None` for no preprocessing
+    pub preprocess_preset: Option<String>,
+}
+
+/// The frame the wizard previews, backed by a file so ffmpeg preprocessing can run on it.
+pub struct SampleFrame {
+    path: PathBuf,
+    _temp: Option<TempFileGuard>,
+}
+
+impl SampleFrame {
+    /// Pick a sample for `input`: the image itself, the first image in a directory, or the video frame at `at`.
+    pub fn for_input(input: &Path, at: Option<&str>, ffmpeg_config: &FfmpegConfig) -> Result<Self> {
+        if input.is_dir() {
+            let mut images: Vec<PathBuf> = fs::read_dir(input).with_context(|| format!("reading directory {}", input.display()))?.flatten().map(|entry| entry.path()).filter(|path| is_image(path)).collect();
+            images.sort();
+            let first = images.into_iter().next().ok_or_else(|| Error::NotFound(format!("No images found in {} to preview", input.display())))?;
+            return Ok(Self {path: first, _temp: None});
+        }
+        if is_image(input) {
+            return Ok(Self {path: input.to_path_buf(), _temp: None});
+        }
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let path = std::env::temp_dir().join(format!("cascii_wizard_{}_{}.png", std::process::id(), stamp));
+        let guard = TempFileGuard::new(path.clone());
+        video::extract_single_frame(input, &path, at, ffmpeg_config).with_context(|| format!("sampling a preview frame from {}", input.display()))?;
+        Ok(Self {path, _temp: Some(guard)})
+    }
+
+    /// Path of the sample image on disk
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    path.is_file() && path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| matches!(ext.to_ascii_lowercase().as_str(), "png" | "jpg" | "jpeg"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Columns,
+    FontRatio,
+    Luminance,
+    Charset,
+    Preprocess,
+}
+
+const FIELDS: [Field; 5] = [Field::Columns, Field::FontRatio, Field::Luminance, Field::Charset, Field::Preprocess];
+
+struct WizardState {
+    columns: u32,
+    font_ratio: f32,
+    luminance: u8,
+    /// `(name, ramp)`; the first entry is the ramp the wizard was started with
+    charsets: Vec<(String, String)>,
+    charset_index: usize,
+    /// 0 = no preprocessing, `i + 1` = `PREPROCESS_PRESETS[i]`
+    preprocess_index: usize,
+    selected: usize,
+    colors: bool,
+}
+
+impl WizardState {
+    fn new(initial: &WizardSettings) -> Self {
+        let mut charsets = vec![("configured".to_string(), initial.ascii_chars.clone())];
+        charsets.extend(WIZARD_CHARSETS.iter().filter(|(_, ramp)| *ramp != initial.ascii_chars).map(|(name, ramp)| (name.to_string(), ramp.to_string())));
+        let preprocess_index = initial.preprocess_preset.as_deref().and_then(find_preprocess_preset).and_then(|preset| PREPROCESS_PRESETS.iter().position(|p| p.name == preset.name)).map_or(0, |i| i + 1);
+        Self {columns: initial.columns, font_ratio: initial.font_ratio, luminance: initial.luminance, charsets, charset_index: 0, preprocess_index, selected: 0, colors: true}
+    }
+
+    fn settings(&self) -> WizardSettings {
+        WizardSettings {columns: self.columns, font_ratio: self.font_ratio, luminance: self.luminance, ascii_chars: self.charsets[self.charset_index].1.clone(), preprocess_preset: self.preprocess_name().map(str::to_string)}
+    }
+
+    fn preprocess_name(&self) -> Option<&'static str> {
+        self.preprocess_index.checked_sub(1).map(|i| PREPROCESS_PRESETS[i].name)
+    }
+
+    /// Move the selected parameter `steps` notches; `coarse` uses larger increments.
+    fn adjust(&mut self, steps: i32, coarse: bool) {
+        match FIELDS[self.selected] {
+            Field::Columns => {
+                let step = if coarse {100} else {10};
+                self.columns = (self.columns as i64 + (steps * step) as i64).clamp(10, 2000) as u32;
+            }
+            Field::FontRatio => {
+                let step = if coarse {0.1} else {0.01};
+                self.font_ratio = ((self.font_ratio + steps as f32 * step).clamp(0.1, 2.0) * 100.0).round() / 100.0;
+            }
+            Field::Luminance => {
+                let step = if coarse {10} else {1};
+                self.luminance = (self.luminance as i32 + steps * step).clamp(0, 255) as u8;
+            }
+            Field::Charset => self.charset_index = cycle(self.charset_index, steps, self.charsets.len()),
+            Field::Preprocess => self.preprocess_index = cycle(self.preprocess_index, steps, PREPROCESS_PRESETS.len() + 1),
+        }
+    }
+
+    fn field_lines(&self) -> Vec<(String, String)> {
+        FIELDS.iter().map(|field| match field {
+            Field::Columns => ("Columns".to_string(), self.columns.to_string()),
+            Field::FontRatio => ("Font ratio".to_string(), format!("{:.2}", self.font_ratio)),
+            Field::Luminance => ("Luminance".to_string(), self.luminance.to_string()),
+            Field::Charset => ("Charset".to_string(), self.charsets[self.charset_index].0.clone()),
+            Field::Preprocess => ("Preprocess".to_string(), self.preprocess_name().unwrap_or("none").to_string()),
+        }).collect()
+    }
+}
+
+fn cycle(index: usize, steps: i32, len: usize) -> usize {
+    (index as i64 + steps as i64).rem_euclid(len as i64) as usize
+}
+
+/// Convert the sample for the preview pane: at most `max_columns` wide and `max_rows` tall.
+fn preview_lines(previewer: &mut Previewer, state: &WizardState, max_columns: u16, max_rows: u16) -> Vec<Line<'static>> {
+    let columns = state.columns.min(max_columns as u32).max(1);
+    let options = ConversionOptions {columns: Some(columns), font_ratio: state.font_ratio, luminance: state.luminance, ascii_chars: state.charsets[state.charset_index].1.clone(), ..ConversionOptions::default()};
+    let Ok(frame) = previewer.preview(&options) else {return Vec::new()};
+    let (width, rgb) = (frame.width, &frame.rgb);
+    frame.text.lines().take(max_rows as usize).enumerate().map(|(row, line)| {
+        if !state.colors {
+            return Line::raw(line.to_string());
+        }
+        let spans: Vec<Span<'static>> = line.chars().enumerate().map(|(col, ch)| {
+            let offset = (row * width as usize + col) * 3;
+            Span::styled(ch.to_string(), Style::default().fg(Color::Rgb(rgb[offset], rgb[offset + 1], rgb[offset + 2])))
+        }).collect();
+        Line::from(spans)
+    }).collect()
+}
+
+/// Run the wizard on `sample`, starting from `initial`.
+///
+/// Returns the chosen settings when the user confirms with Enter, or `None` when they quit with Esc/q/Ctrl-C.
+pub fn run_wizard(sample: &SampleFrame, initial: &WizardSettings, ffmpeg_config: &FfmpegConfig) -> Result<Option<WizardSettings>> {
+    let original = image::open(sample.path()).with_context(|| format!("opening sample frame {}", sample.path().display()))?.to_rgb8();
+    let mut sources: HashMap<usize, Previewer> = HashMap::new();
+    sources.insert(0, downscale_sample(original));
+    let mut state = WizardState::new(initial);
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut state, sample, &mut sources, ffmpeg_config);
+    ratatui::restore();
+    result
+}
+
+fn downscale_sample(image: RgbImage) -> Previewer {
+    let (w, h) = image.dimensions();
+    let image = if w <= SAMPLE_MAX_WIDTH {
+        image
+    } else {
+        let new_h = ((h as f32 * SAMPLE_MAX_WIDTH as f32 / w as f32).round() as u32).max(1);
+        image::imageops::resize(&image, SAMPLE_MAX_WIDTH, new_h, image::imageops::FilterType::Triangle)
+    };
+    Previewer::new(&image::DynamicImage::ImageRgb8(image))
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, state: &mut WizardState, sample: &SampleFrame, sources: &mut HashMap<usize, Previewer>, ffmpeg_config: &FfmpegConfig) -> Result<Option<WizardSettings>> {
+    let mut status: Option<String> = None;
+    loop {
+        if let std::collections::hash_map::Entry::Vacant(entry) = sources.entry(state.preprocess_index) {
+            let filter = PREPROCESS_PRESETS[state.preprocess_index - 1].filter;
+            match preprocess_image_to_temp(sample.path(), filter, ffmpeg_config).and_then(|temp| Ok(image::open(temp.path())?.to_rgb8())) {
+                Ok(image) => {
+                    entry.insert(downscale_sample(image));
+                    status = None;
+                }
+                Err(err) => {
+                    status = Some(format!("preprocessing unavailable: {}", err));
+                    state.preprocess_index = 0;
+                }
+            }
+        }
+        let source = sources.get_mut(&state.preprocess_index).expect("preview source was just inserted");
+        terminal.draw(|frame| draw(frame, state, source, status.as_deref())).context("drawing wizard")?;
+
+        let Event::Key(key) = event::read().context("reading terminal input")? else {continue};
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let coarse = key.modifiers.contains(KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+            KeyCode::Enter => return Ok(Some(state.settings())),
+            KeyCode::Up | KeyCode::Char('k') => state.selected = cycle(state.selected, -1, FIELDS.len()),
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Tab => state.selected = cycle(state.selected, 1, FIELDS.len()),
+            KeyCode::Left | KeyCode::Char('h') => state.adjust(-1, coarse),
+            KeyCode::Right | KeyCode::Char('l') => state.adjust(1, coarse),
+            KeyCode::PageDown => state.adjust(-1, true),
+            KeyCode::PageUp => state.adjust(1, true),
+            KeyCode::Char('c') => state.colors = !state.colors,
+            _ => {}
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, state: &WizardState, source: &mut Previewer, status: Option<&str>) {
+    let columns = Layout::default().direction(Direction::Horizontal).constraints([Constraint::Length(34), Constraint::Min(10)]).split(frame.area());
+    let sidebar = Layout::default().direction(Direction::Vertical).constraints([Constraint::Length(FIELDS.len() as u16 + 2), Constraint::Min(3)]).split(columns[0]);
+
+    let items: Vec<ListItem> = state.field_lines().into_iter().map(|(name, value)| ListItem::new(format!("{:<11} {}", name, value))).collect();
+    let mut list_state = ListState::default().with_selected(Some(state.selected));
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(" Parameters ")).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, sidebar[0], &mut list_state);
+
+    let mut help = vec![Line::raw("↑/↓  select"), Line::raw("←/→  adjust (shift: coarse)"), Line::raw("c    toggle colors"), Line::raw("Enter convert"), Line::raw("Esc  quit")];
+    if let Some(status) = status {
+        help.push(Line::raw(""));
+        help.push(Line::styled(status.to_string(), Style::default().fg(Color::Yellow)));
+    }
+    frame.render_widget(Paragraph::new(help).block(Block::default().borders(Borders::ALL).title(" Keys ")), sidebar[1]);
+
+    let preview_area = columns[1];
+    let inner_w = preview_area.width.saturating_sub(2);
+    let inner_h = preview_area.height.saturating_sub(2);
+    let shown = state.columns.min(inner_w as u32);
+    let title = if shown < state.columns {format!(" Preview ({} of {} columns) ", shown, state.columns)} else {format!(" Preview ({} columns) ", state.columns)};
+    let lines = preview_lines(source, state, inner_w, inner_h);
+    frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)), preview_area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn initial() -> WizardSettings {
+        WizardSettings {columns: 80, font_ratio: 0.5, luminance: 20, ascii_chars: " .:#".to_string(), preprocess_preset: Some("vivid".to_string())}
+    }
+
+    #[test]
+    fn state_round_trips_initial_settings() {
+        let state = WizardState::new(&initial());
+        assert_eq!(state.settings(), initial());
+        assert_eq!(state.charsets.len(), WIZARD_CHARSETS.len() + 1);
+    }
+
+    #[test]
+    fn adjust_clamps_and_cycles() {
+        let mut state = WizardState::new(&initial());
+        state.adjust(-100, true);
+        assert_eq!(state.columns, 10);
+        state.selected = 1;
+        state.adjust(3, false);
+        assert!((state.font_ratio - 0.53).abs() < 1e-6);
+        state.selected = 2;
+        state.adjust(-3, true);
+        assert_eq!(state.luminance, 0);
+        state.selected = 3;
+        state.adjust(-1, false);
+        assert_eq!(state.charset_index, state.charsets.len() - 1);
+        state.selected = 4;
+        state.preprocess_index = PREPROCESS_PRESETS.len();
+        state.adjust(1, false);
+        assert_eq!(state.settings().preprocess_preset, None);
+    }
+
+    #[test]
+    fn preview_fits_the_pane() {
+        let mut previewer = downscale_sample(RgbImage::from_pixel(64, 64, image::Rgb([200, 200, 200])));
+        let mut state = WizardState::new(&initial());
+        state.columns = 400;
+        let lines = preview_lines(&mut previewer, &state, 30, 5);
+        assert_eq!(lines.len(), 5);
+        assert!(lines.iter().all(|line| line.width() == 30));
+    }
+}
@@ -0,0 +1,275 @@
+//! Frames in YUV, the form video decodes to, converted without first being turned into RGB.
+//!
+//! A [`YuvFrame`] holds the Y, U and V planes of a frame as a decoder or a YUV4MPEG2 stream lays
+//! them out, its chroma at full resolution or subsampled (4:2:2, 4:2:0). [`yuv_to_frame`](crate::yuv_to_frame)
+//! converts one to ASCII reading the brightness of every cell straight from the Y plane when the
+//! luminance model measures the luma of the frame's matrix (BT.601 or BT.709), and turns YUV into RGB
+//! only for the cell colors, interpolating subsampled chroma at the position it was sampled at.
+//!
+//! ```
+//! use cascii::yuv::{ChromaSubsampling, YuvFrame, YuvMatrix};
+//! use cascii::ConversionOptions;
+//!
+//! # fn main() -> cascii::Result<()> {
+//! // A 4×2 4:2:0 frame: 8 luma samples, then 2 U and 2 V samples
+//! let planes = [vec![235; 8], vec![128; 2], vec![128; 2]].concat();
+//! let frame = YuvFrame::new(4, 2, ChromaSubsampling::Yuv420, planes)?.with_matrix(YuvMatrix::Bt709);
+//! assert_eq!(frame.to_rgb().get_pixel(0, 0).0, [255, 255, 255]);
+//! let ascii = cascii::yuv_to_frame(&frame, &ConversionOptions::default().with_columns(4))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use image::{GrayImage, RgbImage};
+
+use crate::cell_filter::{Luminance, LuminanceWeights};
+use crate::error::{Error, Result};
+
+/// The matrix a frame's YUV was derived from RGB with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum YuvMatrix {
+    /// Rec.601, standard definition video and what ffmpeg converts RGB sources with
+    #[default]
+    Bt601,
+    /// Rec.709, high definition video
+    Bt709,
+}
+
+impl YuvMatrix {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Bt601 => "bt601",
+            Self::Bt709 => "bt709",
+        }
+    }
+
+    /// The matrix ffprobe names `color_space` (e.g. `bt709`, `smpte170m`), if it is one of these
+    pub fn from_color_space(color_space: &str) -> Option<Self> {
+        match color_space {
+            "bt709" => Some(Self::Bt709),
+            "bt470bg" | "smpte170m" | "fcc" => Some(Self::Bt601),
+            _ => None,
+        }
+    }
+
+    /// The luminance weights whose luma is this matrix's Y
+    pub fn luminance_weights(self) -> LuminanceWeights {
+        match self {
+            Self::Bt601 => LuminanceWeights::REC601,
+            Self::Bt709 => LuminanceWeights::REC709,
+        }
+    }
+
+    /// Multipliers of the luma and of the chroma terms (red from V, green from U, green from V, blue
+    /// from U) turning YUV into RGB, in 8.8 fixed point
+    fn coefficients(self, full_range: bool) -> [i32; 5] {
+        match (self, full_range) {
+            (Self::Bt601, false) => [298, 409, 100, 208, 516],
+            (Self::Bt601, true) => [256, 359, 88, 183, 454],
+            (Self::Bt709, false) => [298, 459, 55, 136, 541],
+            (Self::Bt709, true) => [256, 403, 48, 120, 475],
+        }
+    }
+}
+
+/// How much coarser than the luma the chroma planes of a [`YuvFrame`] are sampled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    /// Chroma at half the width and half the height, as almost all video is encoded
+    #[default]
+    Yuv420,
+    /// Chroma at half the width
+    Yuv422,
+    /// Chroma at full resolution
+    Yuv444,
+}
+
+impl ChromaSubsampling {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Yuv420 => "4:2:0",
+            Self::Yuv422 => "4:2:2",
+            Self::Yuv444 => "4:4:4",
+        }
+    }
+
+    /// Size of each chroma plane of a `width`×`height` frame, odd sizes rounding up
+    pub fn chroma_size(self, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Self::Yuv420 => (width.div_ceil(2), height.div_ceil(2)),
+            Self::Yuv422 => (width.div_ceil(2), height),
+            Self::Yuv444 => (width, height),
+        }
+    }
+}
+
+/// A frame of 8-bit YUV: the Y plane, then the U plane, then the V plane, each row-major without
+/// padding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct YuvFrame {
+    width: u32,
+    height: u32,
+    subsampling: ChromaSubsampling,
+    planes: Vec<u8>,
+    matrix: YuvMatrix,
+    full_range: bool,
+    chroma_cosited: bool,
+}
+
+impl YuvFrame {
+    /// A `width`×`height` frame of limited-range (16–235) BT.601 YUV with chroma sited between the
+    /// luma samples it covers; `planes` must hold exactly the three planes.
+    pub fn new(width: u32, height: u32, subsampling: ChromaSubsampling, planes: Vec<u8>) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidOptions(format!("a YUV frame can't be {}x{}", width, height)));
+        }
+        let (chroma_w, chroma_h) = subsampling.chroma_size(width, height);
+        let expected = width as usize * height as usize + 2 * chroma_w as usize * chroma_h as usize;
+        if planes.len() != expected {
+            return Err(Error::InvalidOptions(format!("the planes of a {}x{} {} frame are {} bytes, got {}", width, height, subsampling.as_str(), expected, planes.len())));
+        }
+        Ok(Self {width, height, subsampling, planes, matrix: YuvMatrix::default(), full_range: false, chroma_cosited: false})
+    }
+
+    /// The frame with the YUV of `matrix`
+    pub fn with_matrix(mut self, matrix: YuvMatrix) -> Self {
+        self.matrix = matrix;
+        self
+    }
+
+    /// The frame with full-range (0–255, as in JPEG) samples instead of limited-range ones
+    pub fn with_full_range(mut self, full_range: bool) -> Self {
+        self.full_range = full_range;
+        self
+    }
+
+    /// The frame with each chroma sample sited on the left luma column of those it covers (MPEG-2
+    /// and H.264) instead of between them (JPEG)
+    pub fn with_cosited_chroma(mut self, cosited: bool) -> Self {
+        self.chroma_cosited = cosited;
+        self
+    }
+
+    /// Width in pixels, the size of the Y plane's rows
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height in pixels, the number of rows of the Y plane
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// How the U and V planes are subsampled relative to the Y plane
+    pub fn subsampling(&self) -> ChromaSubsampling {
+        self.subsampling
+    }
+
+    /// The matrix the samples are converted to RGB with (BT.601 unless set with [`with_matrix`](Self::with_matrix))
+    pub fn matrix(&self) -> YuvMatrix {
+        self.matrix
+    }
+
+    /// The Y plane, as stored
+    pub fn y_plane(&self) -> &[u8] {
+        &self.planes[..self.width as usize * self.height as usize]
+    }
+
+    /// The luma of every pixel stretched to `0..=255`, the brightness the frame's matrix encodes
+    pub fn luma(&self) -> GrayImage {
+        let luma = if self.full_range {self.y_plane().to_vec()} else {self.y_plane().iter().map(|&y| clamp_fixed(298 * (y as i32 - 16) + 128)).collect()};
+        GrayImage::from_raw(self.width, self.height, luma).expect("the Y plane holds a byte per pixel")
+    }
+
+    /// [`luma`](Self::luma), when it is the brightness `luminance` measures
+    pub(crate) fn luma_for(&self, luminance: Luminance) -> Option<GrayImage> {
+        luminance.is_luma_of(self.matrix.luminance_weights()).then(|| self.luma())
+    }
+
+    /// The frame in RGB, subsampled chroma interpolated bilinearly from where it was sampled
+    pub fn to_rgb(&self) -> RgbImage {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (chroma_w, chroma_h) = self.subsampling.chroma_size(self.width, self.height);
+        let (chroma_w, chroma_h) = (chroma_w as usize, chroma_h as usize);
+        let (y, chroma) = self.planes.split_at(width * height);
+        let (u, v) = chroma.split_at(chroma_w * chroma_h);
+        let (halve_w, halve_h) = (self.subsampling != ChromaSubsampling::Yuv444, self.subsampling == ChromaSubsampling::Yuv420);
+        let columns: Vec<_> = (0..width).map(|x| chroma_taps(x, halve_w, self.chroma_cosited, chroma_w)).collect();
+        let [luma, rv, gu, gv, bu] = self.matrix.coefficients(self.full_range);
+        let black = if self.full_range {0} else {16};
+
+        let mut rgb = Vec::with_capacity(width * height * 3);
+        for row in 0..height {
+            // Vertically subsampled chroma sits between the rows it covers in every siting in use
+            let (top, bottom, down) = chroma_taps(row, halve_h, false, chroma_h);
+            for (x, &(left, right, across)) in columns.iter().enumerate() {
+                let sample = |plane: &[u8]| {
+                    let at = |row: usize, column: usize| plane[row * chroma_w + column] as i32;
+                    let top = (4 - across) * at(top, left) + across * at(top, right);
+                    let bottom = (4 - across) * at(bottom, left) + across * at(bottom, right);
+                    ((4 - down) * top + down * bottom + 8) / 16 - 128
+                };
+                let (d, e) = (sample(u), sample(v));
+                let c = luma * (y[row * width + x] as i32 - black) + 128;
+                rgb.extend([clamp_fixed(c + rv * e), clamp_fixed(c - gu * d - gv * e), clamp_fixed(c + bu * d)]);
+            }
+        }
+        RgbImage::from_raw(self.width, self.height, rgb).expect("three channels per pixel")
+    }
+}
+
+/// The two chroma samples around luma sample `index` of a row or column and the weight of the second
+/// in quarters. Halved chroma sits between the pair of luma samples it covers, or on the first of them
+/// when `cosited`.
+fn chroma_taps(index: usize, halved: bool, cosited: bool, len: usize) -> (usize, usize, i32) {
+    // The chroma position of `index`, in quarters of a chroma sample
+    let quarters = match (halved, cosited) {
+        (false, _) => 4 * index as i64,
+        (true, true) => 2 * index as i64,
+        (true, false) => 2 * index as i64 - 1,
+    };
+    let (first, weight) = (quarters.div_euclid(4), quarters.rem_euclid(4) as i32);
+    let clamp = |sample: i64| sample.clamp(0, len as i64 - 1) as usize;
+    (clamp(first), clamp(first + 1), weight)
+}
+
+/// An 8.8 fixed-point value rounded down to a byte
+fn clamp_fixed(value: i32) -> u8 {
+    (value >> 8).clamp(0, 255) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConversionOptions, LuminanceModel};
+
+    #[test]
+    fn yuv_frames_follow_their_matrix_and_chroma_siting() -> Result<()> {
+        let pixel = |frame: &YuvFrame, x: u32, y: u32| frame.to_rgb().get_pixel(x, y).0;
+        let single = |y: u8, u: u8, v: u8| YuvFrame::new(1, 1, ChromaSubsampling::Yuv444, vec![y, u, v]).map(|frame| pixel(&frame, 0, 0));
+        // One chroma sample per 2×2 block, bluish on the left and reddish on the right
+        let planes = [vec![128; 8], vec![240, 90], vec![110, 240]].concat();
+        let centered = YuvFrame::new(4, 2, ChromaSubsampling::Yuv420, planes)?;
+        let cosited = centered.clone().with_cosited_chroma(true);
+        assert_eq!((pixel(&centered, 0, 1), pixel(&cosited, 0, 0)), (single(128, 240, 110)?, single(128, 240, 110)?));
+        // Centered chroma is a quarter of the way to the next sample, cosited chroma halfway
+        assert_eq!(pixel(&centered, 1, 0), single(128, 203, 143)?);
+        assert_eq!(pixel(&cosited, 1, 1), single(128, 165, 175)?);
+        assert!(YuvFrame::new(3, 3, ChromaSubsampling::Yuv420, vec![0; 16]).is_err());
+
+        // The same YUV is a different color under each matrix
+        assert_eq!(single(81, 90, 240)?, [255, 0, 0]);
+        let bt709 = |y: u8, u: u8, v: u8| YuvFrame::new(1, 1, ChromaSubsampling::Yuv444, vec![y, u, v]).map(|frame| pixel(&frame.with_matrix(YuvMatrix::Bt709), 0, 0));
+        assert_ne!(bt709(81, 90, 240)?, [255, 0, 0]);
+        assert!(bt709(63, 102, 240)?.iter().zip([255u8, 0, 0]).all(|(channel, red)| channel.abs_diff(red) <= 1));
+
+        // Out-of-gamut colors clip in RGB, so their brightness is read from the Y plane instead
+        let bright = YuvFrame::new(2, 1, ChromaSubsampling::Yuv444, vec![235, 235, 16, 16, 16, 16])?.with_matrix(YuvMatrix::Bt709);
+        assert_eq!(bright.luma().as_raw(), &[255, 255]);
+        let options = ConversionOptions::default().with_columns(2).with_font_ratio(1.0).with_ascii_chars("0123456789".into()).with_luminance(0);
+        assert_eq!(crate::yuv_to_frame(&bright, &options)?.text, "99\n");
+        assert_ne!(crate::image_to_frame(&image::DynamicImage::ImageRgb8(bright.to_rgb()), &options)?.text, "99\n");
+        assert_eq!(bright.luma_for(Luminance::new(LuminanceModel::Rec601, LuminanceWeights::REC709)), None);
+        Ok(())
+    }
+}